@@ -0,0 +1,182 @@
+//! Listen for the events of a Substrate chain, or go back over those of past blocks, from the
+//! command line:
+//!
+//! ```text
+//! event-listener-cli --url wss://rpc.polkadot.io:443 --filter Balances.Transfer listen
+//! event-listener-cli --output json backfill --from 100 --to 200
+//! ```
+//!
+//! This only uses the public API of `event-listener`, so anything it can't do without reaching
+//! into the crate is something that the crate is missing.
+
+use clap::{ArgEnum, Parser, Subcommand};
+use event_listener::{
+	events::EventDetails,
+	listener::{EventContext, EventRecord, HandlerResult, Routes, Rule, RuleSet},
+	Error, OnlineClient, PolkadotConfig,
+};
+use std::str::FromStr;
+
+type Ctx = EventContext<PolkadotConfig, OnlineClient<PolkadotConfig>>;
+
+/// The route that every rule hands its events to.
+const PRINT: &str = "print";
+
+#[derive(Parser)]
+#[clap(name = "event-listener-cli", version, about)]
+struct Cli {
+	/// The WebSocket URL of the node to connect to.
+	#[clap(long, global = true, default_value = "ws://127.0.0.1:9944")]
+	url: String,
+	/// Only show the events of a pallet (`Balances`) or one event (`Balances.Transfer`). Can be
+	/// given more than once, to show the events matching any of them.
+	#[clap(long = "filter", global = true)]
+	filters: Vec<Filter>,
+	/// How to write events out.
+	#[clap(long, arg_enum, global = true, default_value = "text")]
+	output: Output,
+	#[clap(subcommand)]
+	command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+	/// Show events as their blocks are finalized, until stopped with Ctrl-C.
+	Listen {
+		/// Show the events of best blocks as they're imported, rather than waiting for them to
+		/// be finalized.
+		#[clap(long)]
+		best: bool,
+	},
+	/// Show the events of a range of past blocks. Old blocks need an archive node.
+	Backfill {
+		/// The number of the first block.
+		#[clap(long)]
+		from: u64,
+		/// The number of the last block.
+		#[clap(long)]
+		to: u64,
+		/// How many blocks to fetch at once.
+		#[clap(long, default_value = "8")]
+		fetchers: usize,
+	},
+}
+
+#[derive(Clone, Copy, ArgEnum)]
+enum Output {
+	/// `#<block> <index> Pallet::Event { field: value, .. }`, one event per line.
+	Text,
+	/// An event record (see `event_listener::listener::EventRecord`) per line.
+	Json,
+}
+
+/// A `Pallet` or `Pallet.Event` given with `--filter`.
+#[derive(Clone, Debug)]
+struct Filter {
+	pallet: String,
+	event: Option<String>,
+}
+
+impl FromStr for Filter {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (pallet, event) = match s.split_once('.') {
+			Some((pallet, event)) => (pallet, Some(event)),
+			None => (s, None),
+		};
+		if pallet.is_empty() || event.map_or(false, str::is_empty) {
+			return Err(format!("expected `Pallet` or `Pallet.Event`, not `{}`", s));
+		}
+		Ok(Filter { pallet: pallet.to_owned(), event: event.map(ToOwned::to_owned) })
+	}
+}
+
+#[tokio::main]
+async fn main() {
+	let cli = Cli::parse();
+	if let Err(e) = run(cli).await {
+		eprintln!("error: {}", e);
+		std::process::exit(1);
+	}
+}
+
+async fn run(cli: Cli) -> Result<(), Error> {
+	let api = OnlineClient::<PolkadotConfig>::from_url(&cli.url).await?;
+	let output = cli.output;
+	let routes: Routes<PolkadotConfig, _> =
+		Routes::new().route(PRINT, move |ctx, event| print(output, ctx, event));
+	let builder = api.listener().rules(&rules(&cli.filters), &routes)?;
+
+	match cli.command {
+		Command::Listen { best } => {
+			let listener = builder.best_blocks(best).build();
+			let shutdown = listener.shutdown_handle();
+			tokio::spawn(async move {
+				if tokio::signal::ctrl_c().await.is_ok() {
+					shutdown.shutdown().await;
+				}
+			});
+			listener.run().await
+		},
+		Command::Backfill { from, to, fetchers } => {
+			if from > to {
+				return Err(Error::Other(format!("--from {} is after --to {}", from, to)));
+			}
+			builder.build().backfill(from..=to).fetchers(fetchers).run().await
+		},
+	}
+}
+
+// A rule for each filter, or else one which matches every event.
+fn rules(filters: &[Filter]) -> RuleSet {
+	let rule = |filter: Option<&Filter>| Rule {
+		name: None,
+		pallet: filter.map(|f| f.pallet.clone()),
+		event: filter.and_then(|f| f.event.clone()),
+		conditions: Vec::new(),
+		route: PRINT.to_owned(),
+	};
+	let rules = if filters.is_empty() {
+		vec![rule(None)]
+	} else {
+		filters.iter().map(|f| rule(Some(f))).collect()
+	};
+	RuleSet { rules }
+}
+
+async fn print(output: Output, ctx: Ctx, event: EventDetails) -> HandlerResult {
+	match output {
+		Output::Text => println!("#{} {} {}", ctx.block_number(), ctx.event_index(), event),
+		Output::Json => {
+			let record = EventRecord::new(&ctx, &event);
+			println!("{}", serde_json::to_string(&record)?);
+		},
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn filters_are_parsed() {
+		let filter: Filter = "Balances.Transfer".parse().unwrap();
+		assert_eq!(
+			(filter.pallet.as_str(), filter.event.as_deref()),
+			("Balances", Some("Transfer"))
+		);
+		let filter: Filter = "System".parse().unwrap();
+		assert_eq!((filter.pallet.as_str(), filter.event), ("System", None));
+		assert!("".parse::<Filter>().is_err());
+		assert!("Balances.".parse::<Filter>().is_err());
+	}
+
+	#[test]
+	fn no_filters_match_everything() {
+		let rules = rules(&[]).rules;
+		assert_eq!(rules.len(), 1);
+		assert_eq!((rules[0].pallet.as_ref(), rules[0].event.as_ref()), (None, None));
+	}
+}
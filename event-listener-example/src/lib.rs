@@ -1,5 +1,9 @@
 use futures::StreamExt;
-use event_listener::{OnlineClient, PolkadotConfig};
+use event_listener::{
+	events::EventDetails,
+	listener::{EventContext, HandlerError},
+	OnlineClient, PolkadotConfig,
+};
 
 pub async fn listen_event() {
 	// Create a client to use:
@@ -14,13 +18,31 @@ pub async fn listen_event() {
 		while let Some(events) = event_sub.next().await {
 			let events = events.unwrap();
 			let block_hash = events.block_hash();
-			log::info!("Dynamic event details: {}", block_hash);
+			tracing::info!(block_hash = ?block_hash, "Received events");
 			for event in events.iter() {
 				let event = event.unwrap();
-				let pallet = event.pallet_name();
-				let variant = event.variant_name();
-				log::info!("event details {}::{} ", pallet, variant);
+				tracing::info!(index = event.index(), "{}", event);
 			}
 		}
 	}
 }
+
+pub async fn listen_transfers() {
+	let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+
+	// Let the listener drive the subscription, and just handle the events we want:
+	api.listener()
+		.on_dynamic("Balances", "Transfer", |ctx: EventContext<PolkadotConfig, OnlineClient<PolkadotConfig>>, event: EventDetails| async move {
+			let fields = event.field_values()?;
+			tracing::info!(
+				block = ctx.block_number(),
+				timestamp = ctx.timestamp(),
+				fields = ?fields,
+				"Transfer"
+			);
+			Ok::<_, HandlerError>(())
+		})
+		.run()
+		.await
+		.unwrap();
+}
@@ -0,0 +1,11 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+fn main() {
+    // The gRPC event feed is generated from its protobuf definition, which needs
+    // `protoc` to be installed.
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/event_listener.proto")
+        .expect("Failed to compile proto/event_listener.proto");
+}
@@ -0,0 +1,167 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! A synchronous API over [`OnlineClient`], for programs that don't use `async`.
+//!
+//! [`BlockingClient`] runs the client on a Tokio runtime of its own, on a
+//! background thread, and blocks the calling thread until each call is done.
+//! Events are handed back by iterators rather than streams.
+//!
+//! ```no_run
+//! use event_listener::{
+//!     blocking::BlockingClient,
+//!     PolkadotConfig,
+//! };
+//!
+//! let client = BlockingClient::<PolkadotConfig>::from_url("wss://rpc.polkadot.io:443").unwrap();
+//! for events in client.events_blocking() {
+//!     for event in events.unwrap().iter() {
+//!         println!("{}", event.unwrap());
+//!     }
+//! }
+//! ```
+//!
+//! Don't call these from within an async runtime; they block the thread that
+//! they're called on, and Tokio panics if asked to block one of its own threads.
+
+use crate::{
+    error::Error,
+    events::{
+        Events,
+        SubscribeOpts,
+    },
+    Config,
+    OnlineClient,
+};
+use futures::{
+    future,
+    stream::{
+        self,
+        BoxStream,
+    },
+    StreamExt,
+};
+use std::sync::Arc;
+use tokio::runtime::{
+    Builder,
+    Runtime,
+};
+
+/// An [`OnlineClient`] with a synchronous API. Cheap to clone; clones share the
+/// connection and the runtime that drives it.
+pub struct BlockingClient<T: Config> {
+    client: OnlineClient<T>,
+    runtime: Arc<Runtime>,
+}
+
+impl<T: Config> BlockingClient<T> {
+    /// Connect to a node running locally, on `ws://127.0.0.1:9944`.
+    pub fn new() -> Result<Self, Error> {
+        Self::from_url("ws://127.0.0.1:9944")
+    }
+
+    /// Connect to the node at the URL given.
+    pub fn from_url(url: impl AsRef<str>) -> Result<Self, Error> {
+        let runtime = runtime()?;
+        // The connection spawns tasks of its own, so it has to be made within
+        // the runtime that's going to drive it.
+        let client = runtime.block_on(OnlineClient::from_url(url))?;
+        Ok(BlockingClient {
+            client,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// The async client underneath. Its futures need to be run on
+    /// [`BlockingClient::block_on()`].
+    pub fn client(&self) -> &OnlineClient<T> {
+        &self.client
+    }
+
+    /// Run a future (such as one from [`BlockingClient::client()`]) to
+    /// completion, blocking the current thread until it's done.
+    pub fn block_on<F: std::future::Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// The events of the block with the hash given, or of the latest block if
+    /// `None`.
+    pub fn events_at(&self, block_hash: Option<T::Hash>) -> Result<Events<T>, Error> {
+        self.block_on(self.client.events().at(block_hash))
+    }
+
+    /// Iterate over the events of each finalized block, as they're finalized.
+    /// If subscribing fails, the error is the first and only thing handed back.
+    pub fn events_blocking(&self) -> BlockingEvents<T> {
+        self.events_blocking_with(SubscribeOpts::new())
+    }
+
+    /// Like [`BlockingClient::events_blocking()`], but subscribing with the
+    /// options given. See [`SubscribeOpts`].
+    pub fn events_blocking_with(&self, opts: SubscribeOpts) -> BlockingEvents<T> {
+        let subscribe = self.client.events().subscribe_with(opts);
+        let events = stream::once(subscribe)
+            .flat_map(|sub| {
+                match sub {
+                    Ok(sub) => sub.boxed(),
+                    Err(e) => stream::once(future::ready(Err(e))).boxed(),
+                }
+            })
+            .boxed();
+        BlockingEvents {
+            events,
+            runtime: self.runtime.clone(),
+        }
+    }
+}
+
+impl<T: Config> Clone for BlockingClient<T> {
+    fn clone(&self) -> Self {
+        BlockingClient {
+            client: self.client.clone(),
+            runtime: self.runtime.clone(),
+        }
+    }
+}
+
+impl<T: Config> std::fmt::Debug for BlockingClient<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlockingClient")
+            .field("client", &self.client)
+            .finish()
+    }
+}
+
+/// An iterator over the events of each block, handed back from
+/// [`BlockingClient::events_blocking()`]. Each call to `next()` blocks until the
+/// events of the next block are in.
+pub struct BlockingEvents<T: Config> {
+    events: BoxStream<'static, Result<Events<T>, Error>>,
+    runtime: Arc<Runtime>,
+}
+
+impl<T: Config> Iterator for BlockingEvents<T> {
+    type Item = Result<Events<T>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.events.next())
+    }
+}
+
+impl<T: Config> std::fmt::Debug for BlockingEvents<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlockingEvents").finish()
+    }
+}
+
+// A runtime with a thread of its own, so that the connection is driven (and
+// subscriptions are kept up with) between calls, and not just during them.
+fn runtime() -> Result<Runtime, Error> {
+    let runtime = Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("event-listener-blocking")
+        .enable_all()
+        .build()?;
+    Ok(runtime)
+}
@@ -0,0 +1,347 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use super::{
+    BlockSub,
+    ExtrinsicDetails,
+    Extrinsics,
+};
+use crate::{
+    client::OnlineClientT,
+    error::Error,
+    events::{
+        EventDetails,
+        Events,
+        Phase,
+    },
+    utils::{
+        composite_values,
+        value_as_bytes,
+    },
+    Config,
+};
+use codec::Encode;
+use derivative::Derivative;
+use futures::{
+    stream::{
+        self,
+        BoxStream,
+    },
+    StreamExt,
+};
+use scale_value::{
+    scale::TypeId,
+    Value,
+    ValueDef,
+};
+
+/// Something in a block that involves an account.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub enum Activity<T: Config> {
+    /// An extrinsic signed by the account.
+    Signed(ExtrinsicDetails<T>),
+    /// An extrinsic signed by another account, or not signed, whose call names
+    /// the account, such as a transfer to it.
+    Targeted(ExtrinsicDetails<T>),
+    /// An event that names the account in any of its fields.
+    Event(EventDetails),
+}
+
+impl<T: Config> Activity<T> {
+    /// Everything in the block of the extrinsics given that involves the
+    /// account given, in the order that it happened: the events emitted while
+    /// initializing the block, then each extrinsic followed by the events that
+    /// it emitted, then the events emitted while finalizing the block.
+    ///
+    /// Extrinsics and events that can't be decoded are logged and skipped, so
+    /// that one of them doesn't hide the rest of the block.
+    pub fn in_block(
+        extrinsics: &Extrinsics<T>,
+        account: &T::AccountId,
+    ) -> Result<Vec<Activity<T>>, Error> {
+        let account = account.encode();
+        // Each piece of activity is ordered by the phase of the block that it's
+        // in, and then extrinsics before events.
+        let mut activity = Vec::new();
+        for (index, extrinsic) in extrinsics.iter().enumerate() {
+            match extrinsic.and_then(|extrinsic| extrinsic_activity(extrinsic, &account)) {
+                Ok(Some(found)) => activity.push(found),
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::warn!("Skipping extrinsic {} that can't be decoded: {}", index, e)
+                }
+            }
+        }
+        activity.extend(event_activity(extrinsics.events(), &account));
+        activity.sort_by_key(|(order, _)| *order);
+        Ok(activity.into_iter().map(|(_, activity)| activity).collect())
+    }
+}
+
+/// An [`Activity`], with the block that it's in.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub struct AccountActivity<T: Config> {
+    /// The hash of the block.
+    pub block_hash: T::Hash,
+    /// The number of the block.
+    pub block_number: u64,
+    /// What happened.
+    pub activity: Activity<T>,
+}
+
+/// Follow the blocks of a subscription, handing back everything in them that
+/// involves the account given, in order. See [`Activity::in_block()`].
+///
+/// The body and events of every block are fetched, so this is best suited to
+/// following a handful of accounts, such as for the activity feed of a wallet.
+///
+/// ```no_run
+/// use event_listener::{
+///     blocks,
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+/// use futures::StreamExt;
+/// use sp_core::crypto::{
+///     AccountId32,
+///     Ss58Codec,
+/// };
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let account =
+///     AccountId32::from_ss58check("5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY").unwrap();
+/// let blocks = api.blocks().subscribe_finalized().await.unwrap();
+/// let mut activity = blocks::subscribe_account_activity(blocks, account);
+/// while let Some(activity) = activity.next().await {
+///     let activity = activity.unwrap();
+///     match activity.activity {
+///         blocks::Activity::Signed(ext) | blocks::Activity::Targeted(ext) => {
+///             println!("#{} {}::{}", activity.block_number, ext.pallet_name(), ext.call_name())
+///         }
+///         blocks::Activity::Event(ev) => {
+///             println!("#{} {}::{}", activity.block_number, ev.pallet_name(), ev.variant_name())
+///         }
+///     }
+/// }
+/// # }
+/// ```
+pub fn subscribe_account_activity<T, Client>(
+    blocks: BlockSub<T, Client>,
+    account: T::AccountId,
+) -> BoxStream<'static, Result<AccountActivity<T>, Error>>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    blocks
+        .then(move |block| {
+            let account = account.clone();
+            async move {
+                let block = block?;
+                let block_hash = block.hash();
+                let block_number: u64 = block.number().into();
+                let extrinsics = block.extrinsics().await?;
+                let activity = Activity::in_block(&extrinsics, &account)?;
+                Ok(activity
+                    .into_iter()
+                    .map(|activity| {
+                        AccountActivity {
+                            block_hash,
+                            block_number,
+                            activity,
+                        }
+                    })
+                    .collect::<Vec<_>>())
+            }
+        })
+        .flat_map(|found| {
+            let found = match found {
+                Ok(found) => found.into_iter().map(Ok).collect(),
+                Err(e) => vec![Err(e)],
+            };
+            stream::iter(found)
+        })
+        .boxed()
+}
+
+// The activity of the extrinsic given, if it involves the SCALE encoded account
+// given, with where it comes in the block.
+fn extrinsic_activity<T: Config>(
+    extrinsic: ExtrinsicDetails<T>,
+    account: &[u8],
+) -> Result<Option<((u64, u64), Activity<T>)>, Error> {
+    let order = (phase_order(Phase::ApplyExtrinsic(extrinsic.index())), 0);
+    let signed = match extrinsic.address_value() {
+        Some(address) => mentions(&address?, account),
+        None => false,
+    };
+    if signed {
+        return Ok(Some((order, Activity::Signed(extrinsic))))
+    }
+    if composite_values(&extrinsic.field_values()?).any(|v| mentions(v, account)) {
+        return Ok(Some((order, Activity::Targeted(extrinsic))))
+    }
+    Ok(None)
+}
+
+// The events given that involve the SCALE encoded account given, with where
+// they come in the block. Events that can't be decoded are logged and skipped.
+fn event_activity<T: Config>(
+    events: &Events<T>,
+    account: &[u8],
+) -> Vec<((u64, u64), Activity<T>)> {
+    let mut activity = Vec::new();
+    for (index, event) in events.iter().enumerate() {
+        let found = event.and_then(|event| {
+            let mentioned =
+                composite_values(&event.field_values()?).any(|v| mentions(v, account));
+            Ok(mentioned.then_some(event))
+        });
+        match found {
+            Ok(Some(event)) => {
+                let order = (phase_order(event.phase()), 1 + u64::from(event.index()));
+                activity.push((order, Activity::Event(event)));
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Skipping event {} that can't be decoded: {}", index, e),
+        }
+    }
+    activity
+}
+
+// Where the phase given comes in the block.
+fn phase_order(phase: Phase) -> u64 {
+    match phase {
+        Phase::Initialization => 0,
+        Phase::ApplyExtrinsic(index) => 1 + u64::from(index),
+        Phase::Finalization => u64::MAX,
+    }
+}
+
+// Whether the value given holds the SCALE encoded account given anywhere in it.
+fn mentions(value: &Value<TypeId>, account: &[u8]) -> bool {
+    if let Some(bytes) = value_as_bytes(value) {
+        // Bytes hold nothing else to look in.
+        return bytes == account
+    }
+    match &value.value {
+        ValueDef::Composite(c) => composite_values(c).any(|v| mentions(v, account)),
+        ValueDef::Variant(v) => composite_values(&v.values).any(|v| mentions(v, account)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        events::test_utils::{
+            event_record,
+            events,
+            events_raw,
+            metadata,
+        },
+        pallets::test_utils::account,
+    };
+    use codec::Decode;
+    use scale_info::TypeInfo;
+    use sp_core::crypto::AccountId32;
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Event {
+        Transfer {
+            from: AccountId32,
+            to: AccountId32,
+            amount: u128,
+        },
+        Nested {
+            accounts: Vec<(u32, Option<AccountId32>)>,
+        },
+    }
+
+    #[test]
+    fn accounts_are_found_anywhere_in_a_value() {
+        let metadata = metadata::<Event>();
+        let events = events::<Event>(
+            metadata,
+            vec![
+                event_record(
+                    Phase::ApplyExtrinsic(0),
+                    Event::Transfer {
+                        from: account(1),
+                        to: account(2),
+                        amount: 10,
+                    },
+                ),
+                event_record(
+                    Phase::Finalization,
+                    Event::Nested {
+                        accounts: vec![(1, None), (2, Some(account(3)))],
+                    },
+                ),
+            ],
+        );
+        let found: Vec<Vec<u8>> = events
+            .iter()
+            .map(|event| {
+                let fields = event.unwrap().field_values().unwrap();
+                (1..=4)
+                    .filter(|&byte| {
+                        composite_values(&fields)
+                            .any(|v| mentions(v, &account(byte).encode()))
+                    })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(found, vec![vec![1, 2], vec![3]]);
+    }
+
+    #[test]
+    fn undecodable_events_are_skipped() {
+        let metadata = metadata::<Event>();
+        let mut event_bytes = event_record(
+            Phase::ApplyExtrinsic(0),
+            Event::Transfer {
+                from: account(1),
+                to: account(2),
+                amount: 10,
+            },
+        )
+        .encode();
+        // An event of a pallet that isn't in the metadata.
+        event_bytes.extend([0, 0, 0, 0, 0, 9, 0]);
+        let events = events_raw(metadata, event_bytes, 2);
+        let activity = event_activity(&events, &account(2).encode());
+        assert_eq!(activity.len(), 1);
+        assert!(matches!(
+            &activity[0],
+            ((1, 1), Activity::Event(event)) if event.variant_name() == "Transfer"
+        ));
+    }
+
+    #[test]
+    fn activity_is_ordered_by_phase() {
+        let phases = [
+            Phase::Finalization,
+            Phase::ApplyExtrinsic(2),
+            Phase::Initialization,
+            Phase::ApplyExtrinsic(0),
+        ];
+        let mut sorted = phases.to_vec();
+        sorted.sort_by_key(|phase| phase_order(*phase));
+        assert_eq!(
+            sorted,
+            vec![
+                Phase::Initialization,
+                Phase::ApplyExtrinsic(0),
+                Phase::ApplyExtrinsic(2),
+                Phase::Finalization,
+            ]
+        );
+    }
+}
@@ -0,0 +1,325 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use super::BlockPin;
+use crate::{
+    client::OnlineClientT,
+    error::Error,
+    pallets::twox_64_concat,
+    Config,
+};
+use codec::Decode;
+use sp_core::twox_128;
+use sp_runtime::{
+    traits::Header,
+    DigestItem,
+};
+use std::future::Future;
+
+/// The engine ID of BABE pre-runtime digests.
+pub const BABE_ENGINE_ID: [u8; 4] = *b"BABE";
+/// The engine ID of Aura pre-runtime digests.
+pub const AURA_ENGINE_ID: [u8; 4] = *b"aura";
+
+/// Who is claiming to have authored a block, according to the pre-runtime
+/// digest in its header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuthorClaim {
+    /// A BABE block, authored by the authority at the given index.
+    Babe {
+        /// The index of the author in the authority set.
+        authority_index: u32,
+        /// The slot that the block was authored in.
+        slot: u64,
+    },
+    /// An Aura block. Aura authors take turns, so the slot determines the author.
+    Aura {
+        /// The slot that the block was authored in.
+        slot: u64,
+    },
+}
+
+impl AuthorClaim {
+    /// Find the author claim in the pre-runtime digests of the header given, if
+    /// there is one that we know how to decode.
+    pub fn from_header<T: Config>(header: &T::Header) -> Option<AuthorClaim> {
+        header.digest().logs().iter().find_map(|log| {
+            match log {
+                DigestItem::PreRuntime(engine_id, data) => {
+                    Self::from_pre_runtime(*engine_id, data)
+                }
+                _ => None,
+            }
+        })
+    }
+
+    /// Decode the author claim from a single pre-runtime digest.
+    pub fn from_pre_runtime(engine_id: [u8; 4], data: &[u8]) -> Option<AuthorClaim> {
+        let cursor = &mut &*data;
+        match engine_id {
+            BABE_ENGINE_ID => {
+                // Every BABE pre-digest variant (primary, secondary plain and
+                // secondary VRF) begins with the authority index and then the slot.
+                let variant = u8::decode(cursor).ok()?;
+                if !(1..=3).contains(&variant) {
+                    return None
+                }
+                let authority_index = u32::decode(cursor).ok()?;
+                let slot = u64::decode(cursor).ok()?;
+                Some(AuthorClaim::Babe {
+                    authority_index,
+                    slot,
+                })
+            }
+            AURA_ENGINE_ID => {
+                let slot = u64::decode(cursor).ok()?;
+                Some(AuthorClaim::Aura { slot })
+            }
+            _ => None,
+        }
+    }
+
+    /// The index of the author in an authority set of the size given.
+    pub fn author_index(&self, num_authorities: usize) -> Option<usize> {
+        match *self {
+            AuthorClaim::Babe {
+                authority_index, ..
+            } => {
+                let idx = authority_index as usize;
+                if idx < num_authorities {
+                    Some(idx)
+                } else {
+                    None
+                }
+            }
+            AuthorClaim::Aura { slot } => {
+                if num_authorities == 0 {
+                    None
+                } else {
+                    Some((slot % num_authorities as u64) as usize)
+                }
+            }
+        }
+    }
+}
+
+/// Fetch the authority set that the block with the given hash and header was
+/// authored under, and pick out the author claimed by its pre-runtime digest.
+///
+/// BABE enacts a new epoch while initializing the first block of it, so the
+/// authorities of a BABE block are read from the state of the block itself.
+/// Aura authors are picked from the authorities in the state of the parent
+/// block, since a change made while initializing a block only applies from the
+/// next one on.
+///
+/// If the block is pinned, the state of the block is read through the `chainHead`
+/// subscription pinning it. The parent block isn't pinned by it, so the state of
+/// that is read as usual.
+pub(crate) async fn block_author<T, Client>(
+    client: &Client,
+    block_hash: T::Hash,
+    header: &T::Header,
+    pin: Option<&BlockPin<T::Hash>>,
+) -> Result<Option<T::AccountId>, Error>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let claim = match AuthorClaim::from_header::<T>(header) {
+        Some(claim) => claim,
+        None => return Ok(None),
+    };
+    let parent_hash = *header.parent_hash();
+    claimed_author::<T, _, _>(claim, block_hash, parent_hash, |key, at| {
+        let client = client.clone();
+        let pin = pin.filter(|pin| pin.hash() == at).cloned();
+        async move {
+            let data = match pin {
+                Some(pin) => {
+                    client
+                        .rpc()
+                        .chainhead_unstable_storage(pin.subscription_id(), at, &key)
+                        .await?
+                }
+                None => client.rpc().storage(&key, Some(at)).await?,
+            };
+            Ok(data.map(|data| data.0))
+        }
+    })
+    .await
+}
+
+// Resolve the author of a claim from storage, read at a block with `fetch`.
+async fn claimed_author<T, F, Fut>(
+    claim: AuthorClaim,
+    block_hash: T::Hash,
+    parent_hash: T::Hash,
+    fetch: F,
+) -> Result<Option<T::AccountId>, Error>
+where
+    T: Config,
+    F: Fn(Vec<u8>, T::Hash) -> Fut,
+    Fut: Future<Output = Result<Option<Vec<u8>>, Error>>,
+{
+    let (at, pallet, key_type) = match claim {
+        AuthorClaim::Babe { .. } => (block_hash, "Babe", BABE_KEY_TYPE),
+        AuthorClaim::Aura { .. } => (parent_hash, "Aura", AURA_KEY_TYPE),
+    };
+    let authorities: Vec<[u8; 32]> = match fetch(authorities_key(pallet), at).await? {
+        Some(data) => {
+            match claim {
+                AuthorClaim::Babe { .. } => {
+                    // Each BABE authority comes with a weight, which is unused.
+                    Vec::<([u8; 32], u64)>::decode(&mut &*data)?
+                        .into_iter()
+                        .map(|(key, _)| key)
+                        .collect()
+                }
+                AuthorClaim::Aura { .. } => Vec::<[u8; 32]>::decode(&mut &*data)?,
+            }
+        }
+        None => return Ok(None),
+    };
+    let key = match claim.author_index(authorities.len()) {
+        Some(idx) => authorities[idx],
+        None => return Ok(None),
+    };
+
+    // Authorities are known by their session keys, which `Session::KeyOwner` maps
+    // to the validators that they belong to. Chains without the `Session` pallet
+    // (such as those with a fixed set of Aura authorities) use account keys.
+    match fetch(key_owner_key(key_type, &key), at).await? {
+        Some(owner) => Ok(Some(T::AccountId::decode(&mut &*owner)?)),
+        None => Ok(T::AccountId::decode(&mut &key[..]).ok()),
+    }
+}
+
+// The key types of BABE and Aura session keys.
+const BABE_KEY_TYPE: [u8; 4] = *b"babe";
+const AURA_KEY_TYPE: [u8; 4] = *b"aura";
+
+// The storage key for the `Authorities` of the `Babe` or `Aura` pallet.
+fn authorities_key(pallet: &str) -> Vec<u8> {
+    let mut storage_key = twox_128(pallet.as_bytes()).to_vec();
+    storage_key.extend(twox_128(b"Authorities").to_vec());
+    storage_key
+}
+
+// The storage key for the `Session::KeyOwner` of a session key.
+fn key_owner_key(key_type: [u8; 4], key: &[u8]) -> Vec<u8> {
+    let mut storage_key = twox_128(b"Session").to_vec();
+    storage_key.extend(twox_128(b"KeyOwner").to_vec());
+    storage_key.extend(twox_64_concat(&(key_type, key)));
+    storage_key
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::SubstrateConfig;
+    use codec::Encode;
+    use sp_core::{
+        crypto::AccountId32,
+        H256,
+    };
+    use std::collections::HashMap;
+
+    #[test]
+    fn babe_claims_are_decoded() {
+        // A secondary plain pre-digest: variant, authority index, slot.
+        let data = (2u8, 7u32, 1234u64).encode();
+        assert_eq!(
+            AuthorClaim::from_pre_runtime(BABE_ENGINE_ID, &data),
+            Some(AuthorClaim::Babe {
+                authority_index: 7,
+                slot: 1234
+            })
+        );
+
+        // Unknown variants are ignored.
+        let data = (9u8, 7u32, 1234u64).encode();
+        assert_eq!(AuthorClaim::from_pre_runtime(BABE_ENGINE_ID, &data), None);
+    }
+
+    #[test]
+    fn aura_claims_pick_author_by_slot() {
+        let data = 10u64.encode();
+        let claim = AuthorClaim::from_pre_runtime(AURA_ENGINE_ID, &data).unwrap();
+        assert_eq!(claim, AuthorClaim::Aura { slot: 10 });
+        assert_eq!(claim.author_index(4), Some(2));
+        assert_eq!(claim.author_index(0), None);
+    }
+
+    #[test]
+    fn babe_author_index_is_bounds_checked() {
+        let claim = AuthorClaim::Babe {
+            authority_index: 3,
+            slot: 0,
+        };
+        assert_eq!(claim.author_index(4), Some(3));
+        assert_eq!(claim.author_index(3), None);
+    }
+
+    // Storage either side of the first block of a new session, in which the
+    // authorities were rotated from keys 1 and 2 to keys 3 and 4. The validator
+    // with key `n` is the account `10 + n`.
+    fn session_boundary(
+        key_owners: bool,
+    ) -> (H256, H256, HashMap<(Vec<u8>, H256), Vec<u8>>) {
+        let parent_hash = H256::repeat_byte(1);
+        let block_hash = H256::repeat_byte(2);
+        let mut storage = HashMap::new();
+        for (at, keys) in [(parent_hash, [1u8, 2]), (block_hash, [3, 4])] {
+            let babe: Vec<([u8; 32], u64)> = keys.iter().map(|&k| ([k; 32], 1)).collect();
+            let aura: Vec<[u8; 32]> = keys.iter().map(|&k| [k; 32]).collect();
+            storage.insert((authorities_key("Babe"), at), babe.encode());
+            storage.insert((authorities_key("Aura"), at), aura.encode());
+            for key in 1..=4u8 {
+                if key_owners {
+                    let owner = AccountId32::new([10 + key; 32]).encode();
+                    storage.insert((key_owner_key(BABE_KEY_TYPE, &[key; 32]), at), owner.clone());
+                    storage.insert((key_owner_key(AURA_KEY_TYPE, &[key; 32]), at), owner);
+                }
+            }
+        }
+        (parent_hash, block_hash, storage)
+    }
+
+    fn author(claim: AuthorClaim, key_owners: bool) -> Option<AccountId32> {
+        let (parent_hash, block_hash, storage) = session_boundary(key_owners);
+        let fetch = |key: Vec<u8>, at: H256| {
+            futures::future::ready(Ok(storage.get(&(key, at)).cloned()))
+        };
+        futures::executor::block_on(claimed_author::<SubstrateConfig, _, _>(
+            claim,
+            block_hash,
+            parent_hash,
+            fetch,
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn babe_authors_are_read_from_the_new_epoch() {
+        let claim = AuthorClaim::Babe {
+            authority_index: 0,
+            slot: 100,
+        };
+        assert_eq!(author(claim, true), Some(AccountId32::new([13; 32])));
+        assert_eq!(author(claim, false), Some(AccountId32::new([3; 32])));
+
+        let out_of_range = AuthorClaim::Babe {
+            authority_index: 2,
+            slot: 100,
+        };
+        assert_eq!(author(out_of_range, true), None);
+    }
+
+    #[test]
+    fn aura_authors_are_read_from_the_parent() {
+        let claim = AuthorClaim::Aura { slot: 5 };
+        assert_eq!(author(claim, true), Some(AccountId32::new([12; 32])));
+        assert_eq!(author(claim, false), Some(AccountId32::new([2; 32])));
+    }
+}
@@ -0,0 +1,345 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Attribute the events emitted by a `Utility::batch`, `Utility::batch_all` or
+//! `Utility::force_batch` extrinsic to the inner calls that emitted them.
+
+use crate::{
+    events::EventDetails,
+    utils::composite_values,
+};
+use scale_value::{
+    scale::TypeId,
+    Composite,
+    Value,
+    ValueDef,
+};
+
+/// The events emitted by a single call inside of a batch.
+#[derive(Debug, Clone)]
+pub struct BatchItemEvents {
+    index: usize,
+    events: Vec<EventDetails>,
+    outcome: BatchItemOutcome,
+    nested: Option<Vec<BatchItemEvents>>,
+}
+
+impl BatchItemEvents {
+    /// The index of the call in the batch.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The events emitted by the call, not including the `Utility` event that
+    /// marks the end of the call.
+    pub fn events(&self) -> &[EventDetails] {
+        &self.events
+    }
+
+    /// How the call got on.
+    pub fn outcome(&self) -> &BatchItemOutcome {
+        &self.outcome
+    }
+
+    /// If the call is itself a batch, the events of its inner calls.
+    pub fn nested(&self) -> Option<&[BatchItemEvents]> {
+        self.nested.as_deref()
+    }
+}
+
+/// How a call inside of a batch got on.
+#[derive(Debug, Clone)]
+pub enum BatchItemOutcome {
+    /// The call succeeded (`Utility::ItemCompleted`).
+    Completed,
+    /// The call failed, and the batch carried on (`Utility::ItemFailed`), as
+    /// happens with `force_batch`. The event contains the error.
+    Failed(EventDetails),
+    /// The call failed, and the batch stopped (`Utility::BatchInterrupted`), as
+    /// happens with `batch`. The event contains the error. Any events that the
+    /// call emitted were reverted.
+    Interrupted(EventDetails),
+}
+
+/// The shape of a call, as far as splitting up batch events is concerned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum CallShape {
+    /// Some call which isn't a batch.
+    Plain,
+    /// A batch containing the given calls.
+    Batch(Vec<CallShape>),
+}
+
+impl CallShape {
+    /// Work out the shape of the decoded root call given.
+    pub(crate) fn from_call(call: &Value<TypeId>) -> CallShape {
+        match batch_calls_in(call) {
+            Some(calls) => CallShape::Batch(calls),
+            None => CallShape::Plain,
+        }
+    }
+
+    /// Work out the shapes of the calls in a batch, given the arguments of a
+    /// `Utility` batch call.
+    pub(crate) fn from_batch_args(args: &Composite<TypeId>) -> Option<Vec<CallShape>> {
+        let calls = composite_values(args).next()?;
+        match &calls.value {
+            ValueDef::Composite(calls) => {
+                Some(composite_values(calls).map(CallShape::from_call).collect())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Is this pallet and call one of the `Utility` batch calls?
+pub(crate) fn is_batch_call(pallet: &str, call: &str) -> bool {
+    pallet == "Utility" && matches!(call, "batch" | "batch_all" | "force_batch")
+}
+
+// A root call value is a pallet variant wrapping a call variant.
+fn batch_calls_in(call: &Value<TypeId>) -> Option<Vec<CallShape>> {
+    let pallet = match &call.value {
+        ValueDef::Variant(pallet) => pallet,
+        _ => return None,
+    };
+    let call = match &composite_values(&pallet.values).next()?.value {
+        ValueDef::Variant(call) => call,
+        _ => return None,
+    };
+    if !is_batch_call(&pallet.name, &call.name) {
+        return None
+    }
+    CallShape::from_batch_args(&call.values)
+}
+
+/// The `Utility` events that delimit the calls of a batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Marker {
+    ItemCompleted,
+    ItemFailed,
+    BatchInterrupted,
+    BatchCompleted,
+}
+
+impl Marker {
+    fn of(ev: &EventDetails) -> Option<Marker> {
+        if ev.pallet_name() != "Utility" {
+            return None
+        }
+        match ev.variant_name() {
+            "ItemCompleted" => Some(Marker::ItemCompleted),
+            "ItemFailed" => Some(Marker::ItemFailed),
+            "BatchInterrupted" => Some(Marker::BatchInterrupted),
+            "BatchCompleted" | "BatchCompletedWithErrors" => Some(Marker::BatchCompleted),
+            _ => None,
+        }
+    }
+}
+
+/// Split the events emitted by an extrinsic into those emitted by each of the
+/// calls in the batch. Returns `None` if the events don't line up with the calls.
+pub(crate) fn split_batch_events(
+    events: &[EventDetails],
+    calls: &[CallShape],
+) -> Option<Vec<BatchItemEvents>> {
+    let mut pos = 0;
+    let items = split(events, &mut pos, calls, &Marker::of)?;
+    Some(items.into_iter().map(SplitItem::into_batch_item).collect())
+}
+
+// The splitting logic doesn't care what the events are, which lets us test it.
+#[derive(Debug)]
+struct SplitItem<E> {
+    index: usize,
+    events: Vec<E>,
+    marker: Marker,
+    marker_event: E,
+    nested: Option<Vec<SplitItem<E>>>,
+}
+
+impl SplitItem<EventDetails> {
+    fn into_batch_item(self) -> BatchItemEvents {
+        let outcome = match self.marker {
+            Marker::ItemFailed => BatchItemOutcome::Failed(self.marker_event),
+            Marker::BatchInterrupted => BatchItemOutcome::Interrupted(self.marker_event),
+            _ => BatchItemOutcome::Completed,
+        };
+        BatchItemEvents {
+            index: self.index,
+            events: self.events,
+            outcome,
+            nested: self
+                .nested
+                .map(|n| n.into_iter().map(SplitItem::into_batch_item).collect()),
+        }
+    }
+}
+
+// Split up the events, starting at `pos`, of a batch with the given calls. `pos` is
+// left pointing after the event which ended the batch.
+fn split<E: Clone, F: Fn(&E) -> Option<Marker>>(
+    events: &[E],
+    pos: &mut usize,
+    calls: &[CallShape],
+    marker_of: &F,
+) -> Option<Vec<SplitItem<E>>> {
+    // Find the marker which ends the current call, leaving `pos` just after it.
+    let find_marker = |pos: &mut usize| {
+        loop {
+            let ev = events.get(*pos)?;
+            *pos += 1;
+            match marker_of(ev) {
+                Some(m @ (Marker::ItemCompleted
+                | Marker::ItemFailed
+                | Marker::BatchInterrupted)) => return Some((ev.clone(), m)),
+                Some(Marker::BatchCompleted) => return None,
+                None => continue,
+            }
+        }
+    };
+
+    let mut items = Vec::with_capacity(calls.len());
+    for (index, call) in calls.iter().enumerate() {
+        let start = *pos;
+
+        // A nested batch which ran emits its own markers, ending with one of its
+        // own. If it was reverted instead, there's nothing of it to skip over, so
+        // fall back to that if the events don't line up otherwise.
+        let nested = match call {
+            CallShape::Batch(inner) => {
+                split(events, pos, inner, marker_of)
+                    .and_then(|nested| find_marker(pos).map(|m| (nested, m)))
+            }
+            CallShape::Plain => None,
+        };
+        let (nested, (marker_event, marker)) = match nested {
+            Some((nested, m)) => (Some(nested), m),
+            None => {
+                *pos = start;
+                (None, find_marker(pos)?)
+            }
+        };
+
+        items.push(SplitItem {
+            index,
+            events: events[start..*pos - 1].to_vec(),
+            marker,
+            marker_event,
+            nested,
+        });
+
+        // Nothing more is emitted by an interrupted batch.
+        if marker == Marker::BatchInterrupted {
+            return Some(items)
+        }
+    }
+
+    match events.get(*pos).and_then(marker_of) {
+        Some(Marker::BatchCompleted) => {
+            *pos += 1;
+            Some(items)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Events are either a marker, or some other event with a label.
+    #[derive(Debug, Clone, PartialEq)]
+    enum Ev {
+        M(Marker),
+        Other(&'static str),
+    }
+
+    fn run(events: &[Ev], calls: &[CallShape]) -> Option<Vec<SplitItem<Ev>>> {
+        let mut pos = 0;
+        split(events, &mut pos, calls, &|ev| {
+            match ev {
+                Ev::M(m) => Some(*m),
+                Ev::Other(_) => None,
+            }
+        })
+    }
+
+    fn labels(item: &SplitItem<Ev>) -> Vec<&'static str> {
+        item.events
+            .iter()
+            .map(|ev| {
+                match ev {
+                    Ev::Other(l) => *l,
+                    Ev::M(_) => "marker",
+                }
+            })
+            .collect()
+    }
+
+    use CallShape::*;
+    use Marker::*;
+
+    #[test]
+    fn splits_flat_batch() {
+        let events = vec![
+            Ev::Other("a1"),
+            Ev::Other("a2"),
+            Ev::M(ItemCompleted),
+            Ev::M(ItemFailed),
+            Ev::Other("c1"),
+            Ev::M(ItemCompleted),
+            Ev::M(BatchCompleted),
+        ];
+        let items = run(&events, &[Plain, Plain, Plain]).unwrap();
+        assert_eq!(items.len(), 3);
+        assert_eq!(labels(&items[0]), vec!["a1", "a2"]);
+        assert!(labels(&items[1]).is_empty());
+        assert_eq!(items[1].marker, ItemFailed);
+        assert_eq!(labels(&items[2]), vec!["c1"]);
+    }
+
+    #[test]
+    fn stops_at_interruption() {
+        let events = vec![Ev::Other("a1"), Ev::M(ItemCompleted), Ev::M(BatchInterrupted)];
+        let items = run(&events, &[Plain, Plain, Plain]).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].marker, BatchInterrupted);
+    }
+
+    #[test]
+    fn splits_nested_batch() {
+        let events = vec![
+            Ev::Other("n1"),
+            Ev::M(ItemCompleted),
+            Ev::Other("n2"),
+            Ev::M(ItemCompleted),
+            Ev::M(BatchCompleted),
+            Ev::M(ItemCompleted),
+            Ev::Other("b1"),
+            Ev::M(ItemCompleted),
+            Ev::M(BatchCompleted),
+        ];
+        let items = run(&events, &[Batch(vec![Plain, Plain]), Plain]).unwrap();
+        assert_eq!(items.len(), 2);
+        let nested = items[0].nested.as_ref().unwrap();
+        assert_eq!(labels(&nested[0]), vec!["n1"]);
+        assert_eq!(labels(&nested[1]), vec!["n2"]);
+        assert_eq!(labels(&items[1]), vec!["b1"]);
+    }
+
+    #[test]
+    fn reverted_nested_batch_has_no_nested_events() {
+        let events = vec![Ev::M(BatchInterrupted)];
+        let items = run(&events, &[Batch(vec![Plain]), Plain]).unwrap();
+        assert_eq!(items.len(), 1);
+        assert!(items[0].nested.is_none());
+    }
+
+    #[test]
+    fn mismatched_events_are_rejected() {
+        let events = vec![Ev::M(ItemCompleted), Ev::M(BatchCompleted)];
+        assert!(run(&events, &[Plain, Plain]).is_none());
+    }
+}
@@ -0,0 +1,1097 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use super::{
+    author::block_author,
+    batch::{
+        is_batch_call,
+        split_batch_events,
+        BatchItemEvents,
+        CallShape,
+    },
+    verification::header_hash,
+    BlockPin,
+    BlockSummary,
+};
+use crate::{
+    client::OnlineClientT,
+    error::{
+        BlockError,
+        Error,
+        ErrorContext,
+    },
+    events::{
+        EventDetails,
+        Events,
+        EventsClient,
+        Phase,
+        StaticEvent,
+    },
+    metadata::CallMetadata,
+    rpc::BlockJustification,
+    utils::to_hex,
+    Config,
+    Metadata,
+};
+use codec::{
+    Compact,
+    Decode,
+    Error as CodecError,
+};
+use derivative::Derivative;
+use futures::{
+    future,
+    FutureExt,
+};
+use sp_core::Bytes;
+use sp_runtime::{
+    generic::Era,
+    traits::Header,
+};
+use std::{
+    future::Future,
+    ops::Range,
+    sync::Arc,
+};
+
+/// A representation of a block.
+#[derive(Derivative)]
+#[derivative(Clone(bound = "Client: Clone"), Debug(bound = "Client: std::fmt::Debug"))]
+pub struct Block<T: Config, Client> {
+    header: T::Header,
+    client: Client,
+    // Keeps the block pinned if it was handed back from a chainHead subscription.
+    pin: Option<BlockPin<T::Hash>>,
+    // The metadata to decode the block with, if not that of the client.
+    metadata: Option<Metadata>,
+}
+
+impl<T, Client> Block<T, Client>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    pub(crate) fn new(header: T::Header, client: Client) -> Self {
+        Block {
+            header,
+            client,
+            pin: None,
+            metadata: None,
+        }
+    }
+
+    pub(crate) fn new_pinned(
+        header: T::Header,
+        client: Client,
+        pin: BlockPin<T::Hash>,
+    ) -> Self {
+        Block {
+            header,
+            client,
+            pin: Some(pin),
+            metadata: None,
+        }
+    }
+
+    /// Decode the events and extrinsics of the block with the metadata given,
+    /// rather than that of the client, such as for blocks from before a runtime
+    /// upgrade.
+    pub(crate) fn with_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Return the block hash.
+    pub fn hash(&self) -> T::Hash {
+        header_hash::<T>(&self.header)
+    }
+
+    /// Return the block number.
+    pub fn number(&self) -> T::BlockNumber {
+        *self.header.number()
+    }
+
+    /// Return the entire block header.
+    pub fn header(&self) -> &T::Header {
+        &self.header
+    }
+
+    /// Return the pin keeping this block available on the node, if it was handed
+    /// back from a [`super::ChainHeadSub`].
+    pub fn pin(&self) -> Option<&BlockPin<T::Hash>> {
+        self.pin.as_ref()
+    }
+
+    /// Fetch the events emitted in this block.
+    pub fn events(
+        &self,
+    ) -> impl Future<Output = Result<Events<T>, Error>> + Send + 'static {
+        self.events_with(&EventsClient::new(self.client.clone()))
+    }
+
+    /// Fetch the events emitted in this block with the [`EventsClient`] given, so
+    /// that whatever it caches is shared with the other blocks it fetches for.
+    ///
+    /// The events of a pinned block are read through the `chainHead` subscription
+    /// pinning it.
+    pub(crate) fn events_with(
+        &self,
+        client: &EventsClient<T, Client>,
+    ) -> impl Future<Output = Result<Events<T>, Error>> + Send + 'static {
+        match (self.pin.clone(), self.metadata.clone()) {
+            (Some(pin), metadata) => client.at_pinned(pin, metadata).boxed(),
+            (None, Some(metadata)) => client.at_with_metadata(self.hash(), metadata).boxed(),
+            (None, None) => client.at(Some(self.hash())).boxed(),
+        }
+    }
+
+    /// Fetch the events emitted in this block, and compute the total weight used,
+    /// fees and tips paid by the extrinsics in it. See [`BlockSummary`].
+    pub fn summary(
+        &self,
+    ) -> impl Future<Output = Result<BlockSummary, Error>> + Send + 'static {
+        let events = self.events();
+        async move { BlockSummary::from_events(&events.await?) }
+    }
+
+    /// Fetch the account that authored this block.
+    ///
+    /// The author is claimed by a BABE or Aura pre-runtime digest in the header, which
+    /// is matched up against the `Authorities` of the `Babe` or `Aura` pallet that the
+    /// block was authored under, and then mapped from its session key to its validator
+    /// with `Session::KeyOwner`. `None` is handed back if the header has no such digest
+    /// (for instance the genesis block), or the claimed author isn't an authority.
+    pub fn author(
+        &self,
+    ) -> impl Future<Output = Result<Option<T::AccountId>, Error>> + Send + 'static {
+        let client = self.client.clone();
+        let block_hash = self.hash();
+        let header = self.header.clone();
+        let pin = self.pin.clone();
+        async move { block_author::<T, Client>(&client, block_hash, &header, pin.as_ref()).await }
+    }
+
+    /// Fetch the justifications for this block, as handed back from `chain_getBlock`.
+    ///
+    /// Only some finalized blocks (for instance those that end a GRANDPA authority set)
+    /// have justifications stored for them; `None` is handed back for any others. Each
+    /// justification is tagged with the consensus engine that produced it, and left
+    /// SCALE encoded.
+    pub fn justifications(
+        &self,
+    ) -> impl Future<Output = Result<Option<Vec<BlockJustification>>, Error>> + Send + 'static
+    {
+        let client = self.client.clone();
+        let block_hash = self.hash();
+        async move {
+            match client.rpc().block(Some(block_hash)).await? {
+                Some(block) => Ok(block.justifications),
+                None => Err(BlockError::block_hash_not_found(block_hash).into()),
+            }
+        }
+    }
+
+    /// Fetch the extrinsics that make up the body of this block, along with
+    /// the events in the block so that each extrinsic can hand back the events
+    /// that it emitted.
+    ///
+    /// The body of a pinned block is read through the `chainHead` subscription
+    /// pinning it.
+    pub fn extrinsics(
+        &self,
+    ) -> impl Future<Output = Result<Extrinsics<T>, Error>> + Send + 'static {
+        let client = self.client.clone();
+        let block_hash = self.hash();
+        let pin = self.pin.clone();
+        let metadata = self.metadata.clone();
+        let events = self.events();
+        async move {
+            let (body, events) = future::join(block_body(&client, block_hash, pin), events).await;
+            let body = body.map_err(|e| e.context(ErrorContext::block(block_hash)))?;
+            let metadata = metadata.unwrap_or_else(|| client.metadata());
+            Extrinsics::new(metadata, body, events?)
+        }
+    }
+}
+
+// Fetch the extrinsics in the body of a block, through the `chainHead`
+// subscription pinning it if there is one.
+async fn block_body<T, Client>(
+    client: &Client,
+    block_hash: T::Hash,
+    pin: Option<BlockPin<T::Hash>>,
+) -> Result<Vec<Bytes>, Error>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    if let Some(pin) = pin {
+        return client
+            .rpc()
+            .chainhead_unstable_body(pin.subscription_id(), block_hash)
+            .await
+    }
+    match client.rpc().block(Some(block_hash)).await? {
+        Some(block) => Ok(block.block.extrinsics),
+        None => Err(BlockError::block_hash_not_found(block_hash).into()),
+    }
+}
+
+/// The extrinsics in a block.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub struct Extrinsics<T: Config> {
+    metadata: Metadata,
+    extrinsics: Vec<Arc<[u8]>>,
+    events: Events<T>,
+}
+
+impl<T: Config> Extrinsics<T> {
+    pub(crate) fn new(
+        metadata: Metadata,
+        extrinsics: Vec<Bytes>,
+        events: Events<T>,
+    ) -> Result<Self, Error> {
+        // Each extrinsic is handed back from the node as a SCALE encoded
+        // byte vector, so strip the compact encoded length from the front.
+        let extrinsics = extrinsics
+            .into_iter()
+            .map(|bytes| {
+                let cursor = &mut &*bytes.0;
+                <Compact<u32>>::decode(cursor)?;
+                Ok(Arc::from(*cursor))
+            })
+            .collect::<Result<_, CodecError>>()?;
+
+        Ok(Self {
+            metadata,
+            extrinsics,
+            events,
+        })
+    }
+
+    /// The hash of the block that these extrinsics are from.
+    pub fn block_hash(&self) -> T::Hash {
+        self.events.block_hash()
+    }
+
+    /// All of the events in the block that these extrinsics are from.
+    pub fn events(&self) -> &Events<T> {
+        &self.events
+    }
+
+    /// The number of extrinsics.
+    pub fn len(&self) -> usize {
+        self.extrinsics.len()
+    }
+
+    /// Are there no extrinsics in this block?
+    // Note: mainly here to satisfy clippy.
+    pub fn is_empty(&self) -> bool {
+        self.extrinsics.is_empty()
+    }
+
+    /// Iterate over the extrinsics in the block, in the order that they were
+    /// applied, using metadata to dynamically decode them as we go.
+    pub fn iter(
+        &self,
+    ) -> impl Iterator<Item = Result<ExtrinsicDetails<T>, Error>> + Send + Sync + 'static
+    {
+        let extrinsics = self.extrinsics.clone();
+        let metadata = self.metadata.clone();
+        let events = self.events.clone();
+        extrinsics.into_iter().enumerate().map(move |(index, bytes)| {
+            ExtrinsicDetails::decode_from(
+                metadata.clone(),
+                bytes,
+                index as u32,
+                events.clone(),
+            )
+            .map_err(|e| {
+                e.context(ErrorContext::Extrinsic {
+                    block_hash: to_hex(events.block_hash()),
+                    index: index as u32,
+                })
+            })
+        })
+    }
+}
+
+/// The details of a single, dynamically decoded extrinsic.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub struct ExtrinsicDetails<T: Config> {
+    index: u32,
+    version: u8,
+    // The extrinsic bytes, without the compact encoded length prefix.
+    bytes: Arc<[u8]>,
+    // Details about the signature, if the extrinsic is signed.
+    signed: Option<SignedDetails>,
+    // start of the call (ie the pallet/call index, then the call arguments).
+    call_start_idx: usize,
+    metadata: Metadata,
+    // All of the events in the block.
+    events: Events<T>,
+}
+
+#[derive(Debug, Clone)]
+struct SignedDetails {
+    address_ty: u32,
+    signature_ty: u32,
+    address: Range<usize>,
+    signature: Range<usize>,
+    signed_extensions: Vec<(String, Range<usize>)>,
+}
+
+impl<T: Config> ExtrinsicDetails<T> {
+    // Attempt to dynamically decode a single extrinsic.
+    fn decode_from(
+        metadata: Metadata,
+        bytes: Arc<[u8]>,
+        index: u32,
+        events: Events<T>,
+    ) -> Result<ExtrinsicDetails<T>, Error> {
+        let input = &mut &bytes[..];
+        let types = &metadata.runtime_metadata().types;
+
+        // The first byte contains the version, and whether or not the
+        // extrinsic is signed in the top bit.
+        let version = u8::decode(input)?;
+        let is_signed = version & 0b1000_0000 != 0;
+        let version = version & 0b0111_1111;
+        if version != 4 {
+            return Err(BlockError::UnsupportedVersion(version).into())
+        }
+
+        let skip = |input: &mut &[u8], type_id: u32| -> Result<Range<usize>, Error> {
+            let start = bytes.len() - input.len();
+            scale_decode::decode(
+                input,
+                type_id,
+                types,
+                scale_decode::visitor::IgnoreVisitor,
+            )?;
+            Ok(start..bytes.len() - input.len())
+        };
+
+        let signed = if is_signed {
+            let extrinsic_metadata = metadata.extrinsic();
+            let address_ty = extrinsic_metadata
+                .address_ty()
+                .ok_or(BlockError::MissingType("Address"))?;
+            let signature_ty = extrinsic_metadata
+                .signature_ty()
+                .ok_or(BlockError::MissingType("Signature"))?;
+
+            let address = skip(input, address_ty)?;
+            let signature = skip(input, signature_ty)?;
+            let signed_extensions = extrinsic_metadata
+                .signed_extensions()
+                .iter()
+                .map(|ext| Ok((ext.identifier().to_owned(), skip(input, ext.extra_ty())?)))
+                .collect::<Result<_, Error>>()?;
+
+            Some(SignedDetails {
+                address_ty,
+                signature_ty,
+                address,
+                signature,
+                signed_extensions,
+            })
+        } else {
+            None
+        };
+
+        let call_start_idx = bytes.len() - input.len();
+        let pallet_index = u8::decode(input)?;
+        let call_index = u8::decode(input)?;
+        let call_metadata = metadata.call(pallet_index, call_index)?;
+        tracing::debug!(
+            "Decoding Extrinsic '{}::{}'",
+            call_metadata.pallet(),
+            call_metadata.call()
+        );
+
+        // Skip over the call arguments to make sure that everything lines up.
+        for (_name, type_id) in call_metadata.fields() {
+            skip(input, *type_id)?;
+        }
+        if !input.is_empty() {
+            return Err(BlockError::LeftoverBytes(input.len()).into())
+        }
+
+        Ok(ExtrinsicDetails {
+            index,
+            version,
+            bytes,
+            signed,
+            call_start_idx,
+            metadata,
+            events,
+        })
+    }
+
+    /// The events that were emitted while this extrinsic was being applied.
+    pub fn events(&self) -> ExtrinsicEvents<T> {
+        ExtrinsicEvents::new(self.index, self.events.clone())
+    }
+
+    /// The index of the extrinsic in the block. Events emitted while
+    /// applying this extrinsic have a phase of `ApplyExtrinsic(index)`.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// The extrinsic format version (without the "signed" bit).
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Is this extrinsic signed?
+    pub fn is_signed(&self) -> bool {
+        self.signed.is_some()
+    }
+
+    /// Return _all_ of the bytes representing this extrinsic, which include, in order:
+    /// - The version byte.
+    /// - The address, signature and signed extension data (if signed).
+    /// - Pallet and call index.
+    /// - Call arguments.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The bytes of the address that signed this extrinsic, if it's signed.
+    pub fn address_bytes(&self) -> Option<&[u8]> {
+        self.signed.as_ref().map(|s| &self.bytes[s.address.clone()])
+    }
+
+    /// The bytes of the signature, if the extrinsic is signed.
+    pub fn signature_bytes(&self) -> Option<&[u8]> {
+        self.signed.as_ref().map(|s| &self.bytes[s.signature.clone()])
+    }
+
+    /// Dynamically decode the address that signed this extrinsic, if it's signed.
+    pub fn address_value(
+        &self,
+    ) -> Option<Result<scale_value::Value<scale_value::scale::TypeId>, Error>> {
+        let signed = self.signed.as_ref()?;
+        Some(self.decode_value(signed.address.clone(), signed.address_ty))
+    }
+
+    /// Dynamically decode the signature, if the extrinsic is signed.
+    pub fn signature_value(
+        &self,
+    ) -> Option<Result<scale_value::Value<scale_value::scale::TypeId>, Error>> {
+        let signed = self.signed.as_ref()?;
+        Some(self.decode_value(signed.signature.clone(), signed.signature_ty))
+    }
+
+    /// Iterate over the signed extension data attached to this extrinsic, handing
+    /// back the identifier of each signed extension and the bytes that belong to it.
+    /// This is empty if the extrinsic isn't signed.
+    pub fn signed_extensions(&self) -> impl Iterator<Item = (&str, &[u8])> + '_ {
+        self.signed
+            .iter()
+            .flat_map(|s| s.signed_extensions.iter())
+            .map(|(name, range)| (name.as_str(), &self.bytes[range.clone()]))
+    }
+
+    /// The bytes belonging to the first of the given signed extensions that's present.
+    fn signed_extension(&self, names: &[&str]) -> Option<&[u8]> {
+        self.signed_extensions()
+            .find(|(name, _)| names.contains(name))
+            .map(|(_, bytes)| bytes)
+    }
+
+    /// The era (mortality) of the extrinsic, if it's signed.
+    pub fn era(&self) -> Option<Era> {
+        let mut bytes = self.signed_extension(&["CheckMortality", "CheckEra"])?;
+        Era::decode(&mut bytes).ok()
+    }
+
+    /// The account nonce of the extrinsic, if it's signed.
+    pub fn nonce(&self) -> Option<u64> {
+        let mut bytes = self.signed_extension(&["CheckNonce"])?;
+        <Compact<u64>>::decode(&mut bytes).ok().map(|n| n.0)
+    }
+
+    /// The tip paid for the extrinsic, if it's signed.
+    pub fn tip(&self) -> Option<u128> {
+        let mut bytes = self
+            .signed_extension(&["ChargeTransactionPayment", "ChargeAssetTxPayment"])?;
+        <Compact<u128>>::decode(&mut bytes).ok().map(|n| n.0)
+    }
+
+    /// The index of the pallet that the call belongs to.
+    pub fn pallet_index(&self) -> u8 {
+        // Note: never panics; we expect these bytes to exist
+        // in order that the ExtrinsicDetails could be created.
+        self.bytes[self.call_start_idx]
+    }
+
+    /// The index of the call within its pallet.
+    pub fn call_index(&self) -> u8 {
+        // Note: never panics; we expect these bytes to exist
+        // in order that the ExtrinsicDetails could be created.
+        self.bytes[self.call_start_idx + 1]
+    }
+
+    /// The name of the pallet that the call belongs to.
+    pub fn pallet_name(&self) -> &str {
+        self.call_metadata().pallet()
+    }
+
+    /// The name of the call.
+    pub fn call_name(&self) -> &str {
+        self.call_metadata().call()
+    }
+
+    /// Fetch the metadata for this call.
+    pub fn call_metadata(&self) -> &CallMetadata {
+        self.metadata
+            .call(self.pallet_index(), self.call_index())
+            .expect("this must exist in order to have produced the ExtrinsicDetails")
+    }
+
+    /// Return the bytes representing the call; the pallet and call index
+    /// followed by the call arguments.
+    pub fn call_bytes(&self) -> &[u8] {
+        &self.bytes[self.call_start_idx..]
+    }
+
+    /// Return the bytes representing the arguments to the call.
+    pub fn field_bytes(&self) -> &[u8] {
+        &self.bytes[self.call_start_idx + 2..]
+    }
+
+    /// Decode and provide the call arguments back in the form of a
+    /// [`scale_value::Composite`] type which represents the named or
+    /// unnamed fields that were present in the call.
+    pub fn field_values(
+        &self,
+    ) -> Result<scale_value::Composite<scale_value::scale::TypeId>, Error> {
+        let bytes = &mut self.field_bytes();
+        let call_metadata = self.call_metadata();
+        let types = &self.metadata.runtime_metadata().types;
+
+        // If the first field has a name, we assume that the rest do too.
+        let is_named = call_metadata
+            .fields()
+            .get(0)
+            .map(|(n, _)| n.is_some())
+            .unwrap_or(false);
+
+        let mut values = vec![];
+        for (name, type_id) in call_metadata.fields() {
+            let value = scale_value::scale::decode_as_type(bytes, *type_id, types)?;
+            values.push((name.clone().unwrap_or_default(), value));
+        }
+
+        if is_named {
+            Ok(scale_value::Composite::Named(values))
+        } else {
+            Ok(scale_value::Composite::Unnamed(
+                values.into_iter().map(|(_, v)| v).collect(),
+            ))
+        }
+    }
+
+    /// If this is a `Utility::batch`, `Utility::batch_all` or `Utility::force_batch`
+    /// extrinsic, split the events that it emitted into those emitted by each of the
+    /// calls in the batch, using the `Utility::ItemCompleted`, `Utility::ItemFailed` and
+    /// `Utility::BatchInterrupted` events which mark the end of each call. Nested batches
+    /// are split up too.
+    ///
+    /// Returns `None` if this isn't a batch, or if the events don't line up with the calls
+    /// (for instance because the whole batch was reverted, or the runtime predates the
+    /// `ItemCompleted` event).
+    ///
+    /// **Note:** events emitted before the call is dispatched, such as the withdrawal of
+    /// the transaction fee, are attributed to the first call in the batch.
+    pub fn batch_items(&self) -> Result<Option<Vec<BatchItemEvents>>, Error> {
+        if !is_batch_call(self.pallet_name(), self.call_name()) {
+            return Ok(None)
+        }
+        let calls = match CallShape::from_batch_args(&self.field_values()?) {
+            Some(calls) => calls,
+            None => return Ok(None),
+        };
+        let events = self.events().iter().collect::<Result<Vec<_>, Error>>()?;
+        Ok(split_batch_events(&events, &calls))
+    }
+
+    /// Attempt to decode the call into a root call type (which includes the pallet
+    /// and call enum variants as well as the call arguments).
+    pub fn as_root_call<C: Decode>(&self) -> Result<C, CodecError> {
+        C::decode(&mut self.call_bytes())
+    }
+
+    fn decode_value(
+        &self,
+        range: Range<usize>,
+        type_id: u32,
+    ) -> Result<scale_value::Value<scale_value::scale::TypeId>, Error> {
+        let value = scale_value::scale::decode_as_type(
+            &mut &self.bytes[range],
+            type_id,
+            &self.metadata.runtime_metadata().types,
+        )?;
+        Ok(value)
+    }
+}
+
+/// The events associated with a single extrinsic.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub struct ExtrinsicEvents<T: Config> {
+    ext_idx: u32,
+    events: Events<T>,
+}
+
+impl<T: Config> ExtrinsicEvents<T> {
+    pub(crate) fn new(ext_idx: u32, events: Events<T>) -> Self {
+        Self { ext_idx, events }
+    }
+
+    /// The index of the extrinsic that these events belong to.
+    pub fn extrinsic_index(&self) -> u32 {
+        self.ext_idx
+    }
+
+    /// Return the hash of the block that the extrinsic is in.
+    pub fn block_hash(&self) -> T::Hash {
+        self.events.block_hash()
+    }
+
+    /// Iterate over the events emitted by this extrinsic, using metadata to
+    /// dynamically decode them as we go. If an error occurs, all subsequent
+    /// iterations return `None`.
+    pub fn iter(
+        &self,
+    ) -> impl Iterator<Item = Result<EventDetails, Error>> + Send + Sync + 'static {
+        let phase = Phase::ApplyExtrinsic(self.ext_idx);
+        self.events.iter().filter(move |ev| {
+            ev.as_ref()
+                .map(|ev| ev.phase() == phase)
+                // Keep any errors so that they're handed back.
+                .unwrap_or(true)
+        })
+    }
+
+    /// Iterate through the events emitted by this extrinsic, returning only
+    /// those which decode to the provided `Ev` type.
+    pub fn find<Ev: StaticEvent>(&self) -> impl Iterator<Item = Result<Ev, Error>> + '_ {
+        self.iter().filter_map(|ev| {
+            ev.and_then(|ev| ev.as_event::<Ev>().map_err(Into::into))
+                .transpose()
+        })
+    }
+
+    /// Return the first event emitted by this extrinsic which decodes to
+    /// the provided `Ev` type.
+    pub fn find_first<Ev: StaticEvent>(&self) -> Result<Option<Ev>, Error> {
+        self.find::<Ev>().next().transpose()
+    }
+
+    /// Find an event emitted by this extrinsic that decodes to the type
+    /// provided. Returns true if it was found.
+    pub fn has<Ev: StaticEvent>(&self) -> Result<bool, Error> {
+        Ok(self.find::<Ev>().next().transpose()?.is_some())
+    }
+
+    /// Did the extrinsic succeed? This is true if a `System.ExtrinsicSuccess`
+    /// event was emitted by it, and false otherwise (which will usually mean that
+    /// a `System.ExtrinsicFailed` event was emitted).
+    pub fn is_success(&self) -> Result<bool, Error> {
+        for ev in self.iter() {
+            let ev = ev?;
+            if ev.pallet_name() == "System" {
+                match ev.variant_name() {
+                    "ExtrinsicSuccess" => return Ok(true),
+                    "ExtrinsicFailed" => return Ok(false),
+                    _ => {}
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// Return the `System.ExtrinsicFailed` event emitted by this extrinsic,
+    /// if it failed.
+    pub fn failed_event(&self) -> Result<Option<EventDetails>, Error> {
+        for ev in self.iter() {
+            let ev = ev?;
+            if ev.pallet_name() == "System" && ev.variant_name() == "ExtrinsicFailed" {
+                return Ok(Some(ev))
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        client::test_utils::MockClient,
+        error::RpcError,
+        rpc::{
+            RawValue,
+            RpcClientT,
+            RpcFuture,
+            RpcSubscription,
+        },
+        SubstrateConfig,
+    };
+    use codec::Encode;
+    use frame_metadata::{
+        v14::{
+            ExtrinsicMetadata,
+            PalletCallMetadata,
+            PalletEventMetadata,
+            PalletMetadata,
+            RuntimeMetadataV14,
+            SignedExtensionMetadata,
+        },
+        RuntimeMetadataPrefixed,
+    };
+    use scale_info::{
+        meta_type,
+        TypeInfo,
+    };
+    use scale_value::Value;
+    use serde_json::json;
+    use sp_runtime::{
+        generic::UncheckedExtrinsic,
+        AccountId32,
+        MultiAddress,
+        MultiSignature,
+    };
+    use std::convert::TryFrom;
+
+    type Address = MultiAddress<AccountId32, u32>;
+
+    #[allow(dead_code, non_camel_case_types)]
+    #[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+    enum Call {
+        remark { remark: Vec<u8> },
+        set_thing(bool),
+    }
+
+    #[allow(dead_code)]
+    #[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+    enum SystemEvent {
+        ExtrinsicSuccess,
+        ExtrinsicFailed,
+    }
+
+    // The pallet and call enums are wrapped in an "outer" call enum.
+    #[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+    enum AllCalls {
+        Test(Call),
+    }
+
+    // The calls of the pallet before a runtime upgrade renamed them.
+    #[allow(dead_code, non_camel_case_types)]
+    #[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+    enum OldCall {
+        old_remark { remark: Vec<u8> },
+        old_thing(bool),
+    }
+
+    #[allow(dead_code)]
+    #[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+    enum OldCalls {
+        Test(OldCall),
+    }
+
+    fn metadata() -> Metadata {
+        metadata_with::<Call, AllCalls>()
+    }
+
+    // Metadata with the pallet calls and outer call enum given.
+    fn metadata_with<C: TypeInfo + 'static, A: TypeInfo + 'static>() -> Metadata {
+        let pallets = vec![
+            PalletMetadata {
+                name: "Test",
+                storage: None,
+                calls: Some(PalletCallMetadata {
+                    ty: meta_type::<C>(),
+                }),
+                event: None,
+                constants: vec![],
+                error: None,
+                index: 0,
+            },
+            PalletMetadata {
+                name: "System",
+                storage: None,
+                calls: None,
+                event: Some(PalletEventMetadata {
+                    ty: meta_type::<SystemEvent>(),
+                }),
+                constants: vec![],
+                error: None,
+                index: 1,
+            },
+        ];
+
+        let extrinsic = ExtrinsicMetadata {
+            ty: meta_type::<UncheckedExtrinsic<Address, A, MultiSignature, ()>>(),
+            version: 4,
+            signed_extensions: vec![
+                SignedExtensionMetadata {
+                    identifier: "CheckNonce",
+                    ty: meta_type::<Compact<u32>>(),
+                    additional_signed: meta_type::<()>(),
+                },
+                SignedExtensionMetadata {
+                    identifier: "ChargeTransactionPayment",
+                    ty: meta_type::<Compact<u128>>(),
+                    additional_signed: meta_type::<()>(),
+                },
+            ],
+        };
+
+        let v14 = RuntimeMetadataV14::new(pallets, extrinsic, meta_type::<()>());
+        let runtime_metadata: RuntimeMetadataPrefixed = v14.into();
+        Metadata::try_from(runtime_metadata).unwrap()
+    }
+
+    // Build some System events emitted by the given extrinsics.
+    fn events(metadata: Metadata, records: Vec<(Phase, SystemEvent)>) -> Events<SubstrateConfig> {
+        let mut bytes = Compact(records.len() as u32).encode();
+        for (phase, event) in records {
+            phase.encode_to(&mut bytes);
+            // The System pallet index, followed by the event:
+            1u8.encode_to(&mut bytes);
+            event.encode_to(&mut bytes);
+            // No topics:
+            Vec::<sp_core::H256>::new().encode_to(&mut bytes);
+        }
+        Events::new(metadata, Default::default(), bytes)
+    }
+
+    // Encode an extrinsic the way that it's handed back in a block body.
+    fn as_block_body_bytes(extrinsic: Vec<u8>) -> Bytes {
+        Bytes(extrinsic.encode())
+    }
+
+    fn unsigned(call: Call) -> Bytes {
+        let mut bytes = vec![4u8];
+        AllCalls::Test(call).encode_to(&mut bytes);
+        as_block_body_bytes(bytes)
+    }
+
+    fn signed(call: Call, nonce: u32, tip: u128) -> Bytes {
+        let mut bytes = vec![4u8 | 0b1000_0000];
+        Address::Id(AccountId32::new([1; 32])).encode_to(&mut bytes);
+        MultiSignature::Sr25519(sp_core::sr25519::Signature([2; 64])).encode_to(&mut bytes);
+        Compact(nonce).encode_to(&mut bytes);
+        Compact(tip).encode_to(&mut bytes);
+        AllCalls::Test(call).encode_to(&mut bytes);
+        as_block_body_bytes(bytes)
+    }
+
+    /// A fake node with a single block in it, and no events.
+    struct MockBlock(serde_json::Value);
+
+    impl RpcClientT for MockBlock {
+        fn request_raw<'a>(
+            &'a self,
+            method: &'a str,
+            _params: Option<Box<RawValue>>,
+        ) -> RpcFuture<'a, Box<RawValue>> {
+            let res = match method {
+                "chain_getBlock" => self.0.clone(),
+                "state_getStorage" => json!("0x00"),
+                _ => {
+                    return Box::pin(async {
+                        Err(RpcError::Call {
+                            code: RpcError::METHOD_NOT_FOUND_CODE,
+                            message: "Method not found".into(),
+                            data: None,
+                        })
+                    })
+                }
+            };
+            let res = RawValue::from_string(res.to_string()).unwrap();
+            Box::pin(async move { Ok(res) })
+        }
+
+        fn subscribe_raw<'a>(
+            &'a self,
+            _sub: &'a str,
+            _params: Option<Box<RawValue>>,
+            _unsub: &'a str,
+        ) -> RpcFuture<'a, RpcSubscription> {
+            Box::pin(async {
+                Err(RpcError::Call {
+                    code: RpcError::METHOD_NOT_FOUND_CODE,
+                    message: "Subscriptions aren't supported".into(),
+                    data: None,
+                })
+            })
+        }
+    }
+
+    // A block holding the extrinsics given, fetched with a client that has the
+    // metadata given.
+    fn block(
+        metadata: Metadata,
+        extrinsics: Vec<Bytes>,
+    ) -> Block<SubstrateConfig, MockClient> {
+        let header = <SubstrateConfig as Config>::Header::new(
+            1,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+        let extrinsics: Vec<_> = extrinsics.iter().map(|e| to_hex(&e.0)).collect();
+        let body = json!({
+            "block": { "header": header, "extrinsics": extrinsics },
+            "justifications": null,
+        });
+        Block::new(header, MockClient::new(metadata, MockBlock(body)))
+    }
+
+    #[tokio::test]
+    async fn extrinsics_are_decoded_with_the_metadata_of_the_block() {
+        let extrinsics = vec![unsigned(Call::set_thing(true))];
+
+        // The client has the metadata from after the upgrade.
+        let block = block(metadata(), extrinsics);
+        let ext = block.extrinsics().await.unwrap().iter().next().unwrap().unwrap();
+        assert_eq!(ext.call_name(), "set_thing");
+
+        // The block is from before it, when the call had another name.
+        let block = block.with_metadata(metadata_with::<OldCall, OldCalls>());
+        let ext = block.extrinsics().await.unwrap().iter().next().unwrap().unwrap();
+        assert_eq!(ext.call_name(), "old_thing");
+    }
+
+    #[test]
+    fn decode_unsigned_extrinsic() {
+        let metadata = metadata();
+        let extrinsics = Extrinsics::new(
+            metadata.clone(),
+            vec![unsigned(Call::set_thing(true))],
+            events(metadata, vec![]),
+        )
+        .unwrap();
+
+        let ext = extrinsics.iter().next().unwrap().unwrap();
+        assert_eq!(ext.index(), 0);
+        assert_eq!(ext.version(), 4);
+        assert!(!ext.is_signed());
+        assert!(ext.address_bytes().is_none());
+        assert!(ext.nonce().is_none());
+        assert_eq!(ext.pallet_name(), "Test");
+        assert_eq!(ext.call_name(), "set_thing");
+        assert_eq!(ext.as_root_call::<AllCalls>().unwrap(), AllCalls::Test(Call::set_thing(true)));
+
+        let fields: Vec<_> = ext
+            .field_values()
+            .unwrap()
+            .into_values()
+            .map(|v| v.remove_context())
+            .collect();
+        assert_eq!(fields, vec![Value::bool(true)]);
+    }
+
+    #[test]
+    fn decode_signed_extrinsic() {
+        let metadata = metadata();
+        let extrinsics = Extrinsics::new(
+            metadata.clone(),
+            vec![
+                unsigned(Call::set_thing(false)),
+                signed(Call::remark { remark: vec![1, 2, 3] }, 5, 100),
+            ],
+            events(metadata, vec![]),
+        )
+        .unwrap();
+
+        let ext = extrinsics.iter().nth(1).unwrap().unwrap();
+        assert_eq!(ext.index(), 1);
+        assert!(ext.is_signed());
+        assert_eq!(
+            ext.address_bytes().unwrap(),
+            &Address::Id(AccountId32::new([1; 32])).encode()[..]
+        );
+        assert_eq!(ext.signature_bytes().unwrap().len(), 65);
+        assert_eq!(ext.nonce(), Some(5));
+        assert_eq!(ext.tip(), Some(100));
+        assert!(ext.era().is_none());
+        assert_eq!(ext.call_name(), "remark");
+
+        let fields = ext.field_values().unwrap();
+        let names: Vec<_> = match fields {
+            scale_value::Composite::Named(fields) => {
+                fields.into_iter().map(|(name, _)| name).collect()
+            }
+            _ => panic!("expected named fields"),
+        };
+        assert_eq!(names, vec!["remark".to_string()]);
+    }
+
+    #[test]
+    fn decode_fails_on_leftover_bytes() {
+        let mut bytes = vec![4u8];
+        AllCalls::Test(Call::set_thing(true)).encode_to(&mut bytes);
+        bytes.push(123);
+
+        let metadata = metadata();
+        let extrinsics = Extrinsics::new(
+            metadata.clone(),
+            vec![as_block_body_bytes(bytes)],
+            events(metadata, vec![]),
+        )
+        .unwrap();
+
+        assert!(extrinsics.iter().next().unwrap().is_err());
+    }
+
+    #[test]
+    fn extrinsic_events_are_grouped_by_phase() {
+        let metadata = metadata();
+        let extrinsics = Extrinsics::new(
+            metadata.clone(),
+            vec![
+                unsigned(Call::set_thing(true)),
+                unsigned(Call::set_thing(false)),
+                unsigned(Call::set_thing(true)),
+            ],
+            events(
+                metadata,
+                vec![
+                    (Phase::Initialization, SystemEvent::ExtrinsicSuccess),
+                    (Phase::ApplyExtrinsic(0), SystemEvent::ExtrinsicSuccess),
+                    (Phase::ApplyExtrinsic(1), SystemEvent::ExtrinsicFailed),
+                    (Phase::Finalization, SystemEvent::ExtrinsicFailed),
+                ],
+            ),
+        )
+        .unwrap();
+
+        let exts: Vec<_> = extrinsics.iter().map(|e| e.unwrap()).collect();
+
+        let ext0 = exts[0].events();
+        assert_eq!(ext0.iter().count(), 1);
+        assert!(ext0.is_success().unwrap());
+        assert!(ext0.failed_event().unwrap().is_none());
+
+        let ext1 = exts[1].events();
+        assert_eq!(ext1.iter().count(), 1);
+        assert!(!ext1.is_success().unwrap());
+        assert_eq!(ext1.failed_event().unwrap().unwrap().index(), 2);
+
+        // No events at all for the third extrinsic:
+        let ext2 = exts[2].events();
+        assert_eq!(ext2.iter().count(), 0);
+        assert!(!ext2.is_success().unwrap());
+    }
+}
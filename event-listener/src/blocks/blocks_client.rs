@@ -0,0 +1,409 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use super::{
+    pinning::ChainHeadSub,
+    polling::{
+        heads,
+        HeadSub,
+    },
+    verification::{
+        header_hash,
+        verify_header,
+        verify_parent_hash,
+    },
+    Block,
+};
+use crate::{
+    client::OnlineClientT,
+    error::{
+        BlockError,
+        Error,
+    },
+    Config,
+};
+use derivative::Derivative;
+use futures::{
+    future::Either,
+    stream::{
+        self,
+        BoxStream,
+    },
+    Stream,
+    StreamExt,
+};
+use sp_runtime::traits::Header;
+use std::{
+    future::Future,
+    time::Duration,
+};
+
+/// A stream of blocks, handed back from [`BlocksClient::subscribe()`] and
+/// [`BlocksClient::subscribe_finalized()`].
+pub type BlockSub<T, Client> = BoxStream<'static, Result<Block<T, Client>, Error>>;
+
+/// A client for working with blocks.
+#[derive(Derivative)]
+#[derivative(Clone(bound = "Client: Clone"))]
+pub struct BlocksClient<T, Client> {
+    client: Client,
+    verify_headers: bool,
+    poll_interval: Option<Duration>,
+    _marker: std::marker::PhantomData<T>,
+}
+
+impl<T, Client> BlocksClient<T, Client> {
+    /// Create a new [`BlocksClient`].
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            verify_headers: false,
+            poll_interval: None,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Verify the integrity of the headers handed back from the node. When enabled:
+    ///
+    /// - [`BlocksClient::at()`] checks that the header returned hashes to the
+    ///   block hash that was asked for.
+    /// - [`BlocksClient::subscribe_finalized()`] checks that the parent hash of each
+    ///   finalized header is the hash of the previous finalized header, whenever
+    ///   they directly follow each other.
+    ///
+    /// Any mismatch is handed back as a [`BlockError`]. Subscriptions carry on after
+    /// reporting a mismatch, so the caller can decide whether to stop.
+    pub fn verify_headers(mut self, verify: bool) -> Self {
+        self.verify_headers = verify;
+        self
+    }
+
+    /// Follow new blocks by asking the node for the head of the chain (with
+    /// `chain_getBlockHash` or `chain_getFinalizedHead`) every `interval`, rather
+    /// than by subscribing to new heads. This is for nodes which don't support
+    /// subscriptions, such as those only reachable over HTTP or behind a load
+    /// balancer; blocks are handed back just as they would be otherwise, only
+    /// later. `None` (the default) subscribes.
+    ///
+    /// This doesn't affect [`BlocksClient::subscribe_chain_head()`], which needs
+    /// subscriptions to work at all.
+    pub fn poll_interval(mut self, interval: Option<Duration>) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+}
+
+impl<T, Client> BlocksClient<T, Client>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    /// Obtain block details given the provided block hash, or the latest block if `None`
+    /// is provided.
+    pub fn at(
+        &self,
+        block_hash: Option<T::Hash>,
+    ) -> impl Future<Output = Result<Block<T, Client>, Error>> + Send + 'static {
+        let client = self.client.clone();
+        let verify_headers = self.verify_headers;
+        async move {
+            // If block hash is not provided, get the hash
+            // for the latest block and use that.
+            let block_hash = match block_hash {
+                Some(hash) => hash,
+                None => {
+                    client
+                        .rpc()
+                        .block_hash(None)
+                        .await?
+                        .expect("didn't pass a block number; qed")
+                }
+            };
+
+            let header = match client.rpc().header(Some(block_hash)).await? {
+                Some(header) => header,
+                None => return Err(BlockError::block_hash_not_found(block_hash).into()),
+            };
+            if verify_headers {
+                verify_header::<T>(&header, block_hash)?;
+            }
+
+            Ok(Block::new(header, client))
+        }
+    }
+
+    /// Subscribe to new best blocks.
+    ///
+    /// **Note:** these blocks haven't necessarily been finalized yet; prefer
+    /// [`BlocksClient::subscribe_finalized()`] if that is important.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// use futures::StreamExt;
+    /// use event_listener::{ OnlineClient, PolkadotConfig };
+    ///
+    /// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+    ///
+    /// let mut blocks = api.blocks().subscribe().await.unwrap();
+    ///
+    /// while let Some(block) = blocks.next().await {
+    ///     let block = block.unwrap();
+    ///     println!("Block #{} ({:?})", block.number(), block.hash());
+    ///     for event in block.events().await.unwrap().iter() {
+    ///         let event = event.unwrap();
+    ///         println!("  {}::{}", event.pallet_name(), event.variant_name());
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn subscribe(
+        &self,
+    ) -> impl Future<Output = Result<BlockSub<T, Client>, Error>> + Send + 'static {
+        let client = self.client.clone();
+        let poll_interval = self.poll_interval;
+        async move {
+            let sub = heads(client.clone(), poll_interval, false).await?;
+            // Best blocks can legitimately switch between forks, so we
+            // don't expect them to chain together.
+            Ok(header_sub_into_block_sub(client, sub, false))
+        }
+    }
+
+    /// Follow the chain using the `chainHead` RPC methods. Every block handed back
+    /// is pinned on the node, and is unpinned automatically once it has been finalized
+    /// or pruned and every [`Block`] (and the [`crate::events::Events`] or
+    /// [`super::Extrinsics`] obtained from it) referring to it has been dropped.
+    ///
+    /// If `runtime_updates` is true, the node also reports runtime changes.
+    pub fn subscribe_chain_head(
+        &self,
+        runtime_updates: bool,
+    ) -> impl Future<Output = Result<ChainHeadSub<T, Client>, Error>> + Send + 'static
+    {
+        ChainHeadSub::new(self.client.clone(), runtime_updates)
+    }
+
+    /// Subscribe to finalized blocks.
+    ///
+    /// The node may report that several blocks were finalized at once, in which case
+    /// only the last of them is announced. When that happens, the blocks in between are
+    /// fetched so that every finalized block is handed back exactly once, and in order.
+    pub fn subscribe_finalized(
+        &self,
+    ) -> impl Future<Output = Result<BlockSub<T, Client>, Error>> + Send + 'static {
+        let client = self.client.clone();
+        let verify_headers = self.verify_headers;
+        let poll_interval = self.poll_interval;
+        async move {
+            let sub = heads(client.clone(), poll_interval, true).await?;
+            let sub = subscribe_to_block_headers_filling_in_gaps(client.clone(), None, sub);
+            Ok(header_sub_into_block_sub(client, sub, verify_headers))
+        }
+    }
+
+    /// Subscribe to finalized blocks, starting with the one after the block number
+    /// given. Any blocks between that one and the latest finalized block are fetched
+    /// first, so that every finalized block after the one given is handed back
+    /// exactly once, and in order.
+    pub fn subscribe_finalized_after(
+        &self,
+        block_number: u64,
+    ) -> impl Future<Output = Result<BlockSub<T, Client>, Error>> + Send + 'static {
+        let client = self.client.clone();
+        let verify_headers = self.verify_headers;
+        let poll_interval = self.poll_interval;
+        async move {
+            let sub = heads(client.clone(), poll_interval, true).await?;
+            let sub = subscribe_to_block_headers_filling_in_gaps(
+                client.clone(),
+                Some(block_number),
+                sub,
+            );
+            Ok(header_sub_into_block_sub(client, sub, verify_headers))
+        }
+    }
+
+    /// Follow the headers of new best (or finalized) blocks, subscribing or
+    /// polling according to the poll interval.
+    pub(crate) fn subscribe_heads(
+        &self,
+        finalized: bool,
+    ) -> impl Future<Output = Result<HeadSub<T>, Error>> + Send + 'static {
+        heads(self.client.clone(), self.poll_interval, finalized)
+    }
+}
+
+/// Note: This is exposed for testing but is not considered stable and may change
+/// without notice in a patch release.
+///
+/// Take a subscription that returns block headers, and if any block numbers are missed out
+/// between the block number provided and what's returned from the subscription, we fill in
+/// the gaps and get hold of all intermediate block headers. Headers numbered at or below
+/// the last one handed back are skipped, so that each block is handed back once, and in order.
+#[doc(hidden)]
+pub fn subscribe_to_block_headers_filling_in_gaps<T, Client, S, E>(
+    client: Client,
+    last_block_num: Option<u64>,
+    sub: S,
+) -> impl Stream<Item = Result<T::Header, Error>> + Send
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+    S: Stream<Item = Result<T::Header, E>> + Send,
+    E: Into<Error> + Send + 'static,
+{
+    let rpc = client.rpc().clone();
+    fill_in_gaps::<T, _, _, _, _>(last_block_num, sub, move |n| {
+        let rpc = rpc.clone();
+        async move {
+            let hash = rpc
+                .block_hash(Some(n.into()))
+                .await?
+                .ok_or(BlockError::BlockNumberNotFound(n))?;
+            let header = rpc
+                .header(Some(hash))
+                .await?
+                .ok_or_else(|| BlockError::block_hash_not_found(hash))?;
+            Ok(header)
+        }
+    })
+}
+
+// Fill in the gaps in a subscription to headers, as above, fetching the headers
+// of any blocks that were missed out with `fetch_header`.
+fn fill_in_gaps<T, S, E, F, Fut>(
+    mut last_block_num: Option<u64>,
+    sub: S,
+    fetch_header: F,
+) -> impl Stream<Item = Result<T::Header, Error>> + Send
+where
+    T: Config,
+    S: Stream<Item = Result<T::Header, E>> + Send,
+    E: Into<Error> + Send + 'static,
+    F: Fn(u64) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = Result<T::Header, Error>> + Send + 'static,
+{
+    sub.flat_map(move |s| {
+        // Get the header, or return a stream containing just the error.
+        let header = match s {
+            Ok(header) => header,
+            Err(e) => return Either::Left(stream::iter(vec![Err(e.into())])),
+        };
+
+        // We want all previous details up to, but not including this current block num.
+        let end_block_num: u64 = (*header.number()).into();
+
+        // We've already handed back this block (or one after it), so hand back nothing,
+        // and don't go backwards; the next fill-in carries on from the last block.
+        if last_block_num.map_or(false, |last| end_block_num <= last) {
+            return Either::Left(stream::iter(Vec::new()))
+        }
+
+        // This is one after the last block we returned details for last time.
+        let start_block_num = last_block_num.map(|n| n + 1).unwrap_or(end_block_num);
+
+        // Iterate over all of the previous blocks we need headers for, ignoring the current block
+        // (which we already have the header info for):
+        let previous_headers = stream::iter(start_block_num..end_block_num)
+            .map(fetch_header.clone())
+            .buffered(10);
+
+        // On the next iteration, we'll get details starting just after this end block.
+        last_block_num = Some(end_block_num);
+
+        // Return a combination of any previous headers plus the new header.
+        Either::Right(previous_headers.chain(stream::once(async { Ok(header) })))
+    })
+}
+
+/// Take a subscription that returns block headers, and return a subscription
+/// which hands back [`Block`]s instead. If `verify_chain` is true, consecutive
+/// headers are checked to make sure that they chain together.
+fn header_sub_into_block_sub<T, Client, S>(
+    client: Client,
+    sub: S,
+    verify_chain: bool,
+) -> BlockSub<T, Client>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+    S: Stream<Item = Result<T::Header, Error>> + Send + 'static,
+{
+    let mut last: Option<(u64, T::Hash)> = None;
+    sub.map(move |header| {
+        let header = header?;
+        if verify_chain {
+            let number: u64 = (*header.number()).into();
+            let prev = last.replace((number, header_hash::<T>(&header)));
+            if let Some((prev_number, prev_hash)) = prev {
+                if prev_number + 1 == number {
+                    verify_parent_hash::<T>(&header, prev_hash)?;
+                }
+            }
+        }
+        Ok(Block::new(header, client.clone()))
+    })
+    .boxed()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::SubstrateConfig;
+    use std::sync::{
+        Arc,
+        Mutex,
+    };
+
+    type SubstrateHeader = <SubstrateConfig as Config>::Header;
+
+    fn header(number: u32) -> SubstrateHeader {
+        SubstrateHeader::new(
+            number,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        )
+    }
+
+    // Run headers with the numbers given through `fill_in_gaps`, handing back the
+    // numbers of the headers that come out, and of those that were fetched.
+    fn fill(last_block_num: Option<u64>, numbers: &[u32]) -> (Vec<u32>, Vec<u64>) {
+        let fetched = Arc::new(Mutex::new(Vec::new()));
+        let sub = stream::iter(numbers.iter().map(|&n| Ok::<_, Error>(header(n))));
+        let fetches = fetched.clone();
+        let headers = fill_in_gaps::<SubstrateConfig, _, _, _, _>(last_block_num, sub, move |n| {
+            fetches.lock().unwrap().push(n);
+            async move { Ok(header(n as u32)) }
+        });
+        let handed_back = futures::executor::block_on(headers.collect::<Vec<_>>())
+            .into_iter()
+            .map(|header| *header.unwrap().number())
+            .collect();
+        let fetched = fetched.lock().unwrap().clone();
+        (handed_back, fetched)
+    }
+
+    #[test]
+    fn gaps_are_filled_in() {
+        assert_eq!(fill(None, &[5, 6, 9]), (vec![5, 6, 7, 8, 9], vec![7, 8]));
+        assert_eq!(fill(Some(2), &[5]), (vec![3, 4, 5], vec![3, 4]));
+    }
+
+    #[test]
+    fn duplicates_are_skipped() {
+        assert_eq!(fill(None, &[5, 5, 6, 6]), (vec![5, 6], vec![]));
+        assert_eq!(fill(Some(5), &[5, 6]), (vec![6], vec![]));
+    }
+
+    #[test]
+    fn regressions_are_skipped_without_going_backwards() {
+        // Nothing before 8 is fetched again once 8 has been handed back.
+        assert_eq!(fill(None, &[8, 6, 7, 10]), (vec![8, 9, 10], vec![9]));
+    }
+}
@@ -0,0 +1,53 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! This module exposes the necessary functionality for working with blocks,
+//! including their headers, extrinsics and the events emitted within them.
+
+mod activity;
+mod author;
+mod batch;
+mod block_types;
+mod blocks_client;
+mod pinning;
+mod polling;
+mod summary;
+mod verification;
+
+pub use activity::{
+    subscribe_account_activity,
+    AccountActivity,
+    Activity,
+};
+pub use author::{
+    AuthorClaim,
+    AURA_ENGINE_ID,
+    BABE_ENGINE_ID,
+};
+pub use batch::{
+    BatchItemEvents,
+    BatchItemOutcome,
+};
+pub use block_types::{
+    Block,
+    ExtrinsicDetails,
+    ExtrinsicEvents,
+    Extrinsics,
+};
+pub use blocks_client::{
+    subscribe_to_block_headers_filling_in_gaps,
+    BlockSub,
+    BlocksClient,
+};
+pub use pinning::{
+    BlockPin,
+    ChainHeadEvent,
+    ChainHeadSub,
+};
+pub use summary::BlockSummary;
+pub use verification::{
+    header_hash,
+    verify_header,
+    verify_parent,
+};
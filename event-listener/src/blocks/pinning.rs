@@ -0,0 +1,475 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Automatic management of the blocks pinned by a `chainHead_unstable_follow`
+//! subscription.
+
+use super::Block;
+use crate::{
+    client::OnlineClientT,
+    error::{
+        BlockError,
+        Error,
+    },
+    rpc::{
+        FollowEvent,
+        RpcSubscriptionId,
+    },
+    Config,
+};
+use derivative::Derivative;
+use futures::{
+    channel::mpsc,
+    future::BoxFuture,
+    stream::{
+        BoxStream,
+        FuturesUnordered,
+    },
+    FutureExt,
+    Stream,
+    StreamExt,
+};
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    sync::Arc,
+    task::{
+        Context,
+        Poll,
+    },
+};
+
+/// A handle to a block that has been pinned by a `chainHead_unstable_follow`
+/// subscription. The block stays pinned on the node for as long as any clone of
+/// this handle is alive; once the last one is dropped, the block is unpinned.
+///
+/// [`Block`]s, [`crate::events::Events`] and [`super::Extrinsics`] obtained from
+/// a [`ChainHeadSub`] each hold one of these, so the block they refer to can't be
+/// unpinned from under them.
+#[derive(Debug)]
+pub struct BlockPin<H: Copy + Send + 'static>(Arc<BlockPinInner<H>>);
+
+impl<H: Copy + Send + 'static> Clone for BlockPin<H> {
+    fn clone(&self) -> Self {
+        BlockPin(self.0.clone())
+    }
+}
+
+impl<H: Copy + Send + 'static> BlockPin<H> {
+    /// The hash of the pinned block.
+    pub fn hash(&self) -> H {
+        self.0.hash
+    }
+
+    /// The ID of the subscription which pinned the block.
+    pub fn subscription_id(&self) -> &str {
+        &self.0.subscription_id
+    }
+}
+
+#[derive(Debug)]
+struct BlockPinInner<H: Copy + Send + 'static> {
+    hash: H,
+    subscription_id: RpcSubscriptionId,
+    unpin_tx: mpsc::UnboundedSender<H>,
+}
+
+impl<H: Copy + Send + 'static> Drop for BlockPinInner<H> {
+    fn drop(&mut self) {
+        // The subscription sends the actual unpin request the next time it's
+        // polled. If it's gone already, the node has dropped every pin along
+        // with it, so there is nothing left to do.
+        let _ = self.unpin_tx.unbounded_send(self.hash);
+    }
+}
+
+/// An event handed back from a [`ChainHeadSub`].
+#[derive(Derivative)]
+#[derivative(Debug(bound = "Client: std::fmt::Debug"))]
+pub enum ChainHeadEvent<T: Config, Client> {
+    /// The latest finalized block at the time the subscription was started.
+    Initialized(Block<T, Client>),
+    /// A new, not yet finalized, block.
+    NewBlock(Block<T, Client>),
+    /// The best block changed to the one with the hash given. The block has
+    /// already been handed back in a [`ChainHeadEvent::NewBlock`] event.
+    BestBlockChanged(T::Hash),
+    /// Some blocks were finalized, and others were pruned. The finalized blocks
+    /// are handed back in ascending order, including any which weren't handed
+    /// back in a [`ChainHeadEvent::NewBlock`] event first.
+    Finalized {
+        /// The newly finalized blocks.
+        finalized: Vec<Block<T, Client>>,
+        /// The hashes of the blocks which will never be finalized. They are
+        /// unpinned as soon as they are no longer in use.
+        pruned: Vec<T::Hash>,
+    },
+    /// The node stopped the subscription, and every block pinned by it is no
+    /// longer available. A new subscription needs to be started to carry on.
+    Stop,
+}
+
+/// A subscription to `chainHead_unstable_follow`, handed back from
+/// [`super::BlocksClient::subscribe_chain_head()`].
+///
+/// Every block reported by the node is pinned until it's either finalized or
+/// pruned, and after that for as long as the [`Block`] (or anything obtained
+/// from it) is kept around. Unpin requests are sent while the subscription is
+/// being polled, so it should be polled to completion or dropped.
+pub struct ChainHeadSub<T: Config, Client> {
+    client: Client,
+    subscription_id: RpcSubscriptionId,
+    sub: BoxStream<'static, Result<FollowEvent<T::Hash>, Error>>,
+    unpin_tx: mpsc::UnboundedSender<T::Hash>,
+    unpin_rx: mpsc::UnboundedReceiver<T::Hash>,
+    unpinning: FuturesUnordered<BoxFuture<'static, ()>>,
+    fetching: Option<BoxFuture<'static, Result<ChainHeadEvent<T, Client>, Error>>>,
+    // Blocks which have been reported but not yet finalized or pruned.
+    unfinalized: HashMap<T::Hash, Block<T, Client>>,
+    done: bool,
+}
+
+// None of the fields are structurally pinned.
+impl<T: Config, Client> Unpin for ChainHeadSub<T, Client> {}
+
+impl<T: Config, Client> std::fmt::Debug for ChainHeadSub<T, Client> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChainHeadSub")
+            .field("subscription_id", &self.subscription_id)
+            .field("unfinalized", &self.unfinalized.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<T, Client> ChainHeadSub<T, Client>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    pub(crate) async fn new(client: Client, runtime_updates: bool) -> Result<Self, Error> {
+        let sub = client.rpc().chainhead_unstable_follow(runtime_updates).await?;
+        let subscription_id = sub.subscription_id().cloned().ok_or_else(|| {
+            Error::Other(
+                "chainHead_unstable_follow subscription has no ID to pin blocks with"
+                    .into(),
+            )
+        })?;
+        let (unpin_tx, unpin_rx) = mpsc::unbounded();
+
+        Ok(ChainHeadSub {
+            client,
+            subscription_id,
+            sub: sub.boxed(),
+            unpin_tx,
+            unpin_rx,
+            unpinning: FuturesUnordered::new(),
+            fetching: None,
+            unfinalized: HashMap::new(),
+            done: false,
+        })
+    }
+
+    /// The ID of the underlying `chainHead_unstable_follow` subscription.
+    pub fn subscription_id(&self) -> &str {
+        &self.subscription_id
+    }
+
+    fn pin(&self, hash: T::Hash) -> BlockPin<T::Hash> {
+        BlockPin(Arc::new(BlockPinInner {
+            hash,
+            subscription_id: self.subscription_id.clone(),
+            unpin_tx: self.unpin_tx.clone(),
+        }))
+    }
+
+    // Fetch the header of a block the node just pinned, and hand it back as a
+    // block which holds onto the pin.
+    fn fetch_block(
+        &self,
+        hash: T::Hash,
+    ) -> BoxFuture<'static, Result<Block<T, Client>, Error>> {
+        let client = self.client.clone();
+        let subscription_id = self.subscription_id.clone();
+        let pin = self.pin(hash);
+        async move {
+            let header = client
+                .rpc()
+                .chainhead_unstable_header(&subscription_id, hash)
+                .await?
+                .ok_or_else(|| BlockError::block_hash_not_found(hash))?;
+            Ok(Block::new_pinned(header, client, pin))
+        }
+        .boxed()
+    }
+
+    fn handle_event(
+        &mut self,
+        event: FollowEvent<T::Hash>,
+    ) -> Option<BoxFuture<'static, Result<ChainHeadEvent<T, Client>, Error>>> {
+        let fut = match event {
+            FollowEvent::Initialized(ev) => {
+                self.fetch_block(ev.finalized_block_hash)
+                    .map(|b| b.map(ChainHeadEvent::Initialized))
+                    .boxed()
+            }
+            FollowEvent::NewBlock(ev) => {
+                self.fetch_block(ev.block_hash)
+                    .map(|b| b.map(ChainHeadEvent::NewBlock))
+                    .boxed()
+            }
+            FollowEvent::BestBlockChanged(ev) => {
+                futures::future::ready(Ok(ChainHeadEvent::BestBlockChanged(
+                    ev.best_block_hash,
+                )))
+                .boxed()
+            }
+            FollowEvent::Finalized(ev) => {
+                // Hand over our references to the finalized blocks; dropping the
+                // pruned ones unpins them unless they're still in use elsewhere.
+                // Blocks that were never handed back as new ones (such as those
+                // reported before the subscription caught up) are fetched now.
+                let finalized: Vec<_> = ev
+                    .finalized_block_hashes
+                    .iter()
+                    .map(|hash| {
+                        match self.unfinalized.remove(hash) {
+                            Some(block) => futures::future::ready(Ok(block)).boxed(),
+                            None => self.fetch_block(*hash),
+                        }
+                    })
+                    .collect();
+                for hash in &ev.pruned_block_hashes {
+                    self.unfinalized.remove(hash);
+                }
+                let pruned = ev.pruned_block_hashes;
+                async move {
+                    Ok(ChainHeadEvent::Finalized {
+                        finalized: futures::future::try_join_all(finalized).await?,
+                        pruned,
+                    })
+                }
+                .boxed()
+            }
+            FollowEvent::Stop => {
+                // The node has released every pin already.
+                self.done = true;
+                self.unfinalized.clear();
+                futures::future::ready(Ok(ChainHeadEvent::Stop)).boxed()
+            }
+            FollowEvent::Unknown => return None,
+        };
+        Some(fut)
+    }
+
+    // Send unpin requests for any blocks that are no longer in use, and
+    // make progress on those already sent.
+    fn poll_unpinning(&mut self, cx: &mut Context<'_>) {
+        while let Poll::Ready(Some(hash)) = self.unpin_rx.poll_next_unpin(cx) {
+            if self.done {
+                continue
+            }
+            let client = self.client.clone();
+            let subscription_id = self.subscription_id.clone();
+            self.unpinning.push(
+                async move {
+                    if let Err(e) = client
+                        .rpc()
+                        .chainhead_unstable_unpin(&subscription_id, hash)
+                        .await
+                    {
+                        tracing::warn!("Failed to unpin block {:?}: {}", hash, e);
+                    }
+                }
+                .boxed(),
+            );
+        }
+        while let Poll::Ready(Some(())) = self.unpinning.poll_next_unpin(cx) {}
+    }
+}
+
+impl<T, Client> Stream for ChainHeadSub<T, Client>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    type Item = Result<ChainHeadEvent<T, Client>, Error>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        this.poll_unpinning(cx);
+
+        loop {
+            if let Some(fetching) = &mut this.fetching {
+                let res = futures::ready!(fetching.poll_unpin(cx));
+                this.fetching = None;
+                // Keep hold of new blocks until the node tells us what became of them.
+                if let Ok(ChainHeadEvent::NewBlock(block)) = &res {
+                    this.unfinalized.insert(block.hash(), block.clone());
+                }
+                return Poll::Ready(Some(res))
+            }
+
+            if this.done {
+                return Poll::Ready(None)
+            }
+
+            match futures::ready!(this.sub.poll_next_unpin(cx)) {
+                Some(Ok(event)) => this.fetching = this.handle_event(event),
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => {
+                    this.done = true;
+                    return Poll::Ready(None)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        client::test_utils::MockClient,
+        error::RpcError,
+        events::test_utils::metadata,
+        rpc::{
+            RawValue,
+            RpcClientT,
+            RpcFuture,
+            RpcSubscription,
+        },
+        utils::to_hex,
+        SubstrateConfig,
+    };
+    use codec::Encode;
+    use scale_info::TypeInfo;
+    use serde_json::{
+        json,
+        Value,
+    };
+    use sp_core::H256;
+    use sp_runtime::traits::Header;
+
+    type SubstrateHeader = <SubstrateConfig as Config>::Header;
+
+    #[allow(dead_code)]
+    #[derive(TypeInfo)]
+    enum Event {
+        Unused,
+    }
+
+    /// A fake node which follows a chain of the blocks given, sending the follow
+    /// events given.
+    struct MockNode {
+        headers: Vec<SubstrateHeader>,
+        events: Vec<Value>,
+    }
+
+    impl RpcClientT for MockNode {
+        fn request_raw<'a>(
+            &'a self,
+            method: &'a str,
+            params: Option<Box<RawValue>>,
+        ) -> RpcFuture<'a, Box<RawValue>> {
+            let params: Vec<Value> =
+                serde_json::from_str(params.unwrap().get()).unwrap();
+            let res = match method {
+                "chainHead_unstable_header" => {
+                    let hash: H256 = serde_json::from_value(params[1].clone()).unwrap();
+                    let header = self.headers.iter().find(|h| h.hash() == hash);
+                    json!(header.map(|h| to_hex(h.encode())))
+                }
+                "chainHead_unstable_unpin" => Value::Null,
+                _ => {
+                    return Box::pin(async {
+                        Err(RpcError::Call {
+                            code: RpcError::METHOD_NOT_FOUND_CODE,
+                            message: "Method not found".into(),
+                            data: None,
+                        })
+                    })
+                }
+            };
+            let res = RawValue::from_string(res.to_string()).unwrap();
+            Box::pin(async move { Ok(res) })
+        }
+
+        fn subscribe_raw<'a>(
+            &'a self,
+            _sub: &'a str,
+            _params: Option<Box<RawValue>>,
+            _unsub: &'a str,
+        ) -> RpcFuture<'a, RpcSubscription> {
+            let events: Vec<_> = self
+                .events
+                .iter()
+                .map(|event| Ok::<_, RpcError>(RawValue::from_string(event.to_string()).unwrap()))
+                .collect();
+            Box::pin(async move {
+                Ok(RpcSubscription {
+                    stream: futures::stream::iter(events).boxed(),
+                    id: Some("follow".to_owned()),
+                })
+            })
+        }
+    }
+
+    // A chain of headers, each the parent of the next.
+    fn headers(n: u32) -> Vec<SubstrateHeader> {
+        let mut headers: Vec<SubstrateHeader> = Vec::new();
+        for number in 0..n {
+            let parent_hash = headers.last().map(|h| h.hash()).unwrap_or_default();
+            headers.push(SubstrateHeader::new(
+                number,
+                Default::default(),
+                Default::default(),
+                parent_hash,
+                Default::default(),
+            ));
+        }
+        headers
+    }
+
+    #[tokio::test]
+    async fn blocks_finalized_without_being_announced_are_handed_back() {
+        let headers = headers(3);
+        let hashes: Vec<_> = headers.iter().map(|h| h.hash()).collect();
+        // The third block is finalized without being announced first.
+        let events = vec![
+            json!({ "event": "initialized", "finalizedBlockHash": hashes[0] }),
+            json!({
+                "event": "newBlock",
+                "blockHash": hashes[1],
+                "parentBlockHash": hashes[0],
+            }),
+            json!({
+                "event": "finalized",
+                "finalizedBlockHashes": [hashes[1], hashes[2]],
+                "prunedBlockHashes": [],
+            }),
+        ];
+        let client = MockClient::new(metadata::<Event>(), MockNode { headers, events });
+
+        let events: Vec<_> = ChainHeadSub::new(client, false)
+            .await
+            .unwrap()
+            .map(|event| event.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(events.len(), 3);
+        match &events[2] {
+            ChainHeadEvent::Finalized { finalized, pruned } => {
+                assert!(finalized.iter().all(|b| b.pin().is_some()));
+                let finalized: Vec<_> = finalized.iter().map(|b| b.hash()).collect();
+                assert_eq!(finalized, hashes[1..]);
+                assert!(pruned.is_empty());
+            }
+            _ => panic!("expected the blocks to be finalized"),
+        }
+    }
+}
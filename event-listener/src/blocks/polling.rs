@@ -0,0 +1,117 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use crate::{
+    client::OnlineClientT,
+    error::{
+        BlockError,
+        Error,
+    },
+    Config,
+};
+use futures::{
+    stream::{
+        self,
+        BoxStream,
+    },
+    StreamExt,
+};
+use std::time::Duration;
+
+/// A stream of the headers of new best (or finalized) blocks, handed back from
+/// [`heads()`].
+pub(crate) type HeadSub<T> = BoxStream<'static, Result<<T as Config>::Header, Error>>;
+
+/// Follow the best (or finalized) head of the chain, by subscribing to it or, if
+/// a poll interval is given, by asking the node for it every so often. Polling
+/// works with nodes (or load balancers in front of them) which don't support
+/// subscriptions, such as those only reachable over HTTP.
+pub(crate) async fn heads<T, Client>(
+    client: Client,
+    poll_interval: Option<Duration>,
+    finalized: bool,
+) -> Result<HeadSub<T>, Error>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let sub = match poll_interval {
+        Some(interval) => poll_heads(client, interval, finalized),
+        None if finalized => client.rpc().subscribe_finalized_blocks().await?.boxed(),
+        None => client.rpc().subscribe_blocks().await?.boxed(),
+    };
+    Ok(sub)
+}
+
+// Ask the node for the head of the chain every `interval`, handing back its
+// header whenever it has changed. Like `chain_subscribeFinalizedHeads`, several
+// blocks may be finalized between polls, and only the last is handed back.
+fn poll_heads<T, Client>(client: Client, interval: Duration, finalized: bool) -> HeadSub<T>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let state = PollState {
+        client,
+        last: None,
+        first: true,
+    };
+    stream::unfold(state, move |mut state| {
+        async move {
+            loop {
+                if !state.first {
+                    futures_timer::Delay::new(interval).await;
+                }
+                state.first = false;
+                match next_head::<T, _>(&state.client, finalized, state.last).await {
+                    Ok(Some((hash, header))) => {
+                        state.last = Some(hash);
+                        return Some((Ok(header), state))
+                    }
+                    Ok(None) => continue,
+                    // Carry on polling after an error, just as subscriptions carry
+                    // on after handing one back.
+                    Err(e) => return Some((Err(e), state)),
+                }
+            }
+        }
+    })
+    .boxed()
+}
+
+struct PollState<T: Config, Client> {
+    client: Client,
+    // The hash of the head last handed back.
+    last: Option<T::Hash>,
+    // Whether we've yet to poll at all, and so shouldn't wait to.
+    first: bool,
+}
+
+// The hash and header of the head of the chain, if it's not the one given.
+async fn next_head<T, Client>(
+    client: &Client,
+    finalized: bool,
+    last: Option<T::Hash>,
+) -> Result<Option<(T::Hash, T::Header)>, Error>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let rpc = client.rpc();
+    let hash = if finalized {
+        rpc.finalized_head().await?
+    } else {
+        rpc.block_hash(None)
+            .await?
+            .expect("didn't pass a block number; qed")
+    };
+    if last == Some(hash) {
+        return Ok(None)
+    }
+    let header = rpc
+        .header(Some(hash))
+        .await?
+        .ok_or_else(|| BlockError::block_hash_not_found(hash))?;
+    Ok(Some((hash, header)))
+}
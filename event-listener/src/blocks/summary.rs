@@ -0,0 +1,123 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use crate::{
+    error::Error,
+    events::{
+        EventDetails,
+        Events,
+    },
+    utils::{
+        composite_field,
+        value_as_u128,
+    },
+    Config,
+};
+use scale_value::{
+    scale::TypeId,
+    Composite,
+    Value,
+    ValueDef,
+};
+
+/// Totals for a single block, computed from the `System::ExtrinsicSuccess`,
+/// `System::ExtrinsicFailed` and `TransactionPayment::TransactionFeePaid`
+/// events emitted in it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockSummary {
+    /// How many extrinsics succeeded.
+    pub successful: u32,
+    /// How many extrinsics failed.
+    pub failed: u32,
+    /// The total computation time used by the extrinsics, as reported in
+    /// their dispatch info. On runtimes with one dimensional weights, this is
+    /// the weight itself.
+    pub ref_time: u64,
+    /// The total proof size used by the extrinsics, as reported in their
+    /// dispatch info. This is always 0 on runtimes with one dimensional weights.
+    pub proof_size: u64,
+    /// The total of the fees paid (including tips).
+    pub fees: u128,
+    /// The total of the tips paid.
+    pub tips: u128,
+}
+
+impl BlockSummary {
+    /// Compute the totals for the block that the given events were emitted in.
+    ///
+    /// Only extrinsics are counted, so the weight used by `on_initialize` and
+    /// `on_finalize` hooks isn't included.
+    pub fn from_events<T: Config>(events: &Events<T>) -> Result<BlockSummary, Error> {
+        let mut summary = BlockSummary::default();
+        for ev in events.iter() {
+            summary.add_event(&ev?)?;
+        }
+        Ok(summary)
+    }
+
+    /// The number of extrinsics in the block, counting both successful and
+    /// failed ones.
+    pub fn extrinsics(&self) -> u32 {
+        self.successful + self.failed
+    }
+
+    fn add_event(&mut self, ev: &EventDetails) -> Result<(), Error> {
+        match (ev.pallet_name(), ev.variant_name()) {
+            ("System", "ExtrinsicSuccess") => {
+                self.successful += 1;
+                self.add_dispatch_info(&ev.field_values()?);
+            }
+            ("System", "ExtrinsicFailed") => {
+                self.failed += 1;
+                self.add_dispatch_info(&ev.field_values()?);
+            }
+            ("TransactionPayment", "TransactionFeePaid") => {
+                let fields = ev.field_values()?;
+                let fee = composite_field(&fields, "actual_fee", 1).and_then(value_as_u128);
+                let tip = composite_field(&fields, "tip", 2).and_then(value_as_u128);
+                self.fees = self.fees.saturating_add(fee.unwrap_or(0));
+                self.tips = self.tips.saturating_add(tip.unwrap_or(0));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn add_dispatch_info(&mut self, fields: &Composite<TypeId>) {
+        // `dispatch_info` is the last field of both events.
+        let info = match fields {
+            Composite::Named(_) => composite_field(fields, "dispatch_info", 0),
+            Composite::Unnamed(vals) => vals.last(),
+        };
+        let info = match info {
+            Some(Value {
+                value: ValueDef::Composite(info),
+                ..
+            }) => info,
+            _ => return,
+        };
+        let (ref_time, proof_size) = match composite_field(info, "weight", 0) {
+            Some(weight) => weight_parts(weight),
+            None => return,
+        };
+        self.ref_time = self.ref_time.saturating_add(ref_time);
+        self.proof_size = self.proof_size.saturating_add(proof_size);
+    }
+}
+
+// A weight is either a plain number, or (on newer runtimes) a struct with
+// a `ref_time` and `proof_size`.
+fn weight_parts(weight: &Value<TypeId>) -> (u64, u64) {
+    let to_u64 = |v: Option<&Value<TypeId>>| {
+        v.and_then(value_as_u128)
+            .map(|n| n.min(u64::MAX as u128) as u64)
+            .unwrap_or(0)
+    };
+    match &weight.value {
+        ValueDef::Composite(c @ Composite::Named(_)) => {
+            (to_u64(composite_field(c, "ref_time", 0)), to_u64(composite_field(c, "proof_size", 1)))
+        }
+        _ => (to_u64(Some(weight)), 0),
+    }
+}
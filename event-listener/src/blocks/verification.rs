@@ -0,0 +1,140 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Checks that the headers handed back from a node are internally consistent.
+
+use crate::{
+    error::BlockError,
+    utils::to_hex,
+    Config,
+};
+use sp_runtime::traits::{
+    Hash,
+    Header,
+};
+
+/// Hash the given header using [`Config::Hashing`]. Substrate headers carry their
+/// own hashing type too; this makes sure that the one from the config is used,
+/// so that chains which hash with (for instance) Keccak-256 are handled properly.
+pub fn header_hash<T: Config>(header: &T::Header) -> T::Hash {
+    T::Hashing::hash_of(header)
+}
+
+/// Check that the given header hashes (using [`Config::Hashing`]) to the block hash
+/// that we expect it to.
+pub fn verify_header<T: Config>(
+    header: &T::Header,
+    expected_hash: T::Hash,
+) -> Result<(), BlockError> {
+    let actual_hash = header_hash::<T>(header);
+    if actual_hash != expected_hash {
+        return Err(BlockError::HeaderHashMismatch {
+            expected: to_hex(expected_hash),
+            actual: to_hex(actual_hash),
+        })
+    }
+    Ok(())
+}
+
+/// Check that `child` is a direct descendant of `parent`; its number is one more
+/// than the parent number and its parent hash is the hash of the parent header.
+pub fn verify_parent<T: Config>(
+    parent: &T::Header,
+    child: &T::Header,
+) -> Result<(), BlockError> {
+    let parent_number: u64 = (*parent.number()).into();
+    let child_number: u64 = (*child.number()).into();
+    if parent_number + 1 != child_number {
+        return Err(BlockError::NotConsecutive {
+            parent: parent_number,
+            child: child_number,
+        })
+    }
+    verify_parent_hash::<T>(child, header_hash::<T>(parent))
+}
+
+/// Check that the parent hash of the given header is the one we expect.
+pub(crate) fn verify_parent_hash<T: Config>(
+    child: &T::Header,
+    expected_parent_hash: T::Hash,
+) -> Result<(), BlockError> {
+    if *child.parent_hash() != expected_parent_hash {
+        return Err(BlockError::ParentHashMismatch {
+            number: (*child.number()).into(),
+            expected: to_hex(expected_parent_hash),
+            actual: to_hex(child.parent_hash()),
+        })
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::SubstrateConfig;
+
+    type SubstrateHeader = <SubstrateConfig as Config>::Header;
+
+    fn header(number: u32, parent_hash: sp_core::H256) -> SubstrateHeader {
+        SubstrateHeader::new(
+            number,
+            Default::default(),
+            Default::default(),
+            parent_hash,
+            Default::default(),
+        )
+    }
+
+    #[test]
+    fn header_hash_is_verified() {
+        let h = header(1, Default::default());
+        assert!(verify_header::<SubstrateConfig>(&h, h.hash()).is_ok());
+        assert!(matches!(
+            verify_header::<SubstrateConfig>(&h, Default::default()),
+            Err(BlockError::HeaderHashMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn parent_hashes_must_chain() {
+        let parent = header(1, Default::default());
+        let child = header(2, parent.hash());
+        let imposter = header(2, sp_core::H256::repeat_byte(1));
+        let grandchild = header(3, child.hash());
+
+        assert!(verify_parent::<SubstrateConfig>(&parent, &child).is_ok());
+        assert!(matches!(
+            verify_parent::<SubstrateConfig>(&parent, &imposter),
+            Err(BlockError::ParentHashMismatch { number: 2, .. })
+        ));
+        assert!(matches!(
+            verify_parent::<SubstrateConfig>(&parent, &grandchild),
+            Err(BlockError::NotConsecutive {
+                parent: 1,
+                child: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn keccak_configs_hash_headers_with_keccak() {
+        use crate::config::KeccakConfig;
+        use sp_runtime::traits::{
+            BlakeTwo256,
+            Keccak256,
+        };
+
+        let h = <KeccakConfig as Config>::Header::new(
+            1,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+        let keccak_hash = Keccak256::hash_of(&h);
+        assert_eq!(header_hash::<KeccakConfig>(&h), keccak_hash);
+        assert!(verify_header::<KeccakConfig>(&h, keccak_hash).is_ok());
+        assert!(verify_header::<KeccakConfig>(&h, BlakeTwo256::hash_of(&h)).is_err());
+    }
+}
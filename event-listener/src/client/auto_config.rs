@@ -0,0 +1,503 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Work out which of the shipped [`Config`]s (if any) suits the chain that a
+//! node is running.
+
+use super::{
+    validation::property,
+    ChainExpectations,
+};
+use crate::{
+    error::Error,
+    metadata::Metadata,
+    rpc::{
+        rpc_params,
+        Rpc,
+        RpcClientT,
+        SystemProperties,
+    },
+    utils::to_hex,
+    Config,
+    SubstrateConfig,
+};
+use codec::{
+    Compact,
+    Decode,
+    Encode,
+};
+use frame_metadata::v14::StorageEntryType;
+use futures::future;
+use scale_info::{
+    form::PortableForm,
+    Type,
+    TypeDef,
+    TypeDefPrimitive,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use sp_core::hashing::{
+    blake2_256,
+    keccak_256,
+};
+
+/// Connect to a node and work out what its chain looks like, to help pick
+/// (and then validate) a [`Config`] for it.
+///
+/// # Example
+///
+/// ```no_run
+/// use event_listener::client::AutoConfig;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let profile = AutoConfig::from_url("wss://rpc.polkadot.io:443").await.unwrap();
+/// println!("{}", serde_json::to_string_pretty(&profile).unwrap());
+/// println!("Suggested config: {:?}", profile.suggested_config);
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct AutoConfig;
+
+impl AutoConfig {
+    /// Profile the chain of the node at the URL given.
+    #[cfg(feature = "jsonrpsee")]
+    pub async fn from_url(url: impl AsRef<str>) -> Result<ChainProfile, Error> {
+        let client = super::online_client::jsonrpsee_helpers::ws_client(url.as_ref())
+            .await
+            .map_err(crate::error::RpcError::from)?;
+        AutoConfig::from_rpc_client(client).await
+    }
+
+    /// Profile the chain of the node that the [`RpcClientT`] given is connected to.
+    pub async fn from_rpc_client<R: RpcClientT>(
+        rpc_client: R,
+    ) -> Result<ChainProfile, Error> {
+        // Every shipped config has 32 byte hashes, so this is enough to talk to the node.
+        let rpc = Rpc::<SubstrateConfig>::new(rpc_client);
+
+        let (genesis_hash, runtime_version, metadata, chain, properties) = future::join5(
+            rpc.genesis_hash(),
+            rpc.runtime_version(None),
+            rpc.metadata(),
+            rpc.system_chain(),
+            rpc.system_properties(),
+        )
+        .await;
+        let genesis_hash = genesis_hash?;
+        let runtime_version = runtime_version?;
+
+        // The header is fetched as JSON, since we don't know its types yet.
+        let genesis_header: serde_json::Value = rpc
+            .request("chain_getHeader", rpc_params![genesis_hash])
+            .await?;
+        let hashing = match header_bytes(&genesis_header) {
+            Some(bytes) if blake2_256(&bytes) == genesis_hash.0 => Hashing::Blake2_256,
+            Some(bytes) if keccak_256(&bytes) == genesis_hash.0 => Hashing::Keccak256,
+            _ => Hashing::Unknown,
+        };
+
+        let spec_name = runtime_version
+            .other
+            .get("specName")
+            .and_then(|name| name.as_str())
+            .map(ToOwned::to_owned);
+
+        Ok(ChainProfile::new(
+            chain?,
+            to_hex(genesis_hash),
+            spec_name,
+            runtime_version.spec_version,
+            &properties?,
+            &metadata?,
+            hashing,
+        ))
+    }
+}
+
+/// What a chain looks like, as far as picking a [`Config`] for it goes. This can be
+/// serialized, so that it can be logged or persisted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainProfile {
+    /// The name of the chain.
+    pub chain: String,
+    /// The genesis hash of the chain, as a hex string.
+    pub genesis_hash: String,
+    /// The name of the runtime, if the node told us.
+    pub spec_name: Option<String>,
+    /// The version of the runtime.
+    pub spec_version: u32,
+    /// The SS58 address format of the chain.
+    pub ss58_format: Option<u16>,
+    /// The symbol of the native token.
+    pub token_symbol: Option<String>,
+    /// The number of decimals of the native token.
+    pub token_decimals: Option<u8>,
+    /// The number of bits in a block number.
+    pub block_number_bits: Option<u32>,
+    /// The number of bytes in an account ID.
+    pub account_id_bytes: Option<u32>,
+    /// The address type of extrinsics.
+    pub address: AddressKind,
+    /// The algorithm used to hash blocks.
+    pub hashing: Hashing,
+    /// The path of the signature type of extrinsics.
+    pub signature: Option<String>,
+    /// The shipped config which matches the chain, if there is one.
+    pub suggested_config: Option<SuggestedConfig>,
+    /// Anything about the chain that the shipped configs don't cater for.
+    pub warnings: Vec<String>,
+}
+
+/// The address type of the extrinsics of a chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum AddressKind {
+    /// A `MultiAddress`, with account indices of the given number of bits, or
+    /// `None` if the chain has no account indices.
+    MultiAddress {
+        /// The number of bits in an account index.
+        account_index_bits: Option<u32>,
+    },
+    /// Accounts are used as addresses directly.
+    AccountId,
+    /// Some other address type, given by its path.
+    Other {
+        /// The path of the address type.
+        path: String,
+    },
+    /// The metadata doesn't say.
+    Unknown,
+}
+
+/// The algorithm used to hash the blocks of a chain, worked out by hashing the
+/// genesis header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Hashing {
+    /// Blake2-256, as Substrate uses by default.
+    Blake2_256,
+    /// Keccak-256, as Ethereum uses.
+    Keccak256,
+    /// Neither of the above.
+    Unknown,
+}
+
+/// One of the [`Config`]s that this crate ships with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SuggestedConfig {
+    /// [`crate::config::SubstrateConfig`].
+    SubstrateConfig,
+    /// [`crate::config::PolkadotConfig`], which is also used for Kusama, Westend
+    /// and Asset Hub.
+    PolkadotConfig,
+    /// [`crate::config::SubstrateU64Config`].
+    SubstrateU64Config,
+    /// [`crate::config::KeccakConfig`].
+    KeccakConfig,
+    /// [`crate::config::EvmConfig`].
+    EvmConfig,
+}
+
+impl ChainProfile {
+    fn new(
+        chain: String,
+        genesis_hash: String,
+        spec_name: Option<String>,
+        spec_version: u32,
+        properties: &SystemProperties,
+        metadata: &Metadata,
+        hashing: Hashing,
+    ) -> ChainProfile {
+        let types = &metadata.runtime_metadata().types;
+        let address_ty = metadata
+            .extrinsic()
+            .address_ty()
+            .and_then(|id| types.resolve(id));
+        let account_ty = address_ty.and_then(|ty| {
+            let param = ty.type_params().iter().find(|p| p.name() == "AccountId");
+            match param {
+                Some(param) => param.ty().and_then(|ty| types.resolve(ty.id())),
+                None => Some(ty),
+            }
+        });
+
+        let address = match address_ty {
+            Some(ty) if path_of(ty).ends_with("MultiAddress") => {
+                AddressKind::MultiAddress {
+                    account_index_bits: ty
+                        .type_params()
+                        .iter()
+                        .find(|p| p.name() == "AccountIndex")
+                        .and_then(|p| p.ty())
+                        .and_then(|ty| types.resolve(ty.id()))
+                        .and_then(primitive_bits),
+                }
+            }
+            Some(ty) if account_ty == Some(ty) => AddressKind::AccountId,
+            Some(ty) => AddressKind::Other { path: path_of(ty) },
+            None => AddressKind::Unknown,
+        };
+
+        let mut profile = ChainProfile {
+            chain,
+            genesis_hash,
+            spec_name,
+            spec_version,
+            ss58_format: property(properties, "ss58Format")
+                .and_then(|v| v.as_u64())
+                .map(|v| v as u16),
+            token_symbol: property(properties, "tokenSymbol")
+                .and_then(|v| v.as_str())
+                .map(ToOwned::to_owned),
+            token_decimals: property(properties, "tokenDecimals")
+                .and_then(|v| v.as_u64())
+                .map(|v| v as u8),
+            block_number_bits: block_number_bits(metadata),
+            account_id_bytes: account_ty.and_then(|ty| byte_len(types, ty)),
+            address,
+            hashing,
+            signature: metadata
+                .extrinsic()
+                .signature_ty()
+                .and_then(|id| types.resolve(id))
+                .map(path_of),
+            suggested_config: None,
+            warnings: Vec::new(),
+        };
+        profile.suggest_config();
+        profile
+    }
+
+    // Pick the shipped config which matches the chain, noting down anything
+    // that none of them cater for.
+    fn suggest_config(&mut self) {
+        let mut warnings = Vec::new();
+        if self.hashing == Hashing::Unknown {
+            warnings.push("Blocks are hashed with an unknown algorithm".to_owned());
+        }
+        let block_number_bits = self.block_number_bits.unwrap_or(32);
+        if block_number_bits != 32 && block_number_bits != 64 {
+            warnings.push(format!("Block numbers have {} bits", block_number_bits));
+        }
+
+        let suggested = match (&self.address, self.account_id_bytes) {
+            (AddressKind::AccountId, Some(20)) => Some(SuggestedConfig::EvmConfig),
+            (
+                AddressKind::MultiAddress {
+                    account_index_bits,
+                },
+                Some(32),
+            ) => {
+                match (self.hashing, block_number_bits, account_index_bits) {
+                    (Hashing::Keccak256, 32, Some(32)) => {
+                        Some(SuggestedConfig::KeccakConfig)
+                    }
+                    (Hashing::Blake2_256, 64, Some(32)) => {
+                        Some(SuggestedConfig::SubstrateU64Config)
+                    }
+                    (Hashing::Blake2_256, 32, Some(32)) => {
+                        Some(SuggestedConfig::SubstrateConfig)
+                    }
+                    (Hashing::Blake2_256, 32, None) => {
+                        Some(SuggestedConfig::PolkadotConfig)
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        if suggested.is_none() {
+            warnings.push(format!(
+                "None of the shipped configs have {:?} addresses with {} byte accounts, \
+                 {} bit block numbers and {:?} hashing; a custom config is needed",
+                self.address,
+                self.account_id_bytes.unwrap_or_default(),
+                block_number_bits,
+                self.hashing,
+            ));
+        }
+
+        self.suggested_config = suggested;
+        self.warnings = warnings;
+    }
+
+    /// Build [`ChainExpectations`] which check that a node is running the chain
+    /// which was profiled, with the same SS58 format and token.
+    pub fn expectations<T: Config>(&self) -> Result<ChainExpectations<T>, Error> {
+        let genesis_hash = hex::decode(self.genesis_hash.trim_start_matches("0x"))
+            .map_err(|e| Error::Other(format!("Invalid genesis hash: {}", e)))?;
+        let mut expectations = ChainExpectations::new()
+            .genesis_hash(T::Hash::decode(&mut &*genesis_hash)?);
+        if let Some(ss58_format) = self.ss58_format {
+            expectations = expectations.ss58_format(ss58_format);
+        }
+        if let (Some(symbol), Some(decimals)) = (&self.token_symbol, self.token_decimals) {
+            expectations = expectations.token(symbol.clone(), decimals);
+        }
+        Ok(expectations)
+    }
+}
+
+fn path_of(ty: &Type<PortableForm>) -> String {
+    ty.path().segments().join("::")
+}
+
+// The number of bits in a primitive (possibly wrapped) unsigned integer type.
+fn primitive_bits(ty: &Type<PortableForm>) -> Option<u32> {
+    match ty.type_def() {
+        TypeDef::Primitive(TypeDefPrimitive::U8) => Some(8),
+        TypeDef::Primitive(TypeDefPrimitive::U16) => Some(16),
+        TypeDef::Primitive(TypeDefPrimitive::U32) => Some(32),
+        TypeDef::Primitive(TypeDefPrimitive::U64) => Some(64),
+        TypeDef::Primitive(TypeDefPrimitive::U128) => Some(128),
+        _ => None,
+    }
+}
+
+// The number of bytes in a fixed size byte array (possibly wrapped in a struct).
+fn byte_len(types: &scale_info::PortableRegistry, ty: &Type<PortableForm>) -> Option<u32> {
+    match ty.type_def() {
+        TypeDef::Array(a) => Some(a.len()),
+        TypeDef::Composite(c) if c.fields().len() == 1 => {
+            byte_len(types, types.resolve(c.fields()[0].ty().id())?)
+        }
+        _ => None,
+    }
+}
+
+// The type of the `System::Number` storage entry.
+fn block_number_bits(metadata: &Metadata) -> Option<u32> {
+    let runtime_metadata = metadata.runtime_metadata();
+    let system = runtime_metadata
+        .pallets
+        .iter()
+        .find(|p| p.name == "System")?;
+    let entry = system
+        .storage
+        .as_ref()?
+        .entries
+        .iter()
+        .find(|e| e.name == "Number")?;
+    match &entry.ty {
+        StorageEntryType::Plain(ty) => {
+            primitive_bits(runtime_metadata.types.resolve(ty.id())?)
+        }
+        _ => None,
+    }
+}
+
+// Rebuild the SCALE encoded bytes of a header handed back as JSON, so that it can
+// be hashed without knowing its types up front.
+fn header_bytes(header: &serde_json::Value) -> Option<Vec<u8>> {
+    let hex_field = |v: &serde_json::Value| {
+        hex::decode(v.as_str()?.trim_start_matches("0x")).ok()
+    };
+    let number = u64::from_str_radix(
+        header.get("number")?.as_str()?.trim_start_matches("0x"),
+        16,
+    )
+    .ok()?;
+    let logs = header.get("digest")?.get("logs")?.as_array()?;
+
+    let mut bytes = hex_field(header.get("parentHash")?)?;
+    Compact(number).encode_to(&mut bytes);
+    bytes.extend(hex_field(header.get("stateRoot")?)?);
+    bytes.extend(hex_field(header.get("extrinsicsRoot")?)?);
+    Compact(logs.len() as u32).encode_to(&mut bytes);
+    for log in logs {
+        bytes.extend(hex_field(log)?);
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sp_runtime::traits::{
+        BlakeTwo256,
+        Hash,
+        Header as _,
+    };
+
+    fn profile(
+        address: AddressKind,
+        account_id_bytes: u32,
+        block_number_bits: u32,
+        hashing: Hashing,
+    ) -> ChainProfile {
+        let mut profile = ChainProfile {
+            chain: "Test".to_owned(),
+            genesis_hash: format!("0x{}", "00".repeat(32)),
+            spec_name: None,
+            spec_version: 1,
+            ss58_format: Some(42),
+            token_symbol: Some("UNIT".to_owned()),
+            token_decimals: Some(12),
+            block_number_bits: Some(block_number_bits),
+            account_id_bytes: Some(account_id_bytes),
+            address,
+            hashing,
+            signature: None,
+            suggested_config: None,
+            warnings: Vec::new(),
+        };
+        profile.suggest_config();
+        profile
+    }
+
+    #[test]
+    fn configs_are_suggested() {
+        let multi = |bits| {
+            AddressKind::MultiAddress {
+                account_index_bits: bits,
+            }
+        };
+        let cases = [
+            (multi(Some(32)), 32, 32, Hashing::Blake2_256, Some(SuggestedConfig::SubstrateConfig)),
+            (multi(None), 32, 32, Hashing::Blake2_256, Some(SuggestedConfig::PolkadotConfig)),
+            (multi(Some(32)), 32, 64, Hashing::Blake2_256, Some(SuggestedConfig::SubstrateU64Config)),
+            (multi(Some(32)), 32, 32, Hashing::Keccak256, Some(SuggestedConfig::KeccakConfig)),
+            (AddressKind::AccountId, 20, 32, Hashing::Blake2_256, Some(SuggestedConfig::EvmConfig)),
+            (AddressKind::AccountId, 32, 32, Hashing::Blake2_256, None),
+        ];
+        for (address, account_bytes, block_bits, hashing, expected) in cases {
+            let profile = profile(address, account_bytes, block_bits, hashing);
+            assert_eq!(profile.suggested_config, expected, "{:?}", profile);
+            assert_eq!(profile.warnings.is_empty(), expected.is_some());
+        }
+    }
+
+    #[test]
+    fn json_headers_are_hashed_like_scale_headers() {
+        let header = <SubstrateConfig as Config>::Header::new(
+            0,
+            BlakeTwo256::hash(b"extrinsics"),
+            BlakeTwo256::hash(b"state"),
+            Default::default(),
+            Default::default(),
+        );
+        let json = serde_json::to_value(&header).unwrap();
+        assert_eq!(header_bytes(&json).unwrap(), header.encode());
+    }
+
+    #[test]
+    fn expectations_are_built_from_profiles() {
+        let profile = profile(
+            AddressKind::MultiAddress {
+                account_index_bits: Some(32),
+            },
+            32,
+            32,
+            Hashing::Blake2_256,
+        );
+        assert!(profile.expectations::<SubstrateConfig>().is_ok());
+
+        let json = serde_json::to_string(&profile).unwrap();
+        assert_eq!(serde_json::from_str::<ChainProfile>(&json).unwrap(), profile);
+    }
+}
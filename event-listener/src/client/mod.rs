@@ -8,9 +8,20 @@
 //! require network access. The [`OnlineClient`] requires network
 //! access.
 
+mod auto_config;
 mod offline_client;
 mod online_client;
+mod validation;
+#[cfg(feature = "jsonrpsee")]
+mod ws_config;
 
+pub use auto_config::{
+    AddressKind,
+    AutoConfig,
+    ChainProfile,
+    Hashing,
+    SuggestedConfig,
+};
 pub use offline_client::{
     OfflineClient,
     OfflineClientT,
@@ -19,3 +30,68 @@ pub use online_client::{
     OnlineClient,
     OnlineClientT,
 };
+pub use validation::ChainExpectations;
+#[cfg(feature = "jsonrpsee")]
+pub use ws_config::{
+    WsClientConfig,
+    DEFAULT_MAX_MESSAGE_SIZE,
+    DEFAULT_MAX_NOTIFS_PER_SUBSCRIPTION,
+};
+
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use super::{
+        OfflineClient,
+        OfflineClientT,
+        OnlineClientT,
+    };
+    use crate::{
+        rpc::{
+            Rpc,
+            RpcClientT,
+            RuntimeVersion,
+        },
+        Metadata,
+        SubstrateConfig,
+    };
+
+    /// A client with the metadata given, talking to a fake node.
+    #[derive(Clone)]
+    pub struct MockClient {
+        offline: OfflineClient<SubstrateConfig>,
+        rpc: Rpc<SubstrateConfig>,
+    }
+
+    impl MockClient {
+        /// Create a client with the metadata given, talking to the fake node given.
+        pub fn new(metadata: Metadata, node: impl RpcClientT) -> Self {
+            let runtime_version = RuntimeVersion {
+                spec_version: 2,
+                transaction_version: 1,
+                other: Default::default(),
+            };
+            MockClient {
+                offline: OfflineClient::new(Default::default(), runtime_version, metadata),
+                rpc: Rpc::new(node),
+            }
+        }
+    }
+
+    impl OfflineClientT<SubstrateConfig> for MockClient {
+        fn metadata(&self) -> Metadata {
+            self.offline.metadata()
+        }
+        fn runtime_version(&self) -> RuntimeVersion {
+            self.offline.runtime_version()
+        }
+        fn genesis_hash(&self) -> sp_core::H256 {
+            self.offline.genesis_hash()
+        }
+    }
+
+    impl OnlineClientT<SubstrateConfig> for MockClient {
+        fn rpc(&self) -> &Rpc<SubstrateConfig> {
+            &self.rpc
+        }
+    }
+}
@@ -3,8 +3,12 @@
 // see LICENSE for license details.
 
 use crate::{
+    blocks::BlocksClient,
+    constants::ConstantsClient,
     events::EventsClient,
     rpc::RuntimeVersion,
+    storage::StorageClient,
+    tx::TxClient,
     Config,
     Metadata,
 };
@@ -18,11 +22,47 @@ pub trait OfflineClientT<T: Config>: Clone + Send + Sync + 'static {
     fn metadata(&self) -> Metadata;
     /// Return the provided [`RuntimeVersion`].
     fn runtime_version(&self) -> RuntimeVersion;
+    /// Return the genesis hash of the chain.
+    fn genesis_hash(&self) -> T::Hash;
+
+    /// Hand the [`Metadata`] to the function given, without cloning it. Prefer
+    /// this to [`OfflineClientT::metadata()`] in code that runs for every block
+    /// or event.
+    fn with_metadata<R>(&self, f: impl FnOnce(&Metadata) -> R) -> R {
+        f(&self.metadata())
+    }
+
+    /// Return the spec version of the runtime that the [`Metadata`] is from.
+    /// This changes whenever the metadata does, so it can be checked to tell
+    /// whether a copy of the metadata is still current.
+    fn spec_version(&self) -> u32 {
+        self.runtime_version().spec_version
+    }
+
+    /// Work with constants.
+    fn constants(&self) -> ConstantsClient<T, Self> {
+        ConstantsClient::new(self.clone())
+    }
 
     /// Work with events.
     fn events(&self) -> EventsClient<T, Self> {
         EventsClient::new(self.clone())
     }
+
+    /// Work with blocks.
+    fn blocks(&self) -> BlocksClient<T, Self> {
+        BlocksClient::new(self.clone())
+    }
+
+    /// Work with storage.
+    fn storage(&self) -> StorageClient<T, Self> {
+        StorageClient::new(self.clone())
+    }
+
+    /// Work with transactions.
+    fn tx(&self) -> TxClient<T, Self> {
+        TxClient::new(self.clone())
+    }
 }
 
 /// A client that is capable of performing offline-only operations.
@@ -42,6 +82,26 @@ struct Inner<T: Config> {
 }
 
 impl<T: Config> OfflineClient<T> {
+    /// Construct a new [`OfflineClient`], providing
+    /// the necessary runtime and compile-time arguments.
+    pub fn new(
+        genesis_hash: T::Hash,
+        runtime_version: RuntimeVersion,
+        metadata: Metadata,
+    ) -> OfflineClient<T> {
+        OfflineClient {
+            inner: Arc::new(Inner {
+                genesis_hash,
+                runtime_version,
+                metadata,
+            }),
+        }
+    }
+
+    /// Return the genesis hash.
+    pub fn genesis_hash(&self) -> T::Hash {
+        self.inner.genesis_hash
+    }
 
     /// Return the runtime version.
     pub fn runtime_version(&self) -> RuntimeVersion {
@@ -52,16 +112,24 @@ impl<T: Config> OfflineClient<T> {
     pub fn metadata(&self) -> Metadata {
         self.inner.metadata.clone()
     }
-
 }
 
 impl<T: Config> OfflineClientT<T> for OfflineClient<T> {
+    fn genesis_hash(&self) -> T::Hash {
+        self.genesis_hash()
+    }
     fn runtime_version(&self) -> RuntimeVersion {
         self.runtime_version()
     }
     fn metadata(&self) -> Metadata {
         self.metadata()
     }
+    fn with_metadata<R>(&self, f: impl FnOnce(&Metadata) -> R) -> R {
+        f(&self.inner.metadata)
+    }
+    fn spec_version(&self) -> u32 {
+        self.inner.runtime_version.spec_version
+    }
 }
 
 // For ergonomics; cloning a client is deliberately fairly cheap (via Arc),
@@ -2,21 +2,43 @@
 // This file is dual-licensed as Apache-2.0 or GPL-3.0.
 // see LICENSE for license details.
 
-use super::OfflineClientT;
+#[cfg(feature = "jsonrpsee")]
+use super::WsClientConfig;
+use super::{
+    ChainExpectations,
+    OfflineClient,
+    OfflineClientT,
+};
 use crate::{
+    blocks::BlocksClient,
+    config::Ss58Format,
+    constants::ConstantsClient,
     error::Error,
     events::EventsClient,
+    listener::EventListenerBuilder,
     rpc::{
         Rpc,
         RpcClientT,
         RuntimeVersion,
     },
+    runtime_api::RuntimeApiClient,
+    storage::StorageClient,
+    tx::TxClient,
     Config,
     Metadata,
 };
 use derivative::Derivative;
 use futures::future;
-use std::sync::Arc;
+use std::{
+    future::Future,
+    sync::{
+        atomic::{
+            AtomicU32,
+            Ordering,
+        },
+        Arc,
+    },
+};
 use parking_lot::RwLock;
 
 /// A trait representing a client that can perform
@@ -24,6 +46,11 @@ use parking_lot::RwLock;
 pub trait OnlineClientT<T: Config>: OfflineClientT<T> {
     /// Return an RPC client that can be used to communicate with a node.
     fn rpc(&self) -> &Rpc<T>;
+
+    /// Work with runtime APIs.
+    fn runtime_api(&self) -> RuntimeApiClient<T, Self> {
+        RuntimeApiClient::new(self.clone())
+    }
 }
 
 /// A client that can be used to perform API calls (that is, either those
@@ -32,6 +59,10 @@ pub trait OnlineClientT<T: Config>: OfflineClientT<T> {
 #[derivative(Clone(bound = ""))]
 pub struct OnlineClient<T: Config> {
     inner: Arc<RwLock<Inner>>,
+    // The spec version of the runtime in `inner`, so that it can be checked
+    // without taking the lock.
+    spec_version: Arc<AtomicU32>,
+    genesis_hash: T::Hash,
     rpc: Rpc<T>,
 }
 
@@ -46,6 +77,7 @@ impl<T: Config> std::fmt::Debug for OnlineClient<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Client")
             .field("rpc", &"RpcClient")
+            .field("genesis_hash", &self.genesis_hash)
             .field("inner", &self.inner)
             .finish()
     }
@@ -62,33 +94,75 @@ impl<T: Config> OnlineClient<T> {
     }
 
     /// Construct a new [`OnlineClient`], providing a URL to connect to.
+    ///
+    /// The connection is driven by the Tokio runtime that this is called from,
+    /// unless the `runtime-agnostic` feature is enabled, in which case it's
+    /// driven by a runtime of our own in the background, and the client can be
+    /// used from any executor.
     pub async fn from_url(url: impl AsRef<str>) -> Result<OnlineClient<T>, Error> {
         let client = jsonrpsee_helpers::ws_client(url.as_ref())
             .await
-            .map_err(|e| crate::error::RpcError(e.to_string()))?;
+            .map_err(crate::error::RpcError::from)?;
+        OnlineClient::from_rpc_client(client).await
+    }
+
+    /// Construct a new [`OnlineClient`], providing a URL to connect to, and how to
+    /// set up the WebSocket connection to it. See [`WsClientConfig`].
+    pub async fn from_url_with_config(
+        url: impl AsRef<str>,
+        config: &WsClientConfig,
+    ) -> Result<OnlineClient<T>, Error> {
+        let client = config
+            .connect(url.as_ref())
+            .await
+            .map_err(crate::error::RpcError::from)?;
         OnlineClient::from_rpc_client(client).await
     }
+
+    /// Construct a new [`OnlineClient`], providing a URL to connect to, and check
+    /// that the node is running the chain we expect it to be. See
+    /// [`OnlineClient::validate()`].
+    pub async fn from_url_validated(
+        url: impl AsRef<str>,
+        expectations: &ChainExpectations<T>,
+    ) -> Result<OnlineClient<T>, Error>
+    where
+        T::AccountId: scale_info::TypeInfo + 'static,
+        T::Address: scale_info::TypeInfo + 'static,
+    {
+        let client = OnlineClient::from_url(url).await?;
+        client.validate(expectations).await?;
+        Ok(client)
+    }
 }
 
 impl<T: Config> OnlineClient<T> {
     /// Construct a new [`OnlineClient`] by providing an underlying [`RpcClientT`]
     /// implementation to drive the connection.
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(name = "connect", skip_all, err)
+    )]
     pub async fn from_rpc_client<R: RpcClientT>(
         rpc_client: R,
     ) -> Result<OnlineClient<T>, Error> {
         let rpc = Rpc::new(rpc_client);
 
-        let ( runtime_version, metadata) = future::join(
+        let (genesis_hash, runtime_version, metadata) = future::join3(
+            rpc.genesis_hash(),
             rpc.runtime_version(None),
             rpc.metadata(),
         )
         .await;
 
+        let runtime_version = runtime_version?;
         Ok(OnlineClient {
+            spec_version: Arc::new(AtomicU32::new(runtime_version.spec_version)),
             inner: Arc::new(RwLock::new(Inner {
-                runtime_version: runtime_version?,
+                runtime_version,
                 metadata: metadata?,
             })),
+            genesis_hash: genesis_hash?,
             rpc,
         })
     }
@@ -99,16 +173,132 @@ impl<T: Config> OnlineClient<T> {
         inner.metadata.clone()
     }
 
+    /// Hand the [`Metadata`] used in this client to the function given, without
+    /// cloning it. Prefer this to [`OnlineClient::metadata()`] in code that runs
+    /// for every block or event, and keep the function short, since the
+    /// metadata can't be updated while it runs.
+    pub fn with_metadata<R>(&self, f: impl FnOnce(&Metadata) -> R) -> R {
+        let inner = self.inner.read();
+        f(&inner.metadata)
+    }
+
     /// Return the runtime version.
     pub fn runtime_version(&self) -> RuntimeVersion {
         let inner = self.inner.read();
         inner.runtime_version.clone()
     }
 
+    /// Return the spec version of the runtime. Unlike
+    /// [`OnlineClient::runtime_version()`], this doesn't take a lock, so it's
+    /// cheap enough to check for every block.
+    pub fn spec_version(&self) -> u32 {
+        self.spec_version.load(Ordering::Acquire)
+    }
+
+    /// Replace the runtime version and metadata of this client (and of every
+    /// clone of it), such as after a runtime upgrade. Code which holds on to a
+    /// copy of the metadata can tell that it's out of date by the spec version
+    /// changing.
+    pub fn update_runtime(&self, runtime_version: RuntimeVersion, metadata: Metadata) {
+        let mut inner = self.inner.write();
+        // Stored while the lock is held, so that anyone who sees the new spec
+        // version and then reads the metadata gets the new metadata.
+        self.spec_version
+            .store(runtime_version.spec_version, Ordering::Release);
+        inner.runtime_version = runtime_version;
+        inner.metadata = metadata;
+    }
+
+    /// Return the genesis hash.
+    pub fn genesis_hash(&self) -> T::Hash {
+        self.genesis_hash
+    }
+
+    /// Check that the node is running the chain we expect it to be, and that the
+    /// types in the [`Config`] match those of its runtime. Otherwise, events and
+    /// blocks may fail to decode, or decode to garbage, later on.
+    pub fn validate(
+        &self,
+        expectations: &ChainExpectations<T>,
+    ) -> impl Future<Output = Result<(), Error>> + Send + 'static
+    where
+        T::AccountId: scale_info::TypeInfo + 'static,
+        T::Address: scale_info::TypeInfo + 'static,
+    {
+        let client = self.clone();
+        let expectations = expectations.clone();
+        async move {
+            let properties = if expectations.needs_properties() {
+                Some(client.rpc().system_properties().await?)
+            } else {
+                None
+            };
+            expectations.check(
+                client.genesis_hash(),
+                &client.metadata(),
+                properties.as_ref(),
+            )
+        }
+    }
+
+    /// Fetch the SS58 prefix of the chain from its properties, to format accounts
+    /// as addresses with.
+    pub fn ss58_format(
+        &self,
+    ) -> impl Future<Output = Result<Ss58Format<T>, Error>> + Send + 'static {
+        let client = self.clone();
+        async move {
+            let properties = client.rpc().system_properties().await?;
+            Ok(Ss58Format::from_properties(&properties))
+        }
+    }
+
+    /// Return an [`OfflineClient`] with a snapshot of the genesis hash, runtime
+    /// version and metadata that this client currently has. This can be handed
+    /// to code which only needs to work offline, such as for signing extrinsics.
+    pub fn offline(&self) -> OfflineClient<T> {
+        let inner = self.inner.read();
+        OfflineClient::new(
+            self.genesis_hash,
+            inner.runtime_version.clone(),
+            inner.metadata.clone(),
+        )
+    }
+
+    /// Work with constants.
+    pub fn constants(&self) -> ConstantsClient<T, Self> {
+        <Self as OfflineClientT<T>>::constants(self)
+    }
+
     /// Work with events.
     pub fn events(&self) -> EventsClient<T, Self> {
         <Self as OfflineClientT<T>>::events(self)
     }
+
+    /// Listen for events, by registering handlers for them.
+    pub fn listener(&self) -> EventListenerBuilder<T, Self> {
+        EventListenerBuilder::new(self.clone())
+    }
+
+    /// Work with blocks.
+    pub fn blocks(&self) -> BlocksClient<T, Self> {
+        <Self as OfflineClientT<T>>::blocks(self)
+    }
+
+    /// Work with storage.
+    pub fn storage(&self) -> StorageClient<T, Self> {
+        <Self as OfflineClientT<T>>::storage(self)
+    }
+
+    /// Work with transactions.
+    pub fn tx(&self) -> TxClient<T, Self> {
+        <Self as OfflineClientT<T>>::tx(self)
+    }
+
+    /// Work with runtime APIs.
+    pub fn runtime_api(&self) -> RuntimeApiClient<T, Self> {
+        <Self as OnlineClientT<T>>::runtime_api(self)
+    }
 }
 
 
@@ -119,6 +309,15 @@ impl<T: Config> OfflineClientT<T> for OnlineClient<T> {
     fn runtime_version(&self) -> RuntimeVersion {
         self.runtime_version()
     }
+    fn genesis_hash(&self) -> T::Hash {
+        self.genesis_hash()
+    }
+    fn with_metadata<R>(&self, f: impl FnOnce(&Metadata) -> R) -> R {
+        self.with_metadata(f)
+    }
+    fn spec_version(&self) -> u32 {
+        self.spec_version()
+    }
 }
 
 impl<T: Config> OnlineClientT<T> for OnlineClient<T> {
@@ -129,12 +328,10 @@ impl<T: Config> OnlineClientT<T> for OnlineClient<T> {
 
 // helpers for a jsonrpsee specific OnlineClient.
 #[cfg(feature = "jsonrpsee")]
-mod jsonrpsee_helpers {
+pub(crate) mod jsonrpsee_helpers {
     pub use jsonrpsee::{
         client_transport::ws::{
             InvalidUri,
-            Receiver,
-            Sender,
             Uri,
             WsTransportClientBuilder,
         },
@@ -149,19 +346,6 @@ mod jsonrpsee_helpers {
 
     /// Build WS RPC client from URL
     pub async fn ws_client(url: &str) -> Result<Client, Error> {
-        let (sender, receiver) = ws_transport(url).await?;
-        Ok(ClientBuilder::default()
-            .max_notifs_per_subscription(4096)
-            .build_with_tokio(sender, receiver))
-    }
-
-    async fn ws_transport(url: &str) -> Result<(Sender, Receiver), Error> {
-        let url: Uri = url
-            .parse()
-            .map_err(|e: InvalidUri| Error::Transport(e.into()))?;
-        WsTransportClientBuilder::default()
-            .build(url)
-            .await
-            .map_err(|e| Error::Transport(e.into()))
+        crate::client::WsClientConfig::default().connect(url).await
     }
 }
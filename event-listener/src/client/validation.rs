@@ -0,0 +1,429 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Check that the node a client is connected to is running the chain that
+//! the client's [`Config`] is for.
+
+use crate::{
+    error::{
+        ChainMismatchError,
+        Error,
+    },
+    rpc::SystemProperties,
+    utils::to_hex,
+    Config,
+    Metadata,
+};
+use derivative::Derivative;
+use scale_info::{
+    form::PortableForm,
+    PortableRegistry,
+    Type,
+    TypeDef,
+    TypeInfo,
+};
+use std::collections::HashSet;
+
+/// What to expect of the chain that a node is running. Hand this to
+/// [`super::OnlineClient::validate()`] to check a node against it, or to
+/// [`super::OnlineClient::from_url_validated()`] to check it when connecting.
+///
+/// By default, only the `AccountId` and `Address` types of the [`Config`] are
+/// checked against those in the runtime metadata.
+///
+/// # Example
+///
+/// ```no_run
+/// use event_listener::{ client::ChainExpectations, OnlineClient, PolkadotConfig };
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let expectations = ChainExpectations::<PolkadotConfig>::new()
+///     .ss58_format(0)
+///     .token("DOT", 10);
+///
+/// let api = OnlineClient::<PolkadotConfig>::from_url_validated(
+///     "wss://rpc.polkadot.io:443",
+///     &expectations,
+/// )
+/// .await
+/// .unwrap();
+/// # }
+/// ```
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub struct ChainExpectations<T: Config> {
+    genesis_hash: Option<T::Hash>,
+    ss58_format: Option<u16>,
+    token_symbol: Option<String>,
+    token_decimals: Option<u8>,
+    check_types: bool,
+}
+
+impl<T: Config> Default for ChainExpectations<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Config> ChainExpectations<T> {
+    /// Expect nothing but that the types of the [`Config`] match the runtime.
+    pub fn new() -> Self {
+        Self {
+            genesis_hash: None,
+            ss58_format: None,
+            token_symbol: None,
+            token_decimals: None,
+            check_types: true,
+        }
+    }
+
+    /// Expect the chain to have the genesis hash given.
+    pub fn genesis_hash(mut self, genesis_hash: T::Hash) -> Self {
+        self.genesis_hash = Some(genesis_hash);
+        self
+    }
+
+    /// Expect the chain to have the SS58 address format given.
+    pub fn ss58_format(mut self, ss58_format: u16) -> Self {
+        self.ss58_format = Some(ss58_format);
+        self
+    }
+
+    /// Expect the native token of the chain to have the symbol and number of
+    /// decimals given.
+    pub fn token(mut self, symbol: impl Into<String>, decimals: u8) -> Self {
+        self.token_symbol = Some(symbol.into());
+        self.token_decimals = Some(decimals);
+        self
+    }
+
+    /// Whether to check the `AccountId` and `Address` types of the [`Config`]
+    /// against those in the runtime metadata. This is on by default.
+    pub fn check_types(mut self, check_types: bool) -> Self {
+        self.check_types = check_types;
+        self
+    }
+
+    /// Do we need to fetch the chain properties to check these expectations?
+    pub(crate) fn needs_properties(&self) -> bool {
+        self.ss58_format.is_some()
+            || self.token_symbol.is_some()
+            || self.token_decimals.is_some()
+    }
+
+    /// Check the details of a chain against these expectations.
+    pub(crate) fn check(
+        &self,
+        genesis_hash: T::Hash,
+        metadata: &Metadata,
+        properties: Option<&SystemProperties>,
+    ) -> Result<(), Error>
+    where
+        T::AccountId: TypeInfo + 'static,
+        T::Address: TypeInfo + 'static,
+    {
+        if let Some(expected) = self.genesis_hash {
+            if expected != genesis_hash {
+                return Err(ChainMismatchError::GenesisHash {
+                    expected: to_hex(expected),
+                    actual: to_hex(genesis_hash),
+                }
+                .into())
+            }
+        }
+
+        if let Some(properties) = properties {
+            check_property(
+                "ss58Format",
+                self.ss58_format,
+                property_u64(properties, "ss58Format"),
+            )?;
+            check_property(
+                "tokenSymbol",
+                self.token_symbol.as_deref(),
+                property_str(properties, "tokenSymbol").as_deref(),
+            )?;
+            check_property(
+                "tokenDecimals",
+                self.token_decimals.map(u64::from),
+                property_u64(properties, "tokenDecimals"),
+            )?;
+        }
+
+        if self.check_types {
+            check_types::<T>(metadata)?;
+        }
+        Ok(())
+    }
+}
+
+fn check_property<V: PartialEq + ToString>(
+    name: &'static str,
+    expected: Option<V>,
+    actual: Option<V>,
+) -> Result<(), ChainMismatchError> {
+    match expected {
+        Some(expected) if actual.as_ref() != Some(&expected) => {
+            Err(ChainMismatchError::Property {
+                name,
+                expected: expected.to_string(),
+                actual: actual.map(|a| a.to_string()),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+// Chains with multiple tokens give arrays of symbols and decimals; the first
+// entry is the native token.
+pub(crate) fn property<'a>(
+    properties: &'a SystemProperties,
+    name: &str,
+) -> Option<&'a serde_json::Value> {
+    match properties.get(name)? {
+        serde_json::Value::Array(values) => values.first(),
+        value => Some(value),
+    }
+}
+
+fn property_u64(properties: &SystemProperties, name: &str) -> Option<u64> {
+    property(properties, name)?.as_u64()
+}
+
+fn property_str(properties: &SystemProperties, name: &str) -> Option<String> {
+    property(properties, name)?.as_str().map(ToOwned::to_owned)
+}
+
+// The address type of an extrinsic is usually `MultiAddress<AccountId, AccountIndex>`,
+// in which case the `AccountId` is a type parameter of it. Otherwise, accounts
+// are used as addresses directly.
+fn check_types<T>(metadata: &Metadata) -> Result<(), ChainMismatchError>
+where
+    T: Config,
+    T::AccountId: TypeInfo + 'static,
+    T::Address: TypeInfo + 'static,
+{
+    let runtime_types = &metadata.runtime_metadata().types;
+    let address_ty = match metadata.extrinsic().address_ty() {
+        Some(ty) => ty,
+        // Without an address type, there is nothing to compare against.
+        None => return Ok(()),
+    };
+    let account_ty = runtime_types
+        .resolve(address_ty)
+        .and_then(|ty| {
+            ty.type_params()
+                .iter()
+                .find(|p| p.name() == "AccountId")
+                .and_then(|p| p.ty())
+        })
+        .map(|ty| ty.id())
+        .unwrap_or(address_ty);
+
+    let mut registry = scale_info::Registry::new();
+    let config_account_ty = registry
+        .register_type(&scale_info::meta_type::<T::AccountId>())
+        .id();
+    let config_address_ty = registry
+        .register_type(&scale_info::meta_type::<T::Address>())
+        .id();
+    let config_types: PortableRegistry = registry.into();
+
+    let checks = [
+        ("AccountId", account_ty, config_account_ty),
+        ("Address", address_ty, config_address_ty),
+    ];
+    for (name, runtime_ty, config_ty) in checks {
+        if !same_shape(
+            runtime_types,
+            runtime_ty,
+            &config_types,
+            config_ty,
+            &mut HashSet::new(),
+        ) {
+            return Err(ChainMismatchError::Type {
+                name,
+                config_type: type_name(&config_types, config_ty),
+                runtime_type: type_name(runtime_types, runtime_ty),
+            })
+        }
+    }
+    Ok(())
+}
+
+fn type_name(types: &PortableRegistry, id: u32) -> String {
+    match types.resolve(id) {
+        Some(ty) if !ty.path().segments().is_empty() => ty.path().segments().join("::"),
+        Some(ty) => format!("{:?}", ty.type_def()),
+        None => format!("unknown type {}", id),
+    }
+}
+
+/// Do the two types given encode in the same way? Names of types and fields
+/// are ignored, but the names and indexes of enum variants have to match.
+pub(crate) fn same_shape(
+    a_types: &PortableRegistry,
+    a: u32,
+    b_types: &PortableRegistry,
+    b: u32,
+    seen: &mut HashSet<(u32, u32)>,
+) -> bool {
+    // Recursive types are assumed to match if everything else about them does.
+    if !seen.insert((a, b)) {
+        return true
+    }
+    let (a_ty, b_ty): (&Type<PortableForm>, &Type<PortableForm>) =
+        match (a_types.resolve(a), b_types.resolve(b)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return false,
+        };
+    let mut same = |a: u32, b: u32| same_shape(a_types, a, b_types, b, seen);
+
+    match (a_ty.type_def(), b_ty.type_def()) {
+        (TypeDef::Composite(a), TypeDef::Composite(b)) => {
+            a.fields().len() == b.fields().len()
+                && a.fields()
+                    .iter()
+                    .zip(b.fields())
+                    .all(|(a, b)| same(a.ty().id(), b.ty().id()))
+        }
+        (TypeDef::Variant(a), TypeDef::Variant(b)) => {
+            a.variants().len() == b.variants().len()
+                && a.variants().iter().zip(b.variants()).all(|(a, b)| {
+                    a.name() == b.name()
+                        && a.index() == b.index()
+                        && a.fields().len() == b.fields().len()
+                        && a.fields()
+                            .iter()
+                            .zip(b.fields())
+                            .all(|(a, b)| same(a.ty().id(), b.ty().id()))
+                })
+        }
+        (TypeDef::Sequence(a), TypeDef::Sequence(b)) => {
+            same(a.type_param().id(), b.type_param().id())
+        }
+        (TypeDef::Array(a), TypeDef::Array(b)) => {
+            a.len() == b.len() && same(a.type_param().id(), b.type_param().id())
+        }
+        (TypeDef::Tuple(a), TypeDef::Tuple(b)) => {
+            a.fields().len() == b.fields().len()
+                && a.fields()
+                    .iter()
+                    .zip(b.fields())
+                    .all(|(a, b)| same(a.id(), b.id()))
+        }
+        (TypeDef::Primitive(a), TypeDef::Primitive(b)) => a == b,
+        (TypeDef::Compact(a), TypeDef::Compact(b)) => {
+            same(a.type_param().id(), b.type_param().id())
+        }
+        (TypeDef::BitSequence(_), TypeDef::BitSequence(_)) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        PolkadotConfig,
+        SubstrateConfig,
+    };
+    use frame_metadata::{
+        v14::{
+            ExtrinsicMetadata,
+            RuntimeMetadataV14,
+        },
+        RuntimeMetadataPrefixed,
+    };
+    use scale_info::meta_type;
+    use sp_runtime::{
+        generic::UncheckedExtrinsic,
+        AccountId32,
+        MultiAddress,
+        MultiSignature,
+    };
+    use std::convert::TryFrom;
+
+    fn metadata<Address: TypeInfo + 'static>() -> Metadata {
+        let extrinsic = ExtrinsicMetadata {
+            ty: meta_type::<UncheckedExtrinsic<Address, (), MultiSignature, ()>>(),
+            version: 4,
+            signed_extensions: vec![],
+        };
+        let v14 = RuntimeMetadataV14::new(vec![], extrinsic, meta_type::<()>());
+        let runtime_metadata: RuntimeMetadataPrefixed = v14.into();
+        Metadata::try_from(runtime_metadata).unwrap()
+    }
+
+    #[test]
+    fn matching_types_are_accepted() {
+        let substrate = metadata::<MultiAddress<AccountId32, u32>>();
+        let polkadot = metadata::<MultiAddress<AccountId32, ()>>();
+        let expectations = ChainExpectations::<SubstrateConfig>::new();
+        assert!(expectations
+            .check(Default::default(), &substrate, None)
+            .is_ok());
+        let expectations = ChainExpectations::<PolkadotConfig>::new();
+        assert!(expectations
+            .check(Default::default(), &polkadot, None)
+            .is_ok());
+    }
+
+    #[test]
+    fn mismatched_types_are_rejected() {
+        let evm = metadata::<crate::config::AccountId20>();
+        let expectations = ChainExpectations::<SubstrateConfig>::new();
+        assert!(matches!(
+            expectations.check(Default::default(), &evm, None),
+            Err(Error::ChainMismatch(ChainMismatchError::Type {
+                name: "AccountId",
+                ..
+            }))
+        ));
+
+        // The account IDs match, but the account index type doesn't.
+        let substrate = metadata::<MultiAddress<AccountId32, u32>>();
+        let expectations = ChainExpectations::<PolkadotConfig>::new();
+        assert!(matches!(
+            expectations.check(Default::default(), &substrate, None),
+            Err(Error::ChainMismatch(ChainMismatchError::Type {
+                name: "Address",
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn properties_and_genesis_hash_are_checked() {
+        let metadata = metadata::<MultiAddress<AccountId32, ()>>();
+        let properties: SystemProperties = serde_json::from_value(serde_json::json!({
+            "ss58Format": 0,
+            "tokenSymbol": ["DOT"],
+            "tokenDecimals": [10],
+        }))
+        .unwrap();
+
+        let expectations = ChainExpectations::<PolkadotConfig>::new()
+            .ss58_format(0)
+            .token("DOT", 10)
+            .genesis_hash(sp_core::H256::repeat_byte(1));
+        assert!(expectations
+            .check(sp_core::H256::repeat_byte(1), &metadata, Some(&properties))
+            .is_ok());
+        assert!(matches!(
+            expectations.check(sp_core::H256::zero(), &metadata, Some(&properties)),
+            Err(Error::ChainMismatch(ChainMismatchError::GenesisHash { .. }))
+        ));
+
+        let expectations = ChainExpectations::<PolkadotConfig>::new().token("KSM", 12);
+        let err = expectations
+            .check(Default::default(), &metadata, Some(&properties))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Chain mismatch: Expected the chain property 'tokenSymbol' to be KSM, but it is DOT"
+        );
+    }
+}
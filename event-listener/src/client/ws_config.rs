@@ -0,0 +1,393 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Tuning the WebSocket connection to a node.
+
+use super::online_client::jsonrpsee_helpers::{
+    Client,
+    ClientBuilder,
+    Error,
+    InvalidUri,
+    Uri,
+    WsTransportClientBuilder,
+};
+
+/// The largest message that can be sent or received by default, which is the
+/// default of `jsonrpsee` too.
+pub const DEFAULT_MAX_MESSAGE_SIZE: u32 = 10 * 1024 * 1024;
+
+/// How many notifications a subscription can have waiting to be handled, by
+/// default, before the connection is closed.
+pub const DEFAULT_MAX_NOTIFS_PER_SUBSCRIPTION: usize = 4096;
+
+/// How the WebSocket connection to a node is set up. Hand this to
+/// [`super::OnlineClient::from_url_with_config()`].
+///
+/// The metadata of a runtime, and the events of busy blocks, can be bigger than
+/// the largest message allowed by default, so raise
+/// [`WsClientConfig::max_message_size()`] if they fail to be fetched. With the
+/// `ws-compression` feature, messages can also be compressed, which they do
+/// well (around 5x for metadata and events), for connections that are metered
+/// or slow.
+///
+/// ```no_run
+/// use event_listener::{
+///     client::WsClientConfig,
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let config = WsClientConfig::new().max_message_size(64 * 1024 * 1024);
+/// let api = OnlineClient::<PolkadotConfig>::from_url_with_config(
+///     "wss://rpc.polkadot.io:443",
+///     &config,
+/// )
+/// .await
+/// .unwrap();
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WsClientConfig {
+    max_message_size: u32,
+    max_notifs_per_subscription: usize,
+    #[cfg(feature = "ws-compression")]
+    max_frame_size: Option<u32>,
+    #[cfg(feature = "ws-compression")]
+    compression: bool,
+}
+
+impl Default for WsClientConfig {
+    fn default() -> Self {
+        WsClientConfig {
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            max_notifs_per_subscription: DEFAULT_MAX_NOTIFS_PER_SUBSCRIPTION,
+            #[cfg(feature = "ws-compression")]
+            max_frame_size: None,
+            #[cfg(feature = "ws-compression")]
+            compression: false,
+        }
+    }
+}
+
+impl WsClientConfig {
+    /// The default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The largest message, in bytes, that can be sent or received. Defaults to
+    /// [`DEFAULT_MAX_MESSAGE_SIZE`].
+    pub fn max_message_size(mut self, max_message_size: u32) -> Self {
+        self.max_message_size = max_message_size;
+        self
+    }
+
+    /// How many notifications each subscription can have waiting to be handled
+    /// before the connection is closed. Defaults to
+    /// [`DEFAULT_MAX_NOTIFS_PER_SUBSCRIPTION`].
+    pub fn max_notifs_per_subscription(mut self, max_notifs: usize) -> Self {
+        self.max_notifs_per_subscription = max_notifs;
+        self
+    }
+
+    /// The largest frame, in bytes, that messages are split into or can be
+    /// received in. By default this is the same as the largest message.
+    #[cfg(feature = "ws-compression")]
+    pub fn max_frame_size(mut self, max_frame_size: u32) -> Self {
+        self.max_frame_size = Some(max_frame_size);
+        self
+    }
+
+    /// Whether to ask the node to compress messages, with the WebSocket
+    /// `permessage-deflate` extension. Nodes which don't support it carry on
+    /// without it. Off by default.
+    #[cfg(feature = "ws-compression")]
+    pub fn compression(mut self, compression: bool) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    // Whether the connection needs more than `jsonrpsee` can set up itself.
+    #[cfg(feature = "ws-compression")]
+    fn needs_own_transport(&self) -> bool {
+        self.compression || self.max_frame_size.is_some()
+    }
+
+    /// Connect to the node at the URL given, on a Tokio runtime in the background
+    /// (see `background`), so that the client can be used from any executor.
+    #[cfg(feature = "runtime-agnostic")]
+    pub(crate) async fn connect(&self, url: &str) -> Result<Client, Error> {
+        let config = self.clone();
+        let url = url.to_owned();
+        background::runtime()
+            .spawn(async move { config.connect_here(&url).await })
+            .await
+            .map_err(|e| Error::Custom(format!("Connection task failed: {}", e)))?
+    }
+
+    /// Connect to the node at the URL given. This must be called from within a
+    /// Tokio runtime, which then drives the connection.
+    #[cfg(not(feature = "runtime-agnostic"))]
+    pub(crate) async fn connect(&self, url: &str) -> Result<Client, Error> {
+        self.connect_here(url).await
+    }
+
+    // Connect to the node, driving the connection on the Tokio runtime that this
+    // is called from.
+    async fn connect_here(&self, url: &str) -> Result<Client, Error> {
+        let url: Uri = url
+            .parse()
+            .map_err(|e: InvalidUri| Error::Transport(e.into()))?;
+        let builder = ClientBuilder::default()
+            .max_notifs_per_subscription(self.max_notifs_per_subscription);
+
+        #[cfg(feature = "ws-compression")]
+        if self.needs_own_transport() {
+            let (sender, receiver) = transport::connect(&url, self)
+                .await
+                .map_err(|e| Error::Transport(e.into()))?;
+            return Ok(builder.build_with_tokio(sender, receiver))
+        }
+
+        let (sender, receiver) = WsTransportClientBuilder::default()
+            .max_request_body_size(self.max_message_size)
+            .build(url)
+            .await
+            .map_err(|e| Error::Transport(e.into()))?;
+        Ok(builder.build_with_tokio(sender, receiver))
+    }
+}
+
+// The runtime that connections are driven by, with the `runtime-agnostic`
+// feature. `jsonrpsee` spawns a Tokio task for each connection, which everything
+// else talks to over channels, so once a connection is made on a runtime of our
+// own, its requests and subscriptions can be awaited from any executor.
+#[cfg(feature = "runtime-agnostic")]
+mod background {
+    use once_cell::sync::Lazy;
+    use tokio::runtime::{
+        Builder,
+        Runtime,
+    };
+
+    static RUNTIME: Lazy<Runtime> = Lazy::new(|| {
+        Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("event-listener-connections")
+            .enable_all()
+            .build()
+            .expect("the background runtime for connections can be started; qed")
+    });
+
+    pub fn runtime() -> &'static Runtime {
+        &RUNTIME
+    }
+}
+
+// A WebSocket transport built on `soketto`, which `jsonrpsee` uses underneath,
+// so that compression and frame sizes can be set up, which `jsonrpsee` doesn't
+// let us do through its own transport.
+#[cfg(feature = "ws-compression")]
+mod transport {
+    use super::{
+        Uri,
+        WsClientConfig,
+    };
+    use jsonrpsee::core::{
+        async_trait,
+        client::{
+            ReceivedMessage,
+            TransportReceiverT,
+            TransportSenderT,
+        },
+    };
+    use soketto::{
+        connection,
+        extension::deflate::Deflate,
+        handshake::{
+            self,
+            ServerResponse,
+        },
+        Data,
+        Incoming,
+    };
+    use std::{
+        convert::TryFrom,
+        sync::Arc,
+    };
+    use tokio::{
+        io::{
+            AsyncRead,
+            AsyncWrite,
+        },
+        net::TcpStream,
+    };
+    use tokio_rustls::{
+        rustls::{
+            ClientConfig,
+            OwnedTrustAnchor,
+            RootCertStore,
+            ServerName,
+        },
+        TlsConnector,
+    };
+    use tokio_util::compat::{
+        Compat,
+        TokioAsyncReadCompatExt,
+    };
+
+    trait Io: AsyncRead + AsyncWrite + Unpin + Send {}
+    impl<T: AsyncRead + AsyncWrite + Unpin + Send> Io for T {}
+
+    type Socket = Compat<Box<dyn Io>>;
+
+    /// Errors setting up or using the connection.
+    #[derive(Debug, thiserror::Error)]
+    pub enum WsError {
+        #[error("Invalid URL {0}: {1}")]
+        Url(String, &'static str),
+        #[error("Cannot connect: {0}")]
+        Io(#[from] std::io::Error),
+        #[error("WebSocket handshake failed: {0}")]
+        Handshake(#[from] handshake::Error),
+        #[error("Node rejected the WebSocket connection with status {0}")]
+        Rejected(u16),
+        #[error("Node redirected the WebSocket connection to {0}")]
+        Redirected(String),
+        #[error("WebSocket connection failed: {0}")]
+        Connection(#[from] connection::Error),
+        #[error("Unexpected message: {0}")]
+        Message(String),
+    }
+
+    pub struct Sender(connection::Sender<Socket>);
+    pub struct Receiver(connection::Receiver<Socket>);
+
+    pub async fn connect(
+        url: &Uri,
+        config: &WsClientConfig,
+    ) -> Result<(Sender, Receiver), WsError> {
+        let invalid = |reason| WsError::Url(url.to_string(), reason);
+        let host = url.host().ok_or_else(|| invalid("no host"))?;
+        let tls = match url.scheme_str() {
+            Some("ws") => false,
+            Some("wss") => true,
+            _ => return Err(invalid("the scheme must be ws or wss")),
+        };
+        let port = url.port_u16().unwrap_or(if tls { 443 } else { 80 });
+        let path = url.path_and_query().map_or("/", |p| p.as_str());
+
+        let tcp = TcpStream::connect((host, port)).await?;
+        tcp.set_nodelay(true)?;
+        let socket: Box<dyn Io> = if tls {
+            let domain = ServerName::try_from(host).map_err(|_| invalid("bad host name"))?;
+            Box::new(tls_connector().connect(domain, tcp).await?)
+        } else {
+            Box::new(tcp)
+        };
+
+        let host_header = match url.port_u16() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_owned(),
+        };
+        let mut client = handshake::Client::new(socket.compat(), &host_header, path);
+        if config.compression {
+            client.add_extension(Box::new(Deflate::new(soketto::Mode::Client)));
+        }
+        match client.handshake().await? {
+            ServerResponse::Accepted { .. } => {}
+            ServerResponse::Rejected { status_code } => {
+                return Err(WsError::Rejected(status_code))
+            }
+            ServerResponse::Redirect { location, .. } => {
+                return Err(WsError::Redirected(location))
+            }
+        }
+
+        let mut builder = client.into_builder();
+        builder.set_max_message_size(config.max_message_size as usize);
+        builder.set_max_frame_size(
+            config.max_frame_size.unwrap_or(config.max_message_size) as usize,
+        );
+        let (sender, receiver) = builder.finish();
+        Ok((Sender(sender), Receiver(receiver)))
+    }
+
+    fn tls_connector() -> TlsConnector {
+        let mut roots = RootCertStore::empty();
+        roots.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|ta| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                ta.subject,
+                ta.spki,
+                ta.name_constraints,
+            )
+        }));
+        let config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        TlsConnector::from(Arc::new(config))
+    }
+
+    #[async_trait]
+    impl TransportSenderT for Sender {
+        type Error = WsError;
+
+        async fn send(&mut self, body: String) -> Result<(), WsError> {
+            self.0.send_text(body).await?;
+            self.0.flush().await?;
+            Ok(())
+        }
+
+        async fn send_ping(&mut self) -> Result<(), WsError> {
+            let data = soketto::data::ByteSlice125::try_from(&[][..])
+                .expect("an empty slice is a valid ping; qed");
+            self.0.send_ping(data).await?;
+            self.0.flush().await?;
+            Ok(())
+        }
+
+        async fn close(&mut self) -> Result<(), WsError> {
+            self.0.close().await?;
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl TransportReceiverT for Receiver {
+        type Error = WsError;
+
+        async fn receive(&mut self) -> Result<ReceivedMessage, WsError> {
+            let mut message = Vec::new();
+            match self.0.receive(&mut message).await? {
+                Incoming::Data(Data::Text(_)) => {
+                    let text = String::from_utf8(message)
+                        .map_err(|e| WsError::Message(e.to_string()))?;
+                    Ok(ReceivedMessage::Text(text))
+                }
+                Incoming::Data(Data::Binary(_)) => Ok(ReceivedMessage::Bytes(message)),
+                Incoming::Pong(_) => Ok(ReceivedMessage::Pong),
+                Incoming::Closed(reason) => {
+                    Err(WsError::Message(format!("connection closed: {:?}", reason)))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn config_defaults_match_jsonrpsee() {
+        let config = WsClientConfig::new();
+        assert_eq!(config.max_message_size, 10 * 1024 * 1024);
+        assert_eq!(config.max_notifs_per_subscription, 4096);
+        let config = config.max_message_size(1024);
+        assert_eq!(config.max_message_size, 1024);
+    }
+}
@@ -0,0 +1,270 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! A [`Config`] for Frontier based chains such as Moonbeam, which use Ethereum
+//! style 20 byte accounts and ECDSA signatures.
+//!
+//! [`AccountId20`] is always available, but the signatures (and so
+//! [`EvmConfig`]) need the `evm` feature, which brings in `libsecp256k1`.
+
+#[cfg(feature = "evm")]
+use super::Config;
+#[cfg(feature = "evm")]
+use crate::{
+    tx::SubstrateExtrinsicParams,
+    utils::to_hex,
+};
+use codec::{
+    Decode,
+    Encode,
+};
+use scale_info::TypeInfo;
+use serde::{
+    Deserialize,
+    Deserializer,
+    Serialize,
+    Serializer,
+};
+use sp_core::{
+    hashing::keccak_256,
+    H160,
+};
+#[cfg(feature = "evm")]
+use sp_runtime::traits::{
+    IdentifyAccount,
+    Lazy,
+    Verify,
+};
+
+/// The types used by Frontier based chains whose accounts are Ethereum style
+/// 20 byte addresses, such as Moonbeam, Moonriver and Moonbase Alpha.
+///
+/// Such chains don't have an account lookup, so the address of an extrinsic
+/// is just the account ID.
+///
+/// **Note:** Ethereum signatures are made over the Keccak-256 hash of the
+/// payload, so [`crate::tx::PairSigner`] (which signs the Blake2 hash of
+/// it) can't be used to sign transactions for these chains.
+#[cfg(feature = "evm")]
+pub enum EvmConfig {}
+
+#[cfg(feature = "evm")]
+impl Config for EvmConfig {
+    type Index = u32;
+    type BlockNumber = u32;
+    type Hash = sp_core::H256;
+    type Hashing = sp_runtime::traits::BlakeTwo256;
+    type AccountId = AccountId20;
+    type Address = AccountId20;
+    type Header =
+        sp_runtime::generic::Header<Self::BlockNumber, sp_runtime::traits::BlakeTwo256>;
+    type Signature = EthereumSignature;
+    type ExtrinsicParams = SubstrateExtrinsicParams<Self>;
+}
+
+/// A 20 byte, Ethereum style account ID. This is formatted and parsed as a
+/// `0x` prefixed hex string.
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Default,
+    Encode,
+    Decode,
+    TypeInfo,
+)]
+pub struct AccountId20(pub [u8; 20]);
+
+impl AccountId20 {
+    /// The account that the uncompressed (65 byte, beginning with `0x04`)
+    /// secp256k1 public key given belongs to.
+    pub fn from_uncompressed_public(public: &[u8; 65]) -> AccountId20 {
+        let hash = keccak_256(&public[1..]);
+        let mut account = [0u8; 20];
+        account.copy_from_slice(&hash[12..]);
+        AccountId20(account)
+    }
+}
+
+impl AsRef<[u8]> for AccountId20 {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<H160> for AccountId20 {
+    fn from(h: H160) -> Self {
+        AccountId20(h.0)
+    }
+}
+
+impl From<AccountId20> for H160 {
+    fn from(a: AccountId20) -> Self {
+        H160(a.0)
+    }
+}
+
+impl From<[u8; 20]> for AccountId20 {
+    fn from(bytes: [u8; 20]) -> Self {
+        AccountId20(bytes)
+    }
+}
+
+impl std::fmt::Display for AccountId20 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&to_hex(self.0))
+    }
+}
+
+impl std::fmt::Debug for AccountId20 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::str::FromStr for AccountId20 {
+    type Err = hex::FromHexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut account = [0u8; 20];
+        hex::decode_to_slice(s.trim_start_matches("0x"), &mut account)?;
+        Ok(AccountId20(account))
+    }
+}
+
+impl Serialize for AccountId20 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for AccountId20 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// A 65 byte recoverable ECDSA signature (`r`, `s` and the recovery ID `v`)
+/// over the Keccak-256 hash of a payload, as used by Ethereum.
+#[cfg(feature = "evm")]
+#[derive(Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct EthereumSignature(pub [u8; 65]);
+
+#[cfg(feature = "evm")]
+impl std::fmt::Debug for EthereumSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EthereumSignature(0x{})", hex::encode(self.0))
+    }
+}
+
+#[cfg(feature = "evm")]
+impl EthereumSignature {
+    /// Recover the account which signed the payload given, if the signature
+    /// is well formed.
+    pub fn recover(&self, payload: &[u8]) -> Option<AccountId20> {
+        let message = libsecp256k1::Message::parse(&keccak_256(payload));
+        let signature =
+            libsecp256k1::Signature::parse_standard_slice(&self.0[..64]).ok()?;
+        // Recovery IDs are sometimes offset by 27, as in Ethereum transactions.
+        let v = self.0[64];
+        let recovery_id =
+            libsecp256k1::RecoveryId::parse(if v >= 27 { v - 27 } else { v }).ok()?;
+        let public = libsecp256k1::recover(&message, &signature, &recovery_id).ok()?;
+        Some(AccountId20::from_uncompressed_public(&public.serialize()))
+    }
+}
+
+#[cfg(feature = "evm")]
+impl Verify for EthereumSignature {
+    type Signer = EthereumSigner;
+
+    fn verify<L: Lazy<[u8]>>(&self, mut msg: L, signer: &AccountId20) -> bool {
+        self.recover(msg.get()).as_ref() == Some(signer)
+    }
+}
+
+/// The public identity behind an [`EthereumSignature`], which is just the
+/// account ID itself.
+#[cfg(feature = "evm")]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Encode, Decode, TypeInfo)]
+pub struct EthereumSigner(pub AccountId20);
+
+#[cfg(feature = "evm")]
+impl IdentifyAccount for EthereumSigner {
+    type AccountId = AccountId20;
+
+    fn into_account(self) -> AccountId20 {
+        self.0
+    }
+}
+
+#[cfg(feature = "evm")]
+impl From<AccountId20> for EthereumSigner {
+    fn from(account: AccountId20) -> Self {
+        EthereumSigner(account)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // The well known first Hardhat/Moonbeam development account.
+    #[cfg(feature = "evm")]
+    const ALITH_SECRET: [u8; 32] = [
+        0x5f, 0xb9, 0x2d, 0x6e, 0x98, 0x88, 0x4f, 0x76, 0xde, 0x46, 0x8f, 0xa3, 0xf6,
+        0x27, 0x8f, 0x88, 0x07, 0xc4, 0x8b, 0xeb, 0xc1, 0x35, 0x95, 0xd4, 0x5a, 0xf5,
+        0xbd, 0xc4, 0xda, 0x70, 0x21, 0x33,
+    ];
+    const ALITH: &str = "0xf24ff3a9cf04c71dbc94d0b566f7a27b94566cac";
+
+    #[cfg(feature = "evm")]
+    fn sign(payload: &[u8]) -> EthereumSignature {
+        let secret = libsecp256k1::SecretKey::parse(&ALITH_SECRET).unwrap();
+        let message = libsecp256k1::Message::parse(&keccak_256(payload));
+        let (signature, recovery_id) = libsecp256k1::sign(&message, &secret);
+        let mut bytes = [0u8; 65];
+        bytes[..64].copy_from_slice(&signature.serialize());
+        bytes[64] = recovery_id.serialize();
+        EthereumSignature(bytes)
+    }
+
+    #[cfg(feature = "evm")]
+    #[test]
+    fn accounts_are_derived_from_public_keys() {
+        let secret = libsecp256k1::SecretKey::parse(&ALITH_SECRET).unwrap();
+        let public = libsecp256k1::PublicKey::from_secret_key(&secret);
+        let account = AccountId20::from_uncompressed_public(&public.serialize());
+        assert_eq!(account.to_string(), ALITH);
+    }
+
+    #[cfg(feature = "evm")]
+    #[test]
+    fn signatures_are_verified_against_the_account() {
+        let alith: AccountId20 = ALITH.parse().unwrap();
+        let signature = sign(b"payload");
+        assert!(signature.verify(&b"payload"[..], &alith));
+        assert!(!signature.verify(&b"other payload"[..], &alith));
+        assert!(!signature.verify(&b"payload"[..], &AccountId20([1; 20])));
+
+        // Recovery IDs offset by 27 are accepted too.
+        let mut offset = signature;
+        offset.0[64] += 27;
+        assert!(offset.verify(&b"payload"[..], &alith));
+    }
+
+    #[test]
+    fn accounts_serialize_as_hex() {
+        let alith: AccountId20 = ALITH.parse().unwrap();
+        let json = serde_json::to_string(&alith).unwrap();
+        assert_eq!(json, format!("\"{}\"", ALITH));
+        assert_eq!(serde_json::from_str::<AccountId20>(&json).unwrap(), alith);
+        assert_eq!(alith.encode().len(), 20);
+    }
+}
@@ -0,0 +1,305 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! This module provides a [`Config`] type, which is used to define various
+//! types that are important in order to speak to a particular chain.
+//! [`SubstrateConfig`] provides a default set of these types suitable for the
+//! default Substrate node implementation, and [`PolkadotConfig`] for a
+//! Polkadot node. Aliases such as [`KusamaConfig`] and [`AssetHubConfig`] are
+//! provided for other well known chains, [`EvmConfig`] for Frontier based
+//! chains with Ethereum style accounts and [`KeccakConfig`] for chains which
+//! hash with Keccak-256. [`Ss58Format`] formats the accounts of a chain as
+//! SS58 addresses.
+
+mod evm;
+mod ss58;
+
+pub use evm::AccountId20;
+#[cfg(feature = "evm")]
+pub use evm::{
+    EthereumSignature,
+    EthereumSigner,
+    EvmConfig,
+};
+pub use ss58::{
+    from_ss58,
+    to_ss58,
+    Ss58Format,
+    DEFAULT_SS58_FORMAT,
+};
+
+use codec::{
+    Codec,
+    Encode,
+    EncodeLike,
+};
+use crate::tx::{
+    ExtrinsicParams,
+    PolkadotExtrinsicParams,
+    SubstrateExtrinsicParams,
+};
+use core::fmt::Debug;
+use sp_runtime::traits::{
+    AtLeast32Bit,
+    Hash,
+    Header,
+    MaybeSerializeDeserialize,
+    Member,
+    Verify,
+};
+
+/// Runtime types.
+// Note: the 'static bound isn't strictly required, but currently deriving TypeInfo
+// automatically applies a 'static bound to all generic types (including this one),
+// and so until that is resolved, we'll keep the (easy to satisfy) constraint here.
+pub trait Config: 'static {
+    /// Account index (aka nonce) type. This stores the number of previous
+    /// transactions associated with a sender account.
+    type Index: Parameter
+        + Member
+        + serde::de::DeserializeOwned
+        + Default
+        + AtLeast32Bit
+        + Copy
+        + scale_info::TypeInfo
+        + Into<u64>;
+
+    /// The block number type used by the runtime.
+    type BlockNumber: Parameter
+        + Member
+        + Default
+        + Copy
+        + core::hash::Hash
+        + core::str::FromStr
+        + Into<u64>;
+
+    /// The output of the `Hashing` function.
+    type Hash: Parameter
+        + Member
+        + MaybeSerializeDeserialize
+        + Ord
+        + Default
+        + Copy
+        + std::hash::Hash
+        + AsRef<[u8]>
+        + AsMut<[u8]>
+        + scale_info::TypeInfo;
+
+    /// The hashing system (algorithm) being used in the runtime (e.g. Blake2).
+    type Hashing: Hash<Output = Self::Hash>;
+
+    /// The user account identifier type for the runtime.
+    type AccountId: Parameter + Member + serde::Serialize;
+
+    /// The address type. This instead of `<frame_system::Trait::Lookup as StaticLookup>::Source`.
+    type Address: Codec + Clone + PartialEq;
+
+    /// The block header.
+    type Header: Parameter
+        + Header<Number = Self::BlockNumber, Hash = Self::Hash>
+        + serde::de::DeserializeOwned;
+
+    /// Signature type.
+    type Signature: Verify + Encode + Send + Sync + 'static;
+
+    /// This type defines the extrinsic extra and additional parameters.
+    type ExtrinsicParams: ExtrinsicParams<Self::Hash>;
+}
+
+/// Parameter trait copied from `substrate::frame_support`
+pub trait Parameter: Codec + EncodeLike + Clone + Eq + Debug {}
+impl<T> Parameter for T where T: Codec + EncodeLike + Clone + Eq + Debug {}
+
+/// Default set of commonly used types by Substrate runtimes.
+// Note: We only use this at the type level, so it should be impossible to
+// create an instance of it.
+pub enum SubstrateConfig {}
+
+impl Config for SubstrateConfig {
+    type Index = u32;
+    type BlockNumber = u32;
+    type Hash = sp_core::H256;
+    type Hashing = sp_runtime::traits::BlakeTwo256;
+    type AccountId = sp_runtime::AccountId32;
+    type Address = sp_runtime::MultiAddress<Self::AccountId, u32>;
+    type Header =
+        sp_runtime::generic::Header<Self::BlockNumber, sp_runtime::traits::BlakeTwo256>;
+    type Signature = sp_runtime::MultiSignature;
+    type ExtrinsicParams = SubstrateExtrinsicParams<Self>;
+}
+
+/// Default set of commonly used types by Polkadot nodes. These are the same as
+/// those of [`SubstrateConfig`], except that Polkadot has no account indices,
+/// so its address type is `MultiAddress<AccountId32, ()>`.
+pub enum PolkadotConfig {}
+
+impl Config for PolkadotConfig {
+    type Index = u32;
+    type BlockNumber = u32;
+    type Hash = sp_core::H256;
+    type Hashing = sp_runtime::traits::BlakeTwo256;
+    type AccountId = sp_runtime::AccountId32;
+    type Address = sp_runtime::MultiAddress<Self::AccountId, ()>;
+    type Header =
+        sp_runtime::generic::Header<Self::BlockNumber, sp_runtime::traits::BlakeTwo256>;
+    type Signature = sp_runtime::MultiSignature;
+    type ExtrinsicParams = PolkadotExtrinsicParams<Self>;
+}
+
+/// The types used by Kusama nodes, which are the same as those of Polkadot.
+pub type KusamaConfig = PolkadotConfig;
+
+/// The types used by Westend nodes, which are the same as those of Polkadot.
+pub type WestendConfig = PolkadotConfig;
+
+/// The types used by the Asset Hub system parachains on Polkadot, Kusama and
+/// Westend, which are the same as those of the relay chains.
+pub type AssetHubConfig = PolkadotConfig;
+
+/// The same types as [`SubstrateConfig`], but with `u64` block numbers, as some
+/// Substrate based chains (for instance those built with `frame-template`
+/// forks that changed `BlockNumber`) use.
+pub type SubstrateU64Config = WithHeader<
+    SubstrateConfig,
+    sp_runtime::generic::Header<u64, sp_runtime::traits::BlakeTwo256>,
+>;
+
+/// The same types as [`SubstrateConfig`], but using Keccak-256 rather than
+/// Blake2-256 to hash blocks and extrinsics, as chains using Ethereum style
+/// hashing do.
+///
+/// Storage keys are unaffected by this; they're built using the hashers given
+/// in the metadata for each storage entry.
+pub enum KeccakConfig {}
+
+impl Config for KeccakConfig {
+    type Index = u32;
+    type BlockNumber = u32;
+    type Hash = sp_core::H256;
+    type Hashing = sp_runtime::traits::Keccak256;
+    type AccountId = sp_runtime::AccountId32;
+    type Address = sp_runtime::MultiAddress<Self::AccountId, u32>;
+    type Header =
+        sp_runtime::generic::Header<Self::BlockNumber, sp_runtime::traits::Keccak256>;
+    type Signature = sp_runtime::MultiSignature;
+    type ExtrinsicParams = SubstrateExtrinsicParams<Self>;
+}
+
+/// Take a type implementing [`Config`] (eg [`SubstrateConfig`]), and swap out
+/// its header type (and with it, the block number type) for the one given.
+///
+/// This is useful for chains whose headers don't match those of Substrate,
+/// for instance because they have a different block number type, or digest
+/// items which `sp_runtime`'s header can't decode.
+///
+/// # Example
+///
+/// ```
+/// use event_listener::config::{ SubstrateConfig, WithHeader };
+/// use event_listener::ext::sp_runtime::{ generic::Header, traits::BlakeTwo256 };
+///
+/// // This is how SubstrateU64Config is implemented:
+/// type SubstrateU64Config = WithHeader<SubstrateConfig, Header<u64, BlakeTwo256>>;
+/// ```
+pub struct WithHeader<T: Config, H> {
+    _marker: std::marker::PhantomData<(T, H)>,
+}
+
+impl<T, H> Config for WithHeader<T, H>
+where
+    T: Config,
+    H: Parameter
+        + Header<Hash = T::Hash>
+        + serde::de::DeserializeOwned
+        + 'static,
+    H::Number: Parameter
+        + Member
+        + Default
+        + Copy
+        + core::hash::Hash
+        + core::str::FromStr
+        + Into<u64>,
+{
+    type Index = T::Index;
+    type BlockNumber = H::Number;
+    type Hash = T::Hash;
+    type Hashing = T::Hashing;
+    type AccountId = T::AccountId;
+    type Address = T::Address;
+    type Header = H;
+    type Signature = T::Signature;
+    type ExtrinsicParams = T::ExtrinsicParams;
+}
+
+/// Take a type implementing [`Config`] (eg [`SubstrateConfig`]), and some type which describes the
+/// additional and extra parameters to pass to an extrinsic (see [`ExtrinsicParams`]),
+/// and returns a type implementing [`Config`] with those new [`ExtrinsicParams`].
+///
+/// # Example
+///
+/// ```
+/// use event_listener::config::{ SubstrateConfig, WithExtrinsicParams };
+/// use event_listener::tx::SubstrateExtrinsicParams;
+///
+/// // This is equivalent to SubstrateConfig:
+/// type MyConfig = WithExtrinsicParams<SubstrateConfig, SubstrateExtrinsicParams<SubstrateConfig>>;
+/// ```
+pub struct WithExtrinsicParams<T: Config, E: ExtrinsicParams<T::Hash>> {
+    _marker: std::marker::PhantomData<(T, E)>,
+}
+
+impl<T: Config, E: ExtrinsicParams<T::Hash>> Config for WithExtrinsicParams<T, E> {
+    type Index = T::Index;
+    type BlockNumber = T::BlockNumber;
+    type Hash = T::Hash;
+    type Hashing = T::Hashing;
+    type AccountId = T::AccountId;
+    type Address = T::Address;
+    type Header = T::Header;
+    type Signature = T::Signature;
+    type ExtrinsicParams = E;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use codec::Decode;
+
+    #[test]
+    fn u64_block_numbers_are_deserialized() {
+        // Block numbers are handed back as hex strings by the node.
+        let json = serde_json::json!({
+            "parentHash": format!("0x{}", "11".repeat(32)),
+            "number": "0x100000000",
+            "stateRoot": format!("0x{}", "22".repeat(32)),
+            "extrinsicsRoot": format!("0x{}", "33".repeat(32)),
+            "digest": { "logs": [] },
+        });
+        let header: <SubstrateU64Config as Config>::Header =
+            serde_json::from_value(json).unwrap();
+        assert_eq!(*header.number(), 1u64 << 32);
+
+        // The header hash is worked out from the SCALE encoded header, so it
+        // should survive a round trip.
+        let decoded = <SubstrateU64Config as Config>::Header::decode(
+            &mut &*header.encode(),
+        )
+        .unwrap();
+        assert_eq!(decoded.hash(), header.hash());
+    }
+
+    #[test]
+    fn u32_block_numbers_reject_larger_numbers() {
+        let json = serde_json::json!({
+            "parentHash": format!("0x{}", "11".repeat(32)),
+            "number": "0x100000000",
+            "stateRoot": format!("0x{}", "22".repeat(32)),
+            "extrinsicsRoot": format!("0x{}", "33".repeat(32)),
+            "digest": { "logs": [] },
+        });
+        let header: Result<<SubstrateConfig as Config>::Header, _> =
+            serde_json::from_value(json);
+        assert!(header.is_err());
+    }
+}
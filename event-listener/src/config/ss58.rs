@@ -0,0 +1,296 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Format accounts as SS58 addresses, and parse them back again.
+
+use super::Config;
+use crate::{
+    error::Ss58Error,
+    rpc::SystemProperties,
+    utils::{
+        composite_values,
+        value_as_u128,
+        PhantomDataSendSync,
+    },
+};
+use codec::{
+    DecodeAll,
+    Encode,
+};
+use derivative::Derivative;
+use scale_value::{
+    scale::TypeId,
+    Value,
+    ValueDef,
+};
+use sp_core::hashing::blake2_512;
+
+/// The SS58 format used when a chain doesn't say which one it uses.
+pub const DEFAULT_SS58_FORMAT: u16 = 42;
+
+const CHECKSUM_PREFIX: &[u8] = b"SS58PRE";
+
+/// Formats the accounts of a chain as SS58 addresses with the chain's prefix, and
+/// parses them back again.
+///
+/// # Example
+///
+/// ```no_run
+/// use event_listener::{ OnlineClient, PolkadotConfig };
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let ss58 = api.ss58_format().await.unwrap();
+///
+/// let account = ss58.parse("15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5").unwrap();
+/// println!("{}", ss58.format(&account));
+/// # }
+/// ```
+#[derive(Derivative)]
+#[derivative(
+    Clone(bound = ""),
+    Copy(bound = ""),
+    Debug(bound = ""),
+    PartialEq(bound = ""),
+    Eq(bound = "")
+)]
+pub struct Ss58Format<T: Config> {
+    prefix: u16,
+    _marker: PhantomDataSendSync<T>,
+}
+
+impl<T: Config> Ss58Format<T> {
+    /// Use the SS58 prefix given.
+    pub fn new(prefix: u16) -> Self {
+        Ss58Format {
+            prefix,
+            _marker: PhantomDataSendSync::new(),
+        }
+    }
+
+    /// Use the SS58 prefix in the `ss58Format` chain property, or
+    /// [`DEFAULT_SS58_FORMAT`] if there isn't one.
+    pub fn from_properties(properties: &SystemProperties) -> Self {
+        let prefix = properties
+            .get("ss58Format")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as u16)
+            .unwrap_or(DEFAULT_SS58_FORMAT);
+        Ss58Format::new(prefix)
+    }
+
+    /// The SS58 prefix.
+    pub fn prefix(&self) -> u16 {
+        self.prefix
+    }
+
+    /// Format an account as an SS58 address.
+    pub fn format(&self, account: &T::AccountId) -> String {
+        to_ss58(&account.encode(), self.prefix)
+    }
+
+    /// Parse an SS58 address into an account. The address must have this prefix.
+    pub fn parse(&self, address: &str) -> Result<T::AccountId, Ss58Error> {
+        let (prefix, data) = from_ss58(address)?;
+        if prefix != self.prefix {
+            return Err(Ss58Error::WrongPrefix {
+                expected: self.prefix,
+                actual: prefix,
+            })
+        }
+        T::AccountId::decode_all(&mut &*data).map_err(|_| Ss58Error::BadLength(data.len()))
+    }
+
+    /// Format a decoded value (such as an event field) as an SS58 address, if it
+    /// holds the bytes of an account. Returns `None` otherwise.
+    pub fn format_value(&self, value: &Value<TypeId>) -> Option<String> {
+        let bytes = value_bytes(value)?;
+        let account = T::AccountId::decode_all(&mut &*bytes).ok()?;
+        Some(self.format(&account))
+    }
+}
+
+/// Encode some bytes (usually an account ID) as an SS58 address with the prefix given.
+pub fn to_ss58(data: &[u8], prefix: u16) -> String {
+    let mut bytes = prefix_bytes(prefix);
+    bytes.extend_from_slice(data);
+    let checksum = checksum(&bytes);
+    bytes.extend_from_slice(&checksum[..checksum_len(data.len())]);
+    bs58::encode(bytes).into_string()
+}
+
+/// Decode an SS58 address, handing back its prefix and the bytes (usually an
+/// account ID) that it encodes.
+pub fn from_ss58(address: &str) -> Result<(u16, Vec<u8>), Ss58Error> {
+    let bytes = bs58::decode(address)
+        .into_vec()
+        .map_err(|_| Ss58Error::BadBase58)?;
+
+    let (prefix_len, prefix) = match bytes.first() {
+        Some(&first @ 0..=63) => (1, first as u16),
+        Some(64..=127) if bytes.len() > 1 => {
+            let lower = (bytes[0] << 2) | (bytes[1] >> 6);
+            let upper = bytes[1] & 0b0011_1111;
+            (2, (lower as u16) | ((upper as u16) << 8))
+        }
+        Some(&first) if first >= 128 => return Err(Ss58Error::BadPrefix),
+        _ => return Err(Ss58Error::BadLength(bytes.len())),
+    };
+
+    // The checksum length depends on the length of the data, which is unknown
+    // until we know the checksum length; only 1, 2, 4 and 8 byte data has a 1
+    // byte checksum, so try that first.
+    let rest = bytes.len() - prefix_len;
+    let data_len = match rest.checked_sub(1) {
+        Some(len @ (1 | 2 | 4 | 8)) => len,
+        _ => {
+            rest.checked_sub(2)
+                .filter(|len| checksum_len(*len) == 2)
+                .ok_or(Ss58Error::BadLength(rest))?
+        }
+    };
+
+    let (body, checksum_bytes) = bytes.split_at(prefix_len + data_len);
+    if checksum(body)[..checksum_bytes.len()] != *checksum_bytes {
+        return Err(Ss58Error::BadChecksum)
+    }
+    Ok((prefix, body[prefix_len..].to_vec()))
+}
+
+fn prefix_bytes(prefix: u16) -> Vec<u8> {
+    // Prefixes are 14 bits at most; anything above that is masked off.
+    let prefix = prefix & 0b0011_1111_1111_1111;
+    match prefix {
+        0..=63 => vec![prefix as u8],
+        _ => {
+            let first = ((prefix & 0b0000_0000_1111_1100) >> 2) as u8 | 0b0100_0000;
+            let second = (prefix >> 8) as u8 | ((prefix & 0b0000_0000_0000_0011) << 6) as u8;
+            vec![first, second]
+        }
+    }
+}
+
+fn checksum(body: &[u8]) -> [u8; 64] {
+    let mut input = CHECKSUM_PREFIX.to_vec();
+    input.extend_from_slice(body);
+    blake2_512(&input)
+}
+
+fn checksum_len(data_len: usize) -> usize {
+    match data_len {
+        1 | 2 | 4 | 8 => 1,
+        _ => 2,
+    }
+}
+
+// The bytes in a decoded byte array, looking through single field structs such as
+// `AccountId32([u8; 32])`.
+fn value_bytes(value: &Value<TypeId>) -> Option<Vec<u8>> {
+    let composite = match &value.value {
+        ValueDef::Composite(c) => c,
+        _ => return None,
+    };
+    let vals: Vec<_> = composite_values(composite).collect();
+    match vals.as_slice() {
+        [inner] => value_bytes(inner),
+        vals => {
+            vals.iter()
+                .map(|v| {
+                    match &v.value {
+                        ValueDef::Primitive(_) => value_as_u128(v)?.try_into().ok(),
+                        _ => None,
+                    }
+                })
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        PolkadotConfig,
+        SubstrateConfig,
+    };
+    use sp_core::crypto::AccountId32;
+
+    // Alice's address with the Substrate and Polkadot prefixes.
+    const ALICE_SUBSTRATE: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+    const ALICE_POLKADOT: &str = "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5";
+
+    fn alice() -> [u8; 32] {
+        hex::decode("d43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d")
+            .unwrap()
+            .try_into()
+            .unwrap()
+    }
+
+    #[test]
+    fn accounts_are_formatted_and_parsed() {
+        let alice = AccountId32::from(alice());
+
+        let substrate = Ss58Format::<SubstrateConfig>::new(DEFAULT_SS58_FORMAT);
+        assert_eq!(substrate.format(&alice), ALICE_SUBSTRATE);
+        assert_eq!(substrate.parse(ALICE_SUBSTRATE).unwrap(), alice);
+
+        let polkadot = Ss58Format::<PolkadotConfig>::new(0);
+        assert_eq!(polkadot.format(&alice), ALICE_POLKADOT);
+        assert_eq!(polkadot.parse(ALICE_POLKADOT).unwrap(), alice);
+
+        assert_eq!(
+            polkadot.parse(ALICE_SUBSTRATE),
+            Err(Ss58Error::WrongPrefix {
+                expected: 0,
+                actual: 42
+            })
+        );
+    }
+
+    #[test]
+    fn two_byte_prefixes_roundtrip() {
+        for prefix in [64, 255, 1284, 16383] {
+            let address = to_ss58(&alice(), prefix);
+            assert_eq!(from_ss58(&address).unwrap(), (prefix, alice().to_vec()));
+        }
+
+        // Short data has a single byte checksum.
+        let address = to_ss58(&[1, 2, 3, 4], 1000);
+        assert_eq!(from_ss58(&address).unwrap(), (1000, vec![1, 2, 3, 4]));
+
+        // 20 byte accounts are fine too.
+        #[cfg(feature = "evm")]
+        {
+            use crate::config::{
+                AccountId20,
+                EvmConfig,
+            };
+            let evm = Ss58Format::<EvmConfig>::new(1284);
+            let account = AccountId20([7; 20]);
+            assert_eq!(evm.parse(&evm.format(&account)).unwrap(), account);
+        }
+    }
+
+    #[test]
+    fn bad_addresses_are_rejected() {
+        let mut address = ALICE_SUBSTRATE.to_owned();
+        address.replace_range(10..11, "b");
+        assert_eq!(from_ss58(&address), Err(Ss58Error::BadChecksum));
+        assert_eq!(from_ss58("0OIl"), Err(Ss58Error::BadBase58));
+        assert!(from_ss58("").is_err());
+    }
+
+    #[test]
+    fn values_holding_accounts_are_formatted() {
+        let bytes = alice().iter().map(|b| Value::u128(*b as u128)).collect();
+        let account = Value::unnamed_composite(vec![Value::unnamed_composite(bytes)])
+            .map_context(|_| TypeId::from(0u32));
+        let ss58 = Ss58Format::<SubstrateConfig>::new(DEFAULT_SS58_FORMAT);
+        assert_eq!(ss58.format_value(&account).as_deref(), Some(ALICE_SUBSTRATE));
+
+        let not_an_account = Value::u128(1).map_context(|_| TypeId::from(0u32));
+        assert_eq!(ss58.format_value(&not_an_account), None);
+    }
+}
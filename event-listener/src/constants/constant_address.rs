@@ -0,0 +1,60 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use derivative::Derivative;
+use std::marker::PhantomData;
+
+/// The address of a constant whose value decodes to the static type `ReturnTy`.
+/// If a hash is provided, the shape of the constant in the node's metadata is
+/// checked against it before the constant is decoded.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub struct StaticConstantAddress<ReturnTy> {
+    pallet_name: &'static str,
+    constant_name: &'static str,
+    constant_hash: Option<[u8; 32]>,
+    _marker: PhantomData<fn() -> ReturnTy>,
+}
+
+impl<ReturnTy> StaticConstantAddress<ReturnTy> {
+    /// Create a new [`StaticConstantAddress`] that will be validated
+    /// against node metadata using the hash given.
+    pub fn new(
+        pallet_name: &'static str,
+        constant_name: &'static str,
+        hash: [u8; 32],
+    ) -> Self {
+        Self {
+            pallet_name,
+            constant_name,
+            constant_hash: Some(hash),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Do not validate this constant prior to accessing it.
+    pub fn unvalidated(self) -> Self {
+        Self {
+            pallet_name: self.pallet_name,
+            constant_name: self.constant_name,
+            constant_hash: None,
+            _marker: self._marker,
+        }
+    }
+
+    /// The name of the pallet that the constant lives in.
+    pub fn pallet_name(&self) -> &'static str {
+        self.pallet_name
+    }
+
+    /// The name of the constant.
+    pub fn constant_name(&self) -> &'static str {
+        self.constant_name
+    }
+
+    /// The hash that the constant is validated against, if any.
+    pub fn validation_hash(&self) -> Option<[u8; 32]> {
+        self.constant_hash
+    }
+}
@@ -0,0 +1,112 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use super::StaticConstantAddress;
+use crate::{
+    client::OfflineClientT,
+    error::Error,
+    metadata::MetadataError,
+    Config,
+};
+use codec::Decode;
+use derivative::Derivative;
+
+/// A client for accessing constants.
+#[derive(Derivative)]
+#[derivative(Clone(bound = "Client: Clone"))]
+pub struct ConstantsClient<T, Client> {
+    client: Client,
+    _marker: std::marker::PhantomData<T>,
+}
+
+impl<T, Client> ConstantsClient<T, Client> {
+    /// Create a new [`ConstantsClient`].
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T: Config, Client: OfflineClientT<T>> ConstantsClient<T, Client> {
+    /// Look up the constant with the given pallet and constant names, and
+    /// dynamically decode it using the type information in the metadata.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// use event_listener::{ OnlineClient, PolkadotConfig };
+    ///
+    /// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+    ///
+    /// let existential_deposit = api
+    ///     .constants()
+    ///     .at("Balances", "ExistentialDeposit")
+    ///     .unwrap();
+    /// println!("Existential deposit: {:?}", existential_deposit);
+    /// # }
+    /// ```
+    pub fn at(
+        &self,
+        pallet_name: &str,
+        constant_name: &str,
+    ) -> Result<scale_value::Value<scale_value::scale::TypeId>, Error> {
+        let metadata = self.client.metadata();
+        let constant = metadata.constant(pallet_name, constant_name)?;
+        let value = scale_value::scale::decode_as_type(
+            &mut &*constant.value,
+            constant.ty.id(),
+            &metadata.runtime_metadata().types,
+        )?;
+        Ok(value)
+    }
+
+    /// Return the raw SCALE encoded bytes of the constant with the given pallet
+    /// and constant names.
+    pub fn bytes_at(
+        &self,
+        pallet_name: &str,
+        constant_name: &str,
+    ) -> Result<Vec<u8>, Error> {
+        let metadata = self.client.metadata();
+        let constant = metadata.constant(pallet_name, constant_name)?;
+        Ok(constant.value.clone())
+    }
+
+    /// Check that the shape of the constant at the address given matches the
+    /// node's metadata. Constants without a validation hash always pass.
+    pub fn validate<ReturnTy>(
+        &self,
+        address: &StaticConstantAddress<ReturnTy>,
+    ) -> Result<(), Error> {
+        if let Some(expected_hash) = address.validation_hash() {
+            let actual_hash = self
+                .client
+                .metadata()
+                .constant_hash(address.pallet_name(), address.constant_name())?;
+            if actual_hash != expected_hash {
+                return Err(MetadataError::IncompatibleMetadata(
+                    address.pallet_name().into(),
+                    address.constant_name().into(),
+                )
+                .into())
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate the constant at the address given against the node's metadata,
+    /// and then decode it into the static type that the address points to.
+    pub fn at_static<ReturnTy: Decode>(
+        &self,
+        address: &StaticConstantAddress<ReturnTy>,
+    ) -> Result<ReturnTy, Error> {
+        self.validate(address)?;
+        let bytes = self.bytes_at(address.pallet_name(), address.constant_name())?;
+        Ok(ReturnTy::decode(&mut &*bytes)?)
+    }
+}
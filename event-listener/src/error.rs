@@ -10,17 +10,29 @@ pub use crate::metadata::{
     InvalidMetadataError,
     MetadataError,
 };
+use crate::{
+    metadata::Metadata,
+    utils::to_hex,
+};
 pub use scale_value::scale::{
     DecodeError,
     EncodeError,
 };
+#[cfg(feature = "substrate-compat")]
 pub use sp_core::crypto::SecretStringError;
+#[cfg(feature = "substrate-compat")]
 pub use sp_runtime::transaction_validity::TransactionValidityError;
+#[cfg(feature = "substrate-compat")]
+use sp_runtime::transaction_validity::{
+    InvalidTransaction,
+    UnknownTransaction,
+};
 
 /// The underlying error enum, generic over the type held by the `Runtime`
 /// variant. Prefer to use the [`Error<E>`] and [`Error`] aliases over
 /// using this type directly.
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum Error {
     /// Codec error.
     #[error("Scale codec error: {0}")]
@@ -31,9 +43,9 @@ pub enum Error {
     /// Serde serialization error
     #[error("Serde json error: {0}")]
     Serialization(#[from] serde_json::error::Error),
-    /// Extrinsic validity error
-    #[error("Transaction Validity Error: {0:?}")]
-    Invalid(TransactionValidityError),
+    /// The node rejected a transaction as invalid.
+    #[error("Invalid transaction: {0}")]
+    Invalid(#[from] ValidityError),
     /// Invalid metadata error
     #[error("Invalid Metadata: {0}")]
     InvalidMetadata(#[from] InvalidMetadataError),
@@ -46,9 +58,162 @@ pub enum Error {
     /// Error encoding from a [`crate::dynamic::Value`].
     #[error("Error encoding from dynamic value: {0}")]
     EncodeValue(#[from] EncodeError<()>),
+    /// Runtime error; the extrinsic was included in a block, but failed.
+    #[error("Runtime error: {0}")]
+    Runtime(DispatchError),
+    /// Transaction progress error.
+    #[error("Transaction error: {0}")]
+    Transaction(#[from] TransactionError),
+    /// Block related error.
+    #[error("Block error: {0}")]
+    Block(#[from] BlockError),
+    /// The node isn't for the chain that the client was configured for.
+    #[error("Chain mismatch: {0}")]
+    ChainMismatch(#[from] ChainMismatchError),
+    /// An SS58 address couldn't be parsed.
+    #[error("SS58 error: {0}")]
+    Ss58(#[from] Ss58Error),
+    /// I/O error, such as when reading or writing a file.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    /// A listener checkpoint couldn't be loaded or saved.
+    #[error("Checkpoint error: {0}")]
+    Checkpoint(String),
+    /// Something couldn't be sent to a sink.
+    #[error("Sink error: {0}")]
+    Sink(String),
+    /// The keys of handled events couldn't be looked up or saved.
+    #[error("Dedup error: {0}")]
+    Dedup(String),
+    /// Listener rules couldn't be loaded or made sense of.
+    #[error("Rule error: {0}")]
+    Rules(String),
+    /// A message template couldn't be parsed.
+    #[error("Template error: {0}")]
+    Template(String),
     /// Other error.
     #[error("Other error: {0}")]
     Other(String),
+    /// Some other error, along with what was being done when it happened.
+    #[error("{source} ({context})")]
+    Context {
+        /// What was being done.
+        context: ErrorContext,
+        /// The error.
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// Add some context about what was being done when the error happened.
+    pub fn context(self, context: ErrorContext) -> Error {
+        Error::Context {
+            context,
+            source: Box::new(self),
+        }
+    }
+
+    /// The error itself, without any of the context added to it.
+    pub fn root(&self) -> &Error {
+        let mut error = self;
+        while let Error::Context { source, .. } = error {
+            error = source;
+        }
+        error
+    }
+
+    /// The context added to the error, outermost first.
+    pub fn contexts(&self) -> impl Iterator<Item = &ErrorContext> {
+        let mut error = self;
+        std::iter::from_fn(move || {
+            match error {
+                Error::Context { context, source } => {
+                    error = source;
+                    Some(context)
+                }
+                _ => None,
+            }
+        })
+    }
+
+    /// What kind of error this is, looking through any context added to it.
+    pub fn kind(&self) -> ErrorKind {
+        match self.root() {
+            Error::Codec(_) => ErrorKind::Codec,
+            Error::Rpc(e) => {
+                match e {
+                    RpcError::Disconnected(_) => ErrorKind::RpcDisconnected,
+                    RpcError::RequestTimeout => ErrorKind::RpcTimeout,
+                    RpcError::SubscriptionDropped(_) => ErrorKind::RpcSubscriptionDropped,
+                    RpcError::Call { .. } => ErrorKind::RpcCall,
+                    RpcError::InvalidResponse(_) => ErrorKind::RpcInvalidResponse,
+                    RpcError::Other(_) => ErrorKind::Rpc,
+                }
+            }
+            Error::Serialization(_) => ErrorKind::Serialization,
+            Error::Invalid(_) => ErrorKind::InvalidTransaction,
+            Error::InvalidMetadata(_) => ErrorKind::InvalidMetadata,
+            Error::Metadata(_) => ErrorKind::Metadata,
+            Error::DecodeValue(_) => ErrorKind::DecodeValue,
+            Error::EncodeValue(_) => ErrorKind::EncodeValue,
+            Error::Runtime(_) => ErrorKind::Runtime,
+            Error::Transaction(_) => ErrorKind::Transaction,
+            Error::Block(_) => ErrorKind::Block,
+            Error::ChainMismatch(_) => ErrorKind::ChainMismatch,
+            Error::Ss58(_) => ErrorKind::Ss58,
+            Error::Io(_) => ErrorKind::Io,
+            Error::Checkpoint(_) => ErrorKind::Checkpoint,
+            Error::Sink(_) => ErrorKind::Sink,
+            Error::Dedup(_) => ErrorKind::Dedup,
+            Error::Rules(_) => ErrorKind::Rules,
+            Error::Template(_) => ErrorKind::Template,
+            Error::Other(_) | Error::Context { .. } => ErrorKind::Other,
+        }
+    }
+
+    /// A stable numeric code for the kind of error, for reporting to external
+    /// systems. See [`ErrorKind::code()`].
+    pub fn code(&self) -> u16 {
+        self.kind().code()
+    }
+
+    /// Is the error likely to be temporary, so that trying again (perhaps after
+    /// reconnecting) may well succeed? This is the case for lost connections,
+    /// timeouts and dropped subscriptions, and for blocks which the node doesn't
+    /// have yet.
+    pub fn is_retryable(&self) -> bool {
+        match self.root() {
+            Error::Rpc(e) => {
+                matches!(
+                    e,
+                    RpcError::Disconnected(_)
+                        | RpcError::RequestTimeout
+                        | RpcError::SubscriptionDropped(_)
+                )
+            }
+            Error::Block(BlockError::BlockNumberNotFound(_)) => true,
+            Error::Transaction(TransactionError::SubscriptionEnded) => true,
+            _ => false,
+        }
+    }
+
+    /// Was the connection to the node lost? If so, a new connection is needed
+    /// before trying again.
+    pub fn is_disconnected(&self) -> bool {
+        matches!(self.root(), Error::Rpc(e) if e.is_disconnected())
+    }
+
+    /// Did something fail to decode? This usually means that the [`crate::Config`]
+    /// or metadata in use doesn't match the chain, so trying again won't help.
+    pub fn is_decoding(&self) -> bool {
+        matches!(
+            self.root(),
+            Error::Codec(_)
+                | Error::DecodeValue(_)
+                | Error::Serialization(_)
+                | Error::Rpc(RpcError::InvalidResponse(_))
+        )
+    }
 }
 
 impl From<String> for Error {
@@ -57,10 +222,731 @@ impl From<String> for Error {
     }
 }
 
-/// An RPC error. Since we are generic over the RPC client that is used,
-/// the error is any custom string.
-#[derive(Debug, thiserror::Error)]
-#[error("RPC error: {0}")]
-pub struct RpcError(pub String);
+/// The kind of an [`Error`], handed back from [`Error::kind()`]. Each kind has a
+/// stable numeric code and name, which won't change between releases; new
+/// kinds may be added.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+#[repr(u16)]
+pub enum ErrorKind {
+    /// [`Error::Codec`].
+    Codec = 100,
+    /// [`Error::Serialization`].
+    Serialization = 101,
+    /// [`Error::DecodeValue`].
+    DecodeValue = 102,
+    /// [`Error::EncodeValue`].
+    EncodeValue = 103,
+    /// [`RpcError::Other`].
+    Rpc = 200,
+    /// [`RpcError::Disconnected`].
+    RpcDisconnected = 201,
+    /// [`RpcError::RequestTimeout`].
+    RpcTimeout = 202,
+    /// [`RpcError::SubscriptionDropped`].
+    RpcSubscriptionDropped = 203,
+    /// [`RpcError::Call`].
+    RpcCall = 204,
+    /// [`RpcError::InvalidResponse`].
+    RpcInvalidResponse = 205,
+    /// [`Error::Metadata`].
+    Metadata = 300,
+    /// [`Error::InvalidMetadata`].
+    InvalidMetadata = 301,
+    /// [`Error::ChainMismatch`].
+    ChainMismatch = 302,
+    /// [`Error::Invalid`].
+    InvalidTransaction = 400,
+    /// [`Error::Runtime`].
+    Runtime = 401,
+    /// [`Error::Transaction`].
+    Transaction = 402,
+    /// [`Error::Block`].
+    Block = 500,
+    /// [`Error::Ss58`].
+    Ss58 = 600,
+    /// [`Error::Io`].
+    Io = 700,
+    /// [`Error::Checkpoint`].
+    Checkpoint = 701,
+    /// [`Error::Sink`].
+    Sink = 702,
+    /// [`Error::Dedup`].
+    Dedup = 703,
+    /// [`Error::Rules`].
+    Rules = 800,
+    /// [`Error::Template`].
+    Template = 801,
+    /// [`Error::Other`].
+    Other = 900,
+}
+
+impl ErrorKind {
+    /// The stable numeric code of this kind of error. Codes are grouped by the
+    /// hundred: 1xx for encoding and decoding, 2xx for RPC, 3xx for metadata
+    /// and chain checks, 4xx for transactions, 5xx for blocks, 6xx for
+    /// addresses, 7xx for files and other storage, 8xx for
+    /// configuration, and 9xx for anything else.
+    pub fn code(self) -> u16 {
+        self as u16
+    }
+
+    /// The stable name of this kind of error, in snake case.
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Codec => "codec",
+            ErrorKind::Serialization => "serialization",
+            ErrorKind::DecodeValue => "decode_value",
+            ErrorKind::EncodeValue => "encode_value",
+            ErrorKind::Rpc => "rpc",
+            ErrorKind::RpcDisconnected => "rpc_disconnected",
+            ErrorKind::RpcTimeout => "rpc_timeout",
+            ErrorKind::RpcSubscriptionDropped => "rpc_subscription_dropped",
+            ErrorKind::RpcCall => "rpc_call",
+            ErrorKind::RpcInvalidResponse => "rpc_invalid_response",
+            ErrorKind::Metadata => "metadata",
+            ErrorKind::InvalidMetadata => "invalid_metadata",
+            ErrorKind::ChainMismatch => "chain_mismatch",
+            ErrorKind::InvalidTransaction => "invalid_transaction",
+            ErrorKind::Runtime => "runtime",
+            ErrorKind::Transaction => "transaction",
+            ErrorKind::Block => "block",
+            ErrorKind::Ss58 => "ss58",
+            ErrorKind::Io => "io",
+            ErrorKind::Checkpoint => "checkpoint",
+            ErrorKind::Sink => "sink",
+            ErrorKind::Dedup => "dedup",
+            ErrorKind::Rules => "rules",
+            ErrorKind::Template => "template",
+            ErrorKind::Other => "other",
+        }
+    }
+}
 
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What was being done when an [`Error`] happened. See [`Error::context()`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ErrorContext {
+    /// An RPC method was being called, or its response decoded.
+    Rpc {
+        /// The name of the method.
+        method: String,
+        /// The parameters it was called with, as JSON. Long parameters are
+        /// truncated.
+        params: Option<String>,
+    },
+    /// Something was being fetched from a block.
+    Block {
+        /// The hash of the block, as a hex string.
+        hash: String,
+    },
+    /// An event was being decoded.
+    Event {
+        /// The hash of the block that the event is in, as a hex string.
+        block_hash: String,
+        /// The index of the event in the block.
+        index: u32,
+        /// The pallet and name of the event, as `Pallet::Event`, if they
+        /// could be decoded.
+        name: Option<String>,
+    },
+    /// An extrinsic was being decoded.
+    Extrinsic {
+        /// The hash of the block that the extrinsic is in, as a hex string.
+        block_hash: String,
+        /// The index of the extrinsic in the block.
+        index: u32,
+    },
+    /// One of the chains followed by a [`crate::listener::MultiChainListener`]
+    /// was being listened to.
+    Chain {
+        /// The identifier of the chain.
+        id: String,
+    },
+}
+
+impl ErrorContext {
+    // Parameters longer than this are truncated; extrinsics in particular can be big.
+    const MAX_PARAMS_LEN: usize = 256;
+
+    /// Context for calling the RPC method given.
+    pub fn rpc(method: &str, params: Option<&str>) -> ErrorContext {
+        let params = params.map(|params| {
+            match params.char_indices().nth(Self::MAX_PARAMS_LEN) {
+                Some((end, _)) => format!("{}...", &params[..end]),
+                None => params.to_owned(),
+            }
+        });
+        ErrorContext::Rpc {
+            method: method.to_owned(),
+            params,
+        }
+    }
+
+    /// Context for fetching something from the block with the hash given.
+    pub fn block(hash: impl AsRef<[u8]>) -> ErrorContext {
+        ErrorContext::Block {
+            hash: to_hex(hash),
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorContext::Rpc { method, params } => {
+                write!(f, "calling {method}")?;
+                if let Some(params) = params {
+                    write!(f, " with params {params}")?;
+                }
+                Ok(())
+            }
+            ErrorContext::Block { hash } => write!(f, "in block {hash}"),
+            ErrorContext::Event {
+                block_hash,
+                index,
+                name,
+            } => {
+                write!(f, "decoding event {index}")?;
+                if let Some(name) = name {
+                    write!(f, " ({name})")?;
+                }
+                write!(f, " in block {block_hash}")
+            }
+            ErrorContext::Extrinsic { block_hash, index } => {
+                write!(f, "decoding extrinsic {index} in block {block_hash}")
+            }
+            ErrorContext::Chain { id } => write!(f, "listening to chain {id}"),
+        }
+    }
+}
 
+/// An RPC error. Since we are generic over the RPC client that is used, each
+/// [`crate::rpc::RpcClientT`] implementation maps its own errors onto these.
+#[derive(Clone, Debug, Eq, thiserror::Error, PartialEq)]
+#[non_exhaustive]
+pub enum RpcError {
+    /// The connection to the node was lost (or couldn't be made). A new
+    /// connection is needed to carry on.
+    #[error("Disconnected from the node: {0}")]
+    Disconnected(String),
+    /// The node didn't respond to a request in time.
+    #[error("The request timed out")]
+    RequestTimeout,
+    /// The node no longer knows about a subscription, so a new one needs to be
+    /// started to carry on.
+    #[error("The subscription was dropped: {0}")]
+    SubscriptionDropped(String),
+    /// The node responded with a JSON-RPC error object.
+    #[error("JSON-RPC error {code}: {message}")]
+    Call {
+        /// The error code.
+        code: i32,
+        /// The error message.
+        message: String,
+        /// Any additional data about the error, as a JSON string.
+        data: Option<String>,
+    },
+    /// The node responded with something that isn't a valid JSON-RPC response.
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
+    /// Some other error, such as invalid parameters being given to the client.
+    #[error("{0}")]
+    Other(String),
+}
+
+impl RpcError {
+    /// The JSON-RPC error code for a method that doesn't exist.
+    pub const METHOD_NOT_FOUND_CODE: i32 = -32601;
+    /// The JSON-RPC error code for invalid method parameters.
+    pub const INVALID_PARAMS_CODE: i32 = -32602;
+
+    /// Is this a disconnect from the node?
+    pub fn is_disconnected(&self) -> bool {
+        matches!(self, RpcError::Disconnected(_))
+    }
+
+    /// Did the node respond that the method called doesn't exist?
+    pub fn is_method_not_found(&self) -> bool {
+        matches!(self, RpcError::Call { code, .. } if *code == Self::METHOD_NOT_FOUND_CODE)
+    }
+}
+
+/// The error that an extrinsic failed with, taken from the `dispatch_error`
+/// of the `System::ExtrinsicFailed` event that it emitted.
+#[derive(Clone, Debug, Eq, thiserror::Error, PartialEq)]
+#[non_exhaustive]
+pub enum DispatchError {
+    /// An error emitted by some pallet, resolved through the metadata.
+    #[error("{0}")]
+    Module(ModuleError),
+    /// An error emitted by some pallet, which isn't in the metadata.
+    #[error("Module error: pallet index {}, error {:?}", .0.pallet_index, .0.error)]
+    UnknownModule(RawModuleError),
+    /// Some other error, such as `BadOrigin`, given by name.
+    #[error("{0}")]
+    Other(String),
+}
+
+impl DispatchError {
+    /// Resolve the pallet and error indices of a module error through the
+    /// metadata given.
+    pub fn from_module_error(metadata: &Metadata, raw: RawModuleError) -> DispatchError {
+        match metadata.error(raw.pallet_index, raw.error_index()) {
+            Ok(details) => {
+                DispatchError::Module(ModuleError {
+                    pallet: details.pallet().to_owned(),
+                    error: details.error().to_owned(),
+                    docs: details.docs().to_vec(),
+                    raw,
+                })
+            }
+            Err(_) => DispatchError::UnknownModule(raw),
+        }
+    }
+}
+
+/// An error emitted by some pallet, with its name and docs from the metadata.
+/// This displays as `Pallet::Error`.
+#[derive(Clone, Debug, Eq, thiserror::Error, PartialEq)]
+#[error("{pallet}::{error}")]
+pub struct ModuleError {
+    /// The name of the pallet that the error came from.
+    pub pallet: String,
+    /// The name of the error.
+    pub error: String,
+    /// The documentation of the error.
+    pub docs: Vec<String>,
+    /// The indices that the error was encoded as.
+    pub raw: RawModuleError,
+}
+
+/// A module error as it's encoded in a `DispatchError`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RawModuleError {
+    /// The index of the pallet that the error came from.
+    pub pallet_index: u8,
+    /// The encoded error. The first byte is the index of the error variant.
+    pub error: [u8; 4],
+}
+
+impl RawModuleError {
+    /// The index of the error variant in the pallet.
+    pub fn error_index(&self) -> u8 {
+        self.error[0]
+    }
+}
+
+/// Why the node rejected a transaction as invalid, mapped from the
+/// [`TransactionValidityError`] of the runtime (or the RPC error describing it).
+#[derive(Clone, Debug, Eq, thiserror::Error, PartialEq)]
+#[non_exhaustive]
+pub enum ValidityError {
+    /// The nonce is ahead of the account's next one, so the transaction won't be
+    /// valid until the transactions before it are.
+    #[error("The nonce is in the future")]
+    FutureNonce,
+    /// The nonce has already been used.
+    #[error("The nonce has already been used")]
+    StaleNonce,
+    /// The account can't pay the fees, perhaps because its balance is too low.
+    #[error("The fees can't be paid")]
+    InsufficientFee,
+    /// The signature is invalid.
+    #[error("The signature is invalid")]
+    BadProof,
+    /// The transaction would exhaust the resources of the block, such as its
+    /// weight or length limits.
+    #[error("The transaction would exhaust the block limits")]
+    ExhaustsResources,
+    /// The block that a mortal transaction was signed at is too far in the past,
+    /// or isn't known.
+    #[error("The transaction's birth block is ancient")]
+    AncientBirthBlock,
+    /// The call isn't expected, or can't be made with this origin.
+    #[error("The call of the transaction isn't expected")]
+    Call,
+    /// The signer of the transaction isn't valid.
+    #[error("The signer of the transaction isn't valid")]
+    BadSigner,
+    /// A mandatory transaction was submitted, or failed.
+    #[error("The transaction is mandatory, and can't be submitted")]
+    Mandatory,
+    /// Some information needed to validate the transaction couldn't be looked up.
+    #[error("Information needed to validate the transaction couldn't be looked up")]
+    CannotLookup,
+    /// There's no validator for the unsigned transaction.
+    #[error("There is no validator for the unsigned transaction")]
+    NoUnsignedValidator,
+    /// A runtime specific error which makes the transaction invalid.
+    #[error("Custom invalid transaction error {0}")]
+    Custom(u8),
+    /// A runtime specific error which means that the validity of the
+    /// transaction couldn't be determined.
+    #[error("Custom unknown transaction validity error {0}")]
+    UnknownCustom(u8),
+    /// Some other reason, as described by the node.
+    #[error("{0}")]
+    Other(String),
+}
+
+impl ValidityError {
+    /// The JSON-RPC error code that Substrate nodes reject invalid transactions with.
+    pub const INVALID_CODE: i32 = 1010;
+    /// The JSON-RPC error code that Substrate nodes reject transactions with when
+    /// their validity is unknown.
+    pub const UNKNOWN_VALIDITY_CODE: i32 = 1011;
+
+    /// Map the error that a Substrate node responds to an invalid transaction
+    /// with. Returns `None` for any other error.
+    pub fn from_rpc_error(error: &RpcError) -> Option<ValidityError> {
+        let (code, data) = match error {
+            RpcError::Call { code, data, .. } => (*code, data.as_deref()),
+            _ => return None,
+        };
+        if code != Self::INVALID_CODE && code != Self::UNKNOWN_VALIDITY_CODE {
+            return None
+        }
+
+        // The reason is a JSON string, such as "Transaction is outdated".
+        let reason = data
+            .and_then(|data| serde_json::from_str::<String>(data).ok())
+            .unwrap_or_default();
+        if let Some(custom) = reason.strip_prefix("Custom error: ") {
+            return Some(match custom.parse() {
+                Ok(n) if code == Self::INVALID_CODE => ValidityError::Custom(n),
+                Ok(n) => ValidityError::UnknownCustom(n),
+                Err(_) => ValidityError::Other(reason),
+            })
+        }
+        let error = match reason.as_str() {
+            "Transaction will be valid in the future" => ValidityError::FutureNonce,
+            "Transaction is outdated" => ValidityError::StaleNonce,
+            "Inability to pay some fees (e.g. account balance too low)" => {
+                ValidityError::InsufficientFee
+            }
+            "Transaction has a bad signature" => ValidityError::BadProof,
+            "Transaction would exhaust the block limits" => {
+                ValidityError::ExhaustsResources
+            }
+            "Transaction has an ancient birth block" => ValidityError::AncientBirthBlock,
+            "Transaction call is not expected" => ValidityError::Call,
+            "Transaction signer is not valid" => ValidityError::BadSigner,
+            "Transaction dispatch is mandatory; transactions must not be validated." |
+            "Transaction dispatch is mandatory; transactions may not have mandatory dispatches." => {
+                ValidityError::Mandatory
+            }
+            "A call was labelled as mandatory, but resulted in an Error." => {
+                ValidityError::Mandatory
+            }
+            "Could not lookup information required to validate the transaction" => {
+                ValidityError::CannotLookup
+            }
+            "Could not find an unsigned validator for the unsigned transaction" => {
+                ValidityError::NoUnsignedValidator
+            }
+            "" => ValidityError::Other(error.to_string()),
+            _ => ValidityError::Other(reason),
+        };
+        Some(error)
+    }
+
+    /// Is the nonce of the transaction wrong? If so, it may be resubmitted with
+    /// a fresh nonce.
+    pub fn is_nonce_error(&self) -> bool {
+        matches!(self, ValidityError::FutureNonce | ValidityError::StaleNonce)
+    }
+}
+
+#[cfg(feature = "substrate-compat")]
+impl From<TransactionValidityError> for ValidityError {
+    fn from(error: TransactionValidityError) -> Self {
+        match error {
+            TransactionValidityError::Invalid(invalid) => {
+                match invalid {
+                    InvalidTransaction::Future => ValidityError::FutureNonce,
+                    InvalidTransaction::Stale => ValidityError::StaleNonce,
+                    InvalidTransaction::Payment => ValidityError::InsufficientFee,
+                    InvalidTransaction::BadProof => ValidityError::BadProof,
+                    InvalidTransaction::ExhaustsResources => {
+                        ValidityError::ExhaustsResources
+                    }
+                    InvalidTransaction::AncientBirthBlock => {
+                        ValidityError::AncientBirthBlock
+                    }
+                    InvalidTransaction::Call => ValidityError::Call,
+                    InvalidTransaction::BadMandatory
+                    | InvalidTransaction::MandatoryDispatch => ValidityError::Mandatory,
+                    InvalidTransaction::Custom(n) => ValidityError::Custom(n),
+                    #[allow(unreachable_patterns)]
+                    other => ValidityError::Other(format!("{:?}", other)),
+                }
+            }
+            TransactionValidityError::Unknown(unknown) => {
+                match unknown {
+                    UnknownTransaction::CannotLookup => ValidityError::CannotLookup,
+                    UnknownTransaction::NoUnsignedValidator => {
+                        ValidityError::NoUnsignedValidator
+                    }
+                    UnknownTransaction::Custom(n) => ValidityError::UnknownCustom(n),
+                }
+            }
+        }
+    }
+}
+
+/// Transaction error.
+#[derive(Clone, Debug, Eq, thiserror::Error, PartialEq)]
+#[non_exhaustive]
+pub enum TransactionError {
+    /// The finality subscription expired (after ~512 blocks we give up if the
+    /// block hasn't yet been finalized).
+    #[error("The finality subscription expired")]
+    FinalityTimeout,
+    /// The block hash that the transaction was added to could not be found.
+    /// This is probably because the block was retracted before being finalized.
+    #[error("The block containing the transaction can no longer be found (perhaps it was on a non-finalized fork?)")]
+    BlockNotFound,
+    /// The transaction was replaced in the pool by another with the same
+    /// sender and nonce.
+    #[error("The transaction was usurped by another with the same nonce")]
+    Usurped,
+    /// The transaction was dropped from the pool.
+    #[error("The transaction was dropped from the pool")]
+    Dropped,
+    /// The transaction is no longer valid.
+    #[error("The transaction is no longer valid")]
+    Invalid,
+    /// The transaction subscription ended before the transaction was finalized.
+    #[error("The transaction subscription ended unexpectedly")]
+    SubscriptionEnded,
+    /// The metadata lists a signed extension that we don't know how to provide
+    /// the data for.
+    #[error("Don't know how to construct the data for the signed extension '{0}'; provide it with `TxParams::signed_extension()`")]
+    UnsupportedSignedExtension(String),
+}
+
+/// Block error
+#[derive(Clone, Debug, Eq, thiserror::Error, PartialEq)]
+#[non_exhaustive]
+pub enum BlockError {
+    /// The block hash we were looking for could not be found.
+    #[error("Could not find a block with hash {0} (perhaps it was on a non-finalized fork?)")]
+    BlockHashNotFound(String),
+    /// There is no block with the given number.
+    #[error("Could not find a block with number {0}")]
+    BlockNumberNotFound(u64),
+    /// Extrinsics with this version aren't supported.
+    #[error("Unsupported extrinsic version {0}; only version 4 is supported")]
+    UnsupportedVersion(u8),
+    /// The metadata doesn't describe some part of the extrinsic.
+    #[error("The extrinsic type in the metadata has no '{0}' type parameter")]
+    MissingType(&'static str),
+    /// Some bytes were left over after decoding an extrinsic.
+    #[error("After decoding the extrinsic, {0} bytes were left over")]
+    LeftoverBytes(usize),
+    /// The header we were handed back doesn't hash to the block hash we expected.
+    #[error("Header hashes to {actual}, but we expected the block hash {expected}")]
+    HeaderHashMismatch {
+        /// The block hash we expected.
+        expected: String,
+        /// The hash of the header that we were handed.
+        actual: String,
+    },
+    /// A header's parent hash doesn't match the hash of the block before it.
+    #[error("Header #{number} has parent hash {actual}, but we expected {expected}")]
+    ParentHashMismatch {
+        /// The number of the header whose parent hash is wrong.
+        number: u64,
+        /// The parent hash we expected.
+        expected: String,
+        /// The parent hash in the header.
+        actual: String,
+    },
+    /// Two headers that were expected to follow each other do not.
+    #[error("Header #{child} does not directly follow header #{parent}")]
+    NotConsecutive {
+        /// The number of the parent header.
+        parent: u64,
+        /// The number of the child header.
+        child: u64,
+    },
+}
+
+impl BlockError {
+    /// Produce an error that a block with the given hash cannot be found.
+    pub fn block_hash_not_found(hash: impl AsRef<[u8]>) -> BlockError {
+        let hash = to_hex(hash);
+        BlockError::BlockHashNotFound(hash)
+    }
+}
+
+/// An SS58 address couldn't be parsed. See [`crate::config::Ss58Format`].
+#[derive(Clone, Debug, Eq, thiserror::Error, PartialEq)]
+#[non_exhaustive]
+pub enum Ss58Error {
+    /// The address isn't valid base58.
+    #[error("The address isn't valid base58")]
+    BadBase58,
+    /// The address has a prefix which is reserved.
+    #[error("The address has a reserved prefix")]
+    BadPrefix,
+    /// The address (after its prefix) has a length that no valid address has, or
+    /// that doesn't match the length of an account.
+    #[error("The address has an invalid length of {0} bytes")]
+    BadLength(usize),
+    /// The checksum of the address is wrong.
+    #[error("The address has an invalid checksum")]
+    BadChecksum,
+    /// The address is for a different chain.
+    #[error("Expected an address with the SS58 prefix {expected}, but it has {actual}")]
+    WrongPrefix {
+        /// The prefix of the chain.
+        expected: u16,
+        /// The prefix of the address.
+        actual: u16,
+    },
+}
+
+/// The chain that a node is running doesn't match what was expected of it.
+/// See [`crate::client::ChainExpectations`].
+#[derive(Clone, Debug, Eq, thiserror::Error, PartialEq)]
+#[non_exhaustive]
+pub enum ChainMismatchError {
+    /// The genesis hash of the chain isn't the expected one.
+    #[error("Expected genesis hash {expected}, but the node's is {actual}")]
+    GenesisHash {
+        /// The expected genesis hash, as a hex string.
+        expected: String,
+        /// The genesis hash of the node's chain, as a hex string.
+        actual: String,
+    },
+    /// A chain property (such as `ss58Format`) isn't the expected value.
+    #[error("Expected the chain property '{name}' to be {expected}, but it is {}", .actual.as_deref().unwrap_or("missing"))]
+    Property {
+        /// The name of the property.
+        name: &'static str,
+        /// The expected value.
+        expected: String,
+        /// The actual value, or `None` if the chain doesn't have this property.
+        actual: Option<String>,
+    },
+    /// A type in the runtime metadata doesn't have the same shape as the
+    /// corresponding type in the [`crate::Config`].
+    #[error("The runtime's {name} type ({runtime_type}) doesn't match the {name} type of the config ({config_type})")]
+    Type {
+        /// Which type is mismatched, such as `AccountId`.
+        name: &'static str,
+        /// The name of the type in the config.
+        config_type: String,
+        /// The path of the type in the runtime metadata.
+        runtime_type: String,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn errors_are_classified() {
+        let disconnected = Error::Rpc(RpcError::Disconnected("closed".into()));
+        assert!(disconnected.is_retryable());
+        assert!(disconnected.is_disconnected());
+        assert!(!disconnected.is_decoding());
+
+        let timeout = Error::Rpc(RpcError::RequestTimeout);
+        assert!(timeout.is_retryable());
+        assert!(!timeout.is_disconnected());
+
+        let call = Error::Rpc(RpcError::Call {
+            code: RpcError::METHOD_NOT_FOUND_CODE,
+            message: "Method not found".into(),
+            data: None,
+        });
+        assert!(!call.is_retryable());
+
+        let codec = Error::Codec(codec::Error::from("bad bytes"));
+        assert!(codec.is_decoding());
+        assert!(!codec.is_retryable());
+
+        let invalid = Error::Rpc(RpcError::InvalidResponse("not JSON".into()));
+        assert!(invalid.is_decoding());
+    }
+
+    #[test]
+    fn invalid_transactions_are_mapped() {
+        let rpc_error = |code, data: &str| {
+            RpcError::Call {
+                code,
+                message: "Invalid Transaction".into(),
+                data: Some(serde_json::to_string(data).unwrap()),
+            }
+        };
+
+        let stale = ValidityError::from_rpc_error(&rpc_error(1010, "Transaction is outdated"));
+        assert_eq!(stale, Some(ValidityError::StaleNonce));
+        assert!(stale.unwrap().is_nonce_error());
+
+        assert_eq!(
+            ValidityError::from_rpc_error(&rpc_error(1010, "Custom error: 3")),
+            Some(ValidityError::Custom(3))
+        );
+        assert_eq!(
+            ValidityError::from_rpc_error(&rpc_error(1011, "Custom error: 3")),
+            Some(ValidityError::UnknownCustom(3))
+        );
+        assert_eq!(
+            ValidityError::from_rpc_error(&rpc_error(-32601, "Transaction is outdated")),
+            None
+        );
+    }
+
+    #[cfg(feature = "substrate-compat")]
+    #[test]
+    fn runtime_validity_errors_are_mapped() {
+        assert_eq!(
+            ValidityError::from(TransactionValidityError::Invalid(
+                InvalidTransaction::Payment
+            )),
+            ValidityError::InsufficientFee
+        );
+        assert_eq!(
+            ValidityError::from(TransactionValidityError::Unknown(
+                UnknownTransaction::Custom(3)
+            )),
+            ValidityError::UnknownCustom(3)
+        );
+    }
+
+    #[test]
+    fn context_is_added_and_looked_through() {
+        let error = Error::Rpc(RpcError::RequestTimeout)
+            .context(ErrorContext::rpc("chain_getBlock", Some(&"1".repeat(300))))
+            .context(ErrorContext::block([1u8; 2]));
+
+        assert!(error.is_retryable());
+        assert!(matches!(error.root(), Error::Rpc(RpcError::RequestTimeout)));
+        assert_eq!(error.kind(), ErrorKind::RpcTimeout);
+        assert_eq!(error.code(), 202);
+
+        let contexts: Vec<_> = error.contexts().collect();
+        assert_eq!(contexts.len(), 2);
+        assert_eq!(contexts[0].to_string(), "in block 0x0101");
+        match contexts[1] {
+            ErrorContext::Rpc { method, params } => {
+                assert_eq!(method, "chain_getBlock");
+                assert_eq!(params.as_ref().unwrap().len(), 259);
+            }
+            other => panic!("unexpected context {other:?}"),
+        }
+
+        assert!(error.to_string().starts_with("Rpc error: The request timed out"));
+    }
+}
@@ -0,0 +1,491 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Displaying decoded events the way that they'd be written in Rust.
+
+use super::EventDetails;
+use crate::{
+    config::to_ss58,
+    listener::{
+        format_balance,
+        ChainProperties,
+    },
+    utils::{
+        is_bytes,
+        to_hex,
+        value_as_bytes,
+        value_as_u128,
+    },
+    Metadata,
+};
+use scale_value::{
+    scale::TypeId,
+    Composite,
+    Primitive,
+    Value,
+    ValueDef,
+};
+use std::fmt::{
+    self,
+    Display,
+    Formatter,
+};
+
+/// Shows the event as `Pallet::Variant { field: value, .. }`, or with its fields
+/// in brackets if they're unnamed. Accounts are shown as SS58 addresses (with
+/// the generic Substrate prefix), other byte arrays and sequences as hex, and
+/// big numbers with their digits grouped, such as `1_000_000_000_000`. Use an
+/// [`EventFormatter`] to show them differently.
+///
+/// ```no_run
+/// # use event_listener::{ OnlineClient, PolkadotConfig };
+/// # #[tokio::main]
+/// # async fn main() {
+/// # let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let events = api.events().at(None).await.unwrap();
+/// for event in events.iter() {
+///     // Such as `Balances::Transfer { from: 5Grw.., to: 5FHn.., amount: 10_000_000_000 }`
+///     println!("{}", event.unwrap());
+/// }
+/// # }
+/// ```
+impl Display for EventDetails {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        ShowEvent {
+            event: self,
+            format: &EventFormatter::default(),
+        }
+        .fmt(f)
+    }
+}
+
+/// Shows events the way that their [`Display`] implementation does, but with
+/// accounts shown with the SS58 prefix of the chain, balances with its
+/// decimals, long byte arrays shortened and fields that shouldn't be shown
+/// redacted, for clean log lines and notifications.
+///
+/// ```no_run
+/// # use event_listener::{ OnlineClient, PolkadotConfig };
+/// use event_listener::{
+///     events::EventFormatter,
+///     listener::ChainProperties,
+/// };
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// # let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let properties = api.rpc().system_properties().await.unwrap();
+/// let formatter = EventFormatter::new()
+///     .chain(ChainProperties::from_properties(&properties))
+///     .balance("amount")
+///     .max_bytes(8)
+///     .redact("remark");
+///
+/// let events = api.events().at(None).await.unwrap();
+/// for event in events.iter() {
+///     // Such as `Balances::Transfer { from: 15oF.., to: 14E5.., amount: 1.5 DOT }`
+///     println!("{}", formatter.format(&event.unwrap()));
+/// }
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EventFormatter {
+    chain: ChainProperties,
+    max_bytes: Option<usize>,
+    balances: Vec<String>,
+    redacted: Vec<String>,
+}
+
+impl Default for EventFormatter {
+    fn default() -> Self {
+        EventFormatter {
+            chain: ChainProperties::default(),
+            max_bytes: None,
+            balances: Vec::new(),
+            redacted: Vec::new(),
+        }
+    }
+}
+
+impl EventFormatter {
+    /// Show events the same way that their [`Display`] implementation does.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Show accounts with the SS58 prefix of this chain, and balances (see
+    /// [`EventFormatter::balance()`]) in its native token.
+    pub fn chain(mut self, properties: ChainProperties) -> Self {
+        self.chain = properties;
+        self
+    }
+
+    /// Shorten byte arrays and sequences longer than this to their first and
+    /// last bytes, such as `0x1234…cdef`. By default they're shown in full.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Show fields with this name, at any depth, as amounts of the chain's
+    /// native token, such as `1.5 DOT`. Can be given more than once.
+    pub fn balance(mut self, field: impl Into<String>) -> Self {
+        self.balances.push(field.into());
+        self
+    }
+
+    /// Show fields with this name, at any depth, as `<redacted>` rather than
+    /// with their values. Can be given more than once.
+    pub fn redact(mut self, field: impl Into<String>) -> Self {
+        self.redacted.push(field.into());
+        self
+    }
+
+    /// Show an event.
+    pub fn format(&self, event: &EventDetails) -> String {
+        ShowEvent {
+            event,
+            format: self,
+        }
+        .to_string()
+    }
+
+    /// Show a decoded value, such as one of the fields of an event.
+    pub fn format_value(&self, value: &Value<TypeId>, metadata: &Metadata) -> String {
+        ShowValue {
+            value,
+            format: self,
+            metadata,
+        }
+        .to_string()
+    }
+}
+
+struct ShowEvent<'a> {
+    event: &'a EventDetails,
+    format: &'a EventFormatter,
+}
+
+impl Display for ShowEvent<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let event = self.event;
+        write!(f, "{}::{}", event.pallet_name(), event.variant_name())?;
+        match event.decoded_fields() {
+            Ok(fields) => {
+                ShowComposite {
+                    composite: fields,
+                    format: self.format,
+                    metadata: event.metadata(),
+                }
+                .fmt(f)
+            }
+            Err(_) => write!(f, " <undecodable>"),
+        }
+    }
+}
+
+struct ShowComposite<'a> {
+    composite: &'a Composite<TypeId>,
+    format: &'a EventFormatter,
+    metadata: &'a Metadata,
+}
+
+impl ShowComposite<'_> {
+    fn show_field(&self, f: &mut Formatter<'_>, name: &str, value: &Value<TypeId>) -> fmt::Result {
+        let format = self.format;
+        if format.redacted.iter().any(|n| n == name) {
+            return write!(f, "{}: <redacted>", name)
+        }
+        if format.balances.iter().any(|n| n == name) {
+            if let Some(amount) = value_as_u128(value) {
+                let chain = &format.chain;
+                let balance =
+                    format_balance(amount, chain.token_decimals, chain.token_symbol.as_deref());
+                return write!(f, "{}: {}", name, balance)
+            }
+        }
+        write!(f, "{}: {}", name, self.show(value))
+    }
+
+    fn show<'a>(&'a self, value: &'a Value<TypeId>) -> ShowValue<'a> {
+        ShowValue {
+            value,
+            format: self.format,
+            metadata: self.metadata,
+        }
+    }
+}
+
+impl Display for ShowComposite<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.composite {
+            Composite::Named(fields) if fields.is_empty() => Ok(()),
+            Composite::Unnamed(fields) if fields.is_empty() => Ok(()),
+            Composite::Named(fields) => {
+                write!(f, " {{ ")?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    self.show_field(f, name, value)?;
+                }
+                write!(f, " }}")
+            }
+            Composite::Unnamed(fields) => {
+                write!(f, "(")?;
+                for (i, value) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", self.show(value))?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+struct ShowValue<'a> {
+    value: &'a Value<TypeId>,
+    format: &'a EventFormatter,
+    metadata: &'a Metadata,
+}
+
+impl ShowValue<'_> {
+    fn composite<'a>(&'a self, composite: &'a Composite<TypeId>) -> ShowComposite<'a> {
+        ShowComposite {
+            composite,
+            format: self.format,
+            metadata: self.metadata,
+        }
+    }
+}
+
+impl Display for ShowValue<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let types = &self.metadata.runtime_metadata().types;
+        let ident = types
+            .resolve(self.value.context.id())
+            .and_then(|ty| ty.path().ident());
+        if ident.as_deref() == Some("AccountId32") {
+            if let Some(bytes) = value_as_bytes(self.value).filter(|b| b.len() == 32) {
+                return write!(f, "{}", to_ss58(&bytes, self.format.chain.ss58_format))
+            }
+        }
+
+        match &self.value.value {
+            ValueDef::Composite(_) if is_bytes(self.value.context, self.metadata) => {
+                let bytes = value_as_bytes(self.value).unwrap_or_default();
+                match self.format.max_bytes {
+                    Some(max) if bytes.len() > max => {
+                        let end = bytes.len() - max / 2;
+                        let (start, end) = (&bytes[..max - max / 2], &bytes[end..]);
+                        write!(f, "0x{}…{}", hex::encode(start), hex::encode(end))
+                    }
+                    _ => f.write_str(&to_hex(bytes)),
+                }
+            }
+            // Look through wrappers like `Weight(u64)`, rather than showing them
+            // in brackets.
+            ValueDef::Composite(Composite::Unnamed(values)) if values.len() == 1 => {
+                ShowValue {
+                    value: &values[0],
+                    format: self.format,
+                    metadata: self.metadata,
+                }
+                .fmt(f)
+            }
+            ValueDef::Composite(c @ Composite::Named(_)) => {
+                // Without the leading space that follows a name.
+                let shown = self.composite(c).to_string();
+                write!(f, "{}", shown.trim_start())
+            }
+            ValueDef::Composite(c) => self.composite(c).fmt(f),
+            ValueDef::Variant(v) => {
+                write!(f, "{}", v.name)?;
+                self.composite(&v.values).fmt(f)
+            }
+            ValueDef::BitSequence(bits) => {
+                let bits: String = bits.iter().map(|b| if *b { '1' } else { '0' }).collect();
+                write!(f, "0b{}", bits)
+            }
+            ValueDef::Primitive(Primitive::U128(n)) => write!(f, "{}", grouped(&n.to_string())),
+            ValueDef::Primitive(Primitive::I128(n)) => {
+                if *n < 0 {
+                    write!(f, "-")?;
+                }
+                write!(f, "{}", grouped(&n.unsigned_abs().to_string()))
+            }
+            ValueDef::Primitive(Primitive::U256(bytes) | Primitive::I256(bytes)) => {
+                f.write_str(&to_hex(bytes))
+            }
+            ValueDef::Primitive(Primitive::Bool(b)) => write!(f, "{}", b),
+            ValueDef::Primitive(Primitive::Char(c)) => write!(f, "{:?}", c),
+            ValueDef::Primitive(Primitive::String(s)) => write!(f, "{:?}", s),
+        }
+    }
+}
+
+// The digits given, in groups of three separated by underscores, if there are
+// enough of them for that to help.
+fn grouped(digits: &str) -> String {
+    if digits.len() < 5 {
+        return digits.to_owned()
+    }
+    let mut grouped = String::with_capacity(digits.len() * 4 / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push('_');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        config::DEFAULT_SS58_FORMAT,
+        events::Phase,
+        pallets::test_utils::account_value,
+        testing::{
+            test_runtime_metadata,
+            EventsBuilder,
+        },
+        SubstrateConfig,
+    };
+
+    #[test]
+    fn events_are_shown_like_rust() {
+        let events = EventsBuilder::<SubstrateConfig>::new(test_runtime_metadata())
+            .event(
+                Phase::ApplyExtrinsic(1),
+                "Balances",
+                "Transfer",
+                Composite::Named(vec![
+                    ("from".into(), account_value(1)),
+                    ("to".into(), account_value(2)),
+                    ("amount".into(), Value::u128(10_000_000_000)),
+                ]),
+            )
+            .event(
+                Phase::ApplyExtrinsic(1),
+                "System",
+                "Remarked",
+                Composite::Named(vec![
+                    ("sender".into(), account_value(1)),
+                    (
+                        "hash".into(),
+                        Value::unnamed_composite(vec![Value::unnamed_composite(vec![
+                            Value::u128(0xab);
+                            32
+                        ])]),
+                    ),
+                ]),
+            )
+            .event(
+                Phase::ApplyExtrinsic(1),
+                "System",
+                "ExtrinsicSuccess",
+                Composite::Named(vec![(
+                    "dispatch_info".into(),
+                    Value::named_composite(vec![
+                        ("weight".into(), Value::u128(10)),
+                        ("class".into(), Value::variant("Normal", Composite::Unnamed(vec![]))),
+                        ("pays_fee".into(), Value::variant("Yes", Composite::Unnamed(vec![]))),
+                    ]),
+                )]),
+            )
+            .build()
+            .unwrap();
+        let shown: Vec<_> = events.iter().map(|ev| ev.unwrap().to_string()).collect();
+
+        let from = to_ss58(&[1; 32], DEFAULT_SS58_FORMAT);
+        let to = to_ss58(&[2; 32], DEFAULT_SS58_FORMAT);
+        assert_eq!(
+            shown[0],
+            format!(
+                "Balances::Transfer {{ from: {}, to: {}, amount: 10_000_000_000 }}",
+                from, to
+            )
+        );
+        assert_eq!(
+            shown[1],
+            format!("System::Remarked {{ sender: {}, hash: 0x{} }}", from, "ab".repeat(32))
+        );
+        assert_eq!(
+            shown[2],
+            concat!(
+                "System::ExtrinsicSuccess ",
+                "{ dispatch_info: { weight: 10, class: Normal, pays_fee: Yes } }"
+            )
+        );
+    }
+
+    #[test]
+    fn events_are_formatted_as_configured() {
+        let events = EventsBuilder::<SubstrateConfig>::new(test_runtime_metadata())
+            .event(
+                Phase::ApplyExtrinsic(1),
+                "Balances",
+                "Transfer",
+                Composite::Named(vec![
+                    ("from".into(), account_value(1)),
+                    ("to".into(), account_value(2)),
+                    ("amount".into(), Value::u128(15_000_000_000)),
+                ]),
+            )
+            .event(
+                Phase::ApplyExtrinsic(1),
+                "System",
+                "Remarked",
+                Composite::Named(vec![
+                    ("sender".into(), account_value(1)),
+                    (
+                        "hash".into(),
+                        Value::unnamed_composite(vec![Value::unnamed_composite(
+                            (0..32).map(Value::u128).collect(),
+                        )]),
+                    ),
+                ]),
+            )
+            .build()
+            .unwrap();
+        let formatter = EventFormatter::new()
+            .chain(ChainProperties {
+                token_symbol: Some("DOT".into()),
+                token_decimals: 10,
+                ss58_format: 0,
+            })
+            .balance("amount")
+            .redact("to")
+            .max_bytes(4);
+        let shown: Vec<_> = events
+            .iter()
+            .map(|ev| formatter.format(&ev.unwrap()))
+            .collect();
+
+        let from = to_ss58(&[1; 32], 0);
+        assert_eq!(
+            shown[0],
+            format!("Balances::Transfer {{ from: {}, to: <redacted>, amount: 1.5 DOT }}", from)
+        );
+        assert_eq!(
+            shown[1],
+            format!("System::Remarked {{ sender: {}, hash: 0x0001…1e1f }}", from)
+        );
+        // The default formatter shows events like their `Display` does.
+        let event = events.iter().next().unwrap().unwrap();
+        assert_eq!(EventFormatter::new().format(&event), event.to_string());
+    }
+
+    #[test]
+    fn digits_are_grouped() {
+        assert_eq!(grouped("0"), "0");
+        assert_eq!(grouped("1000"), "1000");
+        assert_eq!(grouped("10000"), "10_000");
+        assert_eq!(grouped("123456789"), "123_456_789");
+    }
+}
@@ -4,7 +4,12 @@
 
 //! Subscribing to events.
 
+use super::subscribe_opts::{
+    EventNames,
+    SubscribeOpts,
+};
 use crate::{
+    blocks::header_hash,
     client::OnlineClientT,
     error::Error,
     events::EventsClient,
@@ -13,13 +18,14 @@ use crate::{
 };
 use derivative::Derivative;
 use futures::{
-    stream::BoxStream,
-    Future,
-    FutureExt,
+    future::BoxFuture,
+    stream::{
+        BoxStream,
+        FuturesOrdered,
+    },
     Stream,
     StreamExt,
 };
-use sp_runtime::traits::Header;
 use std::{
     marker::Unpin,
     task::Poll,
@@ -47,10 +53,21 @@ pub type EventSub<Item> = Subscription<Item>;
 #[derivative(Debug(bound = "Sub: std::fmt::Debug, Client: std::fmt::Debug"))]
 pub struct EventSubscription<T: Config, Client, Sub> {
     finished: bool,
-    client: Client,
+    // Kept for the whole subscription, so that the metadata it caches is reused
+    // from block to block.
+    events: EventsClient<T, Client>,
     block_header_subscription: Sub,
+    // Whether the block header subscription has ended, and the error it ended
+    // with, if any, to hand back once the events being fetched have been.
+    headers_finished: bool,
+    error: Option<Error>,
+    // The events of the blocks being fetched, in the order they're handed back,
+    // and how many can be fetched at once.
     #[derivative(Debug = "ignore")]
-    at: Option<std::pin::Pin<Box<dyn Future<Output = Result<Events<T>, Error>> + Send>>>,
+    fetching: FuturesOrdered<BoxFuture<'static, Result<Events<T>, Error>>>,
+    buffer_size: usize,
+    names: EventNames,
+    strict: bool,
 }
 
 impl<T: Config, Client, Sub, E: Into<Error>> EventSubscription<T, Client, Sub>
@@ -62,9 +79,34 @@ where
     pub fn new(client: Client, block_header_subscription: Sub) -> Self {
         EventSubscription {
             finished: false,
-            client,
+            events: EventsClient::new(client),
+            block_header_subscription,
+            headers_finished: false,
+            error: None,
+            fetching: FuturesOrdered::new(),
+            buffer_size: 1,
+            names: EventNames::default(),
+            strict: false,
+        }
+    }
+
+    /// Like [`EventSubscription::new()`], but fetching events with the client
+    /// given, and filtering and decoding them according to the options given.
+    pub(crate) fn with_opts(
+        events: EventsClient<T, Client>,
+        block_header_subscription: Sub,
+        opts: &SubscribeOpts,
+    ) -> Self {
+        EventSubscription {
+            finished: false,
+            events,
             block_header_subscription,
-            at: None,
+            headers_finished: false,
+            error: None,
+            fetching: FuturesOrdered::new(),
+            buffer_size: opts.buffer_size,
+            names: opts.names.clone(),
+            strict: opts.strict,
         }
     }
 
@@ -119,12 +161,13 @@ impl<T: Config, Client, Sub: Unpin> Unpin for EventSubscription<T, Client, Sub>
 // ```
 // fn subscribe_events<T: Config, Evs: Decode>(client: &'_ Client<T>, block_sub: Subscription<T::Header>) -> impl Stream<Item=Result<Events<'_, T, Evs>, Error>> + '_ {
 //     use futures::StreamExt;
-//     block_sub.then(move |block_header_res| async move {
+//     block_sub.map(move |block_header_res| async move {
 //         use sp_runtime::traits::Header;
 //         let block_header = block_header_res?;
 //         let block_hash = block_header.hash();
 //         at(client, block_hash).await
 //     })
+//     .buffered(buffer_size)
 // }
 // ```
 //
@@ -148,39 +191,53 @@ where
             return Poll::Ready(None)
         }
 
-        // If there isn't an `at` function yet that's busy resolving a block hash into
-        // some event details, then poll the block header subscription to get one.
-        if self.at.is_none() {
-            match futures::ready!(self.block_header_subscription.poll_next_unpin(cx)) {
-                None => {
-                    self.finished = true;
-                    return Poll::Ready(None)
+        // Start fetching the events of as many blocks as we're allowed to at once,
+        // from the block headers that are ready.
+        while !self.headers_finished && self.fetching.len() < self.buffer_size {
+            match self.block_header_subscription.poll_next_unpin(cx) {
+                Poll::Pending => break,
+                Poll::Ready(None) => self.headers_finished = true,
+                Poll::Ready(Some(Err(e))) => {
+                    self.headers_finished = true;
+                    self.error = Some(e.into());
                 }
-                Some(Err(e)) => {
-                    self.finished = true;
-                    return Poll::Ready(Some(Err(e.into())))
-                }
-                Some(Ok(block_header)) => {
+                Poll::Ready(Some(Ok(block_header))) => {
                     // Note [jsdw]: We may be able to get rid of the per-item allocation
                     // with https://github.com/oblique/reusable-box-future.
-                    let at = EventsClient::new(self.client.clone())
-                        .at(Some(block_header.hash()));
-                    self.at = Some(Box::pin(at));
-                    // Continue, so that we poll this function future we've just created.
+                    let at = self.events.at(Some(header_hash::<T>(&block_header)));
+                    let names = self.names.clone();
+                    let strict = self.strict;
+                    self.fetching.push_back(Box::pin(async move {
+                        let events = at.await?.with_names(names);
+                        if strict {
+                            decode_all(&events)?;
+                        }
+                        Ok(events)
+                    }));
                 }
             }
         }
 
-        // If we get here, there will be an `at` function stored. Unwrap it and poll it to
-        // completion to get our events, throwing it away as soon as it is ready.
-        let at_fn = self
-            .at
-            .as_mut()
-            .expect("'at' function should have been set above'");
-        let events = futures::ready!(at_fn.poll_unpin(cx));
-        self.at = None;
-        Poll::Ready(Some(events))
+        // Hand back the events of the earliest block as soon as they're fetched.
+        // Once there are none left to fetch, and no more headers, we're finished.
+        match futures::ready!(self.fetching.poll_next_unpin(cx)) {
+            Some(events) => Poll::Ready(Some(events)),
+            None if self.headers_finished => {
+                self.finished = true;
+                Poll::Ready(self.error.take().map(Err))
+            }
+            // The header subscription is pending, and will wake us.
+            None => Poll::Pending,
+        }
+    }
+}
+
+// Decode the fields of every event, handing back the first error, if any.
+fn decode_all<T: Config>(events: &Events<T>) -> Result<(), Error> {
+    for event in events.iter() {
+        event?.decoded_fields()?;
     }
+    Ok(())
 }
 
 #[cfg(test)]
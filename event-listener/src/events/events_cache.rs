@@ -0,0 +1,222 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use super::Events;
+use crate::{
+    error::Error,
+    Config,
+};
+use derivative::Derivative;
+use lru::LruCache;
+use parking_lot::Mutex;
+use std::{
+    future::Future,
+    sync::{
+        atomic::{
+            AtomicU64,
+            Ordering,
+        },
+        Arc,
+    },
+};
+
+/// How many blocks' events an [`EventsCache`] holds by default.
+pub const DEFAULT_EVENTS_CACHE_CAPACITY: usize = 128;
+
+/// A cache of the events of recently seen blocks, by block hash, so that looking
+/// the events of a block up again (such as from several consumers, when checking
+/// for reorgs, or when matching events up with extrinsics) doesn't fetch them
+/// again. Once it's full, the events of the block looked up longest ago are
+/// dropped.
+///
+/// The cache is cheap to clone, and clones share their events, so one cache can
+/// be handed to every [`super::EventsClient`] (see
+/// [`super::EventsClient::with_cache()`]) and listener
+/// (see [`crate::listener::EventListenerBuilder::events_cache()`]) that should
+/// share it.
+///
+/// ```no_run
+/// use event_listener::{
+///     events::EventsCache,
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let cache = EventsCache::new(256);
+/// let events = api.events().with_cache(cache.clone());
+///
+/// let hash = api.rpc().finalized_head().await.unwrap();
+/// let first = events.at(Some(hash)).await.unwrap();
+/// let again = events.at(Some(hash)).await.unwrap();
+/// assert_eq!(cache.stats().hits, 1);
+/// # }
+/// ```
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""))]
+pub struct EventsCache<T: Config> {
+    inner: Arc<Inner<T>>,
+}
+
+struct Inner<T: Config> {
+    events: Mutex<LruCache<T::Hash, Events<T>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// How well an [`EventsCache`] is doing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EventsCacheStats {
+    /// How many lookups found the events in the cache.
+    pub hits: u64,
+    /// How many lookups had to fetch the events.
+    pub misses: u64,
+    /// How many blocks' events are in the cache.
+    pub len: usize,
+    /// How many blocks' events the cache holds at most.
+    pub capacity: usize,
+}
+
+impl<T: Config> EventsCache<T> {
+    /// Create a cache which holds the events of up to `capacity` blocks.
+    pub fn new(capacity: usize) -> Self {
+        EventsCache {
+            inner: Arc::new(Inner {
+                events: Mutex::new(LruCache::new(capacity.max(1))),
+                hits: AtomicU64::new(0),
+                misses: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// The events of the block with the given hash, if they're in the cache.
+    /// This counts as a hit or a miss.
+    pub fn get(&self, block_hash: &T::Hash) -> Option<Events<T>> {
+        let events = self.inner.events.lock().get(block_hash).cloned();
+        self.record(events.is_some());
+        events
+    }
+
+    /// Put the events of a block in the cache.
+    pub fn insert(&self, events: Events<T>) {
+        // Cached events mustn't keep their block pinned on the node.
+        let events = events.with_pin(None);
+        self.inner.events.lock().put(events.block_hash(), events);
+    }
+
+    /// Forget the events of the block with the given hash, such as once it's been
+    /// retracted.
+    pub fn remove(&self, block_hash: &T::Hash) {
+        self.inner.events.lock().pop(block_hash);
+    }
+
+    /// Forget every block's events.
+    pub fn clear(&self) {
+        self.inner.events.lock().clear();
+    }
+
+    /// How many lookups have hit and missed, and how full the cache is.
+    pub fn stats(&self) -> EventsCacheStats {
+        let events = self.inner.events.lock();
+        EventsCacheStats {
+            hits: self.inner.hits.load(Ordering::Relaxed),
+            misses: self.inner.misses.load(Ordering::Relaxed),
+            len: events.len(),
+            capacity: events.cap(),
+        }
+    }
+
+    /// The events of the block with the given hash from the cache, or else from
+    /// the future given, caching them if it succeeds.
+    pub async fn get_or_fetch(
+        &self,
+        block_hash: T::Hash,
+        fetch: impl Future<Output = Result<Events<T>, Error>>,
+    ) -> Result<Events<T>, Error> {
+        if let Some(events) = self.get(&block_hash) {
+            return Ok(events)
+        }
+        let events = fetch.await?;
+        self.insert(events.clone());
+        Ok(events)
+    }
+
+    fn record(&self, hit: bool) {
+        let counter = if hit {
+            &self.inner.hits
+        } else {
+            &self.inner.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::increment_counter!(if hit {
+            "event_listener_events_cache_hits"
+        } else {
+            "event_listener_events_cache_misses"
+        });
+    }
+}
+
+impl<T: Config> std::fmt::Debug for EventsCache<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventsCache")
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl<T: Config> Default for EventsCache<T> {
+    fn default() -> Self {
+        EventsCache::new(DEFAULT_EVENTS_CACHE_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        events::test_utils::metadata,
+        SubstrateConfig,
+    };
+    use scale_info::TypeInfo;
+    use sp_core::H256;
+
+    #[allow(dead_code)]
+    #[derive(TypeInfo)]
+    enum Event {
+        A,
+    }
+
+    // No events, in the block with a hash of the byte given.
+    fn events(hash: u8) -> Events<SubstrateConfig> {
+        Events::new(metadata::<Event>(), H256::repeat_byte(hash), vec![0])
+    }
+
+    #[tokio::test]
+    async fn events_are_fetched_once_until_evicted() {
+        let cache = EventsCache::new(2);
+        let fetch = |hash| async move { Ok::<_, Error>(events(hash)) };
+        for hash in [1, 2, 1, 3, 1, 2] {
+            let got = cache
+                .get_or_fetch(H256::repeat_byte(hash), fetch(hash))
+                .await
+                .unwrap();
+            assert_eq!(got.block_hash(), H256::repeat_byte(hash));
+        }
+        // 1 and 2 miss, 1 hits, 3 misses (evicting 2), 1 hits, and 2 misses.
+        assert_eq!(
+            cache.stats(),
+            EventsCacheStats {
+                hits: 2,
+                misses: 4,
+                len: 2,
+                capacity: 2,
+            }
+        );
+        cache.remove(&H256::repeat_byte(2));
+        assert!(cache.get(&H256::repeat_byte(2)).is_none());
+    }
+}
@@ -3,38 +3,81 @@
 // see LICENSE for license details.
 
 use crate::{
-    client::OnlineClientT,
-    error::Error,
+    blocks::{
+        subscribe_to_block_headers_filling_in_gaps,
+        BlockPin,
+    },
+    client::{
+        OfflineClientT,
+        OnlineClientT,
+    },
+    error::{
+        BlockError,
+        Error,
+        ErrorContext,
+    },
     events::{
         EventSub,
         EventSubscription,
         Events,
+        EventsCache,
+        FinalizedEventSub,
+        SubscribeOpts,
     },
     Config,
+    Metadata,
 };
 use derivative::Derivative;
+use parking_lot::Mutex;
 use sp_core::{
     storage::StorageKey,
     twox_128,
 };
-use std::future::Future;
+use futures::{
+    future::Either,
+    stream,
+    Stream,
+    StreamExt,
+};
+use sp_runtime::traits::Header;
+use std::{
+    future::Future,
+    sync::Arc,
+};
 
 /// A client for working with events.
 #[derive(Derivative)]
-#[derivative(Clone(bound = "Client: Clone"))]
-pub struct EventsClient<T, Client> {
+#[derivative(
+    Clone(bound = "Client: Clone"),
+    Debug(bound = "Client: std::fmt::Debug")
+)]
+pub struct EventsClient<T: Config, Client> {
     client: Client,
-    _marker: std::marker::PhantomData<T>,
+    cache: Option<EventsCache<T>>,
+    #[derivative(Debug = "ignore")]
+    metadata: CachedMetadata,
 }
 
-impl<T, Client> EventsClient<T, Client> {
+impl<T: Config, Client> EventsClient<T, Client> {
     /// Create a new [`EventsClient`].
     pub fn new(client: Client) -> Self {
         Self {
             client,
-            _marker: std::marker::PhantomData,
+            cache: None,
+            metadata: CachedMetadata::default(),
         }
     }
+
+    /// Look the events of blocks up in the cache given before fetching them, and
+    /// cache the events that are fetched. See [`EventsCache`].
+    pub fn with_cache(mut self, cache: EventsCache<T>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub(crate) fn cache(&self) -> Option<&EventsCache<T>> {
+        self.cache.as_ref()
+    }
 }
 
 impl<T, Client> EventsClient<T, Client>
@@ -50,7 +93,41 @@ where
         // Clone and pass the client in like this so that we can explicitly
         // return a Future that's Send + 'static, rather than tied to &self.
         let client = self.client.clone();
-        async move { at(client, block_hash).await }
+        let cache = self.cache.clone();
+        let metadata = self.metadata.get(&self.client);
+        async move { cached_at(client, cache, block_hash, None, metadata).await }
+    }
+
+    /// Obtain the events at some block hash, decoding them with the metadata
+    /// given rather than that of the client. Events from before a runtime upgrade
+    /// need decoding with the metadata of the runtime they were emitted by (see
+    /// [`crate::rpc::Rpc::metadata_at()`]).
+    pub fn at_with_metadata(
+        &self,
+        block_hash: T::Hash,
+        metadata: Metadata,
+    ) -> impl Future<Output = Result<Events<T>, Error>> + Send + 'static {
+        let client = self.client.clone();
+        let cache = self.cache.clone();
+        async move { cached_at(client, cache, Some(block_hash), None, metadata).await }
+    }
+
+    /// Obtain the events of a block pinned by a `chainHead_unstable_follow`
+    /// subscription, reading them through that subscription so that the node
+    /// keeps the state of the block around for as long as it takes. They're
+    /// decoded with the metadata given, or with that of the client if none is.
+    pub(crate) fn at_pinned(
+        &self,
+        pin: BlockPin<T::Hash>,
+        metadata: Option<Metadata>,
+    ) -> impl Future<Output = Result<Events<T>, Error>> + Send + 'static {
+        let client = self.client.clone();
+        let cache = self.cache.clone();
+        let metadata = metadata.unwrap_or_else(|| self.metadata.get(&self.client));
+        async move {
+            let events = cached_at(client, cache, Some(pin.hash()), Some(&pin), metadata);
+            Ok(events.await?.with_pin(Some(pin)))
+        }
     }
 
     /// Subscribe to all events from blocks.
@@ -93,11 +170,101 @@ where
         async move { subscribe(client).await }
     }
 
+    /// Subscribe to events from finalized blocks. See [`EventsClient::subscribe()`] for details.
+    ///
+    /// If the node announces several finalized blocks at once, events are handed back
+    /// for each of them in turn, so that no finalized block is missed.
+    ///
+    /// See [`EventsClient::subscribe_with()`] for more control over the subscription.
+    pub fn subscribe_finalized(
+        &self,
+    ) -> impl Future<
+        Output = Result<
+            EventSubscription<T, Client, FinalizedEventSub<T::Header>>,
+            Error,
+        >,
+    > + Send
+           + 'static {
+        let client = self.client.clone();
+        async move { subscribe_finalized(client).await }
+    }
+
+    /// Subscribe to events with the options given, which cover whether to follow
+    /// finalized or best blocks, how many confirmations to wait for, which block
+    /// to start at, which events to hand back, how many blocks' events to fetch
+    /// at once and how strictly to decode them. See [`SubscribeOpts`].
+    pub fn subscribe_with(
+        &self,
+        opts: SubscribeOpts,
+    ) -> impl Future<
+        Output = Result<
+            EventSubscription<T, Client, FinalizedEventSub<T::Header>>,
+            Error,
+        >,
+    > + Send
+           + 'static {
+        let events = self.clone();
+        async move { subscribe_with(events, opts).await }
+    }
+}
+
+// The metadata of the client, as of the last time that events were fetched. It's
+// shared by clones of the client, and only read from the client again once the
+// spec version of the client changes, so that fetching the events of a block
+// doesn't need to lock the metadata of the client.
+#[derive(Clone, Default)]
+struct CachedMetadata(Arc<Mutex<Option<(u32, Metadata)>>>);
+
+impl CachedMetadata {
+    fn get<T: Config, Client: OfflineClientT<T>>(&self, client: &Client) -> Metadata {
+        let spec_version = client.spec_version();
+        let mut cached = self.0.lock();
+        match &*cached {
+            Some((version, metadata)) if *version == spec_version => metadata.clone(),
+            _ => {
+                let metadata = client.metadata();
+                *cached = Some((spec_version, metadata.clone()));
+                metadata
+            }
+        }
+    }
+}
+
+// Look the events up in the cache, if there is one, before fetching them.
+async fn cached_at<T, Client>(
+    client: Client,
+    cache: Option<EventsCache<T>>,
+    block_hash: Option<T::Hash>,
+    pin: Option<&BlockPin<T::Hash>>,
+    metadata: Metadata,
+) -> Result<Events<T>, Error>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    match (cache, block_hash) {
+        (Some(cache), Some(hash)) => {
+            cache
+                .get_or_fetch(hash, at(client, block_hash, pin, metadata))
+                .await
+        }
+        // We don't know which block is the latest until it's fetched.
+        (Some(cache), None) => {
+            let events = at(client, None, pin, metadata).await?;
+            cache.insert(events.clone());
+            Ok(events)
+        }
+        (None, _) => at(client, block_hash, pin, metadata).await,
+    }
 }
 
+// Fetch the events of a block, through the `chainHead_unstable_follow`
+// subscription pinning it if there is one.
 async fn at<T, Client>(
     client: Client,
     block_hash: Option<T::Hash>,
+    pin: Option<&BlockPin<T::Hash>>,
+    metadata: Metadata,
 ) -> Result<Events<T>, Error>
 where
     T: Config,
@@ -116,14 +283,22 @@ where
         }
     };
 
-    let event_bytes = client
-        .rpc()
-        .storage(&*system_events_key().0, Some(block_hash))
-        .await?
+    let key = system_events_key();
+    let event_bytes = match pin {
+        Some(pin) => {
+            client
+                .rpc()
+                .chainhead_unstable_storage(pin.subscription_id(), block_hash, &key.0)
+                .await
+        }
+        None => client.rpc().storage(&key.0, Some(block_hash)).await,
+    };
+    let event_bytes = event_bytes
+        .map_err(|e| e.context(ErrorContext::block(block_hash)))?
         .map(|e| e.0)
         .unwrap_or_else(Vec::new);
 
-    Ok(Events::new(client.metadata(), block_hash, event_bytes))
+    Ok(Events::new(metadata, block_hash, event_bytes))
 }
 
 async fn subscribe<T, Client>(
@@ -136,9 +311,161 @@ where
     let block_subscription = client.rpc().subscribe_blocks().await?;
     Ok(EventSubscription::new(client, block_subscription))
 }
+/// Subscribe to events from finalized blocks.
+async fn subscribe_finalized<T, Client>(
+    client: Client,
+) -> Result<EventSubscription<T, Client, FinalizedEventSub<T::Header>>, Error>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    // fetch the last finalised block details immediately, so that we'll get
+    // events for each block after this one.
+    let last_finalized_block_hash = client.rpc().finalized_head().await?;
+    let last_finalized_block_number = client
+        .rpc()
+        .header(Some(last_finalized_block_hash))
+        .await?
+        .map(|h| (*h.number()).into());
+
+    let sub = client.rpc().subscribe_finalized_blocks().await?;
+
+    // Fill in any gaps between the block above and the finalized blocks reported.
+    let block_subscription = subscribe_to_block_headers_filling_in_gaps(
+        client.clone(),
+        last_finalized_block_number,
+        sub,
+    );
+
+    Ok(EventSubscription::new(client, block_subscription.boxed()))
+}
+
+/// Subscribe to events with the options given.
+async fn subscribe_with<T, Client>(
+    events: EventsClient<T, Client>,
+    opts: SubscribeOpts,
+) -> Result<EventSubscription<T, Client, FinalizedEventSub<T::Header>>, Error>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let client = events.client.clone();
+    let sub: FinalizedEventSub<T::Header> = if opts.finalized {
+        client.rpc().subscribe_finalized_blocks().await?.boxed()
+    } else {
+        client.rpc().subscribe_blocks().await?.boxed()
+    };
+    let sub = match (opts.finalized, opts.confirmations, opts.start_block) {
+        // Best blocks are handed back as they're announced, forks and all, unless
+        // we need to count blocks.
+        (false, 0, None) => sub,
+        _ => {
+            confirmed_headers(client, sub, opts.confirmations, opts.start_block).boxed()
+        }
+    };
+    Ok(EventSubscription::with_opts(events, sub, &opts))
+}
+
+// The headers of each block in turn, from the block number given (or else the
+// first block announced), once `confirmations` more blocks have been announced
+// on top of them. Blocks are looked up by number, so each height is handed back
+// once, from the chain that the node considers best at the time.
+fn confirmed_headers<T, Client>(
+    client: Client,
+    sub: FinalizedEventSub<T::Header>,
+    confirmations: u32,
+    mut next_block_num: Option<u64>,
+) -> impl Stream<Item = Result<T::Header, Error>> + Send
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    sub.flat_map(move |header| {
+        let header = match header {
+            Ok(header) => header,
+            Err(e) => return Either::Left(stream::once(async { Err(e) })),
+        };
+
+        let block_num: u64 = (*header.number()).into();
+        let confirmed = match block_num.checked_sub(confirmations.into()) {
+            Some(confirmed) => confirmed,
+            // There aren't enough blocks yet.
+            None => return Either::Right(Either::Left(stream::empty())),
+        };
+        let start = next_block_num.unwrap_or(confirmed);
+        if confirmed >= start {
+            next_block_num = Some(confirmed + 1);
+        }
+
+        let rpc = client.rpc().clone();
+        let headers = stream::iter(start..confirmed + 1)
+            .map(move |n| {
+                let rpc = rpc.clone();
+                async move {
+                    let hash = rpc
+                        .block_hash(Some(n.into()))
+                        .await?
+                        .ok_or(BlockError::BlockNumberNotFound(n))?;
+                    let header = rpc
+                        .header(Some(hash))
+                        .await?
+                        .ok_or_else(|| BlockError::block_hash_not_found(hash))?;
+                    Ok::<_, Error>(header)
+                }
+            })
+            .buffered(10);
+        Either::Right(Either::Right(headers))
+    })
+}
+
 // The storage key needed to access events.
 fn system_events_key() -> StorageKey {
     let mut storage_key = twox_128(b"System").to_vec();
     storage_key.extend(twox_128(b"Events").to_vec());
     StorageKey(storage_key)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        client::OfflineClient,
+        events::test_utils::metadata,
+        rpc::RuntimeVersion,
+        SubstrateConfig,
+    };
+    use scale_info::TypeInfo;
+
+    #[allow(dead_code)]
+    #[derive(TypeInfo)]
+    enum Old {
+        A,
+    }
+
+    #[allow(dead_code)]
+    #[derive(TypeInfo)]
+    enum New {
+        A,
+        B,
+    }
+
+    fn client(spec_version: u32, metadata: Metadata) -> OfflineClient<SubstrateConfig> {
+        let runtime_version = RuntimeVersion {
+            spec_version,
+            transaction_version: 1,
+            other: Default::default(),
+        };
+        OfflineClient::new(Default::default(), runtime_version, metadata)
+    }
+
+    #[test]
+    fn metadata_is_refreshed_when_the_spec_version_changes() {
+        let cached = CachedMetadata::default();
+        let has_b = |metadata: Metadata| metadata.event(0, 1).is_ok();
+
+        assert!(!has_b(cached.get(&client(1, metadata::<Old>()))));
+        // The same spec version, so the metadata isn't read again.
+        assert!(!has_b(cached.get(&client(1, metadata::<New>()))));
+        assert!(has_b(cached.get(&client(2, metadata::<New>()))));
+    }
+}
@@ -0,0 +1,361 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Decoding events as their bytes arrive, rather than once they all have.
+
+use super::{
+    events_type::event_name,
+    EventDetails,
+};
+use crate::{
+    error::{
+        Error,
+        ErrorContext,
+    },
+    utils::to_hex,
+    Metadata,
+};
+use bytes::{
+    Bytes,
+    BytesMut,
+};
+use codec::{
+    Compact,
+    Decode,
+};
+use futures::{
+    stream,
+    Stream,
+    StreamExt,
+};
+
+/// The most bytes that the number of events on the front of them can take up.
+const MAX_COMPACT_LEN: usize = 5;
+
+/// Decodes the events of a block from their bytes as they arrive, handing back
+/// each event as soon as all of its bytes are in, rather than once all of the
+/// events' bytes are (as [`super::Events`] does). For blocks with tens of MB of
+/// events, this means the first of them can be looked at long before the last
+/// of them has arrived.
+///
+/// This is for bytes that come from somewhere in chunks, such as a file or an
+/// archive of blocks. Events fetched from a node with [`super::EventsClient`]
+/// don't go through it: `state_getStorage` hands back all of the bytes of
+/// `System::Events` in a single response, so they're all in memory before the
+/// first event could be decoded anyway.
+///
+/// Push the bytes in with [`EventsDecoder::push()`], in chunks of any size, and
+/// call [`EventsDecoder::finish()`] once there are no more. Events are handed
+/// back by [`EventsDecoder::next_event()`], and they share the buffers that
+/// the chunks were pushed in with, as far as they can.
+///
+/// `Hash` is the type of the block hash, and of the topics of each event: the
+/// `Hash` of the chain's `Config`, or just `[u8; 32]` without one.
+///
+/// ```
+/// use event_listener::{
+///     events::EventsDecoder,
+///     Metadata,
+/// };
+///
+/// # fn test(metadata: Metadata, chunks: Vec<Vec<u8>>) {
+/// let mut decoder = EventsDecoder::<[u8; 32]>::new(metadata, [0; 32]);
+/// for chunk in chunks {
+///     decoder.push(chunk);
+///     while let Some(event) = decoder.next_event() {
+///         println!("{}", event.unwrap().variant_name());
+///     }
+/// }
+/// decoder.finish();
+/// while let Some(event) = decoder.next_event() {
+///     println!("{}", event.unwrap().variant_name());
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct EventsDecoder<Hash> {
+    metadata: Metadata,
+    block_hash: Hash,
+    // The bytes which events are being decoded from, and how far into them the
+    // next event starts.
+    buffer: Bytes,
+    pos: usize,
+    // Chunks which have been pushed since the buffer was last added to.
+    incoming: Vec<Bytes>,
+    incoming_len: usize,
+    // How many bytes were left over the last time there weren't enough of them
+    // to decode the next event. It's not tried again until there are twice as
+    // many, so that a huge event that arrives in lots of small chunks doesn't
+    // get decoded over and over.
+    stalled_at: Option<usize>,
+    num_events: Option<u32>,
+    index: u32,
+    finished: bool,
+    failed: bool,
+}
+
+impl<Hash: Decode + AsRef<[u8]>> EventsDecoder<Hash> {
+    /// Decode the events of the block with the hash given, with the metadata
+    /// given.
+    pub fn new(metadata: Metadata, block_hash: Hash) -> Self {
+        EventsDecoder {
+            metadata,
+            block_hash,
+            buffer: Bytes::new(),
+            pos: 0,
+            incoming: Vec::new(),
+            incoming_len: 0,
+            stalled_at: None,
+            num_events: None,
+            index: 0,
+            finished: false,
+            failed: false,
+        }
+    }
+
+    /// Add the next chunk of the bytes of the events.
+    pub fn push(&mut self, chunk: impl Into<Bytes>) {
+        let chunk = chunk.into();
+        if !chunk.is_empty() {
+            self.incoming_len += chunk.len();
+            self.incoming.push(chunk);
+        }
+    }
+
+    /// Say that all of the bytes have been pushed, so that whatever events are
+    /// left are decoded, and an error is handed back if they can't be.
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    /// How many events there are in total, once enough bytes are in to know.
+    pub fn num_events(&self) -> Option<u32> {
+        self.num_events
+    }
+
+    /// Whether every event has been handed back, or decoding has failed.
+    pub fn is_done(&self) -> bool {
+        self.failed || self.num_events.map_or(false, |n| self.index >= n)
+    }
+
+    /// The next event, if all of its bytes are in. `None` means that more bytes
+    /// are needed, or, once [`EventsDecoder::is_done()`], that there are no more
+    /// events. Once an error is handed back, no more events are.
+    pub fn next_event(&mut self) -> Option<Result<EventDetails, Error>> {
+        if self.is_done() {
+            return None
+        }
+        let pending = self.buffer.len() - self.pos + self.incoming_len;
+        let stalled = self.stalled_at.map_or(false, |at| pending < at * 2);
+        if stalled && !self.finished {
+            return None
+        }
+        self.take_incoming();
+
+        let num_events = match self.num_events {
+            Some(num_events) => num_events,
+            None => {
+                let cursor = &mut &self.buffer[self.pos..];
+                match <Compact<u32>>::decode(cursor) {
+                    Ok(Compact(num_events)) => {
+                        self.pos = self.buffer.len() - cursor.len();
+                        self.num_events = Some(num_events);
+                        num_events
+                    }
+                    Err(_) if !self.finished && pending < MAX_COMPACT_LEN => return None,
+                    // Like [`super::Events`], no bytes at all means no events.
+                    Err(_) if pending == 0 => {
+                        self.num_events = Some(0);
+                        return None
+                    }
+                    Err(e) => {
+                        self.failed = true;
+                        return Some(Err(e.into()))
+                    }
+                }
+            }
+        };
+        if self.index >= num_events {
+            return None
+        }
+        if self.pos >= self.buffer.len() {
+            if self.finished {
+                // Like [`super::Events`], stop at the end of the bytes.
+                self.num_events = Some(self.index);
+            }
+            return None
+        }
+
+        match EventDetails::decode_from::<Hash>(
+            self.metadata.clone(),
+            self.buffer.clone(),
+            self.pos,
+            self.index,
+        ) {
+            Ok(event) => {
+                self.pos += event.bytes().len();
+                self.index += 1;
+                self.stalled_at = None;
+                Some(Ok(event))
+            }
+            // We can't tell a broken event from one that hasn't all arrived yet,
+            // so wait for more bytes until there are no more to wait for.
+            Err(_) if !self.finished => {
+                self.stalled_at = Some(self.buffer.len() - self.pos);
+                None
+            }
+            Err(e) => {
+                self.failed = true;
+                let context = ErrorContext::Event {
+                    block_hash: to_hex(&self.block_hash),
+                    index: self.index,
+                    name: event_name(&self.metadata, &self.buffer[self.pos..]),
+                };
+                Some(Err(e.context(context)))
+            }
+        }
+    }
+
+    // Add the chunks pushed since last time onto the end of the bytes left over
+    // in the buffer. A chunk that starts off a new buffer isn't copied.
+    fn take_incoming(&mut self) {
+        if self.incoming.is_empty() {
+            return
+        }
+        if self.pos == self.buffer.len() && self.incoming.len() == 1 {
+            self.buffer = self.incoming.remove(0);
+        } else {
+            let leftover = &self.buffer[self.pos..];
+            let mut buffer = BytesMut::with_capacity(leftover.len() + self.incoming_len);
+            buffer.extend_from_slice(leftover);
+            for chunk in self.incoming.drain(..) {
+                buffer.extend_from_slice(&chunk);
+            }
+            self.buffer = buffer.freeze();
+        }
+        self.pos = 0;
+        self.incoming_len = 0;
+    }
+}
+
+/// Decode the events of a block from a stream of chunks of their bytes, handing
+/// back each event as soon as its bytes are in. See [`EventsDecoder`].
+pub fn decode_events_stream<Hash, S, B>(
+    metadata: Metadata,
+    block_hash: Hash,
+    chunks: S,
+) -> impl Stream<Item = Result<EventDetails, Error>> + Send + 'static
+where
+    Hash: Decode + AsRef<[u8]> + Send + 'static,
+    S: Stream<Item = Result<B, Error>> + Send + Unpin + 'static,
+    B: Into<Bytes>,
+{
+    let decoder = EventsDecoder::new(metadata, block_hash);
+    stream::unfold((decoder, chunks), |(mut decoder, mut chunks)| {
+        async move {
+            loop {
+                if let Some(event) = decoder.next_event() {
+                    return Some((event, (decoder, chunks)))
+                }
+                if decoder.is_done() {
+                    return None
+                }
+                match chunks.next().await {
+                    Some(Ok(chunk)) => decoder.push(chunk),
+                    Some(Err(e)) => {
+                        decoder.failed = true;
+                        return Some((Err(e), (decoder, chunks)))
+                    }
+                    None if decoder.finished => return None,
+                    None => decoder.finish(),
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::events::{
+        test_utils::{
+            event_record,
+            metadata,
+        },
+        Phase,
+    };
+    use codec::Encode;
+    use scale_info::TypeInfo;
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Event {
+        A(u8, Vec<u8>),
+    }
+
+    // The first field of each event, which tells them apart.
+    fn firsts(events: &[EventDetails]) -> Vec<u8> {
+        events.iter().map(|ev| ev.field_bytes()[0]).collect()
+    }
+
+    #[test]
+    fn events_are_handed_back_once_their_bytes_are_in() {
+        let bytes = (0..3)
+            .map(|i| event_record(Phase::Finalization, Event::A(i, vec![i; 1000])))
+            .collect::<Vec<_>>()
+            .encode();
+
+        let mut decoder = EventsDecoder::new(metadata::<Event>(), [0u8; 32]);
+        let mut decoded = Vec::new();
+        let mut chunks_in = Vec::new();
+        for (n, chunk) in bytes.chunks(100).enumerate() {
+            decoder.push(chunk.to_vec());
+            while let Some(event) = decoder.next_event() {
+                decoded.push(event.unwrap());
+                chunks_in.push(n + 1);
+            }
+        }
+        decoder.finish();
+        while let Some(event) = decoder.next_event() {
+            decoded.push(event.unwrap());
+        }
+        assert!(decoder.is_done());
+
+        assert_eq!(firsts(&decoded), vec![0, 1, 2]);
+        // The first events are handed back before all of the bytes are in.
+        let num_chunks = (bytes.len() + 99) / 100;
+        assert!(chunks_in.len() >= 2);
+        assert!(chunks_in[0] < chunks_in[1] && chunks_in[1] < num_chunks);
+    }
+
+    #[test]
+    fn truncated_events_fail_once_finished() {
+        let bytes = vec![
+            event_record(Phase::Finalization, Event::A(1, vec![1; 10])),
+            event_record(Phase::Finalization, Event::A(2, vec![2; 10])),
+        ]
+        .encode();
+
+        let mut decoder = EventsDecoder::new(metadata::<Event>(), [0u8; 32]);
+        decoder.push(bytes[..bytes.len() - 5].to_vec());
+        assert!(decoder.next_event().unwrap().is_ok());
+        assert!(decoder.next_event().is_none());
+        decoder.finish();
+        assert!(decoder.next_event().unwrap().is_err());
+        assert!(decoder.is_done());
+    }
+
+    #[tokio::test]
+    async fn events_are_decoded_from_a_stream() {
+        let bytes = vec![event_record(Phase::Finalization, Event::A(7, vec![]))].encode();
+        let chunks: Vec<Result<Vec<u8>, Error>> =
+            bytes.chunks(2).map(|c| Ok(c.to_vec())).collect();
+        let events: Vec<_> =
+            decode_events_stream(metadata::<Event>(), [0u8; 32], stream::iter(chunks))
+                .collect()
+                .await;
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(firsts(&[events[0].as_ref().unwrap().clone()]), vec![7]);
+    }
+}
@@ -8,41 +8,66 @@ use super::{
     Phase,
     StaticEvent,
 };
+#[cfg(feature = "substrate-compat")]
+use super::subscribe_opts::EventNames;
 use crate::{
     error::Error,
     metadata::EventMetadata,
-    Config,
     Metadata,
 };
+#[cfg(feature = "substrate-compat")]
+use crate::{
+    blocks::BlockPin,
+    error::ErrorContext,
+    utils::to_hex,
+    Config,
+};
+use bytes::Bytes;
 use codec::{
-    Compact,
     Decode,
     Error as CodecError,
 };
+#[cfg(feature = "substrate-compat")]
+use codec::Compact;
+#[cfg(feature = "substrate-compat")]
 use derivative::Derivative;
+use once_cell::sync::OnceCell;
+use scale_value::{
+    scale::TypeId,
+    Composite,
+};
 use std::sync::Arc;
 
 /// A collection of events obtained from a block, bundled with the necessary
 /// information needed to decode and iterate over them.
+#[cfg(feature = "substrate-compat")]
 #[derive(Derivative)]
-#[derivative(Debug(bound = ""))]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
 pub struct Events<T: Config> {
     metadata: Metadata,
     block_hash: T::Hash,
     // Note; raw event bytes are prefixed with a Compact<u32> containing
     // the number of events to be decoded. The start_idx reflects that, so
-    // that we can skip over those bytes when decoding them
-    event_bytes: Arc<[u8]>,
+    // that we can skip over those bytes when decoding them. Each event hands
+    // out slices of these bytes, rather than copies.
+    event_bytes: Bytes,
     start_idx: usize,
     num_events: u32,
+    // Keeps the block pinned for as long as the events are around, if they
+    // were obtained from a pinned block.
+    pin: Option<BlockPin<T::Hash>>,
+    // Only the events with these names are handed back, if there are any.
+    names: EventNames,
 }
 
+#[cfg(feature = "substrate-compat")]
 impl<T: Config> Events<T> {
     pub(crate) fn new(
         metadata: Metadata,
         block_hash: T::Hash,
-        event_bytes: Vec<u8>,
+        event_bytes: impl Into<Bytes>,
     ) -> Self {
+        let event_bytes = event_bytes.into();
         // event_bytes is a SCALE encoded vector of events. So, pluck the
         // compact encoded length from the front, leaving the remaining bytes
         // for our iterating to decode.
@@ -58,20 +83,40 @@ impl<T: Config> Events<T> {
         Self {
             metadata,
             block_hash,
-            event_bytes: event_bytes.into(),
+            event_bytes,
             start_idx,
             num_events,
+            pin: None,
+            names: EventNames::default(),
         }
     }
 
+    pub(crate) fn with_pin(mut self, pin: Option<BlockPin<T::Hash>>) -> Self {
+        self.pin = pin;
+        self
+    }
+
+    pub(crate) fn with_names(mut self, names: EventNames) -> Self {
+        self.names = names;
+        self
+    }
+
     /// Return the block hash that these events are from.
     pub fn block_hash(&self) -> T::Hash {
         self.block_hash
     }
 
+    /// The metadata that these events are decoded with.
+    pub(crate) fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
     /// Iterate over all of the events, using metadata to dynamically
     /// decode them as we go, and returning the raw bytes and other associated
     /// details. If an error occurs, all subsequent iterations return `None`.
+    ///
+    /// The events of a subscription that only asked for some of them (see
+    /// [`super::SubscribeOpts::event()`]) only iterate over those.
     pub fn iter(
         &self,
     ) -> impl Iterator<Item = Result<EventDetails, Error>> + Send + Sync + 'static {
@@ -79,14 +124,17 @@ impl<T: Config> Events<T> {
         let event_bytes = self.event_bytes.clone();
         let metadata = self.metadata.clone();
         let num_events = self.num_events;
+        let block_hash = self.block_hash;
+        let names = self.names.clone();
 
         let mut pos = self.start_idx;
         let mut index = 0;
         std::iter::from_fn(move || {
-            if event_bytes.len() <= pos || num_events == index {
-                None
-            } else {
-                match EventDetails::decode_from::<T>(
+            loop {
+                if event_bytes.len() <= pos || num_events == index {
+                    return None
+                }
+                match EventDetails::decode_from::<T::Hash>(
                     metadata.clone(),
                     event_bytes.clone(),
                     pos,
@@ -97,15 +145,25 @@ impl<T: Config> Events<T> {
                         pos += event_details.bytes().len();
                         // Increment the index:
                         index += 1;
-                        // Return the event details:
-                        Some(Ok(event_details))
+                        // Return the event details, unless they're filtered out:
+                        if names.matches(
+                            event_details.pallet_name(),
+                            event_details.variant_name(),
+                        ) {
+                            return Some(Ok(event_details))
+                        }
                     }
                     Err(e) => {
+                        let context = ErrorContext::Event {
+                            block_hash: to_hex(block_hash),
+                            index,
+                            name: event_name(&metadata, &event_bytes[pos..]),
+                        };
                         // By setting the position to the "end" of the event bytes,
                         // the cursor len will become 0 and the iterator will return `None`
                         // from now on:
                         pos = event_bytes.len();
-                        Some(Err(e))
+                        return Some(Err(e.context(context)))
                     }
                 }
             }
@@ -134,12 +192,22 @@ impl<T: Config> Events<T> {
     }
 }
 
+// The name of the event at the start of the bytes given, if we can get that far.
+pub(super) fn event_name(metadata: &Metadata, bytes: &[u8]) -> Option<String> {
+    let input = &mut &*bytes;
+    Phase::decode(input).ok()?;
+    let pallet_index = u8::decode(input).ok()?;
+    let variant_index = u8::decode(input).ok()?;
+    let event = metadata.event(pallet_index, variant_index).ok()?;
+    Some(format!("{}::{}", event.pallet(), event.event()))
+}
+
 /// The event details.
 #[derive(Debug, Clone)]
 pub struct EventDetails {
     phase: Phase,
     index: u32,
-    all_bytes: Arc<[u8]>,
+    all_bytes: Bytes,
     // start of the bytes (phase, pallet/variant index and then fields and then topic to follow).
     start_idx: usize,
     // start of the fields (ie after phase nad pallet/variant index).
@@ -149,13 +217,18 @@ pub struct EventDetails {
     // end of everything (fields + topics)
     end_idx: usize,
     metadata: Metadata,
+    // The decoded fields, once something has asked for them. Only the bounds of
+    // the fields are found up front, so that events which nobody looks at don't
+    // pay for decoding them.
+    fields: Arc<OnceCell<Composite<TypeId>>>,
 }
 
 impl EventDetails {
-    // Attempt to dynamically decode a single event from our events input.
-    fn decode_from<T: Config>(
+    // Attempt to dynamically decode a single event from our events input, whose
+    // topics are hashes of the type given.
+    pub(super) fn decode_from<Hash: Decode>(
         metadata: Metadata,
-        all_bytes: Arc<[u8]>,
+        all_bytes: Bytes,
         start_idx: usize,
         index: u32,
     ) -> Result<EventDetails, Error> {
@@ -191,7 +264,7 @@ impl EventDetails {
 
         // topics come after the event data in EventRecord. They aren't used for
         // anything at the moment, so just decode and throw them away.
-        let _topics = Vec::<T::Hash>::decode(input)?;
+        let _topics = Vec::<Hash>::decode(input)?;
 
         // what bytes did we skip over in total, including topics.
         let end_idx = all_bytes.len() - input.len();
@@ -205,6 +278,7 @@ impl EventDetails {
             end_idx,
             all_bytes,
             metadata,
+            fields: Default::default(),
         })
     }
 
@@ -263,12 +337,42 @@ impl EventDetails {
         &self.all_bytes[self.fields_start_idx..self.fields_end_idx]
     }
 
+    /// Like [`EventDetails::bytes()`], but handing back a slice of the buffer that
+    /// all of the events of the block share, which can be kept around without
+    /// copying the bytes.
+    pub fn shared_bytes(&self) -> Bytes {
+        self.all_bytes.slice(self.start_idx..self.end_idx)
+    }
+
+    /// Like [`EventDetails::field_bytes()`], but handing back a slice of the
+    /// buffer that all of the events of the block share.
+    pub fn shared_field_bytes(&self) -> Bytes {
+        self.all_bytes.slice(self.fields_start_idx..self.fields_end_idx)
+    }
+
     /// Decode and provide the event fields back in the form of a [`scale_value::Composite`]
     /// type which represents the named or unnamed fields that were
-    /// present in the event.
+    /// present in the event. The fields are decoded the first time they're asked
+    /// for, and shared by every clone of these [`EventDetails`] after that.
     pub fn field_values(
         &self,
     ) -> Result<scale_value::Composite<scale_value::scale::TypeId>, Error> {
+        self.decoded_fields().map(Clone::clone)
+    }
+
+    /// The metadata that the event was decoded with.
+    #[cfg(feature = "substrate-compat")]
+    pub(crate) fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// The decoded fields of the event, decoding them if this is the first time
+    /// they've been asked for.
+    pub(crate) fn decoded_fields(&self) -> Result<&Composite<TypeId>, Error> {
+        self.fields.get_or_try_init(|| self.decode_fields())
+    }
+
+    fn decode_fields(&self) -> Result<Composite<TypeId>, Error> {
         let bytes = &mut self.field_bytes();
         let event_metadata = self.event_metadata();
 
@@ -300,7 +404,7 @@ impl EventDetails {
                     *type_id,
                     &self.metadata.runtime_metadata().types,
                 )?;
-                event_values.push((name.clone().unwrap_or_default(), value));
+                event_values.push((name.as_deref().unwrap_or_default().to_owned(), value));
             }
 
             Ok(scale_value::Composite::Named(event_values))
@@ -332,10 +436,8 @@ impl EventDetails {
 #[cfg(test)]
 pub(crate) mod test_utils {
     use super::*;
-    use crate::{
-        Config,
-        SubstrateConfig,
-    };
+    #[cfg(feature = "substrate-compat")]
+    use crate::SubstrateConfig;
     use codec::Encode;
     use frame_metadata::{
         v14::{
@@ -364,7 +466,7 @@ pub(crate) mod test_utils {
     pub struct EventRecord<E: Encode> {
         phase: Phase,
         event: AllEvents<E>,
-        topics: Vec<<SubstrateConfig as Config>::Hash>,
+        topics: Vec<[u8; 32]>,
     }
 
     /// Build an EventRecord, which encoded events in the format expected
@@ -406,6 +508,7 @@ pub(crate) mod test_utils {
 
     /// Build an `Events` object for test purposes, based on the details provided,
     /// and with a default block hash.
+    #[cfg(feature = "substrate-compat")]
     pub fn events<E: Decode + Encode>(
         metadata: Metadata,
         event_records: Vec<EventRecord<E>>,
@@ -420,6 +523,7 @@ pub(crate) mod test_utils {
 
     /// Much like [`events`], but takes pre-encoded events and event count, so that we can
     /// mess with the bytes in tests if we need to.
+    #[cfg(feature = "substrate-compat")]
     pub fn events_raw(
         metadata: Metadata,
         event_bytes: Vec<u8>,
@@ -428,15 +532,11 @@ pub(crate) mod test_utils {
         // Prepend compact encoded length to event bytes:
         let mut all_event_bytes = Compact(num_events).encode();
         all_event_bytes.extend(event_bytes);
-        Events::new(
-            metadata,
-            <SubstrateConfig as Config>::Hash::default(),
-            all_event_bytes,
-        )
+        Events::new(metadata, Default::default(), all_event_bytes)
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "substrate-compat"))]
 mod tests {
     use super::{
         test_utils::{
@@ -512,6 +612,48 @@ mod tests {
         assert_eq!(actual_fields_no_context, expected.fields);
     }
 
+    #[test]
+    fn fields_are_decoded_once_they_are_asked_for() {
+        #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+        enum Event {
+            A(u8),
+        }
+
+        let events = events::<Event>(
+            metadata::<Event>(),
+            vec![event_record(Phase::Finalization, Event::A(1))],
+        );
+        let event = events.iter().next().unwrap().unwrap();
+        assert!(event.fields.get().is_none());
+
+        // Clones share the fields once they've been decoded.
+        let clone = event.clone();
+        let fields = event.decoded_fields().unwrap();
+        assert!(std::ptr::eq(fields, clone.fields.get().unwrap()));
+    }
+
+    #[test]
+    fn shared_bytes_are_not_copied() {
+        #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+        enum Event {
+            A(u8, bool),
+        }
+
+        let events = events::<Event>(
+            metadata::<Event>(),
+            vec![
+                event_record(Phase::Initialization, Event::A(1, true)),
+                event_record(Phase::Finalization, Event::A(2, false)),
+            ],
+        );
+        for event in events.iter() {
+            let event = event.unwrap();
+            assert_eq!(event.shared_bytes(), event.bytes());
+            assert_eq!(event.shared_bytes().as_ptr(), event.bytes().as_ptr());
+            assert_eq!(event.shared_field_bytes().as_ptr(), event.field_bytes().as_ptr());
+        }
+    }
+
     #[test]
     fn dynamically_decode_single_event() {
         #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
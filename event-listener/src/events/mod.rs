@@ -6,28 +6,57 @@
 //! The two main entry points into events are [`crate::OnlineClient::events()`]
 //! and calls like [crate::tx::TxProgress::wait_for_finalized_success()].
 
+#[cfg(feature = "substrate-compat")]
+mod event_display;
+#[cfg(feature = "substrate-compat")]
 mod event_subscription;
+#[cfg(feature = "substrate-compat")]
+mod events_cache;
+#[cfg(feature = "substrate-compat")]
 mod events_client;
+mod events_decoder;
 mod events_type;
+#[cfg(feature = "substrate-compat")]
 mod filter_events;
+#[cfg(feature = "substrate-compat")]
+mod subscribe_opts;
 
+#[cfg(feature = "substrate-compat")]
+pub use event_display::EventFormatter;
+#[cfg(feature = "substrate-compat")]
 pub use event_subscription::{
     EventSub,
     EventSubscription,
     FinalizedEventSub,
 };
+#[cfg(feature = "substrate-compat")]
+pub use events_cache::{
+    EventsCache,
+    EventsCacheStats,
+    DEFAULT_EVENTS_CACHE_CAPACITY,
+};
+#[cfg(feature = "substrate-compat")]
 pub use events_client::{
     EventsClient,
 };
-pub use events_type::{
-    EventDetails,
-    Events,
+pub use events_decoder::{
+    decode_events_stream,
+    EventsDecoder,
 };
+pub use events_type::EventDetails;
+#[cfg(feature = "substrate-compat")]
+pub use events_type::Events;
+#[cfg(feature = "substrate-compat")]
 pub use filter_events::{
     EventFilter,
     FilterEvents,
     FilteredEventDetails,
 };
+#[cfg(feature = "substrate-compat")]
+pub use subscribe_opts::SubscribeOpts;
+
+#[cfg(test)]
+pub(crate) use events_type::test_utils;
 
 use codec::{
     Decode,
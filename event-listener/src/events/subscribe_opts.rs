@@ -0,0 +1,191 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Options for subscribing to events.
+
+use std::sync::Arc;
+
+/// How to subscribe to events, for [`super::EventsClient::subscribe_with()`]. The
+/// defaults match [`super::EventsClient::subscribe_finalized()`].
+///
+/// ```no_run
+/// use event_listener::{
+///     events::SubscribeOpts,
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+/// use futures::StreamExt;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let opts = SubscribeOpts::new()
+///     .finalized(false)
+///     .confirmations(3)
+///     .event("Balances", "Transfer")
+///     .buffer_size(4);
+///
+/// let mut sub = api.events().subscribe_with(opts).await.unwrap();
+/// while let Some(events) = sub.next().await {
+///     for event in events.unwrap().iter() {
+///         println!("{}", event.unwrap());
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubscribeOpts {
+    pub(super) finalized: bool,
+    pub(super) confirmations: u32,
+    pub(super) start_block: Option<u64>,
+    pub(super) names: EventNames,
+    pub(super) buffer_size: usize,
+    pub(super) strict: bool,
+}
+
+impl Default for SubscribeOpts {
+    fn default() -> Self {
+        SubscribeOpts {
+            finalized: true,
+            confirmations: 0,
+            start_block: None,
+            names: EventNames::default(),
+            buffer_size: 1,
+            strict: false,
+        }
+    }
+}
+
+impl SubscribeOpts {
+    /// The default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Follow finalized blocks (the default), or else best blocks. Best blocks
+    /// can be retracted, so events from them aren't necessarily final.
+    pub fn finalized(mut self, finalized: bool) -> Self {
+        self.finalized = finalized;
+        self
+    }
+
+    /// Hand back the events of a block only once this many more blocks have been
+    /// announced on top of it. Blocks are then looked up by number, so that each
+    /// height is handed back once, from whichever fork the node considers best
+    /// at the time, which makes handing back events from a retracted fork less
+    /// likely when following best blocks. Defaults to 0.
+    pub fn confirmations(mut self, confirmations: u32) -> Self {
+        self.confirmations = confirmations;
+        self
+    }
+
+    /// Start with the events of the block with this number, catching up with
+    /// the chain from there, rather than with the next block announced.
+    pub fn start_block(mut self, block_number: u64) -> Self {
+        self.start_block = Some(block_number);
+        self
+    }
+
+    /// Only hand back events from the pallet given. Can be given more than once,
+    /// along with [`SubscribeOpts::event()`], to hand back the events matching
+    /// any of them. By default every event is handed back.
+    pub fn pallet(mut self, pallet: impl Into<String>) -> Self {
+        self.names.push(pallet.into(), None);
+        self
+    }
+
+    /// Only hand back the event with the pallet and variant names given. See
+    /// [`SubscribeOpts::pallet()`].
+    pub fn event(mut self, pallet: impl Into<String>, variant: impl Into<String>) -> Self {
+        self.names.push(pallet.into(), Some(variant.into()));
+        self
+    }
+
+    /// How many blocks' events are fetched at once, ahead of the one being
+    /// handed back. They're still handed back in order. Defaults to 1.
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size.max(1);
+        self
+    }
+
+    /// Decode the fields of every event (that isn't filtered out) before the
+    /// events of a block are handed back, and hand back an error in their place
+    /// if any can't be. By default, events that can't be decoded are only found
+    /// out about while iterating over them.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+}
+
+// The names of the events to hand back, as pallet names and optionally variant
+// names. No names means every event.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct EventNames(Arc<Vec<(String, Option<String>)>>);
+
+impl EventNames {
+    fn push(&mut self, pallet: String, variant: Option<String>) {
+        Arc::make_mut(&mut self.0).push((pallet, variant));
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn matches(&self, pallet: &str, variant: &str) -> bool {
+        self.is_empty()
+            || self.0.iter().any(|(p, v)| {
+                p == pallet && v.as_deref().map_or(true, |v| v == variant)
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::events::{
+        test_utils::{
+            event_record,
+            events,
+            metadata,
+        },
+        Phase,
+    };
+    use codec::{
+        Decode,
+        Encode,
+    };
+    use scale_info::TypeInfo;
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Event {
+        A(u8),
+        B(u8),
+    }
+
+    #[test]
+    fn events_are_filtered_by_name() {
+        let opts = SubscribeOpts::new().event("Test", "B").pallet("Other");
+        assert!(opts.names.matches("Test", "B"));
+        assert!(!opts.names.matches("Test", "A"));
+        assert!(opts.names.matches("Other", "A"));
+        assert!(SubscribeOpts::new().names.matches("Test", "A"));
+
+        let events = events::<Event>(
+            metadata::<Event>(),
+            vec![
+                event_record(Phase::Finalization, Event::A(1)),
+                event_record(Phase::Finalization, Event::B(2)),
+                event_record(Phase::Finalization, Event::A(3)),
+                event_record(Phase::Finalization, Event::B(4)),
+            ],
+        )
+        .with_names(opts.names);
+        let fields: Vec<_> = events
+            .iter()
+            .map(|ev| ev.unwrap().field_bytes().to_vec())
+            .collect();
+        assert_eq!(fields, vec![vec![2], vec![4]]);
+    }
+}
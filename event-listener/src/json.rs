@@ -0,0 +1,431 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Converting decoded values, such as the fields of events, to and from JSON.
+//!
+//! [`value_to_json()`] uses the type information in the metadata to write each
+//! value out in the form that's easiest to work with in JSON:
+//!
+//! - Named fields become objects, and unnamed fields arrays.
+//! - Variants become their name if they have no fields, or else an object of
+//!   their name to their fields, such as `{ "Some": [1] }`.
+//! - Byte arrays and sequences become `0x` prefixed hex strings.
+//! - Integers that don't fit in 64 bits become strings of their digits, since
+//!   most JSON parsers can't read them back as numbers. 256 bit integers become
+//!   hex strings of their little endian bytes.
+//! - Bit sequences become strings of `0`s and `1`s.
+//!
+//! [`json_to_value()`] reads JSON in that form back into a value of the type
+//! given, ready to be encoded. Integers can be given as numbers or strings.
+//!
+//! ```no_run
+//! use event_listener::{
+//!     json,
+//!     OnlineClient,
+//!     PolkadotConfig,
+//! };
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+//! let events = api.events().at(None).await.unwrap();
+//! for event in events.iter() {
+//!     let event = event.unwrap();
+//!     let fields = event.field_values().unwrap();
+//!     let payload = serde_json::json!({
+//!         "event": format!("{}.{}", event.pallet_name(), event.variant_name()),
+//!         "fields": json::composite_to_json(&fields, &api.metadata()),
+//!     });
+//!     println!("{}", payload);
+//! }
+//! # }
+//! ```
+
+use crate::{
+    error::Error,
+    utils::{
+        composite_values,
+        is_bytes,
+        to_hex,
+        value_as_bytes,
+    },
+    Metadata,
+};
+use scale_info::{
+    form::PortableForm,
+    Field,
+    TypeDef,
+    TypeDefPrimitive,
+};
+use scale_value::{
+    scale::TypeId,
+    Composite,
+    Primitive,
+    Value,
+    ValueDef,
+};
+use serde_json::{
+    Map,
+    Value as Json,
+};
+
+/// Convert a decoded value to JSON. See the [module docs](self) for the form
+/// that it takes.
+pub fn value_to_json(value: &Value<TypeId>, metadata: &Metadata) -> Json {
+    match &value.value {
+        ValueDef::Composite(_) if is_bytes(value.context, metadata) => {
+            let bytes = value_as_bytes(value).unwrap_or_default();
+            Json::String(to_hex(bytes))
+        }
+        ValueDef::Composite(c) => composite_to_json(c, metadata),
+        ValueDef::Variant(v) if composite_values(&v.values).next().is_none() => {
+            Json::String(v.name.clone())
+        }
+        ValueDef::Variant(v) => {
+            let mut variant = Map::new();
+            variant.insert(v.name.clone(), composite_to_json(&v.values, metadata));
+            Json::Object(variant)
+        }
+        ValueDef::BitSequence(bits) => {
+            Json::String(bits.iter().map(|b| if *b { '1' } else { '0' }).collect())
+        }
+        ValueDef::Primitive(p) => primitive_to_json(p),
+    }
+}
+
+/// Convert decoded fields, such as those of an event, to JSON: an object if
+/// they're named, or else an array.
+pub fn composite_to_json(composite: &Composite<TypeId>, metadata: &Metadata) -> Json {
+    match composite {
+        Composite::Named(fields) => {
+            let fields: Map<String, Json> = fields
+                .iter()
+                .map(|(name, value)| (name.clone(), value_to_json(value, metadata)))
+                .collect();
+            Json::Object(fields)
+        }
+        Composite::Unnamed(fields) => {
+            Json::Array(fields.iter().map(|v| value_to_json(v, metadata)).collect())
+        }
+    }
+}
+
+fn primitive_to_json(primitive: &Primitive) -> Json {
+    match primitive {
+        Primitive::Bool(b) => Json::Bool(*b),
+        Primitive::Char(c) => Json::String(c.to_string()),
+        Primitive::String(s) => Json::String(s.clone()),
+        Primitive::U128(n) => {
+            u64::try_from(*n).map_or_else(|_| Json::String(n.to_string()), Json::from)
+        }
+        Primitive::I128(n) => {
+            i64::try_from(*n).map_or_else(|_| Json::String(n.to_string()), Json::from)
+        }
+        Primitive::U256(bytes) | Primitive::I256(bytes) => {
+            Json::String(to_hex(bytes))
+        }
+    }
+}
+
+/// Read JSON, in the form that [`value_to_json()`] writes it, back into a value
+/// of the type given. Its context is left empty, ready for encoding with
+/// [`scale_value::scale::encode_as_type()`].
+///
+/// The type of a decoded value is its context, so a value survives a round trip:
+///
+/// ```
+/// # use event_listener::{ ext::scale_value::{ scale::TypeId, Value }, json, Metadata };
+/// # fn round_trip(value: Value<TypeId>, metadata: &Metadata) {
+/// let json = json::value_to_json(&value, metadata);
+/// let read = json::json_to_value(&json, value.context, metadata).unwrap();
+/// assert_eq!(read, value.remove_context());
+/// # }
+/// ```
+pub fn json_to_value(
+    json: &Json,
+    type_id: TypeId,
+    metadata: &Metadata,
+) -> Result<Value<()>, Error> {
+    let types = &metadata.runtime_metadata().types;
+    let ty = types.resolve(type_id.id()).ok_or_else(|| {
+        Error::Other(format!("Type {} not found in the metadata", type_id.id()))
+    })?;
+
+    match ty.type_def() {
+        TypeDef::Composite(composite) => {
+            fields_from_json(json, composite.fields(), metadata)
+                .map(|c| without_context(ValueDef::Composite(c)))
+        }
+        TypeDef::Variant(variant) => {
+            let no_fields = Json::Null;
+            let (name, fields) = match json {
+                Json::String(name) => (name, &no_fields),
+                Json::Object(map) if map.len() == 1 => map.iter().next().expect("one entry"),
+                _ => return Err(mismatch(json, "a variant")),
+            };
+            let v = variant
+                .variants()
+                .iter()
+                .find(|v| v.name() == name)
+                .ok_or_else(|| Error::Other(format!("No variant named {}", name)))?;
+            let fields = fields_from_json(fields, v.fields(), metadata)?;
+            Ok(Value::variant(name.clone(), fields))
+        }
+        TypeDef::Sequence(_) | TypeDef::Array(_) if is_bytes(type_id, metadata) => {
+            let bytes = json
+                .as_str()
+                .and_then(|s| hex::decode(s.trim_start_matches("0x")).ok())
+                .ok_or_else(|| mismatch(json, "a hex string"))?;
+            if let TypeDef::Array(arr) = ty.type_def() {
+                if bytes.len() != arr.len() as usize {
+                    return Err(mismatch(json, &format!("{} bytes", arr.len())))
+                }
+            }
+            let bytes = bytes.into_iter().map(|b| Value::u128(b.into())).collect();
+            Ok(Value::unnamed_composite(bytes))
+        }
+        TypeDef::Sequence(seq) => items_from_json(json, seq.type_param().id(), None, metadata),
+        TypeDef::Array(arr) => {
+            items_from_json(json, arr.type_param().id(), Some(arr.len()), metadata)
+        }
+        TypeDef::Tuple(tuple) => {
+            let items = json.as_array().ok_or_else(|| mismatch(json, "an array"))?;
+            if items.len() != tuple.fields().len() {
+                return Err(mismatch(json, &format!("{} items", tuple.fields().len())))
+            }
+            let values = items
+                .iter()
+                .zip(tuple.fields())
+                .map(|(item, ty)| json_to_value(item, ty.id().into(), metadata))
+                .collect::<Result<_, _>>()?;
+            Ok(Value::unnamed_composite(values))
+        }
+        TypeDef::Compact(compact) => {
+            json_to_value(json, compact.type_param().id().into(), metadata)
+        }
+        TypeDef::BitSequence(_) => {
+            let bits = json
+                .as_str()
+                .filter(|s| s.chars().all(|c| c == '0' || c == '1'))
+                .ok_or_else(|| mismatch(json, "a string of bits"))?;
+            let bits = bits.chars().map(|c| c == '1').collect();
+            Ok(without_context(ValueDef::BitSequence(bits)))
+        }
+        TypeDef::Primitive(primitive) => primitive_from_json(json, primitive),
+    }
+}
+
+fn fields_from_json(
+    json: &Json,
+    fields: &[Field<PortableForm>],
+    metadata: &Metadata,
+) -> Result<Composite<()>, Error> {
+    let named = fields.iter().all(|f| f.name().is_some());
+    match json {
+        // No fields at all.
+        Json::Null if fields.is_empty() => Ok(Composite::Unnamed(Vec::new())),
+        Json::Object(map) if named => {
+            let values = fields
+                .iter()
+                .map(|f| {
+                    let name = f.name().expect("fields are named");
+                    let json = map
+                        .get(name)
+                        .ok_or_else(|| Error::Other(format!("Field {} is missing", name)))?;
+                    Ok((name.clone(), json_to_value(json, f.ty().id().into(), metadata)?))
+                })
+                .collect::<Result<_, Error>>()?;
+            Ok(Composite::Named(values))
+        }
+        Json::Array(items) if items.len() == fields.len() => {
+            let values = items
+                .iter()
+                .zip(fields)
+                .map(|(item, f)| json_to_value(item, f.ty().id().into(), metadata))
+                .collect::<Result<_, _>>()?;
+            Ok(Composite::Unnamed(values))
+        }
+        _ if named && !fields.is_empty() => Err(mismatch(json, "an object")),
+        _ => Err(mismatch(json, &format!("an array of {} items", fields.len()))),
+    }
+}
+
+// The items of a sequence, or of an array if its length is given.
+fn items_from_json(
+    json: &Json,
+    item_type: u32,
+    len: Option<u32>,
+    metadata: &Metadata,
+) -> Result<Value<()>, Error> {
+    let items = json.as_array().ok_or_else(|| mismatch(json, "an array"))?;
+    if let Some(len) = len.filter(|len| *len as usize != items.len()) {
+        return Err(mismatch(json, &format!("{} items", len)))
+    }
+    let values = items
+        .iter()
+        .map(|item| json_to_value(item, item_type.into(), metadata))
+        .collect::<Result<_, _>>()?;
+    Ok(Value::unnamed_composite(values))
+}
+
+fn primitive_from_json(json: &Json, primitive: &TypeDefPrimitive) -> Result<Value<()>, Error> {
+    let value = match primitive {
+        TypeDefPrimitive::Bool => json.as_bool().map(Value::bool),
+        TypeDefPrimitive::Char => {
+            let mut chars = json.as_str().unwrap_or_default().chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Some(Value::char(c)),
+                _ => None,
+            }
+        }
+        TypeDefPrimitive::Str => json.as_str().map(Value::string),
+        TypeDefPrimitive::U8
+        | TypeDefPrimitive::U16
+        | TypeDefPrimitive::U32
+        | TypeDefPrimitive::U64
+        | TypeDefPrimitive::U128 => {
+            let n: Option<u128> = match json {
+                Json::Number(n) => n.as_u64().map(u128::from),
+                Json::String(s) => s.parse().ok(),
+                _ => None,
+            };
+            n.map(Value::u128)
+        }
+        TypeDefPrimitive::I8
+        | TypeDefPrimitive::I16
+        | TypeDefPrimitive::I32
+        | TypeDefPrimitive::I64
+        | TypeDefPrimitive::I128 => {
+            let n: Option<i128> = match json {
+                Json::Number(n) => n.as_i64().map(i128::from),
+                Json::String(s) => s.parse().ok(),
+                _ => None,
+            };
+            n.map(Value::i128)
+        }
+        TypeDefPrimitive::U256 | TypeDefPrimitive::I256 => {
+            json.as_str()
+                .and_then(|s| hex::decode(s.trim_start_matches("0x")).ok())
+                .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+                .map(|bytes| {
+                    let primitive = match primitive {
+                        TypeDefPrimitive::U256 => Primitive::U256(bytes),
+                        _ => Primitive::I256(bytes),
+                    };
+                    without_context(ValueDef::Primitive(primitive))
+                })
+        }
+    };
+    value.ok_or_else(|| mismatch(json, &format!("a {:?}", primitive)))
+}
+
+fn without_context(value: ValueDef<()>) -> Value<()> {
+    Value { value, context: () }
+}
+
+fn mismatch(json: &Json, expected: &str) -> Error {
+    Error::Other(format!("Expected {} in JSON, but found {}", expected, json))
+}
+
+#[cfg(all(test, feature = "substrate-compat"))]
+mod test {
+    use super::*;
+    use crate::{
+        events::Phase,
+        pallets::test_utils::account_value,
+        testing::{
+            test_runtime_metadata,
+            EventsBuilder,
+        },
+        SubstrateConfig,
+    };
+
+    #[test]
+    fn event_fields_survive_a_round_trip() {
+        let metadata = test_runtime_metadata();
+        let events = EventsBuilder::<SubstrateConfig>::new(metadata.clone())
+            .event(
+                Phase::ApplyExtrinsic(1),
+                "Balances",
+                "Transfer",
+                Composite::Named(vec![
+                    ("from".into(), account_value(1)),
+                    ("to".into(), account_value(2)),
+                    ("amount".into(), Value::u128(u128::MAX)),
+                ]),
+            )
+            .event(
+                Phase::ApplyExtrinsic(1),
+                "System",
+                "ExtrinsicSuccess",
+                Composite::Named(vec![(
+                    "dispatch_info".into(),
+                    Value::named_composite(vec![
+                        ("weight".into(), Value::u128(10)),
+                        ("class".into(), Value::variant("Normal", Composite::Unnamed(vec![]))),
+                        ("pays_fee".into(), Value::variant("Yes", Composite::Unnamed(vec![]))),
+                    ]),
+                )]),
+            )
+            .build()
+            .unwrap();
+
+        let fields: Vec<_> = events
+            .iter()
+            .map(|ev| ev.unwrap().field_values().unwrap())
+            .collect();
+        let transfer = composite_to_json(&fields[0], &metadata);
+        assert_eq!(
+            transfer,
+            serde_json::json!({
+                "from": [format!("0x{}", "01".repeat(32))],
+                "to": [format!("0x{}", "02".repeat(32))],
+                "amount": u128::MAX.to_string(),
+            })
+        );
+        let success = composite_to_json(&fields[1], &metadata);
+        assert_eq!(
+            success,
+            serde_json::json!({
+                "dispatch_info": { "weight": 10, "class": "Normal", "pays_fee": "Yes" },
+            })
+        );
+
+        for value in fields.into_iter().flat_map(|f| f.into_values()) {
+            let json = value_to_json(&value, &metadata);
+            let read = json_to_value(&json, value.context, &metadata).unwrap();
+            assert_eq!(read, value.remove_context());
+        }
+    }
+
+    #[test]
+    fn json_of_the_wrong_shape_is_an_error() {
+        let metadata = test_runtime_metadata();
+        let events = EventsBuilder::<SubstrateConfig>::new(metadata.clone())
+            .event(
+                Phase::ApplyExtrinsic(1),
+                "Balances",
+                "Transfer",
+                Composite::Named(vec![
+                    ("from".into(), account_value(1)),
+                    ("to".into(), account_value(2)),
+                    ("amount".into(), Value::u128(1)),
+                ]),
+            )
+            .build()
+            .unwrap();
+        let event = events.iter().next().unwrap().unwrap();
+        let fields: Vec<_> = event.field_values().unwrap().into_values().collect();
+
+        let from = fields[0].context;
+        assert!(json_to_value(&serde_json::json!(["0x01"]), from, &metadata).is_err());
+        let amount = fields[2].context;
+        assert!(json_to_value(&serde_json::json!(-1), amount, &metadata).is_err());
+        assert!(json_to_value(&serde_json::json!("ten"), amount, &metadata).is_err());
+        assert_eq!(
+            json_to_value(&serde_json::json!("10"), amount, &metadata).unwrap(),
+            Value::u128(10)
+        );
+    }
+}
@@ -104,10 +104,25 @@
 //! leave the runtime state of the node out of sync with the information Subxt requires to do things like submit
 //! transactions.
 //!
-//! If this is a concern, you can use the `UpdateClient` API to keep the `RuntimeVersion` and `Metadata` of the client
-//! synced with the target node.
+//! If this is a concern, you can use [`OnlineClient::update_runtime()`] to keep the `RuntimeVersion` and `Metadata` of
+//! the client synced with the target node. Events fetched after that are decoded with the new metadata.
 //!
 //! Please visit the [subscribe_runtime_updates](../examples/examples/subscribe_runtime_updates.rs) example for more details.
+//!
+//! # Decoding events offline
+//!
+//! Programs which only decode events from bytes that they already have (with
+//! [`events::EventsDecoder`], say) don't need to connect to a node or sign
+//! anything, and can leave out the Substrate primitives, RPC client, key pairs
+//! and Ethereum signatures, which are most of the dependency tree, with:
+//!
+//! ```toml
+//! event-listener = { version = "0.1", default-features = false, features = ["minimal"] }
+//! ```
+//!
+//! That leaves [`metadata`], [`events::EventDetails`], [`events::EventsDecoder`]
+//! and [`json`]. Everything that's generic over a `Config` needs the
+//! `substrate-compat` feature.
 
 #![deny(
     bad_style,
@@ -135,16 +150,45 @@
 
 //pub use subxt_macro::subxt;
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "substrate-compat")]
+pub mod blocks;
+#[cfg(feature = "substrate-compat")]
 pub mod client;
+#[cfg(feature = "substrate-compat")]
 pub mod config;
+#[cfg(feature = "substrate-compat")]
+pub mod constants;
 pub mod error;
 pub mod events;
+pub mod json;
+#[cfg(feature = "substrate-compat")]
+pub mod listener;
 pub mod metadata;
+#[cfg(feature = "substrate-compat")]
+pub mod pallets;
+#[cfg(feature = "substrate-compat")]
+pub mod prelude;
+#[cfg(feature = "substrate-compat")]
 pub mod rpc;
+#[cfg(feature = "substrate-compat")]
+pub mod runtime_api;
+#[cfg(feature = "substrate-compat")]
+pub mod storage;
+#[cfg(feature = "substrate-compat")]
+pub mod testing;
+#[cfg(feature = "substrate-compat")]
+pub mod tx;
 pub mod utils;
 
 // Expose a few of the most common types at root,
 // but leave most types behind their respoctive modules.
+pub use crate::{
+    error::Error,
+    metadata::Metadata,
+};
+#[cfg(feature = "substrate-compat")]
 pub use crate::{
     client::{
         OfflineClient,
@@ -155,16 +199,42 @@ pub use crate::{
         PolkadotConfig,
         SubstrateConfig,
     },
-    error::Error,
-    metadata::Metadata,
 };
 
 /// Re-export external crates that are made use of in the subxt API.
+///
+/// These are the versions that this crate is built against, so using them from
+/// here, rather than depending on them directly, means that the types match up
+/// without having to pin the same versions of the Substrate crates.
 pub mod ext {
     pub use bitvec;
     pub use codec;
     pub use frame_metadata;
+    #[cfg(feature = "mqtt")]
+    pub use rumqttc;
+    pub use scale_decode;
+    pub use scale_info;
     pub use scale_value;
+    pub use serde_json;
+    #[cfg(feature = "substrate-compat")]
     pub use sp_core;
+    #[cfg(feature = "substrate-compat")]
     pub use sp_runtime;
+
+    // The types from the above that turn up the most.
+    pub use codec::{
+        Compact,
+        Decode,
+        Encode,
+    };
+    #[cfg(feature = "substrate-compat")]
+    pub use sp_core::{
+        crypto::{
+            AccountId32,
+            Ss58Codec,
+        },
+        H256,
+    };
+    #[cfg(feature = "substrate-compat")]
+    pub use sp_runtime::MultiAddress;
 }
@@ -0,0 +1,278 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Acknowledging events, so that the checkpoint of a listener only moves past
+//! an event once it has been dealt with.
+
+use super::checkpoint::EventCursor;
+use parking_lot::Mutex;
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{
+            AtomicBool,
+            Ordering,
+        },
+        Arc,
+    },
+};
+
+/// When events are acknowledged. The checkpoint of a listener never moves past
+/// an event until every handler that it was handed to has acknowledged it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AckMode {
+    /// Events are acknowledged as soon as the handler returns, whether or not it
+    /// succeeded.
+    Auto,
+    /// Handlers acknowledge events themselves, with [`super::EventContext::ack()`],
+    /// once they're sure that the event has been dealt with (for example, once a
+    /// batch of events has been written somewhere). Events that are never
+    /// acknowledged are handed over again when the listener is restarted, so
+    /// every event is processed at least once.
+    Manual,
+}
+
+impl Default for AckMode {
+    fn default() -> Self {
+        AckMode::Auto
+    }
+}
+
+/// A handle to acknowledge a single event with. Clones acknowledge the same
+/// event, and acknowledging it more than once does nothing.
+#[derive(Clone)]
+pub struct Ack(Option<Arc<AckInner>>);
+
+struct AckInner {
+    seq: u64,
+    acked: AtomicBool,
+    tracker: AckTracker,
+}
+
+impl Ack {
+    /// An ack which isn't tracked by anything.
+    pub(crate) fn noop() -> Ack {
+        Ack(None)
+    }
+
+    /// Acknowledge the event.
+    pub fn ack(&self) {
+        if let Some(inner) = &self.0 {
+            if !inner.acked.swap(true, Ordering::AcqRel) {
+                inner.tracker.ack(inner.seq);
+            }
+        }
+    }
+
+    /// Has the event been acknowledged?
+    pub fn is_acked(&self) -> bool {
+        match &self.0 {
+            Some(inner) => inner.acked.load(Ordering::Acquire),
+            None => true,
+        }
+    }
+}
+
+impl std::fmt::Debug for Ack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Ack")
+            .field("seq", &self.0.as_ref().map(|inner| inner.seq))
+            .field("acked", &self.is_acked())
+            .finish()
+    }
+}
+
+/// Collects [`Ack`]s so that a batch of events can be acknowledged at once, for
+/// instance once they have all been written out together.
+#[derive(Debug, Default)]
+pub struct AckBatch {
+    acks: Vec<Ack>,
+}
+
+impl AckBatch {
+    /// Create an empty batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an event to the batch.
+    pub fn push(&mut self, ack: Ack) {
+        self.acks.push(ack);
+    }
+
+    /// The number of events in the batch.
+    pub fn len(&self) -> usize {
+        self.acks.len()
+    }
+
+    /// Is the batch empty?
+    pub fn is_empty(&self) -> bool {
+        self.acks.is_empty()
+    }
+
+    /// Acknowledge every event in the batch, leaving it empty.
+    pub fn ack_all(&mut self) {
+        for ack in self.acks.drain(..) {
+            ack.ack();
+        }
+    }
+}
+
+/// Keeps track of which events are still waiting to be acknowledged, and how
+/// far the checkpoint can be moved on as a result.
+#[derive(Clone, Default)]
+pub(crate) struct AckTracker(Arc<Mutex<TrackerState>>);
+
+#[derive(Default)]
+struct TrackerState {
+    // The sequence number of the first entry in `pending`.
+    base: u64,
+    // Cursors in the order they were handed out, along with the number of acks
+    // still needed before the checkpoint can move past each.
+    pending: VecDeque<(EventCursor, usize)>,
+    // The furthest cursor that everything up to has been acknowledged.
+    committed: Option<EventCursor>,
+    // The number of events acknowledged since `committed` was last taken.
+    since_taken: usize,
+}
+
+impl AckTracker {
+    /// Start tracking the cursor given, which needs `acks` acknowledgements before
+    /// the checkpoint can move past it. Cursors which need none (such as the ends
+    /// of blocks) are passed as soon as everything before them has been.
+    pub(crate) fn track(&self, cursor: EventCursor, acks: usize) -> Vec<Ack> {
+        let mut state = self.0.lock();
+        let seq = state.base + state.pending.len() as u64;
+        state.pending.push_back((cursor, acks));
+        state.advance();
+        drop(state);
+
+        (0..acks)
+            .map(|_| {
+                Ack(Some(Arc::new(AckInner {
+                    seq,
+                    acked: AtomicBool::new(false),
+                    tracker: self.clone(),
+                })))
+            })
+            .collect()
+    }
+
+    fn ack(&self, seq: u64) {
+        let mut state = self.0.lock();
+        let idx = match seq.checked_sub(state.base) {
+            Some(idx) => idx as usize,
+            None => return,
+        };
+        if let Some((_, remaining)) = state.pending.get_mut(idx) {
+            *remaining = remaining.saturating_sub(1);
+        }
+        state.advance();
+    }
+
+    /// Take the furthest cursor that everything has been acknowledged up to, if it
+    /// has moved on since last time. Unless it's at the end of a block, it's only
+    /// handed back once at least `batch` events have been acknowledged.
+    pub(crate) fn take_committed(&self, batch: usize) -> Option<EventCursor> {
+        let mut state = self.0.lock();
+        let at_block_end = state
+            .committed
+            .as_ref()
+            .map_or(false, |c| c.event_index.is_none());
+        if at_block_end || state.since_taken >= batch.max(1) {
+            state.since_taken = 0;
+            state.committed.take()
+        } else {
+            None
+        }
+    }
+
+    /// Stop tracking every cursor handed out so far, ignoring any acknowledgements
+    /// still to come for them.
+    pub(crate) fn forget(&self) {
+        let mut state = self.0.lock();
+        state.base += state.pending.len() as u64;
+        state.pending.clear();
+        state.committed = None;
+        state.since_taken = 0;
+    }
+}
+
+impl TrackerState {
+    fn advance(&mut self) {
+        while matches!(self.pending.front(), Some((_, 0))) {
+            let (cursor, _) = self.pending.pop_front().expect("front exists; qed");
+            self.base += 1;
+            if cursor.event_index.is_some() {
+                self.since_taken += 1;
+            }
+            self.committed = Some(cursor);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn event(block: u64, idx: u32) -> EventCursor {
+        EventCursor::event(block, vec![0; 32], idx)
+    }
+
+    #[test]
+    fn checkpoint_waits_for_earlier_acks() {
+        let tracker = AckTracker::default();
+        let first = tracker.track(event(1, 0), 2);
+        let second = tracker.track(event(1, 1), 1);
+        tracker.track(EventCursor::block(1, vec![0; 32]), 0);
+
+        // Acking a later event doesn't move the checkpoint past an earlier one.
+        second[0].ack();
+        first[0].ack();
+        assert_eq!(tracker.take_committed(1), None);
+
+        // Acking twice counts once.
+        first[0].clone().ack();
+        assert_eq!(tracker.take_committed(1), None);
+
+        first[1].ack();
+        assert_eq!(
+            tracker.take_committed(1),
+            Some(EventCursor::block(1, vec![0; 32]))
+        );
+        assert_eq!(tracker.take_committed(1), None);
+    }
+
+    #[test]
+    fn batches_are_acked_together() {
+        let tracker = AckTracker::default();
+        let mut batch = AckBatch::new();
+        for idx in 0..3 {
+            batch.push(tracker.track(event(1, idx), 1).remove(0));
+        }
+        assert_eq!(batch.len(), 3);
+
+        batch.ack_all();
+        assert!(batch.is_empty());
+        // Not at the end of a block, so only handed back once enough are acked.
+        assert_eq!(tracker.take_committed(5), None);
+        tracker.track(event(1, 3), 1)[0].ack();
+        tracker.track(event(1, 4), 1)[0].ack();
+        assert_eq!(tracker.take_committed(5), Some(event(1, 4)));
+    }
+
+    #[test]
+    fn forgotten_cursors_are_never_committed() {
+        let tracker = AckTracker::default();
+        let stale = tracker.track(event(1, 0), 1);
+        tracker.forget();
+
+        // Late acks for what was forgotten don't count towards what comes next.
+        let fresh = tracker.track(event(2, 0), 1);
+        stale[0].ack();
+        assert_eq!(tracker.take_committed(1), None);
+        fresh[0].ack();
+        assert_eq!(tracker.take_committed(1), Some(event(2, 0)));
+    }
+}
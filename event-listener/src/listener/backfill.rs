@@ -0,0 +1,326 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Handling the events in ranges of past blocks.
+
+use super::{
+    builder::EventListener,
+    checkpoint::CheckpointStore,
+    handler::BlockContext,
+};
+use crate::{
+    blocks::Block,
+    client::OnlineClientT,
+    error::{
+        BlockError,
+        Error,
+    },
+    events::Events,
+    Config,
+    Metadata,
+};
+use futures::{
+    future::{
+        self,
+        Either,
+    },
+    stream,
+    FutureExt,
+    StreamExt,
+    TryStreamExt,
+};
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    ops::RangeInclusive,
+    sync::Arc,
+};
+
+/// How many blocks a [`Backfill`] fetches at once, unless told otherwise.
+pub const DEFAULT_BACKFILL_FETCHERS: usize = 8;
+
+/// How many block hashes a [`Backfill`] asks for in each request, unless told
+/// otherwise.
+pub const DEFAULT_BACKFILL_HASH_BATCH: usize = 256;
+
+/// Hands the events in a range of past blocks to the handlers of an
+/// [`EventListener`], just as [`EventListener::run()`] would have when they were
+/// new, so that new handlers can be caught up with history. Create one with
+/// [`EventListener::backfill()`].
+///
+/// Blocks are fetched by number, so the node needs to be an archive node to
+/// backfill anything but recent blocks. Several blocks (and their events) are
+/// fetched at once, which is where the time goes when backfilling, but their
+/// events are handed to the handlers strictly in order, one block after another.
+/// Events are decoded with the metadata of the runtime they were emitted by,
+/// which is fetched once for each runtime version in the range. The hashes of
+/// the blocks are looked up many at a time (see [`Backfill::hash_batch()`]),
+/// rather than with a request for each block.
+///
+/// Backfilling doesn't touch the checkpoint store, health or progress of the
+/// listener, and shouldn't be done while it's running. Give the backfill a
+/// checkpoint store of its own with [`Backfill::checkpoint_store()`] to be able
+/// to carry on from where it left off if it's stopped part way through.
+///
+/// # Example
+///
+/// ```no_run
+/// # use event_listener::{ events::EventDetails, listener::{ EventContext, HandlerResult }, OnlineClient, PolkadotConfig };
+/// # type Ctx = EventContext<PolkadotConfig, OnlineClient<PolkadotConfig>>;
+/// # async fn index_transfer(ctx: Ctx, event: EventDetails) -> HandlerResult { Ok(()) }
+/// use event_listener::listener::FileCheckpointStore;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// # let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let listener = api
+///     .listener()
+///     .on_dynamic("Balances", "Transfer", index_transfer)
+///     .build();
+///
+/// listener
+///     .backfill(0..=10_000_000)
+///     .fetchers(32)
+///     .checkpoint_store(FileCheckpointStore::new("backfill.json"))
+///     .run()
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+pub struct Backfill<'a, T: Config, Client> {
+    listener: &'a EventListener<T, Client>,
+    blocks: RangeInclusive<u64>,
+    fetchers: usize,
+    hash_batch: usize,
+    checkpoint_store: Option<Arc<dyn CheckpointStore>>,
+}
+
+impl<'a, T: Config, Client> std::fmt::Debug for Backfill<'a, T, Client> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Backfill")
+            .field("blocks", &self.blocks)
+            .field("fetchers", &self.fetchers)
+            .field("hash_batch", &self.hash_batch)
+            .field("checkpoints", &self.checkpoint_store.is_some())
+            .finish()
+    }
+}
+
+impl<'a, T, Client> Backfill<'a, T, Client>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    pub(super) fn new(
+        listener: &'a EventListener<T, Client>,
+        blocks: RangeInclusive<u64>,
+    ) -> Self {
+        Backfill {
+            listener,
+            blocks,
+            fetchers: DEFAULT_BACKFILL_FETCHERS,
+            hash_batch: DEFAULT_BACKFILL_HASH_BATCH,
+            checkpoint_store: None,
+        }
+    }
+
+    /// Fetch this many blocks at once (at least one). Defaults to
+    /// [`DEFAULT_BACKFILL_FETCHERS`]. Fetched blocks wait to be handled in order,
+    /// so at most this many are held in memory at a time.
+    pub fn fetchers(mut self, fetchers: usize) -> Self {
+        self.fetchers = fetchers.max(1);
+        self
+    }
+
+    /// Look up the hashes of this many blocks (at least one) in each request.
+    /// Defaults to [`DEFAULT_BACKFILL_HASH_BATCH`].
+    pub fn hash_batch(mut self, hash_batch: usize) -> Self {
+        self.hash_batch = hash_batch.max(1);
+        self
+    }
+
+    /// Save how far the backfill has got to the store given, and carry on from
+    /// there if the store has a cursor in it already. This should be a different
+    /// store to that of the listener, and only used for this range of blocks.
+    pub fn checkpoint_store(mut self, store: impl CheckpointStore) -> Self {
+        self.checkpoint_store = Some(Arc::new(store));
+        self
+    }
+
+    /// Fetch every block in the range, and hand their events to the handlers.
+    /// This returns once every block has been handled, or the listener has been
+    /// shut down with its [`super::ShutdownHandle`], or if a block or its events
+    /// can't be fetched or decoded, or a checkpoint or dead letter can't be saved.
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(
+            name = "backfill",
+            skip_all,
+            fields(from = self.blocks.start(), to = self.blocks.end(), fetchers = self.fetchers)
+        )
+    )]
+    pub async fn run(self) -> Result<(), Error> {
+        let listener = self.listener;
+        // Held until we return, letting shutdown handles know once we have.
+        let (mut stop_rx, _done_tx) = match listener.shutdown.take() {
+            Some((stop_rx, done_tx)) => (Some(stop_rx), Some(done_tx)),
+            None => (None, None),
+        };
+
+        let resume_from = match &self.checkpoint_store {
+            Some(store) => store.load().await?,
+            None => None,
+        };
+        let start = match &resume_from {
+            Some(cursor) => cursor.next_block().max(*self.blocks.start()),
+            None => *self.blocks.start(),
+        };
+        tracing::info!(
+            from = start,
+            to = *self.blocks.end(),
+            resume_from = ?resume_from,
+            "Backfilling blocks"
+        );
+
+        let store = self.checkpoint_store.as_deref();
+        let current_spec = listener.client.runtime_version().spec_version;
+        let metadata = Mutex::new(HashMap::new());
+        // Look the hashes up a batch ahead of the blocks being fetched.
+        let hashes = stream::iter(batches(start..=*self.blocks.end(), self.hash_batch))
+            .map(|numbers| self.block_hashes(numbers))
+            .buffered(2)
+            .map_ok(|hashes| stream::iter(hashes.into_iter().map(Ok)))
+            .try_flatten();
+        let mut blocks = hashes
+            .map(|hash| {
+                let fetch = hash.map(|hash| self.fetch(hash, current_spec, &metadata));
+                async move { fetch?.await }
+            })
+            .buffered(self.fetchers);
+
+        let (mut lanes, workers) = listener.dispatcher.lanes();
+        let backfilling = async move {
+            loop {
+                let stopping = match &mut stop_rx {
+                    Some(stop_rx) => Either::Left(stop_rx.map(|_| ())),
+                    None => Either::Right(future::pending()),
+                };
+                let (ctx, events) = match future::select(blocks.next(), stopping).await {
+                    Either::Left((Some(block), _)) => block?,
+                    Either::Left((None, _)) => {
+                        tracing::info!("Backfill finished");
+                        break
+                    }
+                    Either::Right(((), _)) => {
+                        tracing::info!("Shutting down backfill");
+                        break
+                    }
+                };
+                listener
+                    .dispatch_block(&ctx, &events, resume_from.as_ref(), store, &mut lanes)
+                    .await?;
+            }
+            // Dropping the lanes lets the workers finish once they've handled
+            // everything sent to them.
+            drop(lanes);
+            Ok::<_, Error>(())
+        };
+
+        let res = match future::select(backfilling.boxed(), workers).await {
+            Either::Left((res, workers)) => {
+                match res {
+                    Ok(()) => listener.drain(workers).await,
+                    Err(e) => Err(e),
+                }
+            }
+            // The workers only finish early if there are none, or one fails.
+            Either::Right((res, backfilling)) => {
+                match res {
+                    Ok(()) => backfilling.await,
+                    Err(e) => Err(e),
+                }
+            }
+        };
+        let res = match res {
+            Ok(()) => listener.save_checkpoint(store, 1).await,
+            Err(e) => Err(e),
+        };
+        // Nothing backfilled belongs in the checkpoint of the listener itself.
+        listener.dispatcher.acks.forget();
+        res
+    }
+
+    // The hashes of the blocks with the numbers given.
+    async fn block_hashes(&self, numbers: Vec<u64>) -> Result<Vec<T::Hash>, Error> {
+        let rpc = self.listener.client.rpc();
+        let hashes = rpc
+            .block_hashes(numbers.iter().map(|n| (*n).into()).collect())
+            .await?;
+        numbers
+            .into_iter()
+            .zip(hashes)
+            .map(|(number, hash)| {
+                hash.ok_or_else(|| BlockError::BlockNumberNotFound(number).into())
+            })
+            .collect()
+    }
+
+    // Fetch a block, along with its events, decoded with the metadata of the
+    // runtime at that block.
+    async fn fetch(
+        &self,
+        hash: T::Hash,
+        current_spec: u32,
+        metadata: &Mutex<HashMap<u32, Metadata>>,
+    ) -> Result<(BlockContext<T>, Events<T>), Error> {
+        let client = &self.listener.client;
+        let rpc = client.rpc();
+        let header = rpc
+            .header(Some(hash))
+            .await?
+            .ok_or_else(|| BlockError::block_hash_not_found(hash))?;
+
+        let spec_version = rpc.runtime_version(Some(hash)).await?.spec_version;
+        let cached = metadata.lock().get(&spec_version).cloned();
+        let block_metadata = match cached {
+            Some(block_metadata) => block_metadata,
+            // Fetchers might both fetch the metadata of a new runtime version,
+            // which does no harm.
+            None => {
+                let block_metadata = if spec_version == current_spec {
+                    client.metadata()
+                } else {
+                    tracing::debug!(spec_version, "Fetching historical metadata");
+                    rpc.metadata_at(Some(hash)).await?
+                };
+                metadata.lock().insert(spec_version, block_metadata.clone());
+                block_metadata
+            }
+        };
+
+        let block = Block::new(header, client.clone()).with_metadata(block_metadata);
+        self.listener.fetch_block(&block).await
+    }
+}
+
+// The numbers in the range, in batches of the size given.
+fn batches(blocks: RangeInclusive<u64>, size: usize) -> impl Iterator<Item = Vec<u64>> {
+    let mut blocks = blocks.peekable();
+    std::iter::from_fn(move || {
+        blocks.peek()?;
+        Some(blocks.by_ref().take(size).collect())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn blocks_are_batched_in_order() {
+        let batched: Vec<_> = batches(3..=9, 3).collect();
+        assert_eq!(batched, vec![vec![3, 4, 5], vec![6, 7, 8], vec![9]]);
+        assert_eq!(batches(5..=4, 3).count(), 0);
+    }
+}
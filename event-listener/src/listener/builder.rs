@@ -0,0 +1,842 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use super::{
+    ack::{
+        AckMode,
+        AckTracker,
+    },
+    backfill::Backfill,
+    checkpoint::{
+        CheckpointStore,
+        EventCursor,
+    },
+    concurrency::{
+        Concurrency,
+        Lanes,
+    },
+    dead_letter::{
+        DeadLetter,
+        DeadLetterSink,
+    },
+    handler::{
+        BlockContext,
+        Dispatcher,
+        DynamicHandler,
+        EventContext,
+        Handler,
+        HandlerError,
+        OnError,
+        Registered,
+        StaticHandler,
+    },
+    health::Health,
+    progress::Progress,
+    retry::RetryPolicy,
+    rules::{
+        Routes,
+        RuleSet,
+    },
+    shutdown::{
+        Shutdown,
+        ShutdownHandle,
+    },
+};
+use crate::{
+    blocks::{
+        Block,
+        BlocksClient,
+    },
+    client::OnlineClientT,
+    error::Error,
+    events::{
+        EventDetails,
+        Events,
+        EventsCache,
+        EventsClient,
+        StaticEvent,
+    },
+    Config,
+};
+use codec::{
+    Decode,
+    Encode,
+};
+use futures::{
+    future::{
+        self,
+        Either,
+    },
+    stream,
+    FutureExt,
+    StreamExt,
+};
+use sp_core::twox_128;
+use sp_runtime::traits::Header;
+use std::{
+    convert::Infallible,
+    ops::RangeInclusive,
+    sync::Arc,
+    time::Duration,
+};
+
+/// How long a listener waits for handlers to finish when shutting down, unless
+/// told otherwise.
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Build an [`EventListener`], by registering handlers for the events of interest.
+///
+/// # Example
+///
+/// ```no_run
+/// use event_listener::{
+///     events::EventDetails,
+///     listener::{ EventContext, HandlerError },
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+///
+/// type Ctx = EventContext<PolkadotConfig, OnlineClient<PolkadotConfig>>;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+///
+/// api.listener()
+///     .on_dynamic("Balances", "Transfer", |ctx: Ctx, event: EventDetails| {
+///         async move {
+///             println!("Transfer in block #{}: {:?}", ctx.block_number(), event.field_values()?);
+///             Ok::<_, HandlerError>(())
+///         }
+///     })
+///     .run()
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+pub struct EventListenerBuilder<T: Config, Client> {
+    client: Client,
+    handlers: Vec<Registered<T, Client>>,
+    default_retry: RetryPolicy,
+    best_blocks: bool,
+    on_error: OnError<T, Client>,
+    checkpoint_store: Option<Arc<dyn CheckpointStore>>,
+    ack_mode: AckMode,
+    checkpoint_batch: usize,
+    dead_letters: Option<Arc<dyn DeadLetterSink>>,
+    drain_timeout: Option<Duration>,
+    chain_id: Option<Arc<str>>,
+    health: Option<Health>,
+    progress: Option<Progress>,
+    poll_interval: Option<Duration>,
+    events_cache: Option<EventsCache<T>>,
+}
+
+impl<T: Config, Client> std::fmt::Debug for EventListenerBuilder<T, Client> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventListenerBuilder")
+            .field("handlers", &self.handlers.len())
+            .field("best_blocks", &self.best_blocks)
+            .field("checkpoints", &self.checkpoint_store.is_some())
+            .field("ack_mode", &self.ack_mode)
+            .field("checkpoint_batch", &self.checkpoint_batch)
+            .field("dead_letters", &self.dead_letters.is_some())
+            .field("drain_timeout", &self.drain_timeout)
+            .field("chain_id", &self.chain_id)
+            .field("health", &self.health.is_some())
+            .field("progress", &self.progress.is_some())
+            .field("poll_interval", &self.poll_interval)
+            .field("events_cache", &self.events_cache)
+            .finish()
+    }
+}
+
+impl<T, Client> EventListenerBuilder<T, Client>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    /// Start building a listener which follows the chain that the client given
+    /// is connected to.
+    pub fn new(client: Client) -> Self {
+        EventListenerBuilder {
+            client,
+            handlers: Vec::new(),
+            default_retry: RetryPolicy::none(),
+            best_blocks: false,
+            on_error: Arc::new(|ctx: &EventContext<T, Client>, e: &HandlerError| {
+                tracing::error!(
+                    "Handler for {}::{} (event {} in block {:?}) failed: {}",
+                    ctx.pallet_name(),
+                    ctx.variant_name(),
+                    ctx.event_index(),
+                    ctx.block_hash(),
+                    e
+                );
+                #[cfg(feature = "sentry")]
+                super::sentry::report_handler_error(ctx, e);
+            }),
+            checkpoint_store: None,
+            ack_mode: AckMode::Auto,
+            checkpoint_batch: 1,
+            dead_letters: None,
+            drain_timeout: Some(DEFAULT_DRAIN_TIMEOUT),
+            chain_id: None,
+            health: None,
+            progress: None,
+            poll_interval: None,
+            events_cache: None,
+        }
+    }
+
+    /// Handle every event which decodes to the static event type `Ev`.
+    pub fn on<Ev, H>(mut self, handler: H) -> Self
+    where
+        Ev: StaticEvent + 'static,
+        H: Handler<T, Client, Ev>,
+    {
+        self.handlers
+            .push(Registered::new(StaticHandler::<Ev, H>::new(handler)));
+        self
+    }
+
+    /// Handle every event with the given pallet and variant names, handing over
+    /// the dynamically decoded event.
+    pub fn on_dynamic<H>(
+        mut self,
+        pallet: impl Into<String>,
+        variant: impl Into<String>,
+        handler: H,
+    ) -> Self
+    where
+        H: Handler<T, Client, EventDetails>,
+    {
+        self.handlers.push(Registered::new(DynamicHandler::new(
+            pallet.into(),
+            variant.into(),
+            handler,
+        )));
+        self
+    }
+
+    /// Register a handler for each of the rules given, which hands the events
+    /// matching the rule to the handler of its route. Fails if a rule refers to a
+    /// route that doesn't exist, or has a condition that doesn't make sense. See
+    /// [`RuleSet`].
+    pub fn rules(mut self, rules: &RuleSet, routes: &Routes<T, Client>) -> Result<Self, Error> {
+        for rule in rules.compile(routes)? {
+            self.handlers.push(Registered::new(rule));
+        }
+        Ok(self)
+    }
+
+    /// Set the retry policy of the handler registered last, overriding the
+    /// [default](EventListenerBuilder::default_retry()). Does nothing if no
+    /// handlers have been registered yet.
+    ///
+    /// ```no_run
+    /// # use event_listener::{ events::EventDetails, listener::{ EventContext, HandlerResult, RetryPolicy }, OnlineClient, PolkadotConfig };
+    /// # type Ctx = EventContext<PolkadotConfig, OnlineClient<PolkadotConfig>>;
+    /// # async fn post_webhook(ctx: Ctx, event: EventDetails) -> HandlerResult { Ok(()) }
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+    /// api.listener()
+    ///     .on_dynamic("Balances", "Transfer", post_webhook)
+    ///     .retry(RetryPolicy::exponential(5))
+    ///     .run()
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        if let Some(registered) = self.handlers.last_mut() {
+            registered.retry = Some(policy);
+        }
+        self
+    }
+
+    /// Set how the handler registered last is run relative to other events;
+    /// [`Concurrency::Sequential`] by default. Does nothing if no handlers have
+    /// been registered yet.
+    ///
+    /// ```no_run
+    /// # use event_listener::{ events::EventDetails, listener::{ Concurrency, EventContext, HandlerResult }, OnlineClient, PolkadotConfig };
+    /// # type Ctx = EventContext<PolkadotConfig, OnlineClient<PolkadotConfig>>;
+    /// # async fn index_transfer(ctx: Ctx, event: EventDetails) -> HandlerResult { Ok(()) }
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+    /// // Transfers from the same account are handled in order, and up to 8 at once.
+    /// api.listener()
+    ///     .on_dynamic("Balances", "Transfer", index_transfer)
+    ///     .concurrency(Concurrency::keyed_by_field(8, "from"))
+    ///     .run()
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn concurrency(mut self, concurrency: Concurrency<T, Client>) -> Self {
+        if let Some(registered) = self.handlers.last_mut() {
+            registered.concurrency = concurrency;
+        }
+        self
+    }
+
+    /// The retry policy of handlers which weren't given their own with
+    /// [`EventListenerBuilder::retry()`]. By default, handlers aren't retried.
+    pub fn default_retry(mut self, policy: RetryPolicy) -> Self {
+        self.default_retry = policy;
+        self
+    }
+
+    /// Follow new best blocks rather than finalized blocks. Events are handed
+    /// over sooner, but may be from blocks which never end up being finalized.
+    pub fn best_blocks(mut self, best_blocks: bool) -> Self {
+        self.best_blocks = best_blocks;
+        self
+    }
+
+    /// Called whenever a handler fails. By default, the failure is logged (and,
+    /// with the `sentry` feature, reported to Sentry). The listener carries on
+    /// with the next event either way.
+    pub fn on_error(
+        mut self,
+        on_error: impl Fn(&EventContext<T, Client>, &HandlerError) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_error = Arc::new(on_error);
+        self
+    }
+
+    /// Keep track of how far the listener has got in the store given, so that
+    /// when it's run again it carries on from the event after the last one it
+    /// finished handling, rather than from the latest block.
+    ///
+    /// The cursor is saved once each event that a handler wanted has been
+    /// acknowledged (see [`EventListenerBuilder::ack_mode()`]), and at the end
+    /// of each block.
+    pub fn checkpoint_store(mut self, store: impl CheckpointStore) -> Self {
+        self.checkpoint_store = Some(Arc::new(store));
+        self
+    }
+
+    /// When events are acknowledged. [`AckMode::Auto`] by default; use
+    /// [`AckMode::Manual`] to have handlers acknowledge events themselves, so that
+    /// the checkpoint only moves past events once they have definitely been dealt
+    /// with.
+    pub fn ack_mode(mut self, ack_mode: AckMode) -> Self {
+        self.ack_mode = ack_mode;
+        self
+    }
+
+    /// Only save the checkpoint once this many events have been acknowledged
+    /// since it was last saved (or at the end of a block), rather than after
+    /// every event. Defaults to 1.
+    pub fn checkpoint_batch(mut self, events: usize) -> Self {
+        self.checkpoint_batch = events.max(1);
+        self
+    }
+
+    /// Send events that handlers fail to deal with to the sink given, along with
+    /// where they came from and why the handler failed. Once sent, the event is
+    /// acknowledged and the listener moves on. If it can't be sent, the listener
+    /// stops with the error.
+    ///
+    /// Events that can't be decoded are sent here too, with as much as is known
+    /// about them, and skipped rather than stopping the listener.
+    pub fn dead_letters(mut self, sink: impl DeadLetterSink) -> Self {
+        self.dead_letters = Some(Arc::new(sink));
+        self
+    }
+
+    /// How long to wait for handlers to finish with the events they've been handed
+    /// when shutting down (see [`ShutdownHandle`]). `None` waits for as long as it
+    /// takes. Defaults to [`DEFAULT_DRAIN_TIMEOUT`].
+    ///
+    /// Events which aren't finished with in time aren't acknowledged, so the
+    /// checkpoint won't move past them.
+    pub fn drain_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.drain_timeout = timeout;
+        self
+    }
+
+    /// Tag every event with the identifier given (see [`EventContext::chain_id()`]),
+    /// to tell apart the events of different chains handled by the same code.
+    pub fn chain_id(mut self, id: impl Into<String>) -> Self {
+        self.chain_id = Some(id.into().into());
+        self
+    }
+
+    /// Report whether the listener is subscribed to blocks, and when it last
+    /// finished with one, to the [`Health`] given.
+    pub fn health(mut self, health: Health) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    /// Report how far the listener has got to the [`Progress`] given, and keep it
+    /// up to date with the best and finalized heads of the chain, so that it can
+    /// tell how far behind the listener is.
+    pub fn progress(mut self, progress: Progress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Poll the node for new blocks every `interval`, rather than subscribing to
+    /// them, for nodes which don't support subscriptions (see
+    /// [`crate::blocks::BlocksClient::poll_interval()`]). Blocks are handled just
+    /// the same either way.
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = Some(interval);
+        self
+    }
+
+    /// Look the events of blocks up in the cache given before fetching them, and
+    /// cache the events that are fetched, so that other consumers sharing the
+    /// cache needn't fetch them again. See [`EventsCache`].
+    pub fn events_cache(mut self, cache: EventsCache<T>) -> Self {
+        self.events_cache = Some(cache);
+        self
+    }
+
+    /// Build the listener.
+    pub fn build(self) -> EventListener<T, Client> {
+        EventListener {
+            client: self.client.clone(),
+            dispatcher: Dispatcher {
+                handlers: self.handlers,
+                default_retry: self.default_retry,
+                on_error: self.on_error,
+                ack_mode: self.ack_mode,
+                acks: AckTracker::default(),
+                dead_letters: self.dead_letters,
+            },
+            best_blocks: self.best_blocks,
+            checkpoint_store: self.checkpoint_store,
+            checkpoint_batch: self.checkpoint_batch,
+            drain_timeout: self.drain_timeout,
+            shutdown: Shutdown::new(),
+            chain_id: self.chain_id,
+            health: self.health,
+            progress: self.progress,
+            poll_interval: self.poll_interval,
+            events: match self.events_cache {
+                Some(cache) => EventsClient::new(self.client).with_cache(cache),
+                None => EventsClient::new(self.client),
+            },
+        }
+    }
+
+    /// Build the listener and run it. See [`EventListener::run()`].
+    pub async fn run(self) -> Result<(), Error> {
+        self.build().run().await
+    }
+}
+
+/// Follows the chain and hands the events in each block to the handlers which
+/// were registered for them. Build one with an [`EventListenerBuilder`].
+pub struct EventListener<T: Config, Client> {
+    pub(super) client: Client,
+    pub(super) dispatcher: Dispatcher<T, Client>,
+    best_blocks: bool,
+    checkpoint_store: Option<Arc<dyn CheckpointStore>>,
+    checkpoint_batch: usize,
+    drain_timeout: Option<Duration>,
+    pub(super) shutdown: Shutdown,
+    chain_id: Option<Arc<str>>,
+    health: Option<Health>,
+    progress: Option<Progress>,
+    poll_interval: Option<Duration>,
+    // Kept for as long as the listener runs, so that the metadata it caches is
+    // reused from block to block.
+    events: EventsClient<T, Client>,
+}
+
+impl<T: Config, Client> std::fmt::Debug for EventListener<T, Client> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventListener")
+            .field("handlers", &self.dispatcher.handlers.len())
+            .field("best_blocks", &self.best_blocks)
+            .field("checkpoints", &self.checkpoint_store.is_some())
+            .field("ack_mode", &self.dispatcher.ack_mode)
+            .field("checkpoint_batch", &self.checkpoint_batch)
+            .field("dead_letters", &self.dispatcher.dead_letters.is_some())
+            .field("drain_timeout", &self.drain_timeout)
+            .field("chain_id", &self.chain_id)
+            .field("health", &self.health.is_some())
+            .field("progress", &self.progress.is_some())
+            .field("poll_interval", &self.poll_interval)
+            .field("events_cache", &self.events.cache())
+            .finish()
+    }
+}
+
+impl<T, Client> EventListener<T, Client>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    /// A handle to stop the listener with, once it's running.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.handle()
+    }
+
+    /// Subscribe to blocks, and hand each event in them to the handlers which
+    /// want it. Events are handed over in order, one at a time, except to
+    /// handlers which were given some other [`Concurrency`].
+    ///
+    /// If a [`CheckpointStore`] was given, and a cursor was saved in it, handling
+    /// carries on from where it left off. When following finalized blocks, every
+    /// block since the one the cursor points at is fetched and handled first.
+    /// When following best blocks, only events from blocks after the cursor are
+    /// handled.
+    ///
+    /// Handler failures are reported to the [`EventListenerBuilder::on_error()`]
+    /// callback, and the events sent to the [`EventListenerBuilder::dead_letters()`]
+    /// sink if there is one. This returns once the listener has been shut down
+    /// with its [`ShutdownHandle`], or if the subscription ends, or if a block or
+    /// its events can't be fetched or decoded, or a checkpoint or dead letter
+    /// can't be saved.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use event_listener::{ events::EventDetails, listener::{ EventContext, HandlerResult }, OnlineClient, PolkadotConfig };
+    /// # type Ctx = EventContext<PolkadotConfig, OnlineClient<PolkadotConfig>>;
+    /// # async fn handle_transfer(ctx: Ctx, event: EventDetails) -> HandlerResult { Ok(()) }
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+    /// let listener = api
+    ///     .listener()
+    ///     .on_dynamic("Balances", "Transfer", handle_transfer)
+    ///     .build();
+    ///
+    /// let shutdown = listener.shutdown_handle();
+    /// let running = tokio::spawn(async move { listener.run().await });
+    ///
+    /// tokio::signal::ctrl_c().await.unwrap();
+    /// shutdown.shutdown().await;
+    /// running.await.unwrap().unwrap();
+    /// # }
+    /// ```
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(name = "listener", skip_all, fields(chain = self.chain_id.as_deref()))
+    )]
+    pub async fn run(&self) -> Result<(), Error> {
+        let res = self.follow().await;
+        #[cfg(feature = "sentry")]
+        if let Err(e) = &res {
+            super::sentry::report_listener_error(self.chain_id.as_deref(), e);
+        }
+        res
+    }
+
+    async fn follow(&self) -> Result<(), Error> {
+        // Held until we return, letting shutdown handles know once we have.
+        let (mut stop_rx, _done_tx) = match self.shutdown.take() {
+            Some((stop_rx, done_tx)) => (Some(stop_rx), Some(done_tx)),
+            None => (None, None),
+        };
+
+        let resume_from = match &self.checkpoint_store {
+            Some(store) => store.load().await?,
+            None => None,
+        };
+
+        let blocks = self.blocks();
+        let mut sub = match &resume_from {
+            _ if self.best_blocks => blocks.subscribe().await?,
+            Some(cursor) if cursor.next_block() > 0 => {
+                blocks.subscribe_finalized_after(cursor.next_block() - 1).await?
+            }
+            _ => blocks.subscribe_finalized().await?,
+        };
+        tracing::info!(
+            best_blocks = self.best_blocks,
+            resume_from = ?resume_from,
+            "Subscribed to blocks"
+        );
+        let connected = self.health.as_ref().map(Health::connected);
+
+        let (mut lanes, workers) = self.dispatcher.lanes();
+        let following = async move {
+            loop {
+                let stopping = match &mut stop_rx {
+                    Some(stop_rx) => Either::Left(stop_rx.map(|_| ())),
+                    None => Either::Right(future::pending()),
+                };
+                let block = match future::select(sub.next(), stopping).await {
+                    Either::Left((Some(block), _)) => block?,
+                    Either::Left((None, _)) => {
+                        tracing::info!("Block subscription ended");
+                        break
+                    }
+                    Either::Right(((), _)) => {
+                        tracing::info!("Shutting down event listener");
+                        break
+                    }
+                };
+                let store = self.checkpoint_store.as_deref();
+                self.handle_block(&block, resume_from.as_ref(), store, &mut lanes)
+                    .await?;
+                let number: u64 = block.number().into();
+                if let Some(health) = &self.health {
+                    health.block_processed(number);
+                }
+                if let Some(progress) = &self.progress {
+                    progress.processed(number);
+                }
+            }
+            // Dropping the subscription unsubscribes from blocks (and marks the
+            // listener as no longer subscribed), and dropping the lanes lets the
+            // workers finish once they've handled everything sent to them.
+            drop(sub);
+            drop(connected);
+            drop(lanes);
+            Ok::<_, Error>(())
+        };
+        let heads = match &self.progress {
+            Some(progress) => {
+                progress.set_chain(self.chain_id.clone());
+                self.follow_heads(progress).boxed()
+            }
+            None => future::pending().boxed(),
+        };
+        let following = future::select(following.boxed(), heads).map(|res| {
+            match res {
+                Either::Left((res, _)) => res,
+                Either::Right((never, _)) => match never {},
+            }
+        });
+
+        match future::select(following.boxed(), workers).await {
+            Either::Left((res, workers)) => {
+                res?;
+                self.drain(workers).await?;
+            }
+            // The workers only finish early if there are none, or one fails.
+            Either::Right((res, following)) => {
+                res?;
+                following.await?;
+            }
+        }
+        self.save_checkpoint(self.checkpoint_store.as_deref(), 1)
+            .await
+    }
+
+    /// Hand the events in a range of past blocks to the handlers, just as [`Self::run()`]
+    /// would have when they were new, so that new handlers can be caught up with
+    /// history. This is [`Self::backfill()`] with its defaults; see there for the
+    /// details.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use event_listener::{ events::EventDetails, listener::{ EventContext, HandlerResult }, OnlineClient, PolkadotConfig };
+    /// # type Ctx = EventContext<PolkadotConfig, OnlineClient<PolkadotConfig>>;
+    /// # async fn index_transfer(ctx: Ctx, event: EventDetails) -> HandlerResult { Ok(()) }
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+    /// let listener = api
+    ///     .listener()
+    ///     .on_dynamic("Balances", "Transfer", index_transfer)
+    ///     .build();
+    ///
+    /// listener.replay(1_000_000..=1_100_000).await.unwrap();
+    /// # }
+    /// ```
+    pub async fn replay(&self, blocks: RangeInclusive<u64>) -> Result<(), Error> {
+        self.backfill(blocks).run().await
+    }
+
+    /// Set up the handling of the events in a range of past blocks, fetching
+    /// several blocks at once but handing their events to the handlers strictly
+    /// in order. See [`Backfill`].
+    pub fn backfill(&self, blocks: RangeInclusive<u64>) -> Backfill<'_, T, Client> {
+        Backfill::new(self, blocks)
+    }
+
+    // Keep the progress up to date with the heads of the chain. Failing to do so
+    // doesn't stop the listener, so this never resolves.
+    async fn follow_heads(&self, progress: &Progress) -> Infallible {
+        if let Err(e) = self.watch_heads(progress).await {
+            tracing::warn!("Stopped following the heads of the chain: {}", e);
+        }
+        future::pending().await
+    }
+
+    async fn watch_heads(&self, progress: &Progress) -> Result<(), Error> {
+        let blocks = self.blocks();
+        let best = blocks
+            .subscribe_heads(false)
+            .await?
+            .map(|header| (false, header));
+        let finalized = blocks
+            .subscribe_heads(true)
+            .await?
+            .map(|header| (true, header));
+        let mut heads = stream::select(best, finalized);
+        while let Some((is_finalized, header)) = heads.next().await {
+            let number: u64 = (*header?.number()).into();
+            if is_finalized {
+                progress.finalized(number);
+            } else {
+                progress.best(number);
+            }
+        }
+        Ok(())
+    }
+
+    // A client for following blocks, which polls for them if told to.
+    fn blocks(&self) -> BlocksClient<T, Client> {
+        self.client.blocks().poll_interval(self.poll_interval)
+    }
+
+    // Wait for the workers to finish handling the events sent to them, for at
+    // most the drain timeout.
+    pub(super) async fn drain(
+        &self,
+        workers: impl std::future::Future<Output = Result<(), Error>> + Unpin,
+    ) -> Result<(), Error> {
+        let timeout = match self.drain_timeout {
+            Some(timeout) => timeout,
+            None => return workers.await,
+        };
+        match future::select(workers, futures_timer::Delay::new(timeout)).await {
+            Either::Left((res, _)) => res,
+            Either::Right(_) => {
+                tracing::warn!(
+                    "Handlers didn't finish within {:?}; their events will be handled again on restart",
+                    timeout
+                );
+                Ok(())
+            }
+        }
+    }
+
+    // Hand the events in the block to the handlers, skipping over any that the
+    // cursor we resumed from says were handled already.
+    async fn handle_block(
+        &self,
+        block: &Block<T, Client>,
+        resume_from: Option<&EventCursor>,
+        store: Option<&dyn CheckpointStore>,
+        lanes: &mut Lanes<T, Client>,
+    ) -> Result<(), Error> {
+        let number: u64 = block.number().into();
+        if resume_from.map_or(false, |c| number < c.next_block()) {
+            return Ok(())
+        }
+        let (ctx, events) = self.fetch_block(block).await?;
+        self.dispatch_block(&ctx, &events, resume_from, store, lanes)
+            .await
+    }
+
+    // Fetch everything about the block that its events are handled with.
+    pub(super) async fn fetch_block(
+        &self,
+        block: &Block<T, Client>,
+    ) -> Result<(BlockContext<T>, Events<T>), Error> {
+        let events = block.events_with(&self.events).await?;
+        let ctx = BlockContext {
+            hash: block.hash(),
+            number: block.number(),
+            timestamp: self.timestamp(block.hash()).await?,
+            chain: self.chain_id.clone(),
+        };
+        Ok((ctx, events))
+    }
+
+    // Hand the events of a fetched block to the handlers, skipping over any that
+    // the cursor we resumed from says were handled already.
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(
+            name = "block",
+            skip_all,
+            fields(number = ?ctx.number, hash = ?ctx.hash)
+        )
+    )]
+    pub(super) async fn dispatch_block(
+        &self,
+        ctx: &BlockContext<T>,
+        events: &Events<T>,
+        resume_from: Option<&EventCursor>,
+        store: Option<&dyn CheckpointStore>,
+        lanes: &mut Lanes<T, Client>,
+    ) -> Result<(), Error> {
+        let number: u64 = ctx.number.into();
+        let mut dispatched = 0;
+        for event in events.iter() {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    // One bad event shouldn't hold up the block; it's put aside
+                    // to be looked into, like events that handlers fail on.
+                    let letter = DeadLetter::undecodable(ctx, &e);
+                    if !resume_from.map_or(false, |c| c.covers(number, letter.event_index)) {
+                        tracing::warn!(
+                            event_index = letter.event_index,
+                            "Skipping event that can't be decoded: {}",
+                            e
+                        );
+                        self.dispatcher.dead_letter(letter).await?;
+                    }
+                    continue
+                }
+            };
+            if resume_from.map_or(false, |c| c.covers(number, event.index())) {
+                continue
+            }
+            let handled = self
+                .dispatcher
+                .dispatch(&self.client, ctx, &event, lanes)
+                .await?;
+            if handled {
+                dispatched += 1;
+                self.save_checkpoint(store, self.checkpoint_batch).await?;
+            }
+        }
+        tracing::debug!(dispatched, "Handled block");
+        self.dispatcher
+            .acks
+            .track(EventCursor::block(number, ctx.hash.encode()), 0);
+        self.save_checkpoint(store, self.checkpoint_batch).await
+    }
+
+    // Save the furthest cursor that every event before has been acknowledged up
+    // to, if at least `batch` events have been acknowledged since last time.
+    pub(super) async fn save_checkpoint(
+        &self,
+        store: Option<&dyn CheckpointStore>,
+        batch: usize,
+    ) -> Result<(), Error> {
+        let store = match store {
+            Some(store) => store,
+            None => return Ok(()),
+        };
+        match self.dispatcher.acks.take_committed(batch) {
+            Some(cursor) => store.save(cursor).await,
+            None => Ok(()),
+        }
+    }
+
+    // The value of `Timestamp::Now` at the given block, if there is one.
+    async fn timestamp(&self, hash: T::Hash) -> Result<Option<u64>, Error> {
+        match self.client.rpc().storage(&timestamp_now_key(), Some(hash)).await? {
+            Some(data) => Ok(Some(u64::decode(&mut &*data.0)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+// The storage key for `Timestamp::Now`.
+fn timestamp_now_key() -> Vec<u8> {
+    let mut storage_key = twox_128(b"Timestamp").to_vec();
+    storage_key.extend(twox_128(b"Now").to_vec());
+    storage_key
+}
@@ -0,0 +1,392 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Remembering how far a listener got, so that it can carry on from there after
+//! a restart.
+
+use crate::{
+    error::Error,
+    utils::{
+        to_hex,
+        unblock,
+    },
+};
+use futures::{
+    future::BoxFuture,
+    FutureExt,
+};
+use parking_lot::Mutex;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::Arc,
+};
+
+/// Points at the last event that a listener finished processing.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventCursor {
+    /// The number of the block that the event was in.
+    pub block_number: u64,
+    /// The hash of the block that the event was in.
+    #[serde(with = "hex_bytes")]
+    pub block_hash: Vec<u8>,
+    /// The index of the event in the block, or `None` if every event in the
+    /// block has been processed.
+    pub event_index: Option<u32>,
+}
+
+impl EventCursor {
+    /// A cursor pointing at the end of the given block.
+    pub fn block(block_number: u64, block_hash: impl Into<Vec<u8>>) -> Self {
+        EventCursor {
+            block_number,
+            block_hash: block_hash.into(),
+            event_index: None,
+        }
+    }
+
+    /// A cursor pointing at the given event.
+    pub fn event(
+        block_number: u64,
+        block_hash: impl Into<Vec<u8>>,
+        event_index: u32,
+    ) -> Self {
+        EventCursor {
+            block_number,
+            block_hash: block_hash.into(),
+            event_index: Some(event_index),
+        }
+    }
+
+    /// The number of the first block which hasn't been completely processed.
+    pub fn next_block(&self) -> u64 {
+        match self.event_index {
+            Some(_) => self.block_number,
+            None => self.block_number + 1,
+        }
+    }
+
+    /// Has the event at the given position been processed already?
+    pub fn covers(&self, block_number: u64, event_index: u32) -> bool {
+        match self.event_index {
+            _ if block_number < self.block_number => true,
+            Some(idx) if block_number == self.block_number => event_index <= idx,
+            None => block_number == self.block_number,
+            _ => false,
+        }
+    }
+}
+
+/// Somewhere to keep the [`EventCursor`] of a listener, so that it can carry on
+/// where it left off after being restarted.
+pub trait CheckpointStore: Send + Sync + 'static {
+    /// Load the last cursor that was saved, if there is one.
+    fn load(&self) -> BoxFuture<'_, Result<Option<EventCursor>, Error>>;
+
+    /// Save the cursor, replacing any saved before it.
+    fn save(&self, cursor: EventCursor) -> BoxFuture<'_, Result<(), Error>>;
+}
+
+/// A [`CheckpointStore`] which keeps the cursor in memory. Clones share the same
+/// cursor. Nothing survives a restart, so this is mostly useful for testing.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryCheckpointStore(Arc<Mutex<Option<EventCursor>>>);
+
+impl MemoryCheckpointStore {
+    /// Create a store with no cursor saved in it.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The cursor that was last saved.
+    pub fn cursor(&self) -> Option<EventCursor> {
+        self.0.lock().clone()
+    }
+}
+
+impl CheckpointStore for MemoryCheckpointStore {
+    fn load(&self) -> BoxFuture<'_, Result<Option<EventCursor>, Error>> {
+        futures::future::ready(Ok(self.cursor())).boxed()
+    }
+
+    fn save(&self, cursor: EventCursor) -> BoxFuture<'_, Result<(), Error>> {
+        *self.0.lock() = Some(cursor);
+        futures::future::ready(Ok(())).boxed()
+    }
+}
+
+/// A [`CheckpointStore`] which keeps the cursor in a JSON file.
+///
+/// The cursor is written to a temporary file alongside the one given, which is
+/// then renamed over it, so a crash part way through saving leaves the last
+/// cursor intact.
+#[derive(Clone, Debug)]
+pub struct FileCheckpointStore {
+    path: PathBuf,
+}
+
+impl FileCheckpointStore {
+    /// Keep the cursor in the file at the path given. The file is created the first
+    /// time that a cursor is saved.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileCheckpointStore { path: path.into() }
+    }
+
+    /// The path of the file that the cursor is kept in.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn tmp_path(&self) -> PathBuf {
+        let mut file_name = self.path.file_name().unwrap_or_default().to_owned();
+        file_name.push(".tmp");
+        self.path.with_file_name(file_name)
+    }
+}
+
+impl CheckpointStore for FileCheckpointStore {
+    fn load(&self) -> BoxFuture<'_, Result<Option<EventCursor>, Error>> {
+        let path = self.path.clone();
+        unblock(move || read_cursor(&path)).boxed()
+    }
+
+    fn save(&self, cursor: EventCursor) -> BoxFuture<'_, Result<(), Error>> {
+        let (path, tmp_path) = (self.path.clone(), self.tmp_path());
+        unblock(move || write_cursor(&path, &tmp_path, &cursor)).boxed()
+    }
+}
+
+fn read_cursor(path: &Path) -> Result<Option<EventCursor>, Error> {
+    match std::fs::read(path) {
+        Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Write the cursor to a file alongside and then move it into place, so that the
+// file is never left half written.
+fn write_cursor(path: &Path, tmp_path: &Path, cursor: &EventCursor) -> Result<(), Error> {
+    let bytes = serde_json::to_vec(cursor)?;
+    {
+        let mut file = std::fs::File::create(tmp_path)?;
+        std::io::Write::write_all(&mut file, &bytes)?;
+        file.sync_all()?;
+    }
+    std::fs::rename(tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteCheckpointStore;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use super::{
+        CheckpointStore,
+        EventCursor,
+    };
+    use crate::error::Error;
+    use futures::{
+        future::BoxFuture,
+        FutureExt,
+    };
+    use parking_lot::Mutex;
+    use rusqlite::{
+        params,
+        Connection,
+        OptionalExtension,
+    };
+    use std::sync::Arc;
+
+    /// A [`CheckpointStore`] which keeps cursors in an SQLite database, in the
+    /// `event_listener_checkpoints` table. Each cursor is saved under a name, so
+    /// that several listeners can share a database.
+    #[derive(Clone)]
+    pub struct SqliteCheckpointStore {
+        conn: Arc<Mutex<Connection>>,
+        name: String,
+    }
+
+    impl std::fmt::Debug for SqliteCheckpointStore {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("SqliteCheckpointStore")
+                .field("name", &self.name)
+                .finish()
+        }
+    }
+
+    impl SqliteCheckpointStore {
+        /// Open (or create) the database at the path given, and keep the cursor
+        /// under the name given.
+        pub fn open(
+            path: impl AsRef<std::path::Path>,
+            name: impl Into<String>,
+        ) -> Result<Self, Error> {
+            let conn = Connection::open(path).map_err(sqlite_error)?;
+            Self::from_connection(conn, name)
+        }
+
+        /// Use a connection which has already been opened, and keep the cursor
+        /// under the name given.
+        pub fn from_connection(
+            conn: Connection,
+            name: impl Into<String>,
+        ) -> Result<Self, Error> {
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS event_listener_checkpoints (
+                    name TEXT PRIMARY KEY,
+                    block_number INTEGER NOT NULL,
+                    block_hash BLOB NOT NULL,
+                    event_index INTEGER
+                )",
+                [],
+            )
+            .map_err(sqlite_error)?;
+            Ok(SqliteCheckpointStore {
+                conn: Arc::new(Mutex::new(conn)),
+                name: name.into(),
+            })
+        }
+    }
+
+    impl CheckpointStore for SqliteCheckpointStore {
+        fn load(&self) -> BoxFuture<'_, Result<Option<EventCursor>, Error>> {
+            let res = self
+                .conn
+                .lock()
+                .query_row(
+                    "SELECT block_number, block_hash, event_index
+                    FROM event_listener_checkpoints WHERE name = ?1",
+                    params![self.name],
+                    |row| {
+                        Ok(EventCursor {
+                            block_number: row.get::<_, i64>(0)? as u64,
+                            block_hash: row.get(1)?,
+                            event_index: row.get(2)?,
+                        })
+                    },
+                )
+                .optional()
+                .map_err(sqlite_error);
+            futures::future::ready(res).boxed()
+        }
+
+        fn save(&self, cursor: EventCursor) -> BoxFuture<'_, Result<(), Error>> {
+            let res = self
+                .conn
+                .lock()
+                .execute(
+                    "INSERT INTO event_listener_checkpoints
+                        (name, block_number, block_hash, event_index)
+                    VALUES (?1, ?2, ?3, ?4)
+                    ON CONFLICT(name) DO UPDATE SET
+                        block_number = excluded.block_number,
+                        block_hash = excluded.block_hash,
+                        event_index = excluded.event_index",
+                    params![
+                        self.name,
+                        cursor.block_number as i64,
+                        cursor.block_hash,
+                        cursor.event_index
+                    ],
+                )
+                .map(|_| ())
+                .map_err(sqlite_error);
+            futures::future::ready(res).boxed()
+        }
+    }
+
+    fn sqlite_error(e: rusqlite::Error) -> Error {
+        Error::Checkpoint(e.to_string())
+    }
+}
+
+// Serialize bytes as a 0x prefixed hex string.
+mod hex_bytes {
+    use serde::{
+        de::Error as _,
+        Deserialize,
+        Deserializer,
+        Serializer,
+    };
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&to_hex(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(d)?;
+        hex::decode(s.trim_start_matches("0x")).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cursor_covers_earlier_events() {
+        let cursor = EventCursor::event(10, vec![1; 32], 3);
+        assert!(cursor.covers(9, 100));
+        assert!(cursor.covers(10, 3));
+        assert!(!cursor.covers(10, 4));
+        assert!(!cursor.covers(11, 0));
+        assert_eq!(cursor.next_block(), 10);
+
+        let cursor = EventCursor::block(10, vec![1; 32]);
+        assert!(cursor.covers(10, 100));
+        assert!(!cursor.covers(11, 0));
+        assert_eq!(cursor.next_block(), 11);
+    }
+
+    #[test]
+    fn cursor_serializes_hash_as_hex() {
+        let cursor = EventCursor::event(10, vec![0xab, 0xcd], 3);
+        let json = serde_json::to_string(&cursor).unwrap();
+        assert_eq!(
+            json,
+            r#"{"blockNumber":10,"blockHash":"0xabcd","eventIndex":3}"#
+        );
+        assert_eq!(serde_json::from_str::<EventCursor>(&json).unwrap(), cursor);
+    }
+
+    #[tokio::test]
+    async fn file_store_round_trips() {
+        let path = std::env::temp_dir()
+            .join(format!("event-listener-checkpoint-{}.json", std::process::id()));
+        let store = FileCheckpointStore::new(&path);
+        assert_eq!(store.load().await.unwrap(), None);
+
+        let cursor = EventCursor::block(5, vec![2; 32]);
+        store.save(cursor.clone()).await.unwrap();
+        assert_eq!(store.load().await.unwrap(), Some(cursor));
+
+        let cursor = EventCursor::event(6, vec![3; 32], 0);
+        store.save(cursor.clone()).await.unwrap();
+        assert_eq!(store.load().await.unwrap(), Some(cursor));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn sqlite_store_round_trips() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        let store = SqliteCheckpointStore::from_connection(conn, "test").unwrap();
+        assert_eq!(store.load().await.unwrap(), None);
+
+        let cursor = EventCursor::event(6, vec![3; 32], 2);
+        store.save(cursor.clone()).await.unwrap();
+        store.save(cursor.clone()).await.unwrap();
+        assert_eq!(store.load().await.unwrap(), Some(cursor));
+    }
+}
@@ -0,0 +1,128 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Running handlers concurrently, while keeping events in order where it matters.
+
+use super::{
+    ack::Ack,
+    handler::EventContext,
+};
+use crate::{
+    error::Error,
+    events::EventDetails,
+    Config,
+};
+use derivative::Derivative;
+use futures::{
+    channel::mpsc,
+    SinkExt,
+};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{
+        Hash,
+        Hasher,
+    },
+    sync::Arc,
+};
+
+type KeyFn<T, Client> = Arc<dyn Fn(&EventContext<T, Client>) -> u64 + Send + Sync>;
+
+/// How a handler is run, relative to the events before and after it.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub enum Concurrency<T: Config, Client> {
+    /// Handle one event at a time, in the order of the blocks and of the events
+    /// in them. The listener waits for the handler before moving on to the next
+    /// event, so this also keeps the handler in step with the other handlers.
+    Sequential,
+    /// Handle up to this many events at once, in no particular order.
+    Parallel(usize),
+    /// Handle events with the same key one at a time and in order, and events with
+    /// different keys at the same time. Keys are spread over this many `shards`,
+    /// each of which handles one event at a time.
+    Keyed {
+        /// The most events to handle at once.
+        shards: usize,
+        /// Work out the key of an event.
+        #[derivative(Debug = "ignore")]
+        key: KeyFn<T, Client>,
+    },
+}
+
+impl<T: Config, Client> Default for Concurrency<T, Client> {
+    fn default() -> Self {
+        Concurrency::Sequential
+    }
+}
+
+impl<T: Config, Client> Concurrency<T, Client> {
+    /// Handle events with the same key in order, and events with different keys
+    /// at the same time, up to `shards` at once.
+    pub fn keyed<K: Hash>(
+        shards: usize,
+        key: impl Fn(&EventContext<T, Client>) -> K + Send + Sync + 'static,
+    ) -> Self {
+        Concurrency::Keyed {
+            shards: shards.max(1),
+            key: Arc::new(move |ctx| {
+                let mut hasher = DefaultHasher::new();
+                key(ctx).hash(&mut hasher);
+                hasher.finish()
+            }),
+        }
+    }
+
+    /// Handle events which have the same value in the named field (such as `who`,
+    /// or `from`) in order, and those with different values at the same time, up
+    /// to `shards` at once. Events without the field all share a key.
+    pub fn keyed_by_field(shards: usize, field: impl Into<String>) -> Self {
+        let field = field.into();
+        Self::keyed(shards, move |ctx| {
+            ctx.field(&field).map(|value| format!("{:?}", value.value))
+        })
+    }
+}
+
+/// An event for a handler to deal with, sent to the lane of the handler.
+pub(crate) struct Job<T: Config, Client> {
+    pub(crate) ctx: EventContext<T, Client>,
+    pub(crate) event: EventDetails,
+    pub(crate) ack: Ack,
+    // The span the event was dispatched in, so that handlers running on a lane
+    // are traced as part of the block they're from.
+    pub(crate) span: tracing::Span,
+}
+
+/// Where events for a handler which isn't [`Concurrency::Sequential`] are sent.
+pub(crate) struct Lane<T: Config, Client> {
+    pub(crate) senders: Vec<mpsc::Sender<Job<T, Client>>>,
+    pub(crate) key: Option<KeyFn<T, Client>>,
+}
+
+impl<T: Config, Client> Lane<T, Client> {
+    /// Send the job to the handler, waiting if it's busy.
+    pub(crate) async fn send(&mut self, job: Job<T, Client>) -> Result<(), Error> {
+        let shard = match &self.key {
+            Some(key) => (key(&job.ctx) % self.senders.len() as u64) as usize,
+            None => 0,
+        };
+        self.senders[shard]
+            .send(job)
+            .await
+            .map_err(|_| Error::Other("Handler has stopped".into()))
+    }
+}
+
+/// The lanes of each handler, in the order they were registered. Handlers which
+/// are run sequentially have no lane.
+pub(crate) struct Lanes<T: Config, Client> {
+    pub(crate) lanes: Vec<Option<Lane<T, Client>>>,
+}
+
+impl<T: Config, Client> Lanes<T, Client> {
+    pub(crate) fn lane(&mut self, idx: usize) -> Option<&mut Lane<T, Client>> {
+        self.lanes.get_mut(idx).and_then(|lane| lane.as_mut())
+    }
+}
@@ -0,0 +1,299 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Somewhere to put events that handlers failed to deal with, so that they can
+//! be looked into and replayed later.
+
+use super::handler::{
+    BlockContext,
+    EventContext,
+    HandlerError,
+};
+use crate::{
+    error::{
+        Error,
+        ErrorContext,
+    },
+    events::EventDetails,
+    utils::{
+        to_hex,
+        unblock,
+    },
+    Config,
+};
+use codec::Encode;
+use futures::{
+    future::BoxFuture,
+    FutureExt,
+};
+use parking_lot::Mutex;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    io::Write,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::Arc,
+};
+
+/// An event that a handler failed to deal with, along with where it came from
+/// and why the handler failed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeadLetter {
+    /// The identifier of the chain that the event was emitted on, if the listener
+    /// was given one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain: Option<String>,
+    /// The number of the block that the event was emitted in.
+    pub block_number: u64,
+    /// The hash of the block that the event was emitted in, as a hex string.
+    pub block_hash: String,
+    /// When the block was authored, in milliseconds since the Unix epoch.
+    pub timestamp: Option<u64>,
+    /// The index of the event in the block.
+    pub event_index: u32,
+    /// The index of the extrinsic that emitted the event, if it was emitted by one.
+    pub extrinsic_index: Option<u32>,
+    /// The name of the pallet that emitted the event.
+    pub pallet: String,
+    /// The name of the event.
+    pub variant: String,
+    /// The decoded fields of the event, in the form that [`crate::json`] writes
+    /// them.
+    pub fields: serde_json::Value,
+    /// The SCALE encoded fields of the event, as a hex string.
+    pub field_bytes: String,
+    /// Why the handler failed.
+    pub error: String,
+    /// The number of times that the handler was tried.
+    pub attempts: u32,
+}
+
+impl DeadLetter {
+    pub(crate) fn new<T: Config, Client>(
+        ctx: &EventContext<T, Client>,
+        event: &EventDetails,
+        error: &HandlerError,
+        attempts: u32,
+    ) -> Self {
+        DeadLetter {
+            chain: ctx.chain_id().map(ToOwned::to_owned),
+            block_number: ctx.block_number().into(),
+            block_hash: to_hex(ctx.block_hash().encode()),
+            timestamp: ctx.timestamp(),
+            event_index: ctx.event_index(),
+            extrinsic_index: ctx.extrinsic_index(),
+            pallet: ctx.pallet_name().to_owned(),
+            variant: ctx.variant_name().to_owned(),
+            fields: ctx.fields_json(),
+            field_bytes: to_hex(event.field_bytes()),
+            error: error.to_string(),
+            attempts,
+        }
+    }
+
+    // An event of the block given that couldn't be decoded, and so never made it
+    // to a handler. Its index and name are taken from the context of the error,
+    // so far as they're known; its fields are left empty.
+    pub(crate) fn undecodable<T: Config>(block: &BlockContext<T>, error: &Error) -> Self {
+        let (event_index, name) = error
+            .contexts()
+            .find_map(|context| {
+                match context {
+                    ErrorContext::Event { index, name, .. } => Some((*index, name.clone())),
+                    _ => None,
+                }
+            })
+            .unwrap_or_default();
+        let (pallet, variant) = name
+            .as_deref()
+            .and_then(|name| name.split_once("::"))
+            .unwrap_or_default();
+        DeadLetter {
+            chain: block.chain.as_deref().map(ToOwned::to_owned),
+            block_number: block.number.into(),
+            block_hash: to_hex(block.hash.encode()),
+            timestamp: block.timestamp,
+            event_index,
+            extrinsic_index: None,
+            pallet: pallet.to_owned(),
+            variant: variant.to_owned(),
+            fields: serde_json::Value::Null,
+            field_bytes: "0x".to_owned(),
+            error: error.to_string(),
+            attempts: 0,
+        }
+    }
+}
+
+/// Somewhere to send the events that handlers failed to deal with. Once an event
+/// has been sent here, the listener treats it as dealt with and moves on; if it
+/// can't be sent, the listener stops with the error rather than lose the event.
+pub trait DeadLetterSink: Send + Sync + 'static {
+    /// Send the dead letter to the sink.
+    fn send(&self, letter: DeadLetter) -> BoxFuture<'_, Result<(), Error>>;
+}
+
+/// A [`DeadLetterSink`] which keeps dead letters in memory. Clones share the
+/// same letters. This is mostly useful for testing.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryDeadLetterSink(Arc<Mutex<Vec<DeadLetter>>>);
+
+impl MemoryDeadLetterSink {
+    /// Create an empty sink.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The dead letters sent so far.
+    pub fn letters(&self) -> Vec<DeadLetter> {
+        self.0.lock().clone()
+    }
+}
+
+impl DeadLetterSink for MemoryDeadLetterSink {
+    fn send(&self, letter: DeadLetter) -> BoxFuture<'_, Result<(), Error>> {
+        self.0.lock().push(letter);
+        futures::future::ready(Ok(())).boxed()
+    }
+}
+
+/// A [`DeadLetterSink`] which appends dead letters to a file, as one line of JSON
+/// each.
+#[derive(Clone, Debug)]
+pub struct FileDeadLetterSink {
+    path: PathBuf,
+}
+
+impl FileDeadLetterSink {
+    /// Append dead letters to the file at the path given, creating it if needed.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileDeadLetterSink { path: path.into() }
+    }
+
+    /// The path of the file that dead letters are appended to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl DeadLetterSink for FileDeadLetterSink {
+    fn send(&self, letter: DeadLetter) -> BoxFuture<'_, Result<(), Error>> {
+        let path = self.path.clone();
+        unblock(move || append_letter(&path, &letter)).boxed()
+    }
+}
+
+fn append_letter(path: &Path, letter: &DeadLetter) -> Result<(), Error> {
+    let mut line = serde_json::to_vec(letter)?;
+    line.push(b'\n');
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    file.write_all(&line)?;
+    file.sync_data()?;
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteDeadLetterSink;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use super::{
+        DeadLetter,
+        DeadLetterSink,
+    };
+    use crate::error::Error;
+    use futures::{
+        future::BoxFuture,
+        FutureExt,
+    };
+    use parking_lot::Mutex;
+    use rusqlite::{
+        params,
+        Connection,
+    };
+    use std::sync::Arc;
+
+    /// A [`DeadLetterSink`] which inserts dead letters into the
+    /// `event_listener_dead_letters` table of an SQLite database.
+    #[derive(Clone)]
+    pub struct SqliteDeadLetterSink {
+        conn: Arc<Mutex<Connection>>,
+    }
+
+    impl std::fmt::Debug for SqliteDeadLetterSink {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("SqliteDeadLetterSink").finish()
+        }
+    }
+
+    impl SqliteDeadLetterSink {
+        /// Open (or create) the database at the path given.
+        pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self, Error> {
+            let conn = Connection::open(path).map_err(sqlite_error)?;
+            Self::from_connection(conn)
+        }
+
+        /// Use a connection which has already been opened.
+        pub fn from_connection(conn: Connection) -> Result<Self, Error> {
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS event_listener_dead_letters (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    block_number INTEGER NOT NULL,
+                    block_hash TEXT NOT NULL,
+                    event_index INTEGER NOT NULL,
+                    pallet TEXT NOT NULL,
+                    variant TEXT NOT NULL,
+                    error TEXT NOT NULL,
+                    letter TEXT NOT NULL
+                )",
+                [],
+            )
+            .map_err(sqlite_error)?;
+            Ok(SqliteDeadLetterSink {
+                conn: Arc::new(Mutex::new(conn)),
+            })
+        }
+    }
+
+    impl DeadLetterSink for SqliteDeadLetterSink {
+        fn send(&self, letter: DeadLetter) -> BoxFuture<'_, Result<(), Error>> {
+            let res = serde_json::to_string(&letter)
+                .map_err(Error::from)
+                .and_then(|json| {
+                    self.conn
+                        .lock()
+                        .execute(
+                            "INSERT INTO event_listener_dead_letters
+                                (block_number, block_hash, event_index, pallet, variant, error, letter)
+                            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                            params![
+                                letter.block_number as i64,
+                                letter.block_hash,
+                                letter.event_index,
+                                letter.pallet,
+                                letter.variant,
+                                letter.error,
+                                json
+                            ],
+                        )
+                        .map(|_| ())
+                        .map_err(sqlite_error)
+                });
+            futures::future::ready(res).boxed()
+        }
+    }
+
+    fn sqlite_error(e: rusqlite::Error) -> Error {
+        Error::Sink(e.to_string())
+    }
+}
@@ -0,0 +1,447 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Remembering which events have been handled, so that handling them again after
+//! a restart doesn't have the same effect twice.
+
+use super::handler::{
+    EventContext,
+    Handler,
+    HandlerResult,
+};
+use crate::{
+    error::Error,
+    utils::to_hex,
+    Config,
+};
+use codec::Encode;
+use derivative::Derivative;
+use futures::{
+    future::BoxFuture,
+    FutureExt,
+};
+use parking_lot::Mutex;
+use std::{
+    collections::{
+        BTreeMap,
+        HashMap,
+    },
+    sync::Arc,
+};
+
+type KeyFn<T, Client> = Arc<dyn Fn(&EventContext<T, Client>) -> String + Send + Sync>;
+
+/// Somewhere to keep the keys of the events which have been handled.
+pub trait DedupStore: Send + Sync + 'static {
+    /// Has the key been inserted?
+    fn contains<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<bool, Error>>;
+
+    /// Note that the event with this key has been handled.
+    fn insert(&self, key: String) -> BoxFuture<'_, Result<(), Error>>;
+}
+
+impl<S: DedupStore> DedupStore for Arc<S> {
+    fn contains<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<bool, Error>> {
+        (**self).contains(key)
+    }
+
+    fn insert(&self, key: String) -> BoxFuture<'_, Result<(), Error>> {
+        (**self).insert(key)
+    }
+}
+
+/// A [`Handler`] which hands each event to another handler, unless an event with
+/// the same key has been handled already.
+///
+/// Listeners deliver events at least once, so an event may be handed over again
+/// if the listener stops before its checkpoint is saved. Wrapping a handler with
+/// side effects (such as sending a notification) in this means that those only
+/// happen once. The key is noted down once the handler succeeds, so events which
+/// it fails to handle are handed to it again.
+///
+/// By default, events are keyed on the hash of their block and their index in it.
+/// Give a key of your own with [`DedupHandler::key()`] to also skip events which
+/// were emitted again in a different block after a reorg.
+///
+/// ```no_run
+/// use event_listener::{
+///     events::EventDetails,
+///     listener::{ DedupHandler, EventContext, HandlerResult, MemoryDedupStore },
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+///
+/// type Ctx = EventContext<PolkadotConfig, OnlineClient<PolkadotConfig>>;
+///
+/// async fn notify(ctx: Ctx, event: EventDetails) -> HandlerResult {
+///     // Send a notification about the transfer.
+///     Ok(())
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let notify = DedupHandler::new(notify, MemoryDedupStore::new(100_000));
+///
+/// api.listener()
+///     .on_dynamic("Balances", "Transfer", notify)
+///     .run()
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub struct DedupHandler<T: Config, Client, H> {
+    #[derivative(Debug = "ignore")]
+    handler: Arc<H>,
+    #[derivative(Debug = "ignore")]
+    store: Arc<dyn DedupStore>,
+    #[derivative(Debug = "ignore")]
+    key: KeyFn<T, Client>,
+}
+
+impl<T: Config, Client, H> DedupHandler<T, Client, H> {
+    /// Hand events to the handler given, skipping those whose keys are in the
+    /// store.
+    pub fn new(handler: H, store: impl DedupStore) -> Self {
+        DedupHandler {
+            handler: Arc::new(handler),
+            store: Arc::new(store),
+            key: Arc::new(|ctx: &EventContext<T, Client>| {
+                format!("{}:{}", to_hex(ctx.block_hash().encode()), ctx.event_index())
+            }),
+        }
+    }
+
+    /// Work out the key of each event with the function given, rather than from
+    /// its block hash and index.
+    pub fn key(
+        mut self,
+        key: impl Fn(&EventContext<T, Client>) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.key = Arc::new(key);
+        self
+    }
+}
+
+impl<T, Client, Ev, H> Handler<T, Client, Ev> for DedupHandler<T, Client, H>
+where
+    T: Config,
+    Client: Send + 'static,
+    Ev: Send + 'static,
+    H: Handler<T, Client, Ev>,
+{
+    fn handle(
+        &self,
+        ctx: EventContext<T, Client>,
+        event: Ev,
+    ) -> BoxFuture<'static, HandlerResult> {
+        let key = (self.key)(&ctx);
+        let handler = self.handler.clone();
+        let store = self.store.clone();
+        async move {
+            if store.contains(&key).await? {
+                tracing::debug!("Skipping event {}, which was handled already", key);
+                return Ok(())
+            }
+            handler.handle(ctx, event).await?;
+            store.insert(key).await?;
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// A [`DedupStore`] which keeps the most recently used keys in memory, up to a
+/// limit. Clones share the same keys. Nothing survives a restart, so this only
+/// guards against events being handed over again while the listener runs (for
+/// instance by a [`super::MultiChainListener`] reconnecting).
+#[derive(Clone, Debug)]
+pub struct MemoryDedupStore(Arc<Mutex<Lru>>);
+
+#[derive(Debug, Default)]
+struct Lru {
+    capacity: usize,
+    // When each key was last used, and the keys in the order they were used.
+    used: HashMap<String, u64>,
+    order: BTreeMap<u64, String>,
+    clock: u64,
+}
+
+impl Lru {
+    fn touch(&mut self, key: String) {
+        self.clock += 1;
+        if let Some(last) = self.used.insert(key.clone(), self.clock) {
+            self.order.remove(&last);
+        }
+        self.order.insert(self.clock, key);
+        while self.used.len() > self.capacity {
+            let oldest = match self.order.keys().next() {
+                Some(oldest) => *oldest,
+                None => break,
+            };
+            if let Some(key) = self.order.remove(&oldest) {
+                self.used.remove(&key);
+            }
+        }
+    }
+}
+
+impl MemoryDedupStore {
+    /// Keep up to `capacity` keys, forgetting the least recently used ones once
+    /// there are more.
+    pub fn new(capacity: usize) -> Self {
+        MemoryDedupStore(Arc::new(Mutex::new(Lru {
+            capacity: capacity.max(1),
+            ..Lru::default()
+        })))
+    }
+
+    /// The number of keys being kept.
+    pub fn len(&self) -> usize {
+        self.0.lock().used.len()
+    }
+
+    /// Are no keys being kept?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl DedupStore for MemoryDedupStore {
+    fn contains<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<bool, Error>> {
+        let mut lru = self.0.lock();
+        let found = lru.used.contains_key(key);
+        if found {
+            lru.touch(key.to_owned());
+        }
+        futures::future::ready(Ok(found)).boxed()
+    }
+
+    fn insert(&self, key: String) -> BoxFuture<'_, Result<(), Error>> {
+        self.0.lock().touch(key);
+        futures::future::ready(Ok(())).boxed()
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteDedupStore;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use super::DedupStore;
+    use crate::error::Error;
+    use futures::{
+        future::BoxFuture,
+        FutureExt,
+    };
+    use parking_lot::Mutex;
+    use rusqlite::{
+        params,
+        Connection,
+        OptionalExtension,
+    };
+    use std::{
+        sync::Arc,
+        time::{
+            Duration,
+            SystemTime,
+            UNIX_EPOCH,
+        },
+    };
+
+    /// A [`DedupStore`] which keeps keys in an SQLite database, in the
+    /// `event_listener_dedup` table, so that they survive restarts. Keys are kept
+    /// under a name, so that several listeners can share a database.
+    ///
+    /// Keys are kept until they're removed with [`SqliteDedupStore::prune()`].
+    #[derive(Clone)]
+    pub struct SqliteDedupStore {
+        conn: Arc<Mutex<Connection>>,
+        name: String,
+    }
+
+    impl std::fmt::Debug for SqliteDedupStore {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("SqliteDedupStore")
+                .field("name", &self.name)
+                .finish()
+        }
+    }
+
+    impl SqliteDedupStore {
+        /// Open (or create) the database at the path given, and keep keys under
+        /// the name given.
+        pub fn open(
+            path: impl AsRef<std::path::Path>,
+            name: impl Into<String>,
+        ) -> Result<Self, Error> {
+            let conn = Connection::open(path).map_err(sqlite_error)?;
+            Self::from_connection(conn, name)
+        }
+
+        /// Use a connection which has already been opened, and keep keys under
+        /// the name given.
+        pub fn from_connection(
+            conn: Connection,
+            name: impl Into<String>,
+        ) -> Result<Self, Error> {
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS event_listener_dedup (
+                    name TEXT NOT NULL,
+                    key TEXT NOT NULL,
+                    handled_at INTEGER NOT NULL,
+                    PRIMARY KEY (name, key)
+                )",
+                [],
+            )
+            .map_err(sqlite_error)?;
+            Ok(SqliteDedupStore {
+                conn: Arc::new(Mutex::new(conn)),
+                name: name.into(),
+            })
+        }
+
+        /// Remove the keys of events handled more than `older_than` ago, handing
+        /// back how many were removed. Events older than this shouldn't be handed
+        /// over again, so their keys are no longer needed.
+        pub fn prune(&self, older_than: Duration) -> Result<usize, Error> {
+            let cutoff = now_millis().saturating_sub(older_than.as_millis() as i64);
+            self.conn
+                .lock()
+                .execute(
+                    "DELETE FROM event_listener_dedup WHERE name = ?1 AND handled_at < ?2",
+                    params![self.name, cutoff],
+                )
+                .map_err(sqlite_error)
+        }
+    }
+
+    impl DedupStore for SqliteDedupStore {
+        fn contains<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<bool, Error>> {
+            let res = self
+                .conn
+                .lock()
+                .query_row(
+                    "SELECT 1 FROM event_listener_dedup WHERE name = ?1 AND key = ?2",
+                    params![self.name, key],
+                    |_| Ok(()),
+                )
+                .optional()
+                .map(|found| found.is_some())
+                .map_err(sqlite_error);
+            futures::future::ready(res).boxed()
+        }
+
+        fn insert(&self, key: String) -> BoxFuture<'_, Result<(), Error>> {
+            let res = self
+                .conn
+                .lock()
+                .execute(
+                    "INSERT INTO event_listener_dedup (name, key, handled_at)
+                    VALUES (?1, ?2, ?3)
+                    ON CONFLICT(name, key) DO UPDATE SET handled_at = excluded.handled_at",
+                    params![self.name, key, now_millis()],
+                )
+                .map(|_| ())
+                .map_err(sqlite_error);
+            futures::future::ready(res).boxed()
+        }
+    }
+
+    fn now_millis() -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as i64
+    }
+
+    fn sqlite_error(e: rusqlite::Error) -> Error {
+        Error::Dedup(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        events::{
+            test_utils::{
+                event_record,
+                events,
+                metadata,
+            },
+            EventDetails,
+            Phase,
+        },
+        listener::handler::BlockContext,
+        SubstrateConfig,
+    };
+    use codec::Decode;
+    use scale_info::TypeInfo;
+    use std::sync::atomic::{
+        AtomicUsize,
+        Ordering,
+    };
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Event {
+        A(u8),
+    }
+
+    type Ctx = EventContext<SubstrateConfig, ()>;
+
+    #[test]
+    fn least_recently_used_keys_are_forgotten() {
+        let store = MemoryDedupStore::new(2);
+        let contains = |key: &str| futures::executor::block_on(store.contains(key)).unwrap();
+        futures::executor::block_on(store.insert("a".into())).unwrap();
+        futures::executor::block_on(store.insert("b".into())).unwrap();
+        // Using `a` makes `b` the least recently used.
+        assert!(contains("a"));
+        futures::executor::block_on(store.insert("c".into())).unwrap();
+        assert!(contains("a"));
+        assert!(!contains("b"));
+        assert!(contains("c"));
+        assert_eq!(store.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn events_are_only_handled_once() {
+        let events = events(
+            metadata::<Event>(),
+            vec![
+                event_record(Phase::ApplyExtrinsic(0), Event::A(1)),
+                event_record(Phase::ApplyExtrinsic(1), Event::A(2)),
+            ],
+        );
+        let handled = Arc::new(AtomicUsize::new(0));
+        let counter = handled.clone();
+        let handler = DedupHandler::new(
+            move |_ctx: Ctx, _event: EventDetails| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let res: HandlerResult = Ok(());
+                futures::future::ready(res)
+            },
+            MemoryDedupStore::new(10),
+        );
+
+        // Hand every event over twice, as if the listener had been restarted.
+        for _ in 0..2 {
+            for event in events.iter() {
+                let event = event.unwrap();
+                let block = BlockContext {
+                    hash: Default::default(),
+                    number: 1,
+                    timestamp: None,
+                    chain: None,
+                };
+                let ctx = Ctx::new((), block, &event);
+                handler.handle(ctx, event).await.unwrap();
+            }
+        }
+        assert_eq!(handled.load(Ordering::SeqCst), 2);
+    }
+}
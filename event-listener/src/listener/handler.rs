@@ -0,0 +1,847 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use super::{
+    ack::{
+        Ack,
+        AckMode,
+        AckTracker,
+    },
+    checkpoint::EventCursor,
+    concurrency::{
+        Concurrency,
+        Job,
+        Lane,
+        Lanes,
+    },
+    dead_letter::{
+        DeadLetter,
+        DeadLetterSink,
+    },
+    retry::RetryPolicy,
+};
+use crate::{
+    error::Error,
+    events::{
+        EventDetails,
+        Phase,
+        StaticEvent,
+    },
+    json::composite_to_json,
+    Config,
+    Metadata,
+};
+use codec::Encode;
+use derivative::Derivative;
+use futures::{
+    channel::mpsc,
+    future::{
+        self,
+        BoxFuture,
+    },
+    FutureExt,
+    StreamExt,
+    TryFutureExt,
+    TryStreamExt,
+};
+use scale_value::{
+    scale::TypeId,
+    Composite,
+    Value,
+};
+use std::{
+    future::Future,
+    marker::PhantomData,
+    sync::Arc,
+};
+
+/// The error that a handler fails with. Any error type can be handed back.
+pub type HandlerError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// What a handler hands back.
+pub type HandlerResult = Result<(), HandlerError>;
+
+/// Handles events of type `Ev`; either a [`StaticEvent`], or [`EventDetails`] for
+/// dynamically decoded events.
+///
+/// This is implemented for any `Fn(EventContext<T, Client>, Ev) -> impl Future`
+/// closure, but can also be implemented by hand, so that a handler can hold its own
+/// state and be tested without running a listener.
+///
+/// # Example
+///
+/// ```
+/// use event_listener::{
+///     events::EventDetails,
+///     listener::{ EventContext, Handler, HandlerResult },
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+/// use futures::{ future::BoxFuture, FutureExt };
+/// use std::sync::{ atomic::{ AtomicU64, Ordering }, Arc };
+///
+/// /// Counts the events it's handed.
+/// #[derive(Default)]
+/// struct Counter(Arc<AtomicU64>);
+///
+/// impl<Client> Handler<PolkadotConfig, Client, EventDetails> for Counter {
+///     fn handle(
+///         &self,
+///         _ctx: EventContext<PolkadotConfig, Client>,
+///         _event: EventDetails,
+///     ) -> BoxFuture<'static, HandlerResult> {
+///         self.0.fetch_add(1, Ordering::Relaxed);
+///         async { Ok(()) }.boxed()
+///     }
+/// }
+/// ```
+pub trait Handler<T: Config, Client, Ev>: Send + Sync + 'static {
+    /// Handle an event, given the context that it was emitted in.
+    fn handle(
+        &self,
+        ctx: EventContext<T, Client>,
+        event: Ev,
+    ) -> BoxFuture<'static, HandlerResult>;
+}
+
+impl<T, Client, Ev, F, Fut> Handler<T, Client, Ev> for F
+where
+    T: Config,
+    F: Fn(EventContext<T, Client>, Ev) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = HandlerResult> + Send + 'static,
+{
+    fn handle(
+        &self,
+        ctx: EventContext<T, Client>,
+        event: Ev,
+    ) -> BoxFuture<'static, HandlerResult> {
+        self(ctx, event).boxed()
+    }
+}
+
+/// Where an event came from, handed to handlers along with the event itself.
+#[derive(Derivative)]
+#[derivative(Clone(bound = "Client: Clone"))]
+pub struct EventContext<T: Config, Client> {
+    client: Client,
+    block: BlockContext<T>,
+    // The fields of the event are only decoded once something asks for them.
+    event: EventDetails,
+    ack: Ack,
+}
+
+impl<T: Config, Client> std::fmt::Debug for EventContext<T, Client> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventContext")
+            .field("block", &self.block)
+            .field("event_index", &self.event.index())
+            .field("phase", &self.event.phase())
+            .field("pallet", &self.event.pallet_name())
+            .field("variant", &self.event.variant_name())
+            .field("ack", &self.ack)
+            .finish()
+    }
+}
+
+/// The details of a block that are shared by the contexts of all of its events.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub(crate) struct BlockContext<T: Config> {
+    pub(crate) hash: T::Hash,
+    pub(crate) number: T::BlockNumber,
+    pub(crate) timestamp: Option<u64>,
+    pub(crate) chain: Option<Arc<str>>,
+}
+
+impl<T: Config, Client> EventContext<T, Client> {
+    pub(crate) fn new(client: Client, block: BlockContext<T>, event: &EventDetails) -> Self {
+        EventContext {
+            client,
+            block,
+            event: event.clone(),
+            ack: Ack::noop(),
+        }
+    }
+
+    fn with_ack(mut self, ack: Ack) -> Self {
+        self.ack = ack;
+        self
+    }
+
+    /// The client that the listener is using, to make follow-up queries with
+    /// (such as reading storage at [`EventContext::block_hash()`]).
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// The identifier of the chain that the event was emitted on, if the
+    /// listener was given one (see [`super::EventListenerBuilder::chain_id()`]).
+    pub fn chain_id(&self) -> Option<&str> {
+        self.block.chain.as_deref()
+    }
+
+    /// The hash of the block that the event was emitted in.
+    pub fn block_hash(&self) -> T::Hash {
+        self.block.hash
+    }
+
+    /// The number of the block that the event was emitted in.
+    pub fn block_number(&self) -> T::BlockNumber {
+        self.block.number
+    }
+
+    /// The time that the block was authored, in milliseconds since the Unix
+    /// epoch, as set in the `Timestamp` pallet. `None` if the chain has no such
+    /// pallet.
+    pub fn timestamp(&self) -> Option<u64> {
+        self.block.timestamp
+    }
+
+    /// The index of the event in the block.
+    pub fn event_index(&self) -> u32 {
+        self.event.index()
+    }
+
+    /// The phase of the block that the event was emitted in.
+    pub fn phase(&self) -> Phase {
+        self.event.phase()
+    }
+
+    /// The index of the extrinsic that emitted the event, if it was emitted by
+    /// one (rather than when initializing or finalizing the block).
+    pub fn extrinsic_index(&self) -> Option<u32> {
+        match self.event.phase() {
+            Phase::ApplyExtrinsic(idx) => Some(idx),
+            _ => None,
+        }
+    }
+
+    /// The name of the pallet that emitted the event.
+    pub fn pallet_name(&self) -> &str {
+        self.event.pallet_name()
+    }
+
+    /// The name of the event.
+    pub fn variant_name(&self) -> &str {
+        self.event.variant_name()
+    }
+
+    /// The dynamically decoded fields of the event. They're decoded the first
+    /// time they're asked for, so handlers which don't look at them (such as
+    /// those given statically typed events) don't pay for decoding them.
+    ///
+    /// The event was checked to be well formed before being handed over, so the
+    /// fields decode unless the metadata describes them inconsistently; if they
+    /// don't, that's logged and the event is treated as having no fields.
+    pub fn field_values(&self) -> &Composite<TypeId> {
+        static NO_FIELDS: Composite<TypeId> = Composite::Unnamed(Vec::new());
+        match self.event.decoded_fields() {
+            Ok(fields) => fields,
+            Err(e) => {
+                tracing::warn!(
+                    pallet = self.pallet_name(),
+                    variant = self.variant_name(),
+                    "Cannot decode the fields of event: {}",
+                    e
+                );
+                &NO_FIELDS
+            }
+        }
+    }
+
+    // The fields of the event as JSON, in the form that [`crate::json`] writes
+    // them, so that integers too big for JSON numbers aren't lost.
+    pub(crate) fn fields_json(&self) -> serde_json::Value {
+        composite_to_json(self.field_values(), self.metadata())
+    }
+
+    // The metadata that the event was decoded with.
+    pub(crate) fn metadata(&self) -> &Metadata {
+        self.event.metadata()
+    }
+
+    /// The value of the field with the given name, if the event has named fields.
+    pub fn field(&self, name: &str) -> Option<&Value<TypeId>> {
+        match self.field_values() {
+            Composite::Named(vals) => vals.iter().find(|(n, _)| n == name).map(|(_, v)| v),
+            Composite::Unnamed(_) => None,
+        }
+    }
+
+    /// Acknowledge that the event has been dealt with, letting the checkpoint of
+    /// the listener move past it. This only needs calling when the listener was
+    /// built with [`AckMode::Manual`]; otherwise it happens once the handler
+    /// returns.
+    pub fn ack(&self) {
+        self.ack.ack()
+    }
+
+    /// A handle to acknowledge the event with later on, for instance once it has
+    /// been written out along with a batch of others. See
+    /// [`super::AckBatch`].
+    pub fn ack_handle(&self) -> Ack {
+        self.ack.clone()
+    }
+}
+
+/// A handler with the type of event that it takes erased, so that handlers for
+/// different events can be kept together.
+pub(crate) trait ErasedHandler<T: Config, Client>: Send + Sync {
+    /// Does the handler want events with these names?
+    fn matches(&self, pallet: &str, variant: &str) -> bool;
+
+    /// Does the handler want this particular event, given that it
+    /// [matches](ErasedHandler::matches()) by name? Checked before the event is
+    /// handed over, so that unwanted events aren't waited on.
+    fn accepts(&self, _ctx: &EventContext<T, Client>) -> bool {
+        true
+    }
+
+    /// Handle the event.
+    fn handle(
+        &self,
+        ctx: EventContext<T, Client>,
+        event: &EventDetails,
+    ) -> BoxFuture<'static, HandlerResult>;
+}
+
+/// Hands events which decode to the static type `Ev` to a [`Handler`].
+pub(crate) struct StaticHandler<Ev, H> {
+    handler: H,
+    _marker: PhantomData<fn() -> Ev>,
+}
+
+impl<Ev, H> StaticHandler<Ev, H> {
+    pub(crate) fn new(handler: H) -> Self {
+        StaticHandler {
+            handler,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, Client, Ev, H> ErasedHandler<T, Client> for StaticHandler<Ev, H>
+where
+    T: Config,
+    Ev: StaticEvent,
+    H: Handler<T, Client, Ev>,
+{
+    fn matches(&self, pallet: &str, variant: &str) -> bool {
+        Ev::is_event(pallet, variant)
+    }
+
+    fn handle(
+        &self,
+        ctx: EventContext<T, Client>,
+        event: &EventDetails,
+    ) -> BoxFuture<'static, HandlerResult> {
+        match event.as_event::<Ev>() {
+            Ok(Some(ev)) => self.handler.handle(ctx, ev),
+            Ok(None) => future::ready(Ok(())).boxed(),
+            Err(e) => future::ready(Err(Error::from(e).into())).boxed(),
+        }
+    }
+}
+
+/// Hands events with the given pallet and variant names to a [`Handler`] of
+/// dynamically decoded events.
+pub(crate) struct DynamicHandler<H> {
+    pallet: String,
+    variant: String,
+    handler: H,
+}
+
+impl<H> DynamicHandler<H> {
+    pub(crate) fn new(pallet: String, variant: String, handler: H) -> Self {
+        DynamicHandler {
+            pallet,
+            variant,
+            handler,
+        }
+    }
+}
+
+impl<T, Client, H> ErasedHandler<T, Client> for DynamicHandler<H>
+where
+    T: Config,
+    H: Handler<T, Client, EventDetails>,
+{
+    fn matches(&self, pallet: &str, variant: &str) -> bool {
+        self.pallet == pallet && self.variant == variant
+    }
+
+    fn handle(
+        &self,
+        ctx: EventContext<T, Client>,
+        event: &EventDetails,
+    ) -> BoxFuture<'static, HandlerResult> {
+        self.handler.handle(ctx, event.clone())
+    }
+}
+
+/// Called whenever a handler fails.
+pub(crate) type OnError<T, Client> =
+    Arc<dyn Fn(&EventContext<T, Client>, &HandlerError) + Send + Sync>;
+
+/// A handler, along with how it should be run.
+pub(crate) struct Registered<T: Config, Client> {
+    pub(crate) handler: Box<dyn ErasedHandler<T, Client>>,
+    pub(crate) retry: Option<RetryPolicy>,
+    pub(crate) concurrency: Concurrency<T, Client>,
+}
+
+impl<T: Config, Client> Registered<T, Client> {
+    pub(crate) fn new(handler: impl ErasedHandler<T, Client> + 'static) -> Self {
+        Registered {
+            handler: Box::new(handler),
+            retry: None,
+            concurrency: Concurrency::Sequential,
+        }
+    }
+}
+
+/// Hands events to the handlers which want them, and deals with the outcome.
+pub(crate) struct Dispatcher<T: Config, Client> {
+    pub(crate) handlers: Vec<Registered<T, Client>>,
+    pub(crate) default_retry: RetryPolicy,
+    pub(crate) on_error: OnError<T, Client>,
+    pub(crate) ack_mode: AckMode,
+    pub(crate) acks: AckTracker,
+    pub(crate) dead_letters: Option<Arc<dyn DeadLetterSink>>,
+}
+
+impl<T, Client> Dispatcher<T, Client>
+where
+    T: Config,
+    Client: Clone + Send + Sync + 'static,
+{
+    /// Hand an event to each of the handlers which want it. Returns whether any
+    /// handler wanted the event.
+    ///
+    /// [`Concurrency::Sequential`] handlers are waited for, one at a time. The
+    /// event is sent to the lanes of any others, to be handled by the workers
+    /// handed back from [`Dispatcher::lanes()`].
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(
+            name = "dispatch",
+            skip_all,
+            fields(
+                pallet = event.pallet_name(),
+                variant = event.variant_name(),
+                event_index = event.index(),
+            )
+        )
+    )]
+    pub(crate) async fn dispatch(
+        &self,
+        client: &Client,
+        block: &BlockContext<T>,
+        event: &EventDetails,
+        lanes: &mut Lanes<T, Client>,
+    ) -> Result<bool, Error> {
+        let matching: Vec<_> = self
+            .handlers
+            .iter()
+            .enumerate()
+            .filter(|(_, h)| h.handler.matches(event.pallet_name(), event.variant_name()))
+            .map(|(idx, _)| idx)
+            .collect();
+        if matching.is_empty() {
+            return Ok(false)
+        }
+
+        let ctx = EventContext::new(client.clone(), block.clone(), event);
+        let wanted: Vec<_> = matching
+            .into_iter()
+            .filter(|idx| self.handlers[*idx].handler.accepts(&ctx))
+            .collect();
+        if wanted.is_empty() {
+            return Ok(false)
+        }
+        let cursor =
+            EventCursor::event(block.number.into(), block.hash.encode(), event.index());
+        let acks = self.acks.track(cursor, wanted.len());
+        for (idx, ack) in wanted.into_iter().zip(acks) {
+            let job = Job {
+                ctx: ctx.clone().with_ack(ack.clone()),
+                event: event.clone(),
+                ack,
+                span: tracing::Span::current(),
+            };
+            match lanes.lane(idx) {
+                Some(lane) => lane.send(job).await?,
+                None => self.handle(idx, job).await?,
+            }
+        }
+        Ok(true)
+    }
+
+    /// Create a lane for each handler which isn't run sequentially, along with a
+    /// future which handles the events sent to them. The future resolves once
+    /// every lane has been dropped and its events handled, or as soon as a
+    /// handler fails in a way that should stop the listener.
+    pub(crate) fn lanes(&self) -> (Lanes<T, Client>, BoxFuture<'_, Result<(), Error>>) {
+        let mut lanes = Vec::with_capacity(self.handlers.len());
+        let mut workers: Vec<BoxFuture<'_, Result<(), Error>>> = Vec::new();
+        for (idx, registered) in self.handlers.iter().enumerate() {
+            let lane = match &registered.concurrency {
+                Concurrency::Sequential => None,
+                Concurrency::Parallel(limit) => {
+                    let limit = (*limit).max(1);
+                    let (tx, rx) = mpsc::channel(limit);
+                    workers.push(
+                        rx.map(Ok)
+                            .try_for_each_concurrent(limit, move |job| self.handle(idx, job))
+                            .boxed(),
+                    );
+                    Some(Lane {
+                        senders: vec![tx],
+                        key: None,
+                    })
+                }
+                Concurrency::Keyed { shards, key } => {
+                    let mut senders = Vec::with_capacity(*shards);
+                    for _ in 0..(*shards).max(1) {
+                        let (tx, rx) = mpsc::channel(1);
+                        workers.push(
+                            rx.map(Ok)
+                                .try_for_each(move |job| self.handle(idx, job))
+                                .boxed(),
+                        );
+                        senders.push(tx);
+                    }
+                    Some(Lane {
+                        senders,
+                        key: Some(key.clone()),
+                    })
+                }
+            };
+            lanes.push(lane);
+        }
+        let workers = future::try_join_all(workers).map_ok(|_| ()).boxed();
+        (Lanes { lanes }, workers)
+    }
+
+    /// Hand an event to one handler, trying again according to its
+    /// [`RetryPolicy`] if it fails. Once a handler has failed for good, the
+    /// failure is reported to `on_error`, and if there is a [`DeadLetterSink`],
+    /// the event is sent there and then acknowledged regardless of the
+    /// [`AckMode`]. With [`AckMode::Auto`], the event is acknowledged once the
+    /// handler returns either way.
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(
+            name = "handler",
+            skip_all,
+            parent = &job.span,
+            fields(handler = idx)
+        )
+    )]
+    async fn handle(&self, idx: usize, job: Job<T, Client>) -> Result<(), Error> {
+        let Job { ctx, event, ack, .. } = job;
+        let registered = &self.handlers[idx];
+        let retry = registered.retry.as_ref().unwrap_or(&self.default_retry);
+        let mut attempts = 0;
+        let res = loop {
+            attempts += 1;
+            match registered.handler.handle(ctx.clone(), &event).await {
+                Err(e) if retry.should_retry(attempts, &e) => {
+                    let backoff = retry.backoff(attempts);
+                    tracing::warn!(
+                        "Handler for {}::{} failed (attempt {}), retrying in {:?}: {}",
+                        ctx.pallet_name(),
+                        ctx.variant_name(),
+                        attempts,
+                        backoff,
+                        e
+                    );
+                    if !backoff.is_zero() {
+                        futures_timer::Delay::new(backoff).await;
+                    }
+                }
+                res => break res,
+            }
+        };
+        if let Err(e) = res {
+            (self.on_error)(&ctx, &e);
+            if let Some(dead_letters) = &self.dead_letters {
+                dead_letters
+                    .send(DeadLetter::new(&ctx, &event, &e, attempts))
+                    .await?;
+                ack.ack();
+            }
+        }
+        if self.ack_mode == AckMode::Auto {
+            ack.ack();
+        }
+        Ok(())
+    }
+
+    /// Send a dead letter to the [`DeadLetterSink`], if there is one. This is for
+    /// events that never made it to a handler, such as those that couldn't be
+    /// decoded.
+    pub(crate) async fn dead_letter(&self, letter: DeadLetter) -> Result<(), Error> {
+        match &self.dead_letters {
+            Some(dead_letters) => dead_letters.send(letter).await,
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        events::{
+            test_utils::{
+                event_record,
+                events,
+                events_raw,
+                metadata,
+            },
+            Events,
+        },
+        listener::MemoryDeadLetterSink,
+        SubstrateConfig,
+    };
+    use codec::Decode;
+    use parking_lot::Mutex;
+    use scale_info::TypeInfo;
+    use std::time::Duration;
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Event {
+        A(u8),
+        B(bool),
+    }
+
+    #[derive(Debug, PartialEq, Decode)]
+    struct A(u8);
+
+    impl StaticEvent for A {
+        const PALLET: &'static str = "Test";
+        const EVENT: &'static str = "A";
+    }
+
+    type Ctx = EventContext<SubstrateConfig, ()>;
+
+    fn test_events() -> Events<SubstrateConfig> {
+        events(
+            metadata::<Event>(),
+            vec![
+                event_record(Phase::Initialization, Event::A(1)),
+                event_record(Phase::ApplyExtrinsic(0), Event::B(true)),
+                event_record(Phase::Finalization, Event::A(2)),
+            ],
+        )
+    }
+
+    fn test_block() -> BlockContext<SubstrateConfig> {
+        BlockContext {
+            hash: Default::default(),
+            number: 7,
+            timestamp: Some(1000),
+            chain: None,
+        }
+    }
+
+    // A dispatcher whose handlers note down the events they're handed; `A` events
+    // are acknowledged by the handler, and `B` events fail on all three attempts.
+    fn dispatcher(
+        seen: Arc<Mutex<Vec<String>>>,
+        ack_mode: AckMode,
+        dead_letters: Option<Arc<dyn DeadLetterSink>>,
+    ) -> Dispatcher<SubstrateConfig, ()> {
+        let (seen_a, seen_b, seen_err) = (seen.clone(), seen.clone(), seen);
+        Dispatcher {
+            handlers: vec![
+                Registered::new(StaticHandler::new(move |ctx: Ctx, ev: A| {
+                    seen_a.lock().push(format!("A({}) at {}", ev.0, ctx.event_index()));
+                    ctx.ack();
+                    async { Ok::<_, HandlerError>(()) }
+                })),
+                Registered {
+                    retry: Some(
+                        RetryPolicy::exponential(3).initial_backoff(Duration::ZERO),
+                    ),
+                    concurrency: Concurrency::Sequential,
+                    handler: Box::new(DynamicHandler::new(
+                    "Test".into(),
+                    "B".into(),
+                    move |ctx: Ctx, ev: EventDetails| {
+                        assert_eq!(ctx.extrinsic_index(), Some(0));
+                        assert!(matches!(ctx.field_values(), Composite::Unnamed(v) if v.len() == 1));
+                        seen_b.lock().push(format!("{} at {}", ev.variant_name(), ev.index()));
+                        async { Err::<(), HandlerError>("oops".into()) }
+                    },
+                )),
+                },
+            ],
+            default_retry: RetryPolicy::none(),
+            on_error: Arc::new(move |ctx: &Ctx, e: &HandlerError| {
+                seen_err.lock().push(format!(
+                    "error in #{} at {:?}: {}",
+                    ctx.block_number(),
+                    ctx.timestamp(),
+                    e
+                ))
+            }),
+            ack_mode,
+            acks: AckTracker::default(),
+            dead_letters,
+        }
+    }
+
+    async fn dispatch_all(
+        dispatcher: &Dispatcher<SubstrateConfig, ()>,
+        events: &Events<SubstrateConfig>,
+    ) -> Vec<bool> {
+        let (mut lanes, workers) = dispatcher.lanes();
+        let dispatching = async move {
+            let mut dispatched = Vec::new();
+            for event in events.iter() {
+                let event = event.unwrap();
+                dispatched.push(
+                    dispatcher
+                        .dispatch(&(), &test_block(), &event, &mut lanes)
+                        .await
+                        .unwrap(),
+                );
+            }
+            Ok::<_, Error>(dispatched)
+        };
+        let (dispatched, ()) = futures::try_join!(dispatching, workers).unwrap();
+        dispatched
+    }
+
+    #[tokio::test]
+    async fn events_are_dispatched_to_matching_handlers() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let dispatcher = dispatcher(seen.clone(), AckMode::Auto, None);
+
+        assert_eq!(dispatch_all(&dispatcher, &test_events()).await, vec![true, true, true]);
+        assert_eq!(
+            *seen.lock(),
+            vec![
+                "A(1) at 0",
+                "B at 1",
+                "B at 1",
+                "B at 1",
+                "error in #7 at Some(1000): oops",
+                "A(2) at 2"
+            ]
+        );
+        // Failed events are acknowledged too.
+        assert_eq!(
+            dispatcher.acks.take_committed(1),
+            Some(EventCursor::event(7, test_block().hash.encode(), 2))
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_events_are_sent_to_dead_letters() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let dead_letters = MemoryDeadLetterSink::new();
+        let dispatcher = dispatcher(
+            seen.clone(),
+            AckMode::Manual,
+            Some(Arc::new(dead_letters.clone())),
+        );
+        dispatch_all(&dispatcher, &test_events()).await;
+
+        let letters = dead_letters.letters();
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0].block_number, 7);
+        assert_eq!(letters[0].event_index, 1);
+        assert_eq!(letters[0].variant, "B");
+        assert_eq!(letters[0].error, "oops");
+        assert_eq!(letters[0].attempts, 3);
+        assert_eq!(letters[0].field_bytes, "0x01");
+
+        // The failed event is acknowledged once it's been sent to the dead letters,
+        // so the checkpoint can move past it.
+        assert_eq!(
+            dispatcher.acks.take_committed(1),
+            Some(EventCursor::event(7, test_block().hash.encode(), 2))
+        );
+    }
+
+    #[tokio::test]
+    async fn undecodable_events_are_sent_to_dead_letters() {
+        let mut event_bytes = event_record(Phase::Initialization, Event::A(1)).encode();
+        // An event of a pallet that isn't in the metadata.
+        event_bytes.extend([2, 9, 0]);
+        let events = events_raw(metadata::<Event>(), event_bytes, 2);
+        let dead_letters = MemoryDeadLetterSink::new();
+        let dispatcher = dispatcher(
+            Arc::new(Mutex::new(Vec::new())),
+            AckMode::Auto,
+            Some(Arc::new(dead_letters.clone())),
+        );
+
+        for event in events.iter() {
+            if let Err(e) = event {
+                let letter = DeadLetter::undecodable(&test_block(), &e);
+                dispatcher.dead_letter(letter).await.unwrap();
+            }
+        }
+
+        let letters = dead_letters.letters();
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0].block_number, 7);
+        assert_eq!(letters[0].event_index, 1);
+        assert_eq!(letters[0].attempts, 0);
+        assert_eq!(letters[0].field_bytes, "0x");
+    }
+
+    #[tokio::test]
+    async fn keyed_handlers_keep_events_with_the_same_key_in_order() {
+        let events = events(
+            metadata::<Event>(),
+            (1..=6)
+                .map(|n| event_record(Phase::ApplyExtrinsic(n as u32), Event::A(n)))
+                .collect(),
+        );
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen2 = seen.clone();
+        let dispatcher = Dispatcher {
+            handlers: vec![Registered {
+                handler: Box::new(StaticHandler::new(move |_ctx: Ctx, ev: A| {
+                    let seen = seen2.clone();
+                    async move {
+                        // Earlier events take longer, so that they'd finish last if
+                        // nothing kept them in order.
+                        let delay = Duration::from_millis(10 * (7 - ev.0 as u64));
+                        futures_timer::Delay::new(delay).await;
+                        seen.lock().push(ev.0);
+                        Ok::<_, HandlerError>(())
+                    }
+                })),
+                retry: None,
+                concurrency: Concurrency::keyed(2, |ctx: &Ctx| ctx.extrinsic_index().unwrap() % 2),
+            }],
+            default_retry: RetryPolicy::none(),
+            on_error: Arc::new(|_: &Ctx, _: &HandlerError| {}),
+            ack_mode: AckMode::Auto,
+            acks: AckTracker::default(),
+            dead_letters: None,
+        };
+        dispatch_all(&dispatcher, &events).await;
+
+        let seen = seen.lock().clone();
+        assert_eq!(seen.len(), 6);
+        for key in 0..2 {
+            let of_key: Vec<_> = seen.iter().filter(|n| **n % 2 == key).collect();
+            let mut sorted = of_key.clone();
+            sorted.sort();
+            assert_eq!(of_key, sorted);
+        }
+        assert_eq!(
+            dispatcher.acks.take_committed(1),
+            Some(EventCursor::event(7, test_block().hash.encode(), 5))
+        );
+    }
+}
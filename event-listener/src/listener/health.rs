@@ -0,0 +1,301 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Reporting whether a listener is working, for orchestrators to act on.
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+/// Keeps track of whether an [`super::EventListener`] is working, from whether it's
+/// subscribed to blocks, how long ago it last finished with a block, and whether
+/// the components registered with [`Health::component()`] (such as the sinks that
+/// events are written to) are healthy. Hand it to the listener with
+/// [`super::EventListenerBuilder::health()`].
+///
+/// The listener is:
+///
+/// - **live** as long as it has finished with a block within the maximum block
+///   age (or, before the first block, within that long of the [`Health`] being
+///   created). A listener which isn't live is stuck, and should be restarted.
+/// - **ready** when it's live, subscribed to blocks, and every component is
+///   healthy.
+///
+/// With the `health` feature, [`Health::serve()`] reports these over HTTP.
+///
+/// ```no_run
+/// use event_listener::{
+///     listener::{ Health, SinkHandler, JsonLinesSink },
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+/// use std::time::Duration;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let health = Health::new(Duration::from_secs(120));
+/// #[cfg(feature = "health")]
+/// tokio::spawn(health.clone().serve("0.0.0.0:8080".parse().unwrap()));
+///
+/// let sink = SinkHandler::new(JsonLinesSink::new("events", "transfers"))
+///     .health(health.component("transfers"));
+/// api.listener()
+///     .on_dynamic("Balances", "Transfer", sink)
+///     .health(health)
+///     .run()
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Health(Arc<HealthInner>);
+
+struct HealthInner {
+    max_block_age: Duration,
+    started: Instant,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    connected: bool,
+    last_block: Option<(u64, Instant)>,
+    // The reason each component is unhealthy, if it is.
+    components: BTreeMap<String, Option<String>>,
+}
+
+impl std::fmt::Debug for Health {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Health")
+            .field("max_block_age", &self.0.max_block_age)
+            .field("status", &self.status())
+            .finish()
+    }
+}
+
+/// How a listener is doing, from [`Health::status()`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthStatus {
+    /// Whether the listener has finished with a block recently enough.
+    pub live: bool,
+    /// Whether the listener is live, subscribed, and has no unhealthy components.
+    pub ready: bool,
+    /// Whether the listener is subscribed to blocks.
+    pub connected: bool,
+    /// The number of the last block the listener finished with.
+    pub last_block: Option<u64>,
+    /// How many seconds it's been since the last block was finished with, or
+    /// since the [`Health`] was created if none have been.
+    pub seconds_since_last_block: f64,
+    /// The unhealthy components, and why.
+    pub unhealthy: BTreeMap<String, String>,
+}
+
+impl Health {
+    /// Track the health of a listener, which is no longer live once it hasn't
+    /// finished with a block for `max_block_age`. This should be comfortably
+    /// longer than the time between blocks.
+    pub fn new(max_block_age: Duration) -> Self {
+        Health(Arc::new(HealthInner {
+            max_block_age,
+            started: Instant::now(),
+            state: Mutex::new(State::default()),
+        }))
+    }
+
+    /// Register a component which the listener needs to be healthy to be ready.
+    /// It starts off healthy.
+    pub fn component(&self, name: impl Into<String>) -> ComponentHealth {
+        let name = name.into();
+        self.0.state.lock().components.insert(name.clone(), None);
+        ComponentHealth {
+            health: self.clone(),
+            name,
+        }
+    }
+
+    /// How the listener is doing right now.
+    pub fn status(&self) -> HealthStatus {
+        let state = self.0.state.lock();
+        let since_last_block = match state.last_block {
+            Some((_, at)) => at.elapsed(),
+            None => self.0.started.elapsed(),
+        };
+        let unhealthy: BTreeMap<_, _> = state
+            .components
+            .iter()
+            .filter_map(|(name, reason)| Some((name.clone(), reason.clone()?)))
+            .collect();
+        let live = since_last_block < self.0.max_block_age;
+        HealthStatus {
+            live,
+            ready: live && state.connected && unhealthy.is_empty(),
+            connected: state.connected,
+            last_block: state.last_block.map(|(number, _)| number),
+            seconds_since_last_block: since_last_block.as_secs_f64(),
+            unhealthy,
+        }
+    }
+
+    /// Serve the health of the listener over HTTP at the address given, until
+    /// the server fails. `GET /live` and `GET /ready` respond with `200 OK` if
+    /// the listener is live or ready respectively, and `503 Service Unavailable`
+    /// if not, along with the [`HealthStatus`] as JSON.
+    #[cfg(feature = "health")]
+    pub async fn serve(self, addr: std::net::SocketAddr) -> Result<(), crate::error::Error> {
+        use hyper::{
+            service::{
+                make_service_fn,
+                service_fn,
+            },
+            Body,
+            Response,
+            Server,
+        };
+        use std::convert::Infallible;
+
+        let make_service = make_service_fn(move |_| {
+            let health = self.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req: hyper::Request<Body>| {
+                    let (status, body) = health.respond(req.uri().path());
+                    let res = Response::builder()
+                        .status(status)
+                        .header("content-type", "application/json")
+                        .body(Body::from(body))
+                        .expect("response is valid; qed");
+                    async move { Ok::<_, Infallible>(res) }
+                }))
+            }
+        });
+        Server::try_bind(&addr)
+            .map_err(|e| crate::error::Error::Other(e.to_string()))?
+            .serve(make_service)
+            .await
+            .map_err(|e| crate::error::Error::Other(e.to_string()))
+    }
+
+    /// The status code and body to respond to a request for the path given with.
+    #[cfg(feature = "health")]
+    fn respond(&self, path: &str) -> (u16, String) {
+        let status = self.status();
+        let ok = match path {
+            "/live" => status.live,
+            "/ready" => status.ready,
+            _ => return (404, String::new()),
+        };
+        let body = serde_json::to_string(&status).unwrap_or_default();
+        (if ok { 200 } else { 503 }, body)
+    }
+
+    /// Mark the listener as subscribed to blocks, until the guard handed back is
+    /// dropped.
+    pub(crate) fn connected(&self) -> Connected {
+        self.0.state.lock().connected = true;
+        Connected {
+            health: self.clone(),
+        }
+    }
+
+    /// Note that the listener has finished with a block.
+    pub(crate) fn block_processed(&self, number: u64) {
+        self.0.state.lock().last_block = Some((number, Instant::now()));
+    }
+}
+
+/// Marks the listener as no longer subscribed once dropped.
+pub(crate) struct Connected {
+    health: Health,
+}
+
+impl Drop for Connected {
+    fn drop(&mut self) {
+        self.health.0.state.lock().connected = false;
+    }
+}
+
+/// A component that a listener needs to be healthy to be ready, from
+/// [`Health::component()`].
+#[derive(Clone)]
+pub struct ComponentHealth {
+    health: Health,
+    name: String,
+}
+
+impl std::fmt::Debug for ComponentHealth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ComponentHealth")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl ComponentHealth {
+    /// Mark the component as healthy.
+    pub fn healthy(&self) {
+        self.set(None)
+    }
+
+    /// Mark the component as unhealthy, for the reason given.
+    pub fn unhealthy(&self, reason: impl Into<String>) {
+        self.set(Some(reason.into()))
+    }
+
+    fn set(&self, reason: Option<String>) {
+        self.health
+            .0
+            .state
+            .lock()
+            .components
+            .insert(self.name.clone(), reason);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn readiness_follows_connection_and_components() {
+        let health = Health::new(Duration::from_secs(60));
+        let sink = health.component("sink");
+        assert!(health.status().live);
+        assert!(!health.status().ready);
+
+        let connected = health.connected();
+        health.block_processed(10);
+        assert!(health.status().ready);
+        assert_eq!(health.status().last_block, Some(10));
+
+        sink.unhealthy("broker unreachable");
+        let status = health.status();
+        assert!(status.live && !status.ready);
+        assert_eq!(status.unhealthy["sink"], "broker unreachable");
+
+        sink.healthy();
+        assert!(health.status().ready);
+        drop(connected);
+        assert!(!health.status().connected);
+        assert!(!health.status().ready);
+    }
+
+    #[test]
+    fn listeners_are_not_live_once_blocks_are_too_old() {
+        let health = Health::new(Duration::ZERO);
+        let _connected = health.connected();
+        health.block_processed(1);
+        let status = health.status();
+        assert!(!status.live);
+        assert!(!status.ready);
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! A runtime which follows the chain and hands the events in each block to the
+//! handlers registered for them. Start with [`crate::OnlineClient::listener()`]
+//! or [`EventListenerBuilder::new()`].
+
+mod ack;
+mod backfill;
+mod builder;
+mod checkpoint;
+mod concurrency;
+mod dead_letter;
+mod dedup;
+mod handler;
+mod health;
+mod multi_chain;
+#[cfg(feature = "notify")]
+mod notify;
+mod progress;
+mod retry;
+mod rules;
+#[cfg(feature = "sentry")]
+pub mod sentry;
+mod shutdown;
+mod sink;
+#[cfg(feature = "opentelemetry")]
+pub mod telemetry;
+mod template;
+
+pub use ack::{
+    Ack,
+    AckBatch,
+    AckMode,
+};
+pub use backfill::{
+    Backfill,
+    DEFAULT_BACKFILL_FETCHERS,
+};
+pub use builder::{
+    EventListener,
+    EventListenerBuilder,
+    DEFAULT_DRAIN_TIMEOUT,
+};
+#[cfg(feature = "sqlite")]
+pub use checkpoint::SqliteCheckpointStore;
+pub use checkpoint::{
+    CheckpointStore,
+    EventCursor,
+    FileCheckpointStore,
+    MemoryCheckpointStore,
+};
+pub use concurrency::Concurrency;
+#[cfg(feature = "sqlite")]
+pub use dead_letter::SqliteDeadLetterSink;
+pub use dead_letter::{
+    DeadLetter,
+    DeadLetterSink,
+    FileDeadLetterSink,
+    MemoryDeadLetterSink,
+};
+#[cfg(feature = "sqlite")]
+pub use dedup::SqliteDedupStore;
+pub use dedup::{
+    DedupHandler,
+    DedupStore,
+    MemoryDedupStore,
+};
+pub(crate) use handler::BlockContext;
+pub use handler::{
+    EventContext,
+    Handler,
+    HandlerError,
+    HandlerResult,
+};
+pub use health::{
+    ComponentHealth,
+    Health,
+    HealthStatus,
+};
+pub use multi_chain::MultiChainListener;
+#[cfg(feature = "notify")]
+pub use notify::{
+    Notifier,
+    Webhook,
+};
+pub use progress::{
+    Progress,
+    Watermark,
+};
+pub use retry::{
+    RetryDecision,
+    RetryPolicy,
+};
+pub use rules::{
+    Comparison,
+    Condition,
+    Routes,
+    Rule,
+    RuleSet,
+};
+pub use shutdown::ShutdownHandle;
+#[cfg(feature = "aws")]
+pub use sink::{
+    SnsSink,
+    SqsSink,
+};
+#[cfg(feature = "channels")]
+pub use sink::{
+    BroadcastSink,
+    ChannelSink,
+    EventReceiver,
+    WhenFull,
+};
+#[cfg(feature = "csv")]
+pub use sink::CsvSink;
+#[cfg(feature = "grpc")]
+pub use sink::{
+    grpc_proto,
+    GrpcFeed,
+};
+#[cfg(feature = "mqtt")]
+pub use sink::MqttSink;
+#[cfg(feature = "parquet")]
+pub use sink::ParquetSink;
+#[cfg(feature = "postgres")]
+pub use sink::PostgresSink;
+#[cfg(feature = "redis")]
+pub use sink::RedisStreamSink;
+#[cfg(feature = "sqlite")]
+pub use sink::SqliteSink;
+pub use sink::{
+    EventRecord,
+    EventSink,
+    JsonLinesSink,
+    MemoryEventSink,
+    SinkHandler,
+};
+pub(crate) use template::format_balance;
+pub use template::{
+    ChainProperties,
+    MessageTemplate,
+};
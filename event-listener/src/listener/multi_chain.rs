@@ -0,0 +1,295 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Listening to several chains at once, such as a relay chain and its parachains.
+
+use super::{
+    builder::{
+        EventListener,
+        EventListenerBuilder,
+    },
+    retry::RetryPolicy,
+    shutdown::{
+        Shutdown,
+        ShutdownHandle,
+    },
+};
+use crate::{
+    client::OnlineClientT,
+    error::{
+        Error,
+        ErrorContext,
+    },
+    Config,
+};
+use futures::{
+    future::{
+        self,
+        BoxFuture,
+        Either,
+    },
+    FutureExt,
+};
+use parking_lot::Mutex;
+use std::{
+    future::Future,
+    sync::atomic::{
+        AtomicBool,
+        Ordering,
+    },
+    time::Duration,
+};
+
+/// A listener of any chain, with the types of the chain erased.
+trait ChainListener: Send + Sync {
+    fn run(&self) -> BoxFuture<'_, Result<(), Error>>;
+    fn shutdown_handle(&self) -> ShutdownHandle;
+}
+
+impl<T, Client> ChainListener for EventListener<T, Client>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    fn run(&self) -> BoxFuture<'_, Result<(), Error>> {
+        EventListener::run(self).boxed()
+    }
+
+    fn shutdown_handle(&self) -> ShutdownHandle {
+        EventListener::shutdown_handle(self)
+    }
+}
+
+type Connect =
+    Box<dyn Fn() -> BoxFuture<'static, Result<Box<dyn ChainListener>, Error>> + Send + Sync>;
+
+struct Chain {
+    id: String,
+    connect: Connect,
+    // The listener currently running, so that it can be shut down.
+    running: Mutex<Option<ShutdownHandle>>,
+}
+
+/// Runs a listener for each of several chains at once, each with its own client,
+/// metadata and checkpoints. Every event is tagged with the identifier of the
+/// chain it came from (see [`super::EventContext::chain_id()`]).
+///
+/// Each chain is connected to with a function which builds a new client and
+/// listener. If the connection is lost, it's called again, and the new listener
+/// carries on from its checkpoint.
+///
+/// # Example
+///
+/// ```no_run
+/// use event_listener::{
+///     events::EventDetails,
+///     listener::{ EventContext, HandlerResult, MultiChainListener },
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+///
+/// type Ctx = EventContext<PolkadotConfig, OnlineClient<PolkadotConfig>>;
+///
+/// async fn on_transfer(ctx: Ctx, event: EventDetails) -> HandlerResult {
+///     println!("Transfer on {:?}: {:?}", ctx.chain_id(), event.field_values()?);
+///     Ok(())
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// MultiChainListener::new()
+///     .chain("polkadot", || async {
+///         let api = OnlineClient::<PolkadotConfig>::from_url("wss://rpc.polkadot.io:443").await?;
+///         Ok(api.listener().on_dynamic("Balances", "Transfer", on_transfer))
+///     })
+///     .chain("statemint", || async {
+///         let api = OnlineClient::<PolkadotConfig>::from_url("wss://statemint-rpc.polkadot.io:443").await?;
+///         Ok(api.listener().on_dynamic("Balances", "Transfer", on_transfer))
+///     })
+///     .run()
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+pub struct MultiChainListener {
+    chains: Vec<Chain>,
+    reconnect: RetryPolicy,
+    stopping: AtomicBool,
+    shutdown: Shutdown,
+}
+
+impl std::fmt::Debug for MultiChainListener {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MultiChainListener")
+            .field(
+                "chains",
+                &self.chains.iter().map(|c| &c.id).collect::<Vec<_>>(),
+            )
+            .field("reconnect", &self.reconnect)
+            .finish()
+    }
+}
+
+impl Default for MultiChainListener {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MultiChainListener {
+    /// Create a listener with no chains to listen to yet.
+    pub fn new() -> Self {
+        MultiChainListener {
+            chains: Vec::new(),
+            reconnect: RetryPolicy::exponential(u32::MAX)
+                .initial_backoff(Duration::from_secs(1))
+                .max_backoff(Duration::from_secs(60)),
+            stopping: AtomicBool::new(false),
+            shutdown: Shutdown::new(),
+        }
+    }
+
+    /// Listen to a chain, tagging its events with the identifier given. `connect`
+    /// is called to build a client and listener for the chain to begin with, and
+    /// again whenever the connection to it is lost.
+    ///
+    /// Give each chain its own [`super::CheckpointStore`] (or its own name in a
+    /// shared one), so that each carries on from where it left off.
+    pub fn chain<T, Client, F, Fut>(mut self, id: impl Into<String>, connect: F) -> Self
+    where
+        T: Config,
+        Client: OnlineClientT<T>,
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<EventListenerBuilder<T, Client>, Error>> + Send + 'static,
+    {
+        let id = id.into();
+        let chain_id = id.clone();
+        let connect = move || {
+            let chain_id = chain_id.clone();
+            connect()
+                .map(move |builder| {
+                    let listener = builder?.chain_id(chain_id).build();
+                    Ok(Box::new(listener) as Box<dyn ChainListener>)
+                })
+                .boxed()
+        };
+        self.chains.push(Chain {
+            id,
+            connect: Box::new(connect),
+            running: Mutex::new(None),
+        });
+        self
+    }
+
+    /// How long to wait between attempts to reconnect to a chain, and how many
+    /// times to try. Only the backoff and maximum attempts of the policy are used.
+    /// By default, reconnecting is tried forever, waiting from 1s up to 60s
+    /// between attempts.
+    pub fn reconnect(mut self, policy: RetryPolicy) -> Self {
+        self.reconnect = policy;
+        self
+    }
+
+    /// A handle to stop every chain's listener with, once running. See
+    /// [`ShutdownHandle`].
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.handle()
+    }
+
+    /// Listen to every chain until shut down, or until one of them fails in a way
+    /// that reconnecting won't help with.
+    pub async fn run(&self) -> Result<(), Error> {
+        // Held until we return, letting shutdown handles know once we have.
+        let (stop_rx, _done_tx) = match self.shutdown.take() {
+            Some((stop_rx, done_tx)) => (Some(stop_rx), Some(done_tx)),
+            None => (None, None),
+        };
+
+        let chains = future::try_join_all(self.chains.iter().map(|chain| {
+            self.run_chain(chain).map(move |res| {
+                res.map_err(|e| e.context(ErrorContext::Chain { id: chain.id.clone() }))
+            })
+        }))
+        .boxed();
+        let stopping = match stop_rx {
+            Some(stop_rx) => Either::Left(stop_rx.map(|_| ())),
+            None => Either::Right(future::pending()),
+        };
+
+        match future::select(chains, stopping).await {
+            Either::Left((res, _)) => res.map(|_| ()),
+            Either::Right(((), chains)) => {
+                tracing::info!("Shutting down multi-chain listener");
+                self.stopping.store(true, Ordering::SeqCst);
+                let running: Vec<_> = self
+                    .chains
+                    .iter()
+                    .filter_map(|chain| chain.running.lock().clone())
+                    .collect();
+                future::join_all(running.iter().map(|handle| handle.shutdown())).await;
+                chains.await.map(|_| ())
+            }
+        }
+    }
+
+    // Connect to the chain and listen to it, reconnecting if the connection is lost.
+    async fn run_chain(&self, chain: &Chain) -> Result<(), Error> {
+        let mut attempts = 0;
+        loop {
+            if self.stopping.load(Ordering::SeqCst) {
+                return Ok(())
+            }
+
+            let res = match (chain.connect)().await {
+                Ok(listener) => {
+                    *chain.running.lock() = Some(listener.shutdown_handle());
+                    // A shutdown may have been asked for while connecting, in
+                    // which case the handle we just stored was missed.
+                    if self.stopping.load(Ordering::SeqCst) {
+                        return Ok(())
+                    }
+                    let res = listener.run().await;
+                    *chain.running.lock() = None;
+                    // The connection was lost after it was made, so the attempts
+                    // to make it start over. A subscription that ended on its own
+                    // keeps counting, so that the node isn't reconnected to in a
+                    // tight loop.
+                    if res.is_err() {
+                        attempts = 0;
+                    }
+                    res
+                }
+                Err(e) => Err(e),
+            };
+
+            match res {
+                Ok(()) if self.stopping.load(Ordering::SeqCst) => return Ok(()),
+                Ok(()) => {
+                    attempts = attempts.saturating_add(1);
+                    let backoff = self.reconnect.backoff(attempts);
+                    tracing::warn!(
+                        "Subscription to chain {} ended; reconnecting in {:?}",
+                        chain.id,
+                        backoff
+                    );
+                    futures_timer::Delay::new(backoff).await;
+                }
+                Err(e) if e.is_retryable() && attempts + 1 < self.reconnect.max_attempts() => {
+                    attempts += 1;
+                    #[cfg(feature = "sentry")]
+                    super::sentry::report_reconnects(&chain.id, attempts, &e);
+                    let backoff = self.reconnect.backoff(attempts);
+                    tracing::warn!(
+                        "Lost connection to chain {} ({}); reconnecting in {:?}",
+                        chain.id,
+                        e,
+                        backoff
+                    );
+                    futures_timer::Delay::new(backoff).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
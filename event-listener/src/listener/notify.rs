@@ -0,0 +1,388 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Posting notifications about events to chat services.
+
+use super::{
+    handler::{
+        EventContext,
+        Handler,
+        HandlerResult,
+    },
+    template::{
+        ChainProperties,
+        MessageTemplate,
+    },
+};
+use crate::{
+    error::Error,
+    events::EventDetails,
+    Config,
+};
+use futures::{
+    future::{
+        self,
+        BoxFuture,
+    },
+    FutureExt,
+};
+use parking_lot::Mutex;
+use std::{
+    collections::{
+        HashMap,
+        VecDeque,
+    },
+    sync::Arc,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+/// How many times a message is retried when the service says to slow down.
+const MAX_RATE_LIMITED_ATTEMPTS: usize = 3;
+
+/// An incoming webhook of a chat service, which a [`Notifier`] posts messages to.
+/// Each webhook posts to a single channel, so use a webhook for each channel
+/// that notifications are routed to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Webhook {
+    /// The URL of a Slack incoming webhook.
+    Slack(String),
+    /// The URL of a Discord webhook.
+    Discord(String),
+}
+
+impl Webhook {
+    fn url(&self) -> &str {
+        match self {
+            Webhook::Slack(url) | Webhook::Discord(url) => url,
+        }
+    }
+
+    fn payload(&self, message: &str) -> serde_json::Value {
+        match self {
+            Webhook::Slack(_) => serde_json::json!({ "text": message }),
+            Webhook::Discord(_) => serde_json::json!({ "content": message }),
+        }
+    }
+
+    // How many messages each service accepts in how long, for each webhook.
+    fn default_rate_limit(&self) -> (usize, Duration) {
+        match self {
+            Webhook::Slack(_) => (1, Duration::from_secs(1)),
+            Webhook::Discord(_) => (5, Duration::from_secs(2)),
+        }
+    }
+}
+
+/// A [`Handler`] which writes events out with a [`MessageTemplate`], and posts
+/// them to Slack or Discord webhooks. Events are routed by pallet and variant to
+/// a webhook and template; the first route that matches is used, and events
+/// which match none are ignored.
+///
+/// Messages to each webhook are rate limited, by default to what the service
+/// allows (one a second for Slack, and five every two seconds for Discord), with
+/// handlers waiting their turn. If a service still says to slow down, the message
+/// is retried once it says to.
+///
+/// ```no_run
+/// use event_listener::{
+///     listener::{ ChainProperties, MessageTemplate, Notifier, Webhook },
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let properties = api.rpc().system_properties().await.unwrap();
+///
+/// let transfers = MessageTemplate::parse(
+///     "{{ fields.amount | balance }} sent to {{ fields.to | ss58 | short }}",
+/// )
+/// .unwrap();
+/// let governance = MessageTemplate::parse("{{ pallet }}::{{ variant }} in #{{ block_number }}")
+///     .unwrap();
+/// let notifier = Notifier::new(ChainProperties::from_properties(&properties))
+///     .route(
+///         "Balances",
+///         "Transfer",
+///         Webhook::Slack("https://hooks.slack.com/services/...".into()),
+///         transfers,
+///     )
+///     .route_pallet(
+///         "Democracy",
+///         Webhook::Discord("https://discord.com/api/webhooks/...".into()),
+///         governance,
+///     );
+///
+/// api.listener()
+///     .on_dynamic("Balances", "Transfer", notifier.clone())
+///     .on_dynamic("Democracy", "Started", notifier.clone())
+///     .on_dynamic("Democracy", "Passed", notifier)
+///     .run()
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Notifier {
+    chain: ChainProperties,
+    routes: Vec<NotifyRoute>,
+    rate_limit: Option<(usize, Duration)>,
+    http: reqwest::Client,
+    limiters: Arc<Mutex<HashMap<String, Arc<RateLimiter>>>>,
+}
+
+#[derive(Clone, Debug)]
+struct NotifyRoute {
+    pallet: Option<String>,
+    variant: Option<String>,
+    webhook: Webhook,
+    template: MessageTemplate,
+}
+
+impl NotifyRoute {
+    fn matches(&self, pallet: &str, variant: &str) -> bool {
+        self.pallet.as_deref().map_or(true, |p| p == pallet)
+            && self.variant.as_deref().map_or(true, |v| v == variant)
+    }
+}
+
+impl Notifier {
+    /// Write messages with the properties of the chain given, such as its token
+    /// symbol and address format.
+    pub fn new(chain: ChainProperties) -> Self {
+        Notifier {
+            chain,
+            routes: Vec::new(),
+            rate_limit: None,
+            http: reqwest::Client::new(),
+            limiters: Default::default(),
+        }
+    }
+
+    /// Post events with this pallet and variant to the webhook given.
+    pub fn route(
+        self,
+        pallet: impl Into<String>,
+        variant: impl Into<String>,
+        webhook: Webhook,
+        template: MessageTemplate,
+    ) -> Self {
+        self.add_route(Some(pallet.into()), Some(variant.into()), webhook, template)
+    }
+
+    /// Post any events from this pallet to the webhook given.
+    pub fn route_pallet(
+        self,
+        pallet: impl Into<String>,
+        webhook: Webhook,
+        template: MessageTemplate,
+    ) -> Self {
+        self.add_route(Some(pallet.into()), None, webhook, template)
+    }
+
+    /// Post any events which no route before matches to the webhook given.
+    pub fn fallback(self, webhook: Webhook, template: MessageTemplate) -> Self {
+        self.add_route(None, None, webhook, template)
+    }
+
+    /// Post at most `messages` messages to each webhook in any `period`, rather
+    /// than the default for the service.
+    pub fn rate_limit(mut self, messages: usize, period: Duration) -> Self {
+        self.rate_limit = Some((messages.max(1), period));
+        self
+    }
+
+    /// Post messages with the HTTP client given, such as to set a proxy or timeouts.
+    pub fn http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    fn add_route(
+        mut self,
+        pallet: Option<String>,
+        variant: Option<String>,
+        webhook: Webhook,
+        template: MessageTemplate,
+    ) -> Self {
+        self.routes.push(NotifyRoute {
+            pallet,
+            variant,
+            webhook,
+            template,
+        });
+        self
+    }
+
+    // The rate limiter of a webhook, shared by every message posted to it.
+    fn limiter(&self, webhook: &Webhook) -> Arc<RateLimiter> {
+        let (messages, period) = self
+            .rate_limit
+            .unwrap_or_else(|| webhook.default_rate_limit());
+        self.limiters
+            .lock()
+            .entry(webhook.url().to_owned())
+            .or_insert_with(|| Arc::new(RateLimiter::new(messages, period)))
+            .clone()
+    }
+}
+
+impl<T, Client> Handler<T, Client, EventDetails> for Notifier
+where
+    T: Config,
+{
+    fn handle(
+        &self,
+        ctx: EventContext<T, Client>,
+        event: EventDetails,
+    ) -> BoxFuture<'static, HandlerResult> {
+        let route = match self
+            .routes
+            .iter()
+            .find(|r| r.matches(event.pallet_name(), event.variant_name()))
+        {
+            Some(route) => route,
+            None => return future::ready(Ok(())).boxed(),
+        };
+        let message = route.template.render(&ctx, &event, &self.chain);
+        let payload = route.webhook.payload(&message);
+        let url = route.webhook.url().to_owned();
+        let limiter = self.limiter(&route.webhook);
+        let http = self.http.clone();
+        #[cfg(feature = "opentelemetry")]
+        let traceparent = super::telemetry::current_traceparent();
+        #[cfg(not(feature = "opentelemetry"))]
+        let traceparent: Option<String> = None;
+        async move {
+            post(&http, &limiter, &url, &payload, traceparent.as_deref()).await?;
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+// Post a message once the rate limit allows, waiting and trying again if the
+// service says to slow down.
+async fn post(
+    http: &reqwest::Client,
+    limiter: &RateLimiter,
+    url: &str,
+    payload: &serde_json::Value,
+    traceparent: Option<&str>,
+) -> Result<(), Error> {
+    for _ in 0..MAX_RATE_LIMITED_ATTEMPTS {
+        limiter.acquire().await;
+        let mut req = http.post(url).json(payload);
+        if let Some(traceparent) = traceparent {
+            req = req.header("traceparent", traceparent);
+        }
+        let res = req
+            .send()
+            .await
+            .map_err(|e| Error::Sink(e.to_string()))?;
+        if res.status() != reqwest::StatusCode::TOO_MANY_REQUESTS {
+            res.error_for_status()
+                .map_err(|e| Error::Sink(e.to_string()))?;
+            return Ok(())
+        }
+        let retry_after = res
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<f64>().ok())
+            .unwrap_or(1.0);
+        tracing::warn!(retry_after, "Webhook is rate limited; waiting to retry");
+        futures_timer::Delay::new(Duration::from_secs_f64(retry_after.max(0.0))).await;
+    }
+    Err(Error::Sink("Webhook is still rate limited".into()))
+}
+
+/// Lets at most a number of messages through in any period of time.
+#[derive(Debug)]
+struct RateLimiter {
+    messages: usize,
+    period: Duration,
+    sent: Mutex<VecDeque<Instant>>,
+}
+
+impl RateLimiter {
+    fn new(messages: usize, period: Duration) -> Self {
+        RateLimiter {
+            messages,
+            period,
+            sent: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    // Wait until a message can be sent, and count it as sent.
+    async fn acquire(&self) {
+        while let Some(wait) = self.try_acquire(Instant::now()) {
+            futures_timer::Delay::new(wait).await;
+        }
+    }
+
+    // Count a message as sent at the time given if the limit allows, or else hand
+    // back how long to wait before trying again.
+    fn try_acquire(&self, now: Instant) -> Option<Duration> {
+        let mut sent = self.sent.lock();
+        while matches!(sent.front(), Some(at) if now.duration_since(*at) >= self.period) {
+            sent.pop_front();
+        }
+        if sent.len() < self.messages {
+            sent.push_back(now);
+            return None
+        }
+        let oldest = *sent.front().expect("the limit is at least one; qed");
+        Some(self.period - now.duration_since(oldest))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn the_first_matching_route_is_used() {
+        let template = MessageTemplate::parse("{{ variant }}").unwrap();
+        let slack = Webhook::Slack("https://slack".into());
+        let discord = Webhook::Discord("https://discord".into());
+        let notifier = Notifier::new(ChainProperties::default())
+            .route("Balances", "Transfer", slack.clone(), template.clone())
+            .route_pallet("Balances", discord.clone(), template.clone())
+            .fallback(slack.clone(), template);
+
+        let route = |pallet, variant| {
+            notifier
+                .routes
+                .iter()
+                .find(|r| r.matches(pallet, variant))
+                .map(|r| r.webhook.clone())
+        };
+        assert_eq!(route("Balances", "Transfer"), Some(slack.clone()));
+        assert_eq!(route("Balances", "Deposit"), Some(discord.clone()));
+        assert_eq!(route("System", "Remarked"), Some(slack));
+
+        assert_eq!(
+            discord.payload("hi"),
+            serde_json::json!({ "content": "hi" })
+        );
+    }
+
+    #[test]
+    fn messages_wait_for_the_rate_limit() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(10));
+        let start = Instant::now();
+        assert_eq!(limiter.try_acquire(start), None);
+        assert_eq!(limiter.try_acquire(start + Duration::from_secs(4)), None);
+        assert_eq!(
+            limiter.try_acquire(start + Duration::from_secs(6)),
+            Some(Duration::from_secs(4))
+        );
+        assert_eq!(limiter.try_acquire(start + Duration::from_secs(10)), None);
+    }
+}
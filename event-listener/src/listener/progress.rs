@@ -0,0 +1,159 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Keeping track of how far behind the chain a listener is.
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Keeps track of how far an [`super::EventListener`] has got (its watermark),
+/// compared with the best and finalized heads of the chain, so that operators
+/// can tell when it falls behind. Hand it to the listener with
+/// [`super::EventListenerBuilder::progress()`], which then also follows the heads
+/// of the chain.
+///
+/// With the `metrics` feature, these are also reported as gauges through the
+/// [`metrics`](https://docs.rs/metrics) crate, labelled with the chain
+/// identifier of the listener (or `unknown`):
+///
+/// - `event_listener_processed_block`
+/// - `event_listener_best_block`
+/// - `event_listener_finalized_block`
+/// - `event_listener_best_lag_blocks`
+/// - `event_listener_finalized_lag_blocks`
+///
+/// ```no_run
+/// use event_listener::{
+///     listener::Progress,
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let progress = Progress::new();
+/// let listener = api.listener().progress(progress.clone()).build();
+/// tokio::spawn(async move { listener.run().await });
+///
+/// // Later on:
+/// if progress.watermark().finalized_lag() > Some(100) {
+///     eprintln!("Listener is falling behind");
+/// }
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Progress(Arc<Mutex<State>>);
+
+#[derive(Debug, Default)]
+struct State {
+    chain: Option<Arc<str>>,
+    watermark: Watermark,
+}
+
+/// How far a listener has got, compared with the heads of the chain. Block
+/// numbers are `None` until they're first known.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Watermark {
+    /// The number of the last block the listener finished with.
+    pub processed: Option<u64>,
+    /// The number of the best block of the chain.
+    pub best: Option<u64>,
+    /// The number of the last finalized block of the chain.
+    pub finalized: Option<u64>,
+}
+
+impl Watermark {
+    /// How many blocks the listener is behind the best block.
+    pub fn best_lag(&self) -> Option<u64> {
+        Some(self.best?.saturating_sub(self.processed?))
+    }
+
+    /// How many blocks the listener is behind the last finalized block. When
+    /// following best blocks, this is zero once the listener is ahead of it.
+    pub fn finalized_lag(&self) -> Option<u64> {
+        Some(self.finalized?.saturating_sub(self.processed?))
+    }
+}
+
+impl Progress {
+    /// Start off knowing nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How far the listener has got, and the heads of the chain, as of now.
+    pub fn watermark(&self) -> Watermark {
+        self.0.lock().watermark.clone()
+    }
+
+    /// Label metrics with the chain identifier given.
+    pub(crate) fn set_chain(&self, chain: Option<Arc<str>>) {
+        self.0.lock().chain = chain;
+    }
+
+    /// Note that the listener has finished with a block.
+    pub(crate) fn processed(&self, number: u64) {
+        self.update(|w| w.processed = Some(number))
+    }
+
+    /// Note a new best block.
+    pub(crate) fn best(&self, number: u64) {
+        self.update(|w| w.best = Some(number))
+    }
+
+    /// Note a new finalized block.
+    pub(crate) fn finalized(&self, number: u64) {
+        self.update(|w| w.finalized = Some(number))
+    }
+
+    fn update(&self, f: impl FnOnce(&mut Watermark)) {
+        let mut state = self.0.lock();
+        f(&mut state.watermark);
+        #[cfg(feature = "metrics")]
+        report(&state);
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn report(state: &State) {
+    let chain = state.chain.as_deref().unwrap_or("unknown").to_owned();
+    let w = &state.watermark;
+    let gauges = [
+        ("event_listener_processed_block", w.processed),
+        ("event_listener_best_block", w.best),
+        ("event_listener_finalized_block", w.finalized),
+        ("event_listener_best_lag_blocks", w.best_lag()),
+        ("event_listener_finalized_lag_blocks", w.finalized_lag()),
+    ];
+    for (name, value) in gauges {
+        if let Some(value) = value {
+            metrics::gauge!(name, value as f64, "chain" => chain.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lag_is_worked_out_from_the_heads() {
+        let progress = Progress::new();
+        progress.best(12);
+        progress.finalized(10);
+        assert_eq!(progress.watermark().finalized_lag(), None);
+
+        progress.processed(7);
+        let watermark = progress.watermark();
+        assert_eq!(watermark.best_lag(), Some(5));
+        assert_eq!(watermark.finalized_lag(), Some(3));
+
+        // Following best blocks can put the listener ahead of finalization.
+        progress.processed(11);
+        assert_eq!(progress.watermark().finalized_lag(), Some(0));
+    }
+}
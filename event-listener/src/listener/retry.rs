@@ -0,0 +1,188 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Trying handlers again when they fail.
+
+use super::handler::HandlerError;
+use crate::error::Error;
+use std::{
+    sync::Arc,
+    time::Duration,
+};
+
+/// What to do about a handler failure, as decided by the classifier of a
+/// [`RetryPolicy`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RetryDecision {
+    /// Try the handler again, if it has attempts left.
+    Retry,
+    /// Give up on the event straight away, sending it to the dead letter sink if
+    /// there is one.
+    DeadLetter,
+}
+
+type Classifier = Arc<dyn Fn(&HandlerError) -> RetryDecision + Send + Sync>;
+
+/// How many times to try a handler before giving up on an event, and how long
+/// to wait between attempts.
+///
+/// # Example
+///
+/// ```
+/// use event_listener::listener::{ RetryDecision, RetryPolicy };
+/// use std::time::Duration;
+///
+/// // Try up to 5 times, waiting 200ms, 400ms, 800ms and then 1s between attempts,
+/// // but give up straight away on anything that isn't an I/O error.
+/// let policy = RetryPolicy::exponential(5)
+///     .initial_backoff(Duration::from_millis(200))
+///     .max_backoff(Duration::from_secs(1))
+///     .classify(|e| {
+///         if e.is::<std::io::Error>() {
+///             RetryDecision::Retry
+///         } else {
+///             RetryDecision::DeadLetter
+///         }
+///     });
+/// ```
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: f64,
+    classify: Classifier,
+}
+
+impl std::fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("multiplier", &self.multiplier)
+            .finish()
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::none()
+    }
+}
+
+impl RetryPolicy {
+    /// Never try a handler more than once.
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+            multiplier: 1.0,
+            classify: Arc::new(default_classify),
+        }
+    }
+
+    /// Try a handler up to `max_attempts` times in total, doubling the time waited
+    /// between attempts each time, from 100ms up to at most 30s.
+    ///
+    /// By default, every failure is retried except for [`crate::Error`]s which are
+    /// [decoding errors](crate::Error::is_decoding()), since trying again won't
+    /// help with those. Use [`RetryPolicy::classify()`] to change this.
+    pub fn exponential(max_attempts: u32) -> Self {
+        RetryPolicy {
+            max_attempts: max_attempts.max(1),
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            classify: Arc::new(default_classify),
+        }
+    }
+
+    /// How long to wait after the first failed attempt.
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// The longest to wait between attempts.
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// What to multiply the time waited by after each failed attempt.
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// Decide whether a failure is worth retrying, or whether to give up on the
+    /// event straight away.
+    pub fn classify(
+        mut self,
+        classify: impl Fn(&HandlerError) -> RetryDecision + Send + Sync + 'static,
+    ) -> Self {
+        self.classify = Arc::new(classify);
+        self
+    }
+
+    /// The most times that a handler will be tried.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// How long to wait after the given number of failed attempts.
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let exp = attempts.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let secs = self.initial_backoff.as_secs_f64() * self.multiplier.powi(exp);
+        let max_secs = self.max_backoff.as_secs_f64().max(self.initial_backoff.as_secs_f64());
+        Duration::from_secs_f64(secs.min(max_secs))
+    }
+
+    /// Should the handler be tried again, having failed `attempts` times so far
+    /// with the error given?
+    pub fn should_retry(&self, attempts: u32, error: &HandlerError) -> bool {
+        attempts < self.max_attempts && (self.classify)(error) == RetryDecision::Retry
+    }
+}
+
+fn default_classify(error: &HandlerError) -> RetryDecision {
+    match error.downcast_ref::<Error>() {
+        Some(e) if e.is_decoding() => RetryDecision::DeadLetter,
+        _ => RetryDecision::Retry,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_up_to_the_max() {
+        let policy = RetryPolicy::exponential(10)
+            .initial_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_millis(500));
+        let backoffs: Vec<_> = (1..=5).map(|n| policy.backoff(n).as_millis()).collect();
+        assert_eq!(backoffs, vec![100, 200, 400, 500, 500]);
+    }
+
+    #[test]
+    fn retries_are_limited_and_classified() {
+        let policy = RetryPolicy::exponential(3);
+        let err: HandlerError = "oops".into();
+        assert!(policy.should_retry(1, &err));
+        assert!(policy.should_retry(2, &err));
+        assert!(!policy.should_retry(3, &err));
+
+        // Decoding errors aren't retried by default.
+        let err: HandlerError = Box::new(Error::Codec("bad bytes".into()));
+        assert!(!policy.should_retry(1, &err));
+
+        let policy = policy.classify(|_| RetryDecision::DeadLetter);
+        assert!(!policy.should_retry(1, &"oops".into()));
+
+        assert!(!RetryPolicy::none().should_retry(1, &"oops".into()));
+    }
+}
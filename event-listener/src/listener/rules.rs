@@ -0,0 +1,644 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Describing which events to handle in a configuration file, rather than in code.
+
+use super::handler::{
+    ErasedHandler,
+    EventContext,
+    Handler,
+    HandlerResult,
+};
+use crate::{
+    config::from_ss58,
+    error::Error,
+    events::EventDetails,
+    json::value_to_json,
+    utils::{
+        composite_values,
+        value_as_bytes,
+    },
+    Config,
+    Metadata,
+};
+use futures::future::BoxFuture;
+use scale_value::{
+    scale::TypeId,
+    Composite,
+    Primitive,
+    Value,
+    ValueDef,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    path::Path,
+    sync::Arc,
+};
+
+/// A set of rules, each saying which events to match and which handler (or
+/// "route") to hand them to. Rules can be loaded from JSON, or from YAML or TOML
+/// with the `yaml` and `toml` features, so that what is listened for can be
+/// changed without recompiling. Hand them to
+/// [`super::EventListenerBuilder::rules()`] along with the [`Routes`] they refer to.
+///
+/// # Example
+///
+/// ```yaml
+/// rules:
+///   - name: large-transfers
+///     pallet: Balances
+///     event: Transfer
+///     where:
+///       - field: amount
+///         gte: "1000000000000000"
+///     route: alert
+///   - name: treasury-activity
+///     pallet: Treasury
+///     route: archive
+/// ```
+///
+/// Each condition names a `field` of the event (with `.` to look inside nested
+/// fields, such as `dest.Id`) and one comparison: `eq`, `ne`, `gt`, `gte`, `lt`,
+/// `lte`, `in` or `exists`. Numbers bigger than JSON allows can be given as
+/// strings, and account IDs and other byte arrays as `0x` prefixed hex or as
+/// SS58 addresses. Other values are compared in the form that [`crate::json`]
+/// writes them.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleSet {
+    /// The rules, each of which is registered as a handler of its own.
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+/// Which events to match, and where to route them. See [`RuleSet`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// A name for the rule, used when logging.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Only match events from the pallet with this name. Any pallet if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pallet: Option<String>,
+    /// Only match events with this name. Any event if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event: Option<String>,
+    /// Only match events whose fields meet all of these conditions.
+    #[serde(default, rename = "where", skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<Condition>,
+    /// The name of the route to hand matching events to.
+    pub route: String,
+}
+
+/// A condition on one field of an event. See [`RuleSet`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Condition {
+    /// The name of the field, or a `.` separated path to a nested field. Unnamed
+    /// fields are given by their position.
+    pub field: String,
+    /// What the field is compared with.
+    #[serde(flatten)]
+    pub comparison: Comparison,
+}
+
+/// How a field is compared in a [`Condition`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Comparison {
+    /// The field is equal to this value.
+    Eq(serde_json::Value),
+    /// The field is not equal to this value.
+    Ne(serde_json::Value),
+    /// The field is a number greater than this one.
+    Gt(serde_json::Value),
+    /// The field is a number greater than or equal to this one.
+    Gte(serde_json::Value),
+    /// The field is a number less than this one.
+    Lt(serde_json::Value),
+    /// The field is a number less than or equal to this one.
+    Lte(serde_json::Value),
+    /// The field is equal to one of these values.
+    In(Vec<serde_json::Value>),
+    /// The event has (or, if `false`, doesn't have) the field.
+    Exists(bool),
+}
+
+impl RuleSet {
+    /// Parse rules from JSON.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        serde_json::from_str(json).map_err(|e| Error::Rules(e.to_string()))
+    }
+
+    /// Parse rules from YAML.
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml(yaml: &str) -> Result<Self, Error> {
+        serde_yaml::from_str(yaml).map_err(|e| Error::Rules(e.to_string()))
+    }
+
+    /// Parse rules from TOML, where each rule is a `[[rules]]` table.
+    #[cfg(feature = "toml")]
+    pub fn from_toml(toml: &str) -> Result<Self, Error> {
+        toml::from_str(toml).map_err(|e| Error::Rules(e.to_string()))
+    }
+
+    /// Load rules from a file, working out its format from its extension:
+    /// `.json`, `.yaml` or `.yml`, or `.toml`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::from_json(&contents),
+            #[cfg(feature = "yaml")]
+            Some("yaml" | "yml") => Self::from_yaml(&contents),
+            #[cfg(feature = "toml")]
+            Some("toml") => Self::from_toml(&contents),
+            _ => {
+                Err(Error::Rules(format!(
+                    "Can't tell the format of {} from its extension",
+                    path.display()
+                )))
+            }
+        }
+    }
+
+    /// Check each rule and turn it into a handler which hands the events that
+    /// match it to its route.
+    pub(crate) fn compile<T: Config, Client>(
+        &self,
+        routes: &Routes<T, Client>,
+    ) -> Result<Vec<RuleHandler<T, Client>>, Error> {
+        self.rules
+            .iter()
+            .enumerate()
+            .map(|(idx, rule)| {
+                let name = rule.name.clone().unwrap_or_else(|| format!("#{}", idx));
+                let handler = routes.handlers.get(&rule.route).cloned().ok_or_else(|| {
+                    Error::Rules(format!("Rule {} has unknown route '{}'", name, rule.route))
+                })?;
+                let conditions = rule
+                    .conditions
+                    .iter()
+                    .map(|c| {
+                        CompiledCondition::new(c).map_err(|e| {
+                            Error::Rules(format!(
+                                "Rule {} has a bad condition on '{}': {}",
+                                name, c.field, e
+                            ))
+                        })
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(RuleHandler {
+                    name,
+                    pallet: rule.pallet.clone(),
+                    variant: rule.event.clone(),
+                    conditions,
+                    handler,
+                })
+            })
+            .collect()
+    }
+}
+
+type DynHandler<T, Client> = Arc<dyn Handler<T, Client, EventDetails>>;
+
+/// The handlers that the rules of a [`RuleSet`] can route events to, by name.
+///
+/// ```no_run
+/// use event_listener::{
+///     events::EventDetails,
+///     listener::{ EventContext, HandlerResult, Routes, RuleSet },
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+///
+/// type Ctx = EventContext<PolkadotConfig, OnlineClient<PolkadotConfig>>;
+///
+/// async fn alert(ctx: Ctx, event: EventDetails) -> HandlerResult { Ok(()) }
+/// async fn archive(ctx: Ctx, event: EventDetails) -> HandlerResult { Ok(()) }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let routes = Routes::new().route("alert", alert).route("archive", archive);
+///
+/// api.listener()
+///     .rules(&RuleSet::load("rules.json").unwrap(), &routes)
+///     .unwrap()
+///     .run()
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+pub struct Routes<T: Config, Client> {
+    handlers: HashMap<String, DynHandler<T, Client>>,
+}
+
+impl<T: Config, Client> std::fmt::Debug for Routes<T, Client> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Routes")
+            .field("names", &self.handlers.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<T: Config, Client> Default for Routes<T, Client> {
+    fn default() -> Self {
+        Routes {
+            handlers: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Config, Client> Routes<T, Client> {
+    /// Create an empty set of routes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a route with the name given. Rules routed here share the handler.
+    pub fn route(
+        mut self,
+        name: impl Into<String>,
+        handler: impl Handler<T, Client, EventDetails>,
+    ) -> Self {
+        self.handlers.insert(name.into(), Arc::new(handler));
+        self
+    }
+}
+
+/// A [`Rule`], checked and ready to match events with.
+pub(crate) struct RuleHandler<T: Config, Client> {
+    name: String,
+    pallet: Option<String>,
+    variant: Option<String>,
+    conditions: Vec<CompiledCondition>,
+    handler: DynHandler<T, Client>,
+}
+
+impl<T: Config, Client> ErasedHandler<T, Client> for RuleHandler<T, Client> {
+    fn matches(&self, pallet: &str, variant: &str) -> bool {
+        self.pallet.as_deref().map_or(true, |p| p == pallet)
+            && self.variant.as_deref().map_or(true, |v| v == variant)
+    }
+
+    fn accepts(&self, ctx: &EventContext<T, Client>) -> bool {
+        self.conditions
+            .iter()
+            .all(|c| c.test(lookup(ctx.field_values(), &c.path), ctx.metadata()))
+    }
+
+    fn handle(
+        &self,
+        ctx: EventContext<T, Client>,
+        event: &EventDetails,
+    ) -> BoxFuture<'static, HandlerResult> {
+        tracing::trace!(
+            "Rule {} matched {}::{}",
+            self.name,
+            ctx.pallet_name(),
+            ctx.variant_name()
+        );
+        self.handler.handle(ctx, event.clone())
+    }
+}
+
+struct CompiledCondition {
+    path: Vec<String>,
+    test: Test,
+}
+
+enum Test {
+    Eq(serde_json::Value),
+    Ne(serde_json::Value),
+    In(Vec<serde_json::Value>),
+    Exists(bool),
+    Compare(fn(Ordering) -> bool, Number),
+}
+
+impl CompiledCondition {
+    fn new(condition: &Condition) -> Result<Self, String> {
+        let bound = |value: &serde_json::Value| {
+            Number::from_json(value).ok_or_else(|| format!("{} isn't a whole number", value))
+        };
+        let test = match &condition.comparison {
+            Comparison::Eq(value) => Test::Eq(value.clone()),
+            Comparison::Ne(value) => Test::Ne(value.clone()),
+            Comparison::In(values) => Test::In(values.clone()),
+            Comparison::Exists(exists) => Test::Exists(*exists),
+            Comparison::Gt(value) => Test::Compare(Ordering::is_gt, bound(value)?),
+            Comparison::Gte(value) => Test::Compare(Ordering::is_ge, bound(value)?),
+            Comparison::Lt(value) => Test::Compare(Ordering::is_lt, bound(value)?),
+            Comparison::Lte(value) => Test::Compare(Ordering::is_le, bound(value)?),
+        };
+        let path: Vec<String> = condition.field.split('.').map(ToOwned::to_owned).collect();
+        if path.iter().any(|segment| segment.is_empty()) {
+            return Err("the field name is empty".into())
+        }
+        Ok(CompiledCondition { path, test })
+    }
+
+    fn test(&self, value: Option<&Value<TypeId>>, metadata: &Metadata) -> bool {
+        match (&self.test, value) {
+            (Test::Exists(exists), value) => value.is_some() == *exists,
+            (_, None) => false,
+            (Test::Eq(expected), Some(value)) => value_eq(value, expected, metadata),
+            (Test::Ne(expected), Some(value)) => !value_eq(value, expected, metadata),
+            (Test::In(expected), Some(value)) => {
+                expected.iter().any(|e| value_eq(value, e, metadata))
+            }
+            (Test::Compare(is, bound), Some(value)) => {
+                Number::from_value(value).map_or(false, |n| is(n.cmp(bound)))
+            }
+        }
+    }
+}
+
+/// Find a field by its path. A path segment naming the variant of an enum with a
+/// single field (such as `Id` in `dest.Id`) steps into that field.
+pub(super) fn lookup<'a>(
+    fields: &'a Composite<TypeId>,
+    path: &[String],
+) -> Option<&'a Value<TypeId>> {
+    let (first, rest) = path.split_first()?;
+    let mut value = field_of(fields, first)?;
+    for segment in rest {
+        value = match &value.value {
+            ValueDef::Composite(c) => field_of(c, segment)?,
+            ValueDef::Variant(v) if v.name == *segment => {
+                let mut vals = composite_values(&v.values);
+                match (vals.next(), vals.next()) {
+                    (Some(v), None) => v,
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+fn field_of<'a>(fields: &'a Composite<TypeId>, name: &str) -> Option<&'a Value<TypeId>> {
+    match fields {
+        Composite::Named(vals) => vals.iter().find(|(n, _)| n == name).map(|(_, v)| v),
+        Composite::Unnamed(vals) => name.parse::<usize>().ok().and_then(|idx| vals.get(idx)),
+    }
+}
+
+/// Is the field equal to the value from the rules? Numbers, strings, booleans,
+/// enum variants (by name) and byte arrays (as hex or SS58) are compared
+/// directly; anything else is compared as JSON, in the form that [`crate::json`]
+/// writes it.
+fn value_eq(value: &Value<TypeId>, expected: &serde_json::Value, metadata: &Metadata) -> bool {
+    if let (Some(n), Some(expected)) = (Number::from_value(value), Number::from_json(expected)) {
+        return n == expected
+    }
+    match (&value.value, expected) {
+        (ValueDef::Primitive(Primitive::Bool(b)), serde_json::Value::Bool(expected)) => {
+            return b == expected
+        }
+        (ValueDef::Primitive(Primitive::String(s)), serde_json::Value::String(expected)) => {
+            return s == expected
+        }
+        (ValueDef::Primitive(Primitive::Char(c)), serde_json::Value::String(expected)) => {
+            return expected.chars().eq(std::iter::once(*c))
+        }
+        (ValueDef::Variant(v), serde_json::Value::String(expected)) => return v.name == *expected,
+        _ => {}
+    }
+    if let (Some(bytes), serde_json::Value::String(expected)) = (value_as_bytes(value), expected) {
+        let expected = match expected.strip_prefix("0x") {
+            Some(hex) => hex::decode(hex).ok(),
+            None => from_ss58(expected).ok().map(|(_, bytes)| bytes),
+        };
+        return expected.map_or(false, |expected| bytes == expected)
+    }
+    value_to_json(value, metadata) == *expected
+}
+
+/// A whole number from the rules or from an event, which may be too big to fit in
+/// an `i128` or too small to fit in a `u128`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Number {
+    /// Not negative.
+    Unsigned(u128),
+    /// Negative.
+    Negative(i128),
+}
+
+impl Number {
+    fn signed(n: i128) -> Self {
+        match u128::try_from(n) {
+            Ok(n) => Number::Unsigned(n),
+            Err(_) => Number::Negative(n),
+        }
+    }
+
+    fn from_json(value: &serde_json::Value) -> Option<Self> {
+        match value {
+            serde_json::Value::Number(n) => {
+                n.as_u64()
+                    .map(|n| Number::Unsigned(n.into()))
+                    .or_else(|| n.as_i64().map(|n| Number::signed(n.into())))
+            }
+            serde_json::Value::String(s) => {
+                s.parse::<u128>()
+                    .map(Number::Unsigned)
+                    .or_else(|_| s.parse::<i128>().map(Number::signed))
+                    .ok()
+            }
+            _ => None,
+        }
+    }
+
+    // Numbers are often wrapped in single field structs, so look through those.
+    fn from_value(value: &Value<TypeId>) -> Option<Self> {
+        match &value.value {
+            ValueDef::Primitive(Primitive::U128(n)) => Some(Number::Unsigned(*n)),
+            ValueDef::Primitive(Primitive::I128(n)) => Some(Number::signed(*n)),
+            ValueDef::Composite(c) => {
+                let mut vals = composite_values(c);
+                match (vals.next(), vals.next()) {
+                    (Some(v), None) => Number::from_value(v),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Number {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Number::Unsigned(a), Number::Unsigned(b)) => a.cmp(b),
+            (Number::Negative(a), Number::Negative(b)) => a.cmp(b),
+            (Number::Unsigned(_), Number::Negative(_)) => Ordering::Greater,
+            (Number::Negative(_), Number::Unsigned(_)) => Ordering::Less,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        config::to_ss58,
+        events::{
+            test_utils::{
+                event_record,
+                events,
+                metadata,
+                EventRecord,
+            },
+            Phase,
+        },
+        listener::handler::BlockContext,
+        utils::to_hex,
+        SubstrateConfig,
+    };
+    use codec::{
+        Decode,
+        Encode,
+    };
+    use scale_info::TypeInfo;
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Event {
+        Transfer { from: [u8; 32], amount: u128 },
+        Note(bool),
+        Split((u128, u8)),
+    }
+
+    type Ctx = EventContext<SubstrateConfig, ()>;
+
+    fn noop(_ctx: Ctx, _event: EventDetails) -> futures::future::Ready<HandlerResult> {
+        futures::future::ready(Ok(()))
+    }
+
+    fn compile(json: &str) -> Result<Vec<RuleHandler<SubstrateConfig, ()>>, Error> {
+        let routes = Routes::new().route("alert", noop);
+        RuleSet::from_json(json)?.compile(&routes)
+    }
+
+    // Which of the rules accept each of the test events.
+    fn accepted(rules: &[RuleHandler<SubstrateConfig, ()>]) -> Vec<Vec<usize>> {
+        accepted_of(
+            rules,
+            vec![
+                event_record(
+                    Phase::ApplyExtrinsic(0),
+                    Event::Transfer {
+                        from: [1; 32],
+                        amount: 500,
+                    },
+                ),
+                event_record(
+                    Phase::ApplyExtrinsic(1),
+                    Event::Transfer {
+                        from: [2; 32],
+                        amount: 5_000,
+                    },
+                ),
+                event_record(Phase::Finalization, Event::Note(true)),
+            ],
+        )
+    }
+
+    // Which of the rules accept each of the events given.
+    fn accepted_of(
+        rules: &[RuleHandler<SubstrateConfig, ()>],
+        records: Vec<EventRecord<Event>>,
+    ) -> Vec<Vec<usize>> {
+        events(metadata::<Event>(), records)
+            .iter()
+            .map(|event| {
+                let event = event.unwrap();
+                let block = BlockContext {
+                    hash: Default::default(),
+                    number: 1,
+                    timestamp: None,
+                    chain: None,
+                };
+                let ctx = Ctx::new((), block, &event);
+                rules
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, r)| {
+                        r.matches(event.pallet_name(), event.variant_name()) && r.accepts(&ctx)
+                    })
+                    .map(|(idx, _)| idx)
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rules_match_events_by_name_and_fields() {
+        let rules = compile(&format!(
+            r#"{{ "rules": [
+                {{ "pallet": "Test", "route": "alert" }},
+                {{ "event": "Transfer", "where": [{{ "field": "amount", "gte": 1000 }}], "route": "alert" }},
+                {{ "event": "Transfer", "where": [{{ "field": "amount", "lt": "1000" }}], "route": "alert" }},
+                {{ "where": [{{ "field": "from", "eq": "{}" }}], "route": "alert" }},
+                {{ "where": [{{ "field": "from", "in": ["{}"] }}], "route": "alert" }},
+                {{ "where": [{{ "field": "0", "eq": true }}], "route": "alert" }},
+                {{ "where": [{{ "field": "amount", "exists": false }}], "route": "alert" }},
+                {{ "pallet": "Other", "route": "alert" }}
+            ] }}"#,
+            to_hex([1u8; 32]),
+            to_ss58(&[2; 32], 42),
+        ))
+        .unwrap();
+
+        assert_eq!(
+            accepted(&rules),
+            vec![vec![0, 2, 3], vec![0, 1, 4], vec![0, 5, 6]]
+        );
+    }
+
+    #[test]
+    fn big_integers_are_compared_inside_other_values() {
+        let rules = compile(&format!(
+            r#"{{ "rules": [
+                {{ "where": [{{ "field": "0", "eq": ["{max}", 1] }}], "route": "alert" }},
+                {{ "where": [{{ "field": "0", "in": [["{max}", 2], ["{max}", 1]] }}], "route": "alert" }},
+                {{ "where": [{{ "field": "0", "ne": ["{max}", 1] }}], "route": "alert" }}
+            ] }}"#,
+            max = u128::MAX,
+        ))
+        .unwrap();
+
+        let split = Event::Split((u128::MAX, 1));
+        assert_eq!(
+            accepted_of(&rules, vec![event_record(Phase::Finalization, split)]),
+            vec![vec![0, 1]]
+        );
+    }
+
+    #[test]
+    fn bad_rules_are_rejected() {
+        let err = compile(r#"{ "rules": [{ "route": "nowhere" }] }"#).unwrap_err();
+        assert!(err.to_string().contains("unknown route 'nowhere'"), "{}", err);
+
+        let err = compile(
+            r#"{ "rules": [{ "name": "big", "where": [{ "field": "amount", "gt": 1.5 }], "route": "alert" }] }"#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Rule big has a bad condition"), "{}", err);
+
+        assert!(compile(r#"{ "rules": [{ "route": "alert", "typo": 1 }] }"#).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Reporting listener failures to Sentry.
+//!
+//! With the `sentry` feature, listeners report handler failures, the errors they
+//! stop with (such as blocks or events which can't be decoded) and repeated
+//! failures to reconnect to a chain to Sentry, tagged with the chain, block and
+//! event they happened at. Reports go to whichever Sentry client the application
+//! has set up with `sentry::init()`; without one, nothing is reported.
+
+use super::handler::{
+    EventContext,
+    HandlerError,
+};
+use crate::{
+    error::Error,
+    utils::to_hex,
+    Config,
+};
+use codec::Encode;
+use sentry_core::{
+    protocol::{
+        Context,
+        Level,
+    },
+    Scope,
+};
+
+/// How many times in a row reconnecting to a chain fails before it's reported.
+pub const REPORT_RECONNECTS_AFTER: u32 = 3;
+
+/// Report a handler failure to Sentry, along with the event it failed on. This
+/// is what listeners do by default when the `sentry` feature is enabled; call it
+/// from [`super::EventListenerBuilder::on_error()`] callbacks to carry on doing so.
+pub fn report_handler_error<T: Config, Client>(
+    ctx: &EventContext<T, Client>,
+    e: &HandlerError,
+) {
+    sentry_core::with_scope(
+        |scope| {
+            scope.set_tag("chain", ctx.chain_id().unwrap_or("unknown"));
+            scope.set_tag("pallet", ctx.pallet_name());
+            scope.set_tag("variant", ctx.variant_name());
+            let block_number: u64 = ctx.block_number().into();
+            scope.set_tag("block_number", block_number);
+            scope.set_context(
+                "event",
+                context([
+                    (
+                        "block_hash",
+                        to_hex(ctx.block_hash().encode()).into(),
+                    ),
+                    ("event_index", ctx.event_index().into()),
+                    ("extrinsic_index", ctx.extrinsic_index().into()),
+                    ("fields", ctx.fields_json()),
+                ]),
+            );
+        },
+        || sentry_core::capture_error(&**e),
+    );
+}
+
+/// Report the error that a listener stopped with.
+pub(crate) fn report_listener_error(chain: Option<&str>, e: &Error) {
+    sentry_core::with_scope(
+        |scope| set_chain(scope, chain),
+        || sentry_core::capture_error(e),
+    );
+}
+
+/// Report that reconnecting to a chain has failed several times in a row.
+pub(crate) fn report_reconnects(chain: &str, attempts: u32, e: &Error) {
+    if attempts < REPORT_RECONNECTS_AFTER {
+        return
+    }
+    sentry_core::with_scope(
+        |scope| {
+            set_chain(scope, Some(chain));
+            scope.set_level(Some(Level::Warning));
+            scope.set_context(
+                "reconnect",
+                context([
+                    ("attempts", attempts.into()),
+                    ("error", e.to_string().into()),
+                ]),
+            );
+        },
+        || {
+            sentry_core::capture_message(
+                &format!("Failed to reconnect to chain {} {} times", chain, attempts),
+                Level::Warning,
+            )
+        },
+    );
+}
+
+fn set_chain(scope: &mut Scope, chain: Option<&str>) {
+    scope.set_tag("chain", chain.unwrap_or("unknown"));
+}
+
+fn context<const N: usize>(values: [(&str, serde_json::Value); N]) -> Context {
+    Context::Other(
+        values
+            .into_iter()
+            .map(|(key, value)| (key.to_owned(), value))
+            .collect(),
+    )
+}
@@ -0,0 +1,117 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Stopping a listener without losing track of the events it was handling.
+
+use futures::{
+    channel::oneshot,
+    future::Shared,
+    FutureExt,
+};
+use parking_lot::Mutex;
+use std::{
+    future::Future,
+    sync::Arc,
+};
+
+/// A handle to stop an [`super::EventListener`] gracefully with. Get one from
+/// [`super::EventListener::shutdown_handle()`].
+///
+/// Once shutdown has been asked for, the listener stops taking on new blocks,
+/// finishes handling the events of the blocks it had already taken on (waiting
+/// at most as long as the [drain timeout](super::EventListenerBuilder::drain_timeout())),
+/// saves its checkpoint, unsubscribes from blocks, and then returns from
+/// [`super::EventListener::run()`].
+#[derive(Clone)]
+pub struct ShutdownHandle(Arc<ShutdownInner>);
+
+struct ShutdownInner {
+    stop_tx: Mutex<Option<oneshot::Sender<()>>>,
+    done_rx: Shared<oneshot::Receiver<()>>,
+}
+
+impl std::fmt::Debug for ShutdownHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ShutdownHandle")
+            .field("shutting_down", &self.is_shutting_down())
+            .finish()
+    }
+}
+
+impl ShutdownHandle {
+    /// Ask the listener to stop. The future handed back resolves once the
+    /// listener has stopped; it doesn't need to be polled for the listener to
+    /// stop.
+    pub fn shutdown(&self) -> impl Future<Output = ()> + Send + 'static {
+        if let Some(stop_tx) = self.0.stop_tx.lock().take() {
+            let _ = stop_tx.send(());
+        }
+        // The sender is dropped once the listener has stopped.
+        self.0.done_rx.clone().map(|_| ())
+    }
+
+    /// Has the listener been asked to stop?
+    pub fn is_shutting_down(&self) -> bool {
+        self.0.stop_tx.lock().is_none()
+    }
+}
+
+/// The listener's side of a [`ShutdownHandle`].
+#[derive(Debug)]
+pub(crate) struct Shutdown {
+    handle: ShutdownHandle,
+    stop_rx: Mutex<Option<oneshot::Receiver<()>>>,
+    done_tx: Mutex<Option<oneshot::Sender<()>>>,
+}
+
+impl Shutdown {
+    pub(crate) fn new() -> Self {
+        let (stop_tx, stop_rx) = oneshot::channel();
+        let (done_tx, done_rx) = oneshot::channel();
+        Shutdown {
+            handle: ShutdownHandle(Arc::new(ShutdownInner {
+                stop_tx: Mutex::new(Some(stop_tx)),
+                done_rx: done_rx.shared(),
+            })),
+            stop_rx: Mutex::new(Some(stop_rx)),
+            done_tx: Mutex::new(Some(done_tx)),
+        }
+    }
+
+    pub(crate) fn handle(&self) -> ShutdownHandle {
+        self.handle.clone()
+    }
+
+    /// Take the signals for the listener to watch. The first resolves when a
+    /// shutdown is asked for, and dropping the second tells the handles that the
+    /// listener has stopped. Only the first run of a listener can be shut down.
+    pub(crate) fn take(&self) -> Option<(oneshot::Receiver<()>, oneshot::Sender<()>)> {
+        let stop_rx = self.stop_rx.lock().take()?;
+        let done_tx = self.done_tx.lock().take()?;
+        Some((stop_rx, done_tx))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn shutdown_resolves_once_the_listener_stops() {
+        let shutdown = Shutdown::new();
+        let handle = shutdown.handle();
+        let (stop_rx, done_tx) = shutdown.take().unwrap();
+        assert!(shutdown.take().is_none());
+
+        assert!(!handle.is_shutting_down());
+        let stopped = handle.shutdown();
+        assert!(handle.is_shutting_down());
+        stop_rx.await.unwrap();
+
+        drop(done_tx);
+        stopped.await;
+        // Asking again once stopped resolves straight away.
+        handle.shutdown().await;
+    }
+}
@@ -0,0 +1,266 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use super::{
+    EventRecord,
+    EventSink,
+};
+use crate::error::Error;
+use futures::{
+    future::BoxFuture,
+    FutureExt,
+};
+
+/// The most messages that SQS and SNS accept in a single batch.
+const MAX_BATCH: usize = 10;
+
+/// The message group of events sent to FIFO queues and topics, so that they're
+/// delivered in the order they were sent.
+const MESSAGE_GROUP: &str = "events";
+
+/// An [`EventSink`] which sends events as JSON messages to an Amazon SQS queue,
+/// in batches of up to ten.
+///
+/// Each message has the attributes `pallet`, `variant` and `blockNumber` (and
+/// `chain`, if the listener was given one), so that consumers such as Lambda
+/// functions can filter on them without parsing the message. For FIFO queues
+/// (see [`SqsSink::fifo()`]), the [ID of the event](EventRecord::id()) is used to
+/// deduplicate messages, such as when the listener carries on from a checkpoint
+/// after a restart.
+///
+/// The SQS client is set up by the application, usually with `aws-config`:
+///
+/// ```no_run
+/// use event_listener::{
+///     listener::{ SinkHandler, SqsSink },
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+/// use std::time::Duration;
+///
+/// # async fn run(sqs: aws_sdk_sqs::Client) {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let sink = SqsSink::new(sqs, "https://sqs.eu-west-1.amazonaws.com/123456789012/events");
+/// let sink = SinkHandler::new(sink).batch(10, Duration::from_secs(1));
+///
+/// api.listener()
+///     .on_dynamic("Balances", "Transfer", sink)
+///     .run()
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct SqsSink {
+    client: aws_sdk_sqs::Client,
+    queue_url: String,
+    fifo: bool,
+}
+
+impl SqsSink {
+    /// Send events to the queue with the URL given.
+    pub fn new(client: aws_sdk_sqs::Client, queue_url: impl Into<String>) -> Self {
+        SqsSink {
+            client,
+            queue_url: queue_url.into(),
+            fifo: false,
+        }
+    }
+
+    /// Whether the queue is a FIFO queue, whose messages need a group and
+    /// deduplication ID. All events are sent in one group, so that they're
+    /// delivered in order.
+    pub fn fifo(mut self, fifo: bool) -> Self {
+        self.fifo = fifo;
+        self
+    }
+
+    async fn send_batch(&self, records: &[EventRecord]) -> Result<(), Error> {
+        use aws_sdk_sqs::model::{
+            MessageAttributeValue,
+            SendMessageBatchRequestEntry,
+        };
+
+        let mut entries = Vec::with_capacity(records.len());
+        for (idx, record) in records.iter().enumerate() {
+            let mut entry = SendMessageBatchRequestEntry::builder()
+                .id(idx.to_string())
+                .message_body(serde_json::to_string(record)?);
+            for (name, data_type, value) in attributes(record) {
+                let value = MessageAttributeValue::builder()
+                    .data_type(data_type)
+                    .string_value(value)
+                    .build();
+                entry = entry.message_attributes(name, value);
+            }
+            if self.fifo {
+                entry = entry
+                    .message_group_id(MESSAGE_GROUP)
+                    .message_deduplication_id(record.id());
+            }
+            entries.push(entry.build());
+        }
+
+        let output = self
+            .client
+            .send_message_batch()
+            .queue_url(&self.queue_url)
+            .set_entries(Some(entries))
+            .send()
+            .await
+            .map_err(|e| Error::Sink(e.to_string()))?;
+        match output.failed().unwrap_or_default() {
+            [] => Ok(()),
+            failed => {
+                Err(Error::Sink(format!(
+                    "SQS rejected {} of {} messages: {}",
+                    failed.len(),
+                    records.len(),
+                    failed[0].message().unwrap_or_default()
+                )))
+            }
+        }
+    }
+}
+
+impl EventSink for SqsSink {
+    fn send(&self, records: Vec<EventRecord>) -> BoxFuture<'_, Result<(), Error>> {
+        async move {
+            for batch in records.chunks(MAX_BATCH) {
+                self.send_batch(batch).await?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// An [`EventSink`] which publishes events as JSON messages to an Amazon SNS
+/// topic, in batches of up to ten, with the same message attributes as
+/// [`SqsSink`] so that subscriptions can filter on them.
+///
+/// ```no_run
+/// # use event_listener::listener::SnsSink;
+/// # async fn run(sns: aws_sdk_sns::Client) {
+/// let sink = SnsSink::new(sns, "arn:aws:sns:eu-west-1:123456789012:events");
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct SnsSink {
+    client: aws_sdk_sns::Client,
+    topic_arn: String,
+    fifo: bool,
+}
+
+impl SnsSink {
+    /// Publish events to the topic with the ARN given.
+    pub fn new(client: aws_sdk_sns::Client, topic_arn: impl Into<String>) -> Self {
+        SnsSink {
+            client,
+            topic_arn: topic_arn.into(),
+            fifo: false,
+        }
+    }
+
+    /// Whether the topic is a FIFO topic. See [`SqsSink::fifo()`].
+    pub fn fifo(mut self, fifo: bool) -> Self {
+        self.fifo = fifo;
+        self
+    }
+
+    async fn publish_batch(&self, records: &[EventRecord]) -> Result<(), Error> {
+        use aws_sdk_sns::model::{
+            MessageAttributeValue,
+            PublishBatchRequestEntry,
+        };
+
+        let mut entries = Vec::with_capacity(records.len());
+        for (idx, record) in records.iter().enumerate() {
+            let mut entry = PublishBatchRequestEntry::builder()
+                .id(idx.to_string())
+                .message(serde_json::to_string(record)?);
+            for (name, data_type, value) in attributes(record) {
+                let value = MessageAttributeValue::builder()
+                    .data_type(data_type)
+                    .string_value(value)
+                    .build();
+                entry = entry.message_attributes(name, value);
+            }
+            if self.fifo {
+                entry = entry
+                    .message_group_id(MESSAGE_GROUP)
+                    .message_deduplication_id(record.id());
+            }
+            entries.push(entry.build());
+        }
+
+        let output = self
+            .client
+            .publish_batch()
+            .topic_arn(&self.topic_arn)
+            .set_publish_batch_request_entries(Some(entries))
+            .send()
+            .await
+            .map_err(|e| Error::Sink(e.to_string()))?;
+        match output.failed().unwrap_or_default() {
+            [] => Ok(()),
+            failed => {
+                Err(Error::Sink(format!(
+                    "SNS rejected {} of {} messages: {}",
+                    failed.len(),
+                    records.len(),
+                    failed[0].message().unwrap_or_default()
+                )))
+            }
+        }
+    }
+}
+
+impl EventSink for SnsSink {
+    fn send(&self, records: Vec<EventRecord>) -> BoxFuture<'_, Result<(), Error>> {
+        async move {
+            for batch in records.chunks(MAX_BATCH) {
+                self.publish_batch(batch).await?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// The name, data type and value of each message attribute of an event.
+fn attributes(record: &EventRecord) -> Vec<(&'static str, &'static str, String)> {
+    let mut attributes = vec![
+        ("pallet", "String", record.pallet.clone()),
+        ("variant", "String", record.variant.clone()),
+        ("blockNumber", "Number", record.block_number.to_string()),
+    ];
+    if let Some(chain) = &record.chain {
+        attributes.push(("chain", "String", chain.clone()));
+    }
+    attributes
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::listener::sink::test_utils::record;
+
+    #[test]
+    fn events_are_given_attributes_to_filter_on() {
+        let record = EventRecord {
+            chain: Some("polkadot".into()),
+            ..record(12, 3)
+        };
+        assert_eq!(
+            attributes(&record),
+            vec![
+                ("pallet", "String", "Balances".to_owned()),
+                ("variant", "String", "Transfer".to_owned()),
+                ("blockNumber", "Number", "12".to_owned()),
+                ("chain", "String", "polkadot".to_owned()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,268 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use super::{
+    EventRecord,
+    EventSink,
+};
+use crate::error::Error;
+use futures::{
+    future::BoxFuture,
+    FutureExt,
+};
+use std::sync::{
+    atomic::{
+        AtomicU64,
+        Ordering,
+    },
+    Arc,
+};
+use tokio::sync::{
+    broadcast,
+    mpsc,
+};
+
+/// What a [`ChannelSink`] does with events when its channel is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WhenFull {
+    /// Wait for there to be room, holding up the handler until there is.
+    Wait,
+    /// Drop the events that don't fit, counting them (see [`ChannelSink::dropped()`]).
+    Drop,
+    /// Fail to send the events, so that they're retried or dead lettered like
+    /// any other handler failure.
+    Fail,
+}
+
+impl Default for WhenFull {
+    fn default() -> Self {
+        WhenFull::Wait
+    }
+}
+
+/// An [`EventSink`] which sends events to a bounded Tokio [`mpsc`] channel, for
+/// applications built around channels. Events are acknowledged once they're in
+/// the channel, rather than once they've been received from it.
+///
+/// ```no_run
+/// use event_listener::{
+///     listener::{ ChannelSink, SinkHandler },
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let (tx, mut rx) = tokio::sync::mpsc::channel(1024);
+///
+/// let listener = api
+///     .listener()
+///     .on_dynamic("Balances", "Transfer", SinkHandler::new(ChannelSink::new(tx)))
+///     .build();
+/// tokio::spawn(async move { listener.run().await });
+///
+/// while let Some(record) = rx.recv().await {
+///     println!("Transfer in block #{}: {}", record.block_number, record.fields);
+/// }
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ChannelSink {
+    tx: mpsc::Sender<EventRecord>,
+    when_full: WhenFull,
+    dropped: Arc<AtomicU64>,
+}
+
+impl ChannelSink {
+    /// Send events to the channel given, waiting for room when it's full.
+    pub fn new(tx: mpsc::Sender<EventRecord>) -> Self {
+        ChannelSink {
+            tx,
+            when_full: WhenFull::default(),
+            dropped: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Choose what to do with events when the channel is full.
+    pub fn when_full(mut self, when_full: WhenFull) -> Self {
+        self.when_full = when_full;
+        self
+    }
+
+    /// How many events have been dropped because the channel was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    async fn send_all(&self, records: Vec<EventRecord>) -> Result<(), Error> {
+        for record in records {
+            let res = match self.when_full {
+                WhenFull::Wait => self.tx.send(record).await.map_err(|_| closed()),
+                WhenFull::Drop | WhenFull::Fail => {
+                    match self.tx.try_send(record) {
+                        Ok(()) => Ok(()),
+                        Err(mpsc::error::TrySendError::Closed(_)) => Err(closed()),
+                        Err(mpsc::error::TrySendError::Full(_))
+                            if self.when_full == WhenFull::Drop =>
+                        {
+                            self.dropped.fetch_add(1, Ordering::Relaxed);
+                            Ok(())
+                        }
+                        Err(mpsc::error::TrySendError::Full(_)) => {
+                            Err(Error::Sink("channel is full".into()))
+                        }
+                    }
+                }
+            };
+            res?;
+        }
+        Ok(())
+    }
+}
+
+impl EventSink for ChannelSink {
+    fn send(&self, records: Vec<EventRecord>) -> BoxFuture<'_, Result<(), Error>> {
+        self.send_all(records).boxed()
+    }
+}
+
+fn closed() -> Error {
+    Error::Sink("channel is closed".into())
+}
+
+/// An [`EventSink`] which sends events to a Tokio [`broadcast`] channel, so that
+/// any number of receivers (from [`BroadcastSink::subscribe()`]) each see every
+/// event. Sending never waits: receivers which fall more than the capacity of the
+/// channel behind miss the oldest events, and events sent while there are no
+/// receivers are dropped.
+///
+/// ```no_run
+/// use event_listener::{
+///     listener::{ BroadcastSink, SinkHandler },
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let sink = BroadcastSink::new(1024);
+/// let mut transfers = sink.subscribe();
+///
+/// let listener = api
+///     .listener()
+///     .on_dynamic("Balances", "Transfer", SinkHandler::new(sink))
+///     .build();
+/// tokio::spawn(async move { listener.run().await });
+///
+/// while let Some(record) = transfers.recv().await {
+///     println!("Transfer in block #{}: {}", record.block_number, record.fields);
+/// }
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct BroadcastSink {
+    tx: broadcast::Sender<EventRecord>,
+}
+
+impl BroadcastSink {
+    /// Send events to a new channel which holds up to `capacity` events.
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity.max(1));
+        BroadcastSink { tx }
+    }
+
+    /// Send events to an existing channel.
+    pub fn from_sender(tx: broadcast::Sender<EventRecord>) -> Self {
+        BroadcastSink { tx }
+    }
+
+    /// Receive the events sent from now on.
+    pub fn subscribe(&self) -> EventReceiver {
+        EventReceiver {
+            rx: self.tx.subscribe(),
+            missed: 0,
+        }
+    }
+}
+
+impl EventSink for BroadcastSink {
+    fn send(&self, records: Vec<EventRecord>) -> BoxFuture<'_, Result<(), Error>> {
+        for record in records {
+            // This only fails if there are no receivers, which isn't a problem.
+            let _ = self.tx.send(record);
+        }
+        futures::future::ready(Ok(())).boxed()
+    }
+}
+
+/// Receives the events sent to a [`BroadcastSink`], from [`BroadcastSink::subscribe()`].
+#[derive(Debug)]
+pub struct EventReceiver {
+    rx: broadcast::Receiver<EventRecord>,
+    missed: u64,
+}
+
+impl EventReceiver {
+    /// Wait for the next event, or `None` once every [`BroadcastSink`] sending to
+    /// the channel has been dropped. If this receiver has fallen so far behind that
+    /// events were overwritten before it got to them, they're skipped over and
+    /// counted (see [`EventReceiver::missed()`]).
+    pub async fn recv(&mut self) -> Option<EventRecord> {
+        loop {
+            match self.rx.recv().await {
+                Ok(record) => return Some(record),
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::warn!(missed, "Event receiver fell behind; skipping events");
+                    self.missed += missed;
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// How many events this receiver has missed from falling behind.
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+
+    /// The underlying Tokio receiver, to handle falling behind some other way.
+    pub fn into_inner(self) -> broadcast::Receiver<EventRecord> {
+        self.rx
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::listener::sink::test_utils::record;
+
+    #[tokio::test]
+    async fn full_channels_drop_or_fail() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let sink = ChannelSink::new(tx.clone()).when_full(WhenFull::Drop);
+        sink.send(vec![record(1, 0), record(2, 0)]).await.unwrap();
+        assert_eq!(sink.dropped(), 1);
+
+        let failing = ChannelSink::new(tx).when_full(WhenFull::Fail);
+        assert!(failing.send(vec![record(3, 0)]).await.is_err());
+        assert_eq!(rx.recv().await.unwrap().block_number, 1);
+        failing.send(vec![record(4, 0)]).await.unwrap();
+        assert_eq!(rx.recv().await.unwrap().block_number, 4);
+    }
+
+    #[tokio::test]
+    async fn lagging_receivers_skip_missed_events() {
+        let sink = BroadcastSink::new(2);
+        let mut rx = sink.subscribe();
+        sink.send((1..=5).map(|n| record(n, 0)).collect()).await.unwrap();
+
+        assert_eq!(rx.recv().await.unwrap().block_number, 4);
+        assert_eq!(rx.missed(), 3);
+        assert_eq!(rx.recv().await.unwrap().block_number, 5);
+        drop(sink);
+        assert_eq!(rx.recv().await, None);
+    }
+}
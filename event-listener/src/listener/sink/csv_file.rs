@@ -0,0 +1,233 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use super::{
+    EventRecord,
+    EventSink,
+};
+use crate::{
+    error::Error,
+    utils::unblock,
+};
+use futures::{
+    future::BoxFuture,
+    FutureExt,
+};
+use parking_lot::Mutex;
+use serde_json::Value as Json;
+use std::{
+    path::PathBuf,
+    sync::Arc,
+};
+
+/// An [`EventSink`] which appends events to a CSV file, one row per event, with
+/// the columns given. The header row is written when the file is created.
+///
+/// Each column takes its value from a source, which is one of `chain`, `id`,
+/// `block_number`, `block_hash`, `timestamp`, `event_index`, `extrinsic_index`,
+/// `pallet`, `variant`, `fields` (all of the fields as JSON) or `accounts`, or
+/// else `fields.` followed by the `.` separated path to a field of the event
+/// (such as `fields.amount`, or `fields.dest.Id` to look inside an enum). Byte
+/// arrays such as account IDs are written as hex, and other nested values as
+/// JSON. Missing values are left empty.
+///
+/// ```no_run
+/// # use event_listener::listener::CsvSink;
+/// let sink = CsvSink::new("transfers.csv")
+///     .column("block", "block_number")
+///     .column("time", "timestamp")
+///     .column("from", "fields.from")
+///     .column("to", "fields.to")
+///     .column("amount", "fields.amount");
+/// ```
+#[derive(Clone, Debug)]
+pub struct CsvSink {
+    path: PathBuf,
+    columns: Vec<(String, String)>,
+    lock: Arc<Mutex<()>>,
+}
+
+impl CsvSink {
+    /// Append events to the file at the path given. Unless columns are given with
+    /// [`CsvSink::column()`], rows have the block number, event index, pallet,
+    /// variant and fields of each event.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        CsvSink {
+            path: path.into(),
+            columns: Vec::new(),
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Add a column with the header and source given. See [`CsvSink`] for the
+    /// sources available.
+    pub fn column(mut self, header: impl Into<String>, source: impl Into<String>) -> Self {
+        self.columns.push((header.into(), source.into()));
+        self
+    }
+
+    fn columns(&self) -> Vec<(String, String)> {
+        if !self.columns.is_empty() {
+            return self.columns.clone()
+        }
+        ["block_number", "event_index", "pallet", "variant", "fields"]
+            .iter()
+            .map(|c| (c.to_string(), c.to_string()))
+            .collect()
+    }
+
+    fn write(&self, records: Vec<EventRecord>) -> Result<(), Error> {
+        let _lock = self.lock.lock();
+        let columns = self.columns();
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let is_new = file.metadata()?.len() == 0;
+
+        let mut writer = csv::Writer::from_writer(file);
+        if is_new {
+            writer
+                .write_record(columns.iter().map(|(header, _)| header))
+                .map_err(csv_error)?;
+        }
+        for record in &records {
+            writer
+                .write_record(columns.iter().map(|(_, source)| column(record, source)))
+                .map_err(csv_error)?;
+        }
+        let file = writer
+            .into_inner()
+            .map_err(|e| Error::Sink(e.error().to_string()))?;
+        file.sync_data()?;
+        Ok(())
+    }
+}
+
+/// The value of a column for a record, from the source given.
+fn column(record: &EventRecord, source: &str) -> String {
+    let opt = |v: Option<String>| v.unwrap_or_default();
+    match source {
+        "chain" => opt(record.chain.clone()),
+        "id" => record.id(),
+        "block_number" => record.block_number.to_string(),
+        "block_hash" => record.block_hash.clone(),
+        "timestamp" => opt(record.timestamp.map(|t| t.to_string())),
+        "event_index" => record.event_index.to_string(),
+        "extrinsic_index" => opt(record.extrinsic_index.map(|i| i.to_string())),
+        "pallet" => record.pallet.clone(),
+        "variant" => record.variant.clone(),
+        "fields" => record.fields.to_string(),
+        "accounts" => record.accounts.join(" "),
+        _ => {
+            match source.strip_prefix("fields.") {
+                Some(path) => opt(lookup(&record.fields, path).map(flatten)),
+                None => String::new(),
+            }
+        }
+    }
+}
+
+/// Find a value in the JSON fields of an event by its path. The fields are in
+/// the form that [`crate::json`] writes them, in which enum values with fields
+/// are written as `{ "Variant": fields }`, so a segment naming the variant steps
+/// into its fields (or its only field, if it has just one).
+fn lookup<'a>(fields: &'a Json, path: &str) -> Option<&'a Json> {
+    path.split('.').try_fold(fields, |value, segment| {
+        match value {
+            Json::Object(map) if map.len() == 1 && is_variant_name(segment) => {
+                match map.get(segment)? {
+                    Json::Array(values) if values.len() == 1 => values.first(),
+                    Json::Object(values) if values.len() == 1 => values.values().next(),
+                    values => Some(values),
+                }
+            }
+            Json::Object(map) => map.get(segment),
+            Json::Array(values) => values.get(segment.parse::<usize>().ok()?),
+            _ => None,
+        }
+    })
+}
+
+// Variants are named in upper camel case, unlike fields.
+fn is_variant_name(segment: &str) -> bool {
+    segment.starts_with(char::is_uppercase)
+}
+
+/// Write a value out as a single cell.
+fn flatten(value: &Json) -> String {
+    match value {
+        Json::Null => String::new(),
+        Json::String(s) => s.clone(),
+        Json::Number(n) => n.to_string(),
+        Json::Bool(b) => b.to_string(),
+        _ => {
+            match as_hex(value) {
+                Some(hex) => hex.to_owned(),
+                None => value.to_string(),
+            }
+        }
+    }
+}
+
+// Byte arrays are written as hex strings, which may be wrapped in single field
+// structs such as `AccountId32`.
+fn as_hex(value: &Json) -> Option<&str> {
+    match value {
+        Json::String(s) if s.starts_with("0x") => Some(s),
+        Json::Array(values) if values.len() == 1 => as_hex(&values[0]),
+        _ => None,
+    }
+}
+
+impl EventSink for CsvSink {
+    fn send(&self, records: Vec<EventRecord>) -> BoxFuture<'_, Result<(), Error>> {
+        let sink = self.clone();
+        unblock(move || sink.write(records)).boxed()
+    }
+}
+
+fn csv_error(e: csv::Error) -> Error {
+    Error::Sink(e.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::listener::sink::test_utils::record;
+
+    #[test]
+    fn columns_are_flattened_from_fields() {
+        let record = EventRecord {
+            fields: serde_json::json!({
+                "from": ["0x010203"],
+                "dest": { "Id": [["0x0405"]] },
+                "amount": 10,
+                "memo": { "text": "hi", "tags": ["a"] },
+                "status": "Free",
+                "pair": { "Both": { "left": 1, "right": 2 } },
+            }),
+            ..record(3, 1)
+        };
+        let cells: Vec<_> = [
+            "block_number",
+            "extrinsic_index",
+            "fields.from",
+            "fields.dest.Id",
+            "fields.amount",
+            "fields.memo.text",
+            "fields.memo.tags",
+            "fields.status",
+            "fields.pair.Both.left",
+            "fields.missing",
+        ]
+        .iter()
+        .map(|source| column(&record, source))
+        .collect();
+        assert_eq!(
+            cells,
+            vec!["3", "", "0x010203", "0x0405", "10", "hi", "[\"a\"]", "Free", "1", ""]
+        );
+    }
+}
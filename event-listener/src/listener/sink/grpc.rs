@@ -0,0 +1,294 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use super::{
+    EventRecord,
+    EventSink,
+};
+use crate::error::Error;
+use futures::{
+    future::{
+        self,
+        BoxFuture,
+    },
+    stream::{
+        self,
+        BoxStream,
+    },
+    FutureExt,
+    StreamExt,
+};
+use parking_lot::Mutex;
+use std::{
+    collections::VecDeque,
+    net::SocketAddr,
+    sync::Arc,
+};
+use tokio::sync::broadcast;
+use tonic::{
+    Request,
+    Response,
+    Status,
+};
+
+/// The types and service generated from `proto/event_listener.proto`.
+pub mod proto {
+    #![allow(missing_docs)]
+    tonic::include_proto!("event_listener.v1");
+}
+
+use proto::event_feed_server::{
+    EventFeed,
+    EventFeedServer,
+};
+
+/// An [`EventSink`] which serves the events sent to it over gRPC, so that services
+/// written in any language can follow them without talking to a node. See
+/// `proto/event_listener.proto` for the API.
+///
+/// The most recent events are kept in memory (see [`GrpcFeed::new()`]), so that
+/// clients which lose their connection can carry on from the last event they saw.
+/// Clients which fall too far behind are disconnected, and can do the same.
+///
+/// ```no_run
+/// use event_listener::{
+///     listener::{ GrpcFeed, SinkHandler },
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let feed = GrpcFeed::new(10_000);
+/// tokio::spawn(feed.clone().serve("0.0.0.0:50051".parse().unwrap()));
+///
+/// api.listener()
+///     .on_dynamic("Balances", "Transfer", SinkHandler::new(feed))
+///     .run()
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct GrpcFeed {
+    inner: Arc<Feed>,
+}
+
+struct Feed {
+    capacity: usize,
+    recent: Mutex<Recent>,
+    live: broadcast::Sender<EventRecord>,
+}
+
+#[derive(Default)]
+struct Recent {
+    records: VecDeque<EventRecord>,
+    // The position of the last record to have been dropped to make room.
+    dropped: Option<(u64, u32)>,
+}
+
+impl std::fmt::Debug for GrpcFeed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GrpcFeed")
+            .field("capacity", &self.inner.capacity)
+            .field("subscribers", &self.inner.live.receiver_count())
+            .finish()
+    }
+}
+
+impl GrpcFeed {
+    /// Create a feed which keeps the `capacity` most recent events, for clients
+    /// to resume from. This is also how far behind a client can fall before being
+    /// disconnected.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        let (live, _) = broadcast::channel(capacity);
+        GrpcFeed {
+            inner: Arc::new(Feed {
+                capacity,
+                recent: Mutex::new(Recent::default()),
+                live,
+            }),
+        }
+    }
+
+    /// The gRPC service, to serve along with others on a [`tonic`] server of your
+    /// own.
+    pub fn service(&self) -> EventFeedServer<GrpcFeed> {
+        EventFeedServer::new(self.clone())
+    }
+
+    /// Serve the feed at the address given, until the server fails.
+    pub async fn serve(self, addr: SocketAddr) -> Result<(), Error> {
+        tonic::transport::Server::builder()
+            .add_service(self.service())
+            .serve(addr)
+            .await
+            .map_err(|e| Error::Sink(e.to_string()))
+    }
+}
+
+impl EventSink for GrpcFeed {
+    fn send(&self, records: Vec<EventRecord>) -> BoxFuture<'_, Result<(), Error>> {
+        let mut recent = self.inner.recent.lock();
+        for record in records {
+            if recent.records.len() == self.inner.capacity {
+                recent.dropped = recent.records.pop_front().map(|r| position(&r));
+            }
+            recent.records.push_back(record.clone());
+            // Nobody may be listening, which is fine.
+            let _ = self.inner.live.send(record);
+        }
+        future::ready(Ok(())).boxed()
+    }
+}
+
+fn position(record: &EventRecord) -> (u64, u32) {
+    (record.block_number, record.event_index)
+}
+
+#[tonic::async_trait]
+impl EventFeed for GrpcFeed {
+    type SubscribeStream = BoxStream<'static, Result<proto::Event, Status>>;
+
+    async fn subscribe(
+        &self,
+        request: Request<proto::SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let req = request.into_inner();
+        let after = req.after.map(|c| (c.block_number, c.event_index));
+
+        // Subscribe while holding the lock, so that no events are missed between
+        // the recent ones and the live ones.
+        let (backlog, live) = {
+            let recent = self.inner.recent.lock();
+            if let (Some(after), Some(dropped)) = (after, recent.dropped) {
+                if after < dropped {
+                    return Err(Status::out_of_range(
+                        "Events after the cursor are no longer available",
+                    ))
+                }
+            }
+            let backlog: Vec<_> = match after {
+                Some(after) => {
+                    recent
+                        .records
+                        .iter()
+                        .filter(|r| position(r) > after)
+                        .cloned()
+                        .collect()
+                }
+                None => Vec::new(),
+            };
+            (backlog, self.inner.live.subscribe())
+        };
+
+        let live = stream::unfold(Some(live), |live| {
+            async move {
+                let mut live = live?;
+                match live.recv().await {
+                    Ok(record) => Some((Ok(record), Some(live))),
+                    Err(broadcast::error::RecvError::Lagged(_)) => {
+                        Some((
+                            Err(Status::data_loss(
+                                "Fell too far behind; resubscribe from the last event seen",
+                            )),
+                            None,
+                        ))
+                    }
+                    Err(broadcast::error::RecvError::Closed) => None,
+                }
+            }
+        });
+        let wanted = move |record: &EventRecord| {
+            after.map_or(true, |after| position(record) > after)
+                && (req.pallets.is_empty() || req.pallets.contains(&record.pallet))
+                && (req.variants.is_empty() || req.variants.contains(&record.variant))
+        };
+        let events = stream::iter(backlog.into_iter().map(Ok))
+            .chain(live)
+            .filter(move |res| {
+                future::ready(match res {
+                    Ok(record) => wanted(record),
+                    Err(_) => true,
+                })
+            })
+            .map(|res| res.map(to_proto))
+            .boxed();
+        Ok(Response::new(events))
+    }
+}
+
+fn to_proto(record: EventRecord) -> proto::Event {
+    proto::Event {
+        id: record.id(),
+        field_bytes: hex::decode(record.field_bytes.trim_start_matches("0x"))
+            .unwrap_or_default(),
+        fields_json: record.fields.to_string(),
+        chain: record.chain,
+        block_number: record.block_number,
+        block_hash: record.block_hash,
+        timestamp: record.timestamp,
+        event_index: record.event_index,
+        extrinsic_index: record.extrinsic_index,
+        pallet: record.pallet,
+        variant: record.variant,
+        accounts: record.accounts,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::listener::sink::test_utils::record;
+
+    fn in_pallet(block_number: u64, pallet: &str) -> EventRecord {
+        EventRecord {
+            pallet: pallet.into(),
+            ..record(block_number, 0)
+        }
+    }
+
+    async fn subscribe(
+        feed: &GrpcFeed,
+        after: Option<u64>,
+        pallets: &[&str],
+    ) -> Result<BoxStream<'static, Result<proto::Event, Status>>, Status> {
+        let req = proto::SubscribeRequest {
+            after: after.map(|block_number| {
+                proto::Cursor {
+                    block_number,
+                    event_index: 0,
+                }
+            }),
+            pallets: pallets.iter().map(|p| p.to_string()).collect(),
+            variants: Vec::new(),
+        };
+        feed.subscribe(Request::new(req))
+            .await
+            .map(Response::into_inner)
+    }
+
+    #[tokio::test]
+    async fn clients_resume_from_their_cursor() {
+        let feed = GrpcFeed::new(3);
+        let records = (1..=4).map(|n| in_pallet(n, "A")).collect();
+        feed.send(records).await.unwrap();
+
+        // Block 1 has been dropped, so resuming after it is fine but not before.
+        let err = subscribe(&feed, Some(0), &[]).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::OutOfRange);
+        let mut events = subscribe(&feed, Some(2), &["A"]).await.unwrap();
+
+        feed.send(vec![in_pallet(5, "B"), in_pallet(6, "A")]).await.unwrap();
+        let blocks: Vec<_> = events
+            .by_ref()
+            .take(3)
+            .map(|e| e.unwrap().block_number)
+            .collect()
+            .await;
+        assert_eq!(blocks, vec![3, 4, 6]);
+    }
+}
@@ -0,0 +1,216 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use super::{
+    EventRecord,
+    EventSink,
+};
+use crate::{
+    error::Error,
+    utils::unblock,
+};
+use futures::{
+    future::BoxFuture,
+    FutureExt,
+};
+use parking_lot::Mutex;
+use std::{
+    fs::File,
+    io::Write,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::Arc,
+    time::{
+        Duration,
+        Instant,
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+
+/// An [`EventSink`] which appends events to a file, as one line of JSON each,
+/// moving the file aside once it gets too big or too old. This suits log shippers
+/// such as Vector or Fluent Bit, which can follow the file and pick up where they
+/// left off.
+///
+/// Events are appended to `<dir>/<prefix>.jsonl`. When it's rotated, it's renamed
+/// to `<dir>/<prefix>-<millis since the Unix epoch>.jsonl` (and, with the `gzip`
+/// feature and [`JsonLinesSink::gzip()`], compressed to `.jsonl.gz`), and a new
+/// file is started. By default, files are rotated once they reach 100MiB.
+///
+/// ```no_run
+/// # use event_listener::listener::JsonLinesSink;
+/// # use std::time::Duration;
+/// let sink = JsonLinesSink::new("/var/log/chain", "events")
+///     .max_bytes(Some(64 * 1024 * 1024))
+///     .max_age(Some(Duration::from_secs(60 * 60)));
+/// ```
+#[derive(Clone, Debug)]
+pub struct JsonLinesSink {
+    dir: PathBuf,
+    prefix: String,
+    max_bytes: Option<u64>,
+    max_age: Option<Duration>,
+    gzip: bool,
+    current: Arc<Mutex<Option<Current>>>,
+}
+
+#[derive(Debug)]
+struct Current {
+    file: File,
+    len: u64,
+    opened: Instant,
+}
+
+impl JsonLinesSink {
+    /// Append events to `<dir>/<prefix>.jsonl`. The directory is created if needed.
+    pub fn new(dir: impl Into<PathBuf>, prefix: impl Into<String>) -> Self {
+        JsonLinesSink {
+            dir: dir.into(),
+            prefix: prefix.into(),
+            max_bytes: Some(100 * 1024 * 1024),
+            max_age: None,
+            gzip: false,
+            current: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Rotate the file once it's at least this many bytes long, or never if `None`.
+    pub fn max_bytes(mut self, max_bytes: Option<u64>) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Rotate the file once it has been written to for this long. Files are only
+    /// rotated as events are written, so a file may be left open for longer if no
+    /// events turn up.
+    pub fn max_age(mut self, max_age: Option<Duration>) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Compress files with gzip once they've been rotated.
+    #[cfg(feature = "gzip")]
+    pub fn gzip(mut self, gzip: bool) -> Self {
+        self.gzip = gzip;
+        self
+    }
+
+    /// The path of the file that events are being appended to.
+    pub fn path(&self) -> PathBuf {
+        self.dir.join(format!("{}.jsonl", self.prefix))
+    }
+
+    fn write(&self, records: Vec<EventRecord>) -> Result<(), Error> {
+        let mut lines = Vec::new();
+        for record in &records {
+            serde_json::to_writer(&mut lines, record)?;
+            lines.push(b'\n');
+        }
+
+        let mut current = self.current.lock();
+        if let Some(file) = &*current {
+            let too_big = self.max_bytes.map_or(false, |max| file.len >= max);
+            let too_old = self.max_age.map_or(false, |max| file.opened.elapsed() >= max);
+            if too_big || too_old {
+                *current = None;
+                self.rotate()?;
+            }
+        }
+        let file = match current.take() {
+            Some(file) => file,
+            None => self.open()?,
+        };
+        let file = current.insert(file);
+        file.file.write_all(&lines)?;
+        file.file.sync_data()?;
+        file.len += lines.len() as u64;
+        Ok(())
+    }
+
+    fn open(&self) -> Result<Current, Error> {
+        std::fs::create_dir_all(&self.dir)?;
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path())?;
+        Ok(Current {
+            len: file.metadata()?.len(),
+            file,
+            opened: Instant::now(),
+        })
+    }
+
+    fn rotate(&self) -> Result<(), Error> {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let rotated = self.dir.join(format!("{}-{}.jsonl", self.prefix, millis));
+        std::fs::rename(self.path(), &rotated)?;
+        if self.gzip {
+            compress(&rotated)?;
+        }
+        tracing::debug!("Rotated {} to {}", self.path().display(), rotated.display());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "gzip")]
+fn compress(path: &Path) -> Result<(), Error> {
+    let mut gz_path = path.as_os_str().to_owned();
+    gz_path.push(".gz");
+    let mut encoder = flate2::write::GzEncoder::new(
+        File::create(&gz_path)?,
+        flate2::Compression::default(),
+    );
+    std::io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+    std::fs::remove_file(path)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "gzip"))]
+fn compress(_path: &Path) -> Result<(), Error> {
+    Ok(())
+}
+
+impl EventSink for JsonLinesSink {
+    fn send(&self, records: Vec<EventRecord>) -> BoxFuture<'_, Result<(), Error>> {
+        let sink = self.clone();
+        unblock(move || sink.write(records)).boxed()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::listener::sink::test_utils::record;
+
+    #[tokio::test]
+    async fn files_are_rotated_once_too_big() {
+        let dir = std::env::temp_dir()
+            .join(format!("event-listener-jsonl-{}", std::process::id()));
+        let sink = JsonLinesSink::new(&dir, "events").max_bytes(Some(1));
+        let record = record(1, 0);
+
+        sink.send(vec![record.clone(), record.clone()]).await.unwrap();
+        let contents = std::fs::read_to_string(sink.path()).unwrap();
+        assert_eq!(contents.lines().count(), 2);
+        assert_eq!(
+            serde_json::from_str::<EventRecord>(contents.lines().next().unwrap()).unwrap(),
+            record
+        );
+
+        // The file is now big enough to be moved aside before writing again.
+        sink.send(vec![record]).await.unwrap();
+        let contents = std::fs::read_to_string(sink.path()).unwrap();
+        assert_eq!(contents.lines().count(), 1);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,574 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Writing events out to other systems, such as databases and message brokers.
+
+#[cfg(feature = "aws")]
+mod aws;
+#[cfg(feature = "channels")]
+mod channel;
+#[cfg(feature = "csv")]
+mod csv_file;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod jsonl;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+#[cfg(feature = "parquet")]
+mod parquet_file;
+#[cfg(feature = "postgres")]
+mod postgres;
+#[cfg(feature = "redis")]
+mod redis_stream;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+#[cfg(feature = "aws")]
+pub use aws::{
+    SnsSink,
+    SqsSink,
+};
+#[cfg(feature = "channels")]
+pub use channel::{
+    BroadcastSink,
+    ChannelSink,
+    EventReceiver,
+    WhenFull,
+};
+#[cfg(feature = "csv")]
+pub use csv_file::CsvSink;
+#[cfg(feature = "grpc")]
+pub use grpc::{
+    proto as grpc_proto,
+    GrpcFeed,
+};
+pub use jsonl::JsonLinesSink;
+#[cfg(feature = "mqtt")]
+pub use mqtt::MqttSink;
+#[cfg(feature = "parquet")]
+pub use parquet_file::ParquetSink;
+#[cfg(feature = "postgres")]
+pub use postgres::PostgresSink;
+#[cfg(feature = "redis")]
+pub use redis_stream::RedisStreamSink;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteSink;
+
+use super::{
+    handler::{
+        EventContext,
+        Handler,
+        HandlerResult,
+    },
+    health::ComponentHealth,
+};
+use crate::{
+    error::Error,
+    events::EventDetails,
+    utils::{
+        composite_values,
+        to_hex,
+        value_as_bytes,
+    },
+    Config,
+};
+use codec::Encode;
+use futures::{
+    channel::oneshot,
+    future::{
+        self,
+        BoxFuture,
+        Either,
+    },
+    FutureExt,
+};
+use parking_lot::Mutex;
+use scale_value::{
+    scale::TypeId,
+    Composite,
+    Value,
+    ValueDef,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    sync::Arc,
+    time::Duration,
+};
+
+/// An event along with where it came from, as written out by an [`EventSink`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventRecord {
+    /// The identifier of the chain that the event was emitted on, if the listener
+    /// was given one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain: Option<String>,
+    /// The number of the block that the event was emitted in.
+    pub block_number: u64,
+    /// The hash of the block that the event was emitted in, as a hex string.
+    pub block_hash: String,
+    /// When the block was authored, in milliseconds since the Unix epoch.
+    pub timestamp: Option<u64>,
+    /// The index of the event in the block.
+    pub event_index: u32,
+    /// The index of the extrinsic that emitted the event, if it was emitted by one.
+    pub extrinsic_index: Option<u32>,
+    /// The name of the pallet that emitted the event.
+    pub pallet: String,
+    /// The name of the event.
+    pub variant: String,
+    /// The decoded fields of the event, in the form that [`crate::json`] writes
+    /// them.
+    pub fields: serde_json::Value,
+    /// The SCALE encoded fields of the event, as a hex string.
+    pub field_bytes: String,
+    /// The accounts that the event refers to, as hex strings. These are found by
+    /// looking for 32 byte (or, for Ethereum style accounts, 20 byte) arrays in
+    /// the fields of the event, so may include hashes as well.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accounts: Vec<String>,
+    /// The W3C `traceparent` of the span that the event was handled in, with the
+    /// `opentelemetry` feature, so that the systems it's sent to can carry on the
+    /// trace.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traceparent: Option<String>,
+}
+
+impl EventRecord {
+    /// Turn an event handed to a handler into a record.
+    pub fn new<T: Config, Client>(ctx: &EventContext<T, Client>, event: &EventDetails) -> Self {
+        EventRecord {
+            chain: ctx.chain_id().map(ToOwned::to_owned),
+            block_number: ctx.block_number().into(),
+            block_hash: to_hex(ctx.block_hash().encode()),
+            timestamp: ctx.timestamp(),
+            event_index: ctx.event_index(),
+            extrinsic_index: ctx.extrinsic_index(),
+            pallet: ctx.pallet_name().to_owned(),
+            variant: ctx.variant_name().to_owned(),
+            fields: ctx.fields_json(),
+            field_bytes: to_hex(event.field_bytes()),
+            accounts: accounts(ctx.field_values()),
+            #[cfg(feature = "opentelemetry")]
+            traceparent: super::telemetry::current_traceparent(),
+            #[cfg(not(feature = "opentelemetry"))]
+            traceparent: None,
+        }
+    }
+
+    /// An identifier which is unique to the event, and the same each time it's
+    /// handled, so that events written out more than once (for instance, when the
+    /// listener restarts from a checkpoint) can be told apart from new ones.
+    pub fn id(&self) -> String {
+        format!(
+            "{}:{}:{}",
+            self.chain.as_deref().unwrap_or_default(),
+            self.block_hash,
+            self.event_index
+        )
+    }
+}
+
+// Find the byte arrays in the fields which look like accounts.
+fn accounts(fields: &Composite<TypeId>) -> Vec<String> {
+    fn find(value: &Value<TypeId>, found: &mut Vec<String>) {
+        match value_as_bytes(value) {
+            Some(bytes) if bytes.len() == 32 || bytes.len() == 20 => {
+                let account = to_hex(bytes);
+                if !found.contains(&account) {
+                    found.push(account);
+                }
+                return
+            }
+            Some(_) => return,
+            None => {}
+        }
+        match &value.value {
+            ValueDef::Composite(c) => composite_values(c).for_each(|v| find(v, found)),
+            ValueDef::Variant(v) => composite_values(&v.values).for_each(|v| find(v, found)),
+            _ => {}
+        }
+    }
+
+    let mut found = Vec::new();
+    composite_values(fields).for_each(|v| find(v, &mut found));
+    found
+}
+
+/// Somewhere to write events out to. Wrap a sink in a [`SinkHandler`] to hand it
+/// the events of a listener.
+pub trait EventSink: Send + Sync + 'static {
+    /// Write out the records, in order. Once the future resolves successfully,
+    /// the records should be safely stored.
+    fn send(&self, records: Vec<EventRecord>) -> BoxFuture<'_, Result<(), Error>>;
+}
+
+impl<S: EventSink + ?Sized> EventSink for Arc<S> {
+    fn send(&self, records: Vec<EventRecord>) -> BoxFuture<'_, Result<(), Error>> {
+        (**self).send(records)
+    }
+}
+
+/// A [`Handler`] which writes the events it's handed out to an [`EventSink`].
+///
+/// Events can be written out in batches with [`SinkHandler::batch()`]. Since each
+/// event is only finished with once its batch has been written, this is mostly
+/// useful along with [`super::Concurrency::Parallel`], so that the listener
+/// hands over the events of a batch without waiting on the ones before.
+///
+/// # Example
+///
+/// ```no_run
+/// use event_listener::{
+///     listener::{ Concurrency, MemoryEventSink, SinkHandler },
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+/// use std::time::Duration;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let sink = MemoryEventSink::new();
+///
+/// api.listener()
+///     .on_dynamic(
+///         "Balances",
+///         "Transfer",
+///         SinkHandler::new(sink.clone()).batch(100, Duration::from_millis(500)),
+///     )
+///     .concurrency(Concurrency::Parallel(100))
+///     .run()
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SinkHandler<S> {
+    sink: Arc<S>,
+    batch: Arc<Batch>,
+    health: Option<ComponentHealth>,
+}
+
+impl<S> Clone for SinkHandler<S> {
+    fn clone(&self) -> Self {
+        SinkHandler {
+            sink: self.sink.clone(),
+            batch: self.batch.clone(),
+            health: self.health.clone(),
+        }
+    }
+}
+
+impl<S: EventSink> SinkHandler<S> {
+    /// Write events out to the sink given, one at a time.
+    pub fn new(sink: S) -> Self {
+        SinkHandler {
+            sink: Arc::new(sink),
+            batch: Arc::new(Batch::new(1, Duration::ZERO)),
+            health: None,
+        }
+    }
+
+    /// Write events out in batches of up to `size`, waiting at most `max_delay`
+    /// after the first event of a batch for the rest to turn up.
+    pub fn batch(mut self, size: usize, max_delay: Duration) -> Self {
+        self.batch = Arc::new(Batch::new(size, max_delay));
+        self
+    }
+
+    /// Mark the component given as unhealthy whenever events can't be written
+    /// out, and healthy again once they can.
+    pub fn health(mut self, health: ComponentHealth) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    /// The sink that events are written out to.
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+}
+
+impl<T, Client, S> Handler<T, Client, EventDetails> for SinkHandler<S>
+where
+    T: Config,
+    S: EventSink,
+{
+    fn handle(
+        &self,
+        ctx: EventContext<T, Client>,
+        event: EventDetails,
+    ) -> BoxFuture<'static, HandlerResult> {
+        let record = EventRecord::new(&ctx, &event);
+        let sink = self.sink.clone();
+        let batch = self.batch.clone();
+        let health = self.health.clone();
+        async move {
+            let res = batch.send(&*sink, record).await;
+            if let Some(health) = &health {
+                match &res {
+                    Ok(()) => health.healthy(),
+                    Err(e) => health.unhealthy(e.to_string()),
+                }
+            }
+            res?;
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// Gathers records together to send to a sink at once. The first record of each
+/// batch waits for the batch to fill up (or for the delay to pass) and then sends
+/// it, letting the others know how it went.
+#[derive(Debug)]
+struct Batch {
+    size: usize,
+    max_delay: Duration,
+    pending: Mutex<Pending>,
+}
+
+type Sent = oneshot::Sender<Result<(), String>>;
+
+#[derive(Debug, Default)]
+struct Pending {
+    records: Vec<EventRecord>,
+    sent: Vec<Sent>,
+    // Hands the batch to its first record once it's full.
+    full: Option<oneshot::Sender<(Vec<EventRecord>, Vec<Sent>)>>,
+}
+
+impl Batch {
+    fn new(size: usize, max_delay: Duration) -> Self {
+        Batch {
+            size: size.max(1),
+            max_delay,
+            pending: Mutex::new(Pending::default()),
+        }
+    }
+
+    async fn send<S: EventSink>(&self, sink: &S, record: EventRecord) -> Result<(), Error> {
+        if self.size == 1 {
+            return sink.send(vec![record]).await
+        }
+
+        let (sent_tx, sent_rx) = oneshot::channel();
+        let full_rx = {
+            let mut pending = self.pending.lock();
+            pending.records.push(record);
+            pending.sent.push(sent_tx);
+            if pending.records.len() == 1 {
+                let (full_tx, full_rx) = oneshot::channel();
+                pending.full = Some(full_tx);
+                Some(full_rx)
+            } else {
+                if pending.records.len() >= self.size {
+                    let Pending {
+                        records,
+                        sent,
+                        full,
+                    } = std::mem::take(&mut *pending);
+                    if let Some(full) = full {
+                        let _ = full.send((records, sent));
+                    }
+                }
+                None
+            }
+        };
+
+        // The first record of the batch sends it.
+        if let Some(full_rx) = full_rx {
+            let delay = futures_timer::Delay::new(self.max_delay);
+            let (records, sent) = match future::select(full_rx, delay).await {
+                Either::Left((batch, _)) => batch.unwrap_or_default(),
+                Either::Right(((), mut full_rx)) => {
+                    let mut pending = self.pending.lock();
+                    // The batch may have filled up just as the delay passed.
+                    match full_rx.try_recv() {
+                        Ok(Some(batch)) => batch,
+                        _ => {
+                            let Pending { records, sent, .. } = std::mem::take(&mut *pending);
+                            (records, sent)
+                        }
+                    }
+                }
+            };
+            if !records.is_empty() {
+                let res = sink.send(records).await.map_err(|e| e.to_string());
+                for tx in sent {
+                    let _ = tx.send(res.clone());
+                }
+            }
+        }
+
+        match sent_rx.await {
+            Ok(res) => res.map_err(Error::Sink),
+            Err(_) => Err(Error::Sink("Batch was dropped before being sent".into())),
+        }
+    }
+}
+
+/// An [`EventSink`] which keeps records in memory. Clones share the same
+/// records. This is mostly useful for testing.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryEventSink(Arc<Mutex<Vec<EventRecord>>>);
+
+impl MemoryEventSink {
+    /// Create an empty sink.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The records sent so far.
+    pub fn records(&self) -> Vec<EventRecord> {
+        self.0.lock().clone()
+    }
+}
+
+impl EventSink for MemoryEventSink {
+    fn send(&self, records: Vec<EventRecord>) -> BoxFuture<'_, Result<(), Error>> {
+        self.0.lock().extend(records);
+        future::ready(Ok(())).boxed()
+    }
+}
+
+/// Sink related test utilities used outside this module.
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use super::EventRecord;
+
+    /// A record of a `Balances::Transfer` event, with the block number and event
+    /// index given and nothing else filled in.
+    pub fn record(block_number: u64, event_index: u32) -> EventRecord {
+        EventRecord {
+            chain: None,
+            block_number,
+            block_hash: "0x00".into(),
+            timestamp: None,
+            event_index,
+            extrinsic_index: None,
+            pallet: "Balances".into(),
+            variant: "Transfer".into(),
+            fields: serde_json::Value::Null,
+            field_bytes: "0x".into(),
+            accounts: Vec::new(),
+            traceparent: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        events::{
+            test_utils::{
+                event_record,
+                events,
+                metadata,
+            },
+            Phase,
+        },
+        listener::{
+            handler::BlockContext,
+            sink::test_utils::record,
+        },
+        SubstrateConfig,
+    };
+    use codec::Decode;
+    use scale_info::TypeInfo;
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Event {
+        Deposit { who: [u8; 32], amount: u64 },
+        Minted { amount: u128 },
+    }
+
+    #[tokio::test]
+    async fn sink_handler_writes_records_out() {
+        let events = events(
+            metadata::<Event>(),
+            vec![event_record(
+                Phase::ApplyExtrinsic(3),
+                Event::Deposit {
+                    who: [1; 32],
+                    amount: 10,
+                },
+            )],
+        );
+        let event = events.iter().next().unwrap().unwrap();
+        let block = BlockContext::<SubstrateConfig> {
+            hash: Default::default(),
+            number: 9,
+            timestamp: Some(1000),
+            chain: Some("local".into()),
+        };
+        let ctx = EventContext::new((), block, &event);
+
+        let sink = MemoryEventSink::new();
+        SinkHandler::new(sink.clone()).handle(ctx, event).await.unwrap();
+
+        let records = sink.records();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.chain.as_deref(), Some("local"));
+        assert_eq!(record.block_number, 9);
+        assert_eq!(record.extrinsic_index, Some(3));
+        assert_eq!((&*record.pallet, &*record.variant), ("Test", "Deposit"));
+        assert_eq!(record.fields["amount"], 10);
+        let who = to_hex([1u8; 32]);
+        assert_eq!(record.field_bytes, format!("{}0a00000000000000", who));
+        assert_eq!(record.accounts, vec![who]);
+        assert_eq!(record.id(), format!("local:{}:0", record.block_hash));
+    }
+
+    #[test]
+    fn big_integers_are_written_as_strings() {
+        let events = events(
+            metadata::<Event>(),
+            vec![event_record(
+                Phase::Finalization,
+                Event::Minted { amount: u128::MAX },
+            )],
+        );
+        let event = events.iter().next().unwrap().unwrap();
+        let block = BlockContext::<SubstrateConfig> {
+            hash: Default::default(),
+            number: 9,
+            timestamp: None,
+            chain: None,
+        };
+        let record = EventRecord::new(&EventContext::new((), block, &event), &event);
+        assert_eq!(record.fields["amount"], u128::MAX.to_string());
+    }
+
+    #[derive(Default)]
+    struct BatchSizes(Mutex<Vec<usize>>);
+
+    impl EventSink for BatchSizes {
+        fn send(&self, records: Vec<EventRecord>) -> BoxFuture<'_, Result<(), Error>> {
+            self.0.lock().push(records.len());
+            future::ready(Ok(())).boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn records_are_sent_in_batches() {
+        let sink = BatchSizes::default();
+        let batch = Batch::new(3, Duration::from_millis(50));
+
+        // A full batch is sent straight away, and what's left once the delay is up.
+        let sends = (0..5).map(|idx| batch.send(&sink, record(1, idx)));
+        let started = std::time::Instant::now();
+        for res in future::join_all(sends).await {
+            res.unwrap();
+        }
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert_eq!(*sink.0.lock(), vec![3, 2]);
+    }
+}
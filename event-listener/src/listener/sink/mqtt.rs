@@ -0,0 +1,162 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use super::{
+    EventRecord,
+    EventSink,
+};
+use crate::error::Error;
+use futures::{
+    future::BoxFuture,
+    FutureExt,
+};
+use rumqttc::{
+    AsyncClient,
+    MqttOptions,
+    QoS,
+};
+use std::time::Duration;
+
+/// An [`EventSink`] which publishes each event as JSON to an MQTT broker.
+///
+/// The topic that an event is published to is worked out from a template (see
+/// [`MqttSink::topic()`]). The connection is configured with
+/// [`rumqttc::MqttOptions`] (re-exported at `event_listener::ext::rumqttc`),
+/// which is also where TLS is set up, and is made again if it's lost.
+///
+/// Publishing resolves once the message has been queued to be sent; with
+/// [`QoS::AtLeastOnce`] or [`QoS::ExactlyOnce`], it's then sent again until the
+/// broker has it, for as long as the listener runs.
+///
+/// ```no_run
+/// use event_listener::{
+///     ext::rumqttc::{ MqttOptions, QoS, Transport },
+///     listener::MqttSink,
+/// };
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let mut options = MqttOptions::new("event-listener", "broker.example.com", 8883);
+/// options.set_transport(Transport::tls_with_default_config());
+///
+/// let sink = MqttSink::new(options)
+///     .topic("chains/{chain}/{pallet}/{variant}")
+///     .qos(QoS::AtLeastOnce);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct MqttSink {
+    client: AsyncClient,
+    topic: String,
+    qos: QoS,
+    retain: bool,
+}
+
+impl std::fmt::Debug for MqttSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MqttSink")
+            .field("topic", &self.topic)
+            .field("qos", &self.qos)
+            .field("retain", &self.retain)
+            .finish()
+    }
+}
+
+impl MqttSink {
+    /// Connect to the broker with the options given. The connection is driven on
+    /// a Tokio task, which runs until the sink (and any clones of it) are dropped.
+    pub fn new(options: MqttOptions) -> Self {
+        let (client, mut event_loop) = AsyncClient::new(options, 64);
+        tokio::spawn(async move {
+            loop {
+                match event_loop.poll().await {
+                    Ok(_) => {}
+                    // Every client has gone, so there's nothing left to publish.
+                    Err(rumqttc::ConnectionError::RequestsDone) => break,
+                    Err(e) => {
+                        tracing::warn!("MQTT connection failed; reconnecting: {}", e);
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                    }
+                }
+            }
+        });
+        MqttSink {
+            client,
+            topic: "events/{pallet}/{variant}".into(),
+            qos: QoS::AtLeastOnce,
+            retain: false,
+        }
+    }
+
+    /// The topic to publish events to, in which `{chain}`, `{pallet}`,
+    /// `{variant}`, `{block_number}` and `{event_index}` are replaced with those of
+    /// the event. `{chain}` is replaced with `unknown` for listeners which weren't
+    /// given a chain identifier. Defaults to `events/{pallet}/{variant}`.
+    pub fn topic(mut self, template: impl Into<String>) -> Self {
+        self.topic = template.into();
+        self
+    }
+
+    /// The quality of service to publish with; [`QoS::AtLeastOnce`] by default.
+    pub fn qos(mut self, qos: QoS) -> Self {
+        self.qos = qos;
+        self
+    }
+
+    /// Whether the broker should keep the latest event of each topic for new
+    /// subscribers. `false` by default.
+    pub fn retain(mut self, retain: bool) -> Self {
+        self.retain = retain;
+        self
+    }
+}
+
+fn render_topic(template: &str, record: &EventRecord) -> String {
+    template
+        .replace("{chain}", record.chain.as_deref().unwrap_or("unknown"))
+        .replace("{pallet}", &record.pallet)
+        .replace("{variant}", &record.variant)
+        .replace("{block_number}", &record.block_number.to_string())
+        .replace("{event_index}", &record.event_index.to_string())
+}
+
+impl EventSink for MqttSink {
+    fn send(&self, records: Vec<EventRecord>) -> BoxFuture<'_, Result<(), Error>> {
+        async move {
+            for record in records {
+                let topic = render_topic(&self.topic, &record);
+                let payload = serde_json::to_vec(&record)?;
+                self.client
+                    .publish(topic, self.qos, self.retain, payload)
+                    .await
+                    .map_err(|e| Error::Sink(e.to_string()))?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::listener::sink::test_utils::record;
+
+    #[test]
+    fn topics_are_rendered_from_records() {
+        let record = EventRecord {
+            chain: Some("polkadot".into()),
+            ..record(12, 3)
+        };
+        assert_eq!(
+            render_topic("{chain}/{pallet}/{variant}/{block_number}-{event_index}", &record),
+            "polkadot/Balances/Transfer/12-3"
+        );
+        let record = EventRecord {
+            chain: None,
+            ..record
+        };
+        assert_eq!(render_topic("{chain}/{pallet}", &record), "unknown/Balances");
+    }
+}
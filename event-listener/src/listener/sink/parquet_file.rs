@@ -0,0 +1,278 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use super::{
+    EventRecord,
+    EventSink,
+};
+use crate::{
+    error::Error,
+    utils::unblock,
+};
+use arrow::{
+    array::{
+        ArrayRef,
+        BinaryArray,
+        StringArray,
+        TimestampMillisecondArray,
+        UInt32Array,
+        UInt64Array,
+    },
+    datatypes::{
+        DataType,
+        Field,
+        Schema,
+        SchemaRef,
+        TimeUnit,
+    },
+    record_batch::RecordBatch,
+};
+use futures::{
+    future::BoxFuture,
+    FutureExt,
+};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::Compression,
+    file::properties::WriterProperties,
+};
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::Arc,
+};
+
+/// An [`EventSink`] which writes events to Parquet files, partitioned by the date
+/// of their block and their pallet, so that they can be queried directly with
+/// DuckDB, Spark, or anything else which understands Hive style partitions:
+///
+/// ```text
+/// <dir>/date=2022-10-16/pallet=Balances/part-<block>-<event index>-<count>.parquet
+/// ```
+///
+/// Each call writes a file per partition, so use [`super::SinkHandler::batch()`]
+/// with large batches to avoid lots of small files. Files are named after the
+/// first event in them, so writing the same events again replaces the files
+/// rather than duplicating them. Events from blocks without a timestamp go in
+/// `date=unknown`.
+///
+/// ```no_run
+/// use event_listener::{
+///     listener::{ Concurrency, ParquetSink, SinkHandler },
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+/// use std::time::Duration;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let sink = SinkHandler::new(ParquetSink::new("events"))
+///     .batch(50_000, Duration::from_secs(60));
+///
+/// api.listener()
+///     .on_dynamic("Balances", "Transfer", sink)
+///     .concurrency(Concurrency::Parallel(50_000))
+///     .run()
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ParquetSink {
+    dir: PathBuf,
+    schema: SchemaRef,
+}
+
+impl ParquetSink {
+    /// Write files to the directory given, creating it if needed.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let schema = Schema::new(vec![
+            Field::new("chain", DataType::Utf8, true),
+            Field::new("block_number", DataType::UInt64, false),
+            Field::new("block_hash", DataType::Utf8, false),
+            Field::new(
+                "timestamp",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                true,
+            ),
+            Field::new("event_index", DataType::UInt32, false),
+            Field::new("extrinsic_index", DataType::UInt32, true),
+            Field::new("pallet", DataType::Utf8, false),
+            Field::new("variant", DataType::Utf8, false),
+            Field::new("fields", DataType::Utf8, false),
+            Field::new("field_bytes", DataType::Binary, false),
+            Field::new("accounts", DataType::Utf8, false),
+        ]);
+        ParquetSink {
+            dir: dir.into(),
+            schema: Arc::new(schema),
+        }
+    }
+
+    /// The schema of the files written; `fields` holds the decoded fields of the
+    /// event as JSON, and `accounts` a JSON array of hex encoded accounts.
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn write(&self, records: Vec<EventRecord>) -> Result<(), Error> {
+        let mut partitions: BTreeMap<(String, String), Vec<EventRecord>> = BTreeMap::new();
+        for record in records {
+            let date = record
+                .timestamp
+                .map(date_of)
+                .unwrap_or_else(|| "unknown".into());
+            partitions
+                .entry((date, record.pallet.clone()))
+                .or_default()
+                .push(record);
+        }
+
+        for ((date, pallet), records) in partitions {
+            let dir = self
+                .dir
+                .join(format!("date={}", date))
+                .join(format!("pallet={}", pallet));
+            std::fs::create_dir_all(&dir)?;
+            let name = format!(
+                "part-{}-{}-{}.parquet",
+                records[0].block_number,
+                records[0].event_index,
+                records.len()
+            );
+
+            // Write to a temporary file first, so that readers never see half a file.
+            let tmp_path = dir.join(format!(".{}.tmp", name));
+            let batch = self.batch(&records)?;
+            let props = WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .build();
+            let mut writer =
+                ArrowWriter::try_new(std::fs::File::create(&tmp_path)?, self.schema(), Some(props))
+                    .map_err(parquet_error)?;
+            writer.write(&batch).map_err(parquet_error)?;
+            writer.close().map_err(parquet_error)?;
+            std::fs::rename(&tmp_path, dir.join(name))?;
+        }
+        Ok(())
+    }
+
+    fn batch(&self, records: &[EventRecord]) -> Result<RecordBatch, Error> {
+        let field_bytes: Vec<Vec<u8>> = records
+            .iter()
+            .map(|r| hex::decode(r.field_bytes.trim_start_matches("0x")).unwrap_or_default())
+            .collect();
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(StringArray::from(
+                records.iter().map(|r| r.chain.as_deref()).collect::<Vec<_>>(),
+            )),
+            Arc::new(UInt64Array::from(
+                records.iter().map(|r| r.block_number).collect::<Vec<_>>(),
+            )),
+            Arc::new(StringArray::from(
+                records.iter().map(|r| &*r.block_hash).collect::<Vec<_>>(),
+            )),
+            Arc::new(TimestampMillisecondArray::from(
+                records
+                    .iter()
+                    .map(|r| r.timestamp.map(|t| t as i64))
+                    .collect::<Vec<_>>(),
+            )),
+            Arc::new(UInt32Array::from(
+                records.iter().map(|r| r.event_index).collect::<Vec<_>>(),
+            )),
+            Arc::new(UInt32Array::from(
+                records.iter().map(|r| r.extrinsic_index).collect::<Vec<_>>(),
+            )),
+            Arc::new(StringArray::from(
+                records.iter().map(|r| &*r.pallet).collect::<Vec<_>>(),
+            )),
+            Arc::new(StringArray::from(
+                records.iter().map(|r| &*r.variant).collect::<Vec<_>>(),
+            )),
+            Arc::new(StringArray::from(
+                records.iter().map(|r| r.fields.to_string()).collect::<Vec<_>>(),
+            )),
+            Arc::new(BinaryArray::from(
+                field_bytes.iter().map(|b| &b[..]).collect::<Vec<_>>(),
+            )),
+            Arc::new(StringArray::from(
+                records
+                    .iter()
+                    .map(|r| serde_json::to_string(&r.accounts))
+                    .collect::<Result<Vec<_>, _>>()?,
+            )),
+        ];
+        RecordBatch::try_new(self.schema(), columns).map_err(|e| Error::Sink(e.to_string()))
+    }
+}
+
+/// The UTC date (as `YYYY-MM-DD`) of a time in milliseconds since the Unix epoch.
+fn date_of(millis: u64) -> String {
+    // See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = (millis / 86_400_000) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+impl EventSink for ParquetSink {
+    fn send(&self, records: Vec<EventRecord>) -> BoxFuture<'_, Result<(), Error>> {
+        let sink = self.clone();
+        unblock(move || sink.write(records)).boxed()
+    }
+}
+
+fn parquet_error(e: parquet::errors::ParquetError) -> Error {
+    Error::Sink(e.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::listener::sink::test_utils::record;
+    use parquet::file::reader::{
+        FileReader,
+        SerializedFileReader,
+    };
+
+    #[test]
+    fn dates_are_worked_out_from_timestamps() {
+        assert_eq!(date_of(0), "1970-01-01");
+        assert_eq!(date_of(951_782_400_000), "2000-02-29");
+        assert_eq!(date_of(1_665_921_599_999), "2022-10-16");
+    }
+
+    #[tokio::test]
+    async fn events_are_written_to_partitions() {
+        let dir = std::env::temp_dir()
+            .join(format!("event-listener-parquet-{}", std::process::id()));
+        let event = |event_index, pallet: &str| {
+            EventRecord {
+                timestamp: Some(1_665_921_599_999),
+                pallet: pallet.into(),
+                ..record(1, event_index)
+            }
+        };
+        ParquetSink::new(&dir)
+            .send(vec![event(0, "A"), event(1, "B"), event(2, "A")])
+            .await
+            .unwrap();
+
+        let path = dir.join("date=2022-10-16/pallet=A/part-1-0-2.parquet");
+        let reader = SerializedFileReader::new(std::fs::File::open(path).unwrap()).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 2);
+        assert!(dir.join("date=2022-10-16/pallet=B/part-1-1-1.parquet").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,241 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use super::{
+    EventRecord,
+    EventSink,
+};
+use crate::error::Error;
+use futures::{
+    future::BoxFuture,
+    FutureExt,
+};
+use std::sync::Arc;
+use tokio_postgres::{
+    types::ToSql,
+    Client,
+    NoTls,
+};
+
+/// The changes made to the events table over time, applied in order. `{table}`
+/// is replaced with the name of the table.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS {table} (
+        id BIGSERIAL PRIMARY KEY,
+        event_id TEXT NOT NULL UNIQUE,
+        chain TEXT,
+        block_number BIGINT NOT NULL,
+        block_hash TEXT NOT NULL,
+        block_timestamp BIGINT,
+        event_index BIGINT NOT NULL,
+        extrinsic_index BIGINT,
+        pallet TEXT NOT NULL,
+        variant TEXT NOT NULL,
+        accounts TEXT[] NOT NULL DEFAULT '{}',
+        payload JSONB NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS {table}_block_number_idx ON {table} (block_number);
+    CREATE INDEX IF NOT EXISTS {table}_pallet_variant_idx ON {table} (pallet, variant);
+    CREATE INDEX IF NOT EXISTS {table}_accounts_idx ON {table} USING GIN (accounts);",
+];
+
+// The most rows to insert with one statement, keeping well within the limit of
+// 65535 parameters.
+const MAX_ROWS_PER_INSERT: usize = 1000;
+
+/// An [`EventSink`] which inserts events into a PostgreSQL table, named
+/// `event_listener_events` unless told otherwise.
+///
+/// The table is created when connecting if it doesn't exist, and updated if it
+/// was created by an older version of this crate. Each row has the whole
+/// [`EventRecord`] as `jsonb` in `payload`, along with indexed columns for the
+/// block number, pallet, variant and accounts of the event. Events which are
+/// already in the table (by [ID](EventRecord::id())) are skipped, so events can be
+/// written out again safely.
+///
+/// Use [`super::SinkHandler::batch()`] to insert events in batches.
+///
+/// ```no_run
+/// # use event_listener::listener::PostgresSink;
+/// # #[tokio::main]
+/// # async fn main() {
+/// let sink = PostgresSink::connect("host=localhost user=postgres dbname=chain")
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct PostgresSink {
+    client: Arc<Client>,
+    table: String,
+}
+
+impl std::fmt::Debug for PostgresSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PostgresSink")
+            .field("table", &self.table)
+            .finish()
+    }
+}
+
+impl PostgresSink {
+    /// Connect to the database described by the connection string given (see
+    /// [`tokio_postgres::Config`]), without TLS, and write events to the
+    /// `event_listener_events` table. The connection is driven on a Tokio task.
+    pub async fn connect(config: &str) -> Result<Self, Error> {
+        let (client, conn) = tokio_postgres::connect(config, NoTls)
+            .await
+            .map_err(postgres_error)?;
+        tokio::spawn(async move {
+            if let Err(e) = conn.await {
+                tracing::error!("Postgres connection failed: {}", e);
+            }
+        });
+        Self::from_client(client, "event_listener_events").await
+    }
+
+    /// Use a client which is already connected (for instance, over TLS), and
+    /// write events to the table with the name given.
+    pub async fn from_client(mut client: Client, table: &str) -> Result<Self, Error> {
+        if table.is_empty() || !table.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(Error::Sink(format!("Invalid table name '{}'", table)))
+        }
+        migrate(&mut client, table).await.map_err(postgres_error)?;
+        Ok(PostgresSink {
+            client: Arc::new(client),
+            table: table.to_owned(),
+        })
+    }
+
+    /// The client used to insert events, to query them with.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+}
+
+// Bring the table up to date, keeping track of which migrations have been applied
+// in a table of its own. The lock stops listeners starting at the same time from
+// applying the same migrations.
+async fn migrate(client: &mut Client, table: &str) -> Result<(), tokio_postgres::Error> {
+    let tx = client.transaction().await?;
+    tx.execute("SELECT pg_advisory_xact_lock(hashtext('event_listener_migrations'))", &[])
+        .await?;
+    tx.batch_execute(
+        "CREATE TABLE IF NOT EXISTS event_listener_migrations (
+            table_name TEXT PRIMARY KEY,
+            version INTEGER NOT NULL
+        )",
+    )
+    .await?;
+    let version: i32 = tx
+        .query_opt(
+            "SELECT version FROM event_listener_migrations WHERE table_name = $1",
+            &[&table],
+        )
+        .await?
+        .map(|row| row.get(0))
+        .unwrap_or(0);
+    for (idx, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        tracing::info!("Applying migration {} to {}", idx + 1, table);
+        tx.batch_execute(&migration.replace("{table}", table)).await?;
+    }
+    tx.execute(
+        "INSERT INTO event_listener_migrations (table_name, version) VALUES ($1, $2)
+        ON CONFLICT (table_name) DO UPDATE SET version = EXCLUDED.version",
+        &[&table, &(MIGRATIONS.len() as i32)],
+    )
+    .await?;
+    tx.commit().await
+}
+
+/// The values of a row, in the order of the columns inserted.
+struct Row {
+    event_id: String,
+    chain: Option<String>,
+    block_number: i64,
+    block_hash: String,
+    block_timestamp: Option<i64>,
+    event_index: i64,
+    extrinsic_index: Option<i64>,
+    pallet: String,
+    variant: String,
+    accounts: Vec<String>,
+    payload: serde_json::Value,
+}
+
+impl Row {
+    const COLUMNS: usize = 11;
+
+    fn new(record: EventRecord) -> Result<Self, Error> {
+        Ok(Row {
+            event_id: record.id(),
+            payload: serde_json::to_value(&record)?,
+            chain: record.chain,
+            block_number: record.block_number as i64,
+            block_hash: record.block_hash,
+            block_timestamp: record.timestamp.map(|t| t as i64),
+            event_index: record.event_index.into(),
+            extrinsic_index: record.extrinsic_index.map(Into::into),
+            pallet: record.pallet,
+            variant: record.variant,
+            accounts: record.accounts,
+        })
+    }
+
+    fn params(&self) -> [&(dyn ToSql + Sync); Row::COLUMNS] {
+        [
+            &self.event_id,
+            &self.chain,
+            &self.block_number,
+            &self.block_hash,
+            &self.block_timestamp,
+            &self.event_index,
+            &self.extrinsic_index,
+            &self.pallet,
+            &self.variant,
+            &self.accounts,
+            &self.payload,
+        ]
+    }
+}
+
+impl EventSink for PostgresSink {
+    fn send(&self, records: Vec<EventRecord>) -> BoxFuture<'_, Result<(), Error>> {
+        async move {
+            let rows = records
+                .into_iter()
+                .map(Row::new)
+                .collect::<Result<Vec<_>, _>>()?;
+            for chunk in rows.chunks(MAX_ROWS_PER_INSERT) {
+                let placeholders: Vec<_> = (0..chunk.len())
+                    .map(|row| {
+                        let params: Vec<_> = (1..=Row::COLUMNS)
+                            .map(|col| format!("${}", row * Row::COLUMNS + col))
+                            .collect();
+                        format!("({})", params.join(", "))
+                    })
+                    .collect();
+                let query = format!(
+                    "INSERT INTO {} (event_id, chain, block_number, block_hash, block_timestamp,
+                        event_index, extrinsic_index, pallet, variant, accounts, payload)
+                    VALUES {}
+                    ON CONFLICT (event_id) DO NOTHING",
+                    self.table,
+                    placeholders.join(", ")
+                );
+                let params: Vec<_> = chunk.iter().flat_map(Row::params).collect();
+                self.client
+                    .execute(query.as_str(), &params)
+                    .await
+                    .map_err(postgres_error)?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+fn postgres_error(e: tokio_postgres::Error) -> Error {
+    Error::Sink(e.to_string())
+}
@@ -0,0 +1,138 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use super::{
+    EventRecord,
+    EventSink,
+};
+use crate::error::Error;
+use futures::{
+    future::BoxFuture,
+    FutureExt,
+};
+use redis::aio::ConnectionManager;
+
+/// An [`EventSink`] which adds each event to a Redis stream with `XADD`.
+///
+/// Each entry has the fields `pallet`, `variant` and `blockNumber`, the event
+/// itself as JSON in `event`, and the [ID of the event](EventRecord::id()) in
+/// `id` (see [`RedisStreamSink::id_field()`]), so that consumers can skip over
+/// events which are added more than once, such as when the listener carries on
+/// from a checkpoint after a restart.
+///
+/// ```no_run
+/// # use event_listener::listener::RedisStreamSink;
+/// # #[tokio::main]
+/// # async fn main() {
+/// let sink = RedisStreamSink::connect("redis://127.0.0.1/", "chain-events")
+///     .await
+///     .unwrap()
+///     .max_len(100_000);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RedisStreamSink {
+    conn: ConnectionManager,
+    stream: String,
+    max_len: Option<usize>,
+    exact_trim: bool,
+    id_field: String,
+}
+
+impl std::fmt::Debug for RedisStreamSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisStreamSink")
+            .field("stream", &self.stream)
+            .field("max_len", &self.max_len)
+            .field("exact_trim", &self.exact_trim)
+            .field("id_field", &self.id_field)
+            .finish()
+    }
+}
+
+impl RedisStreamSink {
+    /// Connect to the Redis server at the URL given, to add events to the stream
+    /// with the key given. The connection is made again if it's lost.
+    pub async fn connect(url: &str, stream: impl Into<String>) -> Result<Self, Error> {
+        let client = redis::Client::open(url).map_err(redis_error)?;
+        let conn = client
+            .get_tokio_connection_manager()
+            .await
+            .map_err(redis_error)?;
+        Ok(Self::from_connection(conn, stream))
+    }
+
+    /// Use a connection which has already been made.
+    pub fn from_connection(conn: ConnectionManager, stream: impl Into<String>) -> Self {
+        RedisStreamSink {
+            conn,
+            stream: stream.into(),
+            max_len: None,
+            exact_trim: false,
+            id_field: "id".into(),
+        }
+    }
+
+    /// Trim the stream to about this many entries as events are added. Redis trims
+    /// whole nodes of the stream at a time, so it may be left a little longer,
+    /// unless [`RedisStreamSink::exact_trim()`] is set. The stream isn't trimmed
+    /// by default.
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = Some(max_len);
+        self
+    }
+
+    /// Trim the stream to exactly [`RedisStreamSink::max_len()`] entries, which is
+    /// slower than trimming to about that many.
+    pub fn exact_trim(mut self, exact: bool) -> Self {
+        self.exact_trim = exact;
+        self
+    }
+
+    /// The name of the field holding the ID of the event; `id` by default.
+    pub fn id_field(mut self, field: impl Into<String>) -> Self {
+        self.id_field = field.into();
+        self
+    }
+}
+
+impl EventSink for RedisStreamSink {
+    fn send(&self, records: Vec<EventRecord>) -> BoxFuture<'_, Result<(), Error>> {
+        async move {
+            let mut pipe = redis::pipe();
+            for record in &records {
+                let cmd = pipe.cmd("XADD").arg(&self.stream);
+                if let Some(max_len) = self.max_len {
+                    cmd.arg("MAXLEN")
+                        .arg(if self.exact_trim { "=" } else { "~" })
+                        .arg(max_len);
+                }
+                cmd.arg("*")
+                    .arg(&self.id_field)
+                    .arg(record.id())
+                    .arg("pallet")
+                    .arg(&record.pallet)
+                    .arg("variant")
+                    .arg(&record.variant)
+                    .arg("blockNumber")
+                    .arg(record.block_number)
+                    .arg("event")
+                    .arg(serde_json::to_string(record)?);
+                if let Some(traceparent) = &record.traceparent {
+                    cmd.arg("traceparent").arg(traceparent);
+                }
+                cmd.ignore();
+            }
+            let mut conn = self.conn.clone();
+            pipe.query_async::<_, ()>(&mut conn)
+                .await
+                .map_err(redis_error)
+        }
+        .boxed()
+    }
+}
+
+fn redis_error(e: redis::RedisError) -> Error {
+    Error::Sink(e.to_string())
+}
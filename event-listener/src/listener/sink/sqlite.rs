@@ -0,0 +1,221 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use super::{
+    EventRecord,
+    EventSink,
+};
+use crate::error::Error;
+use futures::{
+    future::{
+        self,
+        BoxFuture,
+    },
+    FutureExt,
+};
+use parking_lot::Mutex;
+use rusqlite::{
+    params,
+    Connection,
+    OptionalExtension,
+};
+use std::sync::Arc;
+
+/// The changes made to the events tables over time, applied in order. `{table}` is
+/// replaced with the name of the table. The columns match those of
+/// [`super::PostgresSink`]; since SQLite has no arrays, accounts are also kept in
+/// a table of their own so that they can be indexed.
+const MIGRATIONS: &[&str] = &["CREATE TABLE IF NOT EXISTS {table} (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        event_id TEXT NOT NULL UNIQUE,
+        chain TEXT,
+        block_number INTEGER NOT NULL,
+        block_hash TEXT NOT NULL,
+        block_timestamp INTEGER,
+        event_index INTEGER NOT NULL,
+        extrinsic_index INTEGER,
+        pallet TEXT NOT NULL,
+        variant TEXT NOT NULL,
+        accounts TEXT NOT NULL DEFAULT '[]',
+        payload TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS {table}_block_number_idx ON {table} (block_number);
+    CREATE INDEX IF NOT EXISTS {table}_pallet_variant_idx ON {table} (pallet, variant);
+    CREATE TABLE IF NOT EXISTS {table}_accounts (
+        event_id TEXT NOT NULL,
+        account TEXT NOT NULL,
+        PRIMARY KEY (account, event_id)
+    );"];
+
+/// An [`EventSink`] which inserts events into a table of an SQLite database, named
+/// `event_listener_events` unless told otherwise. The table is laid out in the
+/// same way as that of [`super::PostgresSink`], except that the `accounts` and
+/// `payload` columns hold JSON text, and the accounts of each event are also
+/// kept in a `<table>_accounts` table.
+///
+/// As with [`super::PostgresSink`], the table is created or updated when opening
+/// the database, and events which are already in the table are skipped.
+#[derive(Clone)]
+pub struct SqliteSink {
+    conn: Arc<Mutex<Connection>>,
+    table: String,
+}
+
+impl std::fmt::Debug for SqliteSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SqliteSink")
+            .field("table", &self.table)
+            .finish()
+    }
+}
+
+impl SqliteSink {
+    /// Open (or create) the database at the path given, and write events to the
+    /// `event_listener_events` table.
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self, Error> {
+        let conn = Connection::open(path).map_err(sqlite_error)?;
+        Self::from_connection(conn, "event_listener_events")
+    }
+
+    /// Use a connection which has already been opened, and write events to the
+    /// table with the name given.
+    pub fn from_connection(mut conn: Connection, table: &str) -> Result<Self, Error> {
+        if table.is_empty() || !table.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(Error::Sink(format!("Invalid table name '{}'", table)))
+        }
+        migrate(&mut conn, table).map_err(sqlite_error)?;
+        Ok(SqliteSink {
+            conn: Arc::new(Mutex::new(conn)),
+            table: table.to_owned(),
+        })
+    }
+
+    fn insert(&self, records: Vec<EventRecord>) -> Result<(), Error> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(sqlite_error)?;
+        {
+            let mut insert_event = tx
+                .prepare_cached(&format!(
+                    "INSERT OR IGNORE INTO {} (event_id, chain, block_number, block_hash,
+                        block_timestamp, event_index, extrinsic_index, pallet, variant,
+                        accounts, payload)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                    self.table
+                ))
+                .map_err(sqlite_error)?;
+            let mut insert_account = tx
+                .prepare_cached(&format!(
+                    "INSERT OR IGNORE INTO {}_accounts (event_id, account) VALUES (?1, ?2)",
+                    self.table
+                ))
+                .map_err(sqlite_error)?;
+            for record in &records {
+                let event_id = record.id();
+                insert_event
+                    .execute(params![
+                        event_id,
+                        record.chain,
+                        record.block_number as i64,
+                        record.block_hash,
+                        record.timestamp.map(|t| t as i64),
+                        record.event_index,
+                        record.extrinsic_index,
+                        record.pallet,
+                        record.variant,
+                        serde_json::to_string(&record.accounts)?,
+                        serde_json::to_string(record)?,
+                    ])
+                    .map_err(sqlite_error)?;
+                for account in &record.accounts {
+                    insert_account
+                        .execute(params![event_id, account])
+                        .map_err(sqlite_error)?;
+                }
+            }
+        }
+        tx.commit().map_err(sqlite_error)
+    }
+}
+
+// Bring the tables up to date, keeping track of which migrations have been
+// applied in a table of its own.
+fn migrate(conn: &mut Connection, table: &str) -> Result<(), rusqlite::Error> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS event_listener_migrations (
+            table_name TEXT PRIMARY KEY,
+            version INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    let version: i64 = tx
+        .query_row(
+            "SELECT version FROM event_listener_migrations WHERE table_name = ?1",
+            [table],
+            |row| row.get(0),
+        )
+        .optional()?
+        .unwrap_or(0);
+    for (idx, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        tracing::info!("Applying migration {} to {}", idx + 1, table);
+        tx.execute_batch(&migration.replace("{table}", table))?;
+    }
+    tx.execute(
+        "INSERT OR REPLACE INTO event_listener_migrations (table_name, version) VALUES (?1, ?2)",
+        params![table, MIGRATIONS.len() as i64],
+    )?;
+    tx.commit()
+}
+
+impl EventSink for SqliteSink {
+    fn send(&self, records: Vec<EventRecord>) -> BoxFuture<'_, Result<(), Error>> {
+        future::ready(self.insert(records)).boxed()
+    }
+}
+
+fn sqlite_error(e: rusqlite::Error) -> Error {
+    Error::Sink(e.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::listener::sink::test_utils::record;
+
+    fn with_accounts(event_index: u32, accounts: &[&str]) -> EventRecord {
+        EventRecord {
+            accounts: accounts.iter().map(|a| a.to_string()).collect(),
+            ..record(4, event_index)
+        }
+    }
+
+    #[tokio::test]
+    async fn events_are_inserted_once() {
+        let sink = SqliteSink::from_connection(
+            Connection::open_in_memory().unwrap(),
+            "events",
+        )
+        .unwrap();
+        sink.send(vec![with_accounts(0, &["0xaa", "0xbb"]), with_accounts(1, &["0xaa"])])
+            .await
+            .unwrap();
+        // Sending an event again does nothing.
+        sink.send(vec![with_accounts(1, &["0xaa"])]).await.unwrap();
+
+        let conn = sink.conn.lock();
+        let count = |sql: &str| conn.query_row(sql, [], |row| row.get::<_, i64>(0)).unwrap();
+        assert_eq!(count("SELECT COUNT(*) FROM events"), 2);
+        assert_eq!(
+            count("SELECT COUNT(*) FROM events_accounts WHERE account = '0xaa'"),
+            2
+        );
+        let payload: String = conn
+            .query_row("SELECT payload FROM events WHERE event_index = 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        let stored: EventRecord = serde_json::from_str(&payload).unwrap();
+        assert_eq!(stored, with_accounts(1, &["0xaa"]));
+    }
+}
@@ -0,0 +1,81 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Exporting the traces of a listener with OpenTelemetry.
+//!
+//! With the `opentelemetry` feature, the `tracing` spans that listeners create
+//! (see the `instrument` feature) can be exported over OTLP with [`otlp_layer()`],
+//! and the trace context of each event is handed on to the systems that events
+//! are sent to, as the W3C `traceparent` of [`super::EventRecord`]s (which sinks
+//! such as [`super::RedisStreamSink`] write alongside the event) and as the
+//! `traceparent` header of webhooks. Downstream services can then carry on the
+//! trace, from the block an event was emitted in to wherever it ends up.
+
+use crate::error::Error;
+use opentelemetry::{
+    propagation::TextMapPropagator,
+    sdk::{
+        propagation::TraceContextPropagator,
+        trace::{
+            self,
+            Tracer,
+        },
+        Resource,
+    },
+    KeyValue,
+};
+use std::collections::HashMap;
+use tracing_opentelemetry::{
+    OpenTelemetryLayer,
+    OpenTelemetrySpanExt,
+};
+use tracing_subscriber::registry::LookupSpan;
+
+/// A `tracing` layer which exports spans over OTLP (with gRPC) to the collector
+/// at the endpoint given, such as `http://localhost:4317`, as the service named.
+/// Spans are exported in batches on the Tokio runtime, so this needs to be called
+/// from within one.
+///
+/// ```no_run
+/// use tracing_subscriber::prelude::*;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let otlp = event_listener::listener::telemetry::otlp_layer(
+///     "transfer-indexer",
+///     "http://localhost:4317",
+/// )
+/// .unwrap();
+/// tracing_subscriber::registry().with(otlp).init();
+/// # }
+/// ```
+pub fn otlp_layer<S>(
+    service_name: &str,
+    endpoint: &str,
+) -> Result<OpenTelemetryLayer<S, Tracer>, Error>
+where
+    S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+{
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(trace::config().with_resource(Resource::new(vec![
+            KeyValue::new("service.name", service_name.to_owned()),
+        ])))
+        .install_batch(opentelemetry::runtime::Tokio)
+        .map_err(|e| Error::Other(e.to_string()))?;
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+/// The W3C `traceparent` of the current span, if it's being exported.
+pub(crate) fn current_traceparent() -> Option<String> {
+    let context = tracing::Span::current().context();
+    let mut carrier = HashMap::new();
+    TraceContextPropagator::new().inject_context(&context, &mut carrier);
+    carrier.remove("traceparent")
+}
@@ -0,0 +1,464 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Turning events into messages for people to read, for alerts and notifications.
+
+use super::{
+    handler::EventContext,
+    rules::lookup,
+};
+use crate::{
+    config::{
+        to_ss58,
+        DEFAULT_SS58_FORMAT,
+    },
+    error::Error,
+    events::EventDetails,
+    rpc::SystemProperties,
+    utils::{
+        composite_values,
+        to_hex,
+        value_as_bytes,
+        value_as_u128,
+    },
+    Config,
+};
+use codec::Encode;
+use scale_value::{
+    scale::TypeId,
+    Primitive,
+    Value,
+    ValueDef,
+};
+
+/// A message with placeholders in, which is filled in from the fields of an event
+/// and the chain that it was emitted on.
+///
+/// Placeholders look like `{{ name }}` or `{{ name | filter | filter }}`. The names
+/// available are `chain`, `pallet`, `variant`, `block_number`, `block_hash`,
+/// `timestamp`, `event_index`, `extrinsic_index`, `docs` (the documentation of
+/// the event from the metadata), `symbol` and `decimals` (of the chain's native
+/// token), or else `fields.` followed by the `.` separated path to a field of
+/// the event (such as `fields.amount`, or `fields.dest.Id` to look inside an
+/// enum). Numbers are written out in full, byte arrays as hex, and enum variants
+/// by name. Missing values are left empty.
+///
+/// The filters are:
+///
+/// - `balance`: write a number as an amount of the native token, such as
+///   `1.5 DOT`.
+/// - `ss58`: write an account as an SS58 address, with the chain's prefix.
+/// - `hex`: write a number as hex.
+/// - `short`: shorten anything longer than 13 characters to its first 6 and
+///   last 4, such as `0x1234…cdef`.
+/// - `first_line`: keep only the first line, such as of `docs`.
+///
+/// ```
+/// # use event_listener::listener::MessageTemplate;
+/// let template = MessageTemplate::parse(
+///     "{{ fields.from | ss58 | short }} sent {{ fields.amount | balance }} \
+///      to {{ fields.to | ss58 | short }} in block #{{ block_number }}",
+/// )
+/// .unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessageTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Placeholder { name: Name, filters: Vec<Filter> },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Name {
+    Chain,
+    Pallet,
+    Variant,
+    BlockNumber,
+    BlockHash,
+    Timestamp,
+    EventIndex,
+    ExtrinsicIndex,
+    Docs,
+    Symbol,
+    Decimals,
+    Field(Vec<String>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Filter {
+    Balance,
+    Ss58,
+    Hex,
+    Short,
+    FirstLine,
+}
+
+/// The properties of a chain that messages are written with, usually from
+/// [`ChainProperties::from_properties()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChainProperties {
+    /// The symbol of the native token, if it has one.
+    pub token_symbol: Option<String>,
+    /// The number of decimals of the native token.
+    pub token_decimals: u8,
+    /// The SS58 prefix of addresses.
+    pub ss58_format: u16,
+}
+
+impl Default for ChainProperties {
+    fn default() -> Self {
+        ChainProperties {
+            token_symbol: None,
+            token_decimals: 0,
+            ss58_format: DEFAULT_SS58_FORMAT,
+        }
+    }
+}
+
+impl ChainProperties {
+    /// Take the token symbol and decimals and SS58 prefix from the properties of
+    /// a chain, as handed back from [`crate::rpc::Rpc::system_properties()`]. For
+    /// chains with several tokens, the first is the native token.
+    pub fn from_properties(properties: &SystemProperties) -> Self {
+        // Chains with several tokens give arrays of symbols and decimals.
+        let property = |name: &str| {
+            match properties.get(name)? {
+                serde_json::Value::Array(values) => values.first(),
+                value => Some(value),
+            }
+        };
+        let defaults = ChainProperties::default();
+        ChainProperties {
+            token_symbol: property("tokenSymbol")
+                .and_then(|v| v.as_str())
+                .map(ToOwned::to_owned),
+            token_decimals: property("tokenDecimals")
+                .and_then(|v| v.as_u64())
+                .map_or(defaults.token_decimals, |v| v as u8),
+            ss58_format: property("ss58Format")
+                .and_then(|v| v.as_u64())
+                .map_or(defaults.ss58_format, |v| v as u16),
+        }
+    }
+}
+
+impl MessageTemplate {
+    /// Parse a template. Fails if a placeholder isn't closed, or has a name or
+    /// filter which isn't known.
+    pub fn parse(source: &str) -> Result<Self, Error> {
+        let mut parts = Vec::new();
+        let mut rest = source;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_owned()));
+            }
+            let len = rest[start..].find("}}").ok_or_else(|| {
+                Error::Template(format!("Unclosed placeholder in '{}'", source))
+            })?;
+            parts.push(parse_placeholder(&rest[start + 2..start + len])?);
+            rest = &rest[start + len + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_owned()));
+        }
+        Ok(MessageTemplate { parts })
+    }
+
+    /// Fill in the template from an event and the context it was handed to a
+    /// handler with.
+    pub fn render<T: Config, Client>(
+        &self,
+        ctx: &EventContext<T, Client>,
+        event: &EventDetails,
+        chain: &ChainProperties,
+    ) -> String {
+        let mut message = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => message.push_str(text),
+                Part::Placeholder { name, filters } => {
+                    let value = filters
+                        .iter()
+                        .fold(resolve(name, ctx, event, chain), |value, filter| {
+                            apply(*filter, value, chain)
+                        });
+                    message.push_str(&value.into_string());
+                }
+            }
+        }
+        message
+    }
+}
+
+fn parse_placeholder(inner: &str) -> Result<Part, Error> {
+    let mut segments = inner.split('|').map(str::trim);
+    let name = match segments.next().unwrap_or_default() {
+        "chain" => Name::Chain,
+        "pallet" => Name::Pallet,
+        "variant" => Name::Variant,
+        "block_number" => Name::BlockNumber,
+        "block_hash" => Name::BlockHash,
+        "timestamp" => Name::Timestamp,
+        "event_index" => Name::EventIndex,
+        "extrinsic_index" => Name::ExtrinsicIndex,
+        "docs" => Name::Docs,
+        "symbol" => Name::Symbol,
+        "decimals" => Name::Decimals,
+        name => {
+            match name.strip_prefix("fields.") {
+                Some(path) if !path.is_empty() => {
+                    Name::Field(path.split('.').map(ToOwned::to_owned).collect())
+                }
+                _ => return Err(Error::Template(format!("Unknown name '{}'", name))),
+            }
+        }
+    };
+    let filters = segments
+        .map(|filter| {
+            match filter {
+                "balance" => Ok(Filter::Balance),
+                "ss58" => Ok(Filter::Ss58),
+                "hex" => Ok(Filter::Hex),
+                "short" => Ok(Filter::Short),
+                "first_line" => Ok(Filter::FirstLine),
+                _ => Err(Error::Template(format!("Unknown filter '{}'", filter))),
+            }
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Part::Placeholder { name, filters })
+}
+
+/// A value on its way to being written into a message.
+enum Resolved {
+    Number(u128),
+    Bytes(Vec<u8>),
+    Text(String),
+    Missing,
+}
+
+impl Resolved {
+    fn into_string(self) -> String {
+        match self {
+            Resolved::Number(n) => n.to_string(),
+            Resolved::Bytes(bytes) => to_hex(bytes),
+            Resolved::Text(text) => text,
+            Resolved::Missing => String::new(),
+        }
+    }
+}
+
+fn resolve<T: Config, Client>(
+    name: &Name,
+    ctx: &EventContext<T, Client>,
+    event: &EventDetails,
+    chain: &ChainProperties,
+) -> Resolved {
+    let text = |s: &str| Resolved::Text(s.to_owned());
+    match name {
+        Name::Chain => ctx.chain_id().map_or(Resolved::Missing, text),
+        Name::Pallet => text(ctx.pallet_name()),
+        Name::Variant => text(ctx.variant_name()),
+        Name::BlockNumber => {
+            let number: u64 = ctx.block_number().into();
+            Resolved::Number(number.into())
+        }
+        Name::BlockHash => Resolved::Bytes(ctx.block_hash().encode()),
+        Name::Timestamp => {
+            ctx.timestamp()
+                .map_or(Resolved::Missing, |t| Resolved::Number(t.into()))
+        }
+        Name::EventIndex => Resolved::Number(ctx.event_index().into()),
+        Name::ExtrinsicIndex => {
+            ctx.extrinsic_index()
+                .map_or(Resolved::Missing, |i| Resolved::Number(i.into()))
+        }
+        Name::Docs => {
+            let docs = event.event_metadata().docs();
+            let lines: Vec<_> = docs.iter().map(|line| line.trim()).collect();
+            Resolved::Text(lines.join("\n"))
+        }
+        Name::Symbol => chain.token_symbol.as_deref().map_or(Resolved::Missing, text),
+        Name::Decimals => Resolved::Number(chain.token_decimals.into()),
+        Name::Field(path) => {
+            lookup(ctx.field_values(), path).map_or(Resolved::Missing, resolve_value)
+        }
+    }
+}
+
+fn resolve_value(value: &Value<TypeId>) -> Resolved {
+    if let Some(n) = value_as_u128(value) {
+        return Resolved::Number(n)
+    }
+    if let Some(bytes) = value_as_bytes(value) {
+        return Resolved::Bytes(bytes)
+    }
+    match &value.value {
+        ValueDef::Primitive(Primitive::Bool(b)) => Resolved::Text(b.to_string()),
+        ValueDef::Primitive(Primitive::Char(c)) => Resolved::Text(c.to_string()),
+        ValueDef::Primitive(Primitive::String(s)) => Resolved::Text(s.clone()),
+        ValueDef::Primitive(Primitive::I128(n)) => Resolved::Text(n.to_string()),
+        ValueDef::Variant(v) => {
+            let mut vals = composite_values(&v.values);
+            match (vals.next(), vals.next()) {
+                (None, _) => Resolved::Text(v.name.clone()),
+                (Some(val), None) => resolve_value(val),
+                _ => json(value),
+            }
+        }
+        _ => json(value),
+    }
+}
+
+fn json(value: &Value<TypeId>) -> Resolved {
+    serde_json::to_string(value).map_or(Resolved::Missing, Resolved::Text)
+}
+
+fn apply(filter: Filter, value: Resolved, chain: &ChainProperties) -> Resolved {
+    match (filter, value) {
+        (Filter::Balance, Resolved::Number(n)) => {
+            let symbol = chain.token_symbol.as_deref();
+            Resolved::Text(format_balance(n, chain.token_decimals, symbol))
+        }
+        (Filter::Ss58, Resolved::Bytes(bytes)) if bytes.len() == 32 => {
+            Resolved::Text(to_ss58(&bytes, chain.ss58_format))
+        }
+        (Filter::Hex, Resolved::Number(n)) => Resolved::Text(format!("{:#x}", n)),
+        (Filter::Short, value) => {
+            let s = value.into_string();
+            let chars: Vec<char> = s.chars().collect();
+            if chars.len() <= 13 {
+                return Resolved::Text(s)
+            }
+            let start: String = chars[..6].iter().collect();
+            let end: String = chars[chars.len() - 4..].iter().collect();
+            Resolved::Text(format!("{}…{}", start, end))
+        }
+        (Filter::FirstLine, value) => {
+            let s = value.into_string();
+            Resolved::Text(s.lines().next().unwrap_or_default().to_owned())
+        }
+        (_, value) => value,
+    }
+}
+
+/// Write an amount in the smallest unit of a token with the given number of
+/// decimals, leaving off any trailing zeros after the point.
+pub(crate) fn format_balance(amount: u128, decimals: u8, symbol: Option<&str>) -> String {
+    let unit = 10u128.checked_pow(decimals.into());
+    let mut s = match unit {
+        Some(unit) if unit > 1 => {
+            let fraction = format!("{:0width$}", amount % unit, width = decimals as usize);
+            let fraction = fraction.trim_end_matches('0');
+            if fraction.is_empty() {
+                (amount / unit).to_string()
+            } else {
+                format!("{}.{}", amount / unit, fraction)
+            }
+        }
+        _ => amount.to_string(),
+    };
+    if let Some(symbol) = symbol {
+        s.push(' ');
+        s.push_str(symbol);
+    }
+    s
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        events::{
+            test_utils::{
+                event_record,
+                events,
+                metadata,
+            },
+            Phase,
+        },
+        listener::handler::BlockContext,
+        SubstrateConfig,
+    };
+    use codec::{
+        Decode,
+        Encode,
+    };
+    use scale_info::TypeInfo;
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Event {
+        Transfer {
+            from: [u8; 32],
+            amount: u128,
+            status: Status,
+        },
+    }
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Status {
+        Free,
+    }
+
+    #[test]
+    fn balances_are_formatted_with_decimals() {
+        assert_eq!(format_balance(15_000_000_000, 10, Some("DOT")), "1.5 DOT");
+        assert_eq!(format_balance(20_000_000_000, 10, None), "2");
+        assert_eq!(format_balance(1, 12, Some("KSM")), "0.000000000001 KSM");
+        assert_eq!(format_balance(42, 0, None), "42");
+    }
+
+    #[test]
+    fn templates_are_rendered_from_events() {
+        let events = events(
+            metadata::<Event>(),
+            vec![event_record(
+                Phase::ApplyExtrinsic(2),
+                Event::Transfer {
+                    from: [1; 32],
+                    amount: 25_000_000_000,
+                    status: Status::Free,
+                },
+            )],
+        );
+        let event = events.iter().next().unwrap().unwrap();
+        let block = BlockContext {
+            hash: Default::default(),
+            number: 7,
+            timestamp: None,
+            chain: Some("polkadot".into()),
+        };
+        let ctx = EventContext::<SubstrateConfig, ()>::new((), block, &event);
+        let chain = ChainProperties {
+            token_symbol: Some("DOT".into()),
+            token_decimals: 10,
+            ss58_format: 0,
+        };
+
+        let template = MessageTemplate::parse(
+            "[{{chain}}] {{ fields.from | ss58 | short }} sent {{ fields.amount | balance }} \
+             ({{ fields.status }}) in #{{ block_number }}/{{ extrinsic_index }}{{ timestamp }}",
+        )
+        .unwrap();
+        let from = to_ss58(&[1; 32], 0);
+        assert_eq!(
+            template.render(&ctx, &event, &chain),
+            format!(
+                "[polkadot] {}…{} sent 2.5 DOT (Free) in #7/2",
+                &from[..6],
+                &from[from.len() - 4..]
+            )
+        );
+    }
+
+    #[test]
+    fn bad_templates_are_rejected() {
+        assert!(MessageTemplate::parse("{{ pallet").is_err());
+        assert!(MessageTemplate::parse("{{ nonsense }}").is_err());
+        assert!(MessageTemplate::parse("{{ fields.amount | shout }}").is_err());
+    }
+}
@@ -0,0 +1,76 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::{
+    collections::HashSet,
+    sync::Arc,
+};
+
+/// Don't bother pruning until there are at least this many strings.
+const MIN_PRUNE_LEN: usize = 1024;
+
+// Every string interned so far. Metadata of the same runtime, or of different
+// versions of a runtime, repeats the same names and docs over and over, so this
+// is shared by all of it.
+static STRINGS: Lazy<Mutex<Interner>> = Lazy::new(Default::default);
+
+/// A copy of the string given that's shared with any other metadata that has
+/// interned the same string, so that it's only kept in memory once.
+pub fn intern(s: &str) -> Arc<str> {
+    STRINGS.lock().intern(s)
+}
+
+#[derive(Default)]
+struct Interner {
+    strings: HashSet<Arc<str>>,
+    // How many strings to let there be before pruning the unused ones again.
+    prune_at: usize,
+}
+
+impl Interner {
+    fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(interned) = self.strings.get(s) {
+            return interned.clone()
+        }
+
+        // Strings that nothing but the interner holds on to are from metadata
+        // that has since been dropped, so forget them once the set has doubled
+        // in size.
+        if self.strings.len() >= self.prune_at.max(MIN_PRUNE_LEN) {
+            self.strings.retain(|s| Arc::strong_count(s) > 1);
+            self.prune_at = self.strings.len() * 2;
+        }
+        let interned: Arc<str> = s.into();
+        self.strings.insert(interned.clone());
+        interned
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn equal_strings_are_shared() {
+        let mut interner = Interner::default();
+        let a = interner.intern("amount");
+        let b = interner.intern(&String::from("amount"));
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &interner.intern("who")));
+    }
+
+    #[test]
+    fn unused_strings_are_pruned() {
+        let mut interner = Interner::default();
+        for i in 0..MIN_PRUNE_LEN {
+            interner.intern(&i.to_string());
+        }
+        let kept = interner.intern("kept");
+        // Nothing else holds on to the numbers, so they're all pruned.
+        assert_eq!(interner.strings.len(), 1);
+        assert!(interner.strings.contains(&kept));
+    }
+}
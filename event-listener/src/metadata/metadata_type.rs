@@ -15,12 +15,18 @@ use frame_metadata::{
 	RuntimeMetadataV14,
 };
 
-use crate::metadata::metadata_utils::{get_storage_hash, NotFound};
+use crate::metadata::metadata_utils::{get_constant_hash, get_storage_hash, NotFound};
+use frame_metadata::PalletConstantMetadata;
+use scale_info::form::PortableForm;
 
-use super::hash_cache::HashCache;
+use super::{
+	hash_cache::HashCache,
+	interner::intern,
+};
 
 /// Metadata error originated from inspecting the internal representation of the runtime metadata.
 #[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum MetadataError {
 	/// Module is not in metadata.
 	#[error("Pallet not found")]
@@ -31,12 +37,21 @@ pub enum MetadataError {
 	/// Event is not in metadata.
 	#[error("Pallet {0}, Event {0} not found")]
 	EventNotFound(u8, u8),
+	/// Event with the given pallet and event names is not in metadata.
+	#[error("Event {0}::{1} not found")]
+	EventNameNotFound(String, String),
+	/// Error is not in metadata.
+	#[error("Pallet {0}, Error {1} not found")]
+	ErrorNotFound(u8, u8),
 	/// Storage is not in metadata.
 	#[error("Storage not found")]
 	StorageNotFound,
 	/// Constant is not in metadata.
 	#[error("Constant not found")]
 	ConstantNotFound,
+	/// The shape of an item in the metadata differs from what was expected.
+	#[error("Pallet {0}, Item {1} has incompatible metadata")]
+	IncompatibleMetadata(String, String),
 }
 
 // We hide the innards behind an Arc so that it's easy to clone and share.
@@ -44,7 +59,11 @@ pub enum MetadataError {
 struct MetadataInner {
 	metadata: RuntimeMetadataV14,
 	events: HashMap<(u8, u8), EventMetadata>,
+	calls: HashMap<(u8, u8), CallMetadata>,
+	errors: HashMap<(u8, u8), ErrorMetadata>,
+	extrinsic: ExtrinsicMetadata,
 	cached_storage_hashes: HashCache,
+	cached_constant_hashes: HashCache,
 }
 
 /// A representation of the runtime metadata received from a node.
@@ -68,11 +87,115 @@ impl Metadata {
 		Ok(event)
 	}
 
+	/// Returns the pallet and event indices, and the metadata, of the event with the given
+	/// pallet and event names.
+	pub fn event_by_name(
+		&self,
+		pallet: &str,
+		event: &str,
+	) -> Result<((u8, u8), &EventMetadata), MetadataError> {
+		self.inner
+			.events
+			.iter()
+			.find(|(_, e)| &*e.pallet == pallet && e.event == event)
+			.map(|(idx, e)| (*idx, e))
+			.ok_or_else(|| MetadataError::EventNameNotFound(pallet.into(), event.into()))
+	}
+
+	/// Returns the metadata for the call at the given pallet and call indices.
+	pub fn call(
+		&self,
+		pallet_index: u8,
+		call_index: u8,
+	) -> Result<&CallMetadata, MetadataError> {
+		let call = self
+			.inner
+			.calls
+			.get(&(pallet_index, call_index))
+			.ok_or(MetadataError::CallNotFound)?;
+		Ok(call)
+	}
+
+	/// Returns the pallet and call indices, and the metadata, of the call with the given
+	/// pallet and call names.
+	pub fn call_by_name(
+		&self,
+		pallet: &str,
+		call: &str,
+	) -> Result<((u8, u8), &CallMetadata), MetadataError> {
+		self.inner
+			.calls
+			.iter()
+			.find(|(_, c)| &*c.pallet == pallet && c.call == call)
+			.map(|(idx, c)| (*idx, c))
+			.ok_or(MetadataError::CallNotFound)
+	}
+
+	/// Returns the metadata for the error at the given pallet and error indices.
+	pub fn error(
+		&self,
+		pallet_index: u8,
+		error_index: u8,
+	) -> Result<&ErrorMetadata, MetadataError> {
+		let error = self
+			.inner
+			.errors
+			.get(&(pallet_index, error_index))
+			.ok_or(MetadataError::ErrorNotFound(pallet_index, error_index))?;
+		Ok(error)
+	}
+
+	/// Returns the metadata describing the shape of the extrinsics in a block.
+	pub fn extrinsic(&self) -> &ExtrinsicMetadata {
+		&self.inner.extrinsic
+	}
+
 	/// Return the runtime metadata.
 	pub fn runtime_metadata(&self) -> &RuntimeMetadataV14 {
 		&self.inner.metadata
 	}
 
+	/// Returns the metadata for the constant with the given pallet and constant names.
+	pub fn constant(
+		&self,
+		pallet: &str,
+		constant: &str,
+	) -> Result<&PalletConstantMetadata<PortableForm>, MetadataError> {
+		self.inner
+			.metadata
+			.pallets
+			.iter()
+			.find(|p| p.name == pallet)
+			.ok_or(MetadataError::PalletNotFound)?
+			.constants
+			.iter()
+			.find(|c| c.name == constant)
+			.ok_or(MetadataError::ConstantNotFound)
+	}
+
+	/// Obtain the unique hash for a specific constant.
+	pub fn constant_hash(
+		&self,
+		pallet: &str,
+		constant: &str,
+	) -> Result<[u8; 32], MetadataError> {
+		self.inner
+			.cached_constant_hashes
+			.get_or_insert(pallet, constant, || {
+				get_constant_hash(&self.inner.metadata, pallet, constant)
+					.map_err(|e| {
+						match e {
+							NotFound::Pallet => {
+								MetadataError::PalletNotFound
+							}
+							NotFound::Item => {
+								MetadataError::ConstantNotFound
+							}
+						}
+					})
+			})
+	}
+
 	/// Obtain the unique hash for a specific storage entry.
 	pub fn storage_hash(
 		&self,
@@ -103,9 +226,11 @@ pub struct EventMetadata {
 	// The pallet name is shared across every event, so put it
 	// behind an Arc to avoid lots of needless clones of it existing.
 	pallet: Arc<str>,
-	event: String,
-	fields: Vec<(Option<String>, u32)>,
-	docs: Vec<String>,
+	// Names and docs are interned, since the same ones turn up in many events,
+	// and again in every version of the metadata that's kept around.
+	event: Arc<str>,
+	fields: Box<[(Option<Arc<str>>, u32)]>,
+	docs: Box<[Arc<str>]>,
 }
 
 impl EventMetadata {
@@ -120,19 +245,136 @@ impl EventMetadata {
 	}
 
 	/// The names and types of each field in the event.
-	pub fn fields(&self) -> &[(Option<String>, u32)] {
+	pub fn fields(&self) -> &[(Option<Arc<str>>, u32)] {
 		&self.fields
 	}
 
 	/// Documentation for this event.
+	pub fn docs(&self) -> &[Arc<str>] {
+		&self.docs
+	}
+}
+
+/// Metadata for specific calls.
+#[derive(Clone, Debug)]
+pub struct CallMetadata {
+	// The pallet name is shared across every call, so put it
+	// behind an Arc to avoid lots of needless clones of it existing.
+	pallet: Arc<str>,
+	call: String,
+	fields: Vec<(Option<String>, u32)>,
+	docs: Vec<String>,
+}
+
+impl CallMetadata {
+	/// Get the name of the pallet that the call belongs to.
+	pub fn pallet(&self) -> &str {
+		&self.pallet
+	}
+
+	/// Get the name of the call.
+	pub fn call(&self) -> &str {
+		&self.call
+	}
+
+	/// The names and types of each argument to the call.
+	pub fn fields(&self) -> &[(Option<String>, u32)] {
+		&self.fields
+	}
+
+	/// Documentation for this call.
+	pub fn docs(&self) -> &[String] {
+		&self.docs
+	}
+}
+
+/// Metadata for specific errors.
+#[derive(Clone, Debug)]
+pub struct ErrorMetadata {
+	// The pallet name is shared across every error, so put it
+	// behind an Arc to avoid lots of needless clones of it existing.
+	pallet: Arc<str>,
+	error: String,
+	docs: Vec<String>,
+}
+
+impl ErrorMetadata {
+	/// Get the name of the pallet that the error belongs to.
+	pub fn pallet(&self) -> &str {
+		&self.pallet
+	}
+
+	/// Get the name of the error.
+	pub fn error(&self) -> &str {
+		&self.error
+	}
+
+	/// Documentation for this error.
 	pub fn docs(&self) -> &[String] {
 		&self.docs
 	}
 }
 
+/// Metadata describing the extrinsics in a block.
+#[derive(Clone, Debug)]
+pub struct ExtrinsicMetadata {
+	version: u8,
+	address_ty: Option<u32>,
+	signature_ty: Option<u32>,
+	signed_extensions: Vec<SignedExtensionMetadata>,
+}
+
+impl ExtrinsicMetadata {
+	/// The extrinsic format version.
+	pub fn version(&self) -> u8 {
+		self.version
+	}
+
+	/// The type ID of the address that signed extrinsics carry, if known.
+	pub fn address_ty(&self) -> Option<u32> {
+		self.address_ty
+	}
+
+	/// The type ID of the signature that signed extrinsics carry, if known.
+	pub fn signature_ty(&self) -> Option<u32> {
+		self.signature_ty
+	}
+
+	/// The signed extensions, in the order that their data appears in signed extrinsics.
+	pub fn signed_extensions(&self) -> &[SignedExtensionMetadata] {
+		&self.signed_extensions
+	}
+}
+
+/// Metadata for a single signed extension.
+#[derive(Clone, Debug)]
+pub struct SignedExtensionMetadata {
+	identifier: String,
+	extra_ty: u32,
+	additional_ty: u32,
+}
+
+impl SignedExtensionMetadata {
+	/// The unique identifier of the signed extension, eg `CheckNonce`.
+	pub fn identifier(&self) -> &str {
+		&self.identifier
+	}
+
+	/// The type ID of the data that this extension adds to each signed extrinsic.
+	pub fn extra_ty(&self) -> u32 {
+		self.extra_ty
+	}
+
+	/// The type ID of the additional data that is signed but not included in the extrinsic.
+	pub fn additional_ty(&self) -> u32 {
+		self.additional_ty
+	}
+}
+
 /// Error originated from converting a runtime metadata [RuntimeMetadataPrefixed] to
 /// the internal [Metadata] representation.
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum InvalidMetadataError {
 	/// Invalid prefix
 	#[error("Invalid prefix")]
@@ -175,7 +417,7 @@ impl TryFrom<RuntimeMetadataPrefixed> for Metadata {
 		let mut events = HashMap::<(u8, u8), EventMetadata>::new();
 		for pallet in &metadata.pallets {
 			if let Some(event) = &pallet.event {
-				let pallet_name: Arc<str> = pallet.name.to_string().into();
+				let pallet_name = intern(&pallet.name);
 				let event_type_id = event.ty.id();
 				let event_variant = get_type_def_variant(event_type_id)?;
 				for variant in event_variant.variants() {
@@ -183,7 +425,31 @@ impl TryFrom<RuntimeMetadataPrefixed> for Metadata {
 						(pallet.index, variant.index()),
 						EventMetadata {
 							pallet: pallet_name.clone(),
-							event: variant.name().to_owned(),
+							event: intern(variant.name()),
+							fields: variant
+								.fields()
+								.iter()
+								.map(|f| (f.name().map(|n| intern(n)), f.ty().id()))
+								.collect(),
+							docs: variant.docs().iter().map(|d| intern(d)).collect(),
+						},
+					);
+				}
+			}
+		}
+
+		let mut calls = HashMap::<(u8, u8), CallMetadata>::new();
+		for pallet in &metadata.pallets {
+			if let Some(call) = &pallet.calls {
+				let pallet_name: Arc<str> = pallet.name.to_string().into();
+				let call_type_id = call.ty.id();
+				let call_variant = get_type_def_variant(call_type_id)?;
+				for variant in call_variant.variants() {
+					calls.insert(
+						(pallet.index, variant.index()),
+						CallMetadata {
+							pallet: pallet_name.clone(),
+							call: variant.name().to_owned(),
 							fields: variant
 								.fields()
 								.iter()
@@ -196,11 +462,60 @@ impl TryFrom<RuntimeMetadataPrefixed> for Metadata {
 			}
 		}
 
+		let mut errors = HashMap::<(u8, u8), ErrorMetadata>::new();
+		for pallet in &metadata.pallets {
+			if let Some(error) = &pallet.error {
+				let pallet_name: Arc<str> = pallet.name.to_string().into();
+				let error_type_id = error.ty.id();
+				let error_variant = get_type_def_variant(error_type_id)?;
+				for variant in error_variant.variants() {
+					errors.insert(
+						(pallet.index, variant.index()),
+						ErrorMetadata {
+							pallet: pallet_name.clone(),
+							error: variant.name().to_owned(),
+							docs: variant.docs().to_vec(),
+						},
+					);
+				}
+			}
+		}
+
+		// The extrinsic type is something like `UncheckedExtrinsic<Address, Call, Signature, Extra>`,
+		// so we look at its type parameters to learn about the address and signature types.
+		let extrinsic_type_param = |name: &str| {
+			metadata
+				.types
+				.resolve(metadata.extrinsic.ty.id())
+				.and_then(|ty| ty.type_params().iter().find(|p| p.name() == name))
+				.and_then(|p| p.ty())
+				.map(|ty| ty.id())
+		};
+		let extrinsic = ExtrinsicMetadata {
+			version: metadata.extrinsic.version,
+			address_ty: extrinsic_type_param("Address"),
+			signature_ty: extrinsic_type_param("Signature"),
+			signed_extensions: metadata
+				.extrinsic
+				.signed_extensions
+				.iter()
+				.map(|ext| SignedExtensionMetadata {
+					identifier: ext.identifier.clone(),
+					extra_ty: ext.ty.id(),
+					additional_ty: ext.additional_signed.id(),
+				})
+				.collect(),
+		};
+
 		Ok(Metadata {
 			inner: Arc::new(MetadataInner {
 				metadata,
 				events,
+				calls,
+				errors,
+				extrinsic,
 				cached_storage_hashes: Default::default(),
+				cached_constant_hashes: Default::default(),
 			}),
 		})
 	}
@@ -349,4 +664,19 @@ mod tests {
 		assert_eq!(call_number, 0);
 		assert_eq!(hash.unwrap(), hash_cached.unwrap());
 	}
+
+	#[cfg(feature = "substrate-compat")]
+	#[test]
+	fn event_names_are_shared_between_metadata() {
+		let a = crate::testing::test_runtime_metadata();
+		let b = crate::testing::test_runtime_metadata();
+		let (_, a) = a.event_by_name("Balances", "Transfer").unwrap();
+		let (_, b) = b.event_by_name("Balances", "Transfer").unwrap();
+
+		assert!(Arc::ptr_eq(&a.event, &b.event));
+		for ((a, _), (b, _)) in a.fields().iter().zip(b.fields()) {
+			assert!(Arc::ptr_eq(a.as_ref().unwrap(), b.as_ref().unwrap()));
+		}
+		assert_eq!(a.fields().len(), 3);
+	}
 }
@@ -32,7 +32,7 @@ enum TypeBeingHashed {
 
 /// Hashing function utilized internally.
 fn hash(bytes: &[u8]) -> [u8; 32] {
-	sp_core::hashing::twox_256(bytes)
+	sp_core_hashing::twox_256(bytes)
 }
 
 /// XOR two hashes together. If we have two pseudorandom hashes, then this will
@@ -238,6 +238,29 @@ pub fn get_storage_hash(
 	Ok(hash)
 }
 
+/// Obtain the hash for a specific constant, or an error if it's not found.
+pub fn get_constant_hash(
+	metadata: &RuntimeMetadataV14,
+	pallet_name: &str,
+	constant_name: &str,
+) -> Result<[u8; 32], NotFound> {
+	let pallet = metadata
+		.pallets
+		.iter()
+		.find(|p| p.name == pallet_name)
+		.ok_or(NotFound::Pallet)?;
+
+	let constant = pallet
+		.constants
+		.iter()
+		.find(|c| c.name == constant_name)
+		.ok_or(NotFound::Item)?;
+
+	// We only need to check that the type of the constant asked for matches.
+	let bytes = get_type_hash(&metadata.types, constant.ty.id(), &mut HashSet::new());
+	Ok(bytes)
+}
+
 /// An error returned if we attempt to get the hash for a specific call, constant
 /// or storage item that doesn't exist.
 #[derive(Clone, Debug)]
@@ -5,12 +5,17 @@
 //! Types representing the metadata obtained from a node.
 
 mod hash_cache;
+mod interner;
 mod metadata_type;
 mod metadata_utils;
 
 pub use metadata_type::{
+    CallMetadata,
+    ErrorMetadata,
     EventMetadata,
+    ExtrinsicMetadata,
     InvalidMetadataError,
     Metadata,
     MetadataError,
+    SignedExtensionMetadata,
 };
@@ -0,0 +1,266 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Helpers for the `Assets` pallet, and for its other instances such as
+//! `ForeignAssets` on AssetHub, which hold fungible assets other than the
+//! native token.
+
+use super::{
+    subscribe_in,
+    EventFields,
+    PalletEvent,
+    PalletEventSub,
+};
+use crate::{
+    client::OnlineClientT,
+    error::Error,
+    events::SubscribeOpts,
+    utils::value_as_u128,
+    Config,
+};
+use derivative::Derivative;
+use std::future::Future;
+
+/// The name of the instance of the pallet holding assets from other chains on
+/// AssetHub, whose assets are identified by XCM locations rather than numbers.
+pub const FOREIGN_ASSETS: &str = "ForeignAssets";
+
+/// The ID of an asset: a number for most instances of the pallet, or else the
+/// SCALE encoding of the ID, such as of the XCM location of a foreign asset.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AssetId {
+    /// A numeric asset ID.
+    Index(u128),
+    /// The SCALE encoded ID of an asset whose IDs aren't numbers.
+    Encoded(Vec<u8>),
+}
+
+/// What happened to an amount of an asset.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub enum AssetAction<T: Config> {
+    /// `Assets::Transferred`: moved from one account to another.
+    Transferred {
+        /// The account that it was moved from.
+        from: T::AccountId,
+        /// The account that it was moved to.
+        to: T::AccountId,
+    },
+    /// `Assets::Issued`: minted into an account.
+    Issued {
+        /// The account that it was minted into.
+        owner: T::AccountId,
+    },
+    /// `Assets::Burned`: burned from an account.
+    Burned {
+        /// The account that it was burned from.
+        owner: T::AccountId,
+    },
+}
+
+/// `Assets::Transferred`, `Issued` or `Burned`, from any instance of the pallet.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub struct AssetEvent<T: Config> {
+    /// The name of the instance of the pallet, such as `Assets` or
+    /// [`FOREIGN_ASSETS`].
+    pub pallet: String,
+    /// The asset.
+    pub asset_id: AssetId,
+    /// What happened to it.
+    pub action: AssetAction<T>,
+    /// The amount of the asset, in its smallest unit.
+    pub amount: u128,
+}
+
+impl<T: Config> PalletEvent<T> for AssetEvent<T> {
+    const PALLET: &'static str = "Assets";
+
+    fn decode(variant: &str, fields: &EventFields<'_>) -> Result<Option<Self>, Error> {
+        let (action, amount) = match variant {
+            "Transferred" => {
+                let action = AssetAction::Transferred {
+                    from: fields.account::<T>("from", 1)?,
+                    to: fields.account::<T>("to", 2)?,
+                };
+                (action, fields.number("amount", 3)?)
+            }
+            "Issued" => {
+                let owner = fields.account::<T>("owner", 1)?;
+                // Older versions of the pallet called the amount `total_supply`.
+                let amount = if fields.has("total_supply") {
+                    fields.number("total_supply", 2)?
+                } else {
+                    fields.number("amount", 2)?
+                };
+                (AssetAction::Issued { owner }, amount)
+            }
+            "Burned" => {
+                let owner = fields.account::<T>("owner", 1)?;
+                (AssetAction::Burned { owner }, fields.number("balance", 2)?)
+            }
+            _ => return Ok(None),
+        };
+        let asset_id = match value_as_u128(fields.value("asset_id", 0)?) {
+            Some(index) => AssetId::Index(index),
+            None => AssetId::Encoded(fields.encoded("asset_id", 0)?),
+        };
+        Ok(Some(AssetEvent {
+            pallet: fields.pallet().to_owned(),
+            asset_id,
+            action,
+            amount,
+        }))
+    }
+}
+
+/// Subscribe to asset transfers, issues and burns from both `Assets` and
+/// [`FOREIGN_ASSETS`], or from whichever of them the chain has.
+///
+/// ```no_run
+/// use event_listener::{
+///     events::SubscribeOpts,
+///     pallets::assets::{
+///         self,
+///         AssetId,
+///     },
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+/// use futures::StreamExt;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let url = "wss://polkadot-asset-hub-rpc.polkadot.io:443";
+/// let api = OnlineClient::<PolkadotConfig>::from_url(url).await.unwrap();
+/// let mut transfers = assets::subscribe_assets(&api, SubscribeOpts::new()).await.unwrap();
+/// while let Some(transfer) = transfers.next().await {
+///     let transfer = transfer.unwrap().event;
+///     // USDT
+///     if transfer.asset_id == AssetId::Index(1984) {
+///         println!("{:?} of {}", transfer.action, transfer.amount);
+///     }
+/// }
+/// # }
+/// ```
+pub fn subscribe_assets<T, Client>(
+    client: &Client,
+    opts: SubscribeOpts,
+) -> impl Future<Output = Result<PalletEventSub<T, AssetEvent<T>>, Error>> + Send + 'static
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    subscribe_in::<T, Client, AssetEvent<T>>(client, &["Assets", FOREIGN_ASSETS], opts)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        pallets::{
+            find_in,
+            test_utils::{
+                account,
+                pallet_events,
+            },
+        },
+        SubstrateConfig,
+    };
+    use codec::{
+        Decode,
+        Encode,
+    };
+    use scale_info::TypeInfo;
+    use sp_core::crypto::AccountId32;
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    struct Location {
+        parents: u8,
+        interior: Vec<u32>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Event<Id> {
+        Transferred {
+            asset_id: Id,
+            from: AccountId32,
+            to: AccountId32,
+            amount: u128,
+        },
+        Issued {
+            asset_id: Id,
+            owner: AccountId32,
+            amount: u128,
+        },
+        Burned {
+            asset_id: Id,
+            owner: AccountId32,
+            balance: u128,
+        },
+    }
+
+    #[test]
+    fn asset_events_are_decoded() {
+        let events = pallet_events(vec![
+            Event::Transferred {
+                asset_id: 1984u32,
+                from: account(1),
+                to: account(2),
+                amount: 10,
+            },
+            Event::Issued {
+                asset_id: 1984,
+                owner: account(1),
+                amount: 20,
+            },
+            Event::Burned {
+                asset_id: 1984,
+                owner: account(2),
+                balance: 30,
+            },
+        ]);
+        let found: Vec<_> = find_in::<SubstrateConfig, AssetEvent<_>>(&events, "Test")
+            .map(|found| {
+                let event = found.unwrap().event;
+                assert_eq!(event.pallet, "Test");
+                assert_eq!(event.asset_id, AssetId::Index(1984));
+                (event.action, event.amount)
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    AssetAction::Transferred {
+                        from: account(1),
+                        to: account(2)
+                    },
+                    10
+                ),
+                (AssetAction::Issued { owner: account(1) }, 20),
+                (AssetAction::Burned { owner: account(2) }, 30),
+            ]
+        );
+    }
+
+    #[test]
+    fn foreign_asset_ids_are_encoded() {
+        let location = Location {
+            parents: 1,
+            interior: vec![2011],
+        };
+        let events = pallet_events(vec![Event::Issued {
+            asset_id: location.clone(),
+            owner: account(1),
+            amount: 20,
+        }]);
+        let event = find_in::<SubstrateConfig, AssetEvent<_>>(&events, "Test")
+            .next()
+            .unwrap()
+            .unwrap()
+            .event;
+        assert_eq!(event.asset_id, AssetId::Encoded(location.encode()));
+    }
+}
@@ -0,0 +1,559 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Helpers for the `Balances` pallet, which holds the native token, and for
+//! watching the balances of accounts.
+//!
+//! Events don't tell the whole story of a balance: older runtimes emit nothing
+//! for transaction fees, and dust can be lost when an account is reaped. So
+//! [`subscribe_balance_changes()`] reads the balances of the accounts that it
+//! watches from `System::Account` after each block, and hands back how much of
+//! each change the events account for.
+
+use super::{
+    blake2_128_concat,
+    fetch_storage,
+    find,
+    named_field,
+    EventFields,
+    InBlock,
+    PalletEvent,
+};
+use crate::{
+    client::OnlineClientT,
+    error::{
+        BlockError,
+        Error,
+    },
+    events::{
+        Events,
+        EventsClient,
+        SubscribeOpts,
+    },
+    utils::value_as_u128,
+    Config,
+};
+use derivative::Derivative;
+use futures::{
+    future,
+    stream::{
+        self,
+        BoxStream,
+    },
+    StreamExt,
+};
+use scale_value::{
+    scale::TypeId,
+    Value,
+};
+use sp_runtime::traits::Header;
+use std::future::Future;
+
+/// An event of the `Balances` pallet that changes the balance of an account.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub enum BalanceEvent<T: Config> {
+    /// `Balances::Transfer`: moved from one account to another.
+    Transfer {
+        /// The account that it was moved from.
+        from: T::AccountId,
+        /// The account that it was moved to.
+        to: T::AccountId,
+        /// The amount moved.
+        amount: u128,
+    },
+    /// `Balances::Deposit`: added to an account, such as a reward.
+    Deposit {
+        /// The account.
+        who: T::AccountId,
+        /// The amount added.
+        amount: u128,
+    },
+    /// `Balances::Withdraw`: taken from an account, such as a fee.
+    Withdraw {
+        /// The account.
+        who: T::AccountId,
+        /// The amount taken.
+        amount: u128,
+    },
+    /// `Balances::Minted`: minted into an account.
+    Minted {
+        /// The account.
+        who: T::AccountId,
+        /// The amount minted.
+        amount: u128,
+    },
+    /// `Balances::Burned`: burned from an account.
+    Burned {
+        /// The account.
+        who: T::AccountId,
+        /// The amount burned.
+        amount: u128,
+    },
+    /// `Balances::Reserved`: moved from the free balance of an account to its
+    /// reserved balance.
+    Reserved {
+        /// The account.
+        who: T::AccountId,
+        /// The amount moved.
+        amount: u128,
+    },
+    /// `Balances::Unreserved`: moved from the reserved balance of an account to
+    /// its free balance.
+    Unreserved {
+        /// The account.
+        who: T::AccountId,
+        /// The amount moved.
+        amount: u128,
+    },
+    /// `Balances::ReserveRepatriated`: moved from the reserved balance of one
+    /// account to another account.
+    ReserveRepatriated {
+        /// The account that it was moved from.
+        from: T::AccountId,
+        /// The account that it was moved to.
+        to: T::AccountId,
+        /// The amount moved.
+        amount: u128,
+    },
+    /// `Balances::Slashed`: taken from an account as a penalty.
+    Slashed {
+        /// The account.
+        who: T::AccountId,
+        /// The amount taken.
+        amount: u128,
+    },
+    /// `Balances::DustLost`: lost when an account was reaped, because it was
+    /// below the existential deposit.
+    DustLost {
+        /// The account.
+        account: T::AccountId,
+        /// The amount lost.
+        amount: u128,
+    },
+}
+
+impl<T: Config> BalanceEvent<T> {
+    /// The change that the event makes to the total balance of the account
+    /// given, free and reserved together.
+    pub fn total_delta(&self, account: &T::AccountId) -> i128 {
+        let (from, to, amount) = match self {
+            BalanceEvent::Transfer { from, to, amount }
+            | BalanceEvent::ReserveRepatriated { from, to, amount } => {
+                (Some(from), Some(to), *amount)
+            }
+            BalanceEvent::Deposit { who, amount } | BalanceEvent::Minted { who, amount } => {
+                (None, Some(who), *amount)
+            }
+            BalanceEvent::Withdraw { who, amount }
+            | BalanceEvent::Burned { who, amount }
+            | BalanceEvent::Slashed { who, amount }
+            | BalanceEvent::DustLost {
+                account: who,
+                amount,
+            } => (Some(who), None, *amount),
+            BalanceEvent::Reserved { .. } | BalanceEvent::Unreserved { .. } => return 0,
+        };
+        let mut delta = 0;
+        if from == Some(account) {
+            delta -= signed(amount);
+        }
+        if to == Some(account) {
+            delta += signed(amount);
+        }
+        delta
+    }
+
+    /// Whether the event is about the account given.
+    pub fn involves(&self, account: &T::AccountId) -> bool {
+        match self {
+            BalanceEvent::Transfer { from, to, .. }
+            | BalanceEvent::ReserveRepatriated { from, to, .. } => from == account || to == account,
+            BalanceEvent::Deposit { who, .. }
+            | BalanceEvent::Withdraw { who, .. }
+            | BalanceEvent::Minted { who, .. }
+            | BalanceEvent::Burned { who, .. }
+            | BalanceEvent::Reserved { who, .. }
+            | BalanceEvent::Unreserved { who, .. }
+            | BalanceEvent::Slashed { who, .. }
+            | BalanceEvent::DustLost { account: who, .. } => who == account,
+        }
+    }
+}
+
+impl<T: Config> PalletEvent<T> for BalanceEvent<T> {
+    const PALLET: &'static str = "Balances";
+
+    fn decode(variant: &str, fields: &EventFields<'_>) -> Result<Option<Self>, Error> {
+        let who = || fields.account::<T>("who", 0);
+        let amount = |index| fields.number("amount", index);
+        let event = match variant {
+            "Transfer" => {
+                BalanceEvent::Transfer {
+                    from: fields.account::<T>("from", 0)?,
+                    to: fields.account::<T>("to", 1)?,
+                    amount: amount(2)?,
+                }
+            }
+            "ReserveRepatriated" => {
+                BalanceEvent::ReserveRepatriated {
+                    from: fields.account::<T>("from", 0)?,
+                    to: fields.account::<T>("to", 1)?,
+                    amount: amount(2)?,
+                }
+            }
+            "Deposit" => BalanceEvent::Deposit { who: who()?, amount: amount(1)? },
+            "Withdraw" => BalanceEvent::Withdraw { who: who()?, amount: amount(1)? },
+            "Minted" => BalanceEvent::Minted { who: who()?, amount: amount(1)? },
+            "Burned" => BalanceEvent::Burned { who: who()?, amount: amount(1)? },
+            "Reserved" => BalanceEvent::Reserved { who: who()?, amount: amount(1)? },
+            "Unreserved" => BalanceEvent::Unreserved { who: who()?, amount: amount(1)? },
+            "Slashed" => BalanceEvent::Slashed { who: who()?, amount: amount(1)? },
+            "DustLost" => {
+                BalanceEvent::DustLost {
+                    account: fields.account::<T>("account", 0)?,
+                    amount: amount(1)?,
+                }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(event))
+    }
+}
+
+/// The balance of an account, from `System::Account`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AccountBalance {
+    /// The balance that can be spent, or reserved.
+    pub free: u128,
+    /// The balance that has been reserved, such as for deposits.
+    pub reserved: u128,
+    /// The part of the free balance that is frozen, such as by staking locks.
+    /// The larger of `misc_frozen` and `fee_frozen` on older runtimes.
+    pub frozen: u128,
+}
+
+impl AccountBalance {
+    /// The free and reserved balances together.
+    pub fn total(&self) -> u128 {
+        self.free.saturating_add(self.reserved)
+    }
+}
+
+/// The change in the balance of an account over a block.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub struct BalanceChange<T: Config> {
+    /// The hash of the block.
+    pub block_hash: T::Hash,
+    /// The account.
+    pub account: T::AccountId,
+    /// The balance of the account before the block.
+    pub before: AccountBalance,
+    /// The balance of the account after the block.
+    pub after: AccountBalance,
+    /// The events of the block about the account.
+    pub events: Vec<InBlock<T, BalanceEvent<T>>>,
+}
+
+impl<T: Config> BalanceChange<T> {
+    /// The change in the total balance of the account, as read from storage.
+    pub fn delta(&self) -> i128 {
+        signed(self.after.total()) - signed(self.before.total())
+    }
+
+    /// The change in the total balance of the account that the events account
+    /// for.
+    pub fn explained_delta(&self) -> i128 {
+        self.events
+            .iter()
+            .map(|found| found.event.total_delta(&self.account))
+            .sum()
+    }
+
+    /// The change in the total balance of the account that the events don't
+    /// account for, such as fees that older runtimes emit no events for.
+    pub fn unexplained_delta(&self) -> i128 {
+        self.delta() - self.explained_delta()
+    }
+}
+
+/// Subscribe to the changes in the balances of the accounts given, block by
+/// block. A change is handed back for each account whose balance changed in a
+/// block, or that any [`BalanceEvent`]s of the block were about.
+///
+/// The balances are read from `System::Account` after each block, and compared
+/// with those before it, which are read from the parent block unless they're
+/// already known.
+///
+/// ```no_run
+/// use event_listener::{
+///     events::SubscribeOpts,
+///     pallets::balances,
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+/// use futures::StreamExt;
+/// use sp_core::crypto::{
+///     AccountId32,
+///     Ss58Codec,
+/// };
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let account =
+///     AccountId32::from_ss58check("5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY").unwrap();
+/// let mut changes = balances::subscribe_balance_changes(&api, vec![account], SubscribeOpts::new())
+///     .await
+///     .unwrap();
+/// while let Some(change) = changes.next().await {
+///     let change = change.unwrap();
+///     println!(
+///         "{}: {:+} ({:+} not from events)",
+///         change.account,
+///         change.delta(),
+///         change.unexplained_delta(),
+///     );
+/// }
+/// # }
+/// ```
+pub fn subscribe_balance_changes<T, Client>(
+    client: &Client,
+    accounts: Vec<T::AccountId>,
+    opts: SubscribeOpts,
+) -> impl Future<Output = Result<BoxStream<'static, Result<BalanceChange<T>, Error>>, Error>>
+       + Send
+       + 'static
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let client = client.clone();
+    // Every block is needed, not just those with `Balances` events, since
+    // balances can change without any.
+    let events = EventsClient::new(client.clone());
+    async move {
+        let sub = events.subscribe_with(opts).await?;
+        // The balances of the accounts after the last block seen.
+        let last: Option<(T::Hash, Vec<AccountBalance>)> = None;
+        let changes = stream::unfold((sub, last, client), move |(mut sub, mut last, client)| {
+            let accounts = accounts.clone();
+            async move {
+                let changes = match sub.next().await? {
+                    Ok(events) => balance_changes(&client, &accounts, &events, &mut last).await,
+                    Err(e) => Err(e),
+                };
+                Some((changes, (sub, last, client)))
+            }
+        })
+        .flat_map(|found| {
+            let found = match found {
+                Ok(found) => found.into_iter().map(Ok).collect(),
+                Err(e) => vec![Err(e)],
+            };
+            stream::iter(found)
+        });
+        Ok(changes.boxed())
+    }
+}
+
+// The changes to the balances of the accounts given over the block of the
+// events given.
+async fn balance_changes<T, Client>(
+    client: &Client,
+    accounts: &[T::AccountId],
+    events: &Events<T>,
+    last: &mut Option<(T::Hash, Vec<AccountBalance>)>,
+) -> Result<Vec<BalanceChange<T>>, Error>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let block_hash = events.block_hash();
+    let header = match client.rpc().header(Some(block_hash)).await? {
+        Some(header) => header,
+        None => return Err(BlockError::block_hash_not_found(block_hash).into()),
+    };
+    let parent_hash = *header.parent_hash();
+    let before = match last.take() {
+        Some((hash, balances)) if hash == parent_hash => balances,
+        // Blocks were skipped, or this is the first.
+        _ => balances(client, accounts, parent_hash).await?,
+    };
+    let after = balances(client, accounts, block_hash).await?;
+
+    let found = find::<T, BalanceEvent<T>>(events).collect::<Result<Vec<_>, Error>>()?;
+    let changes = accounts
+        .iter()
+        .zip(before.iter().zip(&after))
+        .filter_map(|(account, (&before, &after))| {
+            let events: Vec<_> = found
+                .iter()
+                .filter(|found| found.event.involves(account))
+                .cloned()
+                .collect();
+            (before != after || !events.is_empty()).then(|| {
+                BalanceChange {
+                    block_hash,
+                    account: account.clone(),
+                    before,
+                    after,
+                    events,
+                }
+            })
+        })
+        .collect();
+    *last = Some((block_hash, after));
+    Ok(changes)
+}
+
+/// The balances of the accounts given as of the block with the hash given, from
+/// `System::Account`. Accounts that don't exist have a balance of zero.
+pub async fn balances<T, Client>(
+    client: &Client,
+    accounts: &[T::AccountId],
+    block_hash: T::Hash,
+) -> Result<Vec<AccountBalance>, Error>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    future::try_join_all(accounts.iter().map(|account| {
+        async move {
+            let key = blake2_128_concat(account);
+            let info = fetch_storage(client, "System", "Account", &key, block_hash).await?;
+            Ok::<_, Error>(info.map(|info| account_balance(&info)).unwrap_or_default())
+        }
+    }))
+    .await
+}
+
+// Read the balance from the value of `System::Account`.
+fn account_balance(info: &Value<TypeId>) -> AccountBalance {
+    let data = named_field(info, "data");
+    let number = |name: &str| {
+        data.and_then(|data| named_field(data, name))
+            .and_then(value_as_u128)
+    };
+    // Older runtimes had two frozen balances rather than one.
+    let frozen = number("frozen").unwrap_or_else(|| {
+        number("misc_frozen")
+            .unwrap_or_default()
+            .max(number("fee_frozen").unwrap_or_default())
+    });
+    AccountBalance {
+        free: number("free").unwrap_or_default(),
+        reserved: number("reserved").unwrap_or_default(),
+        frozen,
+    }
+}
+
+fn signed(amount: u128) -> i128 {
+    i128::try_from(amount).unwrap_or(i128::MAX)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        events::Phase,
+        pallets::{
+            find_in,
+            test_utils::{
+                account,
+                pallet_events,
+            },
+        },
+        SubstrateConfig,
+    };
+    use codec::{
+        Decode,
+        Encode,
+    };
+    use scale_info::TypeInfo;
+    use sp_core::{
+        crypto::AccountId32,
+        H256,
+    };
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Event {
+        Transfer {
+            from: AccountId32,
+            to: AccountId32,
+            amount: u128,
+        },
+        Withdraw {
+            who: AccountId32,
+            amount: u128,
+        },
+        Reserved {
+            who: AccountId32,
+            amount: u128,
+        },
+    }
+
+    #[test]
+    fn balance_events_are_decoded() {
+        let events = pallet_events(vec![
+            Event::Transfer {
+                from: account(1),
+                to: account(2),
+                amount: 100,
+            },
+            Event::Withdraw {
+                who: account(1),
+                amount: 3,
+            },
+            Event::Reserved {
+                who: account(2),
+                amount: 50,
+            },
+        ]);
+        let found: Vec<_> = find_in::<SubstrateConfig, BalanceEvent<_>>(&events, "Test")
+            .map(|found| found.unwrap().event)
+            .collect();
+        let deltas: Vec<_> = found
+            .iter()
+            .map(|event| (event.total_delta(&account(1)), event.total_delta(&account(2))))
+            .collect();
+        assert_eq!(deltas, vec![(-100, 100), (-3, 0), (0, 0)]);
+        assert!(found[2].involves(&account(2)));
+        assert!(!found[2].involves(&account(1)));
+    }
+
+    #[test]
+    fn changes_not_from_events_are_unexplained() {
+        let transfer = InBlock {
+            block_hash: H256::zero(),
+            event_index: 0,
+            phase: Phase::ApplyExtrinsic(1),
+            event: BalanceEvent::Transfer {
+                from: account(1),
+                to: account(2),
+                amount: 100,
+            },
+        };
+        // A transfer, along with a fee of 3 that no event was emitted for.
+        let change = BalanceChange::<SubstrateConfig> {
+            block_hash: H256::zero(),
+            account: account(1),
+            before: AccountBalance {
+                free: 1000,
+                reserved: 10,
+                frozen: 0,
+            },
+            after: AccountBalance {
+                free: 897,
+                reserved: 10,
+                frozen: 0,
+            },
+            events: vec![transfer],
+        };
+        assert_eq!(change.delta(), -103);
+        assert_eq!(change.explained_delta(), -100);
+        assert_eq!(change.unexplained_delta(), -3);
+    }
+}
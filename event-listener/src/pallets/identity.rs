@@ -0,0 +1,447 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Helpers for the `Identity` pallet, which holds on-chain identities of
+//! accounts, such as their display names, and the judgements of registrars on
+//! them.
+
+use super::{
+    fetch_storage,
+    named_field,
+    sequence_items,
+    subscribe,
+    twox_64_concat,
+    EventFields,
+    PalletEvent,
+};
+use crate::{
+    client::{
+        OfflineClientT,
+        OnlineClientT,
+    },
+    error::Error,
+    events::SubscribeOpts,
+    utils::{
+        composite_values,
+        value_as_bytes,
+        value_as_u128,
+    },
+    Config,
+};
+use codec::Decode;
+use derivative::Derivative;
+use futures::{
+    stream::BoxStream,
+    StreamExt,
+};
+use scale_info::PortableRegistry;
+use scale_value::{
+    scale::TypeId,
+    Composite,
+    Value,
+    ValueDef,
+};
+use sp_runtime::traits::Header;
+use std::future::Future;
+
+/// What happened to an identity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdentityAction {
+    /// `Identity::IdentitySet`: the account set or changed its identity.
+    Set,
+    /// `Identity::JudgementGiven`: a registrar judged the account's identity.
+    JudgementGiven {
+        /// The index of the registrar.
+        registrar_index: u32,
+    },
+    /// `Identity::IdentityKilled`: the account's identity was removed by force,
+    /// and its deposit slashed.
+    Killed {
+        /// The deposit that was slashed.
+        deposit: u128,
+    },
+}
+
+/// `Identity::IdentitySet`, `JudgementGiven` or `IdentityKilled`.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub struct IdentityEvent<T: Config> {
+    /// The account whose identity it is.
+    pub who: T::AccountId,
+    /// What happened to it.
+    pub action: IdentityAction,
+}
+
+impl<T: Config> PalletEvent<T> for IdentityEvent<T> {
+    const PALLET: &'static str = "Identity";
+
+    fn decode(variant: &str, fields: &EventFields<'_>) -> Result<Option<Self>, Error> {
+        let (who, action) = match variant {
+            "IdentitySet" => (fields.account::<T>("who", 0)?, IdentityAction::Set),
+            "JudgementGiven" => {
+                let registrar_index = fields.u32("registrar_index", 1)?;
+                (
+                    fields.account::<T>("target", 0)?,
+                    IdentityAction::JudgementGiven { registrar_index },
+                )
+            }
+            "IdentityKilled" => {
+                let deposit = fields.number("deposit", 1)?;
+                (
+                    fields.account::<T>("who", 0)?,
+                    IdentityAction::Killed { deposit },
+                )
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(IdentityEvent { who, action }))
+    }
+}
+
+/// The parts of an identity that people read, from `Identity::IdentityOf`.
+/// Fields that aren't set, that are only given as hashes, or that the version of
+/// the pallet doesn't have, are `None`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Identity {
+    /// The display name.
+    pub display: Option<String>,
+    /// The legal name.
+    pub legal: Option<String>,
+    /// A website.
+    pub web: Option<String>,
+    /// An email address.
+    pub email: Option<String>,
+    /// A Twitter handle.
+    pub twitter: Option<String>,
+    /// The judgements of registrars, as their indices and the names of their
+    /// judgements, such as `Reasonable` or `KnownGood`.
+    pub judgements: Vec<(u32, String)>,
+}
+
+impl Identity {
+    /// Whether a registrar has judged the identity to be `Reasonable` or
+    /// `KnownGood`.
+    pub fn is_verified(&self) -> bool {
+        self.judgements
+            .iter()
+            .any(|(_, judgement)| judgement == "Reasonable" || judgement == "KnownGood")
+    }
+
+    // Read an identity from the value of `Identity::IdentityOf`.
+    fn from_value(value: &Value<TypeId>, types: &PortableRegistry) -> Identity {
+        // Newer versions of the pallet store the registration along with a
+        // username, as a tuple.
+        let registration = match &value.value {
+            ValueDef::Composite(Composite::Unnamed(values)) if !values.is_empty() => &values[0],
+            _ => value,
+        };
+        let info = named_field(registration, "info");
+        let text = |name: &str| info.and_then(|info| named_field(info, name)).and_then(data_text);
+        let judgements = named_field(registration, "judgements")
+            .and_then(|judgements| sequence_items(judgements, types))
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|judgement| {
+                let mut parts = match &judgement.value {
+                    ValueDef::Composite(c) => composite_values(c),
+                    _ => return None,
+                };
+                let index = parts.next().and_then(value_as_u128)?;
+                match &parts.next()?.value {
+                    ValueDef::Variant(v) => Some((u32::try_from(index).ok()?, v.name.clone())),
+                    _ => None,
+                }
+            })
+            .collect();
+        Identity {
+            display: text("display"),
+            legal: text("legal"),
+            web: text("web"),
+            email: text("email"),
+            twitter: text("twitter"),
+            judgements,
+        }
+    }
+}
+
+/// An [`IdentityEvent`], along with the identity of the account as of the block
+/// that it was emitted in, or as of the block before for identities that were
+/// killed.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub struct IdentityChange<T: Config> {
+    /// The hash of the block that the event was emitted in.
+    pub block_hash: T::Hash,
+    /// The index of the event in the events of the block.
+    pub event_index: u32,
+    /// The event.
+    pub event: IdentityEvent<T>,
+    /// The identity of the account, if it has one.
+    pub identity: Option<Identity>,
+    /// For judgements, the account of the registrar that gave the judgement.
+    pub registrar: Option<T::AccountId>,
+}
+
+/// Subscribe to changes to identities, along with the identities themselves,
+/// which are read from storage for each event. To go without reading them, use
+/// [`super::subscribe()`] with [`IdentityEvent`].
+///
+/// ```no_run
+/// use event_listener::{
+///     events::SubscribeOpts,
+///     pallets::identity,
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+/// use futures::StreamExt;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let url = "wss://polkadot-people-rpc.polkadot.io:443";
+/// let api = OnlineClient::<PolkadotConfig>::from_url(url).await.unwrap();
+/// let mut changes = identity::subscribe_identity_changes(&api, SubscribeOpts::new())
+///     .await
+///     .unwrap();
+/// while let Some(change) = changes.next().await {
+///     let change = change.unwrap();
+///     let name = change
+///         .identity
+///         .and_then(|identity| identity.display)
+///         .unwrap_or_else(|| change.event.who.to_string());
+///     println!("{:?}: {}", change.event.action, name);
+/// }
+/// # }
+/// ```
+pub fn subscribe_identity_changes<T, Client>(
+    client: &Client,
+    opts: SubscribeOpts,
+) -> impl Future<Output = Result<BoxStream<'static, Result<IdentityChange<T>, Error>>, Error>>
+       + Send
+       + 'static
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let client = client.clone();
+    async move {
+        let sub = subscribe::<T, Client, IdentityEvent<T>>(&client, opts).await?;
+        let changes = sub.then(move |found| {
+            let client = client.clone();
+            async move {
+                let found = found?;
+                let event = found.event;
+                // Killed identities are gone by the end of the block, so they're
+                // read from the state before it.
+                let at = match event.action {
+                    IdentityAction::Killed { .. } => {
+                        client
+                            .rpc()
+                            .header(Some(found.block_hash))
+                            .await?
+                            .map_or(found.block_hash, |header| *header.parent_hash())
+                    }
+                    _ => found.block_hash,
+                };
+                let identity = identity_of(&client, &event.who, at).await?;
+                let registrar = match event.action {
+                    IdentityAction::JudgementGiven { registrar_index } => {
+                        registrar(&client, registrar_index, found.block_hash).await?
+                    }
+                    _ => None,
+                };
+                Ok(IdentityChange {
+                    block_hash: found.block_hash,
+                    event_index: found.event_index,
+                    event,
+                    identity,
+                    registrar,
+                })
+            }
+        });
+        Ok(changes.boxed())
+    }
+}
+
+/// The identity of an account as of the block with the hash given, from
+/// `Identity::IdentityOf`, if it has one.
+pub async fn identity_of<T, Client>(
+    client: &Client,
+    who: &T::AccountId,
+    block_hash: T::Hash,
+) -> Result<Option<Identity>, Error>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let key = twox_64_concat(who);
+    let value = fetch_storage(client, "Identity", "IdentityOf", &key, block_hash).await?;
+    let metadata = client.metadata();
+    let types = &metadata.runtime_metadata().types;
+    Ok(value.map(|value| Identity::from_value(&value, types)))
+}
+
+/// The account of the registrar with the index given, as of the block with the
+/// hash given, from `Identity::Registrars`.
+pub async fn registrar<T, Client>(
+    client: &Client,
+    registrar_index: u32,
+    block_hash: T::Hash,
+) -> Result<Option<T::AccountId>, Error>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let registrars =
+        match fetch_storage(client, "Identity", "Registrars", &[], block_hash).await? {
+            Some(registrars) => registrars,
+            None => return Ok(None),
+        };
+    let metadata = client.metadata();
+    let types = &metadata.runtime_metadata().types;
+    // Registrars are `Option`s, since they can be removed.
+    let account = sequence_items(&registrars, types)
+        .and_then(|registrars| registrars.into_iter().nth(registrar_index as usize))
+        .and_then(|registrar| {
+            match &registrar.value {
+                ValueDef::Variant(v) if v.name == "Some" => composite_values(&v.values).next(),
+                _ => None,
+            }
+        })
+        .and_then(|info| named_field(info, "account"))
+        .and_then(value_as_bytes);
+    match account {
+        Some(bytes) => Ok(Some(T::AccountId::decode(&mut &*bytes)?)),
+        None => Ok(None),
+    }
+}
+
+// The text of a `Data` field of an identity, if it's given as raw bytes rather
+// than as a hash.
+fn data_text(value: &Value<TypeId>) -> Option<String> {
+    match &value.value {
+        ValueDef::Variant(v) if v.name.starts_with("Raw") => {
+            let bytes = composite_values(&v.values).next().and_then(value_as_bytes)?;
+            Some(String::from_utf8_lossy(&bytes).into_owned())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        pallets::{
+            find_in,
+            test_utils::pallet_events,
+        },
+        SubstrateConfig,
+    };
+    use codec::Encode;
+    use scale_info::TypeInfo;
+    use sp_core::crypto::AccountId32;
+
+    // Shaped like the types of the same names in the pallet.
+    #[allow(dead_code)]
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Data {
+        None,
+        Raw5([u8; 5]),
+        BlakeTwo256([u8; 32]),
+    }
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    struct IdentityInfo {
+        display: Data,
+        legal: Data,
+        web: Data,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Judgement {
+        Unknown,
+        FeePaid(u128),
+        Reasonable,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    struct BoundedVec<T>(Vec<T>);
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    struct Registration {
+        judgements: BoundedVec<(u32, Judgement)>,
+        deposit: u128,
+        info: IdentityInfo,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Event {
+        IdentitySet { who: AccountId32 },
+        JudgementGiven { target: AccountId32, registrar_index: u32 },
+        IdentityKilled { who: AccountId32, deposit: u128 },
+        // Not an event of the pallet, but the value of `IdentityOf`, to read
+        // back as an identity.
+        IdentityOf((Registration, Option<Vec<u8>>)),
+    }
+
+    #[test]
+    fn identity_events_are_decoded() {
+        let who = AccountId32::new([1; 32]);
+        let events = pallet_events(vec![
+            Event::IdentitySet { who: who.clone() },
+            Event::JudgementGiven {
+                target: who.clone(),
+                registrar_index: 2,
+            },
+            Event::IdentityKilled {
+                who: who.clone(),
+                deposit: 10,
+            },
+        ]);
+        let found: Vec<_> = find_in::<SubstrateConfig, IdentityEvent<_>>(&events, "Test")
+            .map(|found| {
+                let event = found.unwrap().event;
+                assert_eq!(event.who, who);
+                event.action
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                IdentityAction::Set,
+                IdentityAction::JudgementGiven { registrar_index: 2 },
+                IdentityAction::Killed { deposit: 10 },
+            ]
+        );
+    }
+
+    #[test]
+    fn identities_are_read_from_storage_values() {
+        let registration = Registration {
+            judgements: BoundedVec(vec![(0, Judgement::FeePaid(1)), (2, Judgement::Reasonable)]),
+            deposit: 10,
+            info: IdentityInfo {
+                display: Data::Raw5(*b"Alice"),
+                legal: Data::None,
+                web: Data::BlakeTwo256([0; 32]),
+            },
+        };
+        let events = pallet_events(vec![Event::IdentityOf((registration, None))]);
+        let event = events.iter().next().unwrap().unwrap();
+        let value = event.field_values().unwrap().into_values().next().unwrap();
+        let types = &event.metadata().runtime_metadata().types;
+
+        let identity = Identity::from_value(&value, types);
+        assert_eq!(
+            identity,
+            Identity {
+                display: Some("Alice".into()),
+                judgements: vec![(0, "FeePaid".into()), (2, "Reasonable".into())],
+                ..Identity::default()
+            }
+        );
+        assert!(identity.is_verified());
+    }
+}
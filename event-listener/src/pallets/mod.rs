@@ -0,0 +1,602 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Typed helpers for the events of common pallets.
+//!
+//! The events here are decoded from the fields of each event by name, rather
+//! than with types generated from the metadata of one chain, so they work on
+//! any chain with the pallet, whatever its runtime looks like otherwise. Each
+//! implements [`PalletEvent`], which is how they're picked out of a block with
+//! [`find()`], or subscribed to with [`subscribe()`]:
+//!
+//! ```no_run
+//! use event_listener::{
+//!     events::SubscribeOpts,
+//!     pallets::{
+//!         self,
+//!         session::NewSession,
+//!     },
+//!     OnlineClient,
+//!     PolkadotConfig,
+//! };
+//! use futures::StreamExt;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+//! let mut sessions = pallets::subscribe::<_, _, NewSession>(&api, SubscribeOpts::new())
+//!     .await
+//!     .unwrap();
+//! while let Some(session) = sessions.next().await {
+//!     println!("{:?}", session.unwrap());
+//! }
+//! # }
+//! ```
+
+pub mod assets;
+pub mod balances;
+pub mod identity;
+pub mod multisig;
+pub mod nomination_pools;
+pub mod para_inclusion;
+pub mod proxy;
+pub mod scheduler;
+pub mod session;
+pub mod sudo;
+pub mod system;
+pub mod treasury;
+pub mod vesting;
+
+use crate::{
+    blocks::Extrinsics,
+    client::{
+        OfflineClientT,
+        OnlineClientT,
+    },
+    error::{
+        BlockError,
+        Error,
+    },
+    events::{
+        EventDetails,
+        Events,
+        Phase,
+        SubscribeOpts,
+    },
+    utils::{
+        composite_field,
+        composite_values,
+        value_as_bytes,
+        value_as_u128,
+    },
+    Config,
+    Metadata,
+};
+use codec::{
+    Decode,
+    Encode,
+};
+use derivative::Derivative;
+use frame_metadata::StorageEntryType;
+use futures::{
+    stream::{
+        self,
+        BoxStream,
+    },
+    StreamExt,
+};
+use scale_info::{
+    PortableRegistry,
+    TypeDef,
+};
+use scale_value::{
+    scale::TypeId,
+    Composite,
+    Primitive,
+    Value,
+    ValueDef,
+};
+use sp_core::{
+    blake2_128,
+    twox_128,
+    twox_64,
+};
+use std::future::Future;
+
+/// Events of a pallet, decoded from their fields by name.
+pub trait PalletEvent<T: Config>: Sized {
+    /// The name of the pallet that these events come from. Pallets with more
+    /// than one instance can be looked for under other names, with
+    /// [`PalletEvent::from_event_in()`] and [`subscribe_in()`].
+    const PALLET: &'static str;
+
+    /// Decode the event with the variant name and fields given, or hand back
+    /// `None` if it isn't one of these events.
+    fn decode(variant: &str, fields: &EventFields<'_>) -> Result<Option<Self>, Error>;
+
+    /// Decode the event given, if it's one of these events.
+    fn from_event(event: &EventDetails) -> Result<Option<Self>, Error> {
+        Self::from_event_in(event, Self::PALLET)
+    }
+
+    /// Decode the event given, if it's one of these events from the instance of
+    /// the pallet with the name given.
+    fn from_event_in(event: &EventDetails, pallet: &str) -> Result<Option<Self>, Error> {
+        if event.pallet_name() != pallet {
+            return Ok(None)
+        }
+        let fields = event.field_values()?;
+        let fields = EventFields {
+            pallet: event.pallet_name(),
+            variant: event.variant_name(),
+            fields: &fields,
+            metadata: event.metadata(),
+        };
+        Self::decode(event.variant_name(), &fields)
+    }
+}
+
+/// The decoded fields of an event, for [`PalletEvent::decode()`] to read from.
+/// Fields are looked up by name, or by position for events with unnamed fields,
+/// as older runtimes have.
+#[derive(Derivative)]
+#[derivative(Clone, Copy, Debug)]
+pub struct EventFields<'a> {
+    pallet: &'a str,
+    variant: &'a str,
+    fields: &'a Composite<TypeId>,
+    #[derivative(Debug = "ignore")]
+    metadata: &'a Metadata,
+}
+
+impl<'a> EventFields<'a> {
+    /// The name of the pallet that the event is from, which for pallets with more
+    /// than one instance is the name of the instance.
+    pub fn pallet(&self) -> &'a str {
+        self.pallet
+    }
+
+    /// The field with the name given, or at the position given if the fields
+    /// are unnamed.
+    pub fn value(&self, name: &str, index: usize) -> Result<&'a Value<TypeId>, Error> {
+        composite_field(self.fields, name, index).ok_or_else(|| {
+            Error::Other(format!(
+                "{}::{} has no field {}",
+                self.pallet, self.variant, name
+            ))
+        })
+    }
+
+    /// Whether the event has a field with the name given. Fields that aren't
+    /// in every version of a pallet can be checked for with this first.
+    pub fn has(&self, name: &str) -> bool {
+        match self.fields {
+            Composite::Named(fields) => fields.iter().any(|(n, _)| n == name),
+            Composite::Unnamed(_) => false,
+        }
+    }
+
+    /// The fields of a field holding a struct, such as a descriptor.
+    pub fn nested(&self, name: &str, index: usize) -> Result<EventFields<'a>, Error> {
+        match &self.value(name, index)?.value {
+            ValueDef::Composite(fields) => Ok(EventFields { fields, ..*self }),
+            _ => Err(self.mismatch(name, "a struct")),
+        }
+    }
+
+    /// A field, encoded again as the type that it was decoded as, such as for
+    /// hashing it.
+    pub fn encoded(&self, name: &str, index: usize) -> Result<Vec<u8>, Error> {
+        let value = self.value(name, index)?;
+        let types = &self.metadata.runtime_metadata().types;
+        let mut bytes = Vec::new();
+        scale_value::scale::encode_as_type(
+            value.clone().remove_context(),
+            value.context.id(),
+            types,
+            &mut bytes,
+        )?;
+        Ok(bytes)
+    }
+
+    /// A field holding a number, such as an amount or an index.
+    pub fn number(&self, name: &str, index: usize) -> Result<u128, Error> {
+        value_as_u128(self.value(name, index)?).ok_or_else(|| self.mismatch(name, "a number"))
+    }
+
+    /// A field holding a `bool`.
+    pub fn bool(&self, name: &str, index: usize) -> Result<bool, Error> {
+        match self.value(name, index)?.value {
+            ValueDef::Primitive(Primitive::Bool(b)) => Ok(b),
+            _ => Err(self.mismatch(name, "a bool")),
+        }
+    }
+
+    /// A field holding an enum, such as a `DispatchResult`, as the name of its
+    /// variant.
+    pub fn variant_name(&self, name: &str, index: usize) -> Result<&'a str, Error> {
+        match &self.value(name, index)?.value {
+            ValueDef::Variant(v) => Ok(&v.name),
+            _ => Err(self.mismatch(name, "an enum")),
+        }
+    }
+
+    /// A field holding a number that fits in a `u32`, such as an ID.
+    pub fn u32(&self, name: &str, index: usize) -> Result<u32, Error> {
+        u32::try_from(self.number(name, index)?).map_err(|_| self.mismatch(name, "a u32"))
+    }
+
+    /// A field holding a number that fits in a `u64`, such as a block number.
+    pub fn u64(&self, name: &str, index: usize) -> Result<u64, Error> {
+        u64::try_from(self.number(name, index)?).map_err(|_| self.mismatch(name, "a u64"))
+    }
+
+    /// A field holding bytes, such as a hash or an account, decoded as `D`.
+    pub fn decode<D: Decode>(&self, name: &str, index: usize) -> Result<D, Error> {
+        let bytes =
+            value_as_bytes(self.value(name, index)?).ok_or_else(|| self.mismatch(name, "bytes"))?;
+        D::decode(&mut &*bytes).map_err(Into::into)
+    }
+
+    /// A field holding an account.
+    pub fn account<T: Config>(&self, name: &str, index: usize) -> Result<T::AccountId, Error> {
+        self.decode(name, index)
+    }
+
+    /// A field holding a hash.
+    pub fn hash<T: Config>(&self, name: &str, index: usize) -> Result<T::Hash, Error> {
+        self.decode(name, index)
+    }
+
+    fn mismatch(&self, name: &str, expected: &str) -> Error {
+        Error::Other(format!(
+            "Field {} of {}::{} isn't {}",
+            name, self.pallet, self.variant, expected
+        ))
+    }
+}
+
+/// A [`PalletEvent`], with where it was found.
+#[derive(Derivative)]
+#[derivative(
+    Clone(bound = "E: Clone"),
+    Debug(bound = "E: std::fmt::Debug"),
+    PartialEq(bound = "E: PartialEq")
+)]
+pub struct InBlock<T: Config, E> {
+    /// The hash of the block that the event was emitted in.
+    pub block_hash: T::Hash,
+    /// The index of the event in the events of the block.
+    pub event_index: u32,
+    /// When, in the block, the event was emitted.
+    pub phase: Phase,
+    /// The event.
+    pub event: E,
+}
+
+/// A stream of [`PalletEvent`]s, handed back from [`subscribe()`].
+pub type PalletEventSub<T, E> = BoxStream<'static, Result<InBlock<T, E>, Error>>;
+
+/// The events of type `E` in a block.
+pub fn find<T: Config, E: PalletEvent<T>>(
+    events: &Events<T>,
+) -> impl Iterator<Item = Result<InBlock<T, E>, Error>> + '_ {
+    find_in::<T, E>(events, E::PALLET)
+}
+
+/// The events of type `E` in a block, from the instance of the pallet with the
+/// name given.
+pub fn find_in<'a, T: Config, E: PalletEvent<T>>(
+    events: &'a Events<T>,
+    pallet: &'a str,
+) -> impl Iterator<Item = Result<InBlock<T, E>, Error>> + 'a {
+    find_matching(events, move |name| name == pallet)
+}
+
+// The events of type `E` in a block, from the pallets whose names match.
+fn find_matching<'a, T: Config, E: PalletEvent<T>>(
+    events: &'a Events<T>,
+    matches: impl Fn(&str) -> bool + 'a,
+) -> impl Iterator<Item = Result<InBlock<T, E>, Error>> + 'a {
+    let block_hash = events.block_hash();
+    events.iter().filter_map(move |event| {
+        let event = match event {
+            Ok(event) if matches(event.pallet_name()) => event,
+            Ok(_) => return None,
+            Err(e) => return Some(Err(e)),
+        };
+        E::from_event_in(&event, event.pallet_name())
+            .transpose()
+            .map(|decoded| {
+                decoded.map(|decoded| {
+                    InBlock {
+                        block_hash,
+                        event_index: event.index(),
+                        phase: event.phase(),
+                        event: decoded,
+                    }
+                })
+            })
+    })
+}
+
+/// Subscribe to the events of type `E`. The options are those of
+/// [`crate::events::EventsClient::subscribe_with()`], narrowed down to the
+/// events of the pallet.
+pub fn subscribe<T, Client, E>(
+    client: &Client,
+    opts: SubscribeOpts,
+) -> impl Future<Output = Result<PalletEventSub<T, E>, Error>> + Send + 'static
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+    E: PalletEvent<T> + Send + 'static,
+{
+    subscribe_in::<T, Client, E>(client, &[E::PALLET], opts)
+}
+
+/// Subscribe to the events of type `E` from the instances of the pallet with
+/// the names given, such as both `Assets` and `ForeignAssets`. See
+/// [`subscribe()`].
+pub fn subscribe_in<T, Client, E>(
+    client: &Client,
+    pallets: &[&str],
+    opts: SubscribeOpts,
+) -> impl Future<Output = Result<PalletEventSub<T, E>, Error>> + Send + 'static
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+    E: PalletEvent<T> + Send + 'static,
+{
+    let events = crate::events::EventsClient::new(client.clone());
+    let pallets: Vec<String> = pallets.iter().map(|&p| p.to_owned()).collect();
+    let opts = pallets.iter().fold(opts, |opts, p| opts.pallet(p.clone()));
+    async move {
+        let sub = events.subscribe_with(opts).await?;
+        let sub = sub.flat_map(move |events| {
+            let found: Vec<_> = match events {
+                Ok(events) => {
+                    find_matching::<T, E>(&events, |name| pallets.iter().any(|p| p == name))
+                        .collect()
+                }
+                Err(e) => vec![Err(e)],
+            };
+            stream::iter(found)
+        });
+        Ok(sub.boxed())
+    }
+}
+
+/// The extrinsics of the block of the events given, which are fetched from the
+/// node.
+pub(crate) async fn block_extrinsics<T, Client>(
+    client: &Client,
+    events: &Events<T>,
+) -> Result<Extrinsics<T>, Error>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let block_hash = events.block_hash();
+    let block = match client.rpc().block(Some(block_hash)).await? {
+        Some(block) => block,
+        None => return Err(BlockError::block_hash_not_found(block_hash).into()),
+    };
+    Extrinsics::new(client.metadata(), block.block.extrinsics, events.clone())
+}
+
+/// Read the storage entry of the pallet given at a block, and decode it with the
+/// type that the metadata gives for it. The key is what follows the prefix of
+/// the entry, hashed as the entry's hashers say, or nothing for a plain value.
+/// `None` if there's nothing at the key, or if the chain has no such entry.
+pub(crate) async fn fetch_storage<T, Client>(
+    client: &Client,
+    pallet: &str,
+    entry: &str,
+    key: &[u8],
+    block_hash: T::Hash,
+) -> Result<Option<Value<TypeId>>, Error>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let metadata = client.metadata();
+    let storage = metadata
+        .runtime_metadata()
+        .pallets
+        .iter()
+        .find(|p| p.name == pallet)
+        .and_then(|p| p.storage.as_ref());
+    let (prefix, ty) = match storage
+        .and_then(|s| s.entries.iter().find(|e| e.name == entry).map(|e| (&s.prefix, e)))
+    {
+        Some((prefix, entry)) => {
+            let ty = match &entry.ty {
+                StorageEntryType::Plain(ty) => ty.id(),
+                StorageEntryType::Map { value, .. } => value.id(),
+            };
+            (prefix, ty)
+        }
+        None => return Ok(None),
+    };
+
+    let mut storage_key = twox_128(prefix.as_bytes()).to_vec();
+    storage_key.extend(twox_128(entry.as_bytes()));
+    storage_key.extend(key);
+    let data = match client.rpc().storage(&storage_key, Some(block_hash)).await? {
+        Some(data) => data,
+        None => return Ok(None),
+    };
+    let types = &metadata.runtime_metadata().types;
+    let value = scale_value::scale::decode_as_type(&mut &*data.0, ty, types)?;
+    Ok(Some(value))
+}
+
+/// The key of an entry in a storage map whose key is hashed with
+/// `Twox64Concat`, such as most maps keyed by account.
+pub(crate) fn twox_64_concat(key: &impl Encode) -> Vec<u8> {
+    let key = key.encode();
+    let mut hashed = twox_64(&key).to_vec();
+    hashed.extend(key);
+    hashed
+}
+
+/// The key of an entry in a storage map whose key is hashed with
+/// `Blake2_128Concat`, as for maps keyed by values that users can choose.
+pub(crate) fn blake2_128_concat(key: &impl Encode) -> Vec<u8> {
+    let key = key.encode();
+    let mut hashed = blake2_128(&key).to_vec();
+    hashed.extend(key);
+    hashed
+}
+
+/// The named field of a struct value.
+pub(crate) fn named_field<'a>(value: &'a Value<TypeId>, name: &str) -> Option<&'a Value<TypeId>> {
+    match &value.value {
+        ValueDef::Composite(c @ Composite::Named(_)) => composite_field(c, name, 0),
+        _ => None,
+    }
+}
+
+/// The items of a sequence value, looking through wrappers such as `BoundedVec`.
+pub(crate) fn sequence_items<'a>(
+    value: &'a Value<TypeId>,
+    types: &PortableRegistry,
+) -> Option<Vec<&'a Value<TypeId>>> {
+    let c = match &value.value {
+        ValueDef::Composite(c) => c,
+        _ => return None,
+    };
+    match types.resolve(value.context.id())?.type_def() {
+        TypeDef::Sequence(_) | TypeDef::Array(_) => Some(composite_values(c).collect()),
+        TypeDef::Composite(_) => {
+            let mut values = composite_values(c);
+            match (values.next(), values.next()) {
+                (Some(inner), None) => sequence_items(inner, types),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use crate::{
+        events::{
+            test_utils::{
+                event_record,
+                events,
+                metadata,
+            },
+            Events,
+            Phase,
+        },
+        SubstrateConfig,
+    };
+    use codec::{
+        Decode,
+        Encode,
+    };
+    use scale_info::TypeInfo;
+    use scale_value::Value;
+    use sp_core::crypto::AccountId32;
+
+    /// The account with an ID made up of the byte given.
+    pub fn account(byte: u8) -> AccountId32 {
+        AccountId32::new([byte; 32])
+    }
+
+    /// The same account as [`account`], as the value of an `AccountId32` field.
+    pub fn account_value(byte: u8) -> Value<()> {
+        let bytes = vec![Value::u128(byte.into()); 32];
+        Value::unnamed_composite(vec![Value::unnamed_composite(bytes)])
+    }
+
+    /// The events given, as the events of a block from a pallet named "Test".
+    pub fn pallet_events<E>(pallet_events: Vec<E>) -> Events<SubstrateConfig>
+    where
+        E: Decode + Encode + TypeInfo + 'static,
+    {
+        let records = pallet_events
+            .into_iter()
+            .map(|event| event_record(Phase::Finalization, event))
+            .collect();
+        events(metadata::<E>(), records)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::SubstrateConfig;
+    use codec::Encode;
+    use scale_info::TypeInfo;
+    use sp_core::crypto::AccountId32;
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Event {
+        Named { who: AccountId32, amount: u128 },
+        Unnamed(AccountId32, u128),
+        Other,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Paid {
+        who: AccountId32,
+        amount: u128,
+    }
+
+    impl PalletEvent<SubstrateConfig> for Paid {
+        const PALLET: &'static str = "Test";
+
+        fn decode(variant: &str, fields: &EventFields<'_>) -> Result<Option<Self>, Error> {
+            if variant != "Named" && variant != "Unnamed" {
+                return Ok(None)
+            }
+            Ok(Some(Paid {
+                who: fields.account::<SubstrateConfig>("who", 0)?,
+                amount: fields.number("amount", 1)?,
+            }))
+        }
+    }
+
+    #[test]
+    fn events_are_found_by_field_name_or_position() {
+        let events = test_utils::pallet_events(vec![
+            Event::Named {
+                who: AccountId32::new([1; 32]),
+                amount: 10,
+            },
+            Event::Other,
+            Event::Unnamed(AccountId32::new([2; 32]), 20),
+        ]);
+        let found: Vec<_> = find::<_, Paid>(&events)
+            .map(|found| {
+                let found = found.unwrap();
+                (found.event_index, found.event)
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    0,
+                    Paid {
+                        who: AccountId32::new([1; 32]),
+                        amount: 10
+                    }
+                ),
+                (
+                    2,
+                    Paid {
+                        who: AccountId32::new([2; 32]),
+                        amount: 20
+                    }
+                ),
+            ]
+        );
+        assert_eq!(find_in::<_, Paid>(&events, "Other").count(), 0);
+    }
+}
@@ -0,0 +1,464 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Helpers for the `Multisig` pallet, whose accounts dispatch calls once enough
+//! of their signatories have approved them.
+
+use super::{
+    subscribe,
+    EventFields,
+    InBlock,
+    PalletEvent,
+    PalletEventSub,
+};
+use crate::{
+    client::OnlineClientT,
+    error::Error,
+    events::SubscribeOpts,
+    Config,
+};
+use derivative::Derivative;
+use futures::{
+    future,
+    StreamExt,
+};
+use std::{
+    collections::HashMap,
+    future::Future,
+};
+
+/// Where a multisig operation was first approved: the number of the block, and
+/// the index of the extrinsic in it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Timepoint {
+    /// The number of the block.
+    pub height: u64,
+    /// The index of the extrinsic in the block.
+    pub index: u32,
+}
+
+/// What happened to a multisig operation.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub enum MultisigAction<T: Config> {
+    /// `Multisig::NewMultisig`: the operation was started, with its first
+    /// approval.
+    New {
+        /// The signatory that started it.
+        approving: T::AccountId,
+    },
+    /// `Multisig::MultisigApproval`: the operation was approved by another
+    /// signatory, without reaching the threshold.
+    Approval {
+        /// The signatory that approved it.
+        approving: T::AccountId,
+        /// Where the operation was started.
+        timepoint: Timepoint,
+    },
+    /// `Multisig::MultisigExecuted`: the last approval needed was given, and
+    /// the call was dispatched.
+    Executed {
+        /// The signatory that gave the last approval.
+        approving: T::AccountId,
+        /// Where the operation was started.
+        timepoint: Timepoint,
+        /// Whether the call was dispatched successfully.
+        success: bool,
+    },
+    /// `Multisig::MultisigCancelled`: the operation was cancelled by the
+    /// signatory that started it.
+    Cancelled {
+        /// The signatory that cancelled it.
+        cancelling: T::AccountId,
+        /// Where the operation was started.
+        timepoint: Timepoint,
+    },
+}
+
+/// `Multisig::NewMultisig`, `MultisigApproval`, `MultisigExecuted` or
+/// `MultisigCancelled`.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub struct MultisigEvent<T: Config> {
+    /// The multisig account.
+    pub multisig: T::AccountId,
+    /// The hash of the call that the operation is to dispatch, which along with
+    /// the multisig account identifies the operation.
+    pub call_hash: [u8; 32],
+    /// What happened to the operation.
+    pub action: MultisigAction<T>,
+}
+
+impl<T: Config> PalletEvent<T> for MultisigEvent<T> {
+    const PALLET: &'static str = "Multisig";
+
+    fn decode(variant: &str, fields: &EventFields<'_>) -> Result<Option<Self>, Error> {
+        let timepoint = || -> Result<Timepoint, Error> {
+            let timepoint = fields.nested("timepoint", 1)?;
+            Ok(Timepoint {
+                height: timepoint.u64("height", 0)?,
+                index: timepoint.u32("index", 1)?,
+            })
+        };
+        let (action, multisig, call_hash) = match variant {
+            "NewMultisig" => {
+                let action = MultisigAction::New {
+                    approving: fields.account::<T>("approving", 0)?,
+                };
+                (action, ("multisig", 1), ("call_hash", 2))
+            }
+            "MultisigApproval" => {
+                let action = MultisigAction::Approval {
+                    approving: fields.account::<T>("approving", 0)?,
+                    timepoint: timepoint()?,
+                };
+                (action, ("multisig", 2), ("call_hash", 3))
+            }
+            "MultisigExecuted" => {
+                // The result is a `DispatchResult`, so `Ok(())` or `Err(..)`.
+                let success = fields.variant_name("result", 4)? == "Ok";
+                let action = MultisigAction::Executed {
+                    approving: fields.account::<T>("approving", 0)?,
+                    timepoint: timepoint()?,
+                    success,
+                };
+                (action, ("multisig", 2), ("call_hash", 3))
+            }
+            "MultisigCancelled" => {
+                let action = MultisigAction::Cancelled {
+                    cancelling: fields.account::<T>("cancelling", 0)?,
+                    timepoint: timepoint()?,
+                };
+                (action, ("multisig", 2), ("call_hash", 3))
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(MultisigEvent {
+            multisig: fields.account::<T>(multisig.0, multisig.1)?,
+            call_hash: fields.decode(call_hash.0, call_hash.1)?,
+            action,
+        }))
+    }
+}
+
+/// Where a multisig operation has got to.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub enum MultisigStatus<T: Config> {
+    /// Waiting for more approvals.
+    Pending,
+    /// The call was dispatched.
+    Executed {
+        /// Whether the call was dispatched successfully.
+        success: bool,
+    },
+    /// The operation was cancelled.
+    Cancelled {
+        /// The signatory that cancelled it.
+        by: T::AccountId,
+    },
+}
+
+/// The state of a multisig operation, as pieced together from its events.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub struct MultisigOperation<T: Config> {
+    /// The multisig account.
+    pub multisig: T::AccountId,
+    /// The hash of the call that the operation is to dispatch.
+    pub call_hash: [u8; 32],
+    /// Where the operation was started. Not known until an event after
+    /// `NewMultisig`, which doesn't give it.
+    pub timepoint: Option<Timepoint>,
+    /// The signatories that have approved the operation, in order. Only those
+    /// seen by the [`MultisigTracker`] are included, so approvals given before
+    /// it started are missing.
+    pub approvals: Vec<T::AccountId>,
+    /// Where the operation has got to.
+    pub status: MultisigStatus<T>,
+}
+
+/// Follows the operations of a multisig account across blocks, from its
+/// [`MultisigEvent`]s.
+///
+/// Operations are tracked until they're executed or cancelled, after which
+/// they're forgotten.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub struct MultisigTracker<T: Config> {
+    multisig: T::AccountId,
+    pending: HashMap<[u8; 32], MultisigOperation<T>>,
+}
+
+impl<T: Config> MultisigTracker<T> {
+    /// Track the operations of the multisig account given.
+    pub fn new(multisig: T::AccountId) -> Self {
+        MultisigTracker {
+            multisig,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// The multisig account being tracked.
+    pub fn multisig(&self) -> &T::AccountId {
+        &self.multisig
+    }
+
+    /// The operations waiting for more approvals.
+    pub fn pending(&self) -> impl Iterator<Item = &MultisigOperation<T>> {
+        self.pending.values()
+    }
+
+    /// Apply an event, handing back the state of the operation that it was for,
+    /// or `None` if it was for another multisig account.
+    pub fn apply(&mut self, event: &MultisigEvent<T>) -> Option<MultisigOperation<T>> {
+        if event.multisig != self.multisig {
+            return None
+        }
+        let operation = self.pending.entry(event.call_hash).or_insert_with(|| {
+            MultisigOperation {
+                multisig: event.multisig.clone(),
+                call_hash: event.call_hash,
+                timepoint: None,
+                approvals: Vec::new(),
+                status: MultisigStatus::Pending,
+            }
+        });
+        let (approving, timepoint) = match &event.action {
+            MultisigAction::New { approving } => (Some(approving), None),
+            MultisigAction::Approval {
+                approving,
+                timepoint,
+            } => (Some(approving), Some(*timepoint)),
+            MultisigAction::Executed {
+                approving,
+                timepoint,
+                success,
+            } => {
+                operation.status = MultisigStatus::Executed { success: *success };
+                (Some(approving), Some(*timepoint))
+            }
+            MultisigAction::Cancelled {
+                cancelling,
+                timepoint,
+            } => {
+                operation.status = MultisigStatus::Cancelled {
+                    by: cancelling.clone(),
+                };
+                (None, Some(*timepoint))
+            }
+        };
+        if let Some(approving) = approving {
+            if !operation.approvals.contains(approving) {
+                operation.approvals.push(approving.clone());
+            }
+        }
+        if timepoint.is_some() {
+            operation.timepoint = timepoint;
+        }
+
+        if operation.status == MultisigStatus::Pending {
+            Some(operation.clone())
+        } else {
+            self.pending.remove(&event.call_hash)
+        }
+    }
+}
+
+/// Subscribe to the operations of a multisig account, handing back the state
+/// of an operation each time that something happens to it.
+///
+/// ```no_run
+/// use event_listener::{
+///     events::SubscribeOpts,
+///     pallets::multisig::{
+///         self,
+///         MultisigStatus,
+///     },
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+/// use futures::StreamExt;
+/// use sp_core::crypto::{
+///     AccountId32,
+///     Ss58Codec,
+/// };
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let account =
+///     AccountId32::from_ss58check("5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY").unwrap();
+/// let mut operations = multisig::subscribe_multisig(&api, account, SubscribeOpts::new())
+///     .await
+///     .unwrap();
+/// while let Some(operation) = operations.next().await {
+///     let operation = operation.unwrap().event;
+///     match operation.status {
+///         MultisigStatus::Pending => {
+///             println!("{} approvals of {:?}", operation.approvals.len(), operation.call_hash)
+///         }
+///         status => println!("{:?}: {:?}", operation.call_hash, status),
+///     }
+/// }
+/// # }
+/// ```
+pub fn subscribe_multisig<T, Client>(
+    client: &Client,
+    multisig: T::AccountId,
+    opts: SubscribeOpts,
+) -> impl Future<Output = Result<PalletEventSub<T, MultisigOperation<T>>, Error>> + Send + 'static
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let sub = subscribe::<T, Client, MultisigEvent<T>>(client, opts);
+    async move {
+        let operations = sub.await?.scan(MultisigTracker::new(multisig), |tracker, found| {
+            let operation = match found {
+                Ok(found) => {
+                    tracker.apply(&found.event).map(|operation| {
+                        Ok(InBlock {
+                            block_hash: found.block_hash,
+                            event_index: found.event_index,
+                            phase: found.phase,
+                            event: operation,
+                        })
+                    })
+                }
+                Err(e) => Some(Err(e)),
+            };
+            future::ready(Some(operation))
+        });
+        Ok(operations.filter_map(future::ready).boxed())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        pallets::{
+            find_in,
+            test_utils::{
+                account,
+                pallet_events,
+            },
+        },
+        SubstrateConfig,
+    };
+    use codec::{
+        Decode,
+        Encode,
+    };
+    use scale_info::TypeInfo;
+    use sp_core::crypto::AccountId32;
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    struct EventTimepoint {
+        height: u32,
+        index: u32,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Event {
+        NewMultisig {
+            approving: AccountId32,
+            multisig: AccountId32,
+            call_hash: [u8; 32],
+        },
+        MultisigApproval {
+            approving: AccountId32,
+            timepoint: EventTimepoint,
+            multisig: AccountId32,
+            call_hash: [u8; 32],
+        },
+        MultisigExecuted {
+            approving: AccountId32,
+            timepoint: EventTimepoint,
+            multisig: AccountId32,
+            call_hash: [u8; 32],
+            result: Result<(), u8>,
+        },
+    }
+
+    #[test]
+    fn operations_are_followed_until_executed() {
+        let timepoint = EventTimepoint {
+            height: 100,
+            index: 2,
+        };
+        let events = pallet_events(vec![
+            Event::NewMultisig {
+                approving: account(1),
+                multisig: account(9),
+                call_hash: [7; 32],
+            },
+            // Another multisig account, which isn't tracked.
+            Event::NewMultisig {
+                approving: account(1),
+                multisig: account(8),
+                call_hash: [7; 32],
+            },
+            Event::MultisigApproval {
+                approving: account(2),
+                timepoint: timepoint.clone(),
+                multisig: account(9),
+                call_hash: [7; 32],
+            },
+            Event::MultisigExecuted {
+                approving: account(3),
+                timepoint,
+                multisig: account(9),
+                call_hash: [7; 32],
+                result: Ok(()),
+            },
+        ]);
+
+        let mut tracker = MultisigTracker::<SubstrateConfig>::new(account(9));
+        let operations: Vec<_> = find_in::<SubstrateConfig, MultisigEvent<_>>(&events, "Test")
+            .filter_map(|found| tracker.apply(&found.unwrap().event))
+            .map(|operation| (operation.approvals.len(), operation.status))
+            .collect();
+        assert_eq!(
+            operations,
+            vec![
+                (1, MultisigStatus::Pending),
+                (2, MultisigStatus::Pending),
+                (3, MultisigStatus::Executed { success: true }),
+            ]
+        );
+        assert_eq!(tracker.pending().count(), 0);
+    }
+
+    #[test]
+    fn failed_dispatches_are_reported() {
+        let events = pallet_events(vec![Event::MultisigExecuted {
+            approving: account(3),
+            timepoint: EventTimepoint {
+                height: 100,
+                index: 2,
+            },
+            multisig: account(9),
+            call_hash: [7; 32],
+            result: Err(0),
+        }]);
+        let event = find_in::<SubstrateConfig, MultisigEvent<_>>(&events, "Test")
+            .next()
+            .unwrap()
+            .unwrap()
+            .event;
+        assert_eq!(
+            event.action,
+            MultisigAction::Executed {
+                approving: account(3),
+                timepoint: Timepoint {
+                    height: 100,
+                    index: 2
+                },
+                success: false,
+            }
+        );
+    }
+}
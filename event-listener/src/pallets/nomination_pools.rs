@@ -0,0 +1,246 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Helpers for the `NominationPools` pallet, whose members stake together in
+//! pools.
+
+use super::{
+    EventFields,
+    PalletEvent,
+};
+use crate::{
+    error::Error,
+    Config,
+};
+use derivative::Derivative;
+
+/// What a member of a pool did, or had done for them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoolAction {
+    /// `NominationPools::Bonded`: the member bonded funds into the pool.
+    Bonded {
+        /// The amount bonded.
+        amount: u128,
+        /// Whether the member joined the pool by bonding, rather than bonding
+        /// more as an existing member.
+        joined: bool,
+    },
+    /// `NominationPools::Unbonded`: the member started unbonding funds.
+    Unbonded {
+        /// The amount being unbonded.
+        amount: u128,
+        /// The pool points unbonded. Not given by older versions of the pallet.
+        points: Option<u128>,
+        /// The era in which the funds can be withdrawn. Not given by older
+        /// versions of the pallet.
+        era: Option<u32>,
+    },
+    /// `NominationPools::PaidOut`: the member was paid their rewards.
+    PaidOut {
+        /// The amount paid out.
+        amount: u128,
+    },
+    /// `NominationPools::Withdrawn`: the member withdrew funds that had been
+    /// unbonded.
+    Withdrawn {
+        /// The amount withdrawn.
+        amount: u128,
+        /// The pool points withdrawn. Not given by older versions of the pallet.
+        points: Option<u128>,
+    },
+}
+
+impl PoolAction {
+    /// The amount of the native token that the action was for.
+    pub fn amount(&self) -> u128 {
+        match *self {
+            PoolAction::Bonded { amount, .. }
+            | PoolAction::Unbonded { amount, .. }
+            | PoolAction::PaidOut { amount }
+            | PoolAction::Withdrawn { amount, .. } => amount,
+        }
+    }
+}
+
+/// `NominationPools::Bonded`, `Unbonded`, `PaidOut` or `Withdrawn`.
+///
+/// ```no_run
+/// use event_listener::{
+///     events::SubscribeOpts,
+///     pallets::{
+///         self,
+///         nomination_pools::PoolEvent,
+///     },
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+/// use futures::StreamExt;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let mut pool_events = pallets::subscribe::<_, _, PoolEvent<_>>(&api, SubscribeOpts::new())
+///     .await
+///     .unwrap();
+/// while let Some(event) = pool_events.next().await {
+///     let event = event.unwrap().event;
+///     if event.pool_id == 42 {
+///         println!("{}: {:?}", event.member, event.action);
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub struct PoolEvent<T: Config> {
+    /// The ID of the pool.
+    pub pool_id: u32,
+    /// The member of the pool.
+    pub member: T::AccountId,
+    /// What the member did.
+    pub action: PoolAction,
+}
+
+impl<T: Config> PalletEvent<T> for PoolEvent<T> {
+    const PALLET: &'static str = "NominationPools";
+
+    fn decode(variant: &str, fields: &EventFields<'_>) -> Result<Option<Self>, Error> {
+        // Points and eras were added to the events in later versions.
+        let points = || fields.has("points").then(|| fields.number("points", 3)).transpose();
+        let action = match variant {
+            "Bonded" => {
+                PoolAction::Bonded {
+                    amount: fields.number("bonded", 2)?,
+                    joined: fields.bool("joined", 3)?,
+                }
+            }
+            "Unbonded" => {
+                PoolAction::Unbonded {
+                    amount: fields.number("balance", 2)?,
+                    points: points()?,
+                    era: fields.has("era").then(|| fields.u32("era", 4)).transpose()?,
+                }
+            }
+            "PaidOut" => {
+                PoolAction::PaidOut {
+                    amount: fields.number("payout", 2)?,
+                }
+            }
+            "Withdrawn" => {
+                PoolAction::Withdrawn {
+                    amount: fields.number("balance", 2)?,
+                    points: points()?,
+                }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(PoolEvent {
+            pool_id: fields.u32("pool_id", 1)?,
+            member: fields.account::<T>("member", 0)?,
+            action,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        pallets::{
+            find_in,
+            test_utils::pallet_events,
+        },
+        SubstrateConfig,
+    };
+    use codec::{
+        Decode,
+        Encode,
+    };
+    use scale_info::TypeInfo;
+    use sp_core::crypto::AccountId32;
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Event {
+        Bonded {
+            member: AccountId32,
+            pool_id: u32,
+            bonded: u128,
+            joined: bool,
+        },
+        Unbonded {
+            member: AccountId32,
+            pool_id: u32,
+            balance: u128,
+            points: u128,
+            era: u32,
+        },
+        PaidOut {
+            member: AccountId32,
+            pool_id: u32,
+            payout: u128,
+        },
+        // As in older versions of the pallet.
+        Withdrawn {
+            member: AccountId32,
+            pool_id: u32,
+            balance: u128,
+        },
+    }
+
+    #[test]
+    fn pool_events_are_decoded() {
+        let member = AccountId32::new([1; 32]);
+        let events = pallet_events(vec![
+            Event::Bonded {
+                member: member.clone(),
+                pool_id: 42,
+                bonded: 10,
+                joined: true,
+            },
+            Event::Unbonded {
+                member: member.clone(),
+                pool_id: 42,
+                balance: 5,
+                points: 6,
+                era: 100,
+            },
+            Event::PaidOut {
+                member: member.clone(),
+                pool_id: 42,
+                payout: 1,
+            },
+            Event::Withdrawn {
+                member: member.clone(),
+                pool_id: 42,
+                balance: 5,
+            },
+        ]);
+        let found: Vec<_> = find_in::<SubstrateConfig, PoolEvent<_>>(&events, "Test")
+            .map(|found| {
+                let event = found.unwrap().event;
+                assert_eq!((event.pool_id, &event.member), (42, &member));
+                event.action
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                PoolAction::Bonded {
+                    amount: 10,
+                    joined: true
+                },
+                PoolAction::Unbonded {
+                    amount: 5,
+                    points: Some(6),
+                    era: Some(100)
+                },
+                PoolAction::PaidOut { amount: 1 },
+                PoolAction::Withdrawn {
+                    amount: 5,
+                    points: None
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Helpers for the `ParaInclusion` pallet of relay chains, which backs and
+//! includes the candidate blocks of parachains.
+
+use super::{
+    EventFields,
+    PalletEvent,
+};
+use crate::{
+    error::Error,
+    Config,
+};
+use derivative::Derivative;
+use sp_runtime::traits::Hash;
+
+/// What happened to a parachain candidate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CandidateStatus {
+    /// `ParaInclusion::CandidateBacked`: the candidate was backed by its
+    /// validator group, and is waiting for its data to become available.
+    Backed,
+    /// `ParaInclusion::CandidateIncluded`: the candidate's data became
+    /// available, and it was included in the relay chain.
+    Included,
+    /// `ParaInclusion::CandidateTimedOut`: the candidate's data didn't become
+    /// available in time, so it was dropped.
+    TimedOut,
+}
+
+/// `ParaInclusion::CandidateBacked`, `CandidateIncluded` or `CandidateTimedOut`.
+///
+/// ```no_run
+/// use event_listener::{
+///     events::SubscribeOpts,
+///     pallets::{
+///         self,
+///         para_inclusion::{
+///             CandidateEvent,
+///             CandidateStatus,
+///         },
+///     },
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+/// use futures::StreamExt;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let mut candidates =
+///     pallets::subscribe::<_, _, CandidateEvent<_>>(&api, SubscribeOpts::new())
+///         .await
+///         .unwrap();
+/// while let Some(candidate) = candidates.next().await {
+///     let candidate = candidate.unwrap().event;
+///     if candidate.para_id == 1000 && candidate.status == CandidateStatus::TimedOut {
+///         println!("Candidate {:?} timed out", candidate.candidate_hash);
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub struct CandidateEvent<T: Config> {
+    /// Which of the events this is.
+    pub status: CandidateStatus,
+    /// The ID of the parachain that the candidate is for.
+    pub para_id: u32,
+    /// The hash of the candidate receipt, which identifies the candidate.
+    pub candidate_hash: T::Hash,
+    /// The relay chain block that the candidate was built on top of.
+    pub relay_parent: T::Hash,
+    /// The hash of the parachain head that the candidate produces.
+    pub para_head: T::Hash,
+    /// The index of the availability core that the candidate occupies.
+    pub core_index: u32,
+    /// The index of the validator group that backed the candidate. Only given
+    /// with [`CandidateStatus::Backed`] and [`CandidateStatus::Included`].
+    pub group_index: Option<u32>,
+}
+
+impl<T: Config> PalletEvent<T> for CandidateEvent<T> {
+    const PALLET: &'static str = "ParaInclusion";
+
+    fn decode(variant: &str, fields: &EventFields<'_>) -> Result<Option<Self>, Error> {
+        let status = match variant {
+            "CandidateBacked" => CandidateStatus::Backed,
+            "CandidateIncluded" => CandidateStatus::Included,
+            "CandidateTimedOut" => CandidateStatus::TimedOut,
+            _ => return Ok(None),
+        };
+        // The fields are unnamed: the candidate receipt, the head data, the core
+        // index and, other than for timeouts, the group index.
+        let receipt = fields.nested("candidate_receipt", 0)?;
+        let descriptor = receipt.nested("descriptor", 0)?;
+        let group_index = match status {
+            CandidateStatus::TimedOut => None,
+            _ => Some(fields.u32("group_index", 3)?),
+        };
+        Ok(Some(CandidateEvent {
+            status,
+            para_id: descriptor.u32("para_id", 0)?,
+            // Candidates are identified by the hash of their receipt.
+            candidate_hash: T::Hashing::hash(&fields.encoded("candidate_receipt", 0)?),
+            relay_parent: descriptor.hash::<T>("relay_parent", 1)?,
+            para_head: descriptor.hash::<T>("para_head", 7)?,
+            core_index: fields.u32("core_index", 2)?,
+            group_index,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        pallets::{
+            find_in,
+            test_utils::pallet_events,
+        },
+        SubstrateConfig,
+    };
+    use codec::{
+        Decode,
+        Encode,
+    };
+    use scale_info::TypeInfo;
+    use sp_core::H256;
+    use sp_runtime::traits::BlakeTwo256;
+
+    // Shaped like the types of the same names in Polkadot.
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    struct CandidateDescriptor {
+        para_id: Id,
+        relay_parent: H256,
+        collator: [u8; 32],
+        persisted_validation_data_hash: H256,
+        pov_hash: H256,
+        erasure_root: H256,
+        signature: [u8; 64],
+        para_head: H256,
+        validation_code_hash: H256,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    struct Id(u32);
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    struct CandidateReceipt {
+        descriptor: CandidateDescriptor,
+        commitments_hash: H256,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    struct CoreIndex(u32);
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    struct GroupIndex(u32);
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Event {
+        CandidateBacked(CandidateReceipt, Vec<u8>, CoreIndex, GroupIndex),
+        CandidateIncluded(CandidateReceipt, Vec<u8>, CoreIndex, GroupIndex),
+        CandidateTimedOut(CandidateReceipt, Vec<u8>, CoreIndex),
+    }
+
+    #[test]
+    fn candidates_are_decoded() {
+        let receipt = CandidateReceipt {
+            descriptor: CandidateDescriptor {
+                para_id: Id(1000),
+                relay_parent: H256::repeat_byte(1),
+                collator: [2; 32],
+                persisted_validation_data_hash: H256::repeat_byte(3),
+                pov_hash: H256::repeat_byte(4),
+                erasure_root: H256::repeat_byte(5),
+                signature: [6; 64],
+                para_head: H256::repeat_byte(7),
+                validation_code_hash: H256::repeat_byte(8),
+            },
+            commitments_hash: H256::repeat_byte(9),
+        };
+        let events = pallet_events(vec![
+            Event::CandidateBacked(receipt.clone(), vec![1, 2], CoreIndex(3), GroupIndex(4)),
+            Event::CandidateTimedOut(receipt.clone(), vec![1, 2], CoreIndex(3)),
+        ]);
+        let found: Vec<_> = find_in::<SubstrateConfig, CandidateEvent<_>>(&events, "Test")
+            .map(|found| found.unwrap().event)
+            .collect();
+
+        let backed = CandidateEvent::<SubstrateConfig> {
+            status: CandidateStatus::Backed,
+            para_id: 1000,
+            candidate_hash: BlakeTwo256::hash(&receipt.encode()),
+            relay_parent: H256::repeat_byte(1),
+            para_head: H256::repeat_byte(7),
+            core_index: 3,
+            group_index: Some(4),
+        };
+        let timed_out = CandidateEvent {
+            status: CandidateStatus::TimedOut,
+            group_index: None,
+            ..backed.clone()
+        };
+        assert_eq!(found, vec![backed, timed_out]);
+    }
+}
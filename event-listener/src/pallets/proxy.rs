@@ -0,0 +1,393 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Helpers for the `Proxy` pallet, whose accounts let other accounts dispatch
+//! calls on their behalf.
+//!
+//! Proxies with a delay have to announce each call, and can only dispatch it
+//! once the delay has passed. That window is the time to notice an unexpected
+//! announcement against an account, and have the account reject it, which is
+//! what [`subscribe_announcements()`] is for.
+
+use super::{
+    fetch_storage,
+    named_field,
+    sequence_items,
+    subscribe,
+    twox_64_concat,
+    EventFields,
+    PalletEvent,
+};
+use crate::{
+    client::OnlineClientT,
+    error::{
+        BlockError,
+        Error,
+    },
+    events::SubscribeOpts,
+    utils::{
+        composite_values,
+        value_as_bytes,
+        value_as_u128,
+    },
+    Config,
+};
+use codec::Decode;
+use derivative::Derivative;
+use futures::{
+    future,
+    stream::BoxStream,
+    StreamExt,
+};
+use scale_value::ValueDef;
+use sp_runtime::traits::Header;
+use std::future::Future;
+
+/// `Proxy::Announced`, `ProxyExecuted` or `PureCreated`.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub enum ProxyEvent<T: Config> {
+    /// `Proxy::Announced`: a proxy announced a call that it'll dispatch on
+    /// behalf of an account once its delay has passed.
+    Announced {
+        /// The account that the call will be dispatched on behalf of.
+        real: T::AccountId,
+        /// The proxy that announced it.
+        proxy: T::AccountId,
+        /// The hash of the call.
+        call_hash: T::Hash,
+    },
+    /// `Proxy::ProxyExecuted`: a proxy dispatched a call.
+    Executed {
+        /// Whether the call was dispatched successfully.
+        success: bool,
+    },
+    /// `Proxy::PureCreated`, or `AnonymousCreated` in older versions of the
+    /// pallet: a keyless account was created, with a proxy to control it.
+    PureCreated {
+        /// The keyless account.
+        pure: T::AccountId,
+        /// The account that created it, and is its proxy.
+        who: T::AccountId,
+        /// The name of the type of the proxy, such as `Any`.
+        proxy_type: String,
+        /// The index that the account was created with, to tell it apart from
+        /// others created by the same account in the same extrinsic.
+        disambiguation_index: u16,
+    },
+}
+
+impl<T: Config> PalletEvent<T> for ProxyEvent<T> {
+    const PALLET: &'static str = "Proxy";
+
+    fn decode(variant: &str, fields: &EventFields<'_>) -> Result<Option<Self>, Error> {
+        let event = match variant {
+            "Announced" => {
+                ProxyEvent::Announced {
+                    real: fields.account::<T>("real", 0)?,
+                    proxy: fields.account::<T>("proxy", 1)?,
+                    call_hash: fields.hash::<T>("call_hash", 2)?,
+                }
+            }
+            "ProxyExecuted" => {
+                ProxyEvent::Executed {
+                    success: fields.variant_name("result", 0)? == "Ok",
+                }
+            }
+            "PureCreated" | "AnonymousCreated" => {
+                let pure = if fields.has("anonymous") { "anonymous" } else { "pure" };
+                ProxyEvent::PureCreated {
+                    pure: fields.account::<T>(pure, 0)?,
+                    who: fields.account::<T>("who", 1)?,
+                    proxy_type: fields.variant_name("proxy_type", 2)?.to_owned(),
+                    disambiguation_index: fields.decode("disambiguation_index", 3)?,
+                }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(event))
+    }
+}
+
+/// A call announced by a proxy, along with when it can be dispatched.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub struct Announcement<T: Config> {
+    /// The hash of the block that the call was announced in.
+    pub block_hash: T::Hash,
+    /// The index of the `Proxy::Announced` event in the events of the block.
+    pub event_index: u32,
+    /// The account that the call will be dispatched on behalf of.
+    pub real: T::AccountId,
+    /// The proxy that announced it.
+    pub proxy: T::AccountId,
+    /// The hash of the call.
+    pub call_hash: T::Hash,
+    /// The number of the block that the call was announced in.
+    pub announced_at: u64,
+    /// The number of blocks that the proxy has to wait after announcing a call
+    /// before dispatching it, or `None` if it's no longer a proxy of the
+    /// account.
+    pub delay: Option<u64>,
+}
+
+impl<T: Config> Announcement<T> {
+    /// The number of the first block that the call can be dispatched in.
+    pub fn executable_at(&self) -> Option<u64> {
+        self.delay.map(|delay| self.announced_at.saturating_add(delay))
+    }
+
+    /// The number of blocks left after the block with the number given before
+    /// the call can be dispatched, which is 0 once it can be.
+    pub fn blocks_remaining(&self, block_number: u64) -> Option<u64> {
+        self.executable_at()
+            .map(|executable_at| executable_at.saturating_sub(block_number))
+    }
+}
+
+/// Subscribe to calls announced by proxies of the accounts given, along with
+/// when each can be dispatched.
+///
+/// ```no_run
+/// use event_listener::{
+///     events::SubscribeOpts,
+///     pallets::proxy,
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+/// use futures::StreamExt;
+/// use sp_core::crypto::{
+///     AccountId32,
+///     Ss58Codec,
+/// };
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let custody =
+///     AccountId32::from_ss58check("5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY").unwrap();
+/// let mut announcements =
+///     proxy::subscribe_announcements(&api, vec![custody], SubscribeOpts::new())
+///         .await
+///         .unwrap();
+/// while let Some(announcement) = announcements.next().await {
+///     let announcement = announcement.unwrap();
+///     println!(
+///         "{} announced {:?}, which can be dispatched from block {:?}",
+///         announcement.proxy,
+///         announcement.call_hash,
+///         announcement.executable_at(),
+///     );
+/// }
+/// # }
+/// ```
+pub fn subscribe_announcements<T, Client>(
+    client: &Client,
+    accounts: Vec<T::AccountId>,
+    opts: SubscribeOpts,
+) -> impl Future<Output = Result<BoxStream<'static, Result<Announcement<T>, Error>>, Error>>
+       + Send
+       + 'static
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let client = client.clone();
+    async move {
+        let sub = subscribe::<T, Client, ProxyEvent<T>>(&client, opts).await?;
+        let announcements = sub
+            .filter(move |found| {
+                let watched = match found {
+                    Ok(found) => {
+                        match &found.event {
+                            ProxyEvent::Announced { real, .. } => accounts.contains(real),
+                            _ => false,
+                        }
+                    }
+                    Err(_) => true,
+                };
+                future::ready(watched)
+            })
+            .then(move |found| {
+                let client = client.clone();
+                async move {
+                    let found = found?;
+                    let (real, proxy, call_hash) = match found.event {
+                        ProxyEvent::Announced {
+                            real,
+                            proxy,
+                            call_hash,
+                        } => (real, proxy, call_hash),
+                        _ => unreachable!("only announcements are let through; qed"),
+                    };
+                    let header = match client.rpc().header(Some(found.block_hash)).await? {
+                        Some(header) => header,
+                        None => {
+                            return Err(BlockError::block_hash_not_found(found.block_hash).into())
+                        }
+                    };
+                    let delay = announcement_delay(&client, &real, &proxy, found.block_hash).await?;
+                    Ok(Announcement {
+                        block_hash: found.block_hash,
+                        event_index: found.event_index,
+                        real,
+                        proxy,
+                        call_hash,
+                        announced_at: (*header.number()).into(),
+                        delay,
+                    })
+                }
+            });
+        Ok(announcements.boxed())
+    }
+}
+
+/// The delay of the proxy given of an account as of the block with the hash
+/// given, from `Proxy::Proxies`, or `None` if it isn't a proxy of the account.
+///
+/// A proxy can be registered more than once with different proxy types, in
+/// which case the shortest delay is handed back.
+pub async fn announcement_delay<T, Client>(
+    client: &Client,
+    real: &T::AccountId,
+    proxy: &T::AccountId,
+    block_hash: T::Hash,
+) -> Result<Option<u64>, Error>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let key = twox_64_concat(real);
+    let proxies = match fetch_storage(client, "Proxy", "Proxies", &key, block_hash).await? {
+        Some(proxies) => proxies,
+        None => return Ok(None),
+    };
+    let metadata = client.metadata();
+    let types = &metadata.runtime_metadata().types;
+    // The proxies are stored along with the deposit held for them.
+    let definitions = match &proxies.value {
+        ValueDef::Composite(c) => composite_values(c).next(),
+        _ => None,
+    };
+    let mut delay = None;
+    for definition in definitions
+        .and_then(|definitions| sequence_items(definitions, types))
+        .unwrap_or_default()
+    {
+        let delegate = match named_field(definition, "delegate").and_then(value_as_bytes) {
+            Some(delegate) => T::AccountId::decode(&mut &*delegate)?,
+            None => continue,
+        };
+        if &delegate != proxy {
+            continue
+        }
+        let definition_delay = named_field(definition, "delay")
+            .and_then(value_as_u128)
+            .and_then(|delay| u64::try_from(delay).ok());
+        delay = delay.into_iter().chain(definition_delay).min();
+    }
+    Ok(delay)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        pallets::{
+            find_in,
+            test_utils::{
+                account,
+                pallet_events,
+            },
+        },
+        SubstrateConfig,
+    };
+    use codec::Encode;
+    use scale_info::TypeInfo;
+    use sp_core::{
+        crypto::AccountId32,
+        H256,
+    };
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum ProxyType {
+        Any,
+        Governance,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Event {
+        ProxyExecuted {
+            result: Result<(), u8>,
+        },
+        PureCreated {
+            pure: AccountId32,
+            who: AccountId32,
+            proxy_type: ProxyType,
+            disambiguation_index: u16,
+        },
+        Announced {
+            real: AccountId32,
+            proxy: AccountId32,
+            call_hash: H256,
+        },
+    }
+
+    #[test]
+    fn proxy_events_are_decoded() {
+        let events = pallet_events(vec![
+            Event::Announced {
+                real: account(1),
+                proxy: account(2),
+                call_hash: H256::repeat_byte(3),
+            },
+            Event::ProxyExecuted { result: Err(0) },
+            Event::PureCreated {
+                pure: account(4),
+                who: account(1),
+                proxy_type: ProxyType::Governance,
+                disambiguation_index: 5,
+            },
+        ]);
+        let found: Vec<_> = find_in::<SubstrateConfig, ProxyEvent<_>>(&events, "Test")
+            .map(|found| found.unwrap().event)
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ProxyEvent::Announced {
+                    real: account(1),
+                    proxy: account(2),
+                    call_hash: H256::repeat_byte(3),
+                },
+                ProxyEvent::Executed { success: false },
+                ProxyEvent::PureCreated {
+                    pure: account(4),
+                    who: account(1),
+                    proxy_type: "Governance".to_owned(),
+                    disambiguation_index: 5,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn announcements_can_be_dispatched_after_the_delay() {
+        let announcement = Announcement::<SubstrateConfig> {
+            block_hash: H256::zero(),
+            event_index: 0,
+            real: account(1),
+            proxy: account(2),
+            call_hash: H256::repeat_byte(3),
+            announced_at: 100,
+            delay: Some(10),
+        };
+        assert_eq!(announcement.executable_at(), Some(110));
+        assert_eq!(announcement.blocks_remaining(104), Some(6));
+        assert_eq!(announcement.blocks_remaining(120), Some(0));
+
+        let unknown = Announcement { delay: None, ..announcement };
+        assert_eq!(unknown.blocks_remaining(104), None);
+    }
+}
@@ -0,0 +1,456 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Helpers for the `Scheduler` pallet, which dispatches calls at later blocks,
+//! such as the calls of referenda once they've been approved and enacted.
+//!
+//! Scheduled calls are identified by their [`TaskAddress`]: the block that
+//! they're scheduled for, and their index in the agenda of that block.
+
+use super::{
+    fetch_storage,
+    named_field,
+    sequence_items,
+    subscribe,
+    twox_64_concat,
+    EventFields,
+    InBlock,
+    PalletEvent,
+};
+use crate::{
+    client::OnlineClientT,
+    error::{
+        BlockError,
+        Error,
+    },
+    events::SubscribeOpts,
+    utils::{
+        composite_field,
+        composite_values,
+        value_as_bytes,
+        value_as_u128,
+    },
+    Config,
+    Metadata,
+};
+use codec::Encode;
+use derivative::Derivative;
+use futures::{
+    stream::{
+        self,
+        BoxStream,
+    },
+    StreamExt,
+};
+use scale_value::ValueDef;
+use sp_runtime::traits::Header;
+use std::{
+    collections::HashMap,
+    future::Future,
+};
+
+/// The address of a scheduled call: the number of the block that it's
+/// scheduled for, and its index in the agenda of that block.
+#[derive(Derivative)]
+#[derivative(
+    Clone(bound = ""),
+    Copy(bound = ""),
+    Debug(bound = ""),
+    PartialEq(bound = ""),
+    Eq(bound = ""),
+    Hash(bound = "")
+)]
+pub struct TaskAddress<T: Config> {
+    /// The number of the block that the call is scheduled for.
+    pub when: T::BlockNumber,
+    /// The index of the call in the agenda of the block.
+    pub index: u32,
+}
+
+/// `Scheduler::Scheduled`, `Dispatched` or `Canceled`.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub enum SchedulerEvent<T: Config> {
+    /// `Scheduler::Scheduled`: a call was scheduled.
+    Scheduled {
+        /// Where the call was scheduled.
+        task: TaskAddress<T>,
+    },
+    /// `Scheduler::Dispatched`: a scheduled call was dispatched.
+    Dispatched {
+        /// Where the call was scheduled.
+        task: TaskAddress<T>,
+        /// The name that the call was scheduled under, if it was given one.
+        id: Option<Vec<u8>>,
+        /// Whether the call was dispatched successfully.
+        success: bool,
+    },
+    /// `Scheduler::Canceled`: a scheduled call was cancelled.
+    Canceled {
+        /// Where the call was scheduled.
+        task: TaskAddress<T>,
+    },
+}
+
+impl<T: Config> SchedulerEvent<T> {
+    /// Where the call that the event is about was scheduled.
+    pub fn task(&self) -> TaskAddress<T> {
+        match *self {
+            SchedulerEvent::Scheduled { task }
+            | SchedulerEvent::Dispatched { task, .. }
+            | SchedulerEvent::Canceled { task } => task,
+        }
+    }
+}
+
+impl<T: Config> PalletEvent<T> for SchedulerEvent<T> {
+    const PALLET: &'static str = "Scheduler";
+
+    fn decode(variant: &str, fields: &EventFields<'_>) -> Result<Option<Self>, Error> {
+        let address = || -> Result<TaskAddress<T>, Error> {
+            Ok(TaskAddress {
+                when: fields.decode("when", 0)?,
+                index: fields.u32("index", 1)?,
+            })
+        };
+        let event = match variant {
+            "Scheduled" => SchedulerEvent::Scheduled { task: address()? },
+            "Canceled" => SchedulerEvent::Canceled { task: address()? },
+            "Dispatched" => {
+                // The task is a `(when, index)` tuple here.
+                let task = fields.nested("task", 0)?;
+                // Names were `Vec<u8>`s in older versions of the pallet, and are
+                // `[u8; 32]`s now, so they're read as bytes either way.
+                let id = match &fields.value("id", 1)?.value {
+                    ValueDef::Variant(v) if v.name == "Some" => {
+                        composite_values(&v.values).next().and_then(value_as_bytes)
+                    }
+                    _ => None,
+                };
+                SchedulerEvent::Dispatched {
+                    task: TaskAddress {
+                        when: task.decode("when", 0)?,
+                        index: task.u32("index", 1)?,
+                    },
+                    id,
+                    success: fields.variant_name("result", 2)? == "Ok",
+                }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(event))
+    }
+}
+
+/// A scheduled call, read from the agenda of the `Scheduler` pallet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScheduledCall {
+    /// The name of the pallet of the call.
+    pub pallet: String,
+    /// The name of the call.
+    pub call: String,
+    /// The SCALE encoded call.
+    pub encoded: Vec<u8>,
+}
+
+impl ScheduledCall {
+    // Name an encoded call from the pallet and call indices that it starts with.
+    fn from_encoded(encoded: Vec<u8>, metadata: &Metadata) -> Option<ScheduledCall> {
+        let call = metadata.call(*encoded.first()?, *encoded.get(1)?).ok()?;
+        Some(ScheduledCall {
+            pallet: call.pallet().to_owned(),
+            call: call.call().to_owned(),
+            encoded,
+        })
+    }
+}
+
+/// Where a scheduled call has got to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskStatus {
+    /// Waiting for the block that it's scheduled for.
+    Scheduled,
+    /// Dispatched.
+    Dispatched {
+        /// Whether the call was dispatched successfully.
+        success: bool,
+    },
+    /// Cancelled before it was dispatched.
+    Canceled,
+}
+
+/// A scheduled call, as pieced together from the events about it.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub struct ScheduledTask<T: Config> {
+    /// The hash of the block of the event that this is the state after.
+    pub block_hash: T::Hash,
+    /// The index of the event in the events of the block.
+    pub event_index: u32,
+    /// Where the call was scheduled.
+    pub task: TaskAddress<T>,
+    /// The hash of the block that the call was scheduled in, if it was
+    /// scheduled after the subscription started.
+    pub scheduled_in: Option<T::Hash>,
+    /// Where the call has got to.
+    pub status: TaskStatus,
+    /// The call, if it could be read from the agenda. See [`scheduled_call()`].
+    pub call: Option<ScheduledCall>,
+}
+
+/// Subscribe to scheduled calls, handing back the state of a call each time
+/// that it's scheduled, dispatched or cancelled.
+///
+/// The call is read from the agenda when it's scheduled, or else from the
+/// agenda as of the block before it's dispatched or cancelled.
+///
+/// ```no_run
+/// use event_listener::{
+///     events::SubscribeOpts,
+///     pallets::scheduler::{
+///         self,
+///         TaskStatus,
+///     },
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+/// use futures::StreamExt;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let mut tasks = scheduler::subscribe_tasks(&api, SubscribeOpts::new()).await.unwrap();
+/// while let Some(task) = tasks.next().await {
+///     let task = task.unwrap();
+///     if let TaskStatus::Dispatched { success } = task.status {
+///         let call = task.call.map(|call| format!("{}::{}", call.pallet, call.call));
+///         println!("{:?} dispatched {:?}: {}", task.task, call, success);
+///     }
+/// }
+/// # }
+/// ```
+pub fn subscribe_tasks<T, Client>(
+    client: &Client,
+    opts: SubscribeOpts,
+) -> impl Future<Output = Result<BoxStream<'static, Result<ScheduledTask<T>, Error>>, Error>>
+       + Send
+       + 'static
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let client = client.clone();
+    async move {
+        let sub = subscribe::<T, Client, SchedulerEvent<T>>(&client, opts).await?;
+        // The calls scheduled since the subscription started, by where they were
+        // scheduled, along with the block that they were scheduled in.
+        let scheduled = HashMap::new();
+        let tasks = stream::unfold(
+            (sub, scheduled, client),
+            |(mut sub, mut scheduled, client)| {
+                async move {
+                    let task = match sub.next().await? {
+                        Ok(found) => track(&client, &mut scheduled, found).await,
+                        Err(e) => Err(e),
+                    };
+                    Some((task, (sub, scheduled, client)))
+                }
+            },
+        );
+        Ok(tasks.boxed())
+    }
+}
+
+// Work out the state of a call after the event given.
+async fn track<T, Client>(
+    client: &Client,
+    scheduled: &mut HashMap<TaskAddress<T>, (T::Hash, Option<ScheduledCall>)>,
+    found: InBlock<T, SchedulerEvent<T>>,
+) -> Result<ScheduledTask<T>, Error>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let task = found.event.task();
+    let status = match found.event {
+        SchedulerEvent::Scheduled { .. } => TaskStatus::Scheduled,
+        SchedulerEvent::Dispatched { success, .. } => TaskStatus::Dispatched { success },
+        SchedulerEvent::Canceled { .. } => TaskStatus::Canceled,
+    };
+    let (scheduled_in, call) = match status {
+        TaskStatus::Scheduled => {
+            let call = scheduled_call(client, task, found.block_hash).await?;
+            scheduled.insert(task, (found.block_hash, call.clone()));
+            (Some(found.block_hash), call)
+        }
+        _ => {
+            match scheduled.remove(&task) {
+                Some((scheduled_in, call)) => (Some(scheduled_in), call),
+                // The call is taken out of the agenda when it's dispatched or
+                // cancelled, so it's read from the block before.
+                None => {
+                    let header = match client.rpc().header(Some(found.block_hash)).await? {
+                        Some(header) => header,
+                        None => {
+                            return Err(BlockError::block_hash_not_found(found.block_hash).into())
+                        }
+                    };
+                    (None, scheduled_call(client, task, *header.parent_hash()).await?)
+                }
+            }
+        }
+    };
+    Ok(ScheduledTask {
+        block_hash: found.block_hash,
+        event_index: found.event_index,
+        task,
+        scheduled_in,
+        status,
+        call,
+    })
+}
+
+/// The call scheduled at the address given, as of the block with the hash
+/// given, from `Scheduler::Agenda`.
+///
+/// `None` if there's no call there, or if the call isn't held inline or as a
+/// preimage in the `Preimage` pallet, as in older versions of the pallets.
+pub async fn scheduled_call<T, Client>(
+    client: &Client,
+    task: TaskAddress<T>,
+    block_hash: T::Hash,
+) -> Result<Option<ScheduledCall>, Error>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let key = twox_64_concat(&task.when);
+    let agenda = match fetch_storage(client, "Scheduler", "Agenda", &key, block_hash).await? {
+        Some(agenda) => agenda,
+        None => return Ok(None),
+    };
+    let metadata = client.metadata();
+    let types = &metadata.runtime_metadata().types;
+    // Calls in the agenda are `Option`s, since they can be cancelled.
+    let call = sequence_items(&agenda, types)
+        .and_then(|calls| calls.into_iter().nth(task.index as usize))
+        .and_then(|scheduled| {
+            match &scheduled.value {
+                ValueDef::Variant(v) if v.name == "Some" => composite_values(&v.values).next(),
+                _ => None,
+            }
+        })
+        .and_then(|scheduled| named_field(scheduled, "call"));
+    let encoded = match call.map(|call| &call.value) {
+        Some(ValueDef::Variant(v)) if v.name == "Inline" => {
+            composite_values(&v.values).next().and_then(value_as_bytes)
+        }
+        Some(ValueDef::Variant(v)) if v.name == "Lookup" => {
+            let hash = composite_field(&v.values, "hash", 0).and_then(value_as_bytes);
+            let len = composite_field(&v.values, "len", 1)
+                .and_then(value_as_u128)
+                .and_then(|len| u32::try_from(len).ok());
+            match (hash, len) {
+                (Some(hash), Some(len)) => preimage(client, &hash, len, block_hash).await?,
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    Ok(encoded.and_then(|encoded| ScheduledCall::from_encoded(encoded, &metadata)))
+}
+
+// The preimage with the hash and length given, from `Preimage::PreimageFor`.
+async fn preimage<T, Client>(
+    client: &Client,
+    hash: &[u8],
+    len: u32,
+    block_hash: T::Hash,
+) -> Result<Option<Vec<u8>>, Error>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    // The map is keyed by `(hash, len)`, with the `Identity` hasher.
+    let mut key = hash.to_vec();
+    key.extend(len.encode());
+    let preimage = fetch_storage(client, "Preimage", "PreimageFor", &key, block_hash).await?;
+    Ok(preimage.as_ref().and_then(value_as_bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        pallets::{
+            find_in,
+            test_utils::pallet_events,
+        },
+        SubstrateConfig,
+    };
+    use codec::Decode;
+    use scale_info::TypeInfo;
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Event {
+        Scheduled {
+            when: u32,
+            index: u32,
+        },
+        Canceled {
+            when: u32,
+            index: u32,
+        },
+        Dispatched {
+            task: (u32, u32),
+            id: Option<[u8; 32]>,
+            result: Result<(), u8>,
+        },
+    }
+
+    #[test]
+    fn tasks_are_identified_by_their_address() {
+        let events = pallet_events(vec![
+            Event::Scheduled {
+                when: 100,
+                index: 1,
+            },
+            Event::Dispatched {
+                task: (100, 1),
+                id: Some([7; 32]),
+                result: Ok(()),
+            },
+            Event::Canceled {
+                when: 200,
+                index: 0,
+            },
+        ]);
+        let found: Vec<_> = find_in::<SubstrateConfig, SchedulerEvent<_>>(&events, "Test")
+            .map(|found| found.unwrap().event)
+            .collect();
+
+        let task = TaskAddress {
+            when: 100,
+            index: 1,
+        };
+        assert_eq!(
+            found,
+            vec![
+                SchedulerEvent::Scheduled { task },
+                SchedulerEvent::Dispatched {
+                    task,
+                    id: Some(vec![7; 32]),
+                    success: true,
+                },
+                SchedulerEvent::Canceled {
+                    task: TaskAddress {
+                        when: 200,
+                        index: 0,
+                    },
+                },
+            ]
+        );
+        assert_eq!(found[0].task(), found[1].task());
+    }
+}
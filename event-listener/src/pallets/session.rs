@@ -0,0 +1,166 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Helpers for the `Session` pallet, which rotates the set of validators.
+
+use super::{
+    subscribe,
+    EventFields,
+    PalletEvent,
+};
+use crate::{
+    client::OnlineClientT,
+    error::Error,
+    events::SubscribeOpts,
+    Config,
+};
+use codec::Decode;
+use derivative::Derivative;
+use futures::{
+    stream::BoxStream,
+    StreamExt,
+};
+use sp_core::twox_128;
+use std::future::Future;
+
+/// `Session::NewSession`: a new session has started, and with it possibly a new
+/// set of validators.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NewSession {
+    /// The index of the session that has started.
+    pub session_index: u32,
+}
+
+impl<T: Config> PalletEvent<T> for NewSession {
+    const PALLET: &'static str = "Session";
+
+    fn decode(variant: &str, fields: &EventFields<'_>) -> Result<Option<Self>, Error> {
+        if variant != "NewSession" {
+            return Ok(None)
+        }
+        Ok(Some(NewSession {
+            session_index: fields.u32("session_index", 0)?,
+        }))
+    }
+}
+
+/// A new session, along with the validators of it.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub struct SessionChange<T: Config> {
+    /// The hash of the block that the session started in.
+    pub block_hash: T::Hash,
+    /// The index of the session.
+    pub session_index: u32,
+    /// The validators of the session, as of the block that it started in.
+    pub validators: Vec<T::AccountId>,
+}
+
+/// Subscribe to the start of each session, along with the validator set of it,
+/// which is read from `Session::Validators` at the block that it started in.
+///
+/// ```no_run
+/// use event_listener::{
+///     events::SubscribeOpts,
+///     pallets::session,
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+/// use futures::StreamExt;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let mut changes = session::subscribe_session_changes(&api, SubscribeOpts::new())
+///     .await
+///     .unwrap();
+/// while let Some(change) = changes.next().await {
+///     let change = change.unwrap();
+///     println!("Session {}: {} validators", change.session_index, change.validators.len());
+/// }
+/// # }
+/// ```
+pub fn subscribe_session_changes<T, Client>(
+    client: &Client,
+    opts: SubscribeOpts,
+) -> impl Future<Output = Result<BoxStream<'static, Result<SessionChange<T>, Error>>, Error>>
+       + Send
+       + 'static
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let client = client.clone();
+    async move {
+        let sub = subscribe::<T, Client, NewSession>(&client, opts).await?;
+        let changes = sub.then(move |found| {
+            let client = client.clone();
+            async move {
+                let found = found?;
+                let validators = validators(&client, found.block_hash).await?;
+                Ok(SessionChange {
+                    block_hash: found.block_hash,
+                    session_index: found.event.session_index,
+                    validators,
+                })
+            }
+        });
+        Ok(changes.boxed())
+    }
+}
+
+/// The validators of the session as of the block with the hash given, read from
+/// `Session::Validators`. Empty if the chain has no `Session` pallet.
+pub async fn validators<T, Client>(
+    client: &Client,
+    block_hash: T::Hash,
+) -> Result<Vec<T::AccountId>, Error>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    match client
+        .rpc()
+        .storage(&validators_key(), Some(block_hash))
+        .await?
+    {
+        Some(data) => Ok(Vec::<T::AccountId>::decode(&mut &*data.0)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+// The storage key for `Session::Validators`.
+pub(crate) fn validators_key() -> Vec<u8> {
+    let mut storage_key = twox_128(b"Session").to_vec();
+    storage_key.extend(twox_128(b"Validators").to_vec());
+    storage_key
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        pallets::{
+            find_in,
+            test_utils::pallet_events,
+        },
+        SubstrateConfig,
+    };
+    use codec::Encode;
+    use scale_info::TypeInfo;
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Event {
+        NewSession { session_index: u32 },
+    }
+
+    #[test]
+    fn new_sessions_are_decoded() {
+        let events = pallet_events(vec![Event::NewSession { session_index: 7 }]);
+        let found: Vec<_> = find_in::<SubstrateConfig, NewSession>(&events, "Test")
+            .map(|found| found.unwrap().event)
+            .collect();
+        assert_eq!(found, vec![NewSession { session_index: 7 }]);
+    }
+}
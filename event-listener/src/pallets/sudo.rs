@@ -0,0 +1,517 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Helpers for the `Sudo` pallet, whose key can dispatch any call with the root
+//! origin, and for watching for calls dispatched with the root origin more
+//! generally.
+
+use super::{
+    block_extrinsics,
+    find,
+    EventFields,
+    InBlock,
+    PalletEvent,
+};
+use crate::{
+    blocks::ExtrinsicDetails,
+    client::OnlineClientT,
+    error::Error,
+    events::{
+        Events,
+        EventsClient,
+        Phase,
+        SubscribeOpts,
+    },
+    utils::{
+        composite_field,
+        composite_values,
+        value_as_bytes,
+    },
+    Config,
+};
+use codec::Decode;
+use derivative::Derivative;
+use futures::{
+    stream::{
+        self,
+        BoxStream,
+    },
+    StreamExt,
+};
+use scale_value::{
+    scale::TypeId,
+    Composite,
+    Value,
+    ValueDef,
+};
+use std::{
+    collections::HashMap,
+    future::Future,
+};
+
+/// `Sudo::Sudid`, `SudoAsDone`, `KeyChanged` or `KeyRemoved`.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub enum SudoEvent<T: Config> {
+    /// `Sudo::Sudid`: the sudo key dispatched a call with the root origin.
+    Sudid {
+        /// Whether the call was dispatched successfully.
+        success: bool,
+    },
+    /// `Sudo::SudoAsDone`: the sudo key dispatched a call as another account.
+    SudoAsDone {
+        /// Whether the call was dispatched successfully.
+        success: bool,
+    },
+    /// `Sudo::KeyChanged`: the sudo key was changed.
+    KeyChanged {
+        /// The old key, if there was one. Older versions of the pallet give
+        /// this alone.
+        old: Option<T::AccountId>,
+        /// The new key, if the event gives it.
+        new: Option<T::AccountId>,
+    },
+    /// `Sudo::KeyRemoved`: the sudo key was removed, for good.
+    KeyRemoved,
+}
+
+impl<T: Config> PalletEvent<T> for SudoEvent<T> {
+    const PALLET: &'static str = "Sudo";
+
+    fn decode(variant: &str, fields: &EventFields<'_>) -> Result<Option<Self>, Error> {
+        let event = match variant {
+            "Sudid" => {
+                SudoEvent::Sudid {
+                    success: fields.variant_name("sudo_result", 0)? == "Ok",
+                }
+            }
+            "SudoAsDone" => {
+                SudoEvent::SudoAsDone {
+                    success: fields.variant_name("sudo_result", 0)? == "Ok",
+                }
+            }
+            "KeyChanged" if fields.has("new") => {
+                SudoEvent::KeyChanged {
+                    old: fields.decode("old", 0)?,
+                    new: Some(fields.account::<T>("new", 1)?),
+                }
+            }
+            "KeyChanged" => {
+                SudoEvent::KeyChanged {
+                    old: fields.decode("old_sudoer", 0)?,
+                    new: None,
+                }
+            }
+            "KeyRemoved" => SudoEvent::KeyRemoved,
+            _ => return Ok(None),
+        };
+        Ok(Some(event))
+    }
+}
+
+// `Utility::DispatchedAs`: a call was dispatched with `Utility::dispatch_as`.
+struct DispatchedAs {
+    success: bool,
+}
+
+impl<T: Config> PalletEvent<T> for DispatchedAs {
+    const PALLET: &'static str = "Utility";
+
+    fn decode(variant: &str, fields: &EventFields<'_>) -> Result<Option<Self>, Error> {
+        if variant != "DispatchedAs" {
+            return Ok(None)
+        }
+        Ok(Some(DispatchedAs {
+            success: fields.variant_name("result", 0)? == "Ok",
+        }))
+    }
+}
+
+/// How a call was dispatched with a privileged origin.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub enum PrivilegedDispatch<T: Config> {
+    /// With the root origin, by `Sudo::sudo` or `Sudo::sudo_unchecked_weight`.
+    Sudo,
+    /// As another account, by `Sudo::sudo_as`.
+    SudoAs {
+        /// The account that the call was dispatched as, if it was given as an
+        /// account ID rather than as another kind of address.
+        who: Option<T::AccountId>,
+    },
+    /// With the root origin, by `Utility::dispatch_as`.
+    DispatchAsRoot,
+}
+
+/// The name of a call.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallName {
+    /// The name of the pallet of the call.
+    pub pallet: String,
+    /// The name of the call.
+    pub call: String,
+}
+
+/// A call dispatched with a privileged origin.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub struct PrivilegedCall<T: Config> {
+    /// The hash of the block that the call was dispatched in.
+    pub block_hash: T::Hash,
+    /// The index of the event marking the dispatch in the events of the block.
+    pub event_index: u32,
+    /// The index of the extrinsic that dispatched the call in the block.
+    pub extrinsic_index: Option<u32>,
+    /// How the call was dispatched.
+    pub dispatch: PrivilegedDispatch<T>,
+    /// The call, or `None` if it couldn't be found in the extrinsic.
+    pub call: Option<CallName>,
+    /// Whether the call was dispatched successfully.
+    pub success: bool,
+}
+
+/// Subscribe to calls dispatched with the root origin, or as another account
+/// by the sudo key, along with the names of the calls.
+///
+/// Calls are found from the events that mark them, `Sudo::Sudid`,
+/// `Sudo::SudoAsDone` and `Utility::DispatchedAs`, and the extrinsics that
+/// emitted them, including when nested in batches or other calls. Calls with
+/// the root origin from elsewhere, such as the calls of referenda dispatched by
+/// the `Scheduler` pallet, aren't found here; see
+/// [`crate::pallets::scheduler`] for those.
+///
+/// ```no_run
+/// use event_listener::{
+///     events::SubscribeOpts,
+///     pallets::sudo,
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+/// use futures::StreamExt;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let mut calls = sudo::subscribe_privileged_calls(&api, SubscribeOpts::new()).await.unwrap();
+/// while let Some(call) = calls.next().await {
+///     let call = call.unwrap();
+///     eprintln!("ALERT: {:?} dispatched {:?}", call.dispatch, call.call);
+/// }
+/// # }
+/// ```
+pub fn subscribe_privileged_calls<T, Client>(
+    client: &Client,
+    opts: SubscribeOpts,
+) -> impl Future<Output = Result<BoxStream<'static, Result<PrivilegedCall<T>, Error>>, Error>>
+       + Send
+       + 'static
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let client = client.clone();
+    let events = EventsClient::new(client.clone());
+    let opts = opts.pallet("Sudo").pallet("Utility");
+    async move {
+        let sub = events.subscribe_with(opts).await?;
+        let calls = sub
+            .then(move |events| {
+                let client = client.clone();
+                async move {
+                    match events {
+                        Ok(events) => privileged_calls(&client, &events).await,
+                        Err(e) => Err(e),
+                    }
+                }
+            })
+            .flat_map(|found| {
+                let found = match found {
+                    Ok(found) => found.into_iter().map(Ok).collect(),
+                    Err(e) => vec![Err(e)],
+                };
+                stream::iter(found)
+            });
+        Ok(calls.boxed())
+    }
+}
+
+/// The calls dispatched with a privileged origin in the block of the events
+/// given. See [`subscribe_privileged_calls()`]. The body of the block is only
+/// fetched if there are any.
+pub async fn privileged_calls<T, Client>(
+    client: &Client,
+    events: &Events<T>,
+) -> Result<Vec<PrivilegedCall<T>>, Error>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    // Each event is paired with the kind of call that emits it.
+    let mut found: Vec<(InBlock<T, ()>, Kind, bool)> = Vec::new();
+    for sudo in find::<T, SudoEvent<T>>(events) {
+        let sudo = sudo?;
+        let (kind, success) = match sudo.event {
+            SudoEvent::Sudid { success } => (Kind::Sudo, success),
+            SudoEvent::SudoAsDone { success } => (Kind::SudoAs, success),
+            _ => continue,
+        };
+        found.push((without_event(sudo), kind, success));
+    }
+    for dispatched in find::<T, DispatchedAs>(events) {
+        let dispatched = dispatched?;
+        let success = dispatched.event.success;
+        found.push((without_event(dispatched), Kind::DispatchAs, success));
+    }
+    if found.is_empty() {
+        return Ok(Vec::new())
+    }
+    found.sort_by_key(|(found, ..)| found.event_index);
+
+    let extrinsics = block_extrinsics(client, events).await?;
+    // The calls of each extrinsic with any, in the order that they're dispatched.
+    let mut dispatches: HashMap<u32, Vec<Dispatch<T>>> = HashMap::new();
+    let mut calls = Vec::new();
+    for (found, kind, success) in found {
+        let extrinsic_index = match found.phase {
+            Phase::ApplyExtrinsic(index) => Some(index),
+            _ => None,
+        };
+        let dispatch = match extrinsic_index {
+            Some(index) => {
+                if !dispatches.contains_key(&index) {
+                    let extrinsic = extrinsics.iter().nth(index as usize).transpose()?;
+                    let found = match extrinsic {
+                        Some(extrinsic) => extrinsic_dispatches(&extrinsic)?,
+                        None => Vec::new(),
+                    };
+                    dispatches.insert(index, found);
+                }
+                let extrinsic_dispatches = dispatches
+                    .get_mut(&index)
+                    .expect("the dispatches of the extrinsic were just added; qed");
+                // Events are emitted in the order that the calls are dispatched,
+                // so each is for the first call of its kind not yet accounted for.
+                extrinsic_dispatches
+                    .iter()
+                    .position(|dispatch| dispatch.kind == kind)
+                    .map(|position| extrinsic_dispatches.remove(position))
+            }
+            None => None,
+        };
+        let (dispatch, call) = match (kind, dispatch) {
+            (Kind::Sudo, dispatch) => (PrivilegedDispatch::Sudo, dispatch.and_then(|d| d.call)),
+            (Kind::SudoAs, dispatch) => {
+                let (who, call) = dispatch.map(|d| (d.who, d.call)).unwrap_or_default();
+                (PrivilegedDispatch::SudoAs { who }, call)
+            }
+            // Only calls dispatched as root are of interest here.
+            (Kind::DispatchAs, Some(dispatch)) if dispatch.root => {
+                (PrivilegedDispatch::DispatchAsRoot, dispatch.call)
+            }
+            (Kind::DispatchAs, _) => continue,
+        };
+        calls.push(PrivilegedCall {
+            block_hash: found.block_hash,
+            event_index: found.event_index,
+            extrinsic_index,
+            dispatch,
+            call,
+            success,
+        });
+    }
+    Ok(calls)
+}
+
+// Drop the event from where it was found, to keep events of different types
+// together.
+fn without_event<T: Config, E>(found: InBlock<T, E>) -> InBlock<T, ()> {
+    InBlock {
+        block_hash: found.block_hash,
+        event_index: found.event_index,
+        phase: found.phase,
+        event: (),
+    }
+}
+
+// The kinds of calls that dispatch other calls with a privileged origin.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Sudo,
+    SudoAs,
+    DispatchAs,
+}
+
+// A call dispatched with a privileged origin, found in an extrinsic.
+struct Dispatch<T: Config> {
+    kind: Kind,
+    call: Option<CallName>,
+    // For `Sudo::sudo_as`.
+    who: Option<T::AccountId>,
+    // For `Utility::dispatch_as`, whether the origin is root.
+    root: bool,
+}
+
+// The calls dispatched with a privileged origin by an extrinsic, in the order
+// that they're dispatched.
+fn extrinsic_dispatches<T: Config>(
+    extrinsic: &ExtrinsicDetails<T>,
+) -> Result<Vec<Dispatch<T>>, Error> {
+    let mut dispatches = Vec::new();
+    call_dispatches(
+        extrinsic.pallet_name(),
+        extrinsic.call_name(),
+        &extrinsic.field_values()?,
+        &mut dispatches,
+    );
+    Ok(dispatches)
+}
+
+fn call_dispatches<T: Config>(
+    pallet: &str,
+    call: &str,
+    fields: &Composite<TypeId>,
+    dispatches: &mut Vec<Dispatch<T>>,
+) {
+    // Calls nested inside this one are dispatched, and finish, first.
+    for value in composite_values(fields) {
+        nested_dispatches(value, dispatches);
+    }
+    let (kind, index) = match (pallet, call) {
+        ("Sudo", "sudo") | ("Sudo", "sudo_unchecked_weight") => (Kind::Sudo, 0),
+        ("Sudo", "sudo_as") => (Kind::SudoAs, 1),
+        ("Utility", "dispatch_as") => (Kind::DispatchAs, 1),
+        _ => return,
+    };
+    let who = match kind {
+        Kind::SudoAs => composite_field(fields, "who", 0).and_then(account::<T>),
+        _ => None,
+    };
+    let root = match kind {
+        Kind::DispatchAs => composite_field(fields, "as_origin", 0).map_or(false, is_root),
+        _ => false,
+    };
+    dispatches.push(Dispatch {
+        kind,
+        call: composite_field(fields, "call", index).and_then(call_name),
+        who,
+        root,
+    });
+}
+
+// A call is a variant named after its pallet, holding a variant named after the
+// call, so look for those in the values of calls.
+fn nested_dispatches<T: Config>(value: &Value<TypeId>, dispatches: &mut Vec<Dispatch<T>>) {
+    let fields = match &value.value {
+        ValueDef::Variant(pallet) => {
+            let mut values = composite_values(&pallet.values);
+            if let (Some(call), None) = (values.next(), values.next()) {
+                if let ValueDef::Variant(call) = &call.value {
+                    call_dispatches(&pallet.name, &call.name, &call.values, dispatches);
+                    return
+                }
+            }
+            &pallet.values
+        }
+        ValueDef::Composite(c) => c,
+        _ => return,
+    };
+    for value in composite_values(fields) {
+        nested_dispatches(value, dispatches);
+    }
+}
+
+// The name of a call, from its value.
+fn call_name(value: &Value<TypeId>) -> Option<CallName> {
+    let pallet = match &value.value {
+        ValueDef::Variant(pallet) => pallet,
+        _ => return None,
+    };
+    match &composite_values(&pallet.values).next()?.value {
+        ValueDef::Variant(call) => {
+            Some(CallName {
+                pallet: pallet.name.clone(),
+                call: call.name.clone(),
+            })
+        }
+        _ => None,
+    }
+}
+
+// An account, from an address such as `MultiAddress::Id(account)` or from the
+// account itself.
+fn account<T: Config>(value: &Value<TypeId>) -> Option<T::AccountId> {
+    let bytes = match &value.value {
+        ValueDef::Variant(v) if v.name == "Id" => {
+            composite_values(&v.values).next().and_then(value_as_bytes)
+        }
+        _ => value_as_bytes(value),
+    }?;
+    T::AccountId::decode(&mut &*bytes).ok()
+}
+
+// Whether an origin, such as `OriginCaller::system(RawOrigin::Root)`, is root.
+fn is_root(value: &Value<TypeId>) -> bool {
+    match &value.value {
+        ValueDef::Variant(v) if v.name == "Root" => true,
+        ValueDef::Variant(v) if v.name == "system" => {
+            composite_values(&v.values).next().map_or(false, is_root)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        pallets::{
+            find_in,
+            test_utils::{
+                account,
+                pallet_events,
+            },
+        },
+        SubstrateConfig,
+    };
+    use codec::Encode;
+    use scale_info::TypeInfo;
+    use sp_core::crypto::AccountId32;
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Event {
+        Sudid { sudo_result: Result<(), u8> },
+        KeyChanged { old: Option<AccountId32>, new: AccountId32 },
+        SudoAsDone { sudo_result: Result<(), u8> },
+    }
+
+    #[test]
+    fn sudo_events_are_decoded() {
+        let events = pallet_events(vec![
+            Event::Sudid {
+                sudo_result: Ok(()),
+            },
+            Event::KeyChanged {
+                old: Some(account(1)),
+                new: account(2),
+            },
+            Event::SudoAsDone {
+                sudo_result: Err(0),
+            },
+        ]);
+        let found: Vec<_> = find_in::<SubstrateConfig, SudoEvent<_>>(&events, "Test")
+            .map(|found| found.unwrap().event)
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                SudoEvent::Sudid { success: true },
+                SudoEvent::KeyChanged {
+                    old: Some(account(1)),
+                    new: Some(account(2)),
+                },
+                SudoEvent::SudoAsDone { success: false },
+            ]
+        );
+    }
+}
@@ -0,0 +1,300 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Helpers for the `System` pallet.
+//!
+//! Many protocols put their data on chain as remarks, with
+//! `System::remark_with_event`. The `System::Remarked` event that it emits only
+//! carries the hash of the remark, so [`subscribe_remarks()`] fetches the
+//! extrinsic that emitted it to find the remark itself.
+
+use super::{
+    block_extrinsics,
+    find,
+    EventFields,
+    PalletEvent,
+};
+use crate::{
+    blocks::ExtrinsicDetails,
+    client::OnlineClientT,
+    error::Error,
+    events::{
+        Events,
+        EventsClient,
+        Phase,
+        SubscribeOpts,
+    },
+    utils::{
+        composite_field,
+        composite_values,
+        value_as_bytes,
+    },
+    Config,
+};
+use derivative::Derivative;
+use futures::{
+    stream::{
+        self,
+        BoxStream,
+    },
+    StreamExt,
+};
+use scale_value::{
+    scale::TypeId,
+    Value,
+    ValueDef,
+};
+use sp_runtime::traits::Hash;
+use std::future::Future;
+
+/// `System::Remarked`: a remark was made with `System::remark_with_event`.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub struct Remarked<T: Config> {
+    /// The account that made the remark.
+    pub sender: T::AccountId,
+    /// The hash of the remark.
+    pub hash: T::Hash,
+}
+
+impl<T: Config> PalletEvent<T> for Remarked<T> {
+    const PALLET: &'static str = "System";
+
+    fn decode(variant: &str, fields: &EventFields<'_>) -> Result<Option<Self>, Error> {
+        if variant != "Remarked" {
+            return Ok(None)
+        }
+        Ok(Some(Remarked {
+            sender: fields.account::<T>("sender", 0)?,
+            hash: fields.hash::<T>("hash", 1)?,
+        }))
+    }
+}
+
+/// A remark, found from its `System::Remarked` event.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub struct Remark<T: Config> {
+    /// The hash of the block that the remark was made in.
+    pub block_hash: T::Hash,
+    /// The index of the `System::Remarked` event in the events of the block.
+    pub event_index: u32,
+    /// The index of the extrinsic that made the remark in the block.
+    pub extrinsic_index: Option<u32>,
+    /// The account that made the remark.
+    pub sender: T::AccountId,
+    /// The hash of the remark.
+    pub hash: T::Hash,
+    /// The remark, or `None` if it couldn't be found in the extrinsic, such as
+    /// if it was made by a call wrapped in a way that hides it, like a call
+    /// dispatched by a multisig from its hash.
+    pub bytes: Option<Vec<u8>>,
+}
+
+impl<T: Config> Remark<T> {
+    /// The remark as text, if it was found and is valid UTF-8.
+    pub fn text(&self) -> Option<&str> {
+        std::str::from_utf8(self.bytes.as_ref()?).ok()
+    }
+}
+
+/// Subscribe to remarks made with `System::remark_with_event`, fetching the
+/// body of each block with any in to find the remarks themselves. The options
+/// are those of [`crate::events::EventsClient::subscribe_with()`].
+///
+/// ```no_run
+/// use event_listener::{
+///     events::SubscribeOpts,
+///     pallets::system,
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+/// use futures::StreamExt;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let mut remarks = system::subscribe_remarks(&api, SubscribeOpts::new()).await.unwrap();
+/// while let Some(remark) = remarks.next().await {
+///     let remark = remark.unwrap();
+///     match remark.text() {
+///         Some(text) => println!("{} remarked {:?}", remark.sender, text),
+///         None => println!("{} remarked {:?}", remark.sender, remark.bytes),
+///     }
+/// }
+/// # }
+/// ```
+pub fn subscribe_remarks<T, Client>(
+    client: &Client,
+    opts: SubscribeOpts,
+) -> impl Future<Output = Result<BoxStream<'static, Result<Remark<T>, Error>>, Error>>
+       + Send
+       + 'static
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let client = client.clone();
+    let events = EventsClient::new(client.clone());
+    let opts = opts.pallet(<Remarked<T> as PalletEvent<T>>::PALLET);
+    async move {
+        let sub = events.subscribe_with(opts).await?;
+        let remarks = sub
+            .then(move |events| {
+                let client = client.clone();
+                async move {
+                    match events {
+                        Ok(events) => remarks(&client, &events).await,
+                        Err(e) => Err(e),
+                    }
+                }
+            })
+            .flat_map(|found| {
+                let found = match found {
+                    Ok(found) => found.into_iter().map(Ok).collect(),
+                    Err(e) => vec![Err(e)],
+                };
+                stream::iter(found)
+            });
+        Ok(remarks.boxed())
+    }
+}
+
+/// The remarks made in the block of the events given, along with the remarks
+/// themselves, which are found in the extrinsics of the block. The body of the
+/// block is only fetched if there are any remarks.
+pub async fn remarks<T, Client>(
+    client: &Client,
+    events: &Events<T>,
+) -> Result<Vec<Remark<T>>, Error>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let found = find::<T, Remarked<T>>(events).collect::<Result<Vec<_>, Error>>()?;
+    if found.is_empty() {
+        return Ok(Vec::new())
+    }
+
+    let block_hash = events.block_hash();
+    let extrinsics = block_extrinsics(client, events).await?;
+    found
+        .into_iter()
+        .map(|found| {
+            let extrinsic_index = match found.phase {
+                Phase::ApplyExtrinsic(index) => Some(index),
+                _ => None,
+            };
+            let extrinsic = extrinsic_index
+                .and_then(|index| extrinsics.iter().nth(index as usize))
+                .transpose()?;
+            let bytes = match extrinsic {
+                Some(extrinsic) => remark_in(&extrinsic, &found.event.hash)?,
+                None => None,
+            };
+            Ok(Remark {
+                block_hash,
+                event_index: found.event_index,
+                extrinsic_index,
+                sender: found.event.sender,
+                hash: found.event.hash,
+                bytes,
+            })
+        })
+        .collect()
+}
+
+// Find the remark with the hash given in an extrinsic, which may have made it
+// directly or from within another call, such as a batch or a proxy call.
+fn remark_in<T: Config>(
+    extrinsic: &ExtrinsicDetails<T>,
+    hash: &T::Hash,
+) -> Result<Option<Vec<u8>>, Error> {
+    let fields = extrinsic.field_values()?;
+    let mut remarks = Vec::new();
+    if extrinsic.pallet_name() == "System" {
+        remarks.extend(composite_field(&fields, "remark", 0).and_then(value_as_bytes));
+    }
+    for value in composite_values(&fields) {
+        nested_remarks(value, &mut remarks);
+    }
+    Ok(remarks
+        .into_iter()
+        .find(|remark| T::Hashing::hash(remark) == *hash))
+}
+
+// Collect the remarks of any `remark_with_event` calls nested in a value.
+fn nested_remarks(value: &Value<TypeId>, remarks: &mut Vec<Vec<u8>>) {
+    let fields = match &value.value {
+        ValueDef::Variant(v) => {
+            if v.name == "remark_with_event" {
+                remarks.extend(composite_field(&v.values, "remark", 0).and_then(value_as_bytes));
+            }
+            &v.values
+        }
+        ValueDef::Composite(c) => c,
+        _ => return,
+    };
+    for value in composite_values(fields) {
+        nested_remarks(value, remarks);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        pallets::{
+            find_in,
+            test_utils::pallet_events,
+        },
+        SubstrateConfig,
+    };
+    use codec::{
+        Decode,
+        Encode,
+    };
+    use scale_info::TypeInfo;
+    use sp_core::{
+        crypto::AccountId32,
+        H256,
+    };
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Event {
+        Remarked { sender: AccountId32, hash: H256 },
+    }
+
+    #[test]
+    fn remarks_are_decoded_and_read_as_text() {
+        let sender = AccountId32::new([1; 32]);
+        let events = pallet_events(vec![Event::Remarked {
+            sender: sender.clone(),
+            hash: H256::repeat_byte(2),
+        }]);
+        let found = find_in::<SubstrateConfig, Remarked<_>>(&events, "Test")
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.event.sender, sender);
+        assert_eq!(found.event.hash, H256::repeat_byte(2));
+
+        let remark = Remark::<SubstrateConfig> {
+            block_hash: found.block_hash,
+            event_index: found.event_index,
+            extrinsic_index: None,
+            sender,
+            hash: found.event.hash,
+            bytes: Some(b"hello".to_vec()),
+        };
+        assert_eq!(remark.text(), Some("hello"));
+        let binary = Remark {
+            bytes: Some(vec![0xff, 0xfe]),
+            ..remark.clone()
+        };
+        assert_eq!(binary.text(), None);
+        let missing = Remark { bytes: None, ..remark };
+        assert_eq!(missing.text(), None);
+    }
+}
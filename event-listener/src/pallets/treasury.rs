@@ -0,0 +1,404 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Helpers for the `Treasury` pallet, which holds and spends the funds of a
+//! chain, and the `Bounties` pallet, which pays bounties out of them.
+
+use super::{
+    EventFields,
+    PalletEvent,
+};
+use crate::{
+    error::Error,
+    Config,
+};
+use derivative::Derivative;
+
+/// `Treasury::Awarded`, `SpendApproved`, `AssetSpendApproved` or `Paid`.
+///
+/// Spends approved with `AssetSpendApproved` are paid out later, when they're
+/// claimed, which is when `Paid` is emitted with the same index. The other
+/// spends are paid out there and then.
+///
+/// ```no_run
+/// use event_listener::{
+///     events::SubscribeOpts,
+///     pallets::{
+///         self,
+///         treasury::TreasuryEvent,
+///     },
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+/// use futures::StreamExt;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let mut spends = pallets::subscribe::<_, _, TreasuryEvent<_>>(&api, SubscribeOpts::new())
+///     .await
+///     .unwrap();
+/// while let Some(spend) = spends.next().await {
+///     let spend = spend.unwrap().event;
+///     if let (Some(beneficiary), Some(amount)) = (spend.beneficiary(), spend.amount()) {
+///         println!("{} to {}", amount, beneficiary);
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub enum TreasuryEvent<T: Config> {
+    /// `Treasury::Awarded`: a spend proposal was paid out, at the end of a
+    /// spend period.
+    Awarded {
+        /// The index of the proposal.
+        proposal_index: u32,
+        /// The amount paid.
+        amount: u128,
+        /// The account paid.
+        beneficiary: T::AccountId,
+    },
+    /// `Treasury::SpendApproved`: a spend of the native token was approved,
+    /// and will be paid out at the end of the spend period.
+    SpendApproved {
+        /// The index of the proposal that the spend was added as.
+        proposal_index: u32,
+        /// The amount to pay.
+        amount: u128,
+        /// The account to pay.
+        beneficiary: T::AccountId,
+    },
+    /// `Treasury::AssetSpendApproved`: a spend of any asset was approved, to be
+    /// claimed once it's valid and before it expires.
+    AssetSpendApproved {
+        /// The index of the spend.
+        index: u32,
+        /// The SCALE encoded kind of asset to pay, such as the XCM location of
+        /// an asset on AssetHub.
+        asset_kind: Vec<u8>,
+        /// The amount to pay.
+        amount: u128,
+        /// The SCALE encoded beneficiary, such as an XCM location, since it
+        /// needn't be an account on this chain.
+        beneficiary: Vec<u8>,
+        /// The number of the first block in which the spend can be claimed.
+        valid_from: u64,
+        /// The number of the block at which the spend expires.
+        expire_at: u64,
+    },
+    /// `Treasury::Paid`: a spend approved with `AssetSpendApproved` was paid.
+    Paid {
+        /// The index of the spend.
+        index: u32,
+    },
+}
+
+impl<T: Config> TreasuryEvent<T> {
+    /// The account paid or to be paid, if it's an account on this chain.
+    pub fn beneficiary(&self) -> Option<&T::AccountId> {
+        match self {
+            TreasuryEvent::Awarded { beneficiary, .. }
+            | TreasuryEvent::SpendApproved { beneficiary, .. } => Some(beneficiary),
+            TreasuryEvent::AssetSpendApproved { .. } | TreasuryEvent::Paid { .. } => None,
+        }
+    }
+
+    /// The amount paid or to be paid, if the event gives it.
+    pub fn amount(&self) -> Option<u128> {
+        match *self {
+            TreasuryEvent::Awarded { amount, .. }
+            | TreasuryEvent::SpendApproved { amount, .. }
+            | TreasuryEvent::AssetSpendApproved { amount, .. } => Some(amount),
+            TreasuryEvent::Paid { .. } => None,
+        }
+    }
+}
+
+impl<T: Config> PalletEvent<T> for TreasuryEvent<T> {
+    const PALLET: &'static str = "Treasury";
+
+    fn decode(variant: &str, fields: &EventFields<'_>) -> Result<Option<Self>, Error> {
+        let event = match variant {
+            "Awarded" => {
+                TreasuryEvent::Awarded {
+                    proposal_index: fields.u32("proposal_index", 0)?,
+                    amount: fields.number("award", 1)?,
+                    beneficiary: fields.account::<T>("account", 2)?,
+                }
+            }
+            "SpendApproved" => {
+                TreasuryEvent::SpendApproved {
+                    proposal_index: fields.u32("proposal_index", 0)?,
+                    amount: fields.number("amount", 1)?,
+                    beneficiary: fields.account::<T>("beneficiary", 2)?,
+                }
+            }
+            "AssetSpendApproved" => {
+                TreasuryEvent::AssetSpendApproved {
+                    index: fields.u32("index", 0)?,
+                    asset_kind: fields.encoded("asset_kind", 1)?,
+                    amount: fields.number("amount", 2)?,
+                    beneficiary: fields.encoded("beneficiary", 3)?,
+                    valid_from: fields.u64("valid_from", 4)?,
+                    expire_at: fields.u64("expire_at", 5)?,
+                }
+            }
+            "Paid" => {
+                TreasuryEvent::Paid {
+                    index: fields.u32("index", 0)?,
+                }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(event))
+    }
+}
+
+/// What happened to a bounty.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub enum BountyAction<T: Config> {
+    /// `Bounties::BountyProposed`: the bounty was proposed.
+    Proposed,
+    /// `Bounties::BountyApproved`: the bounty was approved, and will be funded
+    /// at the end of the spend period.
+    Approved,
+    /// `Bounties::BountyBecameActive`: the bounty was funded.
+    BecameActive,
+    /// `Bounties::CuratorProposed`: a curator was proposed for the bounty.
+    CuratorProposed {
+        /// The proposed curator.
+        curator: T::AccountId,
+    },
+    /// `Bounties::CuratorAccepted`: a curator accepted the bounty.
+    CuratorAccepted {
+        /// The curator.
+        curator: T::AccountId,
+    },
+    /// `Bounties::BountyAwarded`: the curator awarded the bounty, which can be
+    /// claimed once the payout delay has passed.
+    Awarded {
+        /// The account awarded it.
+        beneficiary: T::AccountId,
+    },
+    /// `Bounties::BountyClaimed`: the bounty was paid out.
+    Claimed {
+        /// The account paid.
+        beneficiary: T::AccountId,
+        /// The amount paid, not including the curator's fee.
+        payout: u128,
+    },
+    /// `Bounties::BountyRejected`: the bounty was rejected, and the bond of its
+    /// proposer slashed.
+    Rejected {
+        /// The bond slashed.
+        bond: u128,
+    },
+    /// `Bounties::BountyCanceled`: the bounty was cancelled.
+    Canceled,
+}
+
+/// An event of the `Bounties` pallet about a bounty.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub struct BountyEvent<T: Config> {
+    /// The index of the bounty.
+    pub index: u32,
+    /// What happened to it.
+    pub action: BountyAction<T>,
+}
+
+impl<T: Config> BountyEvent<T> {
+    /// The account paid or to be paid the bounty, once it's been awarded.
+    pub fn beneficiary(&self) -> Option<&T::AccountId> {
+        match &self.action {
+            BountyAction::Awarded { beneficiary } | BountyAction::Claimed { beneficiary, .. } => {
+                Some(beneficiary)
+            }
+            _ => None,
+        }
+    }
+
+    /// The amount paid out, once the bounty has been claimed.
+    pub fn amount(&self) -> Option<u128> {
+        match self.action {
+            BountyAction::Claimed { payout, .. } => Some(payout),
+            _ => None,
+        }
+    }
+}
+
+impl<T: Config> PalletEvent<T> for BountyEvent<T> {
+    const PALLET: &'static str = "Bounties";
+
+    fn decode(variant: &str, fields: &EventFields<'_>) -> Result<Option<Self>, Error> {
+        let action = match variant {
+            "BountyProposed" => BountyAction::Proposed,
+            "BountyApproved" => BountyAction::Approved,
+            "BountyBecameActive" => BountyAction::BecameActive,
+            "CuratorProposed" => {
+                BountyAction::CuratorProposed {
+                    curator: fields.account::<T>("curator", 1)?,
+                }
+            }
+            "CuratorAccepted" => {
+                BountyAction::CuratorAccepted {
+                    curator: fields.account::<T>("curator", 1)?,
+                }
+            }
+            "BountyAwarded" => {
+                BountyAction::Awarded {
+                    beneficiary: fields.account::<T>("beneficiary", 1)?,
+                }
+            }
+            "BountyClaimed" => {
+                BountyAction::Claimed {
+                    payout: fields.number("payout", 1)?,
+                    beneficiary: fields.account::<T>("beneficiary", 2)?,
+                }
+            }
+            "BountyRejected" => {
+                BountyAction::Rejected {
+                    bond: fields.number("bond", 1)?,
+                }
+            }
+            "BountyCanceled" => BountyAction::Canceled,
+            _ => return Ok(None),
+        };
+        // The curator events call the index `bounty_id`.
+        let index = if fields.has("bounty_id") {
+            fields.u32("bounty_id", 0)?
+        } else {
+            fields.u32("index", 0)?
+        };
+        Ok(Some(BountyEvent { index, action }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        pallets::{
+            find_in,
+            test_utils::{
+                account,
+                pallet_events,
+            },
+        },
+        SubstrateConfig,
+    };
+    use codec::{
+        Decode,
+        Encode,
+    };
+    use scale_info::TypeInfo;
+    use sp_core::crypto::AccountId32;
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum TreasuryEvents {
+        Awarded {
+            proposal_index: u32,
+            award: u128,
+            account: AccountId32,
+        },
+        AssetSpendApproved {
+            index: u32,
+            asset_kind: (u8, u32),
+            amount: u128,
+            beneficiary: (u8, [u8; 32]),
+            valid_from: u32,
+            expire_at: u32,
+        },
+        Paid {
+            index: u32,
+            payment_id: u64,
+        },
+    }
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum BountiesEvents {
+        CuratorAccepted { bounty_id: u32, curator: AccountId32 },
+        BountyClaimed { index: u32, payout: u128, beneficiary: AccountId32 },
+    }
+
+    #[test]
+    fn spends_are_decoded() {
+        let events = pallet_events(vec![
+            TreasuryEvents::Awarded {
+                proposal_index: 1,
+                award: 100,
+                account: account(1),
+            },
+            TreasuryEvents::AssetSpendApproved {
+                index: 2,
+                asset_kind: (1, 1984),
+                amount: 200,
+                beneficiary: (0, [2; 32]),
+                valid_from: 10,
+                expire_at: 20,
+            },
+            TreasuryEvents::Paid {
+                index: 2,
+                payment_id: 3,
+            },
+        ]);
+        let found: Vec<_> = find_in::<SubstrateConfig, TreasuryEvent<_>>(&events, "Test")
+            .map(|found| found.unwrap().event)
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                TreasuryEvent::Awarded {
+                    proposal_index: 1,
+                    amount: 100,
+                    beneficiary: account(1),
+                },
+                TreasuryEvent::AssetSpendApproved {
+                    index: 2,
+                    asset_kind: (1u8, 1984u32).encode(),
+                    amount: 200,
+                    beneficiary: (0u8, [2u8; 32]).encode(),
+                    valid_from: 10,
+                    expire_at: 20,
+                },
+                TreasuryEvent::Paid { index: 2 },
+            ]
+        );
+        assert_eq!(found[0].beneficiary(), Some(&account(1)));
+        assert_eq!(found[1].beneficiary(), None);
+        assert_eq!(found[1].amount(), Some(200));
+    }
+
+    #[test]
+    fn bounties_are_decoded() {
+        let events = pallet_events(vec![
+            BountiesEvents::CuratorAccepted {
+                bounty_id: 5,
+                curator: account(1),
+            },
+            BountiesEvents::BountyClaimed {
+                index: 5,
+                payout: 300,
+                beneficiary: account(2),
+            },
+        ]);
+        let found: Vec<_> = find_in::<SubstrateConfig, BountyEvent<_>>(&events, "Test")
+            .map(|found| found.unwrap().event)
+            .collect();
+        assert_eq!(found.len(), 2);
+        assert!(found.iter().all(|bounty| bounty.index == 5));
+        assert_eq!(
+            found[0].action,
+            BountyAction::CuratorAccepted {
+                curator: account(1)
+            }
+        );
+        assert_eq!(
+            (found[1].beneficiary(), found[1].amount()),
+            (Some(&account(2)), Some(300))
+        );
+    }
+}
@@ -0,0 +1,279 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Helpers for the `Vesting` pallet, which locks funds of accounts and unlocks
+//! them block by block.
+
+use super::{
+    blake2_128_concat,
+    fetch_storage,
+    named_field,
+    sequence_items,
+    subscribe,
+    EventFields,
+    PalletEvent,
+};
+use crate::{
+    client::OnlineClientT,
+    error::Error,
+    events::SubscribeOpts,
+    utils::value_as_u128,
+    Config,
+};
+use derivative::Derivative;
+use futures::{
+    stream::BoxStream,
+    StreamExt,
+};
+use std::future::Future;
+
+/// What happened to the vesting of an account.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VestingAction {
+    /// `Vesting::VestingUpdated`: the amount still locked was updated, as funds
+    /// were unlocked or a schedule was added.
+    Updated {
+        /// The amount still locked.
+        unvested: u128,
+    },
+    /// `Vesting::VestingCompleted`: all of the funds of the account have
+    /// been unlocked.
+    Completed,
+}
+
+/// `Vesting::VestingUpdated` or `VestingCompleted`.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub struct VestingEvent<T: Config> {
+    /// The account.
+    pub account: T::AccountId,
+    /// What happened to the vesting of it.
+    pub action: VestingAction,
+}
+
+impl<T: Config> PalletEvent<T> for VestingEvent<T> {
+    const PALLET: &'static str = "Vesting";
+
+    fn decode(variant: &str, fields: &EventFields<'_>) -> Result<Option<Self>, Error> {
+        let action = match variant {
+            "VestingUpdated" => {
+                VestingAction::Updated {
+                    unvested: fields.number("unvested", 1)?,
+                }
+            }
+            "VestingCompleted" => VestingAction::Completed,
+            _ => return Ok(None),
+        };
+        Ok(Some(VestingEvent {
+            account: fields.account::<T>("account", 0)?,
+            action,
+        }))
+    }
+}
+
+/// A vesting schedule of an account, from `Vesting::Vesting`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VestingSchedule {
+    /// The amount locked when the schedule started.
+    pub locked: u128,
+    /// The amount unlocked each block.
+    pub per_block: u128,
+    /// The number of the block that the schedule started unlocking in.
+    pub starting_block: u64,
+}
+
+impl VestingSchedule {
+    /// The amount still locked as of the block with the number given.
+    pub fn locked_at(&self, block_number: u64) -> u128 {
+        let vested_blocks = block_number.saturating_sub(self.starting_block);
+        let vested = self.per_block.saturating_mul(vested_blocks.into());
+        self.locked.saturating_sub(vested)
+    }
+
+    /// The number of the block as of which everything will have been unlocked.
+    pub fn ending_block(&self) -> u64 {
+        // A schedule unlocking nothing each block unlocks everything at once.
+        let per_block = self.per_block.max(1);
+        let blocks = self.locked / per_block + u128::from(self.locked % per_block != 0);
+        self.starting_block
+            .saturating_add(u64::try_from(blocks).unwrap_or(u64::MAX))
+    }
+}
+
+/// A [`VestingEvent`], along with the vesting schedules of the account as of
+/// the block that it was emitted in.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub struct VestingUpdate<T: Config> {
+    /// The hash of the block that the event was emitted in.
+    pub block_hash: T::Hash,
+    /// The index of the event in the events of the block.
+    pub event_index: u32,
+    /// The event.
+    pub event: VestingEvent<T>,
+    /// The vesting schedules of the account, which are empty once its vesting
+    /// has completed.
+    pub schedules: Vec<VestingSchedule>,
+}
+
+/// Subscribe to [`VestingEvent`]s, along with the vesting schedules of each
+/// account, read from `Vesting::Vesting` at the block of the event. Subscribe
+/// with [`crate::pallets::subscribe()`] instead for just the events.
+///
+/// ```no_run
+/// use event_listener::{
+///     events::SubscribeOpts,
+///     pallets::vesting,
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+/// use futures::StreamExt;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let mut updates = vesting::subscribe_vesting_updates(&api, SubscribeOpts::new())
+///     .await
+///     .unwrap();
+/// while let Some(update) = updates.next().await {
+///     let update = update.unwrap();
+///     let ends = update.schedules.iter().map(|s| s.ending_block()).max();
+///     println!("{}: unlocked by block {:?}", update.event.account, ends);
+/// }
+/// # }
+/// ```
+pub fn subscribe_vesting_updates<T, Client>(
+    client: &Client,
+    opts: SubscribeOpts,
+) -> impl Future<Output = Result<BoxStream<'static, Result<VestingUpdate<T>, Error>>, Error>>
+       + Send
+       + 'static
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let client = client.clone();
+    async move {
+        let sub = subscribe::<T, Client, VestingEvent<T>>(&client, opts).await?;
+        let updates = sub.then(move |found| {
+            let client = client.clone();
+            async move {
+                let found = found?;
+                let schedules = match found.event.action {
+                    VestingAction::Completed => Vec::new(),
+                    VestingAction::Updated { .. } => {
+                        vesting_schedules(&client, &found.event.account, found.block_hash)
+                            .await?
+                    }
+                };
+                Ok(VestingUpdate {
+                    block_hash: found.block_hash,
+                    event_index: found.event_index,
+                    event: found.event,
+                    schedules,
+                })
+            }
+        });
+        Ok(updates.boxed())
+    }
+}
+
+/// The vesting schedules of the account given, as of the block with the hash
+/// given, from `Vesting::Vesting`.
+pub async fn vesting_schedules<T, Client>(
+    client: &Client,
+    who: &T::AccountId,
+    block_hash: T::Hash,
+) -> Result<Vec<VestingSchedule>, Error>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    let key = blake2_128_concat(who);
+    let schedules = match fetch_storage(client, "Vesting", "Vesting", &key, block_hash).await? {
+        Some(schedules) => schedules,
+        None => return Ok(Vec::new()),
+    };
+    let metadata = client.metadata();
+    let types = &metadata.runtime_metadata().types;
+    // Older versions of the pallet held a single schedule per account.
+    let schedules = sequence_items(&schedules, types)
+        .unwrap_or_else(|| vec![&schedules])
+        .into_iter()
+        .filter_map(|schedule| {
+            let number = |name: &str| named_field(schedule, name).and_then(value_as_u128);
+            Some(VestingSchedule {
+                locked: number("locked")?,
+                per_block: number("per_block")?,
+                starting_block: u64::try_from(number("starting_block")?).ok()?,
+            })
+        })
+        .collect();
+    Ok(schedules)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        pallets::{
+            find_in,
+            test_utils::pallet_events,
+        },
+        SubstrateConfig,
+    };
+    use codec::{
+        Decode,
+        Encode,
+    };
+    use scale_info::TypeInfo;
+    use sp_core::crypto::AccountId32;
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Event {
+        VestingUpdated { account: AccountId32, unvested: u128 },
+        VestingCompleted { account: AccountId32 },
+    }
+
+    #[test]
+    fn vesting_events_are_decoded() {
+        let account = AccountId32::new([1; 32]);
+        let events = pallet_events(vec![
+            Event::VestingUpdated {
+                account: account.clone(),
+                unvested: 50,
+            },
+            Event::VestingCompleted {
+                account: account.clone(),
+            },
+        ]);
+        let found: Vec<_> = find_in::<SubstrateConfig, VestingEvent<_>>(&events, "Test")
+            .map(|found| {
+                let event = found.unwrap().event;
+                assert_eq!(event.account, account);
+                event.action
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![VestingAction::Updated { unvested: 50 }, VestingAction::Completed]
+        );
+    }
+
+    #[test]
+    fn schedules_unlock_block_by_block() {
+        let schedule = VestingSchedule {
+            locked: 100,
+            per_block: 3,
+            starting_block: 10,
+        };
+        assert_eq!(schedule.locked_at(5), 100);
+        assert_eq!(schedule.locked_at(20), 70);
+        assert_eq!(schedule.locked_at(1000), 0);
+        // 34 blocks to unlock 100 at 3 a block.
+        assert_eq!(schedule.ending_block(), 44);
+        assert_eq!(schedule.locked_at(schedule.ending_block()), 0);
+        assert_eq!(schedule.locked_at(schedule.ending_block() - 1), 1);
+    }
+}
@@ -0,0 +1,57 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! The types that almost every program using this crate needs, so that they can
+//! be imported in one go.
+//!
+//! ```no_run
+//! use event_listener::prelude::*;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Error> {
+//! let api = OnlineClient::<PolkadotConfig>::new().await?;
+//! let mut blocks = api.events().subscribe_finalized().await?;
+//! while let Some(events) = blocks.next().await {
+//!     for event in events?.iter() {
+//!         let event = event?;
+//!         println!("{}::{}", event.pallet_name(), event.variant_name());
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub use crate::{
+    client::{
+        OfflineClient,
+        OfflineClientT,
+        OnlineClient,
+        OnlineClientT,
+    },
+    config::{
+        Config,
+        PolkadotConfig,
+        SubstrateConfig,
+    },
+    error::Error,
+    events::{
+        EventDetails,
+        EventSubscription,
+        Events,
+        Phase,
+        StaticEvent,
+        SubscribeOpts,
+    },
+    listener::{
+        EventContext,
+        EventListener,
+        EventListenerBuilder,
+        HandlerResult,
+    },
+    metadata::Metadata,
+};
+pub use futures::{
+    StreamExt,
+    TryStreamExt,
+};
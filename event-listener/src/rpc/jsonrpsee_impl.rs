@@ -13,12 +13,20 @@ use futures::stream::{
     TryStreamExt,
 };
 use jsonrpsee::{
-    core::client::{
-        Client,
-        ClientT,
-        SubscriptionClientT,
+    core::{
+        client::{
+            Client,
+            ClientT,
+            SubscriptionClientT,
+            SubscriptionKind,
+        },
+        Error as JsonrpseeError,
+    },
+    types::{
+        error::CallError,
+        ParamsSer,
+        SubscriptionId,
     },
-    types::ParamsSer,
 };
 use serde_json::value::{
     RawValue,
@@ -35,7 +43,7 @@ impl RpcClientT for Client {
             let params = prep_params_for_jsonrpsee(params)?;
             let res = ClientT::request(self, method, Some(params))
                 .await
-                .map_err(|e| RpcError(e.to_string()))?;
+                .map_err(RpcError::from)?;
             Ok(res)
         })
     }
@@ -55,10 +63,20 @@ impl RpcClientT for Client {
                 unsub,
             )
             .await
-            .map_err(|e| RpcError(e.to_string()))?
-            .map_err(|e| RpcError(e.to_string()))
-            .boxed();
-            Ok(sub)
+            .map_err(RpcError::from)?;
+
+            let id = match sub.kind() {
+                SubscriptionKind::Subscription(SubscriptionId::Str(id)) => {
+                    Some(id.clone().into_owned())
+                }
+                SubscriptionKind::Subscription(SubscriptionId::Num(id)) => {
+                    Some(id.to_string())
+                }
+                _ => None,
+            };
+
+            let stream = sub.map_err(RpcError::from).boxed();
+            Ok(RpcSubscription { stream, id })
         })
     }
 }
@@ -77,10 +95,49 @@ fn prep_params_for_jsonrpsee(
     let arr = match val {
         Value::Array(arr) => Ok(arr),
         _ => {
-            Err(RpcError(format!(
+            Err(RpcError::Other(format!(
                 "RPC Params are expected to be an array but got {params}"
             )))
         }
     }?;
     Ok(ParamsSer::Array(arr))
 }
+
+impl From<JsonrpseeError> for RpcError {
+    fn from(e: JsonrpseeError) -> Self {
+        match e {
+            JsonrpseeError::Call(CallError::Custom(err)) => {
+                RpcError::Call {
+                    code: err.code(),
+                    message: err.message().to_owned(),
+                    data: err.data().map(|data| data.get().to_owned()),
+                }
+            }
+            JsonrpseeError::Call(CallError::InvalidParams(e)) => {
+                RpcError::Call {
+                    code: RpcError::INVALID_PARAMS_CODE,
+                    message: e.to_string(),
+                    data: None,
+                }
+            }
+            JsonrpseeError::Call(CallError::Failed(e)) => {
+                RpcError::Call {
+                    code: jsonrpsee::types::error::CALL_EXECUTION_FAILED_CODE,
+                    message: e.to_string(),
+                    data: None,
+                }
+            }
+            JsonrpseeError::Transport(e) => RpcError::Disconnected(e.to_string()),
+            JsonrpseeError::RestartNeeded(e) => RpcError::Disconnected(e),
+            JsonrpseeError::AlreadyStopped => RpcError::Disconnected(e.to_string()),
+            JsonrpseeError::RequestTimeout => RpcError::RequestTimeout,
+            JsonrpseeError::InvalidSubscriptionId => {
+                RpcError::SubscriptionDropped(e.to_string())
+            }
+            JsonrpseeError::InvalidResponse(_) | JsonrpseeError::ParseError(_) => {
+                RpcError::InvalidResponse(e.to_string())
+            }
+            e => RpcError::Other(e.to_string()),
+        }
+    }
+}
@@ -67,6 +67,8 @@ pub use rpc_client_t::{
     RpcClientT,
     RpcFuture,
     RpcSubscription,
+    RpcSubscriptionId,
+    RpcSubscriptionStream,
 };
 
 pub use rpc_client::{
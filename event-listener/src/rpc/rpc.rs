@@ -14,21 +14,35 @@ use super::{
     Subscription,
 };
 use crate::{
-    error::Error,
-    utils::PhantomDataSendSync,
+    error::{
+        Error,
+        RpcError,
+        ValidityError,
+    },
+    utils::{
+        to_hex,
+        PhantomDataSendSync,
+    },
     Config,
     Metadata,
 };
 use codec::{
     Decode,
+    Encode,
 };
 use frame_metadata::RuntimeMetadataPrefixed;
+use futures::StreamExt;
 use serde::{
+    de::DeserializeOwned,
     Deserialize,
     Serialize,
 };
 use sp_core::{
-    storage::StorageData,
+    storage::{
+        StorageChangeSet,
+        StorageData,
+        StorageKey,
+    },
     Bytes,
     U256,
 };
@@ -55,6 +69,24 @@ pub enum NumberOrHex {
 #[derive(Serialize)]
 pub struct BlockNumber(NumberOrHex);
 
+impl From<NumberOrHex> for BlockNumber {
+    fn from(x: NumberOrHex) -> Self {
+        BlockNumber(x)
+    }
+}
+
+impl From<u32> for BlockNumber {
+    fn from(x: u32) -> Self {
+        NumberOrHex::Number(x.into()).into()
+    }
+}
+
+impl From<u64> for BlockNumber {
+    fn from(x: u64) -> Self {
+        NumberOrHex::Number(x).into()
+    }
+}
+
 /// Possible transaction status events.
 ///
 /// # Note
@@ -115,6 +147,148 @@ pub struct RuntimeVersion {
     pub other: HashMap<String, serde_json::Value>,
 }
 
+/// The response from `chain_getBlock`.
+#[derive(Debug, Deserialize)]
+#[serde(bound = "T: Config")]
+pub struct ChainBlockResponse<T: Config> {
+    /// The block itself.
+    pub block: ChainBlock<T>,
+    /// Block justification.
+    pub justifications: Option<Vec<BlockJustification>>,
+}
+
+/// An abstraction over justification for a block's validity under a consensus algorithm.
+///
+/// The first element is the ID of the consensus engine which produced the justification
+/// (for instance `*b"FRNK"` for GRANDPA), and the second is the SCALE encoded justification
+/// itself, which is left for the caller to decode.
+pub type BlockJustification = (ConsensusEngineId, Vec<u8>);
+
+/// Consensus engine unique ID.
+pub type ConsensusEngineId = [u8; 4];
+
+/// The properties of a chain, as handed back from `system_properties`. These are
+/// arbitrary JSON values, but typically include `ss58Format`, `tokenSymbol`
+/// and `tokenDecimals`.
+pub type SystemProperties = serde_json::Map<String, serde_json::Value>;
+
+/// Block details in the [`ChainBlockResponse`].
+#[derive(Debug, Deserialize)]
+#[serde(bound = "T: Config")]
+pub struct ChainBlock<T: Config> {
+    /// The block header.
+    pub header: T::Header,
+    /// The accompanying extrinsics. Each of these is SCALE encoded
+    /// as a byte vector, and so begins with a compact encoded length.
+    pub extrinsics: Vec<Bytes>,
+}
+
+/// The event generated by the `chainHead_follow` method.
+///
+/// The events are generated in the following order:
+/// 1. Initialized - generated only once to signal the latest finalized block
+/// 2. NewBlock - a new block was added.
+/// 3. BestBlockChanged - indicate that the best block is now the one from this event. The block was
+///    announced priorly with the `NewBlock` event.
+/// 4. Finalized - State the finalized and pruned blocks.
+///
+/// The following events are related to operations:
+/// - Stop - The subscription has been stopped by the node, and any pinned blocks are
+///   no longer valid.
+///
+/// Every block that is reported by this subscription is pinned by the node until it's
+/// unpinned via `chainHead_unstable_unpin`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(tag = "event")]
+pub enum FollowEvent<Hash> {
+    /// The latest finalized block.
+    Initialized(Initialized<Hash>),
+    /// A new non-finalized block was added.
+    NewBlock(NewBlock<Hash>),
+    /// The best block of the chain.
+    BestBlockChanged(BestBlockChanged<Hash>),
+    /// A list of finalized and pruned blocks.
+    Finalized(Finalized<Hash>),
+    /// The subscription is dropped and no further events
+    /// will be generated.
+    Stop,
+    /// Some other event (for instance, one relating to an operation) that
+    /// we don't make use of.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Contain information about the latest finalized block.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Initialized<Hash> {
+    /// The hash of the latest finalized block.
+    pub finalized_block_hash: Hash,
+}
+
+/// Indicate a new non-finalized block.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewBlock<Hash> {
+    /// The hash of the new block.
+    pub block_hash: Hash,
+    /// The parent hash of the new block.
+    pub parent_block_hash: Hash,
+}
+
+/// Indicate the block hash of the new best block.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BestBlockChanged<Hash> {
+    /// The block hash of the new best block.
+    pub best_block_hash: Hash,
+}
+
+/// Indicate the finalized and pruned block hashes.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Finalized<Hash> {
+    /// Block hashes that are finalized.
+    pub finalized_block_hashes: Vec<Hash>,
+    /// Block hashes that are pruned (removed).
+    pub pruned_block_hashes: Vec<Hash>,
+}
+
+/// The outcome of an operation on a block pinned by a `chainHead_unstable_follow`
+/// subscription, such as `chainHead_unstable_storage`. Each operation opens a
+/// subscription of its own, which sends one of these and then ends.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(tag = "event")]
+pub enum ChainHeadOperation<T> {
+    /// The operation succeeded.
+    Done(ChainHeadResult<T>),
+    /// The node couldn't get hold of what was asked for, for instance because
+    /// none of its peers had it.
+    Inaccessible(ChainHeadError),
+    /// The operation failed.
+    Error(ChainHeadError),
+    /// The follow subscription that the operation was made with has stopped.
+    Disjoint,
+}
+
+/// The result of a successful `chainHead` operation.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainHeadResult<T> {
+    /// The result of the operation.
+    pub result: T,
+}
+
+/// Why a `chainHead` operation failed.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainHeadError {
+    /// The reason for the failure.
+    pub error: String,
+}
+
 /// Client for substrate rpc interfaces
 pub struct Rpc<T: Config> {
     client: RpcClient,
@@ -159,12 +333,70 @@ impl<T: Config> Rpc<T> {
         Ok(data)
     }
 
+    /// Returns the keys with prefix with pagination support.
+    /// Up to `count` keys will be returned.
+    /// If `start_key` is passed, return next keys in storage in lexicographic order.
+    pub async fn storage_keys_paged(
+        &self,
+        key: &[u8],
+        count: u32,
+        start_key: Option<&[u8]>,
+        hash: Option<T::Hash>,
+    ) -> Result<Vec<StorageKey>, Error> {
+        let start_key = start_key.map(to_hex);
+        let params = rpc_params![to_hex(key), count, start_key, hash];
+        let data = self.client.request("state_getKeysPaged", params).await?;
+        Ok(data)
+    }
+
+    /// Query storage entries at some block, returning the value (if any)
+    /// for each of the keys provided.
+    pub async fn query_storage_at<'a>(
+        &self,
+        keys: impl IntoIterator<Item = &'a [u8]>,
+        at: Option<T::Hash>,
+    ) -> Result<Vec<StorageChangeSet<T::Hash>>, Error> {
+        let keys: Vec<String> = keys.into_iter().map(to_hex).collect();
+        let params = rpc_params![keys, at];
+        let data = self.client.request("state_queryStorageAt", params).await?;
+        Ok(data)
+    }
+
+    /// Execute a runtime API call, handing back the SCALE encoded result.
+    ///
+    /// `function` is the runtime API trait and method, joined with an
+    /// underscore (for instance `AccountNonceApi_account_nonce`), and
+    /// `call_parameters` are the SCALE encoded arguments to the call.
+    pub async fn state_call(
+        &self,
+        function: &str,
+        call_parameters: Option<&[u8]>,
+        at: Option<T::Hash>,
+    ) -> Result<Vec<u8>, Error> {
+        let call_parameters = to_hex(call_parameters.unwrap_or_default());
+        let params = rpc_params![function, call_parameters, at];
+        let bytes: Bytes = self.client.request("state_call", params).await?;
+        Ok(bytes.0)
+    }
+
     /// Fetch the metadata
     pub async fn metadata(&self) -> Result<Metadata, Error> {
+        self.metadata_at(None).await
+    }
+
+    /// Fetch the metadata of the runtime at the given block, which may differ
+    /// from the current metadata if the runtime has been upgraded since. Fetches
+    /// the current metadata if no block is given.
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(name = "fetch_metadata", skip_all, err)
+    )]
+    pub async fn metadata_at(&self, at: Option<T::Hash>) -> Result<Metadata, Error> {
         let bytes: Bytes = self
             .client
-            .request("state_getMetadata", rpc_params![])
+            .request("state_getMetadata", rpc_params![at])
             .await?;
+        tracing::debug!(bytes = bytes.len(), "Fetched metadata");
         let meta: RuntimeMetadataPrefixed = Decode::decode(&mut &bytes[..])?;
         let metadata: Metadata = meta.try_into()?;
         Ok(metadata)
@@ -180,6 +412,86 @@ impl<T: Config> Rpc<T> {
         Ok(block_hash)
     }
 
+    /// Get the hashes of the blocks with the numbers given, all in one request,
+    /// in the same order as the numbers. `None` is handed back for any blocks
+    /// which the node doesn't know about.
+    pub async fn block_hashes(
+        &self,
+        block_numbers: Vec<BlockNumber>,
+    ) -> Result<Vec<Option<T::Hash>>, Error> {
+        if block_numbers.is_empty() {
+            return Ok(Vec::new())
+        }
+        let len = block_numbers.len();
+        // The node accepts a list of block numbers as well as a single one.
+        let params = rpc_params![block_numbers];
+        let block_hashes: Vec<Option<T::Hash>> =
+            self.client.request("chain_getBlockHash", params).await?;
+        if block_hashes.len() != len {
+            return Err(RpcError::InvalidResponse(format!(
+                "Asked for {} block hashes but got {}",
+                len,
+                block_hashes.len()
+            ))
+            .into())
+        }
+        Ok(block_hashes)
+    }
+
+    /// Fetch the genesis hash
+    pub async fn genesis_hash(&self) -> Result<T::Hash, Error> {
+        let block_zero = 0u32;
+        let params = rpc_params![block_zero];
+        let genesis_hash: Option<T::Hash> =
+            self.client.request("chain_getBlockHash", params).await?;
+        genesis_hash.ok_or_else(|| "Genesis hash not found".to_string().into())
+    }
+
+    /// Fetch the name of the chain, as given in its chain spec.
+    pub async fn system_chain(&self) -> Result<String, Error> {
+        let chain = self.client.request("system_chain", rpc_params![]).await?;
+        Ok(chain)
+    }
+
+    /// Fetch the properties of the chain, such as its SS58 format and token
+    /// symbol and decimals, as given in its chain spec.
+    pub async fn system_properties(&self) -> Result<SystemProperties, Error> {
+        let properties = self
+            .client
+            .request("system_properties", rpc_params![])
+            .await?;
+        Ok(properties)
+    }
+
+    /// Get a block hash of the latest finalized block
+    pub async fn finalized_head(&self) -> Result<T::Hash, Error> {
+        let hash = self
+            .client
+            .request("chain_getFinalizedHead", rpc_params![])
+            .await?;
+        Ok(hash)
+    }
+
+    /// Get a header
+    pub async fn header(
+        &self,
+        hash: Option<T::Hash>,
+    ) -> Result<Option<T::Header>, Error> {
+        let params = rpc_params![hash];
+        let header = self.client.request("chain_getHeader", params).await?;
+        Ok(header)
+    }
+
+    /// Get a block
+    pub async fn block(
+        &self,
+        hash: Option<T::Hash>,
+    ) -> Result<Option<ChainBlockResponse<T>>, Error> {
+        let params = rpc_params![hash];
+        let block = self.client.request("chain_getBlock", params).await?;
+        Ok(block)
+    }
+
     /// Fetch the runtime version
     pub async fn runtime_version(
         &self,
@@ -206,10 +518,200 @@ impl<T: Config> Rpc<T> {
 
         Ok(subscription)
     }
+
+    /// Subscribe to `chainHead_unstable_follow` to obtain all reported blocks by the chain.
+    ///
+    /// The subscription ID can be used to make queries for the block's header
+    /// ([`Rpc::chainhead_unstable_header`]), body ([`Rpc::chainhead_unstable_body`])
+    /// and storage ([`Rpc::chainhead_unstable_storage`]), and to unpin the blocks
+    /// that are reported ([`Rpc::chainhead_unstable_unpin`]).
+    pub async fn chainhead_unstable_follow(
+        &self,
+        runtime_updates: bool,
+    ) -> Result<Subscription<FollowEvent<T::Hash>>, Error> {
+        let subscription = self
+            .client
+            .subscribe(
+                "chainHead_unstable_follow",
+                rpc_params![runtime_updates],
+                "chainHead_unstable_unfollow",
+            )
+            .await?;
+        Ok(subscription)
+    }
+
+    /// Get the block header of the given block, which must be pinned by the
+    /// `chainHead_unstable_follow` subscription with the ID given.
+    pub async fn chainhead_unstable_header(
+        &self,
+        subscription_id: &str,
+        hash: T::Hash,
+    ) -> Result<Option<T::Header>, Error> {
+        let header: Option<Bytes> = self
+            .client
+            .request(
+                "chainHead_unstable_header",
+                rpc_params![subscription_id, hash],
+            )
+            .await?;
+        let header = header
+            .map(|h| <T::Header as Decode>::decode(&mut &*h.0))
+            .transpose()?;
+        Ok(header)
+    }
+
+    /// Get the value of the storage entry with the key given at a block, which
+    /// must be pinned by the `chainHead_unstable_follow` subscription with the
+    /// ID given. The node keeps the state of a pinned block around until it's
+    /// unpinned, so this can't fail because the block has since been pruned.
+    pub async fn chainhead_unstable_storage(
+        &self,
+        subscription_id: &str,
+        hash: T::Hash,
+        key: &[u8],
+    ) -> Result<Option<StorageData>, Error> {
+        let sub = self
+            .client
+            .subscribe(
+                "chainHead_unstable_storage",
+                rpc_params![subscription_id, hash, to_hex(key)],
+                "chainHead_unstable_stopStorage",
+            )
+            .await?;
+        chainhead_operation("chainHead_unstable_storage", sub).await
+    }
+
+    /// Get the extrinsics in the body of a block, which must be pinned by the
+    /// `chainHead_unstable_follow` subscription with the ID given. Like those of
+    /// [`ChainBlock`], each extrinsic is SCALE encoded as a byte vector.
+    pub async fn chainhead_unstable_body(
+        &self,
+        subscription_id: &str,
+        hash: T::Hash,
+    ) -> Result<Vec<Bytes>, Error> {
+        let sub = self
+            .client
+            .subscribe(
+                "chainHead_unstable_body",
+                rpc_params![subscription_id, hash],
+                "chainHead_unstable_stopBody",
+            )
+            .await?;
+        let body: Bytes = chainhead_operation("chainHead_unstable_body", sub).await?;
+        let extrinsics = Vec::<Vec<u8>>::decode(&mut &*body.0)?;
+        Ok(extrinsics.into_iter().map(|e| Bytes(e.encode())).collect())
+    }
+
+    /// Unpin a block reported by the `chainHead_unstable_follow` subscription
+    /// with the ID given.
+    pub async fn chainhead_unstable_unpin(
+        &self,
+        subscription_id: &str,
+        hash: T::Hash,
+    ) -> Result<(), Error> {
+        self.client
+            .request::<()>(
+                "chainHead_unstable_unpin",
+                rpc_params![subscription_id, hash],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Fetch the next nonce (account index) to use for a transaction sent by
+    /// the account given, taking into account any transactions in the pool.
+    pub async fn system_account_next_index(
+        &self,
+        account: &T::AccountId,
+    ) -> Result<T::Index, Error> {
+        let params = rpc_params![account];
+        let index = self
+            .client
+            .request("system_accountNextIndex", params)
+            .await?;
+        Ok(index)
+    }
+
+    /// Submit an encoded extrinsic, handing back its hash.
+    pub async fn submit_extrinsic(&self, extrinsic: &[u8]) -> Result<T::Hash, Error> {
+        let params = rpc_params![to_hex(extrinsic)];
+        let xt_hash = self
+            .client
+            .request("author_submitExtrinsic", params)
+            .await
+            .map_err(invalid_transaction)?;
+        Ok(xt_hash)
+    }
+
+    /// Submit an encoded extrinsic, and subscribe to its status.
+    pub async fn watch_extrinsic(
+        &self,
+        extrinsic: &[u8],
+    ) -> Result<Subscription<SubstrateTxStatus<T::Hash, T::Hash>>, Error> {
+        let subscription = self
+            .client
+            .subscribe(
+                "author_submitAndWatchExtrinsic",
+                rpc_params![to_hex(extrinsic)],
+                "author_unwatchExtrinsic",
+            )
+            .await
+            .map_err(invalid_transaction)?;
+        Ok(subscription)
+    }
+
+    /// Subscribe to finalized blocks.
+    pub async fn subscribe_finalized_blocks(
+        &self,
+    ) -> Result<Subscription<T::Header>, Error> {
+        let subscription = self
+            .client
+            .subscribe(
+                "chain_subscribeFinalizedHeads",
+                rpc_params![],
+                "chain_unsubscribeFinalizedHeads",
+            )
+            .await?;
+        Ok(subscription)
+    }
 }
 
-fn to_hex(bytes: impl AsRef<[u8]>) -> String {
-    format!("0x{}", hex::encode(bytes.as_ref()))
+// Map the error that a node rejects an invalid transaction with onto an
+// `Error::Invalid`, leaving any other error as it is.
+fn invalid_transaction(error: Error) -> Error {
+    let validity = match error.root() {
+        Error::Rpc(e) => ValidityError::from_rpc_error(e),
+        _ => None,
+    };
+    validity.map(Error::Invalid).unwrap_or(error)
+}
+
+// Wait for the outcome of the `chainHead` operation with the subscription given.
+async fn chainhead_operation<R: DeserializeOwned>(
+    method: &str,
+    mut sub: Subscription<ChainHeadOperation<R>>,
+) -> Result<R, Error> {
+    match sub.next().await {
+        Some(Ok(ChainHeadOperation::Done(done))) => Ok(done.result),
+        Some(Ok(ChainHeadOperation::Inaccessible(e) | ChainHeadOperation::Error(e))) => {
+            Err(RpcError::Other(format!("{} failed: {}", method, e.error)).into())
+        }
+        Some(Ok(ChainHeadOperation::Disjoint)) => {
+            Err(RpcError::SubscriptionDropped(format!(
+                "{} was made with a chainHead subscription that has stopped",
+                method
+            ))
+            .into())
+        }
+        Some(Err(e)) => Err(e),
+        None => {
+            Err(RpcError::SubscriptionDropped(format!(
+                "{} ended without a result",
+                method
+            ))
+            .into())
+        }
+    }
 }
 
 #[cfg(test)]
@@ -263,4 +765,29 @@ mod test {
         assert_deser(r#"0"#, NumberOrHex::Number(0));
         assert_deser(r#"1000000000000"#, NumberOrHex::Number(1000000000000));
     }
+    #[test]
+    fn test_deser_chain_head_operation() {
+        let done: ChainHeadOperation<Option<String>> =
+            serde_json::from_str(r#"{"event":"done","result":"0x01"}"#).unwrap();
+        assert_eq!(
+            done,
+            ChainHeadOperation::Done(ChainHeadResult {
+                result: Some("0x01".to_owned())
+            })
+        );
+
+        let error: ChainHeadOperation<Option<String>> =
+            serde_json::from_str(r#"{"event":"inaccessible","error":"no peers"}"#)
+                .unwrap();
+        assert_eq!(
+            error,
+            ChainHeadOperation::Inaccessible(ChainHeadError {
+                error: "no peers".to_owned()
+            })
+        );
+
+        let disjoint: ChainHeadOperation<Option<String>> =
+            serde_json::from_str(r#"{"event":"disjoint"}"#).unwrap();
+        assert_eq!(disjoint, ChainHeadOperation::Disjoint);
+    }
 }
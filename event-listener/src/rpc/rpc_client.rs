@@ -5,8 +5,13 @@
 use super::{
     RpcClientT,
     RpcSubscription,
+    RpcSubscriptionId,
+    RpcSubscriptionStream,
+};
+use crate::error::{
+    Error,
+    ErrorContext,
 };
-use crate::error::Error;
 use futures::{
     Stream,
     StreamExt,
@@ -39,13 +44,24 @@ impl RpcClient {
     ///
     /// See [`RpcParams`] and the [`rpc_params!`] macro for an example of how to
     /// construct the parameters.
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(name = "rpc", level = "debug", skip_all, fields(method = %method), err)
+    )]
     pub async fn request<Res: DeserializeOwned>(
         &self,
         method: &str,
         params: RpcParams,
     ) -> Result<Res, Error> {
-        let res = self.0.request_raw(method, params.build()).await?;
-        let val = serde_json::from_str(res.get())?;
+        let params = params.build();
+        let context = || ErrorContext::rpc(method, params.as_deref().map(RawValue::get));
+        let res = self
+            .0
+            .request_raw(method, params.clone())
+            .await
+            .map_err(|e| Error::from(e).context(context()))?;
+        let val = serde_json::from_str(res.get())
+            .map_err(|e| Error::from(e).context(context()))?;
         Ok(val)
     }
 
@@ -54,13 +70,25 @@ impl RpcClient {
     ///
     /// See [`RpcParams`] and the [`rpc_params!`] macro for an example of how to
     /// construct the parameters.
+    #[cfg_attr(
+        feature = "instrument",
+        tracing::instrument(name = "rpc_subscribe", level = "debug", skip_all, fields(method = %sub), err)
+    )]
     pub async fn subscribe<Res: DeserializeOwned>(
         &self,
         sub: &str,
         params: RpcParams,
         unsub: &str,
     ) -> Result<Subscription<Res>, Error> {
-        let sub = self.0.subscribe_raw(sub, params.build(), unsub).await?;
+        let params = params.build();
+        let sub = self
+            .0
+            .subscribe_raw(sub, params.clone(), unsub)
+            .await
+            .map_err(|e| {
+                Error::from(e)
+                    .context(ErrorContext::rpc(sub, params.as_deref().map(RawValue::get)))
+            })?;
         Ok(Subscription::new(sub))
     }
 }
@@ -138,17 +166,33 @@ impl RpcParams {
 /// the functionality you'll need to interact with it comes from the
 /// [`StreamExt`] extension trait.
 pub struct Subscription<Res> {
-    inner: RpcSubscription,
+    inner: RpcSubscriptionStream,
+    id: Option<RpcSubscriptionId>,
     _marker: std::marker::PhantomData<Res>,
 }
 
+impl<Res> std::fmt::Debug for Subscription<Res> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Subscription")
+            .field("inner", &"RpcSubscriptionStream")
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
 impl<Res> Subscription<Res> {
     fn new(inner: RpcSubscription) -> Self {
         Self {
-            inner,
+            inner: inner.stream,
+            id: inner.id,
             _marker: std::marker::PhantomData,
         }
     }
+
+    /// Obtain the ID associated with this subscription, if the node handed one back.
+    pub fn subscription_id(&self) -> Option<&RpcSubscriptionId> {
+        self.id.as_ref()
+    }
 }
 
 
@@ -0,0 +1,11 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Types associated with calling into the runtime APIs exposed by a node.
+
+mod runtime_api_client;
+mod runtime_types;
+
+pub use runtime_api_client::RuntimeApiClient;
+pub use runtime_types::RuntimeApi;
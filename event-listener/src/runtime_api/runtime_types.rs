@@ -0,0 +1,112 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use crate::{
+    client::OnlineClientT,
+    error::Error,
+    Config,
+};
+use codec::{
+    Decode,
+    DecodeAll,
+    Encode,
+};
+use derivative::Derivative;
+use std::future::Future;
+
+/// Execute runtime API calls at some block.
+#[derive(Derivative)]
+#[derivative(Clone(bound = "Client: Clone"))]
+pub struct RuntimeApi<T: Config, Client> {
+    client: Client,
+    block_hash: T::Hash,
+}
+
+impl<T: Config, Client> RuntimeApi<T, Client> {
+    /// Create a new [`RuntimeApi`]
+    pub(crate) fn new(client: Client, block_hash: T::Hash) -> Self {
+        Self { client, block_hash }
+    }
+
+    /// The hash of the block that runtime API calls are made at.
+    pub fn block_hash(&self) -> T::Hash {
+        self.block_hash
+    }
+}
+
+impl<T, Client> RuntimeApi<T, Client>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    /// Execute a raw runtime API call, handing back the SCALE encoded result.
+    ///
+    /// `function` is the runtime API trait and method joined with an underscore,
+    /// for instance `AccountNonceApi_account_nonce`.
+    pub fn call_raw<'a>(
+        &self,
+        function: &'a str,
+        call_parameters: Option<&'a [u8]>,
+    ) -> impl Future<Output = Result<Vec<u8>, Error>> + 'a {
+        let client = self.client.clone();
+        let block_hash = self.block_hash;
+        // Ensure that the returned future doesn't have a lifetime tied to api.runtime_api(),
+        // which is a temporary thing we'll be throwing away quickly:
+        async move {
+            let data = client
+                .rpc()
+                .state_call(function, call_parameters, Some(block_hash))
+                .await?;
+            Ok(data)
+        }
+    }
+
+    /// Execute a runtime API call, SCALE encoding the arguments given and decoding
+    /// the result into `ReturnTy`.
+    ///
+    /// Arguments are encoded one after the other, so several of them can be given
+    /// as a tuple. The result must decode into `ReturnTy` with no bytes left over,
+    /// or an error is handed back.
+    ///
+    /// Argument and return types aren't checked against the runtime: that needs
+    /// the runtime API descriptions in V15 metadata, and this crate only supports
+    /// metadata up to V14. It's up to the caller to provide types that match.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// use event_listener::{ ext::sp_runtime::AccountId32, OnlineClient, PolkadotConfig };
+    ///
+    /// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+    ///
+    /// let nonce: u32 = api
+    ///     .runtime_api()
+    ///     .at(None)
+    ///     .await
+    ///     .unwrap()
+    ///     .call("AccountNonceApi_account_nonce", AccountId32::new([0; 32]))
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn call<Args: Encode, ReturnTy: Decode>(
+        &self,
+        function: &str,
+        args: Args,
+    ) -> impl Future<Output = Result<ReturnTy, Error>> + Send + 'static {
+        let client = self.client.clone();
+        let block_hash = self.block_hash;
+        let function = function.to_owned();
+        let args = args.encode();
+        async move {
+            let data = client
+                .rpc()
+                .state_call(&function, Some(&args), Some(block_hash))
+                .await?;
+            Ok(ReturnTy::decode_all(&mut &*data)?)
+        }
+    }
+}
@@ -0,0 +1,21 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Types associated with accessing and working with storage items.
+
+mod scan;
+mod storage_client;
+
+pub use scan::{
+    ScanOptions,
+    ScanProgress,
+    StorageScan,
+};
+pub use storage_client::StorageClient;
+
+// Re-export as this is used in the public API:
+pub use sp_core::storage::{
+    StorageData,
+    StorageKey,
+};
@@ -0,0 +1,303 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Scanning over every entry under some storage prefix.
+
+use super::{
+    StorageData,
+    StorageKey,
+};
+use crate::{
+    error::Error,
+    rpc::Rpc,
+    Config,
+};
+use derivative::Derivative;
+use futures::{
+    stream::{
+        self,
+        BoxStream,
+    },
+    StreamExt,
+};
+use std::sync::Arc;
+
+/// A stream of pages of key/value pairs, returned from
+/// [`super::StorageClient::scan()`].
+pub type StorageScan = BoxStream<'static, Result<Vec<(StorageKey, StorageData)>, Error>>;
+
+/// Progress information handed to the [`ScanOptions::on_progress`] callback
+/// each time a page of values has been fetched.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanProgress {
+    /// How many pages have been fetched so far.
+    pub pages: usize,
+    /// How many key/value pairs have been fetched so far.
+    pub entries: usize,
+}
+
+/// Options to configure how a storage scan is performed.
+#[derive(Derivative)]
+#[derivative(Clone, Debug)]
+pub struct ScanOptions {
+    page_size: u32,
+    concurrency: usize,
+    #[derivative(Debug = "ignore")]
+    on_progress: Option<Arc<dyn Fn(&ScanProgress) + Send + Sync>>,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            page_size: 1000,
+            concurrency: 8,
+            on_progress: None,
+        }
+    }
+}
+
+impl ScanOptions {
+    /// How many keys to ask for in each page. Defaults to 1000.
+    pub fn page_size(mut self, page_size: u32) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// How many pages of values we'll fetch at the same time. Defaults to 8.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Provide a function which will be called with the current
+    /// [`ScanProgress`] each time a page has been fetched.
+    pub fn on_progress(
+        mut self,
+        f: impl Fn(&ScanProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_progress = Some(Arc::new(f));
+        self
+    }
+}
+
+/// Scan over every entry under `prefix` at the block `hash`.
+pub(crate) fn scan_prefix<T: Config>(
+    rpc: Rpc<T>,
+    prefix: Vec<u8>,
+    hash: T::Hash,
+    opts: ScanOptions,
+) -> StorageScan {
+    let ScanOptions {
+        page_size,
+        concurrency,
+        on_progress,
+    } = opts;
+
+    // Listing keys is cheap and inherently sequential (each page starts
+    // where the last one finished), so we do that one page at a time.
+    // `None` as the state means that we've run out of keys.
+    let key_rpc = rpc.clone();
+    let key_pages = stream::unfold(Some(None::<Vec<u8>>), move |state| {
+        let rpc = key_rpc.clone();
+        let prefix = prefix.clone();
+        async move {
+            let start_key = state?;
+            let keys = match rpc
+                .storage_keys_paged(&prefix, page_size, start_key.as_deref(), Some(hash))
+                .await
+            {
+                Ok(keys) => keys,
+                // Hand back the error and then stop.
+                Err(e) => return Some((Err(e), None)),
+            };
+            if keys.is_empty() {
+                return None
+            }
+            // A short page means there's nothing left to fetch.
+            let next = if keys.len() < page_size as usize {
+                None
+            } else {
+                keys.last().map(|k| Some(k.0.clone()))
+            };
+            Some((Ok(keys), next))
+        }
+    });
+
+    // Fetching the values is the expensive part, so we fetch a number of
+    // pages of them at once, while still handing them back in key order.
+    let mut progress = ScanProgress::default();
+    key_pages
+        .map(move |keys| {
+            let rpc = rpc.clone();
+            async move {
+                let keys = keys?;
+                let change_sets = rpc
+                    .query_storage_at(keys.iter().map(|k| &*k.0), Some(hash))
+                    .await?;
+                let entries: Vec<_> = change_sets
+                    .into_iter()
+                    .flat_map(|set| set.changes)
+                    .filter_map(|(key, value)| value.map(|value| (key, value)))
+                    .collect();
+                Ok(entries)
+            }
+        })
+        .buffered(concurrency)
+        .inspect(move |page| {
+            if let (Ok(page), Some(f)) = (page, &on_progress) {
+                progress.pages += 1;
+                progress.entries += page.len();
+                f(&progress);
+            }
+        })
+        .boxed()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        error::RpcError,
+        rpc::{
+            RawValue,
+            RpcClientT,
+            RpcFuture,
+            RpcSubscription,
+        },
+        utils::to_hex,
+        SubstrateConfig,
+    };
+    use serde_json::{
+        json,
+        Value,
+    };
+    use std::{
+        collections::BTreeMap,
+        sync::atomic::{
+            AtomicUsize,
+            Ordering,
+        },
+    };
+
+    /// A fake node which knows about some storage entries.
+    struct MockStorage(BTreeMap<Vec<u8>, Vec<u8>>);
+
+    fn decode_hex(v: &Value) -> Vec<u8> {
+        hex::decode(v.as_str().unwrap().trim_start_matches("0x")).unwrap()
+    }
+
+    impl RpcClientT for MockStorage {
+        fn request_raw<'a>(
+            &'a self,
+            method: &'a str,
+            params: Option<Box<RawValue>>,
+        ) -> RpcFuture<'a, Box<RawValue>> {
+            let params: Vec<Value> =
+                serde_json::from_str(params.unwrap().get()).unwrap();
+            let res = match method {
+                "state_getKeysPaged" => {
+                    let prefix = decode_hex(&params[0]);
+                    let count = params[1].as_u64().unwrap() as usize;
+                    let start = (!params[2].is_null()).then(|| decode_hex(&params[2]));
+                    let keys: Vec<_> = self
+                        .0
+                        .keys()
+                        .filter(|k| k.starts_with(&prefix))
+                        .filter(|k| start.as_ref().map(|s| *k > s).unwrap_or(true))
+                        .take(count)
+                        .map(to_hex)
+                        .collect();
+                    json!(keys)
+                }
+                "state_queryStorageAt" => {
+                    let changes: Vec<_> = params[0]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|k| {
+                            let value = self.0.get(&decode_hex(k)).map(to_hex);
+                            json!([k, value])
+                        })
+                        .collect();
+                    json!([{ "block": params[1], "changes": changes }])
+                }
+                _ => {
+                    return Box::pin(async {
+                        Err(RpcError::Call {
+                            code: RpcError::METHOD_NOT_FOUND_CODE,
+                            message: "Method not found".into(),
+                            data: None,
+                        })
+                    })
+                }
+            };
+            let res = RawValue::from_string(res.to_string()).unwrap();
+            Box::pin(async move { Ok(res) })
+        }
+
+        fn subscribe_raw<'a>(
+            &'a self,
+            _sub: &'a str,
+            _params: Option<Box<RawValue>>,
+            _unsub: &'a str,
+        ) -> RpcFuture<'a, RpcSubscription> {
+            Box::pin(async {
+                Err(RpcError::Call {
+                    code: RpcError::METHOD_NOT_FOUND_CODE,
+                    message: "Subscriptions aren't supported".into(),
+                    data: None,
+                })
+            })
+        }
+    }
+
+    fn mock_rpc(prefix: &[u8], n: u8) -> Rpc<SubstrateConfig> {
+        let mut entries = BTreeMap::new();
+        for i in 0..n {
+            entries.insert([prefix, &[i]].concat(), vec![i]);
+        }
+        // Something with a different prefix that should be ignored:
+        entries.insert(vec![0xff, 0xff], vec![0]);
+        Rpc::new(MockStorage(entries))
+    }
+
+    #[tokio::test]
+    async fn scan_returns_every_entry_in_order() {
+        let rpc = mock_rpc(&[1, 2], 25);
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls2 = calls.clone();
+        let opts = ScanOptions::default()
+            .page_size(10)
+            .concurrency(2)
+            .on_progress(move |_| {
+                calls2.fetch_add(1, Ordering::Relaxed);
+            });
+
+        let pages: Vec<_> = scan_prefix(rpc, vec![1, 2], Default::default(), opts)
+            .map(|page| page.unwrap())
+            .collect()
+            .await;
+
+        let sizes: Vec<_> = pages.iter().map(|p| p.len()).collect();
+        assert_eq!(sizes, vec![10, 10, 5]);
+        let values: Vec<_> = pages.into_iter().flatten().map(|(_, v)| v.0[0]).collect();
+        assert_eq!(values, (0..25).collect::<Vec<u8>>());
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn scan_handles_exact_page_multiples() {
+        let rpc = mock_rpc(&[7], 20);
+        let opts = ScanOptions::default().page_size(10);
+
+        let entries: usize = scan_prefix(rpc, vec![7], Default::default(), opts)
+            .map(|page| page.unwrap().len())
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .sum();
+
+        assert_eq!(entries, 20);
+    }
+}
@@ -0,0 +1,132 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use super::{
+    scan::{
+        scan_prefix,
+        ScanOptions,
+        StorageScan,
+    },
+    StorageKey,
+};
+use crate::{
+    client::OnlineClientT,
+    error::Error,
+    Config,
+};
+use derivative::Derivative;
+use std::future::Future;
+
+/// Query the runtime storage.
+#[derive(Derivative)]
+#[derivative(Clone(bound = "Client: Clone"))]
+pub struct StorageClient<T, Client> {
+    client: Client,
+    _marker: std::marker::PhantomData<T>,
+}
+
+impl<T, Client> StorageClient<T, Client> {
+    /// Create a new [`StorageClient`]
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T, Client> StorageClient<T, Client>
+where
+    T: Config,
+    Client: OnlineClientT<T>,
+{
+    /// Fetch the raw encoded value at the address/key given.
+    pub fn fetch_raw<'a>(
+        &self,
+        key: &'a [u8],
+        hash: Option<T::Hash>,
+    ) -> impl Future<Output = Result<Option<Vec<u8>>, Error>> + 'a {
+        let client = self.client.clone();
+        // Ensure that the returned future doesn't have a lifetime tied to api.storage(),
+        // which is a temporary thing we'll be throwing away quickly:
+        async move {
+            let data = client.rpc().storage(key, hash).await?;
+            Ok(data.map(|d| d.0))
+        }
+    }
+
+    /// Fetch up to `count` keys for a storage map in lexicographic order.
+    ///
+    /// Supports pagination by passing a value to `start_key`.
+    pub fn fetch_keys<'a>(
+        &self,
+        key: &'a [u8],
+        count: u32,
+        start_key: Option<&'a [u8]>,
+        hash: Option<T::Hash>,
+    ) -> impl Future<Output = Result<Vec<StorageKey>, Error>> + 'a {
+        let client = self.client.clone();
+        async move {
+            let keys = client
+                .rpc()
+                .storage_keys_paged(key, count, start_key, hash)
+                .await?;
+            Ok(keys)
+        }
+    }
+
+    /// Scan every key/value pair living under the given storage `prefix`.
+    ///
+    /// Keys are listed a page at a time, and the values for each page are then
+    /// fetched in batches, with up to [`ScanOptions::concurrency`] batches in
+    /// flight at once. Pages are handed back in key order. If no block hash is
+    /// given, the scan is pinned to the latest block at the time of calling, so
+    /// that every page reflects the same state.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// use futures::StreamExt;
+    /// use event_listener::{ OnlineClient, PolkadotConfig, storage::ScanOptions };
+    ///
+    /// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+    ///
+    /// // System.Account
+    /// let prefix = hex::decode("26aa394eea5630e07c48ae0c9558cef7b99d880ec681799c0cf30e8886371da9").unwrap();
+    /// let opts = ScanOptions::default()
+    ///     .concurrency(16)
+    ///     .on_progress(|p| println!("{} entries fetched", p.entries));
+    ///
+    /// let mut pages = api.storage().scan(prefix, None, opts).await.unwrap();
+    /// while let Some(page) = pages.next().await {
+    ///     for (key, value) in page.unwrap() {
+    ///         println!("0x{}: {} bytes", hex::encode(&key.0), value.0.len());
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn scan(
+        &self,
+        prefix: impl Into<Vec<u8>>,
+        hash: Option<T::Hash>,
+        opts: ScanOptions,
+    ) -> impl Future<Output = Result<StorageScan, Error>> + Send + 'static {
+        let client = self.client.clone();
+        let prefix = prefix.into();
+        async move {
+            let rpc = client.rpc().clone();
+            let hash = match hash {
+                Some(hash) => hash,
+                None => {
+                    rpc.block_hash(None)
+                        .await?
+                        .expect("didn't pass a block number; qed")
+                }
+            };
+            Ok(scan_prefix(rpc, prefix, hash, opts))
+        }
+    }
+}
@@ -0,0 +1,288 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use crate::{
+    error::Error,
+    events::{
+        EventDetails,
+        Events,
+        Phase,
+    },
+    listener::{
+        BlockContext,
+        EventContext,
+    },
+    Config,
+    Metadata,
+};
+use codec::{
+    Compact,
+    Encode,
+};
+use scale_value::{
+    Composite,
+    Value,
+};
+
+/// Builds [`Events`] out of dynamic values, encoding each event against the
+/// [`Metadata`] given just as a node would, so that code which works with events
+/// can be tested without a node or captured blocks.
+///
+/// ```
+/// use event_listener::{
+///     events::Phase,
+///     ext::scale_value::{ Composite, Value },
+///     testing::EventsBuilder,
+///     Metadata,
+///     PolkadotConfig,
+/// };
+///
+/// # fn test(metadata: Metadata) {
+/// // An `AccountId32`, which wraps 32 bytes.
+/// let account = |byte: u8| {
+///     let bytes = vec![Value::u128(byte.into()); 32];
+///     Value::unnamed_composite(vec![Value::unnamed_composite(bytes)])
+/// };
+/// let events = EventsBuilder::<PolkadotConfig>::new(metadata)
+///     .event(
+///         Phase::ApplyExtrinsic(1),
+///         "Balances",
+///         "Transfer",
+///         Composite::Named(vec![
+///             ("from".into(), account(1)),
+///             ("to".into(), account(2)),
+///             ("amount".into(), Value::u128(10_000)),
+///         ]),
+///     )
+///     .build()
+///     .unwrap();
+///
+/// let transfer = events.iter().next().unwrap().unwrap();
+/// assert_eq!(transfer.variant_name(), "Transfer");
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct EventsBuilder<T: Config> {
+    metadata: Metadata,
+    block_hash: T::Hash,
+    events: Vec<FakeEvent<T>>,
+}
+
+#[derive(Clone, Debug)]
+struct FakeEvent<T: Config> {
+    phase: Phase,
+    pallet: String,
+    variant: String,
+    fields: Composite<()>,
+    topics: Vec<T::Hash>,
+}
+
+impl<T: Config> EventsBuilder<T> {
+    /// Build events which are encoded against the metadata given, in a block
+    /// with the default hash.
+    pub fn new(metadata: Metadata) -> Self {
+        EventsBuilder {
+            metadata,
+            block_hash: Default::default(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Set the hash of the block that the events are from.
+    pub fn block_hash(mut self, block_hash: T::Hash) -> Self {
+        self.block_hash = block_hash;
+        self
+    }
+
+    /// Add an event, emitted in the phase given. The fields are either named,
+    /// in any order, or unnamed, in the order the event declares them.
+    pub fn event(
+        self,
+        phase: Phase,
+        pallet: impl Into<String>,
+        variant: impl Into<String>,
+        fields: Composite<()>,
+    ) -> Self {
+        self.event_with_topics(phase, pallet, variant, fields, Vec::new())
+    }
+
+    /// Add an event, like [`EventsBuilder::event()`], along with the topics it
+    /// was emitted with.
+    pub fn event_with_topics(
+        mut self,
+        phase: Phase,
+        pallet: impl Into<String>,
+        variant: impl Into<String>,
+        fields: Composite<()>,
+        topics: Vec<T::Hash>,
+    ) -> Self {
+        self.events.push(FakeEvent {
+            phase,
+            pallet: pallet.into(),
+            variant: variant.into(),
+            fields,
+            topics,
+        });
+        self
+    }
+
+    /// Encode the events, failing if any of them isn't in the metadata or has
+    /// fields which don't match it.
+    pub fn build(self) -> Result<Events<T>, Error> {
+        let mut bytes = Compact(self.events.len() as u32).encode();
+        for event in &self.events {
+            event.encode_to(&self.metadata, &mut bytes)?;
+        }
+        Ok(Events::new(self.metadata, self.block_hash, bytes))
+    }
+}
+
+impl<T: Config> FakeEvent<T> {
+    // Encode this in the same way as a record in `System.Events` storage.
+    fn encode_to(&self, metadata: &Metadata, out: &mut Vec<u8>) -> Result<(), Error> {
+        let ((pallet_index, variant_index), event) =
+            metadata.event_by_name(&self.pallet, &self.variant)?;
+        self.phase.encode_to(out);
+        pallet_index.encode_to(out);
+        variant_index.encode_to(out);
+
+        let values: Vec<&Value<()>> = match &self.fields {
+            Composite::Named(fields) => {
+                // Line the named fields up with the fields in the metadata.
+                event
+                    .fields()
+                    .iter()
+                    .map(|(name, _)| {
+                        let name = name.as_deref().unwrap_or_default();
+                        fields
+                            .iter()
+                            .find(|(n, _)| n == name)
+                            .map(|(_, v)| v)
+                            .ok_or_else(|| {
+                                Error::Other(format!(
+                                    "Missing field '{}' for event {}::{}",
+                                    name, self.pallet, self.variant
+                                ))
+                            })
+                    })
+                    .collect::<Result<_, Error>>()?
+            }
+            Composite::Unnamed(fields) => fields.iter().collect(),
+        };
+        if values.len() != event.fields().len() {
+            return Err(Error::Other(format!(
+                "Event {}::{} has {} fields, but {} were given",
+                self.pallet,
+                self.variant,
+                event.fields().len(),
+                values.len()
+            )))
+        }
+
+        let types = &metadata.runtime_metadata().types;
+        for (value, (_, type_id)) in values.into_iter().zip(event.fields()) {
+            scale_value::scale::encode_as_type(value.clone(), *type_id, types, out)?;
+        }
+        self.topics.encode_to(out);
+        Ok(())
+    }
+}
+
+/// The [`EventContext`] that a listener would hand to handlers along with an
+/// event from the events given, as though they were from the block with the
+/// number given. Pass `()` as the client for handlers which don't use it.
+pub fn event_context<T: Config, Client>(
+    client: Client,
+    events: &Events<T>,
+    event: &EventDetails,
+    block_number: T::BlockNumber,
+) -> EventContext<T, Client> {
+    let block = BlockContext {
+        hash: events.block_hash(),
+        number: block_number,
+        timestamp: None,
+        chain: None,
+    };
+    EventContext::new(client, block, event)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        events::test_utils::{
+            metadata,
+            AllEvents,
+        },
+        SubstrateConfig,
+    };
+    use codec::Decode;
+    use scale_info::TypeInfo;
+    use sp_core::H256;
+
+    #[derive(Clone, Debug, PartialEq, Decode, Encode, TypeInfo)]
+    enum Event {
+        A { amount: u64, ok: bool },
+        B(u8),
+    }
+
+    #[test]
+    fn built_events_decode_like_real_ones() {
+        let events = EventsBuilder::<SubstrateConfig>::new(metadata::<Event>())
+            .event(
+                Phase::ApplyExtrinsic(2),
+                "Test",
+                "A",
+                Composite::Named(vec![
+                    ("ok".into(), Value::bool(true)),
+                    ("amount".into(), Value::u128(25)),
+                ]),
+            )
+            .event(
+                Phase::Finalization,
+                "Test",
+                "B",
+                Composite::Unnamed(vec![Value::u128(7)]),
+            )
+            .build()
+            .unwrap();
+
+        let decoded: Vec<_> = events
+            .iter()
+            .map(|ev| {
+                <(Phase, AllEvents<Event>, Vec<H256>)>::decode(&mut ev.unwrap().bytes())
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(
+            decoded,
+            vec![
+                (
+                    Phase::ApplyExtrinsic(2),
+                    AllEvents::Test(Event::A { amount: 25, ok: true }),
+                    vec![],
+                ),
+                (Phase::Finalization, AllEvents::Test(Event::B(7)), vec![]),
+            ]
+        );
+
+        let event = events.iter().nth(1).unwrap().unwrap();
+        let ctx = event_context::<_, ()>((), &events, &event, 12);
+        assert_eq!(ctx.block_number(), 12);
+        assert_eq!(ctx.extrinsic_index(), None);
+        assert_eq!(ctx.variant_name(), "B");
+    }
+
+    #[test]
+    fn events_must_match_the_metadata() {
+        let build = |variant: &str, fields| {
+            EventsBuilder::<SubstrateConfig>::new(metadata::<Event>())
+                .event(Phase::Initialization, "Test", variant, fields)
+                .build()
+        };
+        assert!(build("C", Composite::Unnamed(vec![])).is_err());
+        assert!(build("B", Composite::Unnamed(vec![])).is_err());
+        assert!(build("A", Composite::Named(vec![("ok".into(), Value::bool(true))])).is_err());
+    }
+}
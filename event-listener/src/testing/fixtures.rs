@@ -0,0 +1,259 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use crate::{
+    error::Error,
+    Metadata,
+};
+use codec::{
+    Compact,
+    Decode,
+    Encode,
+};
+use frame_metadata::{
+    v14::{
+        ExtrinsicMetadata,
+        PalletCallMetadata,
+        PalletEventMetadata,
+        PalletMetadata,
+        RuntimeMetadataV14,
+    },
+    RuntimeMetadataPrefixed,
+};
+use scale_info::{
+    meta_type,
+    TypeInfo,
+};
+use sp_core::H256;
+use sp_runtime::{
+    AccountId32,
+    MultiAddress,
+};
+use std::{
+    convert::TryFrom,
+    path::Path,
+};
+
+/// Where [`polkadot_metadata()`] loads the captured Polkadot metadata from. It can
+/// be captured again from a node by `scripts/capture_polkadot_metadata.sh`.
+pub const POLKADOT_METADATA_PATH: &str =
+    concat!(env!("CARGO_MANIFEST_DIR"), "/artifacts/polkadot_metadata.scale");
+
+/// Decode metadata in the form that `state_getMetadata` hands it back, either as
+/// bytes or as a `0x` prefixed hex string.
+pub fn metadata_from_bytes(bytes: &[u8]) -> Result<Metadata, Error> {
+    let meta = match bytes.strip_prefix(b"0x") {
+        Some(hex) => {
+            let bytes = hex::decode(String::from_utf8_lossy(hex).trim())
+                .map_err(|e| Error::Other(format!("Invalid metadata hex: {}", e)))?;
+            RuntimeMetadataPrefixed::decode(&mut &bytes[..])?
+        }
+        None => RuntimeMetadataPrefixed::decode(&mut &*bytes)?,
+    };
+    Ok(Metadata::try_from(meta)?)
+}
+
+/// Load metadata that was captured to a file, as bytes or as hex.
+pub fn metadata_from_file(path: impl AsRef<Path>) -> Result<Metadata, Error> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path).map_err(|e| {
+        Error::Other(format!("Cannot read metadata from {}: {}", path.display(), e))
+    })?;
+    metadata_from_bytes(&bytes)
+}
+
+/// The V14 metadata of a real Polkadot runtime (spec version 1001002), loaded
+/// from [`POLKADOT_METADATA_PATH`].
+pub fn polkadot_metadata() -> Result<Metadata, Error> {
+    metadata_from_file(POLKADOT_METADATA_PATH)
+}
+
+/// The metadata of a small runtime with just enough in it to test against. It has
+/// two pallets, shaped like their Substrate namesakes:
+///
+/// - `System`, at index 0, with the call `remark { remark: Vec<u8> }` and the
+///   events `ExtrinsicSuccess { dispatch_info }`,
+///   `ExtrinsicFailed { dispatch_error, dispatch_info }`,
+///   `NewAccount { account }` and `Remarked { sender, hash }`.
+/// - `Balances`, at index 5, with the call
+///   `transfer { dest: MultiAddress, value: Compact<u128> }` and the events
+///   `Endowed { account, free_balance }`, `Transfer { from, to, amount }`,
+///   `Deposit { who, amount }` and `Withdraw { who, amount }`.
+///
+/// Accounts are `AccountId32`s and balances are `u128`s.
+pub fn test_runtime_metadata() -> Metadata {
+    let pallets = vec![
+        PalletMetadata {
+            name: "System",
+            storage: None,
+            calls: Some(PalletCallMetadata {
+                ty: meta_type::<SystemCall>(),
+            }),
+            event: Some(PalletEventMetadata {
+                ty: meta_type::<SystemEvent>(),
+            }),
+            constants: vec![],
+            error: None,
+            index: 0,
+        },
+        PalletMetadata {
+            name: "Balances",
+            storage: None,
+            calls: Some(PalletCallMetadata {
+                ty: meta_type::<BalancesCall>(),
+            }),
+            event: Some(PalletEventMetadata {
+                ty: meta_type::<BalancesEvent>(),
+            }),
+            constants: vec![],
+            error: None,
+            index: 5,
+        },
+    ];
+    let extrinsic = ExtrinsicMetadata {
+        ty: meta_type::<()>(),
+        version: 4,
+        signed_extensions: vec![],
+    };
+
+    let v14 = RuntimeMetadataV14::new(pallets, extrinsic, meta_type::<()>());
+    Metadata::try_from(RuntimeMetadataPrefixed::from(v14))
+        .expect("the test runtime metadata is valid; qed")
+}
+
+#[allow(dead_code, non_camel_case_types)]
+#[derive(Encode, Decode, TypeInfo)]
+enum SystemCall {
+    remark { remark: Vec<u8> },
+}
+
+#[allow(dead_code)]
+#[derive(Encode, Decode, TypeInfo)]
+enum SystemEvent {
+    ExtrinsicSuccess {
+        dispatch_info: DispatchInfo,
+    },
+    ExtrinsicFailed {
+        dispatch_error: DispatchError,
+        dispatch_info: DispatchInfo,
+    },
+    NewAccount {
+        account: AccountId32,
+    },
+    Remarked {
+        sender: AccountId32,
+        hash: H256,
+    },
+}
+
+#[allow(dead_code)]
+#[derive(Encode, Decode, TypeInfo)]
+struct DispatchInfo {
+    weight: u64,
+    class: DispatchClass,
+    pays_fee: Pays,
+}
+
+#[allow(dead_code)]
+#[derive(Encode, Decode, TypeInfo)]
+enum DispatchClass {
+    Normal,
+    Operational,
+    Mandatory,
+}
+
+#[allow(dead_code)]
+#[derive(Encode, Decode, TypeInfo)]
+enum Pays {
+    Yes,
+    No,
+}
+
+#[allow(dead_code)]
+#[derive(Encode, Decode, TypeInfo)]
+enum DispatchError {
+    Other,
+    BadOrigin,
+    Module { index: u8, error: [u8; 4] },
+}
+
+#[allow(dead_code, non_camel_case_types)]
+#[derive(Encode, Decode, TypeInfo)]
+enum BalancesCall {
+    transfer {
+        dest: MultiAddress<AccountId32, ()>,
+        value: Compact<u128>,
+    },
+}
+
+#[allow(dead_code)]
+#[derive(Encode, Decode, TypeInfo)]
+enum BalancesEvent {
+    Endowed {
+        account: AccountId32,
+        free_balance: u128,
+    },
+    Transfer {
+        from: AccountId32,
+        to: AccountId32,
+        amount: u128,
+    },
+    Deposit {
+        who: AccountId32,
+        amount: u128,
+    },
+    Withdraw {
+        who: AccountId32,
+        amount: u128,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::to_hex;
+
+    #[test]
+    fn test_runtime_has_events_at_their_usual_indices() {
+        let metadata = test_runtime_metadata();
+        let ((pallet, variant), event) =
+            metadata.event_by_name("Balances", "Transfer").unwrap();
+        assert_eq!((pallet, variant), (5, 1));
+        let names: Vec<_> = event
+            .fields()
+            .iter()
+            .map(|(name, _)| name.as_deref().unwrap())
+            .collect();
+        assert_eq!(names, ["from", "to", "amount"]);
+        assert!(metadata.call_by_name("System", "remark").is_ok());
+    }
+
+    #[test]
+    fn metadata_is_loaded_from_bytes_or_hex() {
+        let v14 = RuntimeMetadataV14::new(
+            vec![],
+            ExtrinsicMetadata {
+                ty: meta_type::<()>(),
+                version: 4,
+                signed_extensions: vec![],
+            },
+            meta_type::<()>(),
+        );
+        let bytes = RuntimeMetadataPrefixed::from(v14).encode();
+        assert!(metadata_from_bytes(&bytes).is_ok());
+        let hex = format!("{}\n", to_hex(&bytes));
+        assert!(metadata_from_bytes(hex.as_bytes()).is_ok());
+        assert!(metadata_from_bytes(b"0xnope").is_err());
+    }
+
+    #[test]
+    fn polkadot_metadata_is_checked_in() {
+        let metadata = polkadot_metadata().unwrap();
+        let ((pallet, variant), event) =
+            metadata.event_by_name("Balances", "Transfer").unwrap();
+        assert_eq!((pallet, variant), (5, 2));
+        assert_eq!(event.fields().len(), 3);
+        assert!(metadata.call_by_name("Balances", "transfer_keep_alive").is_ok());
+    }
+}
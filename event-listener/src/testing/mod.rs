@@ -0,0 +1,48 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! This module exposes utilities for testing code built on this crate, such as
+//! event handlers and filters, without needing a node to test against, and for
+//! checking that the decoding of captured blocks doesn't drift with snapshots.
+//!
+//! With the `test-fixtures` feature, it also provides metadata to test against,
+//! so that tests needn't build their own: the metadata of a small synthetic
+//! runtime, and that of a real Polkadot runtime (which can be captured again
+//! with `scripts/capture_polkadot_metadata.sh`).
+//!
+//! With the `integration-tests` feature, [`TestNodeProcess`] runs a Substrate node
+//! to test against end to end.
+
+mod events;
+#[cfg(any(test, feature = "test-fixtures"))]
+mod fixtures;
+#[cfg(all(feature = "integration-tests", feature = "jsonrpsee"))]
+mod node;
+mod snapshot;
+
+pub use events::{
+    event_context,
+    EventsBuilder,
+};
+pub use snapshot::{
+    assert_events_snapshot,
+    events_snapshot,
+    UPDATE_SNAPSHOTS_ENV,
+};
+#[cfg(any(test, feature = "test-fixtures"))]
+pub use fixtures::{
+    metadata_from_bytes,
+    metadata_from_file,
+    polkadot_metadata,
+    test_runtime_metadata,
+    POLKADOT_METADATA_PATH,
+};
+#[cfg(all(feature = "integration-tests", feature = "jsonrpsee"))]
+pub use node::{
+    TestNodeProcess,
+    TestNodeProcessBuilder,
+    DEFAULT_NODE_PATH,
+    DEFAULT_STARTUP_TIMEOUT,
+    NODE_PATH_ENV,
+};
@@ -0,0 +1,272 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use crate::{
+    error::Error,
+    Config,
+    OnlineClient,
+};
+use futures::{
+    channel::oneshot,
+    future::{
+        self,
+        Either,
+    },
+};
+use std::{
+    ffi::{
+        OsStr,
+        OsString,
+    },
+    io::{
+        BufRead,
+        BufReader,
+    },
+    process::{
+        Child,
+        Command,
+        Stdio,
+    },
+    time::Duration,
+};
+
+/// The environment variable which [`TestNodeProcessBuilder::from_env()`] reads the
+/// path of the node binary from.
+pub const NODE_PATH_ENV: &str = "SUBSTRATE_NODE_PATH";
+
+/// The node binary that [`TestNodeProcessBuilder::from_env()`] runs (looking for
+/// it on the `PATH`) if [`NODE_PATH_ENV`] isn't set.
+pub const DEFAULT_NODE_PATH: &str = "node-template";
+
+/// How long a node has to start up by default.
+pub const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A Substrate node running in a development chain of its own, along with a
+/// client connected to it. The node is killed when this is dropped.
+///
+/// ```no_run
+/// use event_listener::{
+///     testing::TestNodeProcess,
+///     PolkadotConfig,
+/// };
+/// use futures::StreamExt;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let node = TestNodeProcess::<PolkadotConfig>::build_from_env()
+///     .spawn()
+///     .await
+///     .unwrap();
+///
+/// let mut blocks = node.client().blocks().subscribe().await.unwrap();
+/// let block = blocks.next().await.unwrap().unwrap();
+/// let events = block.events().await.unwrap();
+/// # }
+/// ```
+pub struct TestNodeProcess<T: Config> {
+    proc: Child,
+    url: String,
+    client: OnlineClient<T>,
+}
+
+impl<T: Config> std::fmt::Debug for TestNodeProcess<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TestNodeProcess")
+            .field("pid", &self.proc.id())
+            .field("url", &self.url)
+            .finish()
+    }
+}
+
+impl<T: Config> TestNodeProcess<T> {
+    /// Run the node binary at the path given.
+    pub fn build(program: impl AsRef<OsStr>) -> TestNodeProcessBuilder {
+        TestNodeProcessBuilder::new(program)
+    }
+
+    /// Run the node binary named by [`NODE_PATH_ENV`], or else
+    /// [`DEFAULT_NODE_PATH`].
+    pub fn build_from_env() -> TestNodeProcessBuilder {
+        TestNodeProcessBuilder::from_env()
+    }
+
+    /// A client connected to the node.
+    pub fn client(&self) -> &OnlineClient<T> {
+        &self.client
+    }
+
+    /// The URL of the node's RPC server.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Kill the node, rather than waiting for this to be dropped.
+    pub fn kill(&mut self) -> Result<(), Error> {
+        tracing::info!(pid = self.proc.id(), "Killing test node");
+        self.proc
+            .kill()
+            .map_err(|e| Error::Other(format!("Cannot kill test node: {}", e)))?;
+        let _ = self.proc.wait();
+        Ok(())
+    }
+}
+
+impl<T: Config> Drop for TestNodeProcess<T> {
+    fn drop(&mut self) {
+        let _ = self.kill();
+    }
+}
+
+/// Configures how a [`TestNodeProcess`] is started.
+#[derive(Clone, Debug)]
+pub struct TestNodeProcessBuilder {
+    program: OsString,
+    authority: Option<String>,
+    args: Vec<OsString>,
+    startup_timeout: Duration,
+}
+
+impl TestNodeProcessBuilder {
+    /// Run the node binary at the path given.
+    pub fn new(program: impl AsRef<OsStr>) -> Self {
+        TestNodeProcessBuilder {
+            program: program.as_ref().to_owned(),
+            authority: None,
+            args: Vec::new(),
+            startup_timeout: DEFAULT_STARTUP_TIMEOUT,
+        }
+    }
+
+    /// Run the node binary named by [`NODE_PATH_ENV`], or else
+    /// [`DEFAULT_NODE_PATH`].
+    pub fn from_env() -> Self {
+        let program = std::env::var_os(NODE_PATH_ENV)
+            .unwrap_or_else(|| DEFAULT_NODE_PATH.into());
+        TestNodeProcessBuilder::new(program)
+    }
+
+    /// Run the node as one of the development authorities, such as `alice`.
+    pub fn authority(mut self, authority: impl Into<String>) -> Self {
+        self.authority = Some(authority.into());
+        self
+    }
+
+    /// Pass another argument to the node.
+    pub fn arg(mut self, arg: impl AsRef<OsStr>) -> Self {
+        self.args.push(arg.as_ref().to_owned());
+        self
+    }
+
+    /// How long to wait for the node's RPC server to start.
+    pub fn startup_timeout(mut self, timeout: Duration) -> Self {
+        self.startup_timeout = timeout;
+        self
+    }
+
+    /// Start the node, and connect to it once its RPC server is up.
+    pub async fn spawn<T: Config>(&self) -> Result<TestNodeProcess<T>, Error> {
+        let mut cmd = Command::new(&self.program);
+        cmd.env("RUST_LOG", "info")
+            .arg("--dev")
+            .arg("--tmp")
+            // Let the OS pick the ports, so that nodes can run side by side.
+            .arg("--port=0")
+            .arg("--rpc-port=0")
+            .arg("--ws-port=0")
+            .args(&self.args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped());
+        if let Some(authority) = &self.authority {
+            cmd.arg(format!("--{}", authority));
+        }
+        let mut proc = cmd.spawn().map_err(|e| {
+            Error::Other(format!(
+                "Cannot run node {}: {}",
+                self.program.to_string_lossy(),
+                e
+            ))
+        })?;
+        tracing::info!(pid = proc.id(), "Started test node");
+
+        // The node logs the address of its RPC server once it's up. Keep reading
+        // its logs after that, so that it never blocks on a full pipe.
+        let stderr = proc.stderr.take().expect("stderr is piped; qed");
+        let (tx, rx) = oneshot::channel();
+        std::thread::spawn(move || {
+            let mut tx = Some(tx);
+            for line in BufReader::new(stderr).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                if let Some(url) = rpc_url(&line) {
+                    if let Some(tx) = tx.take() {
+                        let _ = tx.send(url);
+                    }
+                }
+            }
+        });
+
+        let timeout = futures_timer::Delay::new(self.startup_timeout);
+        let url = match future::select(rx, timeout).await {
+            Either::Left((Ok(url), _)) => url,
+            Either::Left((Err(_), _)) => {
+                let _ = proc.kill();
+                return Err(Error::Other("Test node exited before starting RPC".into()))
+            }
+            Either::Right(_) => {
+                let _ = proc.kill();
+                return Err(Error::Other(format!(
+                    "Test node didn't start RPC within {:?}",
+                    self.startup_timeout
+                )))
+            }
+        };
+
+        match OnlineClient::from_url(&url).await {
+            Ok(client) => Ok(TestNodeProcess { proc, url, client }),
+            Err(e) => {
+                let _ = proc.kill();
+                Err(e)
+            }
+        }
+    }
+}
+
+// The URL of the RPC server, from the line that nodes log once it's up, such as
+// `Running JSON-RPC WS server: addr=127.0.0.1:9944, allowed origins=...`.
+fn rpc_url(line: &str) -> Option<String> {
+    let (_, rest) = line.split_once("Running JSON-RPC")?;
+    if rest.starts_with(" HTTP") {
+        return None
+    }
+    let (_, addr) = rest.split_once("addr=")?;
+    let addr = addr.split(|c: char| c == ',' || c.is_whitespace()).next()?;
+    Some(format!("ws://{}", addr))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rpc_url_is_read_from_logs() {
+        assert_eq!(
+            rpc_url(
+                "2022-10-01 12:00:00 Running JSON-RPC WS server: addr=127.0.0.1:38291, \
+                 allowed origins=None"
+            ),
+            Some("ws://127.0.0.1:38291".into())
+        );
+        assert_eq!(
+            rpc_url("Running JSON-RPC server: addr=127.0.0.1:9944,[::1]:9944"),
+            Some("ws://127.0.0.1:9944".into())
+        );
+        assert_eq!(
+            rpc_url("Running JSON-RPC HTTP server: addr=127.0.0.1:9933, allowed origins=None"),
+            None
+        );
+        assert_eq!(rpc_url("Idle (0 peers), best: #0"), None);
+    }
+}
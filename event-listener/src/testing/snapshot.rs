@@ -0,0 +1,187 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use crate::{
+    error::Error,
+    events::Events,
+    json::composite_to_json,
+    utils::to_hex,
+    Config,
+};
+use codec::Encode;
+use serde_json::json;
+use std::path::Path;
+
+/// The environment variable which, when set, makes [`assert_events_snapshot()`]
+/// write snapshots out rather than compare against them.
+pub const UPDATE_SNAPSHOTS_ENV: &str = "UPDATE_SNAPSHOTS";
+
+/// Write the events of a block out in a canonical form, as pretty printed JSON,
+/// for comparing against a snapshot of them. The same events always give the
+/// same snapshot, so any change to the snapshot means that decoding has changed.
+///
+/// Each event is written with its index, phase, pallet, variant and fields, with
+/// the fields in the form described in [`crate::json`].
+pub fn events_snapshot<T: Config>(events: &Events<T>) -> Result<String, Error> {
+    let mut snapshot = Vec::new();
+    for event in events.iter() {
+        let event = event?;
+        let fields = event.field_values()?;
+        snapshot.push(json!({
+            "index": event.index(),
+            "phase": format!("{:?}", event.phase()),
+            "pallet": event.pallet_name(),
+            "variant": event.variant_name(),
+            "fields": composite_to_json(&fields, events.metadata()),
+        }));
+    }
+    let snapshot = json!({
+        "block_hash": to_hex(events.block_hash().encode()),
+        "events": snapshot,
+    });
+    let mut snapshot = serde_json::to_string_pretty(&snapshot)?;
+    snapshot.push('\n');
+    Ok(snapshot)
+}
+
+/// Check that the [snapshot](events_snapshot()) of the events matches the one in
+/// the file given, panicking with where they differ if not. If the file doesn't
+/// exist yet, or [`UPDATE_SNAPSHOTS_ENV`] is set, the snapshot is written to it
+/// instead.
+///
+/// ```no_run
+/// use event_listener::{
+///     testing::assert_events_snapshot,
+///     OnlineClient,
+///     PolkadotConfig,
+/// };
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = OnlineClient::<PolkadotConfig>::new().await.unwrap();
+/// let hash = api.rpc().block_hash(Some(12_000_000u32.into())).await.unwrap();
+/// let events = api.events().at(hash).await.unwrap();
+///
+/// assert_events_snapshot(&events, "tests/snapshots/polkadot_12000000.json");
+/// # }
+/// ```
+pub fn assert_events_snapshot<T: Config>(events: &Events<T>, path: impl AsRef<Path>) {
+    let path = path.as_ref();
+    let actual = events_snapshot(events).expect("events can be decoded");
+    if std::env::var_os(UPDATE_SNAPSHOTS_ENV).is_some() || !path.exists() {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).expect("can create the snapshot directory");
+        }
+        std::fs::write(path, &actual).expect("can write the snapshot");
+        return
+    }
+
+    let expected = std::fs::read_to_string(path).expect("can read the snapshot");
+    if actual == expected {
+        return
+    }
+    let line = actual
+        .lines()
+        .zip(expected.lines())
+        .position(|(a, e)| a != e)
+        .unwrap_or_else(|| actual.lines().count().min(expected.lines().count()));
+    panic!(
+        "Events differ from the snapshot {} at line {}:\n  expected: {}\n  actual:   {}\n\
+         Set {} to update the snapshot.",
+        path.display(),
+        line + 1,
+        expected.lines().nth(line).unwrap_or("<end of snapshot>"),
+        actual.lines().nth(line).unwrap_or("<end of events>"),
+        UPDATE_SNAPSHOTS_ENV,
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        events::Phase,
+        pallets::test_utils::account_value,
+        testing::{
+            test_runtime_metadata,
+            EventsBuilder,
+        },
+        SubstrateConfig,
+    };
+    use scale_value::{
+        Composite,
+        Value,
+    };
+
+    fn events(amount: u128) -> Events<SubstrateConfig> {
+        EventsBuilder::new(test_runtime_metadata())
+            .event(
+                Phase::ApplyExtrinsic(1),
+                "Balances",
+                "Transfer",
+                Composite::Named(vec![
+                    ("from".into(), account_value(1)),
+                    ("to".into(), account_value(2)),
+                    ("amount".into(), Value::u128(amount)),
+                ]),
+            )
+            .event(
+                Phase::ApplyExtrinsic(1),
+                "System",
+                "ExtrinsicSuccess",
+                Composite::Named(vec![(
+                    "dispatch_info".into(),
+                    Value::named_composite(vec![
+                        ("weight".into(), Value::u128(10)),
+                        ("class".into(), Value::variant("Normal", Composite::Unnamed(vec![]))),
+                        ("pays_fee".into(), Value::variant("Yes", Composite::Unnamed(vec![]))),
+                    ]),
+                )]),
+            )
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn snapshots_are_canonical() {
+        let snapshot: serde_json::Value =
+            serde_json::from_str(&events_snapshot(&events(u128::MAX)).unwrap()).unwrap();
+        assert_eq!(
+            snapshot["events"][0],
+            json!({
+                "index": 0,
+                "phase": "ApplyExtrinsic(1)",
+                "pallet": "Balances",
+                "variant": "Transfer",
+                "fields": {
+                    "from": [format!("0x{}", "01".repeat(32))],
+                    "to": [format!("0x{}", "02".repeat(32))],
+                    "amount": u128::MAX.to_string(),
+                },
+            })
+        );
+        assert_eq!(
+            snapshot["events"][1]["fields"]["dispatch_info"],
+            json!({ "weight": 10, "class": "Normal", "pays_fee": "Yes" })
+        );
+        assert_eq!(
+            events_snapshot(&events(5)).unwrap(),
+            events_snapshot(&events(5)).unwrap()
+        );
+    }
+
+    #[test]
+    fn snapshots_are_written_then_compared() {
+        let path = std::env::temp_dir().join(format!(
+            "event-listener-snapshot-{}.json",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        assert_events_snapshot(&events(5), &path);
+        assert_events_snapshot(&events(5), &path);
+        let changed = std::panic::catch_unwind(|| assert_events_snapshot(&events(6), &path));
+        std::fs::remove_file(&path).unwrap();
+        assert!(changed.is_err());
+    }
+}
@@ -0,0 +1,65 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use codec::Decode;
+
+/// A breakdown of the fee that would be paid for an extrinsic, as handed back
+/// from the `TransactionPaymentApi_query_fee_details` runtime API.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FeeEstimate {
+    /// The minimum fee for a transaction to be included in a block.
+    pub base_fee: u128,
+    /// The length fee; the amount paid for the encoded length (in bytes) of
+    /// the transaction.
+    pub len_fee: u128,
+    /// The weight fee, adjusted by the current fee multiplier.
+    pub adjusted_weight_fee: u128,
+    /// The tip given by the extrinsic, if any.
+    pub tip: u128,
+}
+
+impl FeeEstimate {
+    /// The fee paid for the extrinsic, excluding the tip. This is the figure
+    /// that `payment_queryInfo` hands back as the `partialFee`.
+    pub fn partial_fee(&self) -> u128 {
+        self.base_fee
+            .saturating_add(self.len_fee)
+            .saturating_add(self.adjusted_weight_fee)
+    }
+
+    /// The fee paid for the extrinsic, including the tip.
+    pub fn total(&self) -> u128 {
+        self.partial_fee().saturating_add(self.tip)
+    }
+}
+
+// `pallet_transaction_payment::FeeDetails`, assuming a `u128` balance.
+#[derive(Decode)]
+pub(crate) struct FeeDetails {
+    inclusion_fee: Option<InclusionFee>,
+    tip: u128,
+}
+
+#[derive(Decode)]
+struct InclusionFee {
+    base_fee: u128,
+    len_fee: u128,
+    adjusted_weight_fee: u128,
+}
+
+impl From<FeeDetails> for FeeEstimate {
+    fn from(details: FeeDetails) -> Self {
+        let inclusion_fee = details.inclusion_fee.unwrap_or(InclusionFee {
+            base_fee: 0,
+            len_fee: 0,
+            adjusted_weight_fee: 0,
+        });
+        FeeEstimate {
+            base_fee: inclusion_fee.base_fee,
+            len_fee: inclusion_fee.len_fee,
+            adjusted_weight_fee: inclusion_fee.adjusted_weight_fee,
+            tip: details.tip,
+        }
+    }
+}
@@ -0,0 +1,42 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Create, sign, submit and inspect transactions (extrinsics).
+
+mod fee;
+mod params;
+mod signed_extensions;
+mod signer;
+mod tx_client;
+mod tx_payload;
+mod tx_progress;
+
+pub use fee::FeeEstimate;
+pub use params::{
+    BaseExtrinsicParams,
+    ExtrinsicParams,
+    OtherParamsFor,
+    PolkadotExtrinsicParams,
+    SubstrateExtrinsicParams,
+    TxParams,
+};
+#[cfg(feature = "signer")]
+pub use signer::PairSigner;
+pub use signer::Signer;
+pub use tx_client::{
+    SubmittableExtrinsic,
+    TxClient,
+};
+pub use tx_payload::{
+    dynamic,
+    DynamicTxPayload,
+    RawTxPayload,
+    StaticTxPayload,
+    TxPayload,
+};
+pub use tx_progress::{
+    TxInBlock,
+    TxProgress,
+    TxStatus,
+};
@@ -0,0 +1,274 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use super::signed_extensions::{
+    encode_signed_extensions,
+    SignedExtensionParams,
+};
+use crate::{
+    error::Error,
+    Config,
+    Metadata,
+};
+use codec::Encode;
+use derivative::Derivative;
+use sp_runtime::{
+    generic::Era,
+    traits::Header,
+};
+
+/// This trait allows you to configure the "signed extra" and
+/// "additional" parameters that are signed and used in transactions.
+/// See [`BaseExtrinsicParams`] for an implementation that is compatible with
+/// most chains, which uses the metadata to work out what to provide.
+pub trait ExtrinsicParams<Hash>: 'static {
+    /// These parameters can be provided to the constructor along with
+    /// some default parameters that we understand, in order to help
+    /// construct your [`ExtrinsicParams`] object.
+    type OtherParams;
+
+    /// Construct a new instance of our [`ExtrinsicParams`].
+    fn new(
+        spec_version: u32,
+        tx_version: u32,
+        nonce: u64,
+        genesis_hash: Hash,
+        metadata: &Metadata,
+        other_params: Self::OtherParams,
+    ) -> Result<Self, Error>
+    where
+        Self: Sized;
+
+    /// This is expected to SCALE encode the "signed extra" parameters
+    /// to some buffer that has been provided. These are the parameters
+    /// which are sent along with the transaction, as well as taken into
+    /// account when signing the transaction.
+    fn encode_extra_to(&self, v: &mut Vec<u8>);
+
+    /// This is expected to SCALE encode the "additional" parameters
+    /// to some buffer that has been provided. These parameters are _not_
+    /// sent along with the transaction, but are taken into account when
+    /// signing it, meaning the client and node must agree on their values.
+    fn encode_additional_to(&self, v: &mut Vec<u8>);
+}
+
+/// The [`ExtrinsicParams`] of any [`Config`], as given to [`super::TxClient`]
+/// when creating a signed extrinsic.
+pub type OtherParamsFor<T> = <<T as Config>::ExtrinsicParams as ExtrinsicParams<
+    <T as Config>::Hash,
+>>::OtherParams;
+
+/// A struct representing the signed extra and additional parameters required
+/// to construct a transaction for a Substrate or Polkadot based chain. The
+/// signed extensions listed in the metadata are constructed, in order, from
+/// the [`TxParams`] given; see there for how to provide data for any that
+/// aren't standard.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub struct BaseExtrinsicParams<T: Config> {
+    extra: Vec<u8>,
+    additional: Vec<u8>,
+    _marker: std::marker::PhantomData<T>,
+}
+
+/// The [`ExtrinsicParams`] used by Substrate based chains.
+pub type SubstrateExtrinsicParams<T> = BaseExtrinsicParams<T>;
+
+/// The [`ExtrinsicParams`] used by Polkadot and its parachains.
+pub type PolkadotExtrinsicParams<T> = BaseExtrinsicParams<T>;
+
+impl<T: Config> ExtrinsicParams<T::Hash> for BaseExtrinsicParams<T> {
+    type OtherParams = TxParams<T>;
+
+    fn new(
+        spec_version: u32,
+        tx_version: u32,
+        nonce: u64,
+        genesis_hash: T::Hash,
+        metadata: &Metadata,
+        other_params: Self::OtherParams,
+    ) -> Result<Self, Error> {
+        let params = SignedExtensionParams::<T> {
+            nonce,
+            tx: other_params,
+            spec_version,
+            transaction_version: tx_version,
+            genesis_hash,
+        };
+        let data = encode_signed_extensions(metadata, &params)?;
+        Ok(BaseExtrinsicParams {
+            extra: data.extra,
+            additional: data.additional,
+            _marker: std::marker::PhantomData,
+        })
+    }
+
+    fn encode_extra_to(&self, v: &mut Vec<u8>) {
+        v.extend_from_slice(&self.extra);
+    }
+
+    fn encode_additional_to(&self, v: &mut Vec<u8>) {
+        v.extend_from_slice(&self.additional);
+    }
+}
+
+/// The per-transaction parameters which go into the signed extensions of an
+/// extrinsic, for configs using [`BaseExtrinsicParams`]. By default,
+/// transactions are immortal and carry no tip.
+///
+/// The standard Substrate signed extensions are constructed from these. Chains
+/// with other signed extensions can provide the data for them with
+/// [`TxParams::signed_extension()`].
+///
+/// # Example
+///
+/// ```
+/// use event_listener::{
+///     ext::sp_runtime::generic::Era,
+///     tx::TxParams,
+///     PolkadotConfig,
+/// };
+///
+/// // A transaction with a tip which is valid for 64 blocks from block 1000. Use
+/// // `TxParams::mortal()` to work this out from a recent block header instead.
+/// let checkpoint = Default::default();
+/// let params = TxParams::<PolkadotConfig>::new()
+///     .tip(1_000)
+///     .era(Era::mortal(64, 1000), checkpoint);
+/// ```
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub struct TxParams<T: Config> {
+    pub(crate) tip: u128,
+    pub(crate) era: Era,
+    pub(crate) checkpoint: Option<T::Hash>,
+    pub(crate) asset_id: Option<Vec<u8>>,
+    pub(crate) metadata_hash: Option<[u8; 32]>,
+    pub(crate) custom: Vec<CustomSignedExtension>,
+}
+
+/// The data for a signed extension that was provided by hand.
+#[derive(Clone, Debug)]
+pub(crate) struct CustomSignedExtension {
+    pub identifier: String,
+    pub extra: Vec<u8>,
+    pub additional: Vec<u8>,
+}
+
+impl<T: Config> Default for TxParams<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Config> TxParams<T> {
+    /// Immortal transactions with no tip.
+    pub fn new() -> Self {
+        Self {
+            tip: 0,
+            era: Era::Immortal,
+            checkpoint: None,
+            asset_id: None,
+            metadata_hash: None,
+            custom: Vec::new(),
+        }
+    }
+
+    /// Set the tip to give to the block author, to increase the priority of
+    /// the transaction.
+    pub fn tip(mut self, tip: u128) -> Self {
+        self.tip = tip;
+        self
+    }
+
+    /// Set the era of the transaction, along with the hash of the block that
+    /// the era begins at (the checkpoint). Mortal transactions are only valid
+    /// in the blocks after the checkpoint block that fall within the era.
+    pub fn era(mut self, era: Era, checkpoint: T::Hash) -> Self {
+        self.era = era;
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
+    /// Pay the fees (and tip) in the asset with the ID given, rather than in the
+    /// native token. This is used by the `ChargeAssetTxPayment` signed extension,
+    /// and the ID must have the type that the runtime expects (for instance a
+    /// `u32` on the Asset Hub chains).
+    pub fn asset_id<A: Encode>(mut self, asset_id: A) -> Self {
+        self.asset_id = Some(asset_id.encode());
+        self
+    }
+
+    /// Enable the `CheckMetadataHash` signed extension, committing to the hash
+    /// of the metadata given. This is disabled by default.
+    pub fn metadata_hash(mut self, hash: [u8; 32]) -> Self {
+        self.metadata_hash = Some(hash);
+        self
+    }
+
+    /// Provide the data for the signed extension with the identifier given. The
+    /// `extra` value is included in the extrinsic, and the `additional` value is
+    /// only signed; use `()` for either if the extension doesn't expect any.
+    ///
+    /// This takes precedence over the data we'd otherwise construct for the
+    /// standard signed extensions.
+    pub fn signed_extension(
+        mut self,
+        identifier: impl Into<String>,
+        extra: impl Encode,
+        additional: impl Encode,
+    ) -> Self {
+        let identifier = identifier.into();
+        self.custom.retain(|ext| ext.identifier != identifier);
+        self.custom.push(CustomSignedExtension {
+            identifier,
+            extra: extra.encode(),
+            additional: additional.encode(),
+        });
+        self
+    }
+
+    /// Make the transaction mortal, valid for roughly `period` blocks beginning at
+    /// the block with the header given. This should be a recent block, ideally a
+    /// finalized one, so that the transaction can't be replayed once the era ends.
+    ///
+    /// The period is rounded up to a power of two between 4 and 65536.
+    pub fn mortal(self, from: &T::Header, period: u64) -> Self {
+        let number: u64 = (*from.number()).into();
+        self.era(Era::mortal(period, number), from.hash())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::SubstrateConfig;
+    use sp_runtime::traits::Hash;
+
+    type SubstrateHeader = <SubstrateConfig as Config>::Header;
+
+    #[test]
+    fn mortal_era_begins_at_the_header_given() {
+        let header = SubstrateHeader::new(
+            1000,
+            Default::default(),
+            Default::default(),
+            sp_runtime::traits::BlakeTwo256::hash(b"parent"),
+            Default::default(),
+        );
+        let params = TxParams::<SubstrateConfig>::new().mortal(&header, 64);
+
+        assert_eq!(params.checkpoint, Some(header.hash()));
+        assert!(params.era.is_mortal());
+        assert_eq!(params.era.birth(1000), 1000);
+        assert_eq!(params.era.death(1000), 1064);
+    }
+
+    #[test]
+    fn transactions_are_immortal_by_default() {
+        let params = TxParams::<SubstrateConfig>::default();
+        assert_eq!(params.era, Era::Immortal);
+        assert_eq!(params.checkpoint, None);
+    }
+}
@@ -0,0 +1,215 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Build the data that the signed extensions listed in the metadata expect to
+//! find in (or have signed alongside) an extrinsic.
+
+use super::TxParams;
+use crate::{
+    error::{
+        Error,
+        TransactionError,
+    },
+    metadata::Metadata,
+    Config,
+};
+use codec::{
+    Compact,
+    Encode,
+};
+use scale_info::{
+    PortableRegistry,
+    TypeDef,
+};
+/// The details needed to construct the signed extension data of an extrinsic.
+pub(crate) struct SignedExtensionParams<T: Config> {
+    pub nonce: u64,
+    pub tx: TxParams<T>,
+    pub spec_version: u32,
+    pub transaction_version: u32,
+    pub genesis_hash: T::Hash,
+}
+
+/// The signed extension data of an extrinsic: the "extra" data, which is part of
+/// the extrinsic, and the "additional" data, which is only signed.
+pub(crate) struct SignedExtensionData {
+    pub extra: Vec<u8>,
+    pub additional: Vec<u8>,
+}
+
+/// Encode the data for each of the signed extensions in the metadata, in order.
+pub(crate) fn encode_signed_extensions<T: Config>(
+    metadata: &Metadata,
+    params: &SignedExtensionParams<T>,
+) -> Result<SignedExtensionData, Error> {
+    let types = &metadata.runtime_metadata().types;
+    let tx = &params.tx;
+    let mut extra = Vec::new();
+    let mut additional = Vec::new();
+
+    for ext in metadata.extrinsic().signed_extensions() {
+        if let Some(custom) = tx
+            .custom
+            .iter()
+            .find(|custom| custom.identifier == ext.identifier())
+        {
+            extra.extend_from_slice(&custom.extra);
+            additional.extend_from_slice(&custom.additional);
+            continue
+        }
+
+        match ext.identifier() {
+            "CheckSpecVersion" => params.spec_version.encode_to(&mut additional),
+            "CheckTxVersion" => params.transaction_version.encode_to(&mut additional),
+            "CheckGenesis" => params.genesis_hash.encode_to(&mut additional),
+            "CheckMortality" | "CheckEra" => {
+                // Immortal transactions are checked against the genesis block.
+                tx.era.encode_to(&mut extra);
+                tx.checkpoint
+                    .unwrap_or(params.genesis_hash)
+                    .encode_to(&mut additional);
+            }
+            "CheckNonce" => Compact(params.nonce).encode_to(&mut extra),
+            "ChargeTransactionPayment" => Compact(tx.tip).encode_to(&mut extra),
+            "ChargeAssetTxPayment" => {
+                // The tip, and the optional ID of the asset to pay fees in.
+                Compact(tx.tip).encode_to(&mut extra);
+                match &tx.asset_id {
+                    Some(asset_id) => {
+                        extra.push(1);
+                        extra.extend_from_slice(asset_id);
+                    }
+                    None => extra.push(0),
+                }
+            }
+            "CheckMetadataHash" => {
+                // The mode (enabled or disabled), and the hash being committed to.
+                let enabled = tx.metadata_hash.is_some();
+                (enabled as u8).encode_to(&mut extra);
+                tx.metadata_hash.encode_to(&mut additional);
+            }
+            // Extensions such as `CheckNonZeroSender` and `CheckWeight` carry no
+            // data; anything else has to be provided by hand.
+            other => {
+                if !is_empty_type(types, ext.extra_ty())
+                    || !is_empty_type(types, ext.additional_ty())
+                {
+                    return Err(TransactionError::UnsupportedSignedExtension(
+                        other.to_owned(),
+                    )
+                    .into())
+                }
+            }
+        }
+    }
+
+    Ok(SignedExtensionData { extra, additional })
+}
+
+// Does the type given encode to zero bytes?
+fn is_empty_type(types: &PortableRegistry, type_id: u32) -> bool {
+    let ty = match types.resolve(type_id) {
+        Some(ty) => ty,
+        None => return false,
+    };
+    match ty.type_def() {
+        TypeDef::Composite(c) => {
+            c.fields()
+                .iter()
+                .all(|f| is_empty_type(types, f.ty().id()))
+        }
+        TypeDef::Tuple(t) => t.fields().iter().all(|f| is_empty_type(types, f.id())),
+        TypeDef::Array(a) => a.len() == 0 || is_empty_type(types, a.type_param().id()),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::SubstrateConfig;
+    use frame_metadata::{
+        v14::{
+            ExtrinsicMetadata,
+            RuntimeMetadataV14,
+            SignedExtensionMetadata,
+        },
+        RuntimeMetadataPrefixed,
+    };
+    use scale_info::meta_type;
+    use sp_core::H256;
+    use std::convert::TryFrom;
+
+    fn metadata(signed_extensions: Vec<SignedExtensionMetadata>) -> Metadata {
+        let extrinsic = ExtrinsicMetadata {
+            ty: meta_type::<()>(),
+            version: 4,
+            signed_extensions,
+        };
+        let v14 = RuntimeMetadataV14::new(vec![], extrinsic, meta_type::<()>());
+        let runtime_metadata: RuntimeMetadataPrefixed = v14.into();
+        Metadata::try_from(runtime_metadata).unwrap()
+    }
+
+    fn ext<Extra: scale_info::TypeInfo + 'static, Additional: scale_info::TypeInfo + 'static>(
+        identifier: &'static str,
+    ) -> SignedExtensionMetadata {
+        SignedExtensionMetadata {
+            identifier,
+            ty: meta_type::<Extra>(),
+            additional_signed: meta_type::<Additional>(),
+        }
+    }
+
+    fn params(tx: TxParams<SubstrateConfig>) -> SignedExtensionParams<SubstrateConfig> {
+        SignedExtensionParams {
+            nonce: 5,
+            tx,
+            spec_version: 1,
+            transaction_version: 2,
+            genesis_hash: H256::repeat_byte(1),
+        }
+    }
+
+    #[test]
+    fn asset_tip_and_metadata_hash_are_encoded() {
+        let metadata = metadata(vec![
+            ext::<Compact<u32>, ()>("CheckNonce"),
+            ext::<(Compact<u128>, Option<u32>), ()>("ChargeAssetTxPayment"),
+            ext::<u8, Option<[u8; 32]>>("CheckMetadataHash"),
+        ]);
+        let tx = TxParams::new()
+            .tip(10)
+            .asset_id(7u32)
+            .metadata_hash([2; 32]);
+        let data = encode_signed_extensions(&metadata, &params(tx)).unwrap();
+
+        let mut extra = Compact(5u64).encode();
+        (Compact(10u128), Some(7u32), 1u8).encode_to(&mut extra);
+        assert_eq!(data.extra, extra);
+        assert_eq!(data.additional, Some([2u8; 32]).encode());
+    }
+
+    #[test]
+    fn custom_signed_extensions_are_used() {
+        let metadata = metadata(vec![
+            ext::<u32, u64>("SomethingCustom"),
+            ext::<Compact<u128>, ()>("ChargeTransactionPayment"),
+        ]);
+
+        let err = encode_signed_extensions(&metadata, &params(TxParams::new()));
+        assert!(matches!(
+            err,
+            Err(Error::Transaction(TransactionError::UnsupportedSignedExtension(id)))
+                if id == "SomethingCustom"
+        ));
+
+        let tx = TxParams::new()
+            .signed_extension("SomethingCustom", 1u32, 2u64)
+            .signed_extension("ChargeTransactionPayment", Compact(3u128), ());
+        let data = encode_signed_extensions(&metadata, &params(tx)).unwrap();
+        assert_eq!(data.extra, (1u32, Compact(3u128)).encode());
+        assert_eq!(data.additional, 2u64.encode());
+    }
+}
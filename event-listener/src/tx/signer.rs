@@ -0,0 +1,166 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use crate::Config;
+
+/// Signing transactions requires a [`Signer`]. This is responsible for
+/// providing the "from" account that the transaction is being signed by,
+/// as well as actually signing a SCALE encoded payload.
+pub trait Signer<T: Config> {
+    /// Return the "from" account ID.
+    fn account_id(&self) -> &T::AccountId;
+
+    /// Return the "from" address.
+    fn address(&self) -> T::Address;
+
+    /// Takes a signer payload for an extrinsic, and returns a signature based on it.
+    fn sign(&self, signer_payload: &[u8]) -> T::Signature;
+}
+
+#[cfg(feature = "signer")]
+pub use pair_signer::PairSigner;
+
+// A signer suitable for substrate based chains. This provides compatibility with Substrate
+// packages like sp_keyring and such, and so relies on sp_core and sp_runtime to be included
+// to use.
+#[cfg(feature = "signer")]
+mod pair_signer {
+    use super::Signer;
+    use crate::Config;
+    use sp_core::{
+        crypto::SecretStringError,
+        Pair,
+    };
+    use sp_runtime::traits::{
+        IdentifyAccount,
+        Verify,
+    };
+
+    /// A [`Signer`] implementation that can be constructed from an [`Pair`].
+    /// Any of the `sp_core::sr25519`, `sp_core::ed25519` and `sp_core::ecdsa`
+    /// key pairs can be used.
+    #[derive(Clone, Debug)]
+    pub struct PairSigner<T: Config, P: Pair> {
+        account_id: T::AccountId,
+        signer: P,
+    }
+
+    impl<T, P> PairSigner<T, P>
+    where
+        T: Config,
+        T::Signature: From<P::Signature>,
+        <T::Signature as Verify>::Signer:
+            From<P::Public> + IdentifyAccount<AccountId = T::AccountId>,
+        P: Pair,
+    {
+        /// Creates a new [`Signer`] from a [`Pair`].
+        pub fn new(signer: P) -> Self {
+            let account_id =
+                <T::Signature as Verify>::Signer::from(signer.public()).into_account();
+            Self { account_id, signer }
+        }
+
+        /// Creates a new [`Signer`] from a secret URI, such as `"//Alice"`, a
+        /// mnemonic phrase optionally followed by derivation paths, or a hex
+        /// encoded seed. See [`Pair::from_string`] for the accepted formats.
+        pub fn from_uri(
+            uri: &str,
+            password: Option<&str>,
+        ) -> Result<Self, SecretStringError> {
+            Ok(Self::new(P::from_string(uri, password)?))
+        }
+
+        /// Creates a new [`Signer`] from a BIP39 mnemonic phrase and an
+        /// optional password.
+        pub fn from_phrase(
+            phrase: &str,
+            password: Option<&str>,
+        ) -> Result<Self, SecretStringError> {
+            let (pair, _seed) = P::from_phrase(phrase, password)?;
+            Ok(Self::new(pair))
+        }
+
+        /// Returns the [`Pair`] implementation used to construct this.
+        pub fn signer(&self) -> &P {
+            &self.signer
+        }
+
+        /// Return the account ID.
+        pub fn account_id(&self) -> &T::AccountId {
+            &self.account_id
+        }
+    }
+
+    impl<T, P> Signer<T> for PairSigner<T, P>
+    where
+        T: Config,
+        T::AccountId: Into<T::Address> + Clone + 'static,
+        P: Pair + 'static,
+        P::Signature: Into<T::Signature> + 'static,
+    {
+        fn account_id(&self) -> &T::AccountId {
+            &self.account_id
+        }
+
+        fn address(&self) -> T::Address {
+            self.account_id.clone().into()
+        }
+
+        fn sign(&self, signer_payload: &[u8]) -> T::Signature {
+            self.signer.sign(signer_payload).into()
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+        use crate::SubstrateConfig;
+        use sp_core::{
+            ecdsa,
+            ed25519,
+            sr25519,
+        };
+
+        fn signs_verifiably<P>()
+        where
+            P: Pair + 'static,
+            sp_runtime::MultiSignature: From<P::Signature>,
+            sp_runtime::MultiSigner: From<P::Public>,
+        {
+            let signer = PairSigner::<SubstrateConfig, P>::from_uri("//Alice", None)
+                .expect("dev URI is valid");
+            let payload = b"some payload";
+            let signature = Signer::<SubstrateConfig>::sign(&signer, payload);
+            assert!(signature.verify(&payload[..], signer.account_id()));
+        }
+
+        #[test]
+        fn pair_signers_produce_valid_signatures() {
+            signs_verifiably::<sr25519::Pair>();
+            signs_verifiably::<ed25519::Pair>();
+            signs_verifiably::<ecdsa::Pair>();
+        }
+
+        #[test]
+        fn phrase_and_uri_derive_the_same_account() {
+            let phrase = sp_core::crypto::DEV_PHRASE;
+            let from_phrase =
+                PairSigner::<SubstrateConfig, sr25519::Pair>::from_phrase(phrase, None)
+                    .unwrap();
+            let from_uri =
+                PairSigner::<SubstrateConfig, sr25519::Pair>::from_uri(phrase, None)
+                    .unwrap();
+            assert_eq!(from_phrase.account_id(), from_uri.account_id());
+        }
+
+        #[test]
+        fn invalid_phrases_are_rejected() {
+            assert!(PairSigner::<SubstrateConfig, sr25519::Pair>::from_phrase(
+                "not a valid phrase",
+                None
+            )
+            .is_err());
+        }
+    }
+}
@@ -0,0 +1,377 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use super::{
+    fee::FeeDetails,
+    ExtrinsicParams,
+    FeeEstimate,
+    OtherParamsFor,
+    Signer,
+    TxParams,
+    TxPayload,
+    TxProgress,
+};
+use crate::{
+    client::{
+        OfflineClientT,
+        OnlineClientT,
+    },
+    error::{
+        BlockError,
+        Error,
+    },
+    utils::Encoded,
+    Config,
+};
+use codec::{
+    Compact,
+    Decode,
+    Encode,
+};
+use derivative::Derivative;
+use sp_core::hashing::blake2_256;
+use sp_runtime::traits::Hash;
+use std::future::Future;
+
+/// A client for working with transactions.
+#[derive(Derivative)]
+#[derivative(Clone(bound = "Client: Clone"))]
+pub struct TxClient<T: Config, Client> {
+    client: Client,
+    _marker: std::marker::PhantomData<T>,
+}
+
+impl<T: Config, Client> TxClient<T, Client> {
+    /// Create a new [`TxClient`]
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T: Config, Client: OfflineClientT<T>> TxClient<T, Client> {
+    /// Wrap the SCALE encoded call data given (the pallet and call index followed
+    /// by the call arguments) into an unsigned extrinsic, ready to be submitted.
+    /// The returned bytes begin with the compact encoded length of the extrinsic.
+    pub fn create_unsigned(&self, call_data: &[u8]) -> Vec<u8> {
+        // Version 4 extrinsic, without the "signed" bit set.
+        let mut extrinsic = vec![4u8];
+        extrinsic.extend_from_slice(call_data);
+
+        let mut encoded = Compact(extrinsic.len() as u32).encode();
+        encoded.extend(extrinsic);
+        encoded
+    }
+
+    /// Create a signed extrinsic, using the nonce given for the signer's account.
+    /// This doesn't need to talk to the node, so it can be used with an
+    /// [`crate::OfflineClient`] to sign extrinsics on an air-gapped machine.
+    /// The [`SubmittableExtrinsic::encoded()`] bytes can then be submitted
+    /// later, through any means.
+    ///
+    /// Mortal transactions need the hash of the block that their era begins at
+    /// to be provided in the [`TxParams`], since it can't be looked up offline.
+    pub fn create_signed_with_nonce<Call, Signer>(
+        &self,
+        call: &Call,
+        signer: &Signer,
+        nonce: u64,
+        other_params: OtherParamsFor<T>,
+    ) -> Result<SubmittableExtrinsic<T, Client>, Error>
+    where
+        Call: TxPayload,
+        Signer: self::Signer<T>,
+    {
+        let metadata = self.client.metadata();
+        let runtime_version = self.client.runtime_version();
+
+        let mut call_data = Vec::new();
+        call.encode_call_data(&metadata, &mut call_data)?;
+
+        let params = T::ExtrinsicParams::new(
+            runtime_version.spec_version,
+            runtime_version.transaction_version,
+            nonce,
+            self.client.genesis_hash(),
+            &metadata,
+            other_params,
+        )?;
+
+        // The payload that gets signed is the call data followed by the extra and
+        // additional signed extension data, hashed first if it's longer than 256 bytes.
+        let signature = {
+            let mut payload = call_data.clone();
+            params.encode_extra_to(&mut payload);
+            params.encode_additional_to(&mut payload);
+            if payload.len() > 256 {
+                signer.sign(&blake2_256(&payload))
+            } else {
+                signer.sign(&payload)
+            }
+        };
+
+        // Version 4 extrinsic, with the "signed" bit set.
+        let mut extrinsic = vec![0b1000_0000 + 4];
+        signer.address().encode_to(&mut extrinsic);
+        signature.encode_to(&mut extrinsic);
+        params.encode_extra_to(&mut extrinsic);
+        extrinsic.extend_from_slice(&call_data);
+
+        let mut encoded = Compact(extrinsic.len() as u32).encode();
+        encoded.extend(extrinsic);
+
+        Ok(SubmittableExtrinsic::from_bytes(self.client.clone(), encoded))
+    }
+}
+
+impl<T: Config, Client: OnlineClientT<T>> TxClient<T, Client> {
+    /// Create a signed extrinsic, fetching the next nonce for the signer's account
+    /// from the node.
+    pub async fn create_signed<Call, Signer>(
+        &self,
+        call: &Call,
+        signer: &Signer,
+        other_params: OtherParamsFor<T>,
+    ) -> Result<SubmittableExtrinsic<T, Client>, Error>
+    where
+        Call: TxPayload,
+        Signer: self::Signer<T>,
+    {
+        let nonce = self
+            .client
+            .rpc()
+            .system_account_next_index(signer.account_id())
+            .await?;
+        self.create_signed_with_nonce(call, signer, nonce.into(), other_params)
+    }
+
+    /// Build [`TxParams`] for a mortal transaction which is valid for roughly
+    /// `period` blocks, beginning at the latest finalized block. These can be
+    /// used with configs whose [`Config::ExtrinsicParams`] are
+    /// [`super::BaseExtrinsicParams`].
+    pub fn mortal_params(
+        &self,
+        period: u64,
+    ) -> impl Future<Output = Result<TxParams<T>, Error>> + Send + 'static {
+        let client = self.client.clone();
+        async move {
+            let hash = client.rpc().finalized_head().await?;
+            let header = client
+                .rpc()
+                .header(Some(hash))
+                .await?
+                .ok_or_else(|| BlockError::block_hash_not_found(hash))?;
+            Ok(TxParams::new().mortal(&header, period))
+        }
+    }
+
+    /// Creates and signs an extrinsic and submits it to the chain. Returns a
+    /// [`TxProgress`] which can be used to track the status of the transaction
+    /// and, once it's in a block, to fetch the events that it emitted.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use event_listener::{
+    ///     ext::scale_value::{
+    ///         Composite,
+    ///         Value,
+    ///     },
+    ///     tx,
+    ///     OnlineClient,
+    ///     PolkadotConfig,
+    /// };
+    ///
+    /// # async fn example<S: tx::Signer<PolkadotConfig>>(signer: S) -> Result<(), event_listener::Error> {
+    /// let api = OnlineClient::<PolkadotConfig>::new().await?;
+    ///
+    /// let call = tx::dynamic(
+    ///     "System",
+    ///     "remark",
+    ///     Composite::Named(vec![("remark".into(), Value::from_bytes([1u8, 2, 3]))]),
+    /// );
+    /// let events = api
+    ///     .tx()
+    ///     .sign_and_submit_then_watch(&call, &signer)
+    ///     .await?
+    ///     .wait_for_finalized_success()
+    ///     .await?;
+    ///
+    /// for ev in events.iter() {
+    ///     let ev = ev?;
+    ///     println!("{}::{}", ev.pallet_name(), ev.variant_name());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn sign_and_submit_then_watch<Call, Signer>(
+        &self,
+        call: &Call,
+        signer: &Signer,
+    ) -> Result<TxProgress<T, Client>, Error>
+    where
+        Call: TxPayload,
+        Signer: self::Signer<T>,
+        OtherParamsFor<T>: Default,
+    {
+        self.sign_and_submit_then_watch_with_params(call, signer, Default::default())
+            .await
+    }
+
+    /// Like [`TxClient::sign_and_submit_then_watch()`], but with the given
+    /// parameters (such as [`TxParams`]) instead of the defaults.
+    pub async fn sign_and_submit_then_watch_with_params<Call, Signer>(
+        &self,
+        call: &Call,
+        signer: &Signer,
+        other_params: OtherParamsFor<T>,
+    ) -> Result<TxProgress<T, Client>, Error>
+    where
+        Call: TxPayload,
+        Signer: self::Signer<T>,
+    {
+        self.create_signed(call, signer, other_params)
+            .await?
+            .submit_and_watch()
+            .await
+    }
+
+    /// Creates and signs an extrinsic and submits it to the chain, returning
+    /// the hash of the extrinsic once the node has accepted it into its pool.
+    ///
+    /// **Note:** Success here doesn't mean that the extrinsic has been included
+    /// in a block, or that it succeeded. Use
+    /// [`TxClient::sign_and_submit_then_watch()`] to find that out.
+    pub async fn sign_and_submit<Call, Signer>(
+        &self,
+        call: &Call,
+        signer: &Signer,
+    ) -> Result<T::Hash, Error>
+    where
+        Call: TxPayload,
+        Signer: self::Signer<T>,
+        OtherParamsFor<T>: Default,
+    {
+        self.create_signed(call, signer, Default::default())
+            .await?
+            .submit()
+            .await
+    }
+
+    /// Estimate the fee that would be paid to submit the call given (the pallet and
+    /// call index followed by the SCALE encoded call arguments) at some block, or the
+    /// latest block if `None` is provided.
+    ///
+    /// The call is wrapped in an unsigned extrinsic to estimate the fee, so the length
+    /// fee doesn't account for the bytes that a signature would add. Use
+    /// [`TxClient::estimate_extrinsic_fee()`] with a signed extrinsic for an exact figure.
+    ///
+    /// Fees are assumed to be `u128` balances, as is the case for Substrate and Polkadot
+    /// based chains.
+    pub fn estimate_fee(
+        &self,
+        call_data: &[u8],
+        at: Option<T::Hash>,
+    ) -> impl Future<Output = Result<FeeEstimate, Error>> + Send + 'static {
+        let extrinsic = self.create_unsigned(call_data);
+        self.estimate_extrinsic_fee(extrinsic, at)
+    }
+
+    /// Estimate the fee that would be paid to submit the SCALE encoded extrinsic given
+    /// (including its compact encoded length prefix) at some block, or the latest block
+    /// if `None` is provided.
+    pub fn estimate_extrinsic_fee(
+        &self,
+        extrinsic: Vec<u8>,
+        at: Option<T::Hash>,
+    ) -> impl Future<Output = Result<FeeEstimate, Error>> + Send + 'static {
+        let client = self.client.clone();
+        async move {
+            let len = extrinsic.len() as u32;
+            let args = (Encoded(extrinsic), len).encode();
+            let bytes = client
+                .rpc()
+                .state_call(
+                    "TransactionPaymentApi_query_fee_details",
+                    Some(&args),
+                    at,
+                )
+                .await?;
+            let details = FeeDetails::decode(&mut &*bytes)?;
+            Ok(details.into())
+        }
+    }
+}
+
+/// An extrinsic that is ready to be submitted. The bytes begin with the compact
+/// encoded length of the extrinsic.
+#[derive(Derivative)]
+#[derivative(Clone(bound = "C: Clone"), Debug(bound = "C: std::fmt::Debug"))]
+pub struct SubmittableExtrinsic<T, C> {
+    client: C,
+    encoded: Encoded,
+    _marker: std::marker::PhantomData<T>,
+}
+
+impl<T, C> SubmittableExtrinsic<T, C>
+where
+    T: Config,
+    C: OfflineClientT<T>,
+{
+    /// Create a [`SubmittableExtrinsic`] from some already-signed and prepared
+    /// extrinsic bytes, and some client (anything implementing [`OfflineClientT`]
+    /// or [`OnlineClientT`]).
+    ///
+    /// Prefer to use [`TxClient`] to create and sign extrinsics. This is simply
+    /// exposed in case you want to skip this process and submit something you've
+    /// already created.
+    pub fn from_bytes(client: C, tx_bytes: Vec<u8>) -> Self {
+        Self {
+            client,
+            encoded: Encoded(tx_bytes),
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns the SCALE encoded extrinsic bytes.
+    pub fn encoded(&self) -> &[u8] {
+        &self.encoded.0
+    }
+
+    /// Calculate and return the hash of the extrinsic, based on the configured hasher.
+    pub fn hash(&self) -> T::Hash {
+        T::Hashing::hash_of(&self.encoded)
+    }
+}
+
+impl<T, C> SubmittableExtrinsic<T, C>
+where
+    T: Config,
+    C: OnlineClientT<T>,
+{
+    /// Submits the extrinsic to the chain, returning a [`TxProgress`] which can
+    /// be used to watch its status as it makes its way into a block.
+    pub async fn submit_and_watch(&self) -> Result<TxProgress<T, C>, Error> {
+        // Get a hash of the extrinsic (we'll need this later).
+        let ext_hash = self.hash();
+
+        // Submit and watch for transaction progress.
+        let sub = self.client.rpc().watch_extrinsic(&self.encoded.0).await?;
+
+        Ok(TxProgress::new(sub, self.client.clone(), ext_hash))
+    }
+
+    /// Submits the extrinsic to the chain for block inclusion.
+    ///
+    /// Returns `Ok` with the extrinsic hash if it is valid extrinsic.
+    ///
+    /// **Note:** Success here doesn't mean that the extrinsic has been included
+    /// in a block, or that it succeeded. Use
+    /// [`SubmittableExtrinsic::submit_and_watch()`] to find that out.
+    pub async fn submit(&self) -> Result<T::Hash, Error> {
+        self.client.rpc().submit_extrinsic(&self.encoded.0).await
+    }
+}
@@ -0,0 +1,174 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! This module contains the trait and types used to represent
+//! transactions that can be submitted.
+
+use crate::{
+    error::Error,
+    metadata::Metadata,
+};
+use codec::Encode;
+use scale_value::{
+    Composite,
+    Value,
+};
+
+/// This represents a transaction payload that can be submitted
+/// to a node.
+pub trait TxPayload {
+    /// Encode call data to the provided output.
+    fn encode_call_data(&self, metadata: &Metadata, out: &mut Vec<u8>)
+        -> Result<(), Error>;
+}
+
+/// This represents a statically generated transaction payload; a pallet and
+/// call name, and a type which encodes to the call arguments.
+#[derive(Clone, Debug)]
+pub struct StaticTxPayload<CallData> {
+    pallet_name: &'static str,
+    call_name: &'static str,
+    call_data: CallData,
+}
+
+impl<CallData> StaticTxPayload<CallData> {
+    /// Create a new [`StaticTxPayload`].
+    pub fn new(
+        pallet_name: &'static str,
+        call_name: &'static str,
+        call_data: CallData,
+    ) -> Self {
+        StaticTxPayload {
+            pallet_name,
+            call_name,
+            call_data,
+        }
+    }
+
+    /// Return the call data.
+    pub fn call_data(&self) -> &CallData {
+        &self.call_data
+    }
+}
+
+impl<CallData: Encode> TxPayload for StaticTxPayload<CallData> {
+    fn encode_call_data(
+        &self,
+        metadata: &Metadata,
+        out: &mut Vec<u8>,
+    ) -> Result<(), Error> {
+        let ((pallet_index, call_index), _) =
+            metadata.call_by_name(self.pallet_name, self.call_name)?;
+        pallet_index.encode_to(out);
+        call_index.encode_to(out);
+        self.call_data.encode_to(out);
+        Ok(())
+    }
+}
+
+/// This represents a dynamically generated transaction payload; the arguments
+/// are [`scale_value::Value`]s, and are encoded according to the metadata.
+#[derive(Clone, Debug)]
+pub struct DynamicTxPayload {
+    pallet_name: String,
+    call_name: String,
+    fields: Composite<()>,
+}
+
+/// Construct a new dynamic transaction payload to submit to a node.
+pub fn dynamic(
+    pallet_name: impl Into<String>,
+    call_name: impl Into<String>,
+    fields: Composite<()>,
+) -> DynamicTxPayload {
+    DynamicTxPayload {
+        pallet_name: pallet_name.into(),
+        call_name: call_name.into(),
+        fields,
+    }
+}
+
+impl DynamicTxPayload {
+    /// Return the pallet name.
+    pub fn pallet_name(&self) -> &str {
+        &self.pallet_name
+    }
+
+    /// Return the call name.
+    pub fn call_name(&self) -> &str {
+        &self.call_name
+    }
+
+    /// Return the call fields.
+    pub fn fields(&self) -> &Composite<()> {
+        &self.fields
+    }
+}
+
+impl TxPayload for DynamicTxPayload {
+    fn encode_call_data(
+        &self,
+        metadata: &Metadata,
+        out: &mut Vec<u8>,
+    ) -> Result<(), Error> {
+        let ((pallet_index, call_index), call) =
+            metadata.call_by_name(&self.pallet_name, &self.call_name)?;
+        pallet_index.encode_to(out);
+        call_index.encode_to(out);
+
+        let types = &metadata.runtime_metadata().types;
+        let values: Vec<&Value<()>> = match &self.fields {
+            Composite::Named(fields) => {
+                // Line the named fields up with the arguments in the metadata.
+                call.fields()
+                    .iter()
+                    .map(|(name, _)| {
+                        let name = name.as_deref().unwrap_or_default();
+                        fields
+                            .iter()
+                            .find(|(n, _)| n == name)
+                            .map(|(_, v)| v)
+                            .ok_or_else(|| {
+                                Error::Other(format!(
+                                    "Missing argument '{}' for call {}::{}",
+                                    name, self.pallet_name, self.call_name
+                                ))
+                            })
+                    })
+                    .collect::<Result<_, Error>>()?
+            }
+            Composite::Unnamed(fields) => fields.iter().collect(),
+        };
+        if values.len() != call.fields().len() {
+            return Err(Error::Other(format!(
+                "Call {}::{} takes {} arguments, but {} were given",
+                self.pallet_name,
+                self.call_name,
+                call.fields().len(),
+                values.len()
+            )))
+        }
+
+        for (value, (_, type_id)) in values.into_iter().zip(call.fields()) {
+            scale_value::scale::encode_as_type(value.clone(), *type_id, types, out)?;
+        }
+        Ok(())
+    }
+}
+
+/// Already SCALE encoded call data (the pallet and call index, followed by the
+/// call arguments), which is submitted as-is.
+#[derive(Clone, Debug)]
+pub struct RawTxPayload(pub Vec<u8>);
+
+impl TxPayload for RawTxPayload {
+    fn encode_call_data(
+        &self,
+        _metadata: &Metadata,
+        out: &mut Vec<u8>,
+    ) -> Result<(), Error> {
+        out.extend_from_slice(&self.0);
+        Ok(())
+    }
+}
@@ -96,3 +96,118 @@ unsafe impl<T> Sync for PhantomDataSendSync<T> {}
 /// with collections like BTreeMap. This has the same type params
 /// as `BTreeMap` which allows us to easily swap the two during codegen.
 pub type KeyedVec<K, V> = Vec<(K, V)>;
+
+/// Iterate over the values in a [`scale_value::Composite`], ignoring any names.
+pub(crate) fn composite_values(
+    c: &scale_value::Composite<scale_value::scale::TypeId>,
+) -> Box<dyn Iterator<Item = &scale_value::Value<scale_value::scale::TypeId>> + '_> {
+    match c {
+        scale_value::Composite::Named(vals) => Box::new(vals.iter().map(|(_, v)| v)),
+        scale_value::Composite::Unnamed(vals) => Box::new(vals.iter()),
+    }
+}
+
+/// Find a field in a [`scale_value::Composite`] by name, or by position if the
+/// fields are unnamed.
+#[cfg(feature = "substrate-compat")]
+pub(crate) fn composite_field<'a>(
+    c: &'a scale_value::Composite<scale_value::scale::TypeId>,
+    name: &str,
+    index: usize,
+) -> Option<&'a scale_value::Value<scale_value::scale::TypeId>> {
+    match c {
+        scale_value::Composite::Named(vals) => {
+            vals.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+        }
+        scale_value::Composite::Unnamed(vals) => vals.get(index),
+    }
+}
+
+/// Interpret a [`scale_value::Value`] as an unsigned number. Numbers are often wrapped
+/// in single field structs (for instance `Weight(u64)`), so we look through those.
+#[cfg(feature = "substrate-compat")]
+pub(crate) fn value_as_u128(
+    value: &scale_value::Value<scale_value::scale::TypeId>,
+) -> Option<u128> {
+    match &value.value {
+        scale_value::ValueDef::Primitive(scale_value::Primitive::U128(n)) => Some(*n),
+        scale_value::ValueDef::Composite(c) => {
+            let mut vals = composite_values(c);
+            match (vals.next(), vals.next()) {
+                (Some(v), None) => value_as_u128(v),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Interpret a [`scale_value::Value`] as a byte array, such as an account ID. Like
+/// numbers, byte arrays are often wrapped in single field structs (for instance
+/// `AccountId32([u8; 32])`), so we look through those.
+pub(crate) fn value_as_bytes(
+    value: &scale_value::Value<scale_value::scale::TypeId>,
+) -> Option<Vec<u8>> {
+    let c = match &value.value {
+        scale_value::ValueDef::Composite(c) => c,
+        _ => return None,
+    };
+    let bytes: Option<Vec<u8>> = composite_values(c)
+        .map(|v| {
+            match &v.value {
+                scale_value::ValueDef::Primitive(scale_value::Primitive::U128(n)) => {
+                    u8::try_from(*n).ok()
+                }
+                _ => None,
+            }
+        })
+        .collect();
+    match bytes {
+        Some(bytes) if !bytes.is_empty() => Some(bytes),
+        _ => {
+            let mut vals = composite_values(c);
+            match (vals.next(), vals.next()) {
+                (Some(v), None) => value_as_bytes(v),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Whether the type with the given ID is a sequence or array of bytes.
+pub(crate) fn is_bytes(type_id: scale_value::scale::TypeId, metadata: &crate::Metadata) -> bool {
+    let types = &metadata.runtime_metadata().types;
+    let is_u8 = |id: u32| {
+        matches!(
+            types.resolve(id).map(|ty| ty.type_def()),
+            Some(scale_info::TypeDef::Primitive(scale_info::TypeDefPrimitive::U8))
+        )
+    };
+    match types.resolve(type_id.id()).map(|ty| ty.type_def()) {
+        Some(scale_info::TypeDef::Sequence(seq)) => is_u8(seq.type_param().id()),
+        Some(scale_info::TypeDef::Array(arr)) => is_u8(arr.type_param().id()),
+        _ => false,
+    }
+}
+
+/// Format bytes as a `0x` prefixed hex string.
+pub(crate) fn to_hex(bytes: impl AsRef<[u8]>) -> String {
+    format!("0x{}", hex::encode(bytes.as_ref()))
+}
+
+/// Run blocking work, such as writing out and syncing a file, on a thread of its
+/// own, so that it doesn't hold up the executor of the future awaiting it. Unlike
+/// Tokio's `spawn_blocking`, this works on any executor.
+#[cfg(feature = "substrate-compat")]
+pub(crate) async fn unblock<R, F>(f: F) -> R
+where
+    R: Send + 'static,
+    F: FnOnce() -> R + Send + 'static,
+{
+    let (tx, rx) = futures::channel::oneshot::channel();
+    std::thread::spawn(move || {
+        // Nothing is waiting for the result if the future was dropped.
+        let _ = tx.send(f());
+    });
+    rx.await.expect("blocking work only goes unfinished if it panics")
+}
@@ -0,0 +1,85 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Check the shipped configs against the live runtimes of the chains they're for.
+//! These talk to public RPC nodes, and so only run with the `integration-tests`
+//! feature enabled. Set `<CHAIN>_RPC_URL` (for instance `KUSAMA_RPC_URL`) to use
+//! a different node.
+
+#![cfg(feature = "integration-tests")]
+
+use codec::Decode;
+use event_listener::{
+    client::ChainExpectations,
+    config::{
+        AssetHubConfig,
+        KusamaConfig,
+        PolkadotConfig,
+        WestendConfig,
+    },
+    Config,
+    OnlineClient,
+};
+
+fn url(chain: &str, default: &str) -> String {
+    std::env::var(format!("{}_RPC_URL", chain)).unwrap_or_else(|_| default.to_owned())
+}
+
+// Decode the latest finalized block and its events with the config given, and
+// check that the account and address types line up with the runtime's.
+async fn check_config<T>(url: &str)
+where
+    T: Config,
+    T::AccountId: scale_info::TypeInfo + 'static,
+    T::Address: scale_info::TypeInfo + 'static,
+{
+    let api = OnlineClient::<T>::from_url_validated(url, &ChainExpectations::new())
+        .await
+        .unwrap_or_else(|e| panic!("{} doesn't match the config: {}", url, e));
+
+    let hash = api.rpc().finalized_head().await.unwrap();
+    let block = api.blocks().at(Some(hash)).await.unwrap();
+    assert_eq!(block.hash(), hash, "block hashes should line up for {}", url);
+
+    let events = block.events().await.unwrap();
+    for ev in events.iter() {
+        ev.unwrap();
+    }
+
+    // The addresses of signed extrinsics should decode to the config's type.
+    let extrinsics = block.extrinsics().await.unwrap();
+    for ext in extrinsics.iter() {
+        let ext = ext.unwrap();
+        if let Some(mut address) = ext.address_bytes() {
+            T::Address::decode(&mut address).unwrap_or_else(|e| {
+                panic!("address of extrinsic {} doesn't decode: {}", ext.index(), e)
+            });
+            assert!(address.is_empty(), "address of extrinsic {} has bytes left over", ext.index());
+        }
+    }
+}
+
+#[tokio::test]
+async fn polkadot_config_matches_polkadot() {
+    check_config::<PolkadotConfig>(&url("POLKADOT", "wss://rpc.polkadot.io:443")).await;
+}
+
+#[tokio::test]
+async fn kusama_config_matches_kusama() {
+    check_config::<KusamaConfig>(&url("KUSAMA", "wss://kusama-rpc.polkadot.io:443")).await;
+}
+
+#[tokio::test]
+async fn westend_config_matches_westend() {
+    check_config::<WestendConfig>(&url("WESTEND", "wss://westend-rpc.polkadot.io:443")).await;
+}
+
+#[tokio::test]
+async fn asset_hub_config_matches_asset_hub() {
+    check_config::<AssetHubConfig>(&url(
+        "ASSET_HUB",
+        "wss://polkadot-asset-hub-rpc.polkadot.io:443",
+    ))
+    .await;
+}
@@ -0,0 +1,17 @@
+#!/usr/bin/env bash
+# This script is meant to be run on Unix/Linux based systems
+set -e
+
+echo "*** Capturing Polkadot metadata for the event-listener test fixtures"
+
+cd $(dirname ${BASH_SOURCE[0]})/..
+
+URL=${POLKADOT_RPC_URL:-https://rpc.polkadot.io}
+
+mkdir -p event-listener/artifacts
+curl -sf -H "Content-Type: application/json" \
+    -d '{"id":1,"jsonrpc":"2.0","method":"state_getMetadata","params":[]}' \
+    "$URL" \
+    | jq -r .result \
+    | sed 's/^0x//' \
+    | xxd -r -p > event-listener/artifacts/polkadot_metadata.scale